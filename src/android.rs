@@ -0,0 +1,433 @@
+/*!
+
+# Android credential store
+
+This module keeps credentials in Android's
+[EncryptedSharedPreferences](https://developer.android.com/reference/androidx/security/crypto/EncryptedSharedPreferences),
+whose contents are encrypted under a master key that is kept in the
+Android Keystore (and so can't be extracted from the device).
+It talks to Android by way of JNI, using the Java VM and app context
+provided by the [ndk-context](https://docs.rs/ndk-context) crate
+(which frameworks such as Tauri, Dioxus, and `android-activity`
+initialize), so the app must include the `androidx.security:security-crypto`
+library. It's available when the `android-native` feature is enabled.
+It's not the default store on any platform, so clients have
+to set it as their default (or build their credentials from it directly):
+```no_run
+keyring::set_default_credential_builder(keyring::android::default_credential_builder());
+```
+
+For a given <_target_, _service_, _user_> triple, this module uses the
+preferences file named by the target (or `keyring-rs`, if there is no target),
+and the preference whose key is the service and user (separated by a colon,
+with colons and percent signs in them percent-encoded). Preferences hold
+strings, so secrets are stored hex encoded. The preferences (and the master
+key) are removed when the app is uninstalled.
+
+This store doesn't support [search](crate::search), attributes, or labels.
+ */
+use jni::objects::{JClass, JObject, JString, JValue};
+use jni::{JNIEnv, JavaVM};
+
+use super::credential::{
+    AtRestStatus, Credential, CredentialApi, CredentialBuilder, CredentialBuilderApi,
+    CredentialPersistence, CredentialSpec, CredentialStoreInfo,
+};
+use super::error::{Error as ErrorCode, Result};
+
+/// The preferences file used for credentials without a target.
+const DEFAULT_FILE: &str = "keyring-rs";
+
+/// The representation of an encrypted preference.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AndroidCredential {
+    /// The preferences file, or `None` for the default file
+    pub file: Option<String>,
+    pub service: String,
+    pub user: String,
+}
+
+impl CredentialApi for AndroidCredential {
+    /// Store the (hex encoded) secret in this credential's preference.
+    fn set_secret(&self, secret: &[u8]) -> Result<()> {
+        let key = self.preference_key();
+        let committed = with_preferences(self.file(), |env, prefs| {
+            let key = env.new_string(&key)?;
+            let value = env.new_string(to_hex(secret))?;
+            let editor = edit(env, prefs)?;
+            env.call_method(
+                &editor,
+                "putString",
+                "(Ljava/lang/String;Ljava/lang/String;)Landroid/content/SharedPreferences$Editor;",
+                &[JValue::Object(&key), JValue::Object(&value)],
+            )?;
+            env.call_method(&editor, "commit", "()Z", &[])?.z()
+        })?;
+        match committed {
+            true => Ok(()),
+            false => Err(failure("couldn't write the preference")),
+        }
+    }
+
+    /// Read the secret from this credential's preference, if it exists.
+    ///
+    /// Returns a [NoEntry](ErrorCode::NoEntry) error if there is no preference.
+    fn get_secret(&self) -> Result<Vec<u8>> {
+        let key = self.preference_key();
+        let value = with_preferences(self.file(), |env, prefs| {
+            let key = env.new_string(&key)?;
+            let value = env
+                .call_method(
+                    prefs,
+                    "getString",
+                    "(Ljava/lang/String;Ljava/lang/String;)Ljava/lang/String;",
+                    &[JValue::Object(&key), JValue::Object(&JObject::null())],
+                )?
+                .l()?;
+            if value.is_null() {
+                return Ok(None);
+            }
+            let value: String = env.get_string(&JString::from(value))?.into();
+            Ok(Some(value))
+        })?;
+        let value = value.ok_or(ErrorCode::NoEntry)?;
+        from_hex(&value).ok_or_else(|| {
+            ErrorCode::Invalid(
+                "stored preference".to_string(),
+                "isn't hex encoded".to_string(),
+            )
+        })
+    }
+
+    /// Remove this credential's preference, if it exists.
+    ///
+    /// Returns a [NoEntry](ErrorCode::NoEntry) error if there is no preference.
+    fn delete_credential(&self) -> Result<()> {
+        let key = self.preference_key();
+        let removed = with_preferences(self.file(), |env, prefs| {
+            let key = env.new_string(&key)?;
+            let exists = env
+                .call_method(
+                    prefs,
+                    "contains",
+                    "(Ljava/lang/String;)Z",
+                    &[JValue::Object(&key)],
+                )?
+                .z()?;
+            if !exists {
+                return Ok(None);
+            }
+            let editor = edit(env, prefs)?;
+            env.call_method(
+                &editor,
+                "remove",
+                "(Ljava/lang/String;)Landroid/content/SharedPreferences$Editor;",
+                &[JValue::Object(&key)],
+            )?;
+            Ok(Some(env.call_method(&editor, "commit", "()Z", &[])?.z()?))
+        })?;
+        match removed {
+            None => Err(ErrorCode::NoEntry),
+            Some(true) => Ok(()),
+            Some(false) => Err(failure("couldn't remove the preference")),
+        }
+    }
+
+    /// The target (preferences file), service, and user of this credential.
+    fn spec(&self) -> Option<CredentialSpec> {
+        Some(CredentialSpec {
+            target: self.file.clone(),
+            service: self.service.clone(),
+            user: self.user.clone(),
+        })
+    }
+
+    /// Preferences are encrypted under a key kept in the Android Keystore.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        AtRestStatus::Encrypted
+    }
+
+    /// Return the underlying concrete object with an `Any` type so that it can
+    /// be downgraded to an [AndroidCredential] for platform-specific processing.
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    /// Expose the concrete debug formatter for use via the [Credential] trait
+    fn debug_fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self, f)
+    }
+}
+
+impl AndroidCredential {
+    /// Create a credential for the given target, service, and user.
+    ///
+    /// Returns an [Invalid](ErrorCode::Invalid) error if the target is empty
+    /// or contains a `/` (because it names a file).
+    pub fn new_with_target(target: Option<&str>, service: &str, user: &str) -> Result<Self> {
+        if let Some(target) = target {
+            if target.is_empty() || target.contains('/') {
+                return Err(ErrorCode::Invalid(
+                    "target".to_string(),
+                    "must be a non-empty file name".to_string(),
+                ));
+            }
+        }
+        Ok(Self {
+            file: target.map(|t| t.to_string()),
+            service: service.to_string(),
+            user: user.to_string(),
+        })
+    }
+
+    fn file(&self) -> &str {
+        self.file.as_deref().unwrap_or(DEFAULT_FILE)
+    }
+
+    fn preference_key(&self) -> String {
+        preference_key(&self.service, &self.user)
+    }
+}
+
+/// The builder for Android credentials.
+#[derive(Debug, Default)]
+pub struct AndroidCredentialBuilder {}
+
+/// Returns an instance of the Android credential builder.
+///
+/// This is never called by the crate, since this store is
+/// not the default store on any platform.
+pub fn default_credential_builder() -> Box<CredentialBuilder> {
+    Box::new(AndroidCredentialBuilder {})
+}
+
+impl CredentialBuilderApi for AndroidCredentialBuilder {
+    /// Build an [AndroidCredential] for the given target, service, and user.
+    fn build(&self, target: Option<&str>, service: &str, user: &str) -> Result<Box<Credential>> {
+        Ok(Box::new(AndroidCredential::new_with_target(
+            target, service, user,
+        )?))
+    }
+
+    /// Preferences are encrypted under a key kept in the Android Keystore.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        AtRestStatus::Encrypted
+    }
+
+    /// Return the underlying builder object with an `Any` type so that it can
+    /// be downgraded to an [AndroidCredentialBuilder] for platform-specific processing.
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    /// Preferences are kept until they are deleted (or the app is uninstalled).
+    fn persistence(&self) -> CredentialPersistence {
+        CredentialPersistence::UntilDelete
+    }
+
+    /// Credentials have no attributes, and the store can't be searched.
+    fn info(&self) -> CredentialStoreInfo {
+        CredentialStoreInfo::new("android", self.persistence(), self.at_rest_encryption())
+    }
+}
+
+/// Open the named encrypted preferences file and apply a function to it.
+///
+/// The current thread is attached to the Java VM if necessary, and the local
+/// references made by the function are freed when it returns. Any pending
+/// Java exception is cleared (and reported as a platform failure).
+fn with_preferences<T>(
+    file: &str,
+    f: impl FnOnce(&mut JNIEnv, &JObject) -> jni::errors::Result<T>,
+) -> Result<T> {
+    let context = ndk_context::android_context();
+    let vm = unsafe { JavaVM::from_raw(context.vm().cast()) }.map_err(jni_failure)?;
+    let mut env = vm.attach_current_thread().map_err(jni_failure)?;
+    // the context is a global reference owned by ndk-context, so it's never freed here
+    let app = unsafe { JObject::from_raw(context.context().cast()) };
+    let result = env.with_local_frame(16, |env| {
+        let prefs = open_preferences(env, &app, file)?;
+        f(env, &prefs)
+    });
+    if env.exception_check().unwrap_or(false) {
+        let _ = env.exception_clear();
+    }
+    result.map_err(jni_failure)
+}
+
+/// Create (or open) the named encrypted preferences file,
+/// with a master key (created if necessary) in the Android Keystore.
+fn open_preferences<'local>(
+    env: &mut JNIEnv<'local>,
+    app: &JObject,
+    file: &str,
+) -> jni::errors::Result<JObject<'local>> {
+    let builder_class = load_class(env, app, "androidx.security.crypto.MasterKey$Builder")?;
+    let scheme_class = load_class(env, app, "androidx.security.crypto.MasterKey$KeyScheme")?;
+    let prefs_class = load_class(
+        env,
+        app,
+        "androidx.security.crypto.EncryptedSharedPreferences",
+    )?;
+    let key_scheme_class = load_class(
+        env,
+        app,
+        "androidx.security.crypto.EncryptedSharedPreferences$PrefKeyEncryptionScheme",
+    )?;
+    let value_scheme_class = load_class(
+        env,
+        app,
+        "androidx.security.crypto.EncryptedSharedPreferences$PrefValueEncryptionScheme",
+    )?;
+    let builder = env.new_object(
+        &builder_class,
+        "(Landroid/content/Context;)V",
+        &[JValue::Object(app)],
+    )?;
+    let scheme = env
+        .get_static_field(
+            &scheme_class,
+            "AES256_GCM",
+            "Landroidx/security/crypto/MasterKey$KeyScheme;",
+        )?
+        .l()?;
+    env.call_method(
+        &builder,
+        "setKeyScheme",
+        "(Landroidx/security/crypto/MasterKey$KeyScheme;)Landroidx/security/crypto/MasterKey$Builder;",
+        &[JValue::Object(&scheme)],
+    )?;
+    let master_key = env
+        .call_method(
+            &builder,
+            "build",
+            "()Landroidx/security/crypto/MasterKey;",
+            &[],
+        )?
+        .l()?;
+    let key_scheme = env
+        .get_static_field(
+            &key_scheme_class,
+            "AES256_SIV",
+            "Landroidx/security/crypto/EncryptedSharedPreferences$PrefKeyEncryptionScheme;",
+        )?
+        .l()?;
+    let value_scheme = env
+        .get_static_field(
+            &value_scheme_class,
+            "AES256_GCM",
+            "Landroidx/security/crypto/EncryptedSharedPreferences$PrefValueEncryptionScheme;",
+        )?
+        .l()?;
+    let file = env.new_string(file)?;
+    env.call_static_method(
+        &prefs_class,
+        "create",
+        "(Landroid/content/Context;Ljava/lang/String;Landroidx/security/crypto/MasterKey;\
+         Landroidx/security/crypto/EncryptedSharedPreferences$PrefKeyEncryptionScheme;\
+         Landroidx/security/crypto/EncryptedSharedPreferences$PrefValueEncryptionScheme;)\
+         Landroid/content/SharedPreferences;",
+        &[
+            JValue::Object(app),
+            JValue::Object(&file),
+            JValue::Object(&master_key),
+            JValue::Object(&key_scheme),
+            JValue::Object(&value_scheme),
+        ],
+    )?
+    .l()
+}
+
+/// Load an app class by (binary) name.
+///
+/// Threads attached from native code can only find system classes by name,
+/// so the app's classes are loaded with the app context's class loader.
+fn load_class<'local>(
+    env: &mut JNIEnv<'local>,
+    app: &JObject,
+    name: &str,
+) -> jni::errors::Result<JClass<'local>> {
+    let loader = env
+        .call_method(app, "getClassLoader", "()Ljava/lang/ClassLoader;", &[])?
+        .l()?;
+    let name = env.new_string(name)?;
+    let class = env
+        .call_method(
+            &loader,
+            "loadClass",
+            "(Ljava/lang/String;)Ljava/lang/Class;",
+            &[JValue::Object(&name)],
+        )?
+        .l()?;
+    Ok(JClass::from(class))
+}
+
+/// Start editing the given preferences.
+fn edit<'local>(env: &mut JNIEnv<'local>, prefs: &JObject) -> jni::errors::Result<JObject<'local>> {
+    env.call_method(
+        prefs,
+        "edit",
+        "()Landroid/content/SharedPreferences$Editor;",
+        &[],
+    )?
+    .l()
+}
+
+/// The key of the preference for a service and user.
+fn preference_key(service: &str, user: &str) -> String {
+    let escape = |part: &str| part.replace('%', "%25").replace(':', "%3A");
+    format!("{}:{}", escape(service), escape(user))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn jni_failure(err: jni::errors::Error) -> ErrorCode {
+    ErrorCode::PlatformFailure(Box::new(err))
+}
+
+fn failure(reason: &str) -> ErrorCode {
+    ErrorCode::PlatformFailure(Box::from(format!("Android {reason}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Error;
+
+    use super::{AndroidCredential, from_hex, preference_key, to_hex};
+
+    #[test]
+    fn test_invalid_parameter() {
+        for target in ["", "a/b"] {
+            assert!(matches!(
+                AndroidCredential::new_with_target(Some(target), "service", "user"),
+                Err(Error::Invalid(_, _))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_preference_key() {
+        assert_eq!(preference_key("service", "user"), "service:user");
+        assert_eq!(preference_key("a:b", "100%"), "a%3Ab:100%25");
+    }
+
+    #[test]
+    fn test_hex() {
+        let bytes = [0u8, 1, 0x7f, 0xff];
+        assert_eq!(to_hex(&bytes), "00017fff");
+        assert_eq!(from_hex("00017fff").unwrap(), bytes);
+        assert_eq!(from_hex("0"), None);
+    }
+}
@@ -0,0 +1,220 @@
+/*!
+
+# Async entries
+
+The operations on an [Entry] block until the underlying credential
+store has done its work, which can take a while (for example, when the
+store has to prompt the user to unlock it). An [AsyncEntry] wraps an entry
+and provides `async` versions of its operations, so that apps built on an
+async runtime (such as `tokio`) don't have to wrap every call in something
+like `spawn_blocking` themselves. It's available when the `async` feature
+is enabled.
+
+```
+# use keyring::{Entry, async_entry::AsyncEntry, memory::MemoryCredentialBuilder};
+# use keyring::credential::CredentialBuilderApi;
+# let credential = MemoryCredentialBuilder::default().build(None, "service", "user").unwrap();
+# let entry = Entry::new_with_credential(credential);
+# async fn example(entry: Entry) -> keyring::Result<()> {
+let entry = AsyncEntry::from(entry);
+entry.set_password("async").await?;
+assert_eq!(entry.get_password().await?, "async");
+entry.delete_credential().await?;
+# Ok(())
+# }
+```
+
+This crate doesn't depend on any particular runtime, so each operation
+runs on a thread of its own, and its future completes when the thread is done.
+Operations run inside [Entry::with_interaction_allowed] keep that permission
+on their threads.
+ */
+use std::future::Future;
+use std::panic::{AssertUnwindSafe, catch_unwind, resume_unwind};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use super::{Entry, INTERACTION_ALLOWED, Result, interaction_allowed_override};
+
+/// An entry whose operations are `async`.
+///
+/// Async entries are cheap to clone, and clones share their entry.
+#[derive(Debug, Clone)]
+pub struct AsyncEntry {
+    entry: Arc<Entry>,
+}
+
+impl From<Entry> for AsyncEntry {
+    fn from(entry: Entry) -> Self {
+        Self {
+            entry: Arc::new(entry),
+        }
+    }
+}
+
+impl AsyncEntry {
+    /// Create an async entry for the given service and user.
+    ///
+    /// See [Entry::new]. (Creating an entry doesn't touch the store.)
+    pub fn new(service: &str, user: &str) -> Result<Self> {
+        Ok(Entry::new(service, user)?.into())
+    }
+
+    /// Create an async entry for the given target, service, and user.
+    ///
+    /// See [Entry::new_with_target].
+    pub fn new_with_target(target: &str, service: &str, user: &str) -> Result<Self> {
+        Ok(Entry::new_with_target(target, service, user)?.into())
+    }
+
+    /// The wrapped entry, for operations that have no async version.
+    pub fn entry(&self) -> &Entry {
+        &self.entry
+    }
+
+    /// Set the password for this entry. See [Entry::set_password].
+    pub async fn set_password(&self, password: &str) -> Result<()> {
+        let password = zeroize::Zeroizing::new(password.to_string());
+        self.run(move |entry| entry.set_password(&password)).await
+    }
+
+    /// Set the secret for this entry. See [Entry::set_secret].
+    pub async fn set_secret(&self, secret: &[u8]) -> Result<()> {
+        let secret = zeroize::Zeroizing::new(secret.to_vec());
+        self.run(move |entry| entry.set_secret(&secret)).await
+    }
+
+    /// Retrieve the password saved for this entry. See [Entry::get_password].
+    pub async fn get_password(&self) -> Result<String> {
+        self.run(Entry::get_password).await
+    }
+
+    /// Retrieve the secret saved for this entry. See [Entry::get_secret].
+    pub async fn get_secret(&self) -> Result<Vec<u8>> {
+        self.run(Entry::get_secret).await
+    }
+
+    /// Delete the underlying credential for this entry. See [Entry::delete_credential].
+    pub async fn delete_credential(&self) -> Result<()> {
+        self.run(Entry::delete_credential).await
+    }
+
+    fn run<T, F>(&self, f: F) -> Blocking<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Entry) -> T + Send + 'static,
+    {
+        let entry = self.entry.clone();
+        let allowed = interaction_allowed_override();
+        Blocking::spawn(move || {
+            INTERACTION_ALLOWED.with(|cell| cell.set(allowed));
+            f(&entry)
+        })
+    }
+}
+
+/// A future that runs a blocking function on a thread of its own.
+struct Blocking<T> {
+    state: Arc<Mutex<BlockingState<T>>>,
+}
+
+struct BlockingState<T> {
+    result: Option<std::thread::Result<T>>,
+    waker: Option<Waker>,
+}
+
+impl<T: Send + 'static> Blocking<T> {
+    fn spawn(f: impl FnOnce() -> T + Send + 'static) -> Self {
+        let state = Arc::new(Mutex::new(BlockingState {
+            result: None,
+            waker: None,
+        }));
+        let thread_state = state.clone();
+        std::thread::spawn(move || {
+            // a panic is passed on to the task that's waiting for the result
+            let result = catch_unwind(AssertUnwindSafe(f));
+            let mut state = lock(&thread_state);
+            state.result = Some(result);
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        });
+        Self { state }
+    }
+}
+
+impl<T> Future for Blocking<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut state = lock(&self.state);
+        match state.result.take() {
+            Some(Ok(result)) => Poll::Ready(result),
+            Some(Err(panic)) => resume_unwind(panic),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+fn lock<T>(state: &Mutex<BlockingState<T>>) -> std::sync::MutexGuard<'_, BlockingState<T>> {
+    state
+        .lock()
+        .expect("Poisoned async entry lock in keyring-rs: please report a bug!")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+
+    use super::AsyncEntry;
+    use crate::credential::CredentialBuilderApi;
+    use crate::memory::MemoryCredentialBuilder;
+    use crate::{Entry, Error};
+
+    /// Run a future to completion on the current thread.
+    fn block_on<T>(future: impl Future<Output = T>) -> T {
+        struct ThreadWaker(std::thread::Thread);
+
+        impl Wake for ThreadWaker {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        let waker = Waker::from(Arc::new(ThreadWaker(std::thread::current())));
+        let mut context = Context::from_waker(&waker);
+        let mut future = std::pin::pin!(future);
+        loop {
+            match future.as_mut().poll(&mut context) {
+                Poll::Ready(result) => return result,
+                Poll::Pending => std::thread::park(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_async_entry() {
+        let builder = MemoryCredentialBuilder::default();
+        let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+        let entry = AsyncEntry::from(entry);
+        block_on(async {
+            assert!(matches!(entry.get_password().await, Err(Error::NoEntry)));
+            entry.set_password("async password").await.unwrap();
+            assert_eq!(entry.get_password().await.unwrap(), "async password");
+            entry.clone().set_secret(b"async secret").await.unwrap();
+            assert_eq!(entry.get_secret().await.unwrap(), b"async secret");
+            entry.delete_credential().await.unwrap();
+            assert!(matches!(
+                entry.delete_credential().await,
+                Err(Error::NoEntry)
+            ));
+        });
+        assert!(matches!(entry.entry().get_password(), Err(Error::NoEntry)));
+    }
+}
@@ -0,0 +1,958 @@
+/*!
+
+# AWS credential store
+
+Apps that run on AWS (on EC2, ECS, or Lambda) usually keep their secrets
+in [Secrets Manager](https://docs.aws.amazon.com/secretsmanager/) or as
+`SecureString` parameters in the
+[SSM Parameter Store](https://docs.aws.amazon.com/systems-manager/latest/userguide/systems-manager-parameter-store.html),
+rather than on the machine they run on. This module keeps each credential
+in one of those services (which one is chosen when the builder is created),
+talking to AWS over its HTTP API. It's available (on every platform) when
+the `aws` feature is enabled. It's not the default store on any platform,
+so clients have to set it as their default (or build their credentials
+from it directly):
+```no_run
+use keyring::aws::{AwsCredentialBuilder, AwsService};
+
+let builder = AwsCredentialBuilder::new(AwsService::ParameterStore);
+keyring::set_default_credential_builder(Box::new(builder));
+```
+
+Requests are signed with the AWS keys found the way the AWS SDKs find
+them: in the `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY`, and
+`AWS_SESSION_TOKEN` environment variables (which is where Lambda puts them),
+or else from the ECS container credentials endpoint, or else from the EC2
+instance metadata service. The region is given by the `AWS_REGION` (or
+`AWS_DEFAULT_REGION`) environment variable, or else by the instance metadata
+service. Either can be given explicitly instead (see
+[with_keys](AwsCredentialBuilder::with_keys) and
+[with_region](AwsCredentialBuilder::with_region)). Keys that were found
+(rather than given) are found again if AWS reports that they have expired.
+
+For a given <_target_, _service_, _user_> triple, the name of the secret
+(or parameter) is the builder's prefix (`keyring-rs` by default, with a
+leading slash for parameters) followed by the target (if there is one), the
+service, and the user, separated by slashes. Characters in those parts other
+than ASCII letters, digits, `.`, and `-` are encoded as an underscore
+followed by their hex value (so `/` becomes `_2F`), and none of them may be
+empty. For example, the parameter for service `my-app` and user `alice` is
+`/keyring-rs/my-app/alice`.
+
+Secrets Manager keeps UTF-8 secrets as secret strings and other secrets
+as secret binaries, and deleting a credential deletes its secret without
+a recovery window. (Secrets Manager may still refuse, for a short while,
+to create a new secret with the same name.) The Parameter Store can only
+keep UTF-8 secrets, which it keeps in `SecureString` parameters.
+Both services encrypt their values with the account's default KMS key
+unless another is given (see [with_kms_key_id](AwsCredentialBuilder::with_kms_key_id)).
+
+Credential attributes are kept as tags on the secret (or parameter), and
+the label is kept in its `label` tag. This store doesn't support
+[search](crate::search).
+
+If AWS can't be reached, or refuses the client's keys or the request,
+a [NoStorageAccess](crate::Error::NoStorageAccess) error is returned.
+ */
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use hmac::{Hmac, Mac};
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+use super::credential::{
+    AtRestStatus, AttributeMap, Credential, CredentialApi, CredentialBuilderApi,
+    CredentialPersistence, CredentialSpec, CredentialStoreInfo,
+};
+use super::error::{Error as ErrorCode, Result};
+
+/// How long to wait for AWS to respond.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long to wait for the (local) instance metadata service.
+const METADATA_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The address of the EC2 instance metadata service.
+const IMDS: &str = "http://169.254.169.254";
+
+/// The address of the ECS container credentials endpoint.
+const ECS_CREDENTIALS: &str = "http://169.254.170.2";
+
+/// The AWS service that credentials are kept in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AwsService {
+    /// Keep each credential as a secret in Secrets Manager.
+    SecretsManager,
+    /// Keep each credential as a `SecureString` parameter in the SSM Parameter Store.
+    ParameterStore,
+}
+
+impl AwsService {
+    /// The name of the service in endpoints and signatures.
+    fn endpoint_name(&self) -> &'static str {
+        match self {
+            AwsService::SecretsManager => "secretsmanager",
+            AwsService::ParameterStore => "ssm",
+        }
+    }
+
+    /// The prefix of the service's API targets.
+    fn target_prefix(&self) -> &'static str {
+        match self {
+            AwsService::SecretsManager => "secretsmanager",
+            AwsService::ParameterStore => "AmazonSSM",
+        }
+    }
+}
+
+/// The keys that AWS requests are signed with.
+#[derive(Clone)]
+struct AwsKeys {
+    access_key_id: String,
+    secret_access_key: Zeroizing<String>,
+    session_token: Option<Zeroizing<String>>,
+}
+
+impl AwsKeys {
+    /// Read keys from the JSON returned by the ECS and EC2 credentials endpoints.
+    fn from_json(value: &Value) -> Option<Self> {
+        Some(Self {
+            access_key_id: value["AccessKeyId"].as_str()?.to_string(),
+            secret_access_key: Zeroizing::new(value["SecretAccessKey"].as_str()?.to_string()),
+            session_token: value["Token"]
+                .as_str()
+                .map(|t| Zeroizing::new(t.to_string())),
+        })
+    }
+}
+
+/// The service, region, and keys that credentials are kept with.
+#[derive(Clone)]
+struct Connection {
+    agent: ureq::Agent,
+    service: AwsService,
+    endpoint: Option<String>,
+    region: Arc<Mutex<Option<String>>>,
+    keys: Arc<Mutex<Option<AwsKeys>>>,
+    fixed_keys: bool,
+}
+
+impl std::fmt::Debug for Connection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Connection")
+            .field("service", &self.service)
+            .field("endpoint", &self.endpoint)
+            .field("region", &self.region.lock().ok().and_then(|r| r.clone()))
+            .finish_non_exhaustive()
+    }
+}
+
+impl Connection {
+    fn new(service: AwsService) -> Self {
+        let region = std::env::var("AWS_REGION")
+            .or_else(|_| std::env::var("AWS_DEFAULT_REGION"))
+            .ok();
+        Self {
+            agent: ureq::AgentBuilder::new().timeout(TIMEOUT).build(),
+            service,
+            endpoint: None,
+            region: Arc::new(Mutex::new(region)),
+            keys: Arc::new(Mutex::new(None)),
+            fixed_keys: false,
+        }
+    }
+
+    /// Call an action of the service's API.
+    ///
+    /// Returns `None` if AWS responds that the secret (or parameter) doesn't
+    /// exist. If AWS reports that found keys have expired, they are found
+    /// again and the request is retried once.
+    fn request(&self, action: &str, body: &Value) -> Result<Option<Value>> {
+        let response = match self.call(action, body)? {
+            Err(failure) if failure.kind == "ExpiredTokenException" && !self.fixed_keys => {
+                self.keys.lock().expect("AWS keys lock poisoned").take();
+                self.call(action, body)?
+            }
+            response => response,
+        };
+        match response {
+            Ok(value) => Ok(Some(value)),
+            Err(failure) if failure.is_not_found() => Ok(None),
+            Err(failure) => Err(failure.into_error()),
+        }
+    }
+
+    fn call(
+        &self,
+        action: &str,
+        body: &Value,
+    ) -> Result<std::result::Result<Value, ServiceFailure>> {
+        let region = self.region()?;
+        let keys = self.keys()?;
+        let name = self.service.endpoint_name();
+        let endpoint = match &self.endpoint {
+            Some(endpoint) => endpoint.clone(),
+            None => format!("https://{name}.{region}.amazonaws.com"),
+        };
+        let host = endpoint
+            .split_once("://")
+            .map_or(endpoint.as_str(), |(_, rest)| rest)
+            .split('/')
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        let target = format!("{}.{action}", self.service.target_prefix());
+        let payload = body.to_string();
+        let request = SignedRequest {
+            host: &host,
+            target: &target,
+            payload: &payload,
+            region: &region,
+            service: name,
+            date: amz_date(SystemTime::now()),
+        };
+        let mut http = self
+            .agent
+            .post(&endpoint)
+            .set("Content-Type", CONTENT_TYPE)
+            .set("X-Amz-Date", &request.date)
+            .set("X-Amz-Target", &target)
+            .set("Authorization", &request.authorization(&keys));
+        if let Some(token) = &keys.session_token {
+            http = http.set("X-Amz-Security-Token", token);
+        }
+        match http.send_string(&payload) {
+            Ok(response) => Ok(Ok(response.into_json().map_err(io_failure)?)),
+            Err(ureq::Error::Status(status, response)) => {
+                let body: Value = response.into_json().unwrap_or_default();
+                Ok(Err(ServiceFailure::new(status, &body)))
+            }
+            Err(ureq::Error::Transport(err)) => Err(ErrorCode::NoStorageAccess(Box::new(err))),
+        }
+    }
+
+    /// The region, asking the instance metadata service for it if necessary.
+    fn region(&self) -> Result<String> {
+        let mut region = self.region.lock().expect("AWS region lock poisoned");
+        if region.is_none() {
+            let found = metadata(&self.agent, "/latest/meta-data/placement/region")
+                .ok_or_else(|| no_access("no AWS region is configured"))?;
+            *region = Some(found);
+        }
+        Ok(region.clone().unwrap())
+    }
+
+    /// The signing keys, finding them if necessary.
+    fn keys(&self) -> Result<AwsKeys> {
+        let mut keys = self.keys.lock().expect("AWS keys lock poisoned");
+        if keys.is_none() {
+            *keys =
+                Some(find_keys(&self.agent).ok_or_else(|| no_access("no AWS keys were found"))?);
+        }
+        Ok(keys.clone().unwrap())
+    }
+}
+
+/// Find keys in the environment, or the ECS or EC2 credentials endpoints.
+fn find_keys(agent: &ureq::Agent) -> Option<AwsKeys> {
+    if let (Ok(access_key_id), Ok(secret_access_key)) = (
+        std::env::var("AWS_ACCESS_KEY_ID"),
+        std::env::var("AWS_SECRET_ACCESS_KEY"),
+    ) {
+        return Some(AwsKeys {
+            access_key_id,
+            secret_access_key: Zeroizing::new(secret_access_key),
+            session_token: std::env::var("AWS_SESSION_TOKEN").ok().map(Zeroizing::new),
+        });
+    }
+    if let Ok(uri) = std::env::var("AWS_CONTAINER_CREDENTIALS_RELATIVE_URI") {
+        let value: Value = agent
+            .get(&format!("{ECS_CREDENTIALS}{uri}"))
+            .timeout(METADATA_TIMEOUT)
+            .call()
+            .ok()?
+            .into_json()
+            .ok()?;
+        return AwsKeys::from_json(&value);
+    }
+    let path = "/latest/meta-data/iam/security-credentials/";
+    let role = metadata(agent, path)?;
+    let role = role.lines().next()?;
+    let value: Value = serde_json::from_str(&metadata(agent, &format!("{path}{role}"))?).ok()?;
+    AwsKeys::from_json(&value)
+}
+
+/// Get a value from the EC2 instance metadata service (using IMDSv2).
+fn metadata(agent: &ureq::Agent, path: &str) -> Option<String> {
+    let token = agent
+        .put(&format!("{IMDS}/latest/api/token"))
+        .timeout(METADATA_TIMEOUT)
+        .set("X-aws-ec2-metadata-token-ttl-seconds", "60")
+        .call()
+        .ok()?
+        .into_string()
+        .ok()?;
+    agent
+        .get(&format!("{IMDS}{path}"))
+        .timeout(METADATA_TIMEOUT)
+        .set("X-aws-ec2-metadata-token", &token)
+        .call()
+        .ok()?
+        .into_string()
+        .ok()
+}
+
+/// An error response from an AWS API.
+#[derive(Debug)]
+struct ServiceFailure {
+    status: u16,
+    kind: String,
+    message: String,
+}
+
+impl ServiceFailure {
+    fn new(status: u16, body: &Value) -> Self {
+        // error types may be qualified, as in `namespace#ResourceNotFoundException`
+        let kind = body["__type"].as_str().unwrap_or_default();
+        let kind = kind.rsplit('#').next().unwrap_or_default().to_string();
+        let message = body["message"]
+            .as_str()
+            .or_else(|| body["Message"].as_str())
+            .unwrap_or_default()
+            .to_string();
+        Self {
+            status,
+            kind,
+            message,
+        }
+    }
+
+    fn is_not_found(&self) -> bool {
+        matches!(
+            self.kind.as_str(),
+            "ResourceNotFoundException" | "ParameterNotFound" | "InvalidResourceId"
+        )
+    }
+
+    /// Refused keys or requests mean there's no access to the store.
+    fn into_error(self) -> ErrorCode {
+        let message = format!(
+            "AWS responded {} {}: {}",
+            self.status, self.kind, self.message
+        );
+        match self.kind.as_str() {
+            "AccessDeniedException"
+            | "UnrecognizedClientException"
+            | "InvalidSignatureException"
+            | "IncompleteSignature"
+            | "ExpiredTokenException"
+            | "MissingAuthenticationToken" => ErrorCode::NoStorageAccess(Box::from(message)),
+            _ if self.status == 403 => ErrorCode::NoStorageAccess(Box::from(message)),
+            _ => ErrorCode::PlatformFailure(Box::from(message)),
+        }
+    }
+}
+
+/// The content type of the (JSON 1.1) AWS APIs used by this module.
+const CONTENT_TYPE: &str = "application/x-amz-json-1.1";
+
+/// A JSON API request to be signed with
+/// [Signature Version 4](https://docs.aws.amazon.com/IAM/latest/UserGuide/reference_sigv.html).
+struct SignedRequest<'a> {
+    host: &'a str,
+    target: &'a str,
+    payload: &'a str,
+    region: &'a str,
+    service: &'a str,
+    date: String,
+}
+
+impl SignedRequest<'_> {
+    /// The value of the request's `Authorization` header.
+    fn authorization(&self, keys: &AwsKeys) -> String {
+        let mut headers = vec![
+            ("content-type", CONTENT_TYPE),
+            ("host", self.host),
+            ("x-amz-date", &self.date),
+            ("x-amz-target", self.target),
+        ];
+        if let Some(token) = &keys.session_token {
+            headers.push(("x-amz-security-token", token));
+        }
+        headers.sort();
+        let canonical_headers: String = headers
+            .iter()
+            .map(|(name, value)| format!("{name}:{}\n", value.trim()))
+            .collect();
+        let signed_headers = headers
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>()
+            .join(";");
+        let canonical_request = format!(
+            "POST\n/\n\n{canonical_headers}\n{signed_headers}\n{}",
+            to_hex(&Sha256::digest(self.payload.as_bytes()))
+        );
+        let day = &self.date[..8];
+        let scope = format!("{day}/{}/{}/aws4_request", self.region, self.service);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{scope}\n{}",
+            self.date,
+            to_hex(&Sha256::digest(canonical_request.as_bytes()))
+        );
+        let key = signing_key(&keys.secret_access_key, day, self.region, self.service);
+        let signature = to_hex(&hmac(&key, string_to_sign.as_bytes()));
+        format!(
+            "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+            keys.access_key_id
+        )
+    }
+}
+
+/// Derive the key that signs requests to a service in a region on a day.
+fn signing_key(secret: &str, day: &str, region: &str, service: &str) -> Zeroizing<Vec<u8>> {
+    let key = Zeroizing::new(format!("AWS4{secret}").into_bytes());
+    let key = Zeroizing::new(hmac(&key, day.as_bytes()));
+    let key = Zeroizing::new(hmac(&key, region.as_bytes()));
+    let key = Zeroizing::new(hmac(&key, service.as_bytes()));
+    Zeroizing::new(hmac(&key, b"aws4_request"))
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).unwrap();
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// The time in the (ISO 8601 basic) format that signatures use.
+fn amz_date(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (days, secs) = (secs / 86400, secs % 86400);
+    // convert days since the epoch to a civil date (see http://howardhinnant.github.io/date_algorithms.html)
+    let days = days + 719468;
+    let (era, day_of_era) = (days / 146097, days % 146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    format!(
+        "{year:04}{month:02}{day:02}T{:02}{:02}{:02}Z",
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+/// The representation of a Secrets Manager secret or SSM parameter.
+#[derive(Debug, Clone)]
+pub struct AwsCredential {
+    target: Option<String>,
+    service: String,
+    user: String,
+    name: String,
+    kms_key_id: Option<String>,
+    connection: Connection,
+}
+
+impl CredentialApi for AwsCredential {
+    /// Store the secret in this credential's secret (or parameter),
+    /// creating it if necessary.
+    ///
+    /// Returns an [Invalid](ErrorCode::Invalid) error if the secret
+    /// isn't UTF-8 and this credential is a parameter.
+    fn set_secret(&self, secret: &[u8]) -> Result<()> {
+        match self.connection.service {
+            AwsService::SecretsManager => {
+                let mut body = json!({ "SecretId": self.name });
+                match std::str::from_utf8(secret) {
+                    Ok(secret) => body["SecretString"] = Value::from(secret),
+                    Err(_) => body["SecretBinary"] = Value::from(BASE64.encode(secret)),
+                }
+                if self.connection.request("PutSecretValue", &body)?.is_some() {
+                    return Ok(());
+                }
+                let body = body.as_object_mut().unwrap();
+                let name = body.remove("SecretId").unwrap();
+                body.insert("Name".to_string(), name);
+                if let Some(key_id) = &self.kms_key_id {
+                    body.insert("KmsKeyId".to_string(), Value::from(key_id.as_str()));
+                }
+                self.connection
+                    .request("CreateSecret", &Value::from(body.clone()))?;
+            }
+            AwsService::ParameterStore => {
+                let secret = std::str::from_utf8(secret).map_err(|_| {
+                    ErrorCode::Invalid(
+                        "secret".to_string(),
+                        "must be UTF-8 to be kept in a parameter".to_string(),
+                    )
+                })?;
+                let mut body = json!({
+                    "Name": self.name,
+                    "Value": secret,
+                    "Type": "SecureString",
+                    "Overwrite": true,
+                });
+                if let Some(key_id) = &self.kms_key_id {
+                    body["KeyId"] = Value::from(key_id.as_str());
+                }
+                self.connection.request("PutParameter", &body)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Read the secret from this credential's secret (or parameter), if it exists.
+    ///
+    /// Returns a [NoEntry](ErrorCode::NoEntry) error if there is none.
+    fn get_secret(&self) -> Result<Vec<u8>> {
+        match self.connection.service {
+            AwsService::SecretsManager => {
+                let response = self
+                    .connection
+                    .request("GetSecretValue", &json!({ "SecretId": self.name }))?
+                    .ok_or(ErrorCode::NoEntry)?;
+                if let Some(secret) = response["SecretString"].as_str() {
+                    return Ok(secret.as_bytes().to_vec());
+                }
+                response["SecretBinary"]
+                    .as_str()
+                    .and_then(|secret| BASE64.decode(secret).ok())
+                    .ok_or_else(|| {
+                        ErrorCode::Invalid(
+                            "stored secret".to_string(),
+                            "has no secret string or binary".to_string(),
+                        )
+                    })
+            }
+            AwsService::ParameterStore => {
+                let body = json!({ "Name": self.name, "WithDecryption": true });
+                let response = self
+                    .connection
+                    .request("GetParameter", &body)?
+                    .ok_or(ErrorCode::NoEntry)?;
+                match response["Parameter"]["Value"].as_str() {
+                    Some(secret) => Ok(secret.as_bytes().to_vec()),
+                    None => Err(failure("parameter has no value")),
+                }
+            }
+        }
+    }
+
+    /// Get the tags on this credential's secret (or parameter), if it exists.
+    fn get_attributes(&self) -> Result<AttributeMap<String, String>> {
+        let (action, body, field) = match self.connection.service {
+            AwsService::SecretsManager => {
+                ("DescribeSecret", json!({ "SecretId": self.name }), "Tags")
+            }
+            AwsService::ParameterStore => (
+                "ListTagsForResource",
+                json!({ "ResourceType": "Parameter", "ResourceId": self.name }),
+                "TagList",
+            ),
+        };
+        let response = self
+            .connection
+            .request(action, &body)?
+            .ok_or(ErrorCode::NoEntry)?;
+        let tags = response[field].as_array().cloned().unwrap_or_default();
+        Ok(tags
+            .iter()
+            .filter_map(|tag| {
+                let key = tag["Key"].as_str()?.to_string();
+                Some((key, tag["Value"].as_str()?.to_string()))
+            })
+            .collect())
+    }
+
+    /// Update the tags on this credential's secret (or parameter), if it exists.
+    ///
+    /// Any attribute name can be given a value (if AWS accepts it as a tag).
+    fn update_attributes(&self, attributes: &AttributeMap<&str, &str>) -> Result<()> {
+        let tags: Vec<Value> = attributes
+            .iter()
+            .map(|(key, value)| json!({ "Key": key, "Value": value }))
+            .collect();
+        let (action, body) = match self.connection.service {
+            AwsService::SecretsManager => (
+                "TagResource",
+                json!({ "SecretId": self.name, "Tags": tags }),
+            ),
+            AwsService::ParameterStore => (
+                "AddTagsToResource",
+                json!({ "ResourceType": "Parameter", "ResourceId": self.name, "Tags": tags }),
+            ),
+        };
+        self.connection
+            .request(action, &body)?
+            .ok_or(ErrorCode::NoEntry)?;
+        Ok(())
+    }
+
+    /// The label is kept in the `label` tag (and is empty if that isn't set).
+    fn get_label(&self) -> Result<String> {
+        let attributes = self.get_attributes()?;
+        Ok(attributes.get("label").cloned().unwrap_or_default())
+    }
+
+    /// The label is kept in the `label` tag.
+    fn set_label(&self, label: &str) -> Result<()> {
+        self.update_attributes(&AttributeMap::from([("label", label)]))
+    }
+
+    /// Delete this credential's secret (or parameter), if it exists.
+    ///
+    /// Returns a [NoEntry](ErrorCode::NoEntry) error if there is none.
+    fn delete_credential(&self) -> Result<()> {
+        let (action, body) = match self.connection.service {
+            AwsService::SecretsManager => (
+                "DeleteSecret",
+                json!({ "SecretId": self.name, "ForceDeleteWithoutRecovery": true }),
+            ),
+            AwsService::ParameterStore => ("DeleteParameter", json!({ "Name": self.name })),
+        };
+        self.connection
+            .request(action, &body)?
+            .ok_or(ErrorCode::NoEntry)?;
+        Ok(())
+    }
+
+    /// The target, service, and user this credential was built with.
+    fn spec(&self) -> Option<CredentialSpec> {
+        Some(CredentialSpec {
+            target: self.target.clone(),
+            service: self.service.clone(),
+            user: self.user.clone(),
+        })
+    }
+
+    /// Both services encrypt their values with KMS keys.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        AtRestStatus::Encrypted
+    }
+
+    /// Return the underlying concrete object with an `Any` type so that it can
+    /// be downgraded to an [AwsCredential] for platform-specific processing.
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    /// Expose the concrete debug formatter for use via the [Credential] trait
+    fn debug_fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self, f)
+    }
+}
+
+impl AwsCredential {
+    /// The name of this credential's secret (or parameter).
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// The builder for AWS credentials.
+#[derive(Debug, Clone)]
+pub struct AwsCredentialBuilder {
+    connection: Connection,
+    prefix: String,
+    kms_key_id: Option<String>,
+}
+
+impl AwsCredentialBuilder {
+    /// Create a builder for the given service.
+    ///
+    /// The region and keys are found as described in the [module docs](self)
+    /// when they are first needed, so this can't fail.
+    pub fn new(service: AwsService) -> Self {
+        Self {
+            connection: Connection::new(service),
+            prefix: "keyring-rs".to_string(),
+            kms_key_id: None,
+        }
+    }
+
+    /// The service that this builder's credentials are kept in.
+    pub fn service(&self) -> AwsService {
+        self.connection.service
+    }
+
+    /// Use the given region (rather than finding one).
+    pub fn with_region(mut self, region: &str) -> Self {
+        self.connection.region = Arc::new(Mutex::new(Some(region.to_string())));
+        self
+    }
+
+    /// The region of this builder's service, if it's known yet.
+    pub fn region(&self) -> Option<String> {
+        self.connection.region.lock().ok()?.clone()
+    }
+
+    /// Sign requests with the given keys (rather than finding them).
+    pub fn with_keys(
+        mut self,
+        access_key_id: &str,
+        secret_access_key: &str,
+        session_token: Option<&str>,
+    ) -> Self {
+        let keys = AwsKeys {
+            access_key_id: access_key_id.to_string(),
+            secret_access_key: Zeroizing::new(secret_access_key.to_string()),
+            session_token: session_token.map(|t| Zeroizing::new(t.to_string())),
+        };
+        self.connection.keys = Arc::new(Mutex::new(Some(keys)));
+        self.connection.fixed_keys = true;
+        self
+    }
+
+    /// Send requests to the given endpoint (such as a VPC endpoint or a
+    /// local emulator) rather than the service's public one in the region.
+    pub fn with_endpoint(mut self, endpoint: Option<&str>) -> Self {
+        self.connection.endpoint = endpoint.map(|e| e.trim_end_matches('/').to_string());
+        self
+    }
+
+    /// The endpoint that requests are sent to, if it isn't the public one.
+    pub fn endpoint(&self) -> Option<&str> {
+        self.connection.endpoint.as_deref()
+    }
+
+    /// Name secrets (or parameters) with `prefix` (rather than `keyring-rs`).
+    ///
+    /// The prefix can have several segments (such as `apps/my-app`),
+    /// or be empty.
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.trim_matches('/').to_string();
+        self
+    }
+
+    /// The prefix of the names of this builder's secrets (or parameters).
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// Encrypt new secrets (or parameters) with the given KMS key
+    /// (rather than the account's default key for the service).
+    pub fn with_kms_key_id(mut self, kms_key_id: Option<&str>) -> Self {
+        self.kms_key_id = kms_key_id.map(|k| k.to_string());
+        self
+    }
+
+    /// The KMS key, if any, that new secrets (or parameters) are encrypted with.
+    pub fn kms_key_id(&self) -> Option<&str> {
+        self.kms_key_id.as_deref()
+    }
+}
+
+impl CredentialBuilderApi for AwsCredentialBuilder {
+    /// Build an [AwsCredential] for the given target, service, and user.
+    ///
+    /// Returns an [Invalid](ErrorCode::Invalid) error if any of them is empty,
+    /// since they are segments of the name. The secret (or parameter)
+    /// isn't created until its value is set.
+    fn build(&self, target: Option<&str>, service: &str, user: &str) -> Result<Box<Credential>> {
+        let mut segments = Vec::new();
+        if !self.prefix.is_empty() {
+            segments.push(self.prefix.clone());
+        }
+        for (name, part) in [
+            ("target", target),
+            ("service", Some(service)),
+            ("user", Some(user)),
+        ] {
+            match part {
+                Some("") => {
+                    return Err(ErrorCode::Invalid(
+                        name.to_string(),
+                        "cannot be empty".to_string(),
+                    ));
+                }
+                Some(part) => segments.push(encode_segment(part)),
+                None => {}
+            }
+        }
+        let mut name = segments.join("/");
+        if self.connection.service == AwsService::ParameterStore {
+            // hierarchical parameter names are fully qualified
+            name.insert(0, '/');
+        }
+        Ok(Box::new(AwsCredential {
+            target: target.map(|t| t.to_string()),
+            service: service.to_string(),
+            user: user.to_string(),
+            name,
+            kms_key_id: self.kms_key_id.clone(),
+            connection: self.connection.clone(),
+        }))
+    }
+
+    /// Both services encrypt their values with KMS keys.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        AtRestStatus::Encrypted
+    }
+
+    /// Return the underlying builder object with an `Any` type so that it can
+    /// be downgraded to an [AwsCredentialBuilder] for platform-specific processing.
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    /// Both services keep values until they are deleted.
+    fn persistence(&self) -> CredentialPersistence {
+        CredentialPersistence::UntilDelete
+    }
+
+    /// Secrets have tags (as attributes), but the store isn't searched.
+    fn info(&self) -> CredentialStoreInfo {
+        CredentialStoreInfo::new("aws", self.persistence(), self.at_rest_encryption())
+            .with_attributes(true)
+    }
+}
+
+/// Encode a part of a credential's identity as a single segment of a name.
+///
+/// Only ASCII letters, digits, `.`, and `-` are kept as they are, since
+/// those are allowed in both secret and parameter names; the rest
+/// (including the underscore) are encoded as `_` and their hex value.
+fn encode_segment(part: &str) -> String {
+    let mut encoded = String::with_capacity(part.len());
+    for b in part.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'.' | b'-' => encoded.push(b as char),
+            _ => encoded.push_str(&format!("_{b:02X}")),
+        }
+    }
+    encoded
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn no_access(reason: &str) -> ErrorCode {
+    ErrorCode::NoStorageAccess(Box::from(reason.to_string()))
+}
+
+fn io_failure(err: std::io::Error) -> ErrorCode {
+    ErrorCode::PlatformFailure(Box::new(err))
+}
+
+fn failure(reason: &str) -> ErrorCode {
+    ErrorCode::PlatformFailure(Box::from(format!("AWS {reason}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use serde_json::json;
+
+    use crate::Error;
+    use crate::credential::{CredentialBuilderApi, CredentialPersistence};
+
+    use super::{
+        AwsCredential, AwsCredentialBuilder, AwsService, ServiceFailure, amz_date, signing_key,
+        to_hex,
+    };
+
+    fn name_of(builder: &AwsCredentialBuilder, target: Option<&str>, service: &str) -> String {
+        let credential = builder.build(target, service, "user").unwrap();
+        let credential: &AwsCredential = credential.as_any().downcast_ref().unwrap();
+        credential.name().to_string()
+    }
+
+    #[test]
+    fn test_persistence() {
+        assert!(matches!(
+            AwsCredentialBuilder::new(AwsService::SecretsManager).persistence(),
+            CredentialPersistence::UntilDelete
+        ))
+    }
+
+    #[test]
+    fn test_names() {
+        let builder = AwsCredentialBuilder::new(AwsService::SecretsManager);
+        assert_eq!(
+            name_of(&builder, None, "service"),
+            "keyring-rs/service/user"
+        );
+        assert_eq!(
+            name_of(&builder, Some("target"), "a/b_c d"),
+            "keyring-rs/target/a_2Fb_5Fc_20d/user"
+        );
+        let builder = AwsCredentialBuilder::new(AwsService::ParameterStore);
+        assert_eq!(
+            name_of(&builder, None, "service"),
+            "/keyring-rs/service/user"
+        );
+        let builder = builder.with_prefix("/apps/my-app/");
+        assert_eq!(builder.prefix(), "apps/my-app");
+        assert_eq!(
+            name_of(&builder, None, "service"),
+            "/apps/my-app/service/user"
+        );
+        let builder = builder.with_prefix("");
+        assert_eq!(name_of(&builder, None, "service"), "/service/user");
+    }
+
+    #[test]
+    fn test_invalid_parameter() {
+        let builder = AwsCredentialBuilder::new(AwsService::ParameterStore);
+        for (target, service, user) in [(Some(""), "s", "u"), (None, "", "u"), (None, "s", "")] {
+            assert!(matches!(
+                builder.build(target, service, user),
+                Err(Error::Invalid(_, _))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_amz_date() {
+        let time = UNIX_EPOCH + Duration::from_secs(1329264000);
+        assert_eq!(amz_date(time), "20120215T000000Z");
+        let time = UNIX_EPOCH + Duration::from_secs(951782400 + 3723);
+        assert_eq!(amz_date(time), "20000229T010203Z");
+    }
+
+    #[test]
+    fn test_signing_key() {
+        // the example in the AWS Signature Version 4 documentation
+        let key = signing_key(
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+            "20120215",
+            "us-east-1",
+            "iam",
+        );
+        assert_eq!(
+            to_hex(&key),
+            "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d"
+        );
+    }
+
+    #[test]
+    fn test_service_failures() {
+        let failure = ServiceFailure::new(
+            400,
+            &json!({ "__type": "com.amazonaws.ssm#ParameterNotFound", "message": "gone" }),
+        );
+        assert!(failure.is_not_found());
+        let failure = ServiceFailure::new(400, &json!({ "__type": "AccessDeniedException" }));
+        assert!(!failure.is_not_found());
+        assert!(matches!(failure.into_error(), Error::NoStorageAccess(_)));
+        let failure = ServiceFailure::new(400, &json!({ "__type": "LimitExceededException" }));
+        assert!(matches!(failure.into_error(), Error::PlatformFailure(_)));
+    }
+}
@@ -0,0 +1,305 @@
+/*!
+
+# The keyring command-line tool
+
+A small CLI over the active credential store, for inspecting (and fixing up)
+what keyring-based apps have stored. It goes through the same entry and
+search code paths that apps do, so what it sees is what they see.
+
+Secrets are read from the standard input and written to the standard output,
+so they never appear on the command line:
+```text
+$ printf 'hunter2' | keyring --service my-app --user alice set
+$ keyring --service my-app --user alice get
+hunter2
+$ keyring --service my-app list
+my-app    alice
+$ keyring --service my-app --user alice move --to-user bob
+$ keyring --service my-app --user bob delete
+```
+
+The store is the platform default, unless one is given with `--store`
+(in the format of `keyring::credential_builder_from_str`). The tool exits
+with status 2 when there's no credential to get, move, or delete, and with
+status 1 on any other failure.
+ */
+extern crate keyring;
+
+use std::io::{Read, Write};
+
+use clap::{Parser, Subcommand};
+use zeroize::Zeroizing;
+
+use keyring::credential::{Match, SearchSpec};
+use keyring::{Entry, Error, Result};
+
+#[derive(Debug, Parser)]
+#[clap(author = "github.com/hwchen/keyring-rs")]
+/// Inspect and manage the credentials in the active keystore.
+pub struct Cli {
+    #[clap(long, value_parser, global = true)]
+    /// The credential store to use, as a spec string (e.g. `secret-service`).
+    /// Defaults to the platform store.
+    pub store: Option<String>,
+
+    #[clap(short, long, value_parser, global = true)]
+    /// The target of the credential.
+    pub target: Option<String>,
+
+    #[clap(short, long, value_parser, global = true)]
+    /// The service of the credential.
+    pub service: Option<String>,
+
+    #[clap(short, long, value_parser, global = true)]
+    /// The user of the credential.
+    pub user: Option<String>,
+
+    #[clap(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Set the credential's secret from the standard input.
+    Set {
+        #[clap(short, long, action)]
+        /// Store the input bytes as a binary secret, rather than
+        /// as a (UTF-8) password.
+        binary: bool,
+
+        #[clap(short, long, action)]
+        /// Keep a trailing newline in the input, rather than removing it.
+        raw: bool,
+    },
+    /// Write the credential's secret to the standard output.
+    Get {
+        #[clap(short, long, action)]
+        /// Write the secret's bytes as they are, rather than as a
+        /// (UTF-8) password followed by a newline.
+        binary: bool,
+    },
+    /// Delete the credential.
+    Delete,
+    /// List the credentials matching the given target, service, and user
+    /// (all of them, if none are given), one per line, as tab-separated
+    /// service, user, and (if any) target.
+    List {
+        #[clap(short, long, action)]
+        /// Match credentials whose fields start with the given values,
+        /// rather than those whose fields equal them.
+        prefix: bool,
+    },
+    /// Move the credential (its secret and attributes) to another target,
+    /// service, or user.
+    Move {
+        #[clap(long, value_parser)]
+        /// The target to move to (defaults to the credential's target).
+        to_target: Option<String>,
+
+        #[clap(long, value_parser)]
+        /// The service to move to (defaults to the credential's service).
+        to_service: Option<String>,
+
+        #[clap(long, value_parser)]
+        /// The user to move to (defaults to the credential's user).
+        to_user: Option<String>,
+
+        #[clap(short, long, action)]
+        /// Overwrite a credential that's already at the destination.
+        force: bool,
+    },
+}
+
+fn main() {
+    let cli = Cli::parse();
+    if let Some(spec) = &cli.store {
+        if let Err(err) = keyring::set_default_credential_builder_from_str(spec) {
+            fail(&format!("Couldn't use store '{spec}'"), err);
+        }
+    }
+    let result = match &cli.command {
+        Command::Set { binary, raw } => cli.set(*binary, *raw),
+        Command::Get { binary } => cli.get(*binary),
+        Command::Delete => cli
+            .entry(cli.target.as_deref(), cli.service(), cli.user())
+            .and_then(|entry| entry.delete_credential()),
+        Command::List { prefix } => cli.list(*prefix),
+        Command::Move {
+            to_target,
+            to_service,
+            to_user,
+            force,
+        } => cli.move_to(
+            to_target.as_deref().or(cli.target.as_deref()),
+            to_service.as_deref().unwrap_or(cli.service()),
+            to_user.as_deref().unwrap_or(cli.user()),
+            *force,
+        ),
+    };
+    if let Err(err) = result {
+        fail(
+            &format!("Couldn't {} '{}'", cli.verb(), cli.description()),
+            err,
+        );
+    }
+}
+
+impl Cli {
+    fn service(&self) -> &str {
+        self.required(self.service.as_deref(), "--service")
+    }
+
+    fn user(&self) -> &str {
+        self.required(self.user.as_deref(), "--user")
+    }
+
+    fn required<'a>(&self, value: Option<&'a str>, flag: &str) -> &'a str {
+        value.unwrap_or_else(|| {
+            eprintln!("The {} command needs a {flag} value", self.verb());
+            std::process::exit(1)
+        })
+    }
+
+    fn verb(&self) -> &'static str {
+        match self.command {
+            Command::Set { .. } => "set",
+            Command::Get { .. } => "get",
+            Command::Delete => "delete",
+            Command::List { .. } => "list",
+            Command::Move { .. } => "move",
+        }
+    }
+
+    fn description(&self) -> String {
+        let service = self.service.as_deref().unwrap_or("*");
+        let user = self.user.as_deref().unwrap_or("*");
+        match &self.target {
+            Some(target) => format!("{user}@{service}:{target}"),
+            None => format!("{user}@{service}"),
+        }
+    }
+
+    fn entry(&self, target: Option<&str>, service: &str, user: &str) -> Result<Entry> {
+        match target {
+            Some(target) => Entry::new_with_target(target, service, user),
+            None => Entry::new(service, user),
+        }
+    }
+
+    fn set(&self, binary: bool, raw: bool) -> Result<()> {
+        let entry = self.entry(self.target.as_deref(), self.service(), self.user())?;
+        let mut input = Zeroizing::new(Vec::new());
+        std::io::stdin()
+            .read_to_end(&mut input)
+            .map_err(|err| Error::Invalid("standard input".to_string(), err.to_string()))?;
+        let secret = if raw {
+            input.as_slice()
+        } else {
+            trim_line_ending(&input)
+        };
+        if binary {
+            entry.set_secret(secret)
+        } else {
+            let password = std::str::from_utf8(secret).map_err(|_| {
+                Error::Invalid(
+                    "standard input".to_string(),
+                    "is not UTF-8 (use --binary to store it as a secret)".to_string(),
+                )
+            })?;
+            entry.set_password(password)
+        }
+    }
+
+    fn get(&self, binary: bool) -> Result<()> {
+        let entry = self.entry(self.target.as_deref(), self.service(), self.user())?;
+        let mut stdout = std::io::stdout().lock();
+        let written = if binary {
+            let secret = entry.get_secret_zeroizing()?;
+            stdout.write_all(&secret)
+        } else {
+            let password = Zeroizing::new(entry.get_password()?);
+            writeln!(stdout, "{}", password.as_str())
+        };
+        written
+            .and_then(|_| stdout.flush())
+            .map_err(|err| Error::Invalid("standard output".to_string(), err.to_string()))
+    }
+
+    fn list(&self, prefix: bool) -> Result<()> {
+        let matcher = |value: &Option<String>| match value {
+            Some(value) if prefix => Match::Prefix(value.clone()),
+            Some(value) => Match::Exact(value.clone()),
+            None => Match::Any,
+        };
+        let spec = SearchSpec {
+            target: matcher(&self.target),
+            service: matcher(&self.service),
+            user: matcher(&self.user),
+            ..Default::default()
+        };
+        for entry in keyring::search(&spec)? {
+            match entry.spec() {
+                Some(spec) => match &spec.target {
+                    Some(target) => println!("{}\t{}\t{target}", spec.service, spec.user),
+                    None => println!("{}\t{}", spec.service, spec.user),
+                },
+                None => println!("{:?}", entry.get_credential()),
+            }
+        }
+        Ok(())
+    }
+
+    fn move_to(&self, target: Option<&str>, service: &str, user: &str, force: bool) -> Result<()> {
+        let from = self.entry(self.target.as_deref(), self.service(), self.user())?;
+        let to = self.entry(target, service, user)?;
+        if from.spec() == to.spec() {
+            return Ok(());
+        }
+        if !force && to.exists()? {
+            return Err(Error::Invalid(
+                "destination".to_string(),
+                "already has a credential (use --force to overwrite it)".to_string(),
+            ));
+        }
+        from.copy_into(&to)?;
+        from.delete_credential()
+    }
+}
+
+/// Remove one trailing line ending (`\n` or `\r\n`), as left by `echo` or a terminal.
+fn trim_line_ending(input: &[u8]) -> &[u8] {
+    match input.strip_suffix(b"\n") {
+        Some(line) => line.strip_suffix(b"\r").unwrap_or(line),
+        None => input,
+    }
+}
+
+fn fail(context: &str, err: Error) -> ! {
+    eprintln!("{context}: {err}");
+    match err {
+        Error::NoEntry => std::process::exit(2),
+        _ => std::process::exit(1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use super::{Cli, trim_line_ending};
+
+    #[test]
+    fn test_cli() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_trim_line_ending() {
+        assert_eq!(trim_line_ending(b"secret\n"), b"secret");
+        assert_eq!(trim_line_ending(b"secret\r\n"), b"secret");
+        assert_eq!(trim_line_ending(b"secret\n\n"), b"secret\n");
+        assert_eq!(trim_line_ending(b"secret\r"), b"secret\r");
+        assert_eq!(trim_line_ending(b"secret"), b"secret");
+        assert_eq!(trim_line_ending(b""), b"");
+    }
+}
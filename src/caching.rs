@@ -0,0 +1,363 @@
+/*!
+
+# Caching credential wrapper
+
+Some stores are slow to read from (the Secret Service, for example, makes
+several DBus calls for every read, and can fail when it's read from rapidly),
+so apps that read the same secret many times end up writing their own caches.
+The [CachingCredential] in this module wraps another credential and keeps
+the secrets (and passwords) read from it in memory for a configurable time,
+so that repeated reads within that time don't touch the store.
+The [CachingCredentialBuilder] wraps each credential it builds this way.
+
+```
+# use std::time::Duration;
+# use keyring::{Entry, credential::CredentialBuilderApi, memory::MemoryCredentialBuilder};
+# use keyring::caching::CachingCredentialBuilder;
+let store = MemoryCredentialBuilder::default();
+let builder = CachingCredentialBuilder::new(Box::new(store.clone()), Duration::from_secs(60));
+let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+entry.set_password("cached").unwrap();
+// a change made behind the cache's back isn't seen until the cache expires...
+let raw = Entry::new_with_credential(store.build(None, "service", "user").unwrap());
+raw.set_password("changed").unwrap();
+assert_eq!(entry.get_password().unwrap(), "cached");
+// ...or is invalidated
+let cached: &keyring::caching::CachingCredential = entry.get_credential().downcast_ref().unwrap();
+cached.invalidate();
+assert_eq!(entry.get_password().unwrap(), "changed");
+```
+
+Writes go through to the wrapped credential, and a successful write
+replaces what's cached, so changes made through a caching credential are
+always seen by it. Deleting the credential (or any error from the wrapped
+credential) empties the cache; nothing is cached when there's no entry.
+Cached secrets are zeroed when they are replaced or dropped.
+
+Each caching credential has a cache of its own, so two entries for the
+same credential don't see each other's changes until their caches expire.
+Credentials returned by a search through a caching builder are cached, too.
+ */
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use zeroize::Zeroizing;
+
+use super::credential::{
+    AtRestStatus, AttributeMap, Credential, CredentialApi, CredentialBuilder, CredentialBuilderApi,
+    CredentialPersistence, CredentialSpec, CredentialStoreInfo, SearchSpec,
+};
+use super::error::Result;
+
+/// A credential builder that caches the secrets of the credentials
+/// built by another builder.
+#[derive(Debug)]
+pub struct CachingCredentialBuilder {
+    inner: Box<CredentialBuilder>,
+    ttl: Duration,
+}
+
+impl CachingCredentialBuilder {
+    /// Create a builder whose credentials cache the secrets read from
+    /// the credentials built by `inner` for `ttl`.
+    pub fn new(inner: Box<CredentialBuilder>, ttl: Duration) -> Self {
+        Self { inner, ttl }
+    }
+
+    /// The wrapped builder.
+    pub fn inner(&self) -> &CredentialBuilder {
+        self.inner.as_ref()
+    }
+
+    /// How long this builder's credentials cache secrets.
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+}
+
+impl CredentialBuilderApi for CachingCredentialBuilder {
+    /// Build a credential in the wrapped store, and cache it.
+    fn build(&self, target: Option<&str>, service: &str, user: &str) -> Result<Box<Credential>> {
+        let inner = self.inner.build(target, service, user)?;
+        Ok(Box::new(CachingCredential::new(inner, self.ttl)))
+    }
+
+    /// The at-rest encryption of the wrapped store.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        self.inner.at_rest_encryption()
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    /// The persistence of the wrapped store.
+    fn persistence(&self) -> CredentialPersistence {
+        self.inner.persistence()
+    }
+
+    /// Search the wrapped store, and cache the found credentials.
+    fn search(&self, spec: &SearchSpec) -> Result<Vec<Box<Credential>>> {
+        let found = self.inner.search(spec)?;
+        Ok(found
+            .into_iter()
+            .map(|cred| Box::new(CachingCredential::new(cred, self.ttl)) as Box<Credential>)
+            .collect())
+    }
+
+    /// The info of the wrapped store.
+    fn info(&self) -> CredentialStoreInfo {
+        self.inner.info()
+    }
+}
+
+/// What's been read from (or written to) a credential, and when.
+#[derive(Default)]
+struct Cache {
+    secret: Option<(Zeroizing<Vec<u8>>, Instant)>,
+    password: Option<(Zeroizing<String>, Instant)>,
+}
+
+/// A credential that caches the secrets read from another credential.
+pub struct CachingCredential {
+    inner: Box<Credential>,
+    ttl: Duration,
+    cache: Mutex<Cache>,
+}
+
+impl CachingCredential {
+    /// Wrap a credential, caching the secrets read from it for `ttl`.
+    pub fn new(inner: Box<Credential>, ttl: Duration) -> Self {
+        Self {
+            inner,
+            ttl,
+            cache: Mutex::new(Cache::default()),
+        }
+    }
+
+    /// The wrapped credential.
+    pub fn inner(&self) -> &Credential {
+        self.inner.as_ref()
+    }
+
+    /// How long this credential caches secrets.
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Empty this credential's cache, so the next read goes to the wrapped credential.
+    pub fn invalidate(&self) {
+        *self.lock() = Cache::default();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Cache> {
+        self.cache
+            .lock()
+            .expect("Poisoned cache lock in keyring-rs: please report a bug!")
+    }
+
+    fn fresh(&self, at: Instant) -> bool {
+        at.elapsed() < self.ttl
+    }
+
+    /// Pass a result through, emptying the cache if it's an error.
+    fn checked<T>(&self, result: Result<T>) -> Result<T> {
+        if result.is_err() {
+            self.invalidate();
+        }
+        result
+    }
+}
+
+impl CredentialApi for CachingCredential {
+    fn set_password(&self, password: &str) -> Result<()> {
+        self.checked(self.inner.set_password(password))?;
+        let mut cache = self.lock();
+        *cache = Cache::default();
+        cache.password = Some((Zeroizing::new(password.to_string()), Instant::now()));
+        Ok(())
+    }
+
+    fn set_secret(&self, secret: &[u8]) -> Result<()> {
+        self.checked(self.inner.set_secret(secret))?;
+        let mut cache = self.lock();
+        *cache = Cache::default();
+        cache.secret = Some((Zeroizing::new(secret.to_vec()), Instant::now()));
+        Ok(())
+    }
+
+    fn get_password(&self) -> Result<String> {
+        if let Some((password, at)) = &self.lock().password {
+            if self.fresh(*at) {
+                return Ok(password.to_string());
+            }
+        }
+        let password = self.checked(self.inner.get_password())?;
+        self.lock().password = Some((Zeroizing::new(password.clone()), Instant::now()));
+        Ok(password)
+    }
+
+    fn get_secret(&self) -> Result<Vec<u8>> {
+        if let Some((secret, at)) = &self.lock().secret {
+            if self.fresh(*at) {
+                return Ok(secret.to_vec());
+            }
+        }
+        let secret = self.checked(self.inner.get_secret())?;
+        self.lock().secret = Some((Zeroizing::new(secret.clone()), Instant::now()));
+        Ok(secret)
+    }
+
+    /// Compare and set in the wrapped credential, never against the cache
+    /// (which may be stale), and cache the new password if that succeeds.
+    fn compare_and_set_password(&self, expected: &str, password: &str) -> Result<()> {
+        self.invalidate();
+        self.checked(self.inner.compare_and_set_password(expected, password))?;
+        self.lock().password = Some((Zeroizing::new(password.to_string()), Instant::now()));
+        Ok(())
+    }
+
+    /// Compare and set in the wrapped credential, never against the cache
+    /// (which may be stale), and cache the new secret if that succeeds.
+    fn compare_and_set_secret(&self, expected: &[u8], secret: &[u8]) -> Result<()> {
+        self.invalidate();
+        self.checked(self.inner.compare_and_set_secret(expected, secret))?;
+        self.lock().secret = Some((Zeroizing::new(secret.to_vec()), Instant::now()));
+        Ok(())
+    }
+
+    fn exists(&self) -> Result<bool> {
+        self.inner.exists()
+    }
+
+    fn get_attributes(&self) -> Result<AttributeMap<String, String>> {
+        self.inner.get_attributes()
+    }
+
+    fn update_attributes(&self, attributes: &AttributeMap<&str, &str>) -> Result<()> {
+        self.inner.update_attributes(attributes)
+    }
+
+    fn get_label(&self) -> Result<String> {
+        self.inner.get_label()
+    }
+
+    fn set_label(&self, label: &str) -> Result<()> {
+        self.inner.set_label(label)
+    }
+
+    /// Set the wrapped credential's expiration, and empty the cache
+    /// (so the cache won't outlive the credential).
+    fn set_expiration(&self, ttl: Duration) -> Result<()> {
+        self.invalidate();
+        self.inner.set_expiration(ttl)
+    }
+
+    fn unlock(&self) -> Result<()> {
+        self.inner.unlock()
+    }
+
+    fn max_secret_len(&self) -> Option<usize> {
+        self.inner.max_secret_len()
+    }
+
+    fn spec(&self) -> Option<CredentialSpec> {
+        self.inner.spec()
+    }
+
+    /// Delete the wrapped credential, and empty the cache.
+    fn delete_credential(&self) -> Result<()> {
+        self.invalidate();
+        self.inner.delete_credential()
+    }
+
+    /// The at-rest encryption of the wrapped credential.
+    ///
+    /// (Cached secrets are kept unencrypted in memory.)
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        self.inner.at_rest_encryption()
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn debug_fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CachingCredential")
+            .field("inner", &self.inner)
+            .field("ttl", &self.ttl)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{CachingCredential, CachingCredentialBuilder};
+    use crate::credential::CredentialBuilderApi;
+    use crate::memory::MemoryCredentialBuilder;
+    use crate::{Entry, Error};
+
+    #[test]
+    fn test_caching() {
+        let store = MemoryCredentialBuilder::default();
+        let builder =
+            CachingCredentialBuilder::new(Box::new(store.clone()), Duration::from_secs(60));
+        let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+        let raw = Entry::new_with_credential(store.build(None, "service", "user").unwrap());
+        assert!(matches!(entry.get_secret(), Err(Error::NoEntry)));
+        raw.set_secret(b"first").unwrap();
+        assert_eq!(entry.get_secret().unwrap(), b"first");
+        raw.set_secret(b"second").unwrap();
+        assert_eq!(entry.get_secret().unwrap(), b"first");
+        entry.set_secret(b"third").unwrap();
+        assert_eq!(entry.get_secret().unwrap(), b"third");
+        let cached: &CachingCredential = entry.get_credential().downcast_ref().unwrap();
+        raw.set_secret(b"fourth").unwrap();
+        cached.invalidate();
+        assert_eq!(entry.get_secret().unwrap(), b"fourth");
+        entry.delete_credential().unwrap();
+        assert!(matches!(entry.get_secret(), Err(Error::NoEntry)));
+    }
+
+    #[test]
+    fn test_compare_and_set() {
+        let store = MemoryCredentialBuilder::default();
+        let builder =
+            CachingCredentialBuilder::new(Box::new(store.clone()), Duration::from_secs(60));
+        let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+        let raw = Entry::new_with_credential(store.build(None, "service", "user").unwrap());
+        entry.set_password("v1").unwrap();
+        raw.set_password("v2").unwrap();
+        // the stale cached password doesn't count as the current one
+        assert!(matches!(
+            entry.update_password("v1", "v3"),
+            Err(Error::Conflict)
+        ));
+        assert_eq!(raw.get_password().unwrap(), "v2");
+        assert_eq!(entry.get_password().unwrap(), "v2");
+        entry.update_password("v2", "v3").unwrap();
+        assert_eq!(raw.get_password().unwrap(), "v3");
+        assert_eq!(entry.get_password().unwrap(), "v3");
+
+        entry.set_secret(b"s1").unwrap();
+        raw.set_secret(b"s2").unwrap();
+        assert!(matches!(
+            entry.update_secret(b"s1", b"s3"),
+            Err(Error::Conflict)
+        ));
+        assert_eq!(raw.get_secret().unwrap(), b"s2");
+    }
+
+    #[test]
+    fn test_expiry() {
+        let store = MemoryCredentialBuilder::default();
+        let builder = CachingCredentialBuilder::new(Box::new(store.clone()), Duration::ZERO);
+        let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+        let raw = Entry::new_with_credential(store.build(None, "service", "user").unwrap());
+        entry.set_password("first").unwrap();
+        raw.set_password("second").unwrap();
+        assert_eq!(entry.get_password().unwrap(), "second");
+    }
+}
@@ -0,0 +1,428 @@
+/*!
+
+# Chained credential stores
+
+Some apps can't be sure which stores will be reachable when they run:
+for example, a desktop app on Linux would rather use the Secret Service,
+but there's no Secret Service without a DBus session, and then it would
+rather use an encrypted file than fail. The [ChainedCredentialBuilder]
+in this module wraps an ordered list of builders, and its credentials
+wrap a credential from each of them:
+
+- Secrets are written to the first store in the chain. If that store
+  can't be reached (that is, it returns a
+  [NoStorageAccess](crate::Error::NoStorageAccess) error), they are
+  written to the next one, and so on.
+- Secrets are read from the first store in the chain that has an entry
+  for the credential, skipping stores that can't be reached. The
+  other metadata operations (such as [set_label](CredentialApi::set_label))
+  act on the same entry.
+- Deleting a credential deletes its entry in every store that can be
+  reached, so it can't be read from a later store after it's deleted.
+
+Any other error ends the operation, so a store that's reachable but locked
+or failing isn't silently skipped. If every store fails, the error from the
+first one is returned.
+
+```
+# use keyring::{Entry, credential::CredentialBuilderApi};
+# use keyring::{chained::ChainedCredentialBuilder, memory::MemoryCredentialBuilder};
+let preferred = MemoryCredentialBuilder::default();
+let backup = MemoryCredentialBuilder::default();
+let builder = ChainedCredentialBuilder::new(vec![
+    Box::new(preferred.clone()),
+    Box::new(backup.clone()),
+]);
+Entry::new_with_credential(backup.build(None, "service", "user").unwrap())
+    .set_password("old")
+    .unwrap();
+let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+assert_eq!(entry.get_password().unwrap(), "old");
+entry.set_password("new").unwrap();
+let preferred = Entry::new_with_credential(preferred.build(None, "service", "user").unwrap());
+assert_eq!(preferred.get_password().unwrap(), "new");
+```
+
+Builders that can't be reached when a credential is built are left out of its
+chain. Searches return the credentials found in every store that can be reached.
+ */
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::time::Duration;
+
+use log::debug;
+
+use super::credential::{
+    AtRestStatus, AttributeMap, Credential, CredentialApi, CredentialBuilder, CredentialBuilderApi,
+    CredentialPersistence, CredentialSpec, CredentialStoreInfo, SearchSpec,
+};
+use super::error::{Error, Result};
+
+/// A credential builder that chains the credentials of several other builders.
+#[derive(Debug)]
+pub struct ChainedCredentialBuilder {
+    builders: Vec<Box<CredentialBuilder>>,
+}
+
+impl ChainedCredentialBuilder {
+    /// Create a builder that chains the given builders, in order of preference.
+    pub fn new(builders: Vec<Box<CredentialBuilder>>) -> Self {
+        Self { builders }
+    }
+
+    /// The chained builders, in order of preference.
+    pub fn builders(&self) -> &[Box<CredentialBuilder>] {
+        &self.builders
+    }
+}
+
+impl CredentialBuilderApi for ChainedCredentialBuilder {
+    /// Build a credential with each of the chained builders, and chain them.
+    ///
+    /// Builders that can't reach their store are left out. Returns the error
+    /// from the first builder if none of them can reach their store.
+    fn build(&self, target: Option<&str>, service: &str, user: &str) -> Result<Box<Credential>> {
+        let mut credentials = Vec::new();
+        let mut first_err = None;
+        for builder in &self.builders {
+            match builder.build(target, service, user) {
+                Ok(cred) => credentials.push(cred),
+                Err(err @ Error::NoStorageAccess(_)) => {
+                    debug!("skipping unreachable builder {builder:?}: {err}");
+                    first_err.get_or_insert(err);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        match first_err {
+            Some(err) if credentials.is_empty() => Err(err),
+            _ => Ok(Box::new(ChainedCredential::new(credentials))),
+        }
+    }
+
+    /// The at-rest encryption of the first chained store.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        match self.builders.first() {
+            Some(builder) => builder.at_rest_encryption(),
+            None => AtRestStatus::Unknown,
+        }
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+
+    /// The persistence of the first chained store.
+    fn persistence(&self) -> CredentialPersistence {
+        match self.builders.first() {
+            Some(builder) => builder.persistence(),
+            None => CredentialPersistence::EntryOnly,
+        }
+    }
+
+    /// Search each of the chained stores that can be reached, in order.
+    fn search(&self, spec: &SearchSpec) -> Result<Vec<Box<Credential>>> {
+        let mut found = Vec::new();
+        let mut searched = false;
+        let mut first_err = None;
+        for builder in &self.builders {
+            match builder.search(spec) {
+                Ok(creds) => {
+                    searched = true;
+                    found.extend(creds);
+                }
+                Err(err @ Error::NoStorageAccess(_)) => {
+                    debug!("skipping unreachable builder {builder:?} in search: {err}");
+                    first_err.get_or_insert(err);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        match first_err {
+            Some(err) if !searched => Err(err),
+            _ => Ok(found),
+        }
+    }
+
+    /// The chained stores can be searched. Credentials are written to
+    /// the first of them, so the rest of the info is that of the first store.
+    fn info(&self) -> CredentialStoreInfo {
+        let info = match self.builders.first() {
+            Some(builder) => builder.info(),
+            None => {
+                CredentialStoreInfo::new("chained", self.persistence(), self.at_rest_encryption())
+            }
+        };
+        info.with_search(true)
+    }
+}
+
+/// A credential that chains credentials from several stores.
+pub struct ChainedCredential {
+    credentials: Vec<Box<Credential>>,
+}
+
+impl ChainedCredential {
+    /// Chain the given credentials, in order of preference.
+    pub fn new(credentials: Vec<Box<Credential>>) -> Self {
+        Self { credentials }
+    }
+
+    /// The chained credentials, in order of preference.
+    pub fn credentials(&self) -> &[Box<Credential>] {
+        &self.credentials
+    }
+
+    /// Do a write on the first credential whose store can be reached.
+    fn write<T>(&self, op: impl Fn(&Credential) -> Result<T>) -> Result<T> {
+        let mut first_err = None;
+        for cred in &self.credentials {
+            match op(cred.as_ref()) {
+                Err(err @ Error::NoStorageAccess(_)) => {
+                    debug!("skipping unreachable credential {cred:?}: {err}");
+                    first_err.get_or_insert(err);
+                }
+                result => return result,
+            }
+        }
+        Err(first_err.unwrap_or(Error::NoEntry))
+    }
+
+    /// Do a read (or update) on the first credential that has an entry,
+    /// skipping those whose store can't be reached.
+    fn read<T>(&self, op: impl Fn(&Credential) -> Result<T>) -> Result<T> {
+        let mut first_err = None;
+        for cred in &self.credentials {
+            match op(cred.as_ref()) {
+                Err(Error::NoEntry) => {}
+                Err(err @ Error::NoStorageAccess(_)) => {
+                    debug!("skipping unreachable credential {cred:?}: {err}");
+                    first_err.get_or_insert(err);
+                }
+                result => return result,
+            }
+        }
+        Err(first_err.unwrap_or(Error::NoEntry))
+    }
+}
+
+impl CredentialApi for ChainedCredential {
+    fn set_password(&self, password: &str) -> Result<()> {
+        self.write(|cred| cred.set_password(password))
+    }
+
+    fn set_secret(&self, secret: &[u8]) -> Result<()> {
+        self.write(|cred| cred.set_secret(secret))
+    }
+
+    fn get_password(&self) -> Result<String> {
+        self.read(|cred| cred.get_password())
+    }
+
+    fn get_secret(&self) -> Result<Vec<u8>> {
+        self.read(|cred| cred.get_secret())
+    }
+
+    /// Update the first chained credential that has an entry, as reads do.
+    fn compare_and_set_password(&self, expected: &str, password: &str) -> Result<()> {
+        self.read(|cred| cred.compare_and_set_password(expected, password))
+    }
+
+    /// Update the first chained credential that has an entry, as reads do.
+    fn compare_and_set_secret(&self, expected: &[u8], secret: &[u8]) -> Result<()> {
+        self.read(|cred| cred.compare_and_set_secret(expected, secret))
+    }
+
+    /// Whether any of the chained stores that can be reached has an entry.
+    fn exists(&self) -> Result<bool> {
+        match self.read(|cred| match cred.exists()? {
+            true => Ok(()),
+            false => Err(Error::NoEntry),
+        }) {
+            Ok(()) => Ok(true),
+            Err(Error::NoEntry) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn get_attributes(&self) -> Result<AttributeMap<String, String>> {
+        self.read(|cred| cred.get_attributes())
+    }
+
+    fn update_attributes(&self, attributes: &AttributeMap<&str, &str>) -> Result<()> {
+        self.read(|cred| cred.update_attributes(attributes))
+    }
+
+    fn get_label(&self) -> Result<String> {
+        self.read(|cred| cred.get_label())
+    }
+
+    fn set_label(&self, label: &str) -> Result<()> {
+        self.read(|cred| cred.set_label(label))
+    }
+
+    fn set_expiration(&self, ttl: Duration) -> Result<()> {
+        self.read(|cred| cred.set_expiration(ttl))
+    }
+
+    fn unlock(&self) -> Result<()> {
+        self.write(|cred| cred.unlock())
+    }
+
+    /// The limit of the first chained credential, which is the one written to.
+    fn max_secret_len(&self) -> Option<usize> {
+        self.credentials
+            .first()
+            .and_then(|cred| cred.max_secret_len())
+    }
+
+    fn spec(&self) -> Option<CredentialSpec> {
+        self.credentials.first().and_then(|cred| cred.spec())
+    }
+
+    /// Delete this credential's entry in every store that can be reached.
+    ///
+    /// Returns a [NoEntry](Error::NoEntry) error if no store had an entry.
+    fn delete_credential(&self) -> Result<()> {
+        let mut deleted = false;
+        let mut first_err = None;
+        for cred in &self.credentials {
+            match cred.delete_credential() {
+                Ok(()) => deleted = true,
+                Err(Error::NoEntry) => {}
+                Err(err @ Error::NoStorageAccess(_)) => {
+                    debug!("skipping unreachable credential {cred:?}: {err}");
+                    first_err.get_or_insert(err);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        match first_err {
+            _ if deleted => Ok(()),
+            Some(err) => Err(err),
+            None => Err(Error::NoEntry),
+        }
+    }
+
+    /// The at-rest encryption of the first chained credential.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        match self.credentials.first() {
+            Some(cred) => cred.at_rest_encryption(),
+            None => AtRestStatus::Unknown,
+        }
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+
+    fn debug_fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ChainedCredential")
+            .field("credentials", &self.credentials)
+            .finish()
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::ChainedCredentialBuilder;
+    use crate::credential::{Credential, CredentialApi, CredentialBuilderApi, SearchSpec};
+    use crate::memory::MemoryCredentialBuilder;
+    use crate::tests::CasOnlyCredentialBuilder;
+    use crate::{Entry, Error, Result};
+
+    /// A store that can never be reached.
+    #[derive(Debug)]
+    struct Unreachable;
+
+    fn unreachable() -> Error {
+        Error::NoStorageAccess(Box::from("unreachable"))
+    }
+
+    impl CredentialApi for Unreachable {
+        fn set_secret(&self, _: &[u8]) -> Result<()> {
+            Err(unreachable())
+        }
+
+        fn get_secret(&self) -> Result<Vec<u8>> {
+            Err(unreachable())
+        }
+
+        fn delete_credential(&self) -> Result<()> {
+            Err(unreachable())
+        }
+
+        fn as_any(&self) -> &dyn std::any::Any {
+            self
+        }
+    }
+
+    impl CredentialBuilderApi for Unreachable {
+        fn build(&self, _: Option<&str>, _: &str, _: &str) -> Result<Box<Credential>> {
+            Ok(Box::new(Unreachable))
+        }
+
+        fn as_any(&self) -> &dyn std::any::Any {
+            self
+        }
+
+        fn search(&self, _: &SearchSpec) -> Result<Vec<Box<Credential>>> {
+            Err(unreachable())
+        }
+    }
+
+    #[test]
+    fn test_fall_back() {
+        let first = MemoryCredentialBuilder::default();
+        let second = MemoryCredentialBuilder::default();
+        let builder = ChainedCredentialBuilder::new(vec![
+            Box::new(Unreachable),
+            Box::new(first.clone()),
+            Box::new(second.clone()),
+        ]);
+        let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+        assert!(matches!(
+            entry.get_password(),
+            Err(Error::NoStorageAccess(_))
+        ));
+        let old = Entry::new_with_credential(second.build(None, "service", "user").unwrap());
+        old.set_password("old").unwrap();
+        assert_eq!(entry.get_password().unwrap(), "old");
+        entry.set_password("new").unwrap();
+        let new = Entry::new_with_credential(first.build(None, "service", "user").unwrap());
+        assert_eq!(new.get_password().unwrap(), "new");
+        assert_eq!(entry.get_password().unwrap(), "new");
+        assert_eq!(builder.search(&SearchSpec::default()).unwrap().len(), 2);
+        entry.delete_credential().unwrap();
+        assert!(matches!(new.get_password(), Err(Error::NoEntry)));
+        assert!(matches!(old.get_password(), Err(Error::NoEntry)));
+        assert!(matches!(
+            entry.delete_credential(),
+            Err(Error::NoStorageAccess(_))
+        ));
+    }
+
+    #[test]
+    fn test_unreachable() {
+        let builder = ChainedCredentialBuilder::new(vec![Box::new(Unreachable)]);
+        let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+        assert!(matches!(
+            entry.set_password("test"),
+            Err(Error::NoStorageAccess(_))
+        ));
+        assert!(matches!(
+            builder.search(&SearchSpec::default()),
+            Err(Error::NoStorageAccess(_))
+        ));
+    }
+
+    #[test]
+    fn test_compare_and_set() {
+        let builder = ChainedCredentialBuilder::new(vec![
+            Box::new(Unreachable),
+            Box::new(CasOnlyCredentialBuilder::default()),
+        ]);
+        crate::tests::test_forwards_compare_and_set(&builder);
+    }
+}
@@ -0,0 +1,562 @@
+/*!
+
+# Chunked secrets
+
+Some stores limit the length of secrets (see
+[max_secret_len](crate::credential::CredentialApi::max_secret_len));
+for example, the Windows Credential Manager can't hold more than 2560
+bytes. The [ChunkedCredentialBuilder] in this module wraps another builder
+so that secrets of any length can be stored: a secret that's too long for
+the wrapped store is split into chunks, each of which is stored in a
+credential of its own, and the chunks are reassembled when the secret
+is read.
+
+```
+# use keyring::{Entry, credential::CredentialBuilderApi};
+# use keyring::{chunked::ChunkedCredentialBuilder, memory::MemoryCredentialBuilder};
+let builder = ChunkedCredentialBuilder::new(Box::new(MemoryCredentialBuilder::default()));
+let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+entry.set_secret(&[7u8; 10_000]).unwrap();
+assert_eq!(entry.get_secret().unwrap().len(), 10_000);
+assert_eq!(entry.max_secret_len(), None);
+```
+
+Secrets that fit in the wrapped store are stored as-is, so existing
+credentials can be read through a chunking builder, and credentials
+written through one can be read without it as long as their secrets fit.
+When a secret doesn't fit, the entry's own credential holds a short
+_manifest_ that gives the number of chunks, the secret's length, and a
+checksum of the secret, and the chunks are kept
+in credentials with the same target and service, whose users are
+the entry's user followed by `#chunk1`, `#chunk2`, and so on. (Those chunk
+credentials are left out of searches through a chunking builder.)
+
+Writing a chunked secret takes several writes to the wrapped store,
+which aren't atomic: if a write fails partway, or two processes write
+the same entry at once, the entry can be left with a mix of old and new
+chunks. The reassembled secret is checked against the manifest's length
+and checksum, so reading such an entry gives an [Invalid](crate::Error::Invalid)
+error (rather than a corrupted secret) until it's written again.
+(The checksum is there to catch accidents, not tampering: anyone who can
+rewrite the chunks can rewrite the manifest too.)
+ */
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use super::credential::{
+    AtRestStatus, AttributeMap, Credential, CredentialApi, CredentialBuilder, CredentialBuilderApi,
+    CredentialPersistence, CredentialSpec, CredentialStoreInfo, SearchSpec,
+};
+use super::error::{Error, Result};
+
+/// The start of the manifest that's stored in place of a chunked secret.
+const MANIFEST_PREFIX: &[u8] = b"keyring-rs chunked secret: ";
+
+/// The user of a chunk credential (numbered from 1) for the given user.
+fn chunk_user(user: &str, index: usize) -> String {
+    format!("{user}#chunk{index}")
+}
+
+/// Whether the given user is that of a chunk credential.
+fn is_chunk_user(user: &str) -> bool {
+    user.rsplit_once("#chunk")
+        .is_some_and(|(_, index)| !index.is_empty() && index.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// What a manifest says about the chunked secret it stands for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Manifest {
+    /// The number of chunks
+    count: usize,
+    /// The length of the whole secret
+    len: usize,
+    /// The [checksum] of the whole secret
+    checksum: u64,
+}
+
+impl Manifest {
+    fn new(count: usize, secret: &[u8]) -> Self {
+        Self {
+            count,
+            len: secret.len(),
+            checksum: checksum(secret),
+        }
+    }
+
+    fn to_bytes(self) -> Vec<u8> {
+        let fields = format!("{} {} {:016x}", self.count, self.len, self.checksum);
+        [MANIFEST_PREFIX, fields.as_bytes()].concat()
+    }
+}
+
+/// The manifest a secret holds, if it's one.
+fn parse_manifest(secret: &[u8]) -> Option<Result<Manifest>> {
+    let fields = secret.strip_prefix(MANIFEST_PREFIX)?;
+    let manifest = core::str::from_utf8(fields).ok().and_then(|fields| {
+        let mut fields = fields.split(' ');
+        let manifest = Manifest {
+            count: fields.next()?.parse().ok()?,
+            len: fields.next()?.parse().ok()?,
+            checksum: u64::from_str_radix(fields.next()?, 16).ok()?,
+        };
+        fields.next().is_none().then_some(manifest)
+    });
+    Some(manifest.ok_or_else(|| invalid("has a malformed chunk manifest")))
+}
+
+/// The 64-bit FNV-1a hash of a secret, which a manifest keeps so that
+/// a secret reassembled from chunks of different writes can be noticed.
+fn checksum(secret: &[u8]) -> u64 {
+    secret.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+fn invalid(reason: &str) -> Error {
+    Error::Invalid("chunked secret".to_string(), reason.to_string())
+}
+
+/// A credential builder whose credentials split long secrets into
+/// chunks that fit in the store of another builder.
+pub struct ChunkedCredentialBuilder {
+    inner: Arc<CredentialBuilder>,
+}
+
+impl ChunkedCredentialBuilder {
+    /// Create a builder that chunks the secrets of the credentials built by `inner`.
+    pub fn new(inner: Box<CredentialBuilder>) -> Self {
+        Self {
+            inner: Arc::from(inner),
+        }
+    }
+
+    /// The wrapped builder.
+    pub fn inner(&self) -> &CredentialBuilder {
+        self.inner.as_ref()
+    }
+}
+
+impl core::fmt::Debug for ChunkedCredentialBuilder {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ChunkedCredentialBuilder")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+impl CredentialBuilderApi for ChunkedCredentialBuilder {
+    /// Build a credential in the wrapped store, and chunk its secrets.
+    fn build(&self, target: Option<&str>, service: &str, user: &str) -> Result<Box<Credential>> {
+        Ok(Box::new(ChunkedCredential {
+            base: self.inner.build(target, service, user)?,
+            spec: CredentialSpec {
+                target: target.map(|t| t.to_string()),
+                service: service.to_string(),
+                user: user.to_string(),
+            },
+            builder: self.inner.clone(),
+        }))
+    }
+
+    /// The at-rest encryption of the wrapped store.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        self.inner.at_rest_encryption()
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+
+    /// The persistence of the wrapped store.
+    fn persistence(&self) -> CredentialPersistence {
+        self.inner.persistence()
+    }
+
+    /// Search the wrapped store, leaving out chunk credentials
+    /// (and credentials whose identity isn't known).
+    fn search(&self, spec: &SearchSpec) -> Result<Vec<Box<Credential>>> {
+        let found = self.inner.search(spec)?;
+        Ok(found
+            .into_iter()
+            .filter_map(|base| {
+                let spec = base.spec()?;
+                if is_chunk_user(&spec.user) {
+                    return None;
+                }
+                Some(Box::new(ChunkedCredential {
+                    base,
+                    spec,
+                    builder: self.inner.clone(),
+                }) as Box<Credential>)
+            })
+            .collect())
+    }
+
+    /// The info of the wrapped store, which (chunked) has no limit on secret length.
+    fn info(&self) -> CredentialStoreInfo {
+        self.inner.info().with_max_secret_len(None)
+    }
+}
+
+/// A credential whose secret may be split into chunks.
+pub struct ChunkedCredential {
+    base: Box<Credential>,
+    spec: CredentialSpec,
+    builder: Arc<CredentialBuilder>,
+}
+
+impl ChunkedCredential {
+    /// The credential that holds the secret (or its manifest).
+    pub fn inner(&self) -> &Credential {
+        self.base.as_ref()
+    }
+
+    /// The credential that holds the chunk with the given index (from 1).
+    fn chunk(&self, index: usize) -> Result<Box<Credential>> {
+        let spec = &self.spec;
+        self.builder.build(
+            spec.target.as_deref(),
+            &spec.service,
+            &chunk_user(&spec.user, index),
+        )
+    }
+
+    /// Whether the secret can be stored as-is, without chunking it.
+    fn fits(&self, secret: &[u8]) -> bool {
+        let fits = self
+            .base
+            .max_secret_len()
+            .is_none_or(|max| secret.len() <= max);
+        fits && !secret.starts_with(MANIFEST_PREFIX)
+    }
+
+    /// The number of chunks the stored secret has, if it's chunked.
+    fn stored_chunks(&self) -> Result<usize> {
+        match self.base.get_secret() {
+            Ok(secret) => match parse_manifest(&secret) {
+                Some(manifest) => Ok(manifest?.count),
+                None => Ok(0),
+            },
+            Err(Error::NoEntry) => Ok(0),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Delete the chunks with indexes from `first` through `last`.
+    fn delete_chunks(&self, first: usize, last: usize) -> Result<()> {
+        for index in first..=last {
+            match self.chunk(index)?.delete_credential() {
+                Ok(()) | Err(Error::NoEntry) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl CredentialApi for ChunkedCredential {
+    fn set_password(&self, password: &str) -> Result<()> {
+        self.set_secret(password.as_bytes())
+    }
+
+    /// Store the secret, splitting it into chunks if it's too long
+    /// for the wrapped store (or if it looks like a manifest).
+    fn set_secret(&self, secret: &[u8]) -> Result<()> {
+        let old_chunks = self.stored_chunks()?;
+        if self.fits(secret) {
+            self.base.set_secret(secret)?;
+            return self.delete_chunks(1, old_chunks);
+        }
+        let chunk_len = self.base.max_secret_len().unwrap_or(secret.len()).max(1);
+        let chunks: Vec<&[u8]> = secret.chunks(chunk_len).collect();
+        for (index, chunk) in chunks.iter().enumerate() {
+            self.chunk(index + 1)?.set_secret(chunk)?;
+        }
+        let manifest = Manifest::new(chunks.len(), secret).to_bytes();
+        self.base.set_secret(&manifest)?;
+        self.delete_chunks(chunks.len() + 1, old_chunks)
+    }
+
+    /// Read the secret, reassembling it from its chunks if it's chunked.
+    ///
+    /// A reassembled secret whose length or checksum isn't the one in
+    /// its manifest gives an [Invalid](Error::Invalid) error.
+    fn get_secret(&self) -> Result<Vec<u8>> {
+        let stored = self.base.get_secret()?;
+        let manifest = match parse_manifest(&stored) {
+            None => return Ok(stored),
+            Some(manifest) => manifest?,
+        };
+        let mismatch = || invalid("has chunks that don't match its manifest");
+        // allocated once, so no copies of the secret are left behind by growing it
+        let mut secret = zeroize::Zeroizing::new(Vec::with_capacity(manifest.len));
+        for index in 1..=manifest.count {
+            match self.chunk(index)?.get_secret() {
+                Ok(chunk) => {
+                    let chunk = zeroize::Zeroizing::new(chunk);
+                    if chunk.len() > manifest.len - secret.len() {
+                        return Err(mismatch());
+                    }
+                    secret.extend_from_slice(&chunk);
+                }
+                Err(Error::NoEntry) => return Err(invalid("is missing a chunk")),
+                Err(err) => return Err(err),
+            }
+        }
+        if secret.len() != manifest.len || checksum(&secret) != manifest.checksum {
+            return Err(mismatch());
+        }
+        Ok(core::mem::take(&mut *secret))
+    }
+
+    fn compare_and_set_password(&self, expected: &str, password: &str) -> Result<()> {
+        self.compare_and_set_secret(expected.as_bytes(), password.as_bytes())
+    }
+
+    /// Store the secret, but only if the current one is `expected`.
+    ///
+    /// If neither secret needs chunking, this is the wrapped credential's
+    /// [compare_and_set_secret](CredentialApi::compare_and_set_secret) (a stored
+    /// manifest never matches a secret that doesn't need chunking), so it's
+    /// as atomic as that is. Otherwise the secret is read and compared before
+    /// the new one is written, and another write in between can be lost.
+    fn compare_and_set_secret(&self, expected: &[u8], secret: &[u8]) -> Result<()> {
+        if self.fits(expected) && self.fits(secret) {
+            return self.base.compare_and_set_secret(expected, secret);
+        }
+        if *zeroize::Zeroizing::new(self.get_secret()?) != expected {
+            return Err(Error::Conflict);
+        }
+        self.set_secret(secret)
+    }
+
+    fn exists(&self) -> Result<bool> {
+        self.base.exists()
+    }
+
+    fn get_attributes(&self) -> Result<AttributeMap<String, String>> {
+        self.base.get_attributes()
+    }
+
+    fn update_attributes(&self, attributes: &AttributeMap<&str, &str>) -> Result<()> {
+        self.base.update_attributes(attributes)
+    }
+
+    fn get_label(&self) -> Result<String> {
+        self.base.get_label()
+    }
+
+    fn set_label(&self, label: &str) -> Result<()> {
+        self.base.set_label(label)
+    }
+
+    fn unlock(&self) -> Result<()> {
+        self.base.unlock()
+    }
+
+    /// Secrets are chunked as needed, so there's no limit.
+    fn max_secret_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn spec(&self) -> Option<CredentialSpec> {
+        Some(self.spec.clone())
+    }
+
+    /// Delete the credential and its chunks (if any).
+    fn delete_credential(&self) -> Result<()> {
+        let chunks = self.stored_chunks()?;
+        self.base.delete_credential()?;
+        self.delete_chunks(1, chunks)
+    }
+
+    /// The at-rest encryption of the wrapped store.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        self.base.at_rest_encryption()
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+
+    fn debug_fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ChunkedCredential")
+            .field("base", &self.base)
+            .field("spec", &self.spec)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::{ChunkedCredentialBuilder, Manifest, is_chunk_user, parse_manifest};
+    use crate::credential::{
+        Credential, CredentialApi, CredentialBuilder, CredentialBuilderApi, SearchSpec,
+    };
+    use crate::memory::MemoryCredentialBuilder;
+    use crate::tests::CasOnlyCredentialBuilder;
+    use crate::{Entry, Error, Result};
+
+    /// A store whose secrets can be at most 64 bytes long.
+    #[derive(Debug)]
+    struct Limited(Box<Credential>);
+
+    impl CredentialApi for Limited {
+        fn set_secret(&self, secret: &[u8]) -> Result<()> {
+            if secret.len() > 64 {
+                return Err(Error::TooLong("secret".to_string(), 64));
+            }
+            self.0.set_secret(secret)
+        }
+
+        fn get_secret(&self) -> Result<Vec<u8>> {
+            self.0.get_secret()
+        }
+
+        fn max_secret_len(&self) -> Option<usize> {
+            Some(64)
+        }
+
+        fn spec(&self) -> Option<crate::credential::CredentialSpec> {
+            self.0.spec()
+        }
+
+        fn delete_credential(&self) -> Result<()> {
+            self.0.delete_credential()
+        }
+
+        fn as_any(&self) -> &dyn std::any::Any {
+            self
+        }
+    }
+
+    #[derive(Debug)]
+    struct LimitedBuilder(MemoryCredentialBuilder);
+
+    impl CredentialBuilderApi for LimitedBuilder {
+        fn build(
+            &self,
+            target: Option<&str>,
+            service: &str,
+            user: &str,
+        ) -> Result<Box<Credential>> {
+            Ok(Box::new(Limited(self.0.build(target, service, user)?)))
+        }
+
+        fn as_any(&self) -> &dyn std::any::Any {
+            self
+        }
+
+        fn search(&self, spec: &SearchSpec) -> Result<Vec<Box<Credential>>> {
+            let found = self.0.search(spec)?;
+            Ok(found
+                .into_iter()
+                .map(|cred| Box::new(Limited(cred)) as Box<Credential>)
+                .collect())
+        }
+    }
+
+    #[test]
+    fn test_chunk_user() {
+        assert!(is_chunk_user("alice#chunk12"));
+        assert!(!is_chunk_user("alice#chunk"));
+        assert!(!is_chunk_user("alice#chunky"));
+        assert!(!is_chunk_user("alice"));
+    }
+
+    #[test]
+    fn test_chunked() {
+        let store = MemoryCredentialBuilder::default();
+        let inner: Box<CredentialBuilder> = Box::new(LimitedBuilder(store.clone()));
+        let builder = ChunkedCredentialBuilder::new(inner);
+        let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+        assert!(matches!(entry.get_secret(), Err(Error::NoEntry)));
+        // short secrets are stored as-is
+        entry.set_password("short").unwrap();
+        let raw = store.build(None, "service", "user").unwrap();
+        assert_eq!(raw.get_password().unwrap(), "short");
+        // long secrets are chunked, and shrinking them removes chunks
+        entry.set_secret(&[1u8; 160]).unwrap();
+        assert_eq!(entry.get_secret().unwrap(), [1u8; 160]);
+        assert_eq!(store.search(&SearchSpec::default()).unwrap().len(), 4);
+        entry.set_secret(&[2u8; 100]).unwrap();
+        assert_eq!(entry.get_secret().unwrap(), [2u8; 100]);
+        assert_eq!(store.search(&SearchSpec::default()).unwrap().len(), 3);
+        let found = builder.search(&SearchSpec::default()).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].get_secret().unwrap(), [2u8; 100]);
+        // a missing chunk is detected
+        let chunk = store.build(None, "service", "user#chunk2").unwrap();
+        chunk.delete_credential().unwrap();
+        assert!(matches!(entry.get_secret(), Err(Error::Invalid(_, _))));
+        entry.set_password("short").unwrap();
+        assert_eq!(store.search(&SearchSpec::default()).unwrap().len(), 1);
+        entry.set_secret(&[3u8; 200]).unwrap();
+        entry.delete_credential().unwrap();
+        assert!(store.search(&SearchSpec::default()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_manifest() {
+        let manifest = Manifest::new(3, b"secret");
+        assert_eq!(
+            parse_manifest(&manifest.to_bytes()).unwrap().unwrap(),
+            manifest
+        );
+        assert_ne!(Manifest::new(3, b"secreT").checksum, manifest.checksum);
+        assert!(parse_manifest(b"secret").is_none());
+        for malformed in ["3", "3 6", "3 6 x", "3 6 0 0"] {
+            let secret = [super::MANIFEST_PREFIX, malformed.as_bytes()].concat();
+            assert!(matches!(
+                parse_manifest(&secret),
+                Some(Err(Error::Invalid(_, _)))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_mixed_chunks() {
+        let store = MemoryCredentialBuilder::default();
+        let inner: Box<CredentialBuilder> = Box::new(LimitedBuilder(store.clone()));
+        let builder = ChunkedCredentialBuilder::new(inner);
+        let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+        let chunk = store.build(None, "service", "user#chunk2").unwrap();
+        entry.set_secret(&[1u8; 160]).unwrap();
+        let old_chunk = chunk.get_secret().unwrap();
+        // a chunk left over from an earlier write is detected
+        entry.set_secret(&[2u8; 160]).unwrap();
+        chunk.set_secret(&old_chunk).unwrap();
+        assert!(matches!(entry.get_secret(), Err(Error::Invalid(_, _))));
+        // as is one of the wrong length
+        chunk.set_secret(&[2u8; 10]).unwrap();
+        assert!(matches!(entry.get_secret(), Err(Error::Invalid(_, _))));
+        entry.set_secret(&[2u8; 160]).unwrap();
+        assert_eq!(entry.get_secret().unwrap(), [2u8; 160]);
+    }
+
+    #[test]
+    fn test_compare_and_set() {
+        let builder = ChunkedCredentialBuilder::new(Box::new(CasOnlyCredentialBuilder::default()));
+        crate::tests::test_forwards_compare_and_set(&builder);
+        // chunked secrets are compared after reassembling them
+        let store = MemoryCredentialBuilder::default();
+        let builder = ChunkedCredentialBuilder::new(Box::new(LimitedBuilder(store)));
+        let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+        entry.set_secret(&[1u8; 160]).unwrap();
+        assert!(matches!(
+            entry.update_secret(&[2u8; 160], b"short"),
+            Err(Error::Conflict)
+        ));
+        entry.update_secret(&[1u8; 160], b"short").unwrap();
+        assert_eq!(entry.get_secret().unwrap(), b"short");
+        assert!(matches!(
+            entry.update_secret(&[1u8; 160], &[3u8; 100]),
+            Err(Error::Conflict)
+        ));
+        entry.update_secret(b"short", &[3u8; 100]).unwrap();
+        assert_eq!(entry.get_secret().unwrap(), [3u8; 100]);
+    }
+}
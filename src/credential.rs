@@ -8,11 +8,27 @@ and [CredentialApi] for the entries in the store.  These traits must be implemen
 in a thread-safe way, a requirement captured in the [CredentialBuilder] and
 [Credential] types that wrap them.
  */
-use std::any::Any;
-use std::collections::HashMap;
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::any::Any;
+use core::time::Duration;
 
 use super::Result;
 
+/// The map type used for credential attributes.
+///
+/// This is a `HashMap` when the `std` feature is enabled (as it is by default),
+/// and a `BTreeMap` otherwise.
+#[cfg(feature = "std")]
+pub type AttributeMap<K, V> = std::collections::HashMap<K, V>;
+/// The map type used for credential attributes.
+///
+/// This is a `HashMap` when the `std` feature is enabled (as it is by default),
+/// and a `BTreeMap` otherwise.
+#[cfg(not(feature = "std"))]
+pub type AttributeMap<K, V> = alloc::collections::BTreeMap<K, V>;
+
 /// The API that [credentials](Credential) implement.
 pub trait CredentialApi {
     /// Set the credential's password (a string).
@@ -42,6 +58,84 @@ pub trait CredentialApi {
     /// for this entry, a [NoEntry](crate::Error::NoEntry) error is returned.
     fn get_secret(&self) -> Result<Vec<u8>>;
 
+    /// Whether there is an underlying credential in the store.
+    ///
+    /// Stores should check this without reading (or decrypting) the secret,
+    /// and without prompting the user where they can. We provide a default
+    /// implementation, which reads the secret, for stores that can't.
+    fn exists(&self) -> Result<bool> {
+        match self.get_secret() {
+            Ok(secret) => {
+                zeroize::Zeroizing::new(secret);
+                Ok(true)
+            }
+            Err(super::Error::NoEntry) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Retrieve the credential's password, or set it to the given
+    /// password (and return that) if there is no credential.
+    ///
+    /// Stores that can should do this atomically, so that when several
+    /// processes race to create the credential they all get the same password.
+    /// We provide a default implementation, which sets the password
+    /// and then reads it back, for stores that can't.
+    fn get_password_or_set(&self, password: &str) -> Result<String> {
+        match self.get_password() {
+            Err(super::Error::NoEntry) => {
+                self.set_password(password)?;
+                self.get_password()
+            }
+            result => result,
+        }
+    }
+
+    /// Retrieve the credential's secret, or set it to the given
+    /// secret (and return that) if there is no credential.
+    ///
+    /// Stores that can should do this atomically, as with
+    /// [get_password_or_set](CredentialApi::get_password_or_set).
+    /// We provide a default implementation, which sets the secret
+    /// and then reads it back, for stores that can't.
+    fn get_secret_or_set(&self, secret: &[u8]) -> Result<Vec<u8>> {
+        match self.get_secret() {
+            Err(super::Error::NoEntry) => {
+                self.set_secret(secret)?;
+                self.get_secret()
+            }
+            result => result,
+        }
+    }
+
+    /// Set the credential's password, but only if its current password
+    /// is `expected`; otherwise, return a [Conflict](crate::Error::Conflict) error.
+    ///
+    /// Stores that can should do this atomically, so that when several
+    /// processes race to update the credential only one of them succeeds.
+    /// We provide a default implementation, which reads the password and then
+    /// sets it, for stores that can't.
+    fn compare_and_set_password(&self, expected: &str, password: &str) -> Result<()> {
+        if zeroize::Zeroizing::new(self.get_password()?).as_str() != expected {
+            return Err(super::Error::Conflict);
+        }
+        self.set_password(password)
+    }
+
+    /// Set the credential's secret, but only if its current secret
+    /// is `expected`; otherwise, return a [Conflict](crate::Error::Conflict) error.
+    ///
+    /// Stores that can should do this atomically, as with
+    /// [compare_and_set_password](CredentialApi::compare_and_set_password).
+    /// We provide a default implementation, which reads the secret and then
+    /// sets it, for stores that can't.
+    fn compare_and_set_secret(&self, expected: &[u8], secret: &[u8]) -> Result<()> {
+        if *zeroize::Zeroizing::new(self.get_secret()?) != expected {
+            return Err(super::Error::Conflict);
+        }
+        self.set_secret(secret)
+    }
+
     /// Get the secure store attributes on this entry's credential.
     ///
     /// Each credential store may support reading and updating different
@@ -52,11 +146,11 @@ pub trait CredentialApi {
     ///
     /// We provide a default (no-op) implementation of this method
     /// for backward compatibility with stores that don't implement it.
-    fn get_attributes(&self) -> Result<HashMap<String, String>> {
+    fn get_attributes(&self) -> Result<AttributeMap<String, String>> {
         // this should err in the same cases as get_secret, so first call that for effect
-        self.get_secret()?;
+        zeroize::Zeroizing::new(self.get_secret()?);
         // if we got this far, return success with no attributes
-        Ok(HashMap::new())
+        Ok(AttributeMap::new())
     }
 
     /// Update the secure store attributes on this entry's credential.
@@ -70,13 +164,100 @@ pub trait CredentialApi {
     ///
     /// We provide a default no-op implementation of this method
     /// for backward compatibility with stores that don't implement it.
-    fn update_attributes(&self, _: &HashMap<&str, &str>) -> Result<()> {
+    fn update_attributes(&self, _: &AttributeMap<&str, &str>) -> Result<()> {
         // this should err in the same cases as get_secret, so first call that for effect
-        self.get_secret()?;
+        zeroize::Zeroizing::new(self.get_secret()?);
         // if we got this far, return success after setting no attributes
         Ok(())
     }
 
+    /// Get the timestamps that the underlying store keeps for this credential.
+    ///
+    /// We provide a default implementation (which returns no timestamps,
+    /// after checking that the credential exists) for backward compatibility
+    /// with stores that don't implement it.
+    fn get_metadata(&self) -> Result<CredentialMetadata> {
+        // this should err in the same cases as get_secret, so first call that for effect
+        zeroize::Zeroizing::new(self.get_secret()?);
+        Ok(CredentialMetadata::default())
+    }
+
+    /// Get the human-readable label of this entry's credential.
+    ///
+    /// Stores keep the label in whichever of their attributes is shown
+    /// to users as the credential's description; see the documentation
+    /// on each of the stores for details.
+    ///
+    /// We provide a default implementation (which returns a
+    /// [NotSupportedByStore](crate::Error::NotSupportedByStore) error)
+    /// for backward compatibility with stores that don't implement it.
+    fn get_label(&self) -> Result<String> {
+        Err(super::Error::NotSupportedByStore("labels".to_string()))
+    }
+
+    /// Set the human-readable label of this entry's credential.
+    ///
+    /// See [get_label](CredentialApi::get_label) for where the label is kept.
+    ///
+    /// We provide a default implementation (which returns a
+    /// [NotSupportedByStore](crate::Error::NotSupportedByStore) error)
+    /// for backward compatibility with stores that don't implement it.
+    fn set_label(&self, _: &str) -> Result<()> {
+        Err(super::Error::NotSupportedByStore("labels".to_string()))
+    }
+
+    /// Make the underlying credential expire (and be removed by the store)
+    /// after the given time has passed.
+    ///
+    /// We provide a default implementation (which returns a
+    /// [NotSupportedByStore](crate::Error::NotSupportedByStore) error)
+    /// for backward compatibility with stores that don't implement it.
+    fn set_expiration(&self, _: Duration) -> Result<()> {
+        Err(super::Error::NotSupportedByStore("expiration".to_string()))
+    }
+
+    /// Unlock the part of the underlying store that holds this credential,
+    /// prompting the user if necessary.
+    ///
+    /// We provide a default implementation (which returns a
+    /// [NotSupportedByStore](crate::Error::NotSupportedByStore) error)
+    /// for backward compatibility with stores that don't implement it.
+    fn unlock(&self) -> Result<()> {
+        Err(super::Error::NotSupportedByStore("unlocking".to_string()))
+    }
+
+    /// The maximum length (in bytes) of a secret that can be stored
+    /// in this credential, if the underlying store has a limit.
+    ///
+    /// We provide a default implementation (no limit) for backward
+    /// compatibility with stores that don't implement it.
+    fn max_secret_len(&self) -> Option<usize> {
+        None
+    }
+
+    /// The target, service, and user that identify this credential, if known.
+    ///
+    /// For credentials made by a builder, this is what the credential was
+    /// built from (as stored in the underlying store, so it may differ
+    /// in details such as the default target). For credentials that wrap
+    /// third-party items, it's whatever identity can be read from the item.
+    ///
+    /// We provide a default implementation (which returns `None`, meaning
+    /// the identity is unknown) for backward compatibility with stores
+    /// that don't implement it.
+    fn spec(&self) -> Option<CredentialSpec> {
+        None
+    }
+
+    /// Whether the underlying store encrypts this credential's secret at rest.
+    ///
+    /// We provide a default implementation (which returns
+    /// [Unknown](AtRestStatus::Unknown)) for backward compatibility
+    /// with stores that don't implement it.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        AtRestStatus::Unknown
+    }
+
     /// Delete the underlying credential, if there is one.
     ///
     /// This is not idempotent if the credential existed!
@@ -101,22 +282,23 @@ pub trait CredentialApi {
     /// We provide a (useless) default implementation for backward
     /// compatibility with existing implementors who may have not
     /// implemented the Debug trait for their credential objects
-    fn debug_fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        std::fmt::Debug::fmt(self.as_any(), f)
+    fn debug_fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Debug::fmt(self.as_any(), f)
     }
 }
 
 /// A thread-safe implementation of the [Credential API](CredentialApi).
 pub type Credential = dyn CredentialApi + Send + Sync;
 
-impl std::fmt::Debug for Credential {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Debug for Credential {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         self.debug_fmt(f)
     }
 }
 
 /// A descriptor for the lifetime of stored credentials, returned from
 /// a credential store's [persistence](CredentialBuilderApi::persistence) call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[non_exhaustive]
 pub enum CredentialPersistence {
     /// Credentials vanish when the entry vanishes (stored in the entry)
@@ -125,10 +307,121 @@ pub enum CredentialPersistence {
     ProcessOnly,
     /// Credentials persist until the machine reboots (stored in kernel memory)
     UntilReboot,
+    /// Credentials persist until the user logs out (stored with the login session)
+    UntilLogout,
     /// Credentials persist until they are explicitly deleted (stored on disk)
     UntilDelete,
 }
 
+/// Whether a credential store encrypts secrets at rest, as returned from
+/// a credential store's [at_rest_encryption](CredentialBuilderApi::at_rest_encryption) call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AtRestStatus {
+    /// Secrets are stored encrypted
+    Encrypted,
+    /// Secrets are stored unencrypted (whether in memory or on disk)
+    Unencrypted,
+    /// Whether secrets are encrypted can't be determined
+    Unknown,
+}
+
+/// What a credential store is and what it can do, as returned from
+/// a credential store's [info](CredentialBuilderApi::info) call.
+///
+/// Apps can use this to choose their behavior at run time: for example,
+/// to warn the user when secrets won't outlive the session, or to hide
+/// a "remember me" option when there's no persistent store.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct CredentialStoreInfo {
+    /// The store's name, such as `"windows"` or `"secret-service"`
+    /// (the name it's given in [credential_builder_from_str](crate::credential_builder_from_str)
+    /// for the stores provided by this crate)
+    pub name: &'static str,
+    /// The version of the store (or of its storage format), if known
+    pub version: Option<String>,
+    /// The lifetime of the store's credentials
+    pub persistence: CredentialPersistence,
+    /// Whether the store encrypts secrets at rest
+    pub at_rest_encryption: AtRestStatus,
+    /// Whether the store's credentials have attributes that can be updated
+    pub supports_attributes: bool,
+    /// Whether the store can hold secrets that aren't UTF-8 strings
+    pub supports_binary_secrets: bool,
+    /// Whether the store can be [searched](CredentialBuilderApi::search)
+    pub supports_search: bool,
+    /// The length limit (in bytes) on the store's secrets, if there is one
+    pub max_secret_len: Option<usize>,
+}
+
+impl CredentialStoreInfo {
+    /// Describe a store that holds binary secrets of any length,
+    /// but whose credentials have no attributes and which can't be searched.
+    ///
+    /// Use the `with_` methods to describe other capabilities.
+    pub fn new(
+        name: &'static str,
+        persistence: CredentialPersistence,
+        at_rest_encryption: AtRestStatus,
+    ) -> Self {
+        Self {
+            name,
+            version: None,
+            persistence,
+            at_rest_encryption,
+            supports_attributes: false,
+            supports_binary_secrets: true,
+            supports_search: false,
+            max_secret_len: None,
+        }
+    }
+
+    /// Set the store's version.
+    pub fn with_version(mut self, version: &str) -> Self {
+        self.version = Some(version.to_string());
+        self
+    }
+
+    /// Set whether the store's credentials have updatable attributes.
+    pub fn with_attributes(mut self, supported: bool) -> Self {
+        self.supports_attributes = supported;
+        self
+    }
+
+    /// Set whether the store can hold secrets that aren't UTF-8 strings.
+    pub fn with_binary_secrets(mut self, supported: bool) -> Self {
+        self.supports_binary_secrets = supported;
+        self
+    }
+
+    /// Set whether the store can be searched.
+    pub fn with_search(mut self, supported: bool) -> Self {
+        self.supports_search = supported;
+        self
+    }
+
+    /// Set the length limit on the store's secrets.
+    pub fn with_max_secret_len(mut self, max: Option<usize>) -> Self {
+        self.max_secret_len = max;
+        self
+    }
+}
+
+/// How a credential should behave when more than one item in its store
+/// matches it.
+///
+/// Only stores that can hold duplicate items (such as the secret service)
+/// can be ambiguous, so only their builders offer this option.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AmbiguityMode {
+    /// Return an [Ambiguous](crate::Error::Ambiguous) error
+    #[default]
+    Strict,
+    /// Use the first matching item, in an order that is stable across calls
+    FirstMatch,
+}
+
 /// The API that [credential builders](CredentialBuilder) implement.
 pub trait CredentialBuilderApi {
     /// Create a credential identified by the given target, service, and user.
@@ -152,10 +445,126 @@ pub trait CredentialBuilderApi {
     fn persistence(&self) -> CredentialPersistence {
         CredentialPersistence::UntilDelete
     }
+
+    /// Whether this builder's store encrypts secrets at rest.
+    ///
+    /// This is what's known about the store in general; for stores
+    /// where it varies by credential, the credential's
+    /// [at_rest_encryption](CredentialApi::at_rest_encryption) may know more.
+    ///
+    /// A default implementation (which returns [Unknown](AtRestStatus::Unknown))
+    /// is provided for backward compatibility with stores that don't implement it.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        AtRestStatus::Unknown
+    }
+
+    /// Describe this builder's store and its capabilities.
+    ///
+    /// A default implementation is provided for backward compatibility.
+    /// It names the store `"unknown"`, takes its persistence and at-rest
+    /// encryption from this builder, and otherwise describes the store
+    /// as [CredentialStoreInfo::new] does.
+    fn info(&self) -> CredentialStoreInfo {
+        CredentialStoreInfo::new("unknown", self.persistence(), self.at_rest_encryption())
+    }
+
+    /// Find the existing credentials in the store that match a search spec.
+    ///
+    /// Only stores that can enumerate their contents can implement this;
+    /// the returned credentials are those whose target, service, and user
+    /// are all matched by the spec.
+    ///
+    /// Unless the spec is [unsorted](SearchSpec::unsorted), implementations
+    /// must return the credentials sorted by service, then user, then target
+    /// (with credentials that have no target first), so that results are
+    /// in the same order on every platform.
+    ///
+    /// Implementations must not read the secrets of the credentials they find
+    /// (on some platforms, reading a secret can prompt the user to unlock it).
+    ///
+    /// A default implementation is provided for stores that can't enumerate
+    /// their credentials. It returns a
+    /// [NotSupportedByStore](crate::Error::NotSupportedByStore) error.
+    fn search(&self, _: &SearchSpec) -> Result<Vec<Box<Credential>>> {
+        Err(super::Error::NotSupportedByStore("search".to_string()))
+    }
+
+    /// Read the secrets of the credentials with the given specs, in order.
+    ///
+    /// Each spec gets its own result, so one missing credential doesn't
+    /// keep the others from being read. Stores that have a per-call setup
+    /// cost (such as connecting to a service) should share it across the batch.
+    /// A default implementation, which builds and reads each credential
+    /// in turn, is provided for stores that don't.
+    fn get_many(&self, specs: &[CredentialSpec]) -> Vec<Result<Vec<u8>>> {
+        specs
+            .iter()
+            .map(|spec| {
+                self.build(spec.target.as_deref(), &spec.service, &spec.user)
+                    .and_then(|cred| cred.get_secret())
+            })
+            .collect()
+    }
+
+    /// Set the secrets of the credentials with the given specs, in order.
+    ///
+    /// As with [get_many](CredentialBuilderApi::get_many), each item gets its
+    /// own result, and a default implementation is provided.
+    fn set_many(&self, items: &[(CredentialSpec, &[u8])]) -> Vec<Result<()>> {
+        items
+            .iter()
+            .map(|(spec, secret)| {
+                self.build(spec.target.as_deref(), &spec.service, &spec.user)
+                    .and_then(|cred| cred.set_secret(secret))
+            })
+            .collect()
+    }
+
+    /// Delete the credentials with the given specs, in order.
+    ///
+    /// As with [get_many](CredentialBuilderApi::get_many), each spec gets its
+    /// own result, and a default implementation is provided.
+    fn delete_many(&self, specs: &[CredentialSpec]) -> Vec<Result<()>> {
+        specs
+            .iter()
+            .map(|spec| {
+                self.build(spec.target.as_deref(), &spec.service, &spec.user)
+                    .and_then(|cred| cred.delete_credential())
+            })
+            .collect()
+    }
+}
+
+/// Which credentials a [transfer](crate::transfer) copies or moves.
+#[derive(Debug, Clone, Copy)]
+pub enum Selection<'a> {
+    /// The credentials found by a search of the source store
+    Search(&'a SearchSpec),
+    /// The credentials with the given specs
+    Specs(&'a [CredentialSpec]),
+}
+
+/// Whether a [transfer](crate::transfer) leaves the source credentials in place.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TransferMode {
+    /// Leave the source credentials in place
+    #[default]
+    Copy,
+    /// Delete each source credential once it has been copied
+    Move,
+}
+
+/// The outcome of a [transfer](crate::transfer).
+#[derive(Debug, Default)]
+pub struct TransferReport {
+    /// The credentials that were copied or moved, in order
+    pub transferred: Vec<CredentialSpec>,
+    /// The credentials that couldn't be copied or moved, with the reason
+    pub failed: Vec<(CredentialSpec, crate::Error)>,
 }
 
-impl std::fmt::Debug for CredentialBuilder {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Debug for CredentialBuilder {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         self.as_any().fmt(f)
     }
 }
@@ -163,6 +572,124 @@ impl std::fmt::Debug for CredentialBuilder {
 /// A thread-safe implementation of the [CredentialBuilder API](CredentialBuilderApi).
 pub type CredentialBuilder = dyn CredentialBuilderApi + Send + Sync;
 
+/// The identifying information that an entry's credential was built from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CredentialSpec {
+    pub target: Option<String>,
+    pub service: String,
+    pub user: String,
+}
+
+/// The timestamps that a store keeps for a credential, as returned from
+/// a credential's [get_metadata](CredentialApi::get_metadata) call.
+///
+/// Each time is the duration since the Unix epoch (so it can be turned into
+/// a `SystemTime` by adding it to `UNIX_EPOCH`). Stores only fill in the
+/// times they keep, so all of them may be `None`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct CredentialMetadata {
+    /// When the credential was created
+    pub created: Option<Duration>,
+    /// When the credential (or its secret) was last changed
+    pub modified: Option<Duration>,
+    /// When the credential was last read
+    pub accessed: Option<Duration>,
+}
+
+impl core::fmt::Display for CredentialSpec {
+    /// Shows the service and user (and the target, if there is one).
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}/{}", self.service, self.user)?;
+        if let Some(target) = &self.target {
+            write!(f, " (target {target})")?;
+        }
+        Ok(())
+    }
+}
+
+/// A matcher for one of the identifying fields of a credential.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Match {
+    /// Matches only the given value
+    Exact(String),
+    /// Matches any value that starts with the given string
+    Prefix(String),
+    /// Matches any value
+    #[default]
+    Any,
+}
+
+impl Match {
+    /// Whether the given value is matched.
+    pub fn matches(&self, value: &str) -> bool {
+        match self {
+            Match::Exact(exact) => value == exact,
+            Match::Prefix(prefix) => value.starts_with(prefix.as_str()),
+            Match::Any => true,
+        }
+    }
+
+    /// The value that matched fields must have, if there is one.
+    ///
+    /// Stores can use this to narrow their native search before
+    /// filtering the results with [matches](Match::matches).
+    pub fn exact(&self) -> Option<&str> {
+        match self {
+            Match::Exact(exact) => Some(exact.as_str()),
+            _ => None,
+        }
+    }
+}
+
+/// A specification of which credentials to return from a
+/// [search](CredentialBuilderApi::search) of a credential store.
+///
+/// The default spec matches every credential in the store.
+/// For example, to find all the credentials for a service whose
+/// users are namespaced by tenant:
+/// ```
+/// # use keyring::credential::{Match, SearchSpec};
+/// let spec = SearchSpec {
+///     service: Match::Exact("my-service".to_string()),
+///     user: Match::Prefix("tenant1:".to_string()),
+///     ..Default::default()
+/// };
+/// assert!(spec.matches(None, "my-service", "tenant1:alice"));
+/// assert!(!spec.matches(None, "my-service", "tenant2:bob"));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchSpec {
+    pub target: Match,
+    pub service: Match,
+    pub user: Match,
+    /// Whether results can be returned in any order (see [unsorted](SearchSpec::unsorted)).
+    pub unsorted: bool,
+}
+
+impl SearchSpec {
+    /// Return this spec, but allow results to be returned in any order.
+    ///
+    /// By default, search results are sorted by service, then user,
+    /// then target. Skipping the sort can save time on large result sets.
+    pub fn unsorted(mut self) -> Self {
+        self.unsorted = true;
+        self
+    }
+
+    /// Whether a credential with the given target, service, and user is matched.
+    ///
+    /// A credential with no target is only matched if the spec's target is
+    /// [Any](Match::Any).
+    pub fn matches(&self, target: Option<&str>, service: &str, user: &str) -> bool {
+        let target_matches = match target {
+            Some(target) => self.target.matches(target),
+            None => matches!(self.target, Match::Any),
+        };
+        target_matches && self.service.matches(service) && self.user.matches(user)
+    }
+}
+
 struct NopCredentialBuilder;
 
 impl CredentialBuilderApi for NopCredentialBuilder {
@@ -184,3 +711,36 @@ impl CredentialBuilderApi for NopCredentialBuilder {
 pub fn nop_credential_builder() -> Box<CredentialBuilder> {
     Box::new(NopCredentialBuilder)
 }
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::{Match, SearchSpec};
+
+    #[test]
+    fn test_match() {
+        assert!(Match::Exact("tenant1:alice".to_string()).matches("tenant1:alice"));
+        assert!(!Match::Exact("tenant1".to_string()).matches("tenant1:alice"));
+        assert!(Match::Prefix("tenant1:".to_string()).matches("tenant1:alice"));
+        assert!(Match::Prefix("".to_string()).matches(""));
+        assert!(!Match::Prefix("tenant1:".to_string()).matches("tenant2:alice"));
+        assert!(!Match::Prefix("tenant1:".to_string()).matches("tenant1"));
+        assert!(Match::Any.matches(""));
+    }
+
+    #[test]
+    fn test_search_spec() {
+        let spec = SearchSpec::default();
+        assert!(spec.matches(None, "service", "user"));
+        assert!(spec.matches(Some("target"), "service", "user"));
+        let spec = SearchSpec {
+            target: Match::Prefix("".to_string()),
+            user: Match::Prefix("tenant1:".to_string()),
+            ..Default::default()
+        };
+        assert!(spec.matches(Some("target"), "service", "tenant1:bob"));
+        assert!(!spec.matches(None, "service", "tenant1:bob"));
+        assert!(!spec.matches(Some("target"), "service", "tenant2:bob"));
+    }
+}
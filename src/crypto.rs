@@ -0,0 +1,349 @@
+/*!
+
+# Local encryption
+
+This module provides authenticated encryption for secrets that this crate
+keeps outside of a platform credential store (for example, in files or
+exported bundles), so that all such code goes through one thin wrapper
+over the vetted RustCrypto crates. It's available when the `crypto-rust` feature
+is enabled, and it's pure Rust (no OpenSSL) and `no_std` compatible.
+
+There are just a few functions: [seal] encrypts and authenticates a plaintext,
+[open] checks and decrypts the result, [mac] and [verify_mac] authenticate
+data without encrypting it, [derive_key] turns a high-entropy
+secret (such as a random secret kept in the platform store) into a key
+for a given purpose, and [derive_key_from_passphrase] turns a passphrase
+that a person has chosen into a key.
+```
+# use keyring::crypto::{derive_key, open, seal};
+let key = derive_key(b"some high-entropy secret", b"salt", b"my-app backups");
+let sealed = seal(b"top secret", &key);
+assert_eq!(open(&sealed, &key).unwrap(), b"top secret");
+```
+
+The encryption is AES-256-GCM-SIV (RFC 8452), from the RustCrypto
+`aes-gcm-siv` crate, with a random nonce for each sealed value. GCM-SIV
+was chosen because it stays secure (only revealing whether two sealed
+values are equal) even if a nonce is ever repeated.
+
+[derive_key] uses HKDF-SHA256 (from the `hkdf` crate), which is _not_ a
+password hash: don't use it to make keys from passwords that people have chosen.
+Use [derive_key_from_passphrase] (PBKDF2-HMAC-SHA256, from the `pbkdf2` crate)
+for those.
+
+Nonces come from the operating system's random number generator (by way of
+the `getrandom` crate), so sealing needs a platform that has one.
+ */
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+use aes_gcm_siv::aead::rand_core::RngCore;
+use aes_gcm_siv::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm_siv::{Aes256GcmSiv, Nonce};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use zeroize::Zeroizing;
+
+use super::credential::CredentialSpec;
+use super::error::{Error, Result};
+
+/// The length of the keys used by [seal] and [open].
+pub const KEY_LEN: usize = 32;
+
+/// The length of the tags made by [mac].
+pub const MAC_LEN: usize = 32;
+
+/// The number of bytes that [seal] adds to a plaintext.
+pub const SEAL_OVERHEAD: usize = 1 + NONCE_LEN + TAG_LEN;
+
+/// The format version that [seal] writes as the first byte of its output.
+const VERSION: u8 = 2;
+
+/// The length of the nonce that follows the version.
+const NONCE_LEN: usize = 12;
+
+/// The length of the authentication tag at the end of the ciphertext.
+const TAG_LEN: usize = 16;
+
+/// Encrypt and authenticate the given plaintext with the given key.
+///
+/// The result is a version byte, followed by a random 12-byte nonce,
+/// followed by the ciphertext (which is as long as the plaintext)
+/// and a 16-byte authentication tag.
+pub fn seal(plaintext: &[u8], key: &[u8; KEY_LEN]) -> Vec<u8> {
+    let nonce = Aes256GcmSiv::generate_nonce(&mut OsRng);
+    seal_with_nonce(plaintext, key, nonce.as_ref())
+}
+
+/// Check and decrypt a value sealed with the given key.
+///
+/// Returns an [Invalid](Error::Invalid) error if the sealed value is malformed,
+/// was sealed with a different key, or has been tampered with.
+pub fn open(sealed: &[u8], key: &[u8; KEY_LEN]) -> Result<Vec<u8>> {
+    if sealed.len() < SEAL_OVERHEAD {
+        return Err(invalid("is too short"));
+    }
+    if sealed[0] != VERSION {
+        return Err(invalid("has an unknown format version"));
+    }
+    let (nonce, ciphertext) = sealed[1..].split_at(NONCE_LEN);
+    Aes256GcmSiv::new(key.into())
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| invalid("failed authentication"))
+}
+
+/// Compute the HMAC-SHA256 tag of the given data with the given key.
+pub fn mac(data: &[u8], key: &[u8; KEY_LEN]) -> [u8; MAC_LEN] {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).unwrap();
+    mac.update(data);
+    let mut tag = [0u8; MAC_LEN];
+    tag.copy_from_slice(&mac.finalize().into_bytes());
+    tag
+}
+
+/// Check (in constant time) that a tag made by [mac] matches the data.
+///
+/// Returns an [Invalid](Error::Invalid) error if it doesn't.
+pub fn verify_mac(data: &[u8], tag: &[u8], key: &[u8; KEY_LEN]) -> Result<()> {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).unwrap();
+    mac.update(data);
+    mac.verify_slice(tag)
+        .map_err(|_| Error::Invalid("tag".to_string(), "doesn't match the data".to_string()))
+}
+
+/// Derive a key for the given purpose from a high-entropy secret.
+///
+/// The `salt` may be empty; the `info` names the purpose of the key,
+/// so that keys derived for different purposes are independent.
+pub fn derive_key(secret: &[u8], salt: &[u8], info: &[u8]) -> Zeroizing<[u8; KEY_LEN]> {
+    let mut key = Zeroizing::new([0u8; KEY_LEN]);
+    Hkdf::<Sha256>::new(Some(salt), secret)
+        .expand(info, key.as_mut_slice())
+        .expect("HKDF output is too long: please report a bug!");
+    key
+}
+
+/// Derive a key from a passphrase that a person has chosen.
+///
+/// This is PBKDF2-HMAC-SHA256 with the given number of rounds (at least one):
+/// the more rounds, the slower it is to guess the passphrase from sealed
+/// values. The `salt` should be different for each key, but needn't be secret.
+pub fn derive_key_from_passphrase(
+    passphrase: &[u8],
+    salt: &[u8],
+    rounds: u32,
+) -> Zeroizing<[u8; KEY_LEN]> {
+    let mut key = Zeroizing::new([0u8; KEY_LEN]);
+    pbkdf2::pbkdf2_hmac::<Sha256>(passphrase, salt, rounds.max(1), key.as_mut_slice());
+    key
+}
+
+/// Fill the buffer with bytes from the operating system's random number generator.
+#[allow(dead_code)]
+pub(crate) fn fill_random(buf: &mut [u8]) {
+    OsRng.fill_bytes(buf)
+}
+
+/// Seal with the given nonce, which must never be reused with the same key.
+fn seal_with_nonce(plaintext: &[u8], key: &[u8; KEY_LEN], nonce: &[u8]) -> Vec<u8> {
+    let ciphertext = Aes256GcmSiv::new(key.into())
+        .encrypt(Nonce::from_slice(nonce), plaintext)
+        .expect("Plaintext is too long to seal: please report a bug!");
+    let mut sealed = Vec::with_capacity(1 + NONCE_LEN + ciphertext.len());
+    sealed.push(VERSION);
+    sealed.extend_from_slice(nonce);
+    sealed.extend_from_slice(&ciphertext);
+    sealed
+}
+
+/// An unambiguous encoding of a credential's identity, for binding
+/// keys and tags to one credential.
+///
+/// It's a byte that tells a missing target from an empty one, followed by
+/// the target, service, and user, each with a 4-byte big-endian length.
+#[allow(dead_code)]
+pub(crate) fn identity_bytes(spec: &CredentialSpec) -> Vec<u8> {
+    let mut identity = Vec::from([u8::from(spec.target.is_some())]);
+    for field in [
+        spec.target.as_deref().unwrap_or_default(),
+        spec.service.as_str(),
+        spec.user.as_str(),
+    ] {
+        identity.extend_from_slice(&(field.len() as u32).to_be_bytes());
+        identity.extend_from_slice(field.as_bytes());
+    }
+    identity
+}
+
+fn invalid(reason: &str) -> Error {
+    Error::Invalid("sealed value".to_string(), reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        KEY_LEN, derive_key, derive_key_from_passphrase, identity_bytes, mac, open, seal,
+        seal_with_nonce, verify_mac,
+    };
+    use crate::Error;
+    use crate::credential::CredentialSpec;
+
+    /// The key and nonce of the first AEAD_AES_256_GCM_SIV test vectors in RFC 8452.
+    fn rfc_8452_key_and_nonce() -> ([u8; KEY_LEN], [u8; 12]) {
+        let mut key = [0u8; KEY_LEN];
+        key[0] = 0x01;
+        let mut nonce = [0u8; 12];
+        nonce[0] = 0x03;
+        (key, nonce)
+    }
+
+    #[test]
+    fn test_seal_known_answers() {
+        // from RFC 8452, appendix C.2
+        let (key, nonce) = rfc_8452_key_and_nonce();
+        let vectors: [(&[u8], &[u8]); 2] = [
+            (
+                b"",
+                &[
+                    0x07, 0xf5, 0xf4, 0x16, 0x9b, 0xbf, 0x55, 0xa8, 0x40, 0x0c, 0xd4, 0x7e, 0xa6,
+                    0xfd, 0x40, 0x0f,
+                ],
+            ),
+            (
+                &[0x01, 0, 0, 0, 0, 0, 0, 0],
+                &[
+                    0xc2, 0xef, 0x32, 0x8e, 0x5c, 0x71, 0xc8, 0x3b, 0x84, 0x31, 0x22, 0x13, 0x0f,
+                    0x73, 0x64, 0xb7, 0x61, 0xe0, 0xb9, 0x74, 0x27, 0xe3, 0xdf, 0x28,
+                ],
+            ),
+        ];
+        for (plaintext, ciphertext) in vectors {
+            let sealed = seal_with_nonce(plaintext, &key, &nonce);
+            assert_eq!(sealed[0], 2);
+            assert_eq!(&sealed[1..13], &nonce);
+            assert_eq!(&sealed[13..], ciphertext);
+            assert_eq!(open(&sealed, &key).unwrap(), plaintext);
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let key = derive_key(b"test secret", b"", b"test round trip");
+        for len in [0, 1, 15, 16, 17, 1000] {
+            let plaintext: alloc::vec::Vec<u8> = (0..len).map(|i| i as u8).collect();
+            let sealed = seal(&plaintext, &key);
+            assert_eq!(sealed.len(), plaintext.len() + super::SEAL_OVERHEAD);
+            if len > 0 {
+                assert_ne!(&sealed[13..13 + len], plaintext.as_slice());
+            }
+            assert_eq!(open(&sealed, &key).unwrap(), plaintext);
+            // nonces are random, so sealing twice gives different results
+            assert_ne!(seal(&plaintext, &key), sealed);
+        }
+    }
+
+    #[test]
+    fn test_tampering() {
+        let key = derive_key(b"test secret", b"", b"test tampering");
+        let sealed = seal(b"test tampering", &key);
+        for i in 0..sealed.len() {
+            let mut tampered = sealed.clone();
+            tampered[i] ^= 1;
+            assert!(matches!(open(&tampered, &key), Err(Error::Invalid(_, _))));
+        }
+        assert!(matches!(
+            open(&sealed[..10], &key),
+            Err(Error::Invalid(_, _))
+        ));
+        let other = derive_key(b"test secret", b"", b"other purpose");
+        assert!(matches!(open(&sealed, &other), Err(Error::Invalid(_, _))));
+    }
+
+    #[test]
+    fn test_derive_key() {
+        // the first 32 bytes of test case 1 in RFC 5869
+        let salt: alloc::vec::Vec<u8> = (0x00..=0x0c).collect();
+        let info: alloc::vec::Vec<u8> = (0xf0..=0xf9).collect();
+        let expected = [
+            0x3c, 0xb2, 0x5f, 0x25, 0xfa, 0xac, 0xd5, 0x7a, 0x90, 0x43, 0x4f, 0x64, 0xd0, 0x36,
+            0x2f, 0x2a, 0x2d, 0x2d, 0x0a, 0x90, 0xcf, 0x1a, 0x5a, 0x4c, 0x5d, 0xb0, 0x2d, 0x56,
+            0xec, 0xc4, 0xc5, 0xbf,
+        ];
+        assert_eq!(*derive_key(&[0x0b; 22], &salt, &info), expected);
+        let key = derive_key(b"test secret", b"salt", b"info");
+        assert_eq!(*key, *derive_key(b"test secret", b"salt", b"info"));
+        assert_ne!(*key, *derive_key(b"test secret", b"pepper", b"info"));
+        assert_ne!(*key, *derive_key(b"test secret", b"salt", b"other"));
+    }
+
+    #[test]
+    fn test_mac() {
+        // test case 2 in RFC 4231 (HMAC pads short keys with zeros)
+        let mut jefe = [0u8; KEY_LEN];
+        jefe[..4].copy_from_slice(b"Jefe");
+        let expected = [
+            0x5b, 0xdc, 0xc1, 0x46, 0xbf, 0x60, 0x75, 0x4e, 0x6a, 0x04, 0x24, 0x26, 0x08, 0x95,
+            0x75, 0xc7, 0x5a, 0x00, 0x3f, 0x08, 0x9d, 0x27, 0x39, 0x83, 0x9d, 0xec, 0x58, 0xb9,
+            0x64, 0xec, 0x38, 0x43,
+        ];
+        assert_eq!(mac(b"what do ya want for nothing?", &jefe), expected);
+        let key = derive_key(b"test secret", b"", b"test mac");
+        let tag = mac(b"data", &key);
+        assert!(verify_mac(b"data", &tag, &key).is_ok());
+        assert!(verify_mac(b"date", &tag, &key).is_err());
+        assert!(verify_mac(b"data", &tag[..16], &key).is_err());
+        let other = derive_key(b"test secret", b"", b"other purpose");
+        assert!(verify_mac(b"data", &tag, &other).is_err());
+    }
+
+    #[test]
+    fn test_derive_key_from_passphrase() {
+        // the first blocks of the PBKDF2-HMAC-SHA256 test vectors in RFC 7914
+        let expected = [
+            0x4d, 0xdc, 0xd8, 0xf6, 0x0b, 0x98, 0xbe, 0x21, 0x83, 0x0c, 0xee, 0x5e, 0xf2, 0x27,
+            0x01, 0xf9, 0x64, 0x1a, 0x44, 0x18, 0xd0, 0x4c, 0x04, 0x14, 0xae, 0xff, 0x08, 0x87,
+            0x6b, 0x34, 0xab, 0x56,
+        ];
+        assert_eq!(
+            *derive_key_from_passphrase(b"Password", b"NaCl", 80000),
+            expected
+        );
+        let expected = [
+            0x55, 0xac, 0x04, 0x6e, 0x56, 0xe3, 0x08, 0x9f, 0xec, 0x16, 0x91, 0xc2, 0x25, 0x44,
+            0xb6, 0x05, 0xf9, 0x41, 0x85, 0x21, 0x6d, 0xde, 0x04, 0x65, 0xe6, 0x8b, 0x9d, 0x57,
+            0xc2, 0x0d, 0xac, 0xbc,
+        ];
+        assert_eq!(*derive_key_from_passphrase(b"passwd", b"salt", 1), expected);
+        let key = derive_key_from_passphrase(b"passwd", b"salt", 100);
+        assert_ne!(*key, expected);
+        assert_eq!(*key, *derive_key_from_passphrase(b"passwd", b"salt", 100));
+        assert_ne!(*key, *derive_key_from_passphrase(b"passwd", b"pepper", 100));
+    }
+
+    #[test]
+    fn test_identity_bytes() {
+        let spec = |target: Option<&str>, service: &str, user: &str| CredentialSpec {
+            target: target.map(str::to_string),
+            service: service.to_string(),
+            user: user.to_string(),
+        };
+        assert_eq!(
+            identity_bytes(&spec(Some("t"), "s", "u")),
+            [1, 0, 0, 0, 1, b't', 0, 0, 0, 1, b's', 0, 0, 0, 1, b'u']
+        );
+        // a missing target is distinct from an empty one,
+        // and fields can't run into each other
+        let identities = [
+            identity_bytes(&spec(None, "service", "user")),
+            identity_bytes(&spec(Some(""), "service", "user")),
+            identity_bytes(&spec(None, "serv", "iceuser")),
+            identity_bytes(&spec(Some("service"), "", "user")),
+        ];
+        for (i, a) in identities.iter().enumerate() {
+            for b in &identities[i + 1..] {
+                assert_ne!(a, b);
+            }
+        }
+    }
+}
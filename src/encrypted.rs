@@ -0,0 +1,376 @@
+/*!
+
+# Client-side encryption
+
+Platform stores don't all protect their secrets equally well: the Windows
+Credential Manager, for example, hands any credential to any process running
+in the user's session. Apps that want defense in depth can wrap their
+store's builder in the [EncryptedCredentialBuilder] in this module, whose
+credentials encrypt each secret (with [seal](crate::crypto::seal)) before
+handing it to the wrapped store, and decrypt it when it's read back. Entries
+for encrypted credentials are used just like any others. This module is
+available when the `client-crypto` feature is enabled.
+
+```
+# use keyring::{Entry, credential::CredentialBuilderApi, memory::MemoryCredentialBuilder};
+# use keyring::encrypted::EncryptedCredentialBuilder;
+let store = MemoryCredentialBuilder::default();
+let key = [7u8; 32]; // in real life, a random key kept somewhere else
+let builder = EncryptedCredentialBuilder::new(Box::new(store.clone()), &key);
+let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+entry.set_password("protected twice").unwrap();
+assert_eq!(entry.get_password().unwrap(), "protected twice");
+let raw = store.build(None, "service", "user").unwrap();
+assert_ne!(raw.get_secret().unwrap(), b"protected twice");
+```
+
+The key is either given to the builder ([new](EncryptedCredentialBuilder::new)),
+or derived from a high-entropy secret
+([from_secret](EncryptedCredentialBuilder::from_secret)) or from a
+passphrase that a person has chosen
+([from_passphrase](EncryptedCredentialBuilder::from_passphrase)).
+Either way, the app has to keep the key (or what it's derived from)
+somewhere other than the wrapped store, or the encryption adds nothing.
+
+Each credential's secret is sealed with a key of its own, derived from the
+builder's key and the credential's target, service, and user, so a sealed
+secret that's copied to another credential can't be read there. Reading a
+secret that wasn't sealed with the right key (including one that was set
+without encryption) returns an [Invalid](Error::Invalid) error. Attributes,
+labels, and other metadata are _not_ encrypted.
+ */
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::time::Duration;
+
+use zeroize::Zeroizing;
+
+use super::credential::{
+    AtRestStatus, AttributeMap, Credential, CredentialApi, CredentialBuilder, CredentialBuilderApi,
+    CredentialMetadata, CredentialPersistence, CredentialSpec, CredentialStoreInfo, SearchSpec,
+};
+use super::crypto::{
+    KEY_LEN, SEAL_OVERHEAD, derive_key, derive_key_from_passphrase, identity_bytes, open, seal,
+};
+use super::error::{Error, Result};
+
+/// A credential builder whose credentials encrypt their secrets
+/// before storing them.
+pub struct EncryptedCredentialBuilder {
+    inner: Box<CredentialBuilder>,
+    key: Zeroizing<[u8; KEY_LEN]>,
+}
+
+impl EncryptedCredentialBuilder {
+    /// Create a builder that encrypts the secrets of the credentials
+    /// built by `inner` with the given key.
+    pub fn new(inner: Box<CredentialBuilder>, key: &[u8; KEY_LEN]) -> Self {
+        Self {
+            inner,
+            key: Zeroizing::new(*key),
+        }
+    }
+
+    /// Create a builder whose key is derived from a high-entropy secret
+    /// (see [derive_key](crate::crypto::derive_key)).
+    pub fn from_secret(inner: Box<CredentialBuilder>, secret: &[u8]) -> Self {
+        let key = derive_key(secret, b"", b"keyring-rs encrypted credentials");
+        Self { inner, key }
+    }
+
+    /// Create a builder whose key is derived from a passphrase
+    /// (see [derive_key_from_passphrase](crate::crypto::derive_key_from_passphrase)).
+    ///
+    /// The same passphrase, salt, and rounds must be used to read
+    /// the secrets again.
+    pub fn from_passphrase(
+        inner: Box<CredentialBuilder>,
+        passphrase: &str,
+        salt: &[u8],
+        rounds: u32,
+    ) -> Self {
+        let key = derive_key_from_passphrase(passphrase.as_bytes(), salt, rounds);
+        Self { inner, key }
+    }
+
+    /// The wrapped builder.
+    pub fn inner(&self) -> &CredentialBuilder {
+        self.inner.as_ref()
+    }
+
+    /// Wrap a credential, with a key derived from its identity as the
+    /// wrapped store reports it (see [spec](CredentialApi::spec)), so that
+    /// built and found credentials agree even when the store normalizes
+    /// targets.
+    fn wrap(&self, inner: Box<Credential>, spec: &CredentialSpec) -> EncryptedCredential {
+        let info = identity_bytes(spec);
+        EncryptedCredential {
+            inner,
+            key: derive_key(self.key.as_slice(), b"keyring-rs credential", &info),
+        }
+    }
+}
+
+impl core::fmt::Debug for EncryptedCredentialBuilder {
+    /// Shows the wrapped builder, but never the key.
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("EncryptedCredentialBuilder")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CredentialBuilderApi for EncryptedCredentialBuilder {
+    /// Build a credential in the wrapped store that encrypts its secret.
+    fn build(&self, target: Option<&str>, service: &str, user: &str) -> Result<Box<Credential>> {
+        let inner = self.inner.build(target, service, user)?;
+        let spec = inner.spec().unwrap_or_else(|| CredentialSpec {
+            target: target.map(str::to_string),
+            service: service.to_string(),
+            user: user.to_string(),
+        });
+        Ok(Box::new(self.wrap(inner, &spec)))
+    }
+
+    /// Encrypted credentials are at least as protected as the wrapped store's.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        self.inner.at_rest_encryption()
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+
+    /// The persistence of the wrapped store.
+    fn persistence(&self) -> CredentialPersistence {
+        self.inner.persistence()
+    }
+
+    /// Search the wrapped store, and make the found credentials encrypted.
+    ///
+    /// Found credentials that don't know their identity can't be
+    /// given their keys, so they are skipped.
+    fn search(&self, spec: &SearchSpec) -> Result<Vec<Box<Credential>>> {
+        let found = self.inner.search(spec)?;
+        Ok(found
+            .into_iter()
+            .filter_map(|cred| {
+                let spec = cred.spec()?;
+                Some(Box::new(self.wrap(cred, &spec)) as Box<Credential>)
+            })
+            .collect())
+    }
+
+    /// The info of the wrapped store.
+    fn info(&self) -> CredentialStoreInfo {
+        self.inner.info()
+    }
+}
+
+/// A credential that encrypts its secret before storing it.
+pub struct EncryptedCredential {
+    inner: Box<Credential>,
+    key: Zeroizing<[u8; KEY_LEN]>,
+}
+
+impl EncryptedCredential {
+    /// The wrapped credential.
+    ///
+    /// Its secret is the sealed one.
+    pub fn inner(&self) -> &Credential {
+        self.inner.as_ref()
+    }
+
+    /// Decrypt a sealed secret read from the wrapped credential.
+    fn open_sealed(&self, sealed: &[u8]) -> Result<Vec<u8>> {
+        open(sealed, &self.key).map_err(|_| {
+            Error::Invalid(
+                "secret".to_string(),
+                "wasn't encrypted with this credential's key".to_string(),
+            )
+        })
+    }
+}
+
+impl core::fmt::Debug for EncryptedCredential {
+    /// Shows the wrapped credential, but never the key.
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("EncryptedCredential")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CredentialApi for EncryptedCredential {
+    fn set_secret(&self, secret: &[u8]) -> Result<()> {
+        self.inner.set_secret(&seal(secret, &self.key))
+    }
+
+    /// Decrypt the stored secret.
+    ///
+    /// Returns an [Invalid](Error::Invalid) error if it wasn't sealed
+    /// with this credential's key, or has been tampered with.
+    fn get_secret(&self) -> Result<Vec<u8>> {
+        let sealed = self.inner.get_secret()?;
+        self.open_sealed(&sealed)
+    }
+
+    fn compare_and_set_password(&self, expected: &str, password: &str) -> Result<()> {
+        self.compare_and_set_secret(expected.as_bytes(), password.as_bytes())
+    }
+
+    /// Encrypt and store the secret, but only if the stored one
+    /// decrypts to `expected`.
+    ///
+    /// The sealed secret that was read is what's compared by the wrapped
+    /// credential when it stores the new one, so this is as atomic as the
+    /// wrapped store's [compare_and_set_secret](CredentialApi::compare_and_set_secret).
+    fn compare_and_set_secret(&self, expected: &[u8], secret: &[u8]) -> Result<()> {
+        let sealed = Zeroizing::new(self.inner.get_secret()?);
+        if *Zeroizing::new(self.open_sealed(&sealed)?) != expected {
+            return Err(Error::Conflict);
+        }
+        self.inner
+            .compare_and_set_secret(&sealed, &seal(secret, &self.key))
+    }
+
+    fn exists(&self) -> Result<bool> {
+        self.inner.exists()
+    }
+
+    fn get_attributes(&self) -> Result<AttributeMap<String, String>> {
+        self.inner.get_attributes()
+    }
+
+    fn update_attributes(&self, attributes: &AttributeMap<&str, &str>) -> Result<()> {
+        self.inner.update_attributes(attributes)
+    }
+
+    fn get_metadata(&self) -> Result<CredentialMetadata> {
+        self.inner.get_metadata()
+    }
+
+    fn get_label(&self) -> Result<String> {
+        self.inner.get_label()
+    }
+
+    fn set_label(&self, label: &str) -> Result<()> {
+        self.inner.set_label(label)
+    }
+
+    fn set_expiration(&self, ttl: Duration) -> Result<()> {
+        self.inner.set_expiration(ttl)
+    }
+
+    fn unlock(&self) -> Result<()> {
+        self.inner.unlock()
+    }
+
+    /// Sealing adds 29 bytes to a secret, so it must leave room for them.
+    fn max_secret_len(&self) -> Option<usize> {
+        self.inner
+            .max_secret_len()
+            .map(|len| len.saturating_sub(SEAL_OVERHEAD))
+    }
+
+    fn spec(&self) -> Option<CredentialSpec> {
+        self.inner.spec()
+    }
+
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        self.inner.at_rest_encryption()
+    }
+
+    fn delete_credential(&self) -> Result<()> {
+        self.inner.delete_credential()
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+
+    fn debug_fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Debug::fmt(self, f)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::EncryptedCredentialBuilder;
+    use crate::credential::{CredentialBuilderApi, SearchSpec};
+    use crate::memory::MemoryCredentialBuilder;
+    use crate::tests::{CasOnlyCredentialBuilder, DefaultTargetCredentialBuilder};
+    use crate::{Entry, Error};
+
+    #[test]
+    fn test_encrypted() {
+        let store = MemoryCredentialBuilder::default();
+        let builder = EncryptedCredentialBuilder::from_secret(Box::new(store.clone()), b"secret");
+        let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+        assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+        entry.set_password("encrypted").unwrap();
+        assert_eq!(entry.get_password().unwrap(), "encrypted");
+        let raw = store.build(None, "service", "user").unwrap();
+        let sealed = raw.get_secret().unwrap();
+        assert_eq!(sealed.len(), "encrypted".len() + 29);
+
+        // a sealed secret can't be read by another credential, or with another key
+        let other = store.build(None, "service", "other").unwrap();
+        other.set_secret(&sealed).unwrap();
+        let other = builder.build(None, "service", "other").unwrap();
+        assert!(matches!(other.get_secret(), Err(Error::Invalid(_, _))));
+        let rekeyed = EncryptedCredentialBuilder::from_secret(Box::new(store.clone()), b"other");
+        let entry2 = rekeyed.build(None, "service", "user").unwrap();
+        assert!(matches!(entry2.get_secret(), Err(Error::Invalid(_, _))));
+
+        let found = builder.search(&SearchSpec::default()).unwrap();
+        assert_eq!(found.len(), 2);
+        let user = found
+            .iter()
+            .find(|c| c.spec().unwrap().user == "user")
+            .unwrap();
+        assert_eq!(user.get_password().unwrap(), "encrypted");
+    }
+
+    #[test]
+    fn test_normalized_targets() {
+        let store = DefaultTargetCredentialBuilder::default();
+        let builder = EncryptedCredentialBuilder::from_secret(Box::new(store), b"secret");
+        let built = builder.build(None, "service", "user").unwrap();
+        built.set_password("normalized").unwrap();
+        let found = builder.search(&SearchSpec::default()).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].get_password().unwrap(), "normalized");
+        let again = builder.build(Some("default"), "service", "user").unwrap();
+        assert_eq!(again.get_password().unwrap(), "normalized");
+    }
+
+    #[test]
+    fn test_from_passphrase() {
+        let store = MemoryCredentialBuilder::default();
+        let make = |passphrase| {
+            EncryptedCredentialBuilder::from_passphrase(
+                Box::new(store.clone()),
+                passphrase,
+                b"salt",
+                10,
+            )
+        };
+        let entry = make("passphrase").build(None, "service", "user").unwrap();
+        entry.set_secret(b"secret").unwrap();
+        let again = make("passphrase").build(None, "service", "user").unwrap();
+        assert_eq!(again.get_secret().unwrap(), b"secret");
+        let wrong = make("wrong").build(None, "service", "user").unwrap();
+        assert!(matches!(wrong.get_secret(), Err(Error::Invalid(_, _))));
+    }
+
+    #[test]
+    fn test_compare_and_set() {
+        let builder = EncryptedCredentialBuilder::from_secret(
+            Box::new(CasOnlyCredentialBuilder::default()),
+            b"secret",
+        );
+        crate::tests::test_forwards_compare_and_set(&builder);
+    }
+}
@@ -0,0 +1,371 @@
+/*!
+
+# Environment-variable credential store
+
+CI pipelines and containers usually get their secrets as environment
+variables, and have no platform store to put them in. The
+[EnvCredentialBuilder] in this module builds credentials whose secrets are
+read from environment variables named after their entries: by default,
+`KEYRING_<SERVICE>_<USER>` (or `KEYRING_<TARGET>_<SERVICE>_<USER>` for
+entries that have a target), with every letter uppercased and every other
+character that isn't an ASCII letter or digit replaced by `_`.
+Both the prefix and the naming convention can be changed.
+
+```
+# use keyring::{Entry, Error, credential::CredentialBuilderApi, env_store::EnvCredentialBuilder};
+let builder = EnvCredentialBuilder::new().with_prefix("MYAPP");
+assert_eq!(builder.var_name(None, "api.example.com", "ci-bot"), "MYAPP_API_EXAMPLE_COM_CI_BOT");
+let entry = Entry::new_with_credential(builder.build(None, "api.example.com", "ci-bot").unwrap());
+assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+assert!(matches!(entry.set_password("changed"), Err(Error::ReadOnly)));
+```
+
+Environment variables are never changed: setting or deleting the secret of
+a credential returns a [ReadOnly](Error::ReadOnly) error.
+
+An environment builder can be layered in front of another store
+(see [with_inner](EnvCredentialBuilder::with_inner)), so that the same
+code runs in CI and on developer machines. The credentials it builds then
+read their secret from their environment variable if it's set, and
+from the wrapped store if it isn't; all changes are made in the wrapped store.
+(So a variable that's set hides changes made to its credential's secret.)
+Searching a layered builder searches the wrapped store.
+
+This store is meant for testing and automation: environment variables
+aren't encrypted, and they are inherited by every process the app starts.
+ */
+use super::credential::{
+    AtRestStatus, AttributeMap, Credential, CredentialApi, CredentialBuilder, CredentialBuilderApi,
+    CredentialPersistence, CredentialSpec, CredentialStoreInfo, SearchSpec,
+};
+use super::error::{Error, Result, decode_password};
+
+/// The prefix of the variable names used by a default builder.
+pub const DEFAULT_PREFIX: &str = "KEYRING";
+
+/// The function that names the environment variable of a credential,
+/// given a builder's prefix and the credential's target, service, and user.
+pub type Naming = fn(&str, Option<&str>, &str, &str) -> String;
+
+/// Name a variable `<PREFIX>_<TARGET>_<SERVICE>_<USER>`, leaving out
+/// the prefix if it's empty and the target if there isn't one.
+///
+/// Each part is uppercased, and every character in it that isn't an
+/// ASCII letter or digit is replaced by `_`.
+pub fn default_naming(prefix: &str, target: Option<&str>, service: &str, user: &str) -> String {
+    let parts = [Some(prefix), target, Some(service), Some(user)];
+    parts
+        .into_iter()
+        .flatten()
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            part.chars()
+                .map(|c| match c {
+                    c if c.is_ascii_alphanumeric() => c.to_ascii_uppercase(),
+                    _ => '_',
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+/// A credential builder whose credentials are read from environment variables.
+#[derive(Debug)]
+pub struct EnvCredentialBuilder {
+    prefix: String,
+    naming: Naming,
+    inner: Option<Box<CredentialBuilder>>,
+}
+
+impl Default for EnvCredentialBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EnvCredentialBuilder {
+    /// Create a builder that uses the [default prefix](DEFAULT_PREFIX)
+    /// and [naming](default_naming), and that isn't layered over a store.
+    pub fn new() -> Self {
+        Self {
+            prefix: DEFAULT_PREFIX.to_string(),
+            naming: default_naming,
+            inner: None,
+        }
+    }
+
+    /// Use a different prefix for variable names.
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    /// The prefix of this builder's variable names.
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// Use a different naming convention for variables.
+    pub fn with_naming(mut self, naming: Naming) -> Self {
+        self.naming = naming;
+        self
+    }
+
+    /// Layer this builder in front of another store.
+    pub fn with_inner(mut self, inner: Box<CredentialBuilder>) -> Self {
+        self.inner = Some(inner);
+        self
+    }
+
+    /// The builder of the store this builder is layered in front of, if any.
+    pub fn inner(&self) -> Option<&CredentialBuilder> {
+        self.inner.as_deref()
+    }
+
+    /// The name of the variable that holds the secret of the credential
+    /// with the given target, service, and user.
+    pub fn var_name(&self, target: Option<&str>, service: &str, user: &str) -> String {
+        (self.naming)(&self.prefix, target, service, user)
+    }
+
+    fn wrap(&self, spec: CredentialSpec, inner: Option<Box<Credential>>) -> Box<Credential> {
+        Box::new(EnvCredential {
+            var: self.var_name(spec.target.as_deref(), &spec.service, &spec.user),
+            spec,
+            inner,
+        })
+    }
+}
+
+impl CredentialBuilderApi for EnvCredentialBuilder {
+    /// Build a credential that reads its secret from its variable
+    /// (and from the wrapped store, if there is one).
+    fn build(&self, target: Option<&str>, service: &str, user: &str) -> Result<Box<Credential>> {
+        let inner = match &self.inner {
+            Some(builder) => Some(builder.build(target, service, user)?),
+            None => None,
+        };
+        let spec = CredentialSpec {
+            target: target.map(str::to_string),
+            service: service.to_string(),
+            user: user.to_string(),
+        };
+        Ok(self.wrap(spec, inner))
+    }
+
+    /// Variables aren't encrypted, so this is the at-rest encryption of
+    /// the wrapped store only if there is one.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        match &self.inner {
+            Some(builder) => builder.at_rest_encryption(),
+            None => AtRestStatus::Unencrypted,
+        }
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    /// Variables vanish with the process, so this is the persistence of
+    /// the wrapped store only if there is one.
+    fn persistence(&self) -> CredentialPersistence {
+        match &self.inner {
+            Some(builder) => builder.persistence(),
+            None => CredentialPersistence::ProcessOnly,
+        }
+    }
+
+    /// Search the wrapped store, leaving out credentials whose identity isn't known.
+    ///
+    /// Variables can't be searched, so this returns a
+    /// [NotSupportedByStore](Error::NotSupportedByStore) error if there's no wrapped store.
+    fn search(&self, spec: &SearchSpec) -> Result<Vec<Box<Credential>>> {
+        let Some(builder) = &self.inner else {
+            return Err(Error::NotSupportedByStore("search".to_string()));
+        };
+        Ok(builder
+            .search(spec)?
+            .into_iter()
+            .filter_map(|cred| Some(self.wrap(cred.spec()?, Some(cred))))
+            .collect())
+    }
+
+    /// Variables can hold secrets of any length, but can't be changed.
+    fn info(&self) -> CredentialStoreInfo {
+        CredentialStoreInfo::new("env", self.persistence(), self.at_rest_encryption())
+            .with_search(self.inner.is_some())
+    }
+}
+
+/// A credential whose secret is read from an environment variable.
+#[derive(Debug)]
+pub struct EnvCredential {
+    var: String,
+    spec: CredentialSpec,
+    inner: Option<Box<Credential>>,
+}
+
+impl EnvCredential {
+    /// The name of the variable this credential's secret is read from.
+    pub fn var(&self) -> &str {
+        &self.var
+    }
+
+    /// The credential in the wrapped store, if there is one.
+    pub fn inner(&self) -> Option<&Credential> {
+        self.inner.as_deref()
+    }
+
+    fn value(&self) -> Option<Vec<u8>> {
+        std::env::var_os(&self.var).map(|value| value.into_encoded_bytes())
+    }
+
+    fn inner_or_read_only(&self) -> Result<&Credential> {
+        self.inner.as_deref().ok_or(Error::ReadOnly)
+    }
+}
+
+impl CredentialApi for EnvCredential {
+    fn set_password(&self, password: &str) -> Result<()> {
+        self.inner_or_read_only()?.set_password(password)
+    }
+
+    fn set_secret(&self, secret: &[u8]) -> Result<()> {
+        self.inner_or_read_only()?.set_secret(secret)
+    }
+
+    /// Read the password from the variable if it's set
+    /// (or from the wrapped store if it isn't).
+    fn get_password(&self) -> Result<String> {
+        match (self.value(), &self.inner) {
+            (Some(value), _) => decode_password(value),
+            (None, Some(inner)) => inner.get_password(),
+            (None, None) => Err(Error::NoEntry),
+        }
+    }
+
+    /// Read the secret from the variable if it's set
+    /// (or from the wrapped store if it isn't).
+    fn get_secret(&self) -> Result<Vec<u8>> {
+        match (self.value(), &self.inner) {
+            (Some(value), _) => Ok(value),
+            (None, Some(inner)) => inner.get_secret(),
+            (None, None) => Err(Error::NoEntry),
+        }
+    }
+
+    fn exists(&self) -> Result<bool> {
+        match (self.value(), &self.inner) {
+            (Some(_), _) => Ok(true),
+            (None, Some(inner)) => inner.exists(),
+            (None, None) => Ok(false),
+        }
+    }
+
+    /// Variables have no attributes, so these are the attributes
+    /// of the credential in the wrapped store, if there is one.
+    fn get_attributes(&self) -> Result<AttributeMap<String, String>> {
+        match &self.inner {
+            Some(inner) => inner.get_attributes(),
+            None => {
+                self.get_secret()?;
+                Ok(AttributeMap::new())
+            }
+        }
+    }
+
+    fn update_attributes(&self, attributes: &AttributeMap<&str, &str>) -> Result<()> {
+        self.inner_or_read_only()?.update_attributes(attributes)
+    }
+
+    fn unlock(&self) -> Result<()> {
+        match &self.inner {
+            Some(inner) => inner.unlock(),
+            None => Ok(()),
+        }
+    }
+
+    fn max_secret_len(&self) -> Option<usize> {
+        self.inner.as_ref().and_then(|inner| inner.max_secret_len())
+    }
+
+    fn spec(&self) -> Option<CredentialSpec> {
+        Some(self.spec.clone())
+    }
+
+    fn delete_credential(&self) -> Result<()> {
+        self.inner_or_read_only()?.delete_credential()
+    }
+
+    /// Variables aren't encrypted.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        AtRestStatus::Unencrypted
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn debug_fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EnvCredentialBuilder, default_naming};
+    use crate::credential::CredentialBuilderApi;
+    use crate::memory::MemoryCredentialBuilder;
+    use crate::{Entry, Error, tests::generate_random_string};
+
+    #[test]
+    fn test_naming() {
+        assert_eq!(
+            default_naming("KEYRING", None, "my-service", "alice@example"),
+            "KEYRING_MY_SERVICE_ALICE_EXAMPLE"
+        );
+        assert_eq!(
+            default_naming("", Some("prod"), "svc", "bob"),
+            "PROD_SVC_BOB"
+        );
+    }
+
+    #[test]
+    fn test_env() {
+        let name = generate_random_string();
+        let builder = EnvCredentialBuilder::new();
+        let entry = Entry::new_with_credential(builder.build(None, &name, "user").unwrap());
+        let var = builder.var_name(None, &name, "user");
+        assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+        assert!(!entry.exists().unwrap());
+        // SAFETY: the variable's name is random, so no other test uses it
+        unsafe { std::env::set_var(&var, "from env") };
+        assert_eq!(entry.get_password().unwrap(), "from env");
+        assert!(matches!(
+            entry.set_password("changed"),
+            Err(Error::ReadOnly)
+        ));
+        assert!(matches!(entry.delete_credential(), Err(Error::ReadOnly)));
+        unsafe { std::env::remove_var(&var) };
+    }
+
+    #[test]
+    fn test_layered() {
+        let name = generate_random_string();
+        let store = MemoryCredentialBuilder::default();
+        let builder = EnvCredentialBuilder::new().with_inner(Box::new(store.clone()));
+        let entry = Entry::new_with_credential(builder.build(None, &name, "user").unwrap());
+        let raw = Entry::new_with_credential(store.build(None, &name, "user").unwrap());
+        entry.set_password("from store").unwrap();
+        assert_eq!(raw.get_password().unwrap(), "from store");
+        let var = builder.var_name(None, &name, "user");
+        // SAFETY: the variable's name is random, so no other test uses it
+        unsafe { std::env::set_var(&var, "from env") };
+        assert_eq!(entry.get_password().unwrap(), "from env");
+        unsafe { std::env::remove_var(&var) };
+        assert_eq!(entry.get_password().unwrap(), "from store");
+        entry.delete_credential().unwrap();
+        assert!(matches!(raw.get_password(), Err(Error::NoEntry)));
+    }
+}
@@ -8,8 +8,35 @@ but the concrete objects returned must be `Send` so they can be
 moved from one thread to another. (Since most platform errors
 are integer error codes, this requirement
 is not much of a burden on the platform-specific store providers.)
+
+When an app has many entries, it helps to know which one a platform
+error is about. So the platform errors returned by the methods of entries
+that know their identity (see [Entry::spec](crate::Entry::spec))
+are wrapped in a [CredentialContext] that names the entry's service
+and user (never its secret), and that name shows up in the error's message.
+Because the platform error is wrapped, downcasting the attached error
+to a platform error type no longer works for these errors; use
+[Error::platform_error] to get the platform error itself, and
+downcast that instead.
+
+Apps that need to react to particular platform errors (telling a locked
+keychain from a denied access, say) can get the OS error code underlying
+a platform error from [Error::platform_code], rather than matching on
+its message:
+```
+# use keyring::error::{Error, ErrorDomain};
+let err = Error::PlatformFailure(Box::new(std::io::Error::from_raw_os_error(13)));
+let code = err.platform_code().unwrap();
+assert_eq!(code.code, Some(13));
+# #[cfg(not(windows))]
+assert_eq!(code.domain, ErrorDomain::Errno);
+```
  */
 
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
 use crate::Credential;
 
 #[derive(Debug)]
@@ -25,13 +52,13 @@ pub enum Error {
     /// This indicates runtime failure in the underlying
     /// platform storage system.  The details of the failure can
     /// be retrieved from the attached platform error.
-    PlatformFailure(Box<dyn std::error::Error + Send + Sync>),
+    PlatformFailure(Box<dyn core::error::Error + Send + Sync>),
     /// This indicates that the underlying secure storage
     /// holding saved items could not be accessed.  Typically, this
     /// is because of access rules in the platform; for example, it
     /// might be that the credential store is locked.  The underlying
     /// platform error will typically give the reason.
-    NoStorageAccess(Box<dyn std::error::Error + Send + Sync>),
+    NoStorageAccess(Box<dyn core::error::Error + Send + Sync>),
     /// This indicates that there is no underlying credential
     /// entry in the platform for this entry.  Either one was
     /// never set, or it was deleted.
@@ -57,12 +84,235 @@ pub enum Error {
     /// This indicates that there was no default credential builder to use;
     /// the client must set one before creating entries.
     NoDefaultCredentialBuilder,
+    /// This indicates that the credential store doesn't support
+    /// the requested operation, which is named by the attached value.
+    /// For example, stores that can't enumerate their credentials
+    /// don't support search.
+    NotSupportedByStore(String),
+    /// This indicates that the user cancelled (or failed) an authentication
+    /// prompt shown by the credential store, such as a biometric prompt
+    /// for a credential that requires it.
+    Cancelled,
+    /// This indicates that the credential store (or the part of it that
+    /// holds the credential) is locked, and unlocking it would have
+    /// prompted the user, which wasn't allowed. Apps can unlock it
+    /// (see [Entry::unlock](crate::Entry::unlock)) and retry.
+    Locked,
+    /// This indicates that the credential is read-only, so it can't be
+    /// changed or deleted (see [read_only](crate::read_only)).
+    ReadOnly,
+    /// This indicates that a conditional update of the credential's secret
+    /// wasn't made, because the stored secret isn't the one expected
+    /// (see [Entry::update_password](crate::Entry::update_password)).
+    Conflict,
+    /// This indicates that the default credential store doesn't persist
+    /// credentials beyond the process (or there is no default store),
+    /// so a strict entry couldn't be created in it
+    /// (see [Entry::new_strict](crate::Entry::new_strict)).
+    NoPersistentStore,
+    /// This indicates that the credential store didn't finish the
+    /// operation within the time allowed (see [timeout](crate::timeout)).
+    /// The operation may still take effect later.
+    Timeout,
+    /// This indicates that the credential's deadline has passed, so its
+    /// secret can't be read (see [expiry](crate::expiry)). The credential
+    /// is still in the store.
+    Expired,
+    /// This indicates that the stored secret failed its integrity check,
+    /// so it may have been changed or replaced by someone else
+    /// (see [integrity](crate::integrity)).
+    IntegrityFailure,
+}
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+impl Error {
+    /// Annotate a platform error with the identity of the credential it's about.
+    ///
+    /// The platform error attached to a [PlatformFailure](Error::PlatformFailure)
+    /// or [NoStorageAccess](Error::NoStorageAccess) error is wrapped in a
+    /// [CredentialContext] with the given context, so a caller that downcasts
+    /// the attached error must get it from [platform_error](Error::platform_error)
+    /// first.  Other variants are returned unchanged, so clients can still
+    /// match on them.
+    pub fn with_context(self, context: &str) -> Error {
+        let wrap = |source| {
+            Box::new(CredentialContext {
+                context: context.to_string(),
+                source,
+            })
+        };
+        match self {
+            Error::PlatformFailure(err) => Error::PlatformFailure(wrap(err)),
+            Error::NoStorageAccess(err) => Error::NoStorageAccess(wrap(err)),
+            other => other,
+        }
+    }
+
+    /// The platform error attached to a [PlatformFailure](Error::PlatformFailure)
+    /// or [NoStorageAccess](Error::NoStorageAccess) error, without
+    /// the [CredentialContext] (if any) that it's wrapped in.
+    ///
+    /// Downcast this, rather than the attached error, to get at
+    /// the error type of a particular store.
+    pub fn platform_error(&self) -> Option<&(dyn core::error::Error + Send + Sync + 'static)> {
+        match self {
+            Error::PlatformFailure(err) | Error::NoStorageAccess(err) => {
+                match err.downcast_ref::<CredentialContext>() {
+                    Some(context) => Some(context.source.as_ref()),
+                    None => Some(err.as_ref()),
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// The OS error code underlying a [PlatformFailure](Error::PlatformFailure)
+    /// or [NoStorageAccess](Error::NoStorageAccess) error, if it has one.
+    ///
+    /// The attached platform error (and its chain of sources, so errors
+    /// with a [CredentialContext] work, too) is searched for an error
+    /// whose code is known: an `OSStatus` from the Apple keychains,
+    /// a Win32 error from the Windows Credential Manager, an `errno`
+    /// (or, on Windows, Win32) code from an I/O error, or the name of a
+    /// DBus error from the Secret Service, KWallet, or Secret portal stores.
+    /// Returns `None` for other errors, including platform errors
+    /// that only have a message.
+    pub fn platform_code(&self) -> Option<PlatformCode> {
+        let mut next: Option<&(dyn core::error::Error + 'static)> = match self {
+            Error::PlatformFailure(err) | Error::NoStorageAccess(err) => Some(err.as_ref()),
+            _ => None,
+        };
+        while let Some(err) = next {
+            if let Some(code) = known_code(err) {
+                return Some(code);
+            }
+            next = err.source();
+        }
+        None
+    }
+}
+
+/// The code of a platform error, if it's of a type whose code we know.
+#[allow(unused_variables)]
+fn known_code(err: &(dyn core::error::Error + 'static)) -> Option<PlatformCode> {
+    #[cfg(feature = "std")]
+    if let Some(err) = err.downcast_ref::<std::io::Error>() {
+        let domain = if cfg!(windows) {
+            ErrorDomain::Win32
+        } else {
+            ErrorDomain::Errno
+        };
+        return err
+            .raw_os_error()
+            .map(|code| PlatformCode::numeric(domain, code as i64));
+    }
+    #[cfg(all(any(target_os = "macos", target_os = "ios"), feature = "apple-native"))]
+    if let Some(err) = err.downcast_ref::<security_framework::base::Error>() {
+        return Some(PlatformCode::numeric(
+            ErrorDomain::OsStatus,
+            err.code() as i64,
+        ));
+    }
+    #[cfg(all(target_os = "windows", feature = "windows-native"))]
+    if let Some(err) = err.downcast_ref::<crate::windows::Error>() {
+        return Some(PlatformCode::numeric(ErrorDomain::Win32, err.0 as i64));
+    }
+    #[cfg(all(
+        any(target_os = "linux", target_os = "freebsd", target_os = "openbsd"),
+        feature = "secret-service"
+    ))]
+    if let Some(dbus_secret_service::Error::Dbus(err)) =
+        err.downcast_ref::<dbus_secret_service::Error>()
+    {
+        return err
+            .name()
+            .map(|name| PlatformCode::named(ErrorDomain::DBus, name));
+    }
+    #[cfg(all(
+        any(target_os = "linux", target_os = "freebsd", target_os = "openbsd"),
+        any(feature = "kwallet", feature = "portal")
+    ))]
+    if let Some(err) = err.downcast_ref::<dbus::Error>() {
+        return err
+            .name()
+            .map(|name| PlatformCode::named(ErrorDomain::DBus, name));
+    }
+    None
+}
+
+/// Where a [PlatformCode] comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ErrorDomain {
+    /// An `OSStatus` returned by Apple's Security framework
+    OsStatus,
+    /// A Win32 error code, as returned by `GetLastError`
+    Win32,
+    /// A POSIX `errno` value
+    Errno,
+    /// A DBus error, which has a name (such as
+    /// `org.freedesktop.DBus.Error.AccessDenied`) rather than a number
+    DBus,
 }
 
-pub type Result<T> = std::result::Result<T, Error>;
+/// The OS error code underlying a platform error (see [Error::platform_code]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlatformCode {
+    /// Where the code comes from
+    pub domain: ErrorDomain,
+    /// The numeric code, in domains that have them
+    pub code: Option<i64>,
+    /// The error's name, in domains that have them
+    pub name: Option<String>,
+}
+
+impl PlatformCode {
+    #[cfg(feature = "std")]
+    fn numeric(domain: ErrorDomain, code: i64) -> Self {
+        Self {
+            domain,
+            code: Some(code),
+            name: None,
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[allow(dead_code)]
+    fn named(domain: ErrorDomain, name: &str) -> Self {
+        Self {
+            domain,
+            code: None,
+            name: Some(name.to_string()),
+        }
+    }
+}
+
+/// A platform error, along with the identity of the credential it's about.
+///
+/// [Entry](crate::Entry) methods wrap the platform errors they return in one of these,
+/// so that logged errors say which credential failed.  The original
+/// platform error is the `source`.
+#[derive(Debug)]
+pub struct CredentialContext {
+    pub context: String,
+    pub source: Box<dyn core::error::Error + Send + Sync>,
+}
+
+impl core::fmt::Display for CredentialContext {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{} (for credential {})", self.source, self.context)
+    }
+}
 
-impl std::fmt::Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl core::error::Error for CredentialContext {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         match self {
             Error::PlatformFailure(err) => write!(f, "Platform secure storage failure: {err}"),
             Error::NoStorageAccess(err) => {
@@ -90,12 +340,30 @@ impl std::fmt::Display for Error {
                     "No default credential builder is available; set one before creating entries"
                 )
             }
+            Error::NotSupportedByStore(op) => {
+                write!(f, "The credential store does not support {op}")
+            }
+            Error::Cancelled => write!(f, "The user cancelled authentication"),
+            Error::Locked => write!(f, "The credential store is locked"),
+            Error::ReadOnly => write!(f, "The credential is read-only"),
+            Error::Conflict => write!(f, "The stored secret is not the one expected"),
+            Error::NoPersistentStore => {
+                write!(
+                    f,
+                    "No credential store that persists credentials is available"
+                )
+            }
+            Error::Timeout => write!(f, "The credential store did not respond in time"),
+            Error::Expired => write!(f, "The credential has expired"),
+            Error::IntegrityFailure => {
+                write!(f, "The stored secret failed its integrity check")
+            }
         }
     }
 }
 
-impl std::error::Error for Error {
-    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+impl core::error::Error for Error {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
         match self {
             Error::PlatformFailure(err) => Some(err.as_ref()),
             Error::NoStorageAccess(err) => Some(err.as_ref()),
@@ -125,4 +393,42 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_platform_code() {
+        let io = std::io::Error::from_raw_os_error(2);
+        let err = Error::NoStorageAccess(Box::new(io)).with_context("service/user");
+        let code = err.platform_code().expect("No code for I/O error");
+        assert_eq!(code.code, Some(2));
+        assert_eq!(code.name, None);
+        assert!(matches!(
+            code.domain,
+            ErrorDomain::Errno | ErrorDomain::Win32
+        ));
+        assert_eq!(
+            Error::PlatformFailure("only a message".into()).platform_code(),
+            None
+        );
+        assert_eq!(Error::NoEntry.platform_code(), None);
+    }
+
+    #[test]
+    fn test_with_context() {
+        let err = Error::PlatformFailure("test failure".into()).with_context("service/user");
+        assert_eq!(
+            err.to_string(),
+            "Platform secure storage failure: test failure (for credential service/user)"
+        );
+        let source = core::error::Error::source(&err).expect("No source for error");
+        let context: &CredentialContext = source.downcast_ref().expect("Source has no context");
+        assert_eq!(context.source.to_string(), "test failure");
+        let platform = err.platform_error().expect("No platform error");
+        assert!(platform.downcast_ref::<CredentialContext>().is_none());
+        assert_eq!(platform.to_string(), "test failure");
+        assert!(matches!(
+            Error::NoEntry.with_context("service/user"),
+            Error::NoEntry
+        ));
+    }
 }
@@ -0,0 +1,332 @@
+/*!
+
+# Serialized access to credential stores
+
+Several platform stores are unreliable when they're used from many
+threads at once: the Windows Credential Manager, the kernel keyutils,
+and the Secret Service (over DBus) have all been seen to lose or
+garble concurrent changes. The [ExclusiveCredentialBuilder] in this
+module wraps another builder, and makes the operations on the credentials
+it builds take turns: each one holds a lock for as long as it runs.
+
+```
+# use keyring::{Entry, credential::CredentialBuilderApi, memory::MemoryCredentialBuilder};
+# use keyring::exclusive::ExclusiveCredentialBuilder;
+let builder = ExclusiveCredentialBuilder::new(Box::new(MemoryCredentialBuilder::default()));
+let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+std::thread::scope(|scope| {
+    for _ in 0..4 {
+        scope.spawn(|| entry.set_password("one at a time").unwrap());
+    }
+});
+```
+
+By default, the lock is shared by every exclusive credential in the process,
+whatever its store, so all their operations are serialized. Apps that use
+several stores, and only need the operations on each store serialized,
+can give each store's builder a lock of its own with
+[ExclusiveCredentialBuilder::new_per_store]. Apps that don't wrap their
+builders this way aren't slowed down at all.
+
+The lock is reentrant, so an operation on an exclusive credential can make
+other exclusive operations on the same thread (as when exclusive builders
+are [chained](crate::chained)) without deadlocking. Searches through an
+exclusive builder hold the lock too, but the credentials they return are
+those of the wrapped store.
+ */
+use std::sync::{Arc, Condvar, LazyLock, Mutex, MutexGuard};
+use std::thread::ThreadId;
+use std::time::Duration;
+
+use super::credential::{
+    AtRestStatus, AttributeMap, Credential, CredentialApi, CredentialBuilder, CredentialBuilderApi,
+    CredentialMetadata, CredentialPersistence, CredentialSpec, CredentialStoreInfo, SearchSpec,
+};
+use super::error::Result;
+
+/// A reentrant lock that exclusive credentials hold while they operate.
+#[derive(Debug, Default)]
+struct AccessLock {
+    /// The thread that holds the lock, and how many times it has taken it.
+    owner: Mutex<Option<(ThreadId, usize)>>,
+    released: Condvar,
+}
+
+impl AccessLock {
+    /// Run `f` while holding this lock.
+    fn run<T>(&self, f: impl FnOnce() -> T) -> T {
+        self.acquire();
+        let _release = Release(self);
+        f()
+    }
+
+    fn acquire(&self) {
+        let me = std::thread::current().id();
+        let mut owner = self.owner();
+        loop {
+            match owner.as_mut() {
+                None => {
+                    *owner = Some((me, 1));
+                    return;
+                }
+                Some((thread, count)) if *thread == me => {
+                    *count += 1;
+                    return;
+                }
+                Some(_) => {
+                    owner = self
+                        .released
+                        .wait(owner)
+                        .expect("Poisoned Mutex in keyring-rs: please report a bug!");
+                }
+            }
+        }
+    }
+
+    fn release(&self) {
+        let mut owner = self.owner();
+        if let Some((_, count)) = owner.as_mut() {
+            *count -= 1;
+            if *count == 0 {
+                *owner = None;
+                self.released.notify_one();
+            }
+        }
+    }
+
+    fn owner(&self) -> MutexGuard<'_, Option<(ThreadId, usize)>> {
+        self.owner
+            .lock()
+            .expect("Poisoned Mutex in keyring-rs: please report a bug!")
+    }
+}
+
+/// Releases a lock when dropped, even if the operation panics.
+struct Release<'a>(&'a AccessLock);
+
+impl Drop for Release<'_> {
+    fn drop(&mut self) {
+        self.0.release();
+    }
+}
+
+/// The lock shared by all the exclusive credentials in the process.
+static PROCESS_LOCK: LazyLock<Arc<AccessLock>> = LazyLock::new(Arc::default);
+
+/// A credential builder whose credentials take turns operating on their store.
+#[derive(Debug)]
+pub struct ExclusiveCredentialBuilder {
+    inner: Box<CredentialBuilder>,
+    lock: Arc<AccessLock>,
+}
+
+impl ExclusiveCredentialBuilder {
+    /// Create a builder whose credentials' operations (and searches)
+    /// are serialized with those of every other exclusive credential
+    /// in the process.
+    pub fn new(inner: Box<CredentialBuilder>) -> Self {
+        Self {
+            inner,
+            lock: PROCESS_LOCK.clone(),
+        }
+    }
+
+    /// Create a builder whose credentials' operations (and searches)
+    /// are serialized only with each other.
+    pub fn new_per_store(inner: Box<CredentialBuilder>) -> Self {
+        Self {
+            inner,
+            lock: Arc::default(),
+        }
+    }
+
+    /// The wrapped builder.
+    pub fn inner(&self) -> &CredentialBuilder {
+        self.inner.as_ref()
+    }
+
+    /// Whether this builder's credentials share the process-wide lock.
+    pub fn is_process_wide(&self) -> bool {
+        Arc::ptr_eq(&self.lock, &PROCESS_LOCK)
+    }
+}
+
+impl CredentialBuilderApi for ExclusiveCredentialBuilder {
+    /// Build a credential in the wrapped store, and serialize its operations.
+    fn build(&self, target: Option<&str>, service: &str, user: &str) -> Result<Box<Credential>> {
+        let inner = self.inner.build(target, service, user)?;
+        Ok(Box::new(ExclusiveCredential {
+            inner,
+            lock: self.lock.clone(),
+        }))
+    }
+
+    /// The at-rest encryption of the wrapped store.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        self.inner.at_rest_encryption()
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    /// The persistence of the wrapped store.
+    fn persistence(&self) -> CredentialPersistence {
+        self.inner.persistence()
+    }
+
+    /// Search the wrapped store while holding the lock.
+    fn search(&self, spec: &SearchSpec) -> Result<Vec<Box<Credential>>> {
+        self.lock.run(|| self.inner.search(spec))
+    }
+
+    /// The info of the wrapped store.
+    fn info(&self) -> CredentialStoreInfo {
+        self.inner.info()
+    }
+}
+
+/// A credential whose operations hold a lock while they run.
+#[derive(Debug)]
+pub struct ExclusiveCredential {
+    inner: Box<Credential>,
+    lock: Arc<AccessLock>,
+}
+
+impl ExclusiveCredential {
+    /// The wrapped credential.
+    pub fn inner(&self) -> &Credential {
+        self.inner.as_ref()
+    }
+}
+
+impl CredentialApi for ExclusiveCredential {
+    fn set_password(&self, password: &str) -> Result<()> {
+        self.lock.run(|| self.inner.set_password(password))
+    }
+
+    fn set_secret(&self, secret: &[u8]) -> Result<()> {
+        self.lock.run(|| self.inner.set_secret(secret))
+    }
+
+    fn get_password(&self) -> Result<String> {
+        self.lock.run(|| self.inner.get_password())
+    }
+
+    fn get_secret(&self) -> Result<Vec<u8>> {
+        self.lock.run(|| self.inner.get_secret())
+    }
+
+    fn exists(&self) -> Result<bool> {
+        self.lock.run(|| self.inner.exists())
+    }
+
+    fn get_password_or_set(&self, password: &str) -> Result<String> {
+        self.lock.run(|| self.inner.get_password_or_set(password))
+    }
+
+    fn get_secret_or_set(&self, secret: &[u8]) -> Result<Vec<u8>> {
+        self.lock.run(|| self.inner.get_secret_or_set(secret))
+    }
+
+    fn compare_and_set_password(&self, expected: &str, password: &str) -> Result<()> {
+        self.lock
+            .run(|| self.inner.compare_and_set_password(expected, password))
+    }
+
+    fn compare_and_set_secret(&self, expected: &[u8], secret: &[u8]) -> Result<()> {
+        self.lock
+            .run(|| self.inner.compare_and_set_secret(expected, secret))
+    }
+
+    fn get_attributes(&self) -> Result<AttributeMap<String, String>> {
+        self.lock.run(|| self.inner.get_attributes())
+    }
+
+    fn update_attributes(&self, attributes: &AttributeMap<&str, &str>) -> Result<()> {
+        self.lock.run(|| self.inner.update_attributes(attributes))
+    }
+
+    fn get_metadata(&self) -> Result<CredentialMetadata> {
+        self.lock.run(|| self.inner.get_metadata())
+    }
+
+    fn get_label(&self) -> Result<String> {
+        self.lock.run(|| self.inner.get_label())
+    }
+
+    fn set_label(&self, label: &str) -> Result<()> {
+        self.lock.run(|| self.inner.set_label(label))
+    }
+
+    fn set_expiration(&self, ttl: Duration) -> Result<()> {
+        self.lock.run(|| self.inner.set_expiration(ttl))
+    }
+
+    fn unlock(&self) -> Result<()> {
+        self.lock.run(|| self.inner.unlock())
+    }
+
+    fn max_secret_len(&self) -> Option<usize> {
+        self.inner.max_secret_len()
+    }
+
+    fn spec(&self) -> Option<CredentialSpec> {
+        self.inner.spec()
+    }
+
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        self.inner.at_rest_encryption()
+    }
+
+    fn delete_credential(&self) -> Result<()> {
+        self.lock.run(|| self.inner.delete_credential())
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::ExclusiveCredentialBuilder;
+    use crate::credential::CredentialBuilderApi;
+    use crate::mock::MockCredentialBuilder;
+    use crate::{Entry, tests::generate_random_string};
+
+    #[test]
+    fn test_exclusive() {
+        let name = generate_random_string();
+        let faulty = MockCredentialBuilder::shared()
+            .with_delay(Duration::from_millis(20))
+            .with_concurrency_faults(true);
+        let builder = ExclusiveCredentialBuilder::new_per_store(Box::new(faulty));
+        assert!(!builder.is_process_wide());
+        let entries: Vec<_> = (0..4)
+            .map(|_| Entry::new_with_credential(builder.build(None, &name, "user").unwrap()))
+            .collect();
+        std::thread::scope(|scope| {
+            for entry in &entries {
+                scope.spawn(|| {
+                    for _ in 0..3 {
+                        entry.set_password("serialized").unwrap();
+                        assert_eq!(entry.get_password().unwrap(), "serialized");
+                    }
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn test_reentrant() {
+        let inner = ExclusiveCredentialBuilder::new(Box::new(MockCredentialBuilder::shared()));
+        let builder = ExclusiveCredentialBuilder::new(Box::new(inner));
+        assert!(builder.is_process_wide());
+        let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+        entry.set_password("nested").unwrap();
+        assert_eq!(entry.get_password().unwrap(), "nested");
+    }
+}
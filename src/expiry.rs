@@ -0,0 +1,377 @@
+/*!
+
+# Secret expiry
+
+Some secrets (such as short-lived tokens, or keys that policy says must be
+replaced every so often) shouldn't be used after a deadline, even if they're
+still in the store. The [ExpiringCredentialBuilder] in this module wraps
+another builder, and the credentials it builds refuse to return their
+secrets once their deadline has passed: reads return an
+[Expired](Error::Expired) error instead. Deadlines are set with
+[Entry::set_expiry] (or [ExpiringCredential::set_expiry]).
+
+```
+# use std::time::{Duration, SystemTime};
+# use keyring::{Entry, Error, credential::CredentialBuilderApi, memory::MemoryCredentialBuilder};
+# use keyring::expiry::ExpiringCredentialBuilder;
+let builder = ExpiringCredentialBuilder::new(Box::new(MemoryCredentialBuilder::default()));
+let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+entry.set_password("short-lived token").unwrap();
+entry.set_expiry(SystemTime::now() + Duration::from_secs(3600)).unwrap();
+assert_eq!(entry.get_password().unwrap(), "short-lived token");
+entry.set_expiry(SystemTime::now() - Duration::from_secs(1)).unwrap();
+assert!(matches!(entry.get_password(), Err(Error::Expired)));
+```
+
+The deadline is kept (as a count of seconds since the Unix epoch) in the
+credential's `keyring-expiry` attribute, just as [versions](crate::versioned)
+are kept in an attribute, so expiry only works with credential stores that
+allow arbitrary attributes to be set on credentials, such as the
+secret-service and [memory](crate::memory) stores. On other stores,
+setting a deadline returns a [NotSupportedByStore](Error::NotSupportedByStore)
+error. Stores that can expire credentials themselves
+(see [set_expiration](crate::Entry::set_expiration)) are also asked to
+remove the credential at the deadline, and once they have, reads return a
+[NoEntry](Error::NoEntry) error rather than an `Expired` one.
+
+An expired credential is left in the store (so it can be inspected, given
+a new deadline, or deleted), and setting its secret doesn't change its
+deadline. Since deadlines are checked by this crate, credentials read
+without an expiring builder (or by other apps) are never expired.
+ */
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::Entry;
+use super::credential::{
+    AtRestStatus, AttributeMap, Credential, CredentialApi, CredentialBuilder, CredentialBuilderApi,
+    CredentialMetadata, CredentialPersistence, CredentialSpec, CredentialStoreInfo, SearchSpec,
+};
+use super::error::{Error, Result};
+
+/// The name of the attribute that holds a credential's deadline.
+pub const EXPIRY_ATTRIBUTE: &str = "keyring-expiry";
+
+/// A credential builder whose credentials can expire.
+#[derive(Debug)]
+pub struct ExpiringCredentialBuilder {
+    inner: Box<CredentialBuilder>,
+}
+
+impl ExpiringCredentialBuilder {
+    /// Create a builder whose credentials are built by `inner`,
+    /// and whose reads fail once the credential has expired.
+    pub fn new(inner: Box<CredentialBuilder>) -> Self {
+        Self { inner }
+    }
+
+    /// The wrapped builder.
+    pub fn inner(&self) -> &CredentialBuilder {
+        self.inner.as_ref()
+    }
+}
+
+impl CredentialBuilderApi for ExpiringCredentialBuilder {
+    /// Build a credential in the wrapped store that can expire.
+    fn build(&self, target: Option<&str>, service: &str, user: &str) -> Result<Box<Credential>> {
+        let inner = self.inner.build(target, service, user)?;
+        Ok(Box::new(ExpiringCredential::new(inner)))
+    }
+
+    /// The at-rest encryption of the wrapped store.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        self.inner.at_rest_encryption()
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    /// The persistence of the wrapped store.
+    fn persistence(&self) -> CredentialPersistence {
+        self.inner.persistence()
+    }
+
+    /// Search the wrapped store, and make the found credentials expire.
+    ///
+    /// Expired credentials are found, too.
+    fn search(&self, spec: &SearchSpec) -> Result<Vec<Box<Credential>>> {
+        let found = self.inner.search(spec)?;
+        Ok(found
+            .into_iter()
+            .map(|cred| Box::new(ExpiringCredential::new(cred)) as Box<Credential>)
+            .collect())
+    }
+
+    /// The info of the wrapped store.
+    fn info(&self) -> CredentialStoreInfo {
+        self.inner.info()
+    }
+}
+
+/// A credential whose secret can't be read after its deadline.
+#[derive(Debug)]
+pub struct ExpiringCredential {
+    inner: Box<Credential>,
+}
+
+impl ExpiringCredential {
+    /// Make a credential expire at the deadline in its attributes.
+    pub fn new(inner: Box<Credential>) -> Self {
+        Self { inner }
+    }
+
+    /// The wrapped credential.
+    pub fn inner(&self) -> &Credential {
+        self.inner.as_ref()
+    }
+
+    /// Set the time after which this credential's secret can't be read.
+    ///
+    /// Returns a [NoEntry](Error::NoEntry) error if there isn't a credential,
+    /// and a [NotSupportedByStore](Error::NotSupportedByStore) error if
+    /// the store can't keep the deadline. See the module header for how
+    /// deadlines are kept.
+    pub fn set_expiry(&self, deadline: SystemTime) -> Result<()> {
+        let seconds = deadline
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        self.set_expiry_attribute(&seconds.to_string())?;
+        if self.get_expiry()? != Some(UNIX_EPOCH + Duration::from_secs(seconds)) {
+            return Err(Error::NotSupportedByStore("expiry".to_string()));
+        }
+        // stores that can expire credentials natively are asked to, too
+        let ttl = deadline
+            .duration_since(SystemTime::now())
+            .unwrap_or_default();
+        match self.inner.set_expiration(ttl) {
+            Ok(()) | Err(Error::NotSupportedByStore(_)) => Ok(()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Remove this credential's deadline, so it never expires.
+    ///
+    /// (A deadline that a store has been asked to enforce natively
+    /// can't be removed this way.)
+    pub fn clear_expiry(&self) -> Result<()> {
+        self.set_expiry_attribute("")
+    }
+
+    /// The time after which this credential's secret can't be read, if any.
+    ///
+    /// Returns a [NoEntry](Error::NoEntry) error if there isn't a credential,
+    /// and an [Invalid](Error::Invalid) error if its deadline attribute
+    /// isn't a number.
+    pub fn get_expiry(&self) -> Result<Option<SystemTime>> {
+        match self.inner.get_attributes()?.get(EXPIRY_ATTRIBUTE) {
+            Some(value) if !value.is_empty() => {
+                let seconds: u64 = value
+                    .parse()
+                    .map_err(|_| Error::Invalid(EXPIRY_ATTRIBUTE.to_string(), value.to_string()))?;
+                Ok(Some(UNIX_EPOCH + Duration::from_secs(seconds)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn set_expiry_attribute(&self, value: &str) -> Result<()> {
+        let attributes = AttributeMap::from([(EXPIRY_ATTRIBUTE, value)]);
+        self.inner.update_attributes(&attributes)
+    }
+
+    /// Fail with an [Expired](Error::Expired) error if the deadline has passed.
+    fn check_expiry(&self) -> Result<()> {
+        match self.get_expiry()? {
+            Some(deadline) if SystemTime::now() >= deadline => Err(Error::Expired),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl CredentialApi for ExpiringCredential {
+    fn set_password(&self, password: &str) -> Result<()> {
+        self.inner.set_password(password)
+    }
+
+    fn set_secret(&self, secret: &[u8]) -> Result<()> {
+        self.inner.set_secret(secret)
+    }
+
+    /// Retrieve the password, unless the credential has expired.
+    fn get_password(&self) -> Result<String> {
+        self.check_expiry()?;
+        self.inner.get_password()
+    }
+
+    /// Retrieve the secret, unless the credential has expired.
+    fn get_secret(&self) -> Result<Vec<u8>> {
+        self.check_expiry()?;
+        self.inner.get_secret()
+    }
+
+    /// Update the password, unless the credential has expired.
+    fn compare_and_set_password(&self, expected: &str, password: &str) -> Result<()> {
+        self.check_expiry()?;
+        self.inner.compare_and_set_password(expected, password)
+    }
+
+    /// Update the secret, unless the credential has expired.
+    fn compare_and_set_secret(&self, expected: &[u8], secret: &[u8]) -> Result<()> {
+        self.check_expiry()?;
+        self.inner.compare_and_set_secret(expected, secret)
+    }
+
+    fn exists(&self) -> Result<bool> {
+        self.inner.exists()
+    }
+
+    fn get_attributes(&self) -> Result<AttributeMap<String, String>> {
+        self.inner.get_attributes()
+    }
+
+    fn update_attributes(&self, attributes: &AttributeMap<&str, &str>) -> Result<()> {
+        self.inner.update_attributes(attributes)
+    }
+
+    fn get_metadata(&self) -> Result<CredentialMetadata> {
+        self.inner.get_metadata()
+    }
+
+    fn get_label(&self) -> Result<String> {
+        self.inner.get_label()
+    }
+
+    fn set_label(&self, label: &str) -> Result<()> {
+        self.inner.set_label(label)
+    }
+
+    fn set_expiration(&self, ttl: Duration) -> Result<()> {
+        self.inner.set_expiration(ttl)
+    }
+
+    fn unlock(&self) -> Result<()> {
+        self.inner.unlock()
+    }
+
+    fn max_secret_len(&self) -> Option<usize> {
+        self.inner.max_secret_len()
+    }
+
+    fn spec(&self) -> Option<CredentialSpec> {
+        self.inner.spec()
+    }
+
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        self.inner.at_rest_encryption()
+    }
+
+    fn delete_credential(&self) -> Result<()> {
+        self.inner.delete_credential()
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+impl Entry {
+    /// Set the time after which the secret of this entry's credential can't be read.
+    ///
+    /// Returns a [NotSupportedByStore](Error::NotSupportedByStore) error
+    /// if the credential wasn't built by an [ExpiringCredentialBuilder].
+    /// See [ExpiringCredential::set_expiry] for the details.
+    pub fn set_expiry(&self, deadline: SystemTime) -> Result<()> {
+        self.expiring()?.set_expiry(deadline)
+    }
+
+    /// The time after which the secret of this entry's credential can't be read, if any.
+    ///
+    /// Returns a [NotSupportedByStore](Error::NotSupportedByStore) error
+    /// if the credential wasn't built by an [ExpiringCredentialBuilder].
+    /// See [ExpiringCredential::get_expiry] for the details.
+    pub fn get_expiry(&self) -> Result<Option<SystemTime>> {
+        self.expiring()?.get_expiry()
+    }
+
+    /// Remove the deadline of this entry's credential, so it never expires.
+    ///
+    /// Returns a [NotSupportedByStore](Error::NotSupportedByStore) error
+    /// if the credential wasn't built by an [ExpiringCredentialBuilder].
+    pub fn clear_expiry(&self) -> Result<()> {
+        self.expiring()?.clear_expiry()
+    }
+
+    fn expiring(&self) -> Result<&ExpiringCredential> {
+        self.get_credential()
+            .downcast_ref()
+            .ok_or_else(|| Error::NotSupportedByStore("expiry".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use super::ExpiringCredentialBuilder;
+    use crate::credential::CredentialBuilderApi;
+    use crate::memory::MemoryCredentialBuilder;
+    use crate::mock::MockCredentialBuilder;
+    use crate::tests::CasOnlyCredentialBuilder;
+    use crate::{Entry, Error};
+
+    #[test]
+    fn test_expiry() {
+        let store = MemoryCredentialBuilder::default();
+        let builder = ExpiringCredentialBuilder::new(Box::new(store.clone()));
+        let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+        let deadline = SystemTime::now() + Duration::from_secs(3600);
+        assert!(matches!(entry.set_expiry(deadline), Err(Error::NoEntry)));
+        entry.set_password("expiring").unwrap();
+        assert_eq!(entry.get_expiry().unwrap(), None);
+        entry.set_expiry(deadline).unwrap();
+        let kept = entry.get_expiry().unwrap().unwrap();
+        assert!(deadline.duration_since(kept).unwrap() < Duration::from_secs(1));
+        assert_eq!(entry.get_password().unwrap(), "expiring");
+
+        entry.set_expiry(SystemTime::now()).unwrap();
+        assert!(matches!(entry.get_password(), Err(Error::Expired)));
+        assert!(matches!(entry.get_secret(), Err(Error::Expired)));
+        // the expired secret is still there, and can be replaced or revived
+        let raw = store.build(None, "service", "user").unwrap();
+        assert_eq!(raw.get_password().unwrap(), "expiring");
+        entry.set_password("renewed").unwrap();
+        assert!(matches!(entry.get_password(), Err(Error::Expired)));
+        entry.clear_expiry().unwrap();
+        assert_eq!(entry.get_password().unwrap(), "renewed");
+        assert_eq!(entry.get_expiry().unwrap(), None);
+    }
+
+    #[test]
+    fn test_store_without_attributes() {
+        let builder = ExpiringCredentialBuilder::new(Box::new(MockCredentialBuilder::default()));
+        let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+        entry.set_password("secret").unwrap();
+        assert!(matches!(
+            entry.set_expiry(SystemTime::now()),
+            Err(Error::NotSupportedByStore(_))
+        ));
+        assert_eq!(entry.get_password().unwrap(), "secret");
+    }
+
+    #[test]
+    fn test_not_expiring() {
+        let store = MemoryCredentialBuilder::default();
+        let entry = Entry::new_with_credential(store.build(None, "service", "user").unwrap());
+        assert!(matches!(
+            entry.set_expiry(SystemTime::now()),
+            Err(Error::NotSupportedByStore(_))
+        ));
+    }
+
+    #[test]
+    fn test_compare_and_set() {
+        let builder = ExpiringCredentialBuilder::new(Box::new(CasOnlyCredentialBuilder::default()));
+        crate::tests::test_forwards_compare_and_set(&builder);
+    }
+}
@@ -0,0 +1,554 @@
+/*!
+
+# Encrypted file credential store
+
+This is a portable credential store that keeps all of its credentials in
+a single encrypted file. It's meant for machines that have no platform
+store to speak of, such as headless servers and containers, where the
+only other choice would be a store that doesn't persist. It's available
+when the `file-store` feature is enabled, and it's pure Rust.
+
+The file is encrypted and authenticated with the [crypto](crate::crypto)
+module, under a 32-byte key that the client supplies when creating the
+builder. Where that key comes from is up to the client: for example, it
+can be read from a file that only the service account can read, taken
+from a secret mounted into a container, or derived (with
+[derive_key](crate::crypto::derive_key)) from a high-entropy secret kept
+in some other store. If the key has to come from a password that a person
+has chosen, derive it with
+[derive_key_from_passphrase](crate::crypto::derive_key_from_passphrase)
+(PBKDF2), with a salt kept next to the file. Never use such a password as
+the key, or derive the key from it with `derive_key`, which is not
+a password hash.
+```
+# use keyring::{Entry, credential::CredentialBuilderApi, file_store::FileCredentialBuilder};
+# let path = std::env::temp_dir().join("keyring-file-store-doc");
+let key = [7u8; 32]; // use a real key!
+let builder = FileCredentialBuilder::new(&path, &key);
+let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+entry.set_password("in a file").unwrap();
+let reopened = FileCredentialBuilder::new(&path, &key);
+let entry = Entry::new_with_credential(reopened.build(None, "service", "user").unwrap());
+assert_eq!(entry.get_password().unwrap(), "in a file");
+# std::fs::remove_file(&path).unwrap();
+```
+
+Credentials are identified by their target, service, and user, exactly
+as given, and each can carry arbitrary attributes (its
+[label](crate::Entry::get_label) is kept in the `label` attribute).
+The store supports [search](crate::credential::CredentialBuilderApi::search).
+
+Every operation reads the whole file, and every change rewrites it
+(to a temporary file next to it, named for the process, which then
+replaces it), so this store
+suits tens or hundreds of credentials, not millions. The file doesn't
+exist until the first credential is stored, and a missing file is an empty
+store. A file that was encrypted with a different key (or that has been
+tampered with) produces an [Invalid](crate::Error::Invalid) error.
+On unix, the file is only readable and writable by its owner.
+
+Changes made through builders for the same file in one process are
+serialized, as long as the builders are clones of each other.
+There is _no_ locking between processes (or unrelated builders):
+each change reads the file, changes its copy, and replaces the file,
+so when two processes change the store at the same time, the change
+that finishes last silently discards the other one. (The file itself is
+never left half-written.) Apps whose processes share a store file must
+make sure that only one of them changes it at a time.
+ */
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use zeroize::Zeroizing;
+
+use super::credential::{
+    AtRestStatus, AttributeMap, Credential, CredentialApi, CredentialBuilderApi,
+    CredentialPersistence, CredentialSpec, CredentialStoreInfo, SearchSpec,
+};
+use super::crypto::{KEY_LEN, open, seal};
+use super::error::{Error, Result, decode_password};
+
+type Key = (Option<String>, String, String);
+
+/// The stored data for a credential.
+#[derive(Debug, Default)]
+struct StoredData {
+    secret: Zeroizing<Vec<u8>>,
+    attributes: AttributeMap<String, String>,
+}
+
+type Contents = BTreeMap<Key, StoredData>;
+
+/// The magic number and format version at the start of the decrypted contents.
+const MAGIC: &[u8] = b"keyring-rs file store 1\n";
+
+/// The file that holds a store's credentials.
+struct StoreFile {
+    path: PathBuf,
+    key: Zeroizing<[u8; KEY_LEN]>,
+    lock: Mutex<()>,
+}
+
+impl StoreFile {
+    /// Read the file's contents and pass them to `f`.
+    fn read<T>(&self, f: impl FnOnce(&Contents) -> Result<T>) -> Result<T> {
+        let _guard = self.lock();
+        f(&self.load()?)
+    }
+
+    /// Read the file's contents, pass them to `f` for updating,
+    /// and write them back if `f` succeeds.
+    fn update<T>(&self, f: impl FnOnce(&mut Contents) -> Result<T>) -> Result<T> {
+        let _guard = self.lock();
+        let mut contents = self.load()?;
+        let result = f(&mut contents)?;
+        self.save(&contents)?;
+        Ok(result)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, ()> {
+        self.lock
+            .lock()
+            .expect("Poisoned file store lock in keyring-rs: please report a bug!")
+    }
+
+    fn load(&self) -> Result<Contents> {
+        let sealed = match std::fs::read(&self.path) {
+            Ok(sealed) => sealed,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Contents::new());
+            }
+            Err(err) => return Err(Error::PlatformFailure(Box::new(err))),
+        };
+        let plaintext = Zeroizing::new(open(&sealed, &self.key)?);
+        decode(&plaintext).ok_or_else(|| {
+            Error::Invalid(
+                self.path.display().to_string(),
+                "is not a credential file".to_string(),
+            )
+        })
+    }
+
+    fn save(&self, contents: &Contents) -> Result<()> {
+        let sealed = seal(&encode(contents), &self.key);
+        let mut temp = self.path.clone().into_os_string();
+        temp.push(format!(".{}.tmp", std::process::id()));
+        let temp = PathBuf::from(temp);
+        write_private(&temp, &sealed)
+            .and_then(|_| std::fs::rename(&temp, &self.path))
+            .map_err(|err| Error::PlatformFailure(Box::new(err)))
+    }
+}
+
+/// Write a file that only its owner can read and write.
+fn write_private(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)?;
+    file.write_all(data)?;
+    file.sync_all()
+}
+
+/// Serialize the contents. Strings and byte strings are written with
+/// a 4-byte little-endian length, followed by their bytes.
+fn encode(contents: &Contents) -> Zeroizing<Vec<u8>> {
+    fn put(buf: &mut Vec<u8>, bytes: &[u8]) {
+        buf.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+        buf.extend_from_slice(bytes);
+    }
+    let mut buf = Zeroizing::new(MAGIC.to_vec());
+    buf.extend_from_slice(&(contents.len() as u32).to_le_bytes());
+    for ((target, service, user), data) in contents {
+        match target {
+            Some(target) => {
+                buf.push(1);
+                put(&mut buf, target.as_bytes());
+            }
+            None => buf.push(0),
+        }
+        put(&mut buf, service.as_bytes());
+        put(&mut buf, user.as_bytes());
+        put(&mut buf, &data.secret);
+        buf.extend_from_slice(&(data.attributes.len() as u32).to_le_bytes());
+        for (key, value) in &data.attributes {
+            put(&mut buf, key.as_bytes());
+            put(&mut buf, value.as_bytes());
+        }
+    }
+    buf
+}
+
+/// Deserialize the contents written by [encode], if they are well-formed.
+fn decode(buf: &[u8]) -> Option<Contents> {
+    struct Reader<'a>(&'a [u8]);
+
+    impl<'a> Reader<'a> {
+        fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+            if self.0.len() < len {
+                return None;
+            }
+            let (taken, rest) = self.0.split_at(len);
+            self.0 = rest;
+            Some(taken)
+        }
+
+        fn count(&mut self) -> Option<usize> {
+            Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?) as usize)
+        }
+
+        fn bytes(&mut self) -> Option<&'a [u8]> {
+            let len = self.count()?;
+            self.take(len)
+        }
+
+        fn string(&mut self) -> Option<String> {
+            String::from_utf8(self.bytes()?.to_vec()).ok()
+        }
+    }
+
+    let mut reader = Reader(buf.strip_prefix(MAGIC)?);
+    let mut contents = Contents::new();
+    for _ in 0..reader.count()? {
+        let target = match reader.take(1)? {
+            [0] => None,
+            [1] => Some(reader.string()?),
+            _ => return None,
+        };
+        let key = (target, reader.string()?, reader.string()?);
+        let secret = Zeroizing::new(reader.bytes()?.to_vec());
+        let mut attributes = AttributeMap::new();
+        for _ in 0..reader.count()? {
+            attributes.insert(reader.string()?, reader.string()?);
+        }
+        contents.insert(key, StoredData { secret, attributes });
+    }
+    reader.0.is_empty().then_some(contents)
+}
+
+/// A credential in an encrypted file store.
+#[derive(Clone)]
+pub struct FileCredential {
+    key: Key,
+    file: Arc<StoreFile>,
+}
+
+impl CredentialApi for FileCredential {
+    /// Set the secret on this credential, creating it if necessary.
+    fn set_secret(&self, secret: &[u8]) -> Result<()> {
+        self.file.update(|contents| {
+            let data = contents.entry(self.key.clone()).or_default();
+            data.secret = Zeroizing::new(secret.to_vec());
+            Ok(())
+        })
+    }
+
+    /// Get the secret from this credential, if it exists.
+    fn get_secret(&self) -> Result<Vec<u8>> {
+        self.file.read(|contents| match contents.get(&self.key) {
+            Some(data) => Ok(data.secret.to_vec()),
+            None => Err(Error::NoEntry),
+        })
+    }
+
+    /// Whether this credential is in the file.
+    fn exists(&self) -> Result<bool> {
+        self.file
+            .read(|contents| Ok(contents.contains_key(&self.key)))
+    }
+
+    /// Get the password from this credential, or set it if the credential
+    /// doesn't exist (atomically, within this process).
+    fn get_password_or_set(&self, password: &str) -> Result<String> {
+        decode_password(self.get_secret_or_set(password.as_bytes())?)
+    }
+
+    /// Get the secret from this credential, or set it if the credential
+    /// doesn't exist (atomically, within this process).
+    fn get_secret_or_set(&self, secret: &[u8]) -> Result<Vec<u8>> {
+        self.file.update(|contents| {
+            let data = contents
+                .entry(self.key.clone())
+                .or_insert_with(|| StoredData {
+                    secret: Zeroizing::new(secret.to_vec()),
+                    ..Default::default()
+                });
+            Ok(data.secret.to_vec())
+        })
+    }
+
+    /// Set the password on this credential, if it exists and has the
+    /// expected password (atomically, within this process).
+    fn compare_and_set_password(&self, expected: &str, password: &str) -> Result<()> {
+        self.compare_and_set_secret(expected.as_bytes(), password.as_bytes())
+    }
+
+    /// Set the secret on this credential, if it exists and has the
+    /// expected secret (atomically, within this process).
+    fn compare_and_set_secret(&self, expected: &[u8], secret: &[u8]) -> Result<()> {
+        self.file
+            .update(|contents| match contents.get_mut(&self.key) {
+                Some(data) if data.secret.as_slice() == expected => {
+                    data.secret = Zeroizing::new(secret.to_vec());
+                    Ok(())
+                }
+                Some(_) => Err(Error::Conflict),
+                None => Err(Error::NoEntry),
+            })
+    }
+
+    /// Get the attributes on this credential, if it exists.
+    fn get_attributes(&self) -> Result<AttributeMap<String, String>> {
+        self.file.read(|contents| match contents.get(&self.key) {
+            Some(data) => Ok(data.attributes.clone()),
+            None => Err(Error::NoEntry),
+        })
+    }
+
+    /// Update the attributes on this credential, if it exists.
+    ///
+    /// Any attribute name can be given a value.
+    fn update_attributes(&self, attributes: &AttributeMap<&str, &str>) -> Result<()> {
+        self.file
+            .update(|contents| match contents.get_mut(&self.key) {
+                Some(data) => {
+                    for (key, value) in attributes {
+                        data.attributes.insert(key.to_string(), value.to_string());
+                    }
+                    Ok(())
+                }
+                None => Err(Error::NoEntry),
+            })
+    }
+
+    /// The label is kept in the `label` attribute (and is empty if that isn't set).
+    fn get_label(&self) -> Result<String> {
+        let attributes = self.get_attributes()?;
+        Ok(attributes.get("label").cloned().unwrap_or_default())
+    }
+
+    /// The label is kept in the `label` attribute.
+    fn set_label(&self, label: &str) -> Result<()> {
+        self.update_attributes(&AttributeMap::from([("label", label)]))
+    }
+
+    /// Delete this credential, if it exists.
+    fn delete_credential(&self) -> Result<()> {
+        self.file
+            .update(|contents| match contents.remove(&self.key) {
+                Some(_) => Ok(()),
+                None => Err(Error::NoEntry),
+            })
+    }
+
+    /// The target, service, and user this credential was built with.
+    fn spec(&self) -> Option<CredentialSpec> {
+        let (target, service, user) = self.key.clone();
+        Some(CredentialSpec {
+            target,
+            service,
+            user,
+        })
+    }
+
+    /// This store encrypts its file.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        AtRestStatus::Encrypted
+    }
+
+    /// Return this credential with an `Any` type so it can be downcast.
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    /// Expose the concrete debug formatter for use via the [Credential] trait
+    fn debug_fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self, f)
+    }
+}
+
+impl std::fmt::Debug for FileCredential {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FileCredential")
+            .field("key", &self.key)
+            .field("path", &self.file.path)
+            .finish()
+    }
+}
+
+/// The builder for encrypted file credentials.
+///
+/// Clones of a builder share its file (and its lock).
+#[derive(Clone)]
+pub struct FileCredentialBuilder {
+    file: Arc<StoreFile>,
+}
+
+impl FileCredentialBuilder {
+    /// Create a builder for the store in the file at `path`, encrypted with `key`.
+    ///
+    /// The file isn't read until a credential is used, so this can't fail.
+    pub fn new(path: impl AsRef<Path>, key: &[u8; KEY_LEN]) -> Self {
+        Self {
+            file: Arc::new(StoreFile {
+                path: path.as_ref().to_path_buf(),
+                key: Zeroizing::new(*key),
+                lock: Mutex::new(()),
+            }),
+        }
+    }
+
+    /// The path of this builder's file.
+    pub fn path(&self) -> &Path {
+        &self.file.path
+    }
+}
+
+impl std::fmt::Debug for FileCredentialBuilder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FileCredentialBuilder")
+            .field("path", &self.file.path)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CredentialBuilderApi for FileCredentialBuilder {
+    /// Build a credential for the given target, service, and user.
+    ///
+    /// The credential isn't stored until its secret is set.
+    fn build(&self, target: Option<&str>, service: &str, user: &str) -> Result<Box<Credential>> {
+        Ok(Box::new(FileCredential {
+            key: (
+                target.map(|t| t.to_string()),
+                service.to_string(),
+                user.to_string(),
+            ),
+            file: self.file.clone(),
+        }))
+    }
+
+    /// This store encrypts its file.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        AtRestStatus::Encrypted
+    }
+
+    /// Return the underlying builder object with an `Any` type so that it can
+    /// be downgraded to a [FileCredentialBuilder] for platform-specific processing.
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    /// This store keeps credentials in a file until they are deleted.
+    fn persistence(&self) -> CredentialPersistence {
+        CredentialPersistence::UntilDelete
+    }
+
+    /// Return a credential for each stored credential that matches the spec.
+    fn search(&self, spec: &SearchSpec) -> Result<Vec<Box<Credential>>> {
+        let mut keys: Vec<Key> = self.file.read(|contents| {
+            Ok(contents
+                .keys()
+                .filter(|(target, service, user)| spec.matches(target.as_deref(), service, user))
+                .cloned()
+                .collect())
+        })?;
+        if !spec.unsorted {
+            keys.sort_by(|(t1, s1, u1), (t2, s2, u2)| (s1, u1, t1).cmp(&(s2, u2, t2)));
+        }
+        Ok(keys
+            .into_iter()
+            .map(|key| {
+                Box::new(FileCredential {
+                    key,
+                    file: self.file.clone(),
+                }) as Box<Credential>
+            })
+            .collect())
+    }
+
+    /// The store can be searched, and its format version is that of its file.
+    fn info(&self) -> CredentialStoreInfo {
+        CredentialStoreInfo::new("file-store", self.persistence(), self.at_rest_encryption())
+            .with_version("1")
+            .with_attributes(true)
+            .with_search(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FileCredentialBuilder;
+    use crate::credential::{CredentialBuilderApi, Match, SearchSpec};
+    use crate::{Entry, Error, tests::generate_random_string};
+
+    fn temp_path() -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("keyring-file-store-{}", generate_random_string()))
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let path = temp_path();
+        let key = [1u8; 32];
+        let builder = FileCredentialBuilder::new(&path, &key);
+        let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+        assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+        assert!(!path.exists(), "File was created by a read");
+        entry.set_password("file password").unwrap();
+        entry.set_label("My account").unwrap();
+        let raw = std::fs::read(&path).unwrap();
+        assert!(!raw.windows(13).any(|w| w == b"file password"));
+        // a new builder sees the stored credentials
+        let reopened = FileCredentialBuilder::new(&path, &key);
+        let targeted = reopened.build(Some("target"), "service", "other").unwrap();
+        targeted.set_secret(&[0, 1, 2]).unwrap();
+        let entry = Entry::new_with_credential(reopened.build(None, "service", "user").unwrap());
+        assert_eq!(entry.get_password().unwrap(), "file password");
+        assert_eq!(entry.get_label().unwrap(), "My account");
+        let spec = SearchSpec {
+            service: Match::Exact("service".to_string()),
+            ..Default::default()
+        };
+        let found: Vec<String> = builder
+            .search(&spec)
+            .unwrap()
+            .iter()
+            .map(|cred| cred.spec().unwrap().user)
+            .collect();
+        assert_eq!(found, ["other", "user"]);
+        entry.delete_credential().unwrap();
+        assert!(matches!(entry.delete_credential(), Err(Error::NoEntry)));
+        assert_eq!(targeted.get_secret().unwrap(), [0, 1, 2]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_get_or_set() {
+        let path = temp_path();
+        let builder = FileCredentialBuilder::new(&path, &[1u8; 32]);
+        let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+        let secret = entry.get_secret_or_set_with(|| vec![1, 2, 3]).unwrap();
+        assert_eq!(secret, [1, 2, 3]);
+        let secret = entry.get_secret_or_set_with(|| vec![4, 5, 6]).unwrap();
+        assert_eq!(secret, [1, 2, 3]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_wrong_key() {
+        let path = temp_path();
+        let builder = FileCredentialBuilder::new(&path, &[1u8; 32]);
+        let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+        entry.set_password("keyed").unwrap();
+        let other = FileCredentialBuilder::new(&path, &[2u8; 32]);
+        let entry = Entry::new_with_credential(other.build(None, "service", "user").unwrap());
+        assert!(matches!(entry.get_password(), Err(Error::Invalid(_, _))));
+        assert!(matches!(
+            entry.set_password("other"),
+            Err(Error::Invalid(_, _))
+        ));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,206 @@
+/*!
+
+# Importing from other password stores
+
+Apps that adopt this crate often have users whose secrets are already kept
+somewhere else. This module helps move them into a credential store.
+
+## `pass`
+
+[import_from_pass] imports the secrets kept by
+[pass](https://www.passwordstore.org/), the standard unix password manager,
+which keeps each secret in a GPG-encrypted file under a directory tree
+(typically `~/.password-store`). This crate doesn't depend on GPG, so the
+caller supplies the function that decrypts each file (for example, by
+running `gpg --decrypt` on it).
+
+Each `.gpg` file in the tree becomes one credential: its directory (relative
+to the store, with `/` separators) is the service, and its name (without
+the `.gpg` extension) is the user, so `email/work/alice.gpg` becomes the
+credential for user `alice` of service `email/work`. Files at the top of
+the tree have an empty service. The secret is the whole decrypted
+file (by `pass` convention, the password is its first line), without its
+final newline. Existing credentials are overwritten.
+
+```no_run
+# use std::path::Path;
+# use keyring::{Error, import::import_from_pass, memory::MemoryCredentialBuilder};
+let decrypt = |path: &Path| {
+    let output = std::process::Command::new("gpg")
+        .args(["--quiet", "--batch", "--decrypt"])
+        .arg(path)
+        .output()
+        .map_err(|err| Error::PlatformFailure(Box::new(err)))?;
+    Ok(output.stdout)
+};
+let home = std::env::var("HOME").unwrap();
+let store = Path::new(&home).join(".password-store");
+let report = import_from_pass(&store, decrypt, &MemoryCredentialBuilder::default()).unwrap();
+for (path, err) in &report.failed {
+    eprintln!("Couldn't import {}: {err}", path.display());
+}
+```
+
+Entries are imported one at a time, as they are found, so a failure
+to decrypt or store one of them doesn't stop the import; failures
+are listed in the returned [ImportReport]. Hidden files and directories
+(such as the store's `.git` directory and its `.gpg-id` files) are skipped.
+ */
+use std::path::{Path, PathBuf};
+
+use zeroize::Zeroizing;
+
+use super::credential::{CredentialBuilderApi, CredentialSpec};
+use super::error::{Error, Result};
+
+/// The outcome of an import.
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    /// The credentials that were imported, in the order they were imported
+    pub imported: Vec<CredentialSpec>,
+    /// The files that couldn't be imported, with the reason
+    pub failed: Vec<(PathBuf, Error)>,
+}
+
+/// Import the secrets in a `pass` store into the store of the given builder.
+///
+/// See the module header for how files are mapped to credentials.
+/// Returns an error only if the store's directory tree can't be read;
+/// files that can't be decrypted or stored are listed in the report.
+pub fn import_from_pass(
+    store_dir: &Path,
+    gpg_decrypt: impl Fn(&Path) -> Result<Vec<u8>>,
+    target_builder: &dyn CredentialBuilderApi,
+) -> Result<ImportReport> {
+    let mut files = Vec::new();
+    find_pass_files(store_dir, &mut files)?;
+    let mut report = ImportReport::default();
+    for path in files {
+        let spec = pass_spec(store_dir, &path);
+        match import_one(&path, &spec, &gpg_decrypt, target_builder) {
+            Ok(()) => report.imported.push(spec),
+            Err(err) => report.failed.push((path, err)),
+        }
+    }
+    Ok(report)
+}
+
+fn import_one(
+    path: &Path,
+    spec: &CredentialSpec,
+    gpg_decrypt: &impl Fn(&Path) -> Result<Vec<u8>>,
+    target_builder: &dyn CredentialBuilderApi,
+) -> Result<()> {
+    let mut secret = Zeroizing::new(gpg_decrypt(path)?);
+    if secret.last() == Some(&b'\n') {
+        secret.pop();
+    }
+    let credential = target_builder.build(None, &spec.service, &spec.user)?;
+    credential.set_secret(&secret)
+}
+
+/// Collect the `.gpg` files in the tree at `dir`, skipping hidden entries,
+/// in a stable (sorted) order.
+fn find_pass_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let mut paths = std::fs::read_dir(dir)
+        .and_then(|entries| {
+            entries
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<std::io::Result<Vec<_>>>()
+        })
+        .map_err(|err| Error::PlatformFailure(Box::new(err)))?;
+    paths.sort();
+    for path in paths {
+        let hidden = path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+        if hidden {
+            continue;
+        }
+        if path.is_dir() {
+            find_pass_files(&path, files)?;
+        } else if path.extension().is_some_and(|ext| ext == "gpg") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// The credential for a `pass` file: its directory is the service,
+/// and its name is the user.
+fn pass_spec(store_dir: &Path, path: &Path) -> CredentialSpec {
+    let relative = path.strip_prefix(store_dir).unwrap_or(path);
+    let service = relative
+        .parent()
+        .map(|parent| {
+            parent
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/")
+        })
+        .unwrap_or_default();
+    let user = relative
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    CredentialSpec {
+        target: None,
+        service,
+        user,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::import_from_pass;
+    use crate::credential::{CredentialBuilderApi, SearchSpec};
+    use crate::memory::MemoryCredentialBuilder;
+    use crate::{Error, tests::generate_random_string};
+
+    #[test]
+    fn test_import_from_pass() {
+        let root = std::env::temp_dir().join(format!("keyring-pass-{}", generate_random_string()));
+        for dir in ["email/work", ".git"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for (file, content) in [
+            (
+                "email/work/alice.gpg",
+                "work password\nurl: mail.example.com\n",
+            ),
+            ("email/bob.gpg", "bob password\n"),
+            ("broken.gpg", ""),
+            (".gpg-id", "ABCDEF"),
+            (".git/config.gpg", "not a secret"),
+            ("notes.txt", "not a secret"),
+        ] {
+            std::fs::write(root.join(file), content).unwrap();
+        }
+        // the test files aren't encrypted, and empty ones "fail to decrypt"
+        let decrypt = |path: &Path| {
+            let content = std::fs::read(path).unwrap();
+            if content.is_empty() {
+                return Err(Error::Invalid("file".to_string(), "is empty".to_string()));
+            }
+            Ok(content)
+        };
+        let builder = MemoryCredentialBuilder::default();
+        let report = import_from_pass(&root, decrypt, &builder).expect("Can't import");
+        let imported: Vec<String> = report.imported.iter().map(|s| s.to_string()).collect();
+        assert_eq!(imported, ["email/bob", "email/work/alice"]);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, root.join("broken.gpg"));
+        let alice = builder.build(None, "email/work", "alice").unwrap();
+        assert_eq!(
+            alice.get_password().unwrap(),
+            "work password\nurl: mail.example.com"
+        );
+        let found = builder.search(&SearchSpec::default()).unwrap();
+        assert_eq!(found.len(), 2);
+        assert!(import_from_pass(&root.join("missing"), decrypt, &builder).is_err());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,360 @@
+/*!
+
+# Integrity checking
+
+Apps that make security decisions based on a stored value (an allow list,
+say, or the public key of a trusted server) need to know that nobody else
+has changed it, and most platform stores let any process running as the
+user replace any credential. The [IntegrityCredentialBuilder] in this module
+wraps another builder, and the credentials it builds store each secret with
+a tag (an HMAC-SHA256, see [mac](crate::crypto::mac)) computed under a key
+that the app holds. Reads check the tag, and return an
+[IntegrityFailure](Error::IntegrityFailure) error if the stored secret wasn't
+written, with that key, to that same credential. This module is available
+when the `client-crypto` feature is enabled.
+
+```
+# use keyring::{Entry, Error, credential::CredentialBuilderApi, memory::MemoryCredentialBuilder};
+# use keyring::integrity::IntegrityCredentialBuilder;
+let store = MemoryCredentialBuilder::default();
+let key = [7u8; 32]; // in real life, a random key kept somewhere else
+let builder = IntegrityCredentialBuilder::new(Box::new(store.clone()), &key);
+let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+entry.set_password("trusted").unwrap();
+assert_eq!(entry.get_password().unwrap(), "trusted");
+store.build(None, "service", "user").unwrap().set_password("tampered").unwrap();
+assert!(matches!(entry.get_password(), Err(Error::IntegrityFailure)));
+```
+
+The tag covers the credential's target, service, and user as well as its
+secret, so a tagged secret that's copied from another credential is caught
+too. Tags aren't encryption: the secret is stored as-is, followed by its
+32-byte tag, so apps that also want it hidden should use an
+[encrypting builder](crate::encrypted) (which authenticates the secrets
+it decrypts, too). Attributes and other metadata aren't covered by the tag.
+ */
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::time::Duration;
+
+use zeroize::Zeroizing;
+
+use super::credential::{
+    AtRestStatus, AttributeMap, Credential, CredentialApi, CredentialBuilder, CredentialBuilderApi,
+    CredentialMetadata, CredentialPersistence, CredentialSpec, CredentialStoreInfo, SearchSpec,
+};
+use super::crypto::{KEY_LEN, MAC_LEN, derive_key, identity_bytes, mac, verify_mac};
+use super::error::{Error, Result};
+
+/// A credential builder whose credentials check that their secrets
+/// haven't been changed behind their backs.
+pub struct IntegrityCredentialBuilder {
+    inner: Box<CredentialBuilder>,
+    key: Zeroizing<[u8; KEY_LEN]>,
+}
+
+impl IntegrityCredentialBuilder {
+    /// Create a builder that tags the secrets of the credentials
+    /// built by `inner` with the given key.
+    pub fn new(inner: Box<CredentialBuilder>, key: &[u8; KEY_LEN]) -> Self {
+        Self {
+            inner,
+            key: Zeroizing::new(*key),
+        }
+    }
+
+    /// Create a builder whose key is derived from a high-entropy secret
+    /// (see [derive_key](crate::crypto::derive_key)).
+    pub fn from_secret(inner: Box<CredentialBuilder>, secret: &[u8]) -> Self {
+        let key = derive_key(secret, b"", b"keyring-rs integrity");
+        Self { inner, key }
+    }
+
+    /// The wrapped builder.
+    pub fn inner(&self) -> &CredentialBuilder {
+        self.inner.as_ref()
+    }
+
+    /// Wrap a credential, tagging with its identity as the wrapped store
+    /// reports it (see [spec](CredentialApi::spec)), so that built and
+    /// found credentials agree even when the store normalizes targets.
+    fn wrap(&self, inner: Box<Credential>, spec: &CredentialSpec) -> IntegrityCredential {
+        IntegrityCredential {
+            inner,
+            key: self.key.clone(),
+            identity: identity_bytes(spec),
+        }
+    }
+}
+
+impl core::fmt::Debug for IntegrityCredentialBuilder {
+    /// Shows the wrapped builder, but never the key.
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("IntegrityCredentialBuilder")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CredentialBuilderApi for IntegrityCredentialBuilder {
+    /// Build a credential in the wrapped store that checks its secret.
+    fn build(&self, target: Option<&str>, service: &str, user: &str) -> Result<Box<Credential>> {
+        let inner = self.inner.build(target, service, user)?;
+        let spec = inner.spec().unwrap_or_else(|| CredentialSpec {
+            target: target.map(str::to_string),
+            service: service.to_string(),
+            user: user.to_string(),
+        });
+        Ok(Box::new(self.wrap(inner, &spec)))
+    }
+
+    /// The at-rest encryption of the wrapped store.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        self.inner.at_rest_encryption()
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+
+    /// The persistence of the wrapped store.
+    fn persistence(&self) -> CredentialPersistence {
+        self.inner.persistence()
+    }
+
+    /// Search the wrapped store, and make the found credentials check their secrets.
+    ///
+    /// Found credentials that don't know their identity can't be
+    /// checked, so they are skipped.
+    fn search(&self, spec: &SearchSpec) -> Result<Vec<Box<Credential>>> {
+        let found = self.inner.search(spec)?;
+        Ok(found
+            .into_iter()
+            .filter_map(|cred| {
+                let spec = cred.spec()?;
+                Some(Box::new(self.wrap(cred, &spec)) as Box<Credential>)
+            })
+            .collect())
+    }
+
+    /// The info of the wrapped store.
+    fn info(&self) -> CredentialStoreInfo {
+        self.inner.info()
+    }
+}
+
+/// A credential that stores its secret with a tag, and checks the tag
+/// when the secret is read.
+pub struct IntegrityCredential {
+    inner: Box<Credential>,
+    key: Zeroizing<[u8; KEY_LEN]>,
+    identity: Vec<u8>,
+}
+
+impl IntegrityCredential {
+    /// The wrapped credential.
+    ///
+    /// Its secret has the tag at the end.
+    pub fn inner(&self) -> &Credential {
+        self.inner.as_ref()
+    }
+
+    /// The secret followed by its tag, as it's stored.
+    fn tagged(&self, secret: &[u8]) -> Zeroizing<Vec<u8>> {
+        let tag = mac(&self.tagged_data(secret), &self.key);
+        let mut stored = Zeroizing::new(Vec::with_capacity(secret.len() + MAC_LEN));
+        stored.extend_from_slice(secret);
+        stored.extend_from_slice(&tag);
+        stored
+    }
+
+    /// The secret in a stored one, if its tag checks out.
+    fn verified<'a>(&self, stored: &'a [u8]) -> Result<&'a [u8]> {
+        if stored.len() < MAC_LEN {
+            return Err(Error::IntegrityFailure);
+        }
+        let (secret, tag) = stored.split_at(stored.len() - MAC_LEN);
+        verify_mac(&self.tagged_data(secret), tag, &self.key)
+            .map_err(|_| Error::IntegrityFailure)?;
+        Ok(secret)
+    }
+
+    /// The data that's tagged: the credential's identity, then the secret.
+    fn tagged_data(&self, secret: &[u8]) -> Zeroizing<Vec<u8>> {
+        let mut data = Zeroizing::new(Vec::with_capacity(self.identity.len() + secret.len()));
+        data.extend_from_slice(&self.identity);
+        data.extend_from_slice(secret);
+        data
+    }
+}
+
+impl core::fmt::Debug for IntegrityCredential {
+    /// Shows the wrapped credential, but never the key.
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("IntegrityCredential")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CredentialApi for IntegrityCredential {
+    fn set_secret(&self, secret: &[u8]) -> Result<()> {
+        self.inner.set_secret(&self.tagged(secret))
+    }
+
+    /// Retrieve the secret, after checking its tag.
+    ///
+    /// Returns an [IntegrityFailure](Error::IntegrityFailure) error if the
+    /// stored secret has no tag, or if its tag doesn't match.
+    fn get_secret(&self) -> Result<Vec<u8>> {
+        let stored = Zeroizing::new(self.inner.get_secret()?);
+        Ok(self.verified(&stored)?.to_vec())
+    }
+
+    fn compare_and_set_password(&self, expected: &str, password: &str) -> Result<()> {
+        self.compare_and_set_secret(expected.as_bytes(), password.as_bytes())
+    }
+
+    /// Tag and store the secret, but only if the stored one checks out
+    /// and is `expected`.
+    ///
+    /// The tagged secret that was read is what's compared by the wrapped
+    /// credential when it stores the new one, so this is as atomic as the
+    /// wrapped store's [compare_and_set_secret](CredentialApi::compare_and_set_secret).
+    fn compare_and_set_secret(&self, expected: &[u8], secret: &[u8]) -> Result<()> {
+        let stored = Zeroizing::new(self.inner.get_secret()?);
+        if self.verified(&stored)? != expected {
+            return Err(Error::Conflict);
+        }
+        self.inner
+            .compare_and_set_secret(&stored, &self.tagged(secret))
+    }
+
+    fn exists(&self) -> Result<bool> {
+        self.inner.exists()
+    }
+
+    fn get_attributes(&self) -> Result<AttributeMap<String, String>> {
+        self.inner.get_attributes()
+    }
+
+    fn update_attributes(&self, attributes: &AttributeMap<&str, &str>) -> Result<()> {
+        self.inner.update_attributes(attributes)
+    }
+
+    fn get_metadata(&self) -> Result<CredentialMetadata> {
+        self.inner.get_metadata()
+    }
+
+    fn get_label(&self) -> Result<String> {
+        self.inner.get_label()
+    }
+
+    fn set_label(&self, label: &str) -> Result<()> {
+        self.inner.set_label(label)
+    }
+
+    fn set_expiration(&self, ttl: Duration) -> Result<()> {
+        self.inner.set_expiration(ttl)
+    }
+
+    fn unlock(&self) -> Result<()> {
+        self.inner.unlock()
+    }
+
+    /// The tag takes 32 bytes, so secrets must leave room for it.
+    fn max_secret_len(&self) -> Option<usize> {
+        self.inner
+            .max_secret_len()
+            .map(|len| len.saturating_sub(MAC_LEN))
+    }
+
+    fn spec(&self) -> Option<CredentialSpec> {
+        self.inner.spec()
+    }
+
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        self.inner.at_rest_encryption()
+    }
+
+    fn delete_credential(&self) -> Result<()> {
+        self.inner.delete_credential()
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+
+    fn debug_fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Debug::fmt(self, f)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::IntegrityCredentialBuilder;
+    use crate::credential::CredentialBuilderApi;
+    use crate::credential::SearchSpec;
+    use crate::memory::MemoryCredentialBuilder;
+    use crate::tests::{CasOnlyCredentialBuilder, DefaultTargetCredentialBuilder};
+    use crate::{Entry, Error};
+
+    #[test]
+    fn test_integrity() {
+        let store = MemoryCredentialBuilder::default();
+        let builder = IntegrityCredentialBuilder::from_secret(Box::new(store.clone()), b"secret");
+        let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+        assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+        entry.set_password("checked").unwrap();
+        assert_eq!(entry.get_password().unwrap(), "checked");
+        let raw = store.build(None, "service", "user").unwrap();
+        let stored = raw.get_secret().unwrap();
+        assert_eq!(&stored[..7], b"checked");
+        assert_eq!(stored.len(), 7 + 32);
+
+        // changed secrets, short secrets, and secrets tagged for other credentials fail
+        let mut tampered = stored.clone();
+        tampered[0] ^= 1;
+        raw.set_secret(&tampered).unwrap();
+        assert!(matches!(entry.get_password(), Err(Error::IntegrityFailure)));
+        raw.set_secret(b"short").unwrap();
+        assert!(matches!(entry.get_password(), Err(Error::IntegrityFailure)));
+        let other = builder.build(None, "service", "other").unwrap();
+        other.set_password("other's").unwrap();
+        let copied = store.build(None, "service", "other").unwrap().get_secret();
+        raw.set_secret(&copied.unwrap()).unwrap();
+        assert!(matches!(entry.get_password(), Err(Error::IntegrityFailure)));
+
+        // and so do secrets tagged with another key
+        let rekeyed = IntegrityCredentialBuilder::from_secret(Box::new(store.clone()), b"other");
+        rekeyed
+            .build(None, "service", "user")
+            .unwrap()
+            .set_password("forged")
+            .unwrap();
+        assert!(matches!(entry.get_password(), Err(Error::IntegrityFailure)));
+        entry.set_password("restored").unwrap();
+        assert_eq!(entry.get_password().unwrap(), "restored");
+    }
+
+    #[test]
+    fn test_normalized_targets() {
+        let store = DefaultTargetCredentialBuilder::default();
+        let builder = IntegrityCredentialBuilder::from_secret(Box::new(store), b"secret");
+        let built = builder.build(None, "service", "user").unwrap();
+        built.set_password("normalized").unwrap();
+        let found = builder.search(&SearchSpec::default()).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].get_password().unwrap(), "normalized");
+    }
+
+    #[test]
+    fn test_compare_and_set() {
+        let builder = IntegrityCredentialBuilder::from_secret(
+            Box::new(CasOnlyCredentialBuilder::default()),
+            b"secret",
+        );
+        crate::tests::test_forwards_compare_and_set(&builder);
+    }
+}
@@ -19,14 +19,61 @@ wildcards when looking up credentials by attribute value.)
 Credentials on iOS can have a large number of _key/value_ attributes,
 but this module controls the _account_ and _name_ attributes and
 ignores all the others. so clients can't use it to access or update any attributes.
+
+## Biometrics
+
+Secrets that should only be readable by the device's owner can be stored by
+credentials built with [IosCredentialBuilder::with_biometrics_required].
+Their items are created with an access control that requires user presence
+(Face ID, Touch ID, or the device passcode) on every read, so reading
+their secrets shows a prompt, and returns a [Cancelled](ErrorCode::Cancelled)
+error if the user dismisses it or fails to authenticate. Reads made
+when the user can't be prompted (for example, from the background) return a
+[NoStorageAccess](ErrorCode::NoStorageAccess) error.
+To require something more specific than user presence (such as biometry only),
+use [IosCredentialBuilder::with_access_policy] with one of the [AccessPolicy] values.
+
+Since the access control of an existing item can't be changed,
+setting the secret of such a credential replaces its item.
+Access controls like this need the data protection keychain, so on macOS
+(where credentials for the _Protected_ target are built by this module)
+credentials with an access policy keep their items there. Apps must be
+signed with a keychain access group entitlement to use that keychain on macOS.
+
+## Accessibility
+
+By default, keychain items are only readable while the device is unlocked.
+Apps that need to read their secrets in the background (or that want
+stricter, device-only items) can choose when their items are accessible
+with [IosCredentialBuilder::with_accessibility]. As with access policies,
+setting the secret of a credential with an accessibility replaces its item.
+
+## Sharing and syncing
+
+Credentials built [with_access_group](IosCredentialBuilder::with_access_group)
+keep their items in the given keychain access group, so they can be shared
+by all the apps (from the same team) that have that group in their
+entitlements. Credentials built
+[with_synchronizable](IosCredentialBuilder::with_synchronizable) keep their
+items in iCloud Keychain, so they are synced to the user's other devices.
+(Synced items are distinct from unsynced ones with the same service and user,
+and can't have access policies or device-only accessibility.)
+On macOS, these credentials also use the data protection keychain.
  */
 
 use security_framework::base::Error;
-use security_framework::passwords::{
-    delete_generic_password, get_generic_password, set_generic_password,
+use security_framework::{
+    access_control::{ProtectionMode, SecAccessControl},
+    passwords::{
+        AccessControlOptions, PasswordOptions, delete_generic_password_options, generic_password,
+        set_generic_password_options,
+    },
 };
 
-use super::credential::{Credential, CredentialApi, CredentialBuilder, CredentialBuilderApi};
+use super::credential::{
+    AtRestStatus, Credential, CredentialApi, CredentialBuilder, CredentialBuilderApi,
+    CredentialSpec, CredentialStoreInfo,
+};
 use super::error::{Error as ErrorCode, Result, decode_password};
 
 /// The representation of a generic Keychain credential.
@@ -38,6 +85,117 @@ use super::error::{Error as ErrorCode, Result, decode_password};
 pub struct IosCredential {
     pub service: String,
     pub account: String,
+    pub biometrics_required: bool,
+    pub access_policy: Option<AccessPolicy>,
+    pub accessibility: Option<Accessibility>,
+    pub access_group: Option<String>,
+    pub synchronizable: bool,
+}
+
+/// When keychain items are accessible (the item's `kSecAttrAccessible` class).
+///
+/// The classes whose names end with `ThisDeviceOnly` keep items from
+/// being restored to (or synchronized with) other devices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Accessibility {
+    /// While the device is unlocked (the default).
+    WhenUnlocked,
+    /// While the device is unlocked, on this device only.
+    WhenUnlockedThisDeviceOnly,
+    /// After the device has been unlocked once since it was restarted.
+    AfterFirstUnlock,
+    /// After the device has been unlocked once since it was restarted, on this device only.
+    AfterFirstUnlockThisDeviceOnly,
+    /// While the device is unlocked and has a passcode, on this device only.
+    WhenPasscodeSetThisDeviceOnly,
+}
+
+impl Accessibility {
+    fn protection_mode(self) -> ProtectionMode {
+        match self {
+            Accessibility::WhenUnlocked => ProtectionMode::AccessibleWhenUnlocked,
+            Accessibility::WhenUnlockedThisDeviceOnly => {
+                ProtectionMode::AccessibleWhenUnlockedThisDeviceOnly
+            }
+            Accessibility::AfterFirstUnlock => ProtectionMode::AccessibleAfterFirstUnlock,
+            Accessibility::AfterFirstUnlockThisDeviceOnly => {
+                ProtectionMode::AccessibleAfterFirstUnlockThisDeviceOnly
+            }
+            Accessibility::WhenPasscodeSetThisDeviceOnly => {
+                ProtectionMode::AccessibleWhenPasscodeSetThisDeviceOnly
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for Accessibility {
+    type Err = ErrorCode;
+
+    /// Parse an accessibility from its kebab-case name, such as `after-first-unlock`.
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "when-unlocked" => Ok(Accessibility::WhenUnlocked),
+            "when-unlocked-this-device-only" => Ok(Accessibility::WhenUnlockedThisDeviceOnly),
+            "after-first-unlock" => Ok(Accessibility::AfterFirstUnlock),
+            "after-first-unlock-this-device-only" => {
+                Ok(Accessibility::AfterFirstUnlockThisDeviceOnly)
+            }
+            "when-passcode-set-this-device-only" => {
+                Ok(Accessibility::WhenPasscodeSetThisDeviceOnly)
+            }
+            _ => Err(ErrorCode::Invalid(
+                "accessibility".to_string(),
+                format!("'{s}' is not a known accessibility class"),
+            )),
+        }
+    }
+}
+
+/// The access control policies that keychain items can be created with.
+///
+/// The OS enforces an item's policy (by prompting the user)
+/// every time the item's secret is read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessPolicy {
+    /// Touch ID, Face ID, or the device passcode (or login password on macOS).
+    UserPresence,
+    /// Touch ID or Face ID, with any enrolled finger or face.
+    BiometryAny,
+    /// Touch ID or Face ID, with the fingers or face enrolled when the item was created.
+    BiometryCurrentSet,
+    /// The device passcode (or login password on macOS).
+    DevicePasscode,
+}
+
+impl AccessPolicy {
+    fn options(self) -> AccessControlOptions {
+        match self {
+            AccessPolicy::UserPresence => AccessControlOptions::USER_PRESENCE,
+            AccessPolicy::BiometryAny => AccessControlOptions::BIOMETRY_ANY,
+            AccessPolicy::BiometryCurrentSet => AccessControlOptions::BIOMETRY_CURRENT_SET,
+            AccessPolicy::DevicePasscode => AccessControlOptions::DEVICE_PASSCODE,
+        }
+    }
+}
+
+impl std::str::FromStr for AccessPolicy {
+    type Err = ErrorCode;
+
+    /// Parse a policy from its kebab-case name, such as `biometry-any`.
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "user-presence" => Ok(AccessPolicy::UserPresence),
+            "biometry-any" => Ok(AccessPolicy::BiometryAny),
+            "biometry-current-set" => Ok(AccessPolicy::BiometryCurrentSet),
+            "device-passcode" => Ok(AccessPolicy::DevicePasscode),
+            _ => Err(ErrorCode::Invalid(
+                "access policy".to_string(),
+                format!(
+                    "'{s}' is not user-presence, biometry-any, biometry-current-set, or device-passcode"
+                ),
+            )),
+        }
+    }
 }
 
 impl CredentialApi for IosCredential {
@@ -57,7 +215,10 @@ impl CredentialApi for IosCredential {
     /// Since there is only one credential with a given _account_ and _user_
     /// in any given keychain, there is no chance of ambiguity.
     fn set_secret(&self, secret: &[u8]) -> Result<()> {
-        set_generic_password(&self.service, &self.account, secret).map_err(decode_error)?;
+        if self.is_protected() {
+            return self.set_protected_secret(secret);
+        }
+        set_generic_password_options(secret, self.password_options()).map_err(decode_error)?;
         Ok(())
     }
 
@@ -75,7 +236,7 @@ impl CredentialApi for IosCredential {
     /// Returns a [NoEntry](ErrorCode::NoEntry) error if there is no
     /// credential in the store.
     fn get_secret(&self) -> Result<Vec<u8>> {
-        get_generic_password(&self.service, &self.account).map_err(decode_error)
+        generic_password(self.password_options()).map_err(decode_error)
     }
 
     /// Delete the underlying generic credential for this entry, if any.
@@ -83,10 +244,24 @@ impl CredentialApi for IosCredential {
     /// Returns a [NoEntry](ErrorCode::NoEntry) error if there is no
     /// credential in the store.
     fn delete_credential(&self) -> Result<()> {
-        delete_generic_password(&self.service, &self.account).map_err(decode_error)?;
+        delete_generic_password_options(self.password_options()).map_err(decode_error)?;
         Ok(())
     }
 
+    /// The service and user of this credential (iOS credentials have no target).
+    fn spec(&self) -> Option<CredentialSpec> {
+        Some(CredentialSpec {
+            target: None,
+            service: self.service.clone(),
+            user: self.account.clone(),
+        })
+    }
+
+    /// Keychain items are encrypted at rest.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        AtRestStatus::Encrypted
+    }
+
     /// Return the underlying concrete object with an `Any` type so that it can
     /// be downgraded to an [IosCredential] for platform-specific processing.
     fn as_any(&self) -> &dyn std::any::Any {
@@ -106,7 +281,7 @@ impl IosCredential {
     /// other than the ones we use to find the generic credential.
     /// But at least this checks whether the underlying credential exists.
     pub fn get_credential(&self) -> Result<Self> {
-        get_generic_password(&self.service, &self.account).map_err(decode_error)?;
+        generic_password(self.password_options()).map_err(decode_error)?;
         Ok(self.clone())
     }
 
@@ -146,27 +321,207 @@ impl IosCredential {
         Ok(Self {
             service: service.to_string(),
             account: user.to_string(),
+            biometrics_required: false,
+            access_policy: None,
+            accessibility: None,
+            access_group: None,
+            synchronizable: false,
         })
     }
+
+    /// Return this credential with biometric gating turned on or off.
+    ///
+    /// See the module header for details.
+    pub fn with_biometrics_required(mut self, biometrics_required: bool) -> Self {
+        self.biometrics_required = biometrics_required;
+        self
+    }
+
+    /// Return this credential with the given access policy (or none).
+    ///
+    /// A policy given here takes precedence over
+    /// [with_biometrics_required](IosCredential::with_biometrics_required).
+    /// See the module header for details.
+    pub fn with_access_policy(mut self, access_policy: Option<AccessPolicy>) -> Self {
+        self.access_policy = access_policy;
+        self
+    }
+
+    /// Return this credential with the given accessibility (or the default).
+    ///
+    /// See the module header for details.
+    pub fn with_accessibility(mut self, accessibility: Option<Accessibility>) -> Self {
+        self.accessibility = accessibility;
+        self
+    }
+
+    /// Return this credential with its item in the given access group (or the default).
+    ///
+    /// See the module header for details.
+    pub fn with_access_group(mut self, access_group: Option<&str>) -> Self {
+        self.access_group = access_group.map(|group| group.to_string());
+        self
+    }
+
+    /// Return this credential with its item synced through iCloud Keychain or not.
+    ///
+    /// See the module header for details.
+    pub fn with_synchronizable(mut self, synchronizable: bool) -> Self {
+        self.synchronizable = synchronizable;
+        self
+    }
+
+    /// Whether this credential's item is created with an access control.
+    fn is_protected(&self) -> bool {
+        self.policy().is_some() || self.accessibility.is_some()
+    }
+
+    /// The access policy this credential's item is created with, if any.
+    fn policy(&self) -> Option<AccessPolicy> {
+        self.access_policy.or(self
+            .biometrics_required
+            .then_some(AccessPolicy::UserPresence))
+    }
+
+    /// The options that identify this credential's item.
+    ///
+    /// On macOS, items with an access control, an access group, or syncing
+    /// are in the data protection keychain.
+    fn password_options(&self) -> PasswordOptions {
+        let mut options = PasswordOptions::new_generic_password(&self.service, &self.account);
+        if let Some(group) = &self.access_group {
+            options.set_access_group(group);
+        }
+        if self.synchronizable {
+            options.set_access_synchronized(Some(true));
+        }
+        #[cfg(target_os = "macos")]
+        if self.is_protected() || self.access_group.is_some() || self.synchronizable {
+            options.use_protected_keychain();
+        }
+        options
+    }
+
+    /// Replace this credential's item with one that has its access control.
+    fn set_protected_secret(&self, secret: &[u8]) -> Result<()> {
+        match delete_generic_password_options(self.password_options()).map_err(decode_error) {
+            Ok(()) | Err(ErrorCode::NoEntry) => {}
+            Err(err) => return Err(err),
+        }
+        let protection = self.accessibility.map(Accessibility::protection_mode);
+        let flags = self.policy().map_or(0, |policy| policy.options().bits());
+        let access_control =
+            SecAccessControl::create_with_protection(protection, flags).map_err(decode_error)?;
+        let mut options = self.password_options();
+        options.set_access_control(access_control);
+        set_generic_password_options(secret, options).map_err(decode_error)
+    }
 }
 
 /// The builder for iOS keychain credentials
-pub struct IosCredentialBuilder {}
+#[derive(Debug, Default)]
+pub struct IosCredentialBuilder {
+    biometrics_required: bool,
+    access_policy: Option<AccessPolicy>,
+    accessibility: Option<Accessibility>,
+    access_group: Option<String>,
+    synchronizable: bool,
+}
+
+impl IosCredentialBuilder {
+    /// Return this builder with biometric gating turned on or off
+    /// for the credentials it builds.
+    ///
+    /// See the module header for details.
+    pub fn with_biometrics_required(mut self, biometrics_required: bool) -> Self {
+        self.biometrics_required = biometrics_required;
+        self
+    }
+
+    /// Whether this builder's credentials require user presence to read.
+    pub fn biometrics_required(&self) -> bool {
+        self.biometrics_required
+    }
+
+    /// Return this builder with an access policy (or none) for the credentials it builds.
+    ///
+    /// See [IosCredential::with_access_policy].
+    pub fn with_access_policy(mut self, access_policy: Option<AccessPolicy>) -> Self {
+        self.access_policy = access_policy;
+        self
+    }
+
+    /// The access policy of this builder's credentials, if any.
+    pub fn access_policy(&self) -> Option<AccessPolicy> {
+        self.access_policy
+    }
+
+    /// Return this builder with an accessibility (or the default) for the credentials it builds.
+    ///
+    /// See [IosCredential::with_accessibility].
+    pub fn with_accessibility(mut self, accessibility: Option<Accessibility>) -> Self {
+        self.accessibility = accessibility;
+        self
+    }
+
+    /// The accessibility of this builder's credentials, if it isn't the default.
+    pub fn accessibility(&self) -> Option<Accessibility> {
+        self.accessibility
+    }
+
+    /// Return this builder with an access group (or the default) for the credentials it builds.
+    ///
+    /// See [IosCredential::with_access_group].
+    pub fn with_access_group(mut self, access_group: Option<&str>) -> Self {
+        self.access_group = access_group.map(|group| group.to_string());
+        self
+    }
+
+    /// The access group of this builder's credentials, if it isn't the default.
+    pub fn access_group(&self) -> Option<&str> {
+        self.access_group.as_deref()
+    }
+
+    /// Return this builder with iCloud Keychain syncing turned on or off
+    /// for the credentials it builds.
+    ///
+    /// See [IosCredential::with_synchronizable].
+    pub fn with_synchronizable(mut self, synchronizable: bool) -> Self {
+        self.synchronizable = synchronizable;
+        self
+    }
+
+    /// Whether this builder's credentials are synced through iCloud Keychain.
+    pub fn synchronizable(&self) -> bool {
+        self.synchronizable
+    }
+}
 
 /// Returns an instance of the iOS credential builder.
 ///
 /// On iOS,
 /// this is called once when an entry is first created.
 pub fn default_credential_builder() -> Box<CredentialBuilder> {
-    Box::new(IosCredentialBuilder {})
+    Box::new(IosCredentialBuilder::default())
 }
 
 impl CredentialBuilderApi for IosCredentialBuilder {
     /// Build an [IosCredential] for the given target, service, and user.
     fn build(&self, target: Option<&str>, service: &str, user: &str) -> Result<Box<Credential>> {
-        Ok(Box::new(IosCredential::new_with_target(
-            target, service, user,
-        )?))
+        let credential = IosCredential::new_with_target(target, service, user)?;
+        Ok(Box::new(
+            credential
+                .with_biometrics_required(self.biometrics_required)
+                .with_access_policy(self.access_policy)
+                .with_accessibility(self.accessibility)
+                .with_access_group(self.access_group.as_deref())
+                .with_synchronizable(self.synchronizable),
+        ))
+    }
+
+    /// Keychain items are encrypted at rest.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        AtRestStatus::Encrypted
     }
 
     /// Return the underlying builder object with an `Any` type so that it can
@@ -174,6 +529,11 @@ impl CredentialBuilderApi for IosCredentialBuilder {
     fn as_any(&self) -> &dyn std::any::Any {
         self
     }
+
+    /// Credentials have no updatable attributes, and the store can't be searched.
+    fn info(&self) -> CredentialStoreInfo {
+        CredentialStoreInfo::new("ios", self.persistence(), self.at_rest_encryption())
+    }
 }
 
 /// Map an iOS API error to a crate error with appropriate annotation
@@ -185,14 +545,21 @@ fn decode_error(err: Error) -> ErrorCode {
         -25291 => ErrorCode::NoStorageAccess(Box::new(err)), // errSecNotAvailable
         -25292 => ErrorCode::NoStorageAccess(Box::new(err)), // errSecReadOnly
         -25300 => ErrorCode::NoEntry,                        // errSecItemNotFound
+        -25308 => ErrorCode::NoStorageAccess(Box::new(err)), // errSecInteractionNotAllowed
+        -128 => ErrorCode::Cancelled,                        // errSecUserCanceled
+        -25293 => ErrorCode::Cancelled,                      // errSecAuthFailed
+        -34018 => ErrorCode::NoStorageAccess(Box::new(err)), // errSecMissingEntitlement
         _ => ErrorCode::PlatformFailure(Box::new(err)),
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{IosCredential, default_credential_builder};
-    use crate::credential::CredentialPersistence;
+    use super::{
+        AccessPolicy, Accessibility, IosCredential, IosCredentialBuilder,
+        default_credential_builder,
+    };
+    use crate::credential::{CredentialBuilderApi, CredentialPersistence};
     use crate::{Entry, Error, tests::generate_random_string};
 
     #[test]
@@ -226,6 +593,91 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_access_policy() {
+        assert_eq!(
+            "biometry-any".parse::<AccessPolicy>().unwrap(),
+            AccessPolicy::BiometryAny
+        );
+        assert!(matches!(
+            "face".parse::<AccessPolicy>(),
+            Err(Error::Invalid(_, _))
+        ));
+        let builder = IosCredentialBuilder::default()
+            .with_biometrics_required(true)
+            .with_access_policy(Some(AccessPolicy::DevicePasscode));
+        assert!(builder.biometrics_required());
+        assert_eq!(builder.access_policy(), Some(AccessPolicy::DevicePasscode));
+        let cred = builder.build(None, "service", "user").unwrap();
+        let ios_cred: &IosCredential = cred
+            .as_any()
+            .downcast_ref()
+            .expect("credential not an IosCredential");
+        assert_eq!(ios_cred.access_policy, Some(AccessPolicy::DevicePasscode));
+    }
+
+    #[test]
+    fn test_accessibility() {
+        assert_eq!(
+            "after-first-unlock".parse::<Accessibility>().unwrap(),
+            Accessibility::AfterFirstUnlock
+        );
+        assert!(matches!(
+            "always".parse::<Accessibility>(),
+            Err(Error::Invalid(_, _))
+        ));
+        let builder = IosCredentialBuilder::default()
+            .with_accessibility(Some(Accessibility::WhenPasscodeSetThisDeviceOnly));
+        assert_eq!(
+            builder.accessibility(),
+            Some(Accessibility::WhenPasscodeSetThisDeviceOnly)
+        );
+        let cred = builder.build(None, "service", "user").unwrap();
+        let ios_cred: &IosCredential = cred
+            .as_any()
+            .downcast_ref()
+            .expect("credential not an IosCredential");
+        assert_eq!(
+            ios_cred.accessibility,
+            Some(Accessibility::WhenPasscodeSetThisDeviceOnly)
+        );
+    }
+
+    #[test]
+    fn test_sharing_options() {
+        let builder = IosCredentialBuilder::default()
+            .with_access_group(Some("TEAMID.com.example.shared"))
+            .with_synchronizable(true);
+        assert_eq!(builder.access_group(), Some("TEAMID.com.example.shared"));
+        assert!(builder.synchronizable());
+        let cred = builder.build(None, "service", "user").unwrap();
+        let ios_cred: &IosCredential = cred
+            .as_any()
+            .downcast_ref()
+            .expect("credential not an IosCredential");
+        assert_eq!(
+            ios_cred.access_group.as_deref(),
+            Some("TEAMID.com.example.shared")
+        );
+        assert!(ios_cred.synchronizable);
+    }
+
+    #[test]
+    #[cfg(target_os = "macos")]
+    fn test_access_policy_needs_entitlement() {
+        // test binaries aren't signed with a keychain access group,
+        // so they can't use the data protection keychain on macOS
+        let name = generate_random_string();
+        let builder = IosCredentialBuilder::default().with_biometrics_required(true);
+        let entry = Entry::new_with_credential(builder.build(None, &name, &name).unwrap());
+        match entry.set_password("biometric") {
+            Ok(()) => entry
+                .delete_credential()
+                .expect("Can't delete protected item"),
+            Err(err) => assert!(matches!(err, Error::NoStorageAccess(_)), "{err:?}"),
+        }
+    }
+
     #[test]
     fn test_missing_entry() {
         crate::tests::test_missing_entry(entry_new);
@@ -0,0 +1,438 @@
+/*!
+
+# Journaling credential builder
+
+If a process is killed while it's writing to a credential store, it can be
+hard to tell afterwards whether the write happened. The
+[JournalingCredentialBuilder] in this module wraps another builder and keeps
+a write-ahead journal in a local file: before each change to a credential
+(setting its secret, updating its attributes, or deleting it), it records
+its intent to change the credential, and after the change it records that
+the change is done (whether or not it succeeded). At startup, an app can call
+[recover_pending](JournalingCredentialBuilder::recover_pending) to find the
+credentials whose changes may not have completed, and then check or redo them.
+
+```
+# use keyring::{Entry, credential::CredentialBuilderApi, memory::MemoryCredentialBuilder};
+# use keyring::journal::JournalingCredentialBuilder;
+# let path = std::env::temp_dir().join(format!("keyring-doc-{}.journal", std::process::id()));
+let builder = JournalingCredentialBuilder::new(&path, Box::new(MemoryCredentialBuilder::default()));
+for pending in builder.recover_pending().unwrap() {
+    println!("The last change to {pending} may not have completed");
+}
+builder.truncate().unwrap();
+let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+entry.set_password("journaled").unwrap();
+assert!(builder.recover_pending().unwrap().is_empty());
+# std::fs::remove_file(&path).unwrap();
+```
+
+The journal never contains secrets, just the identity (target, service,
+and user) of each changed credential and the kind of change. Each record
+is flushed to disk before the change is made, so the journal will
+be accurate even if the process dies mid-write.
+
+The journal grows with every change, so apps should
+[truncate](JournalingCredentialBuilder::truncate) it once they have
+dealt with any pending changes. Credentials returned by a search
+through this builder are those of the wrapped store, so changes
+to them are not journaled.
+ */
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::credential::{
+    AtRestStatus, AttributeMap, Credential, CredentialApi, CredentialBuilder, CredentialBuilderApi,
+    CredentialPersistence, CredentialSpec, CredentialStoreInfo, SearchSpec,
+};
+use super::error::{Error, Result};
+
+/// A credential builder that journals changes to the credentials
+/// built by another builder.
+#[derive(Debug)]
+pub struct JournalingCredentialBuilder {
+    journal: Arc<Journal>,
+    inner: Box<CredentialBuilder>,
+}
+
+impl JournalingCredentialBuilder {
+    /// Create a builder that journals changes to the credentials built by `inner`
+    /// in the file at `path`.
+    ///
+    /// The file is created when the first change is journaled, if it doesn't exist.
+    pub fn new(path: &Path, inner: Box<CredentialBuilder>) -> Self {
+        Self {
+            journal: Arc::new(Journal {
+                path: path.to_path_buf(),
+                lock: Mutex::new(()),
+            }),
+            inner,
+        }
+    }
+
+    /// The path of this builder's journal file.
+    pub fn path(&self) -> &Path {
+        &self.journal.path
+    }
+
+    /// The wrapped builder.
+    pub fn inner(&self) -> &CredentialBuilder {
+        self.inner.as_ref()
+    }
+
+    /// List the credentials whose last journaled change may not have completed.
+    ///
+    /// Each credential is listed once, in the order their changes were started.
+    pub fn recover_pending(&self) -> Result<Vec<CredentialSpec>> {
+        self.journal.pending()
+    }
+
+    /// Empty the journal.
+    ///
+    /// Call this after dealing with the pending changes reported by
+    /// [recover_pending](JournalingCredentialBuilder::recover_pending),
+    /// and before making any new changes.
+    pub fn truncate(&self) -> Result<()> {
+        let _guard = self.journal.lock();
+        match std::fs::remove_file(&self.journal.path) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(journal_failure(err)),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl CredentialBuilderApi for JournalingCredentialBuilder {
+    /// Build a journaling credential that wraps a credential built by the wrapped builder.
+    fn build(&self, target: Option<&str>, service: &str, user: &str) -> Result<Box<Credential>> {
+        let inner = self.inner.build(target, service, user)?;
+        Ok(Box::new(JournalingCredential {
+            spec: CredentialSpec {
+                target: target.map(str::to_string),
+                service: service.to_string(),
+                user: user.to_string(),
+            },
+            journal: self.journal.clone(),
+            inner,
+        }))
+    }
+
+    /// The at-rest encryption of the wrapped store.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        self.inner.at_rest_encryption()
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    /// The persistence of the wrapped store.
+    fn persistence(&self) -> CredentialPersistence {
+        self.inner.persistence()
+    }
+
+    /// Search the wrapped store.
+    fn search(&self, spec: &SearchSpec) -> Result<Vec<Box<Credential>>> {
+        self.inner.search(spec)
+    }
+
+    /// The info of the wrapped store.
+    fn info(&self) -> CredentialStoreInfo {
+        self.inner.info()
+    }
+}
+
+/// A credential whose changes are journaled.
+#[derive(Debug)]
+pub struct JournalingCredential {
+    spec: CredentialSpec,
+    journal: Arc<Journal>,
+    inner: Box<Credential>,
+}
+
+impl JournalingCredential {
+    /// The wrapped credential.
+    pub fn inner(&self) -> &Credential {
+        self.inner.as_ref()
+    }
+
+    fn journaled<T>(&self, op: &str, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        let id = self.journal.begin(op, &self.spec)?;
+        let result = f();
+        self.journal.end(&id)?;
+        result
+    }
+}
+
+impl CredentialApi for JournalingCredential {
+    fn set_secret(&self, secret: &[u8]) -> Result<()> {
+        self.journaled("set", || self.inner.set_secret(secret))
+    }
+
+    fn get_secret(&self) -> Result<Vec<u8>> {
+        self.inner.get_secret()
+    }
+
+    fn compare_and_set_password(&self, expected: &str, password: &str) -> Result<()> {
+        self.journaled("set", || {
+            self.inner.compare_and_set_password(expected, password)
+        })
+    }
+
+    fn compare_and_set_secret(&self, expected: &[u8], secret: &[u8]) -> Result<()> {
+        self.journaled("set", || {
+            self.inner.compare_and_set_secret(expected, secret)
+        })
+    }
+
+    fn exists(&self) -> Result<bool> {
+        self.inner.exists()
+    }
+
+    fn get_attributes(&self) -> Result<AttributeMap<String, String>> {
+        self.inner.get_attributes()
+    }
+
+    fn update_attributes(&self, attributes: &AttributeMap<&str, &str>) -> Result<()> {
+        self.journaled("update", || self.inner.update_attributes(attributes))
+    }
+
+    fn get_label(&self) -> Result<String> {
+        self.inner.get_label()
+    }
+
+    fn set_label(&self, label: &str) -> Result<()> {
+        self.journaled("update", || self.inner.set_label(label))
+    }
+
+    fn set_expiration(&self, ttl: Duration) -> Result<()> {
+        self.journaled("update", || self.inner.set_expiration(ttl))
+    }
+
+    fn unlock(&self) -> Result<()> {
+        self.inner.unlock()
+    }
+
+    fn max_secret_len(&self) -> Option<usize> {
+        self.inner.max_secret_len()
+    }
+
+    fn spec(&self) -> Option<CredentialSpec> {
+        Some(self.spec.clone())
+    }
+
+    fn delete_credential(&self) -> Result<()> {
+        self.journaled("delete", || self.inner.delete_credential())
+    }
+
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        self.inner.at_rest_encryption()
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn debug_fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self, f)
+    }
+}
+
+/// The journal file shared by a builder and its credentials.
+///
+/// Each line of the file is a record with tab-separated fields. A `begin`
+/// record has an id, an operation, and the (escaped) target, service,
+/// and user of the credential; an `end` record has just the id. Every record
+/// ends with a `$` field, so records torn by a crash can be recognized,
+/// and starts on a new line, so a torn record can't swallow the next one.
+#[derive(Debug)]
+struct Journal {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl Journal {
+    fn lock(&self) -> std::sync::MutexGuard<'_, ()> {
+        self.lock
+            .lock()
+            .expect("Poisoned journal lock in keyring-rs: please report a bug!")
+    }
+
+    fn begin(&self, op: &str, spec: &CredentialSpec) -> Result<String> {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        // the time keeps ids from different runs of the same process id distinct
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        let id = format!(
+            "{}.{nanos}.{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        );
+        let target = match &spec.target {
+            Some(target) => format!("+{}", escape(target)),
+            None => "-".to_string(),
+        };
+        let record = format!(
+            "begin\t{id}\t{op}\t{target}\t{}\t{}",
+            escape(&spec.service),
+            escape(&spec.user)
+        );
+        self.append(&record)?;
+        Ok(id)
+    }
+
+    fn end(&self, id: &str) -> Result<()> {
+        self.append(&format!("end\t{id}"))
+    }
+
+    fn append(&self, record: &str) -> Result<()> {
+        let _guard = self.lock();
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(journal_failure)?;
+        let line = format!("\n{record}\t$");
+        file.write_all(line.as_bytes()).map_err(journal_failure)?;
+        file.sync_data().map_err(journal_failure)
+    }
+
+    fn pending(&self) -> Result<Vec<CredentialSpec>> {
+        let _guard = self.lock();
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(err) => return Err(journal_failure(err)),
+        };
+        let mut pending: Vec<(String, CredentialSpec)> = vec![];
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(journal_failure)?;
+            let fields: Vec<&str> = line.split('\t').collect();
+            match fields.as_slice() {
+                ["begin", id, _, target, service, user, "$"] => {
+                    let target = target.strip_prefix('+').map(unescape);
+                    let spec = CredentialSpec {
+                        target,
+                        service: unescape(service),
+                        user: unescape(user),
+                    };
+                    pending.push((id.to_string(), spec));
+                }
+                ["end", id, "$"] => pending.retain(|(pending_id, _)| pending_id != id),
+                // blank lines and torn records (a torn begin record
+                // means the change was never started)
+                _ => continue,
+            }
+        }
+        let mut result: Vec<CredentialSpec> = vec![];
+        for (_, spec) in pending {
+            if !result.contains(&spec) {
+                result.push(spec);
+            }
+        }
+        Ok(result)
+    }
+}
+
+/// Escape the characters that can't appear in a journal field.
+fn escape(value: &str) -> String {
+    value
+        .replace('%', "%25")
+        .replace('\t', "%09")
+        .replace('\n', "%0A")
+        .replace('\r', "%0D")
+}
+
+fn unescape(value: &str) -> String {
+    value
+        .replace("%0D", "\r")
+        .replace("%0A", "\n")
+        .replace("%09", "\t")
+        .replace("%25", "%")
+}
+
+fn journal_failure(err: std::io::Error) -> Error {
+    Error::PlatformFailure(Box::new(err))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::{JournalingCredentialBuilder, escape, unescape};
+    use crate::credential::{CredentialBuilderApi, CredentialSpec};
+    use crate::memory::MemoryCredentialBuilder;
+    use crate::tests::{CasOnlyCredentialBuilder, generate_random_string};
+
+    fn builder_new() -> JournalingCredentialBuilder {
+        let path = std::env::temp_dir().join(format!("{}.journal", generate_random_string()));
+        JournalingCredentialBuilder::new(&path, Box::new(MemoryCredentialBuilder::default()))
+    }
+
+    #[test]
+    fn test_completed_changes() {
+        let builder = builder_new();
+        assert!(builder.recover_pending().unwrap().is_empty());
+        let cred = builder.build(None, "service", "user").unwrap();
+        cred.set_password("journaled").unwrap();
+        assert_eq!(cred.get_password().unwrap(), "journaled");
+        cred.delete_credential().unwrap();
+        // failed changes are complete, too
+        assert!(cred.delete_credential().is_err());
+        assert!(builder.recover_pending().unwrap().is_empty());
+        let journal = std::fs::read_to_string(builder.path()).unwrap();
+        assert!(!journal.contains("journaled"), "Journal contains a secret");
+        builder.truncate().unwrap();
+        assert!(!builder.path().exists());
+    }
+
+    #[test]
+    fn test_pending_changes() {
+        let builder = builder_new();
+        let cred = builder.build(Some("tar\tget"), "service", "user").unwrap();
+        cred.set_password("journaled").unwrap();
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(builder.path())
+            .unwrap();
+        // simulate a crash during a change and another during a journal write
+        file.write_all(b"\nbegin\tcrashed\tset\t+tar%09get\tservice\tuser\t$")
+            .unwrap();
+        file.write_all(b"\nbegin\ttorn\tdelete\t-\tservice\tus")
+            .unwrap();
+        drop(file);
+        cred.delete_credential().unwrap();
+        let pending = builder.recover_pending().unwrap();
+        let expected = CredentialSpec {
+            target: Some("tar\tget".to_string()),
+            service: "service".to_string(),
+            user: "user".to_string(),
+        };
+        assert_eq!(pending, vec![expected]);
+        cred.set_password("journaled").unwrap();
+        assert!(builder.recover_pending().unwrap().len() == 1);
+        builder.truncate().unwrap();
+        assert!(builder.recover_pending().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_escape() {
+        for value in ["plain", "%09", "tab\there", "new\nline\r", "100%"] {
+            assert_eq!(unescape(&escape(value)), value);
+        }
+    }
+
+    #[test]
+    fn test_compare_and_set() {
+        let path = std::env::temp_dir().join(format!("{}.journal", generate_random_string()));
+        let builder =
+            JournalingCredentialBuilder::new(&path, Box::new(CasOnlyCredentialBuilder::default()));
+        crate::tests::test_forwards_compare_and_set(&builder);
+        assert!(builder.recover_pending().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,425 @@
+/*!
+
+# KWallet credential store
+
+On KDE desktops, secrets are kept by KWallet. Recent versions of KWallet
+also provide the Secret Service API, but that bridge is often disabled or
+broken, so this module talks to the KWallet daemon (`kwalletd6`, or
+`kwalletd5` on older systems) directly over its DBus interface. It's
+available when the `kwallet` feature is enabled. It's not the default
+store on any platform, so clients have to set it as their default (or
+build their credentials from it directly):
+```no_run
+keyring::set_default_credential_builder(keyring::kwallet::default_credential_builder());
+```
+
+KWallet keeps secrets in _wallets_, each of which holds _folders_ of
+named _entries_. For a given <_target_, _service_, _user_> triple, this
+module uses the wallet named by the target (or, if there is no target,
+the user's default "network" wallet, which is where apps usually keep
+their passwords), the folder named by the service, and the entry named
+by the user. So a credential made by `Entry::new("my-app", "alice")` can
+be seen in the _KWallet Manager_ app as the `alice` entry in the `my-app`
+folder of the default wallet. Secrets are stored as KWallet _passwords_,
+which are strings, so secrets that aren't UTF-8 can't be stored
+(and produce an [Invalid](crate::Error::Invalid) error).
+
+Opening a wallet prompts the user to unlock it if it's locked (and, for
+wallets that restrict access, to allow this app to use it). If the user
+declines, a [NoStorageAccess](crate::Error::NoStorageAccess) error is
+returned. The same error is returned if no KWallet daemon is running.
+
+This store supports [search](crate::search). Only the default wallet is
+searched, unless the spec matches an exact target, in which case that
+wallet is searched.
+ */
+use std::time::Duration;
+
+use dbus::arg::{AppendAll, ReadAll};
+use dbus::blocking::Connection;
+
+use super::credential::{
+    AtRestStatus, Credential, CredentialApi, CredentialBuilder, CredentialBuilderApi,
+    CredentialPersistence, CredentialSpec, CredentialStoreInfo, SearchSpec,
+};
+use super::error::{Error as ErrorCode, Result};
+
+/// The application name this crate gives KWallet (shown in its prompts).
+const APP_ID: &str = "rust-keyring";
+
+/// The DBus interface of the KWallet daemon.
+const INTERFACE: &str = "org.kde.KWallet";
+
+/// The bus names and object paths of the KWallet daemons, newest first.
+const DAEMONS: [(&str, &str); 2] = [
+    ("org.kde.kwalletd6", "/modules/kwalletd6"),
+    ("org.kde.kwalletd5", "/modules/kwalletd5"),
+];
+
+/// How long to wait for the daemon, which may be prompting the user.
+const TIMEOUT: Duration = Duration::from_secs(120);
+
+/// The representation of a KWallet entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KWalletCredential {
+    /// The wallet, or `None` for the default (network) wallet
+    pub wallet: Option<String>,
+    pub folder: String,
+    pub key: String,
+}
+
+impl CredentialApi for KWalletCredential {
+    /// Write the secret as the password of this credential's entry,
+    /// creating the folder and entry if necessary.
+    ///
+    /// Returns an [Invalid](ErrorCode::Invalid) error if the secret isn't UTF-8.
+    fn set_secret(&self, secret: &[u8]) -> Result<()> {
+        let password = std::str::from_utf8(secret).map_err(|_| {
+            ErrorCode::Invalid(
+                "secret".to_string(),
+                "must be UTF-8 to be stored in KWallet".to_string(),
+            )
+        })?;
+        let wallet = Wallet::open(self.wallet.as_deref())?;
+        let (has_folder,): (bool,) =
+            wallet.call("hasFolder", (wallet.handle, &self.folder, APP_ID))?;
+        if !has_folder {
+            let (created,): (bool,) =
+                wallet.call("createFolder", (wallet.handle, &self.folder, APP_ID))?;
+            if !created {
+                return Err(failure("couldn't create the folder"));
+            }
+        }
+        let (status,): (i32,) = wallet.call(
+            "writePassword",
+            (wallet.handle, &self.folder, &self.key, password, APP_ID),
+        )?;
+        match status {
+            0 => Ok(()),
+            _ => Err(failure("couldn't write the password")),
+        }
+    }
+
+    /// Read the password of this credential's entry, if it exists.
+    ///
+    /// Returns a [NoEntry](ErrorCode::NoEntry) error if there is no
+    /// entry in the wallet.
+    fn get_secret(&self) -> Result<Vec<u8>> {
+        let wallet = Wallet::open(self.wallet.as_deref())?;
+        wallet.check_entry(&self.folder, &self.key)?;
+        let (password,): (String,) = wallet.call(
+            "readPassword",
+            (wallet.handle, &self.folder, &self.key, APP_ID),
+        )?;
+        Ok(password.into_bytes())
+    }
+
+    /// Delete this credential's entry, if it exists.
+    ///
+    /// Returns a [NoEntry](ErrorCode::NoEntry) error if there is no
+    /// entry in the wallet.
+    fn delete_credential(&self) -> Result<()> {
+        let wallet = Wallet::open(self.wallet.as_deref())?;
+        wallet.check_entry(&self.folder, &self.key)?;
+        let (status,): (i32,) = wallet.call(
+            "removeEntry",
+            (wallet.handle, &self.folder, &self.key, APP_ID),
+        )?;
+        match status {
+            0 => Ok(()),
+            _ => Err(failure("couldn't remove the entry")),
+        }
+    }
+
+    /// The target (wallet), service (folder), and user (entry) of this credential.
+    fn spec(&self) -> Option<CredentialSpec> {
+        Some(CredentialSpec {
+            target: self.wallet.clone(),
+            service: self.folder.clone(),
+            user: self.key.clone(),
+        })
+    }
+
+    /// Wallets are encrypted at rest.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        AtRestStatus::Encrypted
+    }
+
+    /// Return the underlying concrete object with an `Any` type so that it can
+    /// be downgraded to a [KWalletCredential] for platform-specific processing.
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    /// Expose the concrete debug formatter for use via the [Credential] trait
+    fn debug_fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self, f)
+    }
+}
+
+impl KWalletCredential {
+    /// Create a credential for the given target, service, and user.
+    ///
+    /// Returns an [Invalid](ErrorCode::Invalid) error if the target, service,
+    /// or user is empty (because KWallet can't name wallets, folders, or
+    /// entries with empty strings).
+    pub fn new_with_target(target: Option<&str>, service: &str, user: &str) -> Result<Self> {
+        for (attr, value) in [
+            ("target", target.unwrap_or("-")),
+            ("service", service),
+            ("user", user),
+        ] {
+            if value.is_empty() {
+                return Err(ErrorCode::Invalid(
+                    attr.to_string(),
+                    "cannot be empty".to_string(),
+                ));
+            }
+        }
+        Ok(Self {
+            wallet: target.map(|t| t.to_string()),
+            folder: service.to_string(),
+            key: user.to_string(),
+        })
+    }
+}
+
+/// An open wallet, which is closed when dropped.
+struct Wallet {
+    connection: Connection,
+    daemon: (&'static str, &'static str),
+    handle: i32,
+}
+
+impl Wallet {
+    /// Open the named wallet (or the default wallet), prompting the user if needed.
+    fn open(name: Option<&str>) -> Result<Self> {
+        let connection = Connection::new_session().map_err(no_access)?;
+        let mut last_err = None;
+        for daemon in DAEMONS {
+            let name = match name {
+                Some(name) => Ok(name.to_string()),
+                None => {
+                    call(&connection, daemon, "networkWallet", ()).map(|(name,): (String,)| name)
+                }
+            };
+            let opened = name.and_then(|name| {
+                call(&connection, daemon, "open", (&name, 0i64, APP_ID))
+                    .map(|(handle,): (i32,)| handle)
+            });
+            match opened {
+                Ok(handle) if handle >= 0 => {
+                    return Ok(Self {
+                        connection,
+                        daemon,
+                        handle,
+                    });
+                }
+                Ok(_) => {
+                    return Err(ErrorCode::NoStorageAccess(Box::from(
+                        "KWallet didn't open the wallet",
+                    )));
+                }
+                // try the next daemon, if this one isn't running
+                Err(err) if err.name() == Some("org.freedesktop.DBus.Error.ServiceUnknown") => {
+                    last_err = Some(err);
+                }
+                Err(err) => return Err(decode_error(err)),
+            }
+        }
+        Err(decode_error(last_err.unwrap()))
+    }
+
+    /// Call a method of the daemon's KWallet interface.
+    fn call<A: AppendAll, R: ReadAll>(&self, method: &str, args: A) -> Result<R> {
+        call(&self.connection, self.daemon, method, args).map_err(decode_error)
+    }
+
+    /// Return a [NoEntry](ErrorCode::NoEntry) error if the entry doesn't exist.
+    fn check_entry(&self, folder: &str, key: &str) -> Result<()> {
+        let (exists,): (bool,) = self.call("hasEntry", (self.handle, folder, key, APP_ID))?;
+        if exists {
+            Ok(())
+        } else {
+            Err(ErrorCode::NoEntry)
+        }
+    }
+
+    /// The names of the wallet's folders.
+    fn folders(&self) -> Result<Vec<String>> {
+        let (folders,): (Vec<String>,) = self.call("folderList", (self.handle, APP_ID))?;
+        Ok(folders)
+    }
+
+    /// The names of the entries in one of the wallet's folders.
+    fn entries(&self, folder: &str) -> Result<Vec<String>> {
+        let (entries,): (Vec<String>,) = self.call("entryList", (self.handle, folder, APP_ID))?;
+        Ok(entries)
+    }
+}
+
+impl Drop for Wallet {
+    fn drop(&mut self) {
+        if self.handle >= 0 {
+            // the daemon keeps the wallet open for other users of it
+            let _: Result<(i32,)> = self.call("close", (self.handle, false, APP_ID));
+        }
+    }
+}
+
+/// Call a method of a daemon's KWallet interface.
+fn call<A: AppendAll, R: ReadAll>(
+    connection: &Connection,
+    (name, path): (&'static str, &'static str),
+    method: &str,
+    args: A,
+) -> std::result::Result<R, dbus::Error> {
+    connection
+        .with_proxy(name, path, TIMEOUT)
+        .method_call(INTERFACE, method, args)
+}
+
+/// The builder for KWallet credentials.
+#[derive(Debug, Default)]
+pub struct KWalletCredentialBuilder {}
+
+/// Returns an instance of the KWallet credential builder.
+///
+/// This is never called by the crate, since KWallet is
+/// not the default store on any platform.
+pub fn default_credential_builder() -> Box<CredentialBuilder> {
+    Box::new(KWalletCredentialBuilder {})
+}
+
+impl CredentialBuilderApi for KWalletCredentialBuilder {
+    /// Build a [KWalletCredential] for the given target, service, and user.
+    fn build(&self, target: Option<&str>, service: &str, user: &str) -> Result<Box<Credential>> {
+        Ok(Box::new(KWalletCredential::new_with_target(
+            target, service, user,
+        )?))
+    }
+
+    /// Wallets are encrypted at rest.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        AtRestStatus::Encrypted
+    }
+
+    /// Return the underlying builder object with an `Any` type so that it can
+    /// be downgraded to a [KWalletCredentialBuilder] for platform-specific processing.
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    /// Wallets are kept on disk until deleted.
+    fn persistence(&self) -> CredentialPersistence {
+        CredentialPersistence::UntilDelete
+    }
+
+    /// Return a credential for each wallet entry that matches the spec.
+    ///
+    /// Only the default wallet is searched, unless the spec matches an exact target.
+    fn search(&self, spec: &SearchSpec) -> Result<Vec<Box<Credential>>> {
+        let target = spec.target.exact();
+        let wallet = Wallet::open(target)?;
+        let mut found = Vec::new();
+        for folder in wallet.folders()? {
+            if !spec.service.matches(&folder) {
+                continue;
+            }
+            for key in wallet.entries(&folder)? {
+                if spec.matches(target, &folder, &key) {
+                    found.push(KWalletCredential {
+                        wallet: target.map(|t| t.to_string()),
+                        folder: folder.clone(),
+                        key,
+                    });
+                }
+            }
+        }
+        if !spec.unsorted {
+            found.sort_by(|a, b| (&a.folder, &a.key).cmp(&(&b.folder, &b.key)));
+        }
+        Ok(found
+            .into_iter()
+            .map(|cred| Box::new(cred) as Box<Credential>)
+            .collect())
+    }
+
+    /// Wallet passwords are strings, so secrets must be UTF-8.
+    fn info(&self) -> CredentialStoreInfo {
+        CredentialStoreInfo::new("kwallet", self.persistence(), self.at_rest_encryption())
+            .with_binary_secrets(false)
+            .with_search(true)
+    }
+}
+
+/// Map DBus errors to crate errors.
+///
+/// A daemon that isn't running means there's no access to the store.
+pub fn decode_error(err: dbus::Error) -> ErrorCode {
+    match err.name() {
+        Some("org.freedesktop.DBus.Error.ServiceUnknown")
+        | Some("org.freedesktop.DBus.Error.NoReply")
+        | Some("org.freedesktop.DBus.Error.AccessDenied") => no_access(err),
+        _ => ErrorCode::PlatformFailure(Box::new(err)),
+    }
+}
+
+fn no_access(err: dbus::Error) -> ErrorCode {
+    ErrorCode::NoStorageAccess(Box::new(err))
+}
+
+fn failure(reason: &str) -> ErrorCode {
+    ErrorCode::PlatformFailure(Box::from(format!("KWallet {reason}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::credential::{CredentialPersistence, Match, SearchSpec};
+    use crate::{Entry, Error, tests::generate_random_string};
+
+    use super::{KWalletCredential, default_credential_builder};
+
+    #[test]
+    fn test_persistence() {
+        assert!(matches!(
+            default_credential_builder().persistence(),
+            CredentialPersistence::UntilDelete
+        ))
+    }
+
+    #[test]
+    fn test_invalid_parameter() {
+        for (target, service, user) in [(Some(""), "s", "u"), (None, "", "u"), (None, "s", "")] {
+            assert!(matches!(
+                KWalletCredential::new_with_target(target, service, user),
+                Err(Error::Invalid(_, _))
+            ));
+        }
+    }
+
+    fn entry_new(service: &str, user: &str) -> Entry {
+        crate::tests::entry_from_constructor(KWalletCredential::new_with_target, service, user)
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let name = generate_random_string();
+        let entry = entry_new(&name, "user");
+        assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+        entry.set_password("kwallet password").unwrap();
+        assert_eq!(entry.get_password().unwrap(), "kwallet password");
+        assert!(matches!(
+            entry.set_secret(&[0xff]),
+            Err(Error::Invalid(_, _))
+        ));
+        let spec = SearchSpec {
+            service: Match::Exact(name.clone()),
+            ..Default::default()
+        };
+        let found = default_credential_builder().search(&spec).unwrap();
+        assert_eq!(found.len(), 1);
+        entry.delete_credential().unwrap();
+        assert!(matches!(entry.delete_credential(), Err(Error::NoEntry)));
+    }
+}
@@ -1,4 +1,5 @@
 #![cfg_attr(docsrs, feature(doc_cfg))]
+#![cfg_attr(not(feature = "std"), no_std)]
 /*!
 
 # Keyring
@@ -62,7 +63,7 @@ concrete credential objects they use to implement the Credential trait.
 In order to allow clients to access this richer model, the Credential trait
 has an [as_any](credential::CredentialApi::as_any) method that returns a
 reference to the underlying
-concrete object typed as [Any](std::any::Any), so that it can be downgraded to
+concrete object typed as [Any](core::any::Any), so that it can be downgraded to
 its concrete type.
 
 ### Credential store features
@@ -95,11 +96,99 @@ don't separately specify one of the included keystore features for your platform
 then no keystore will be built in, and calls to [Entry::new] and [Entry::new_with_target]
 will fail unless the client brings their own keystore (see next section).
 
+### Other features
+
+In addition to the credential store features, this crate has the
+following optional features, none of which are included in the
+default feature set:
+
+- `serde`: Provides [Entry] methods that store any serializable value
+  as a structured secret (see the `serialized` module).
+- `oauth`: Provides a `TokenSet` type for OAuth-style access and
+  refresh tokens, and [Entry] methods that store them as structured secrets.
+- `crypto-rust`: Provides a `crypto` module with pure-Rust authenticated
+  encryption for secrets that are kept outside of a credential store.
+- `async`: Provides an `AsyncEntry` type whose operations are `async`,
+  for apps that run on an async runtime.
+- `kwallet`: Provides a `kwallet` module with a credential store that talks
+  to KWallet directly (rather than through its Secret Service bridge) on
+  Linux, FreeBSD, and OpenBSD. (It's not the default store on any platform.)
+- `libsecret`: Provides a `libsecret` module with a credential store that
+  uses the Secret Service through the native libsecret C library (which
+  must be installed) on Linux, FreeBSD, and OpenBSD. (It's not the default
+  store on any platform.)
+- `portal`: Provides a `portal` module with a credential store for Flatpak
+  and snap sandboxes on Linux, FreeBSD, and OpenBSD, which keeps credentials
+  in a file encrypted with a secret from the Secret portal. (It's not the
+  default store on any platform.)
+- `windows-dpapi`: Provides a `windows_dpapi` module with a Windows
+  credential store that keeps each credential in a DPAPI-encrypted file,
+  for secrets that are too big for the Windows Credential Manager.
+  (It's not the default store on Windows.)
+- `android-native`: Provides an `android` module with a credential store
+  that keeps credentials in Android's encrypted shared preferences, whose
+  key is kept in the Android Keystore. (It's not the default store on any
+  platform.)
+- `web-storage`: Provides a `web_storage` module with a credential store
+  that keeps encrypted credentials in the browser's local storage, for apps
+  compiled to `wasm32-unknown-unknown`. (It's not the default store on any
+  platform.)
+- `file-store`: Provides a `file_store` module with a portable credential
+  store that keeps its credentials in an encrypted file, for machines
+  (such as headless servers and containers) that have no platform store.
+- `vault`: Provides a `vault` module with a credential store that keeps
+  credentials in a HashiCorp Vault KV secrets engine, for server-side apps
+  that use a central secret store. (It's not the default store on any
+  platform.)
+- `aws`: Provides an `aws` module with a credential store that keeps
+  credentials in AWS Secrets Manager or the SSM Parameter Store, for apps
+  that run on AWS. (It's not the default store on any platform.)
+- `pkcs11`: Provides a `pkcs11` module with a credential store that keeps
+  credentials as data objects on a PKCS#11 token (such as a smartcard),
+  using the token's PKCS#11 module. (It's not the default store on any
+  platform.)
+- `tpm`: Provides a `tpm` module (on Linux) with a credential store that
+  keeps credentials in files encrypted under keys sealed by the machine's
+  TPM 2.0, using the tpm2-tss libraries. (It's not the default store on any
+  platform.)
+- `env-store`: Provides an `env_store` module with a read-only credential
+  store that reads secrets from environment variables (optionally layered
+  in front of another store), for CI pipelines and tests that inject
+  secrets without a platform store. (It's not the default store on any
+  platform.)
+- `tracing`: Traces every [Entry] operation with the
+  [tracing](https://docs.rs/tracing) crate: each runs in a span that records
+  the operation, the store, and fingerprints (never the values) of the
+  entry's service and user, and reports its outcome and duration.
+  The Secret Service store also reports the duration of each DBus round trip.
+  Secrets are never traced.
+- `secrecy`: Provides [Entry] methods that return secrets wrapped in
+  the types of the [secrecy](https://docs.rs/secrecy) crate, for apps that
+  already use it.
+- `migration`: Provides a `migration` module that exports credentials
+  to a passphrase-encrypted archive, and imports them from the archive
+  into another store (on the same machine or another one).
+- `client-crypto`: Provides an `encrypted` module with a credential
+  builder that wraps another one, and encrypts secrets on the client
+  before they are handed to its store, and an `integrity` module with
+  one that tags secrets so that changes made behind its back are caught.
+- `cli`: Builds the `keyring` command-line tool, which sets, gets, deletes,
+  lists, and moves the credentials in the active store.
+
+The `std` feature is included in the default feature set, and is
+required by all the credential store features. If you build without it,
+this crate is `no_std` (but requires `alloc`): the [credential] and [error]
+modules, the [Entry] type, and the [memory] credential store are
+all available, but there is no default credential builder,
+so entries must be made with [Entry::new_with_credential].
+Also, credential attributes are kept in a `BTreeMap` rather than a `HashMap`
+(see [AttributeMap]).
+
 ## Client-provided Credential Stores
 
 In addition to the keystores implemented by this crate, clients
 are free to provide their own keystores and use those.  There are
-two mechanisms provided for this:
+several mechanisms provided for this:
 
 - Clients can give their desired credential builder to the crate
   for use by the [Entry::new] and [Entry::new_with_target] calls.
@@ -107,24 +196,51 @@ two mechanisms provided for this:
   The major advantage of this approach is that client code remains
   independent of the credential builder being used.
 
+- Clients can also give the crate a credential builder to fall back on
+  if the default builder fails to build an entry's credential (for example,
+  because the platform store can't be reached).
+  This is done by making a call to [set_fallback_credential_builder].
+
 - Clients can construct their concrete credentials directly and
   then turn them into entries by using the [Entry::new_with_credential]
   call. The major advantage of this approach is that credentials
   can be identified however clients want, rather than being restricted
   to the simple model used by this crate.
 
-## Mock Credential Store
+Libraries that share a store with their host application can keep their
+credentials separate from the host's by wrapping their credential builder
+in a [NamespacedCredentialBuilder](namespace::NamespacedCredentialBuilder).
+Apps that want to transform their secrets (for example, to encrypt them
+with an app-held key) whatever the store can wrap their credential builder
+in a [MiddlewareCredentialBuilder](middleware::MiddlewareCredentialBuilder).
+Apps that need to store secrets that are too long for a store can wrap
+its credential builder in a [ChunkedCredentialBuilder](chunked::ChunkedCredentialBuilder).
+Apps that can't be sure their preferred store will be reachable can chain it
+with others in a [ChainedCredentialBuilder](chained::ChainedCredentialBuilder),
+which falls back to the next store in the chain when one can't be reached.
+Apps that read their secrets often from a slow store can keep them in memory
+for a while by wrapping its credential builder in a
+[CachingCredentialBuilder](caching::CachingCredentialBuilder).
+Apps that should never change their credentials can wrap their credential
+builder in a [ReadOnlyCredentialBuilder](read_only::ReadOnlyCredentialBuilder).
+
+## Mock and In-Memory Credential Stores
 
 In addition to the platform-specific credential stores, this crate
-always provides a mock credential store that clients can use to
+provides a mock credential store that clients can use to
 test their code in a platform independent way.  The mock credential
 store allows for pre-setting errors as well as password values to
 be returned from [Entry] method calls. If you want to use the mock
 credential store as your default in tests, make this call:
 ```
+# #[cfg(feature = "std")]
 keyring::set_default_credential_builder(keyring::mock::default_credential_builder())
 ```
 
+There is also a [memory] credential store that keeps credentials in process
+memory, where they are shared by all the entries for the same target, service, and user.
+Unlike the other stores, it is available even when this crate is built without `std`.
+
 ## Interoperability with Third Parties
 
 Each of the platform-specific credential stores provided by this crate uses
@@ -159,14 +275,25 @@ Windows and Linux, because the accesses may not be serialized in the same order
 they are made. And for RPC-based credential stores such as the dbus-based Secret
 Service, accesses from multiple threads (and even the same thread very quickly)
 are not recommended, as they may cause the RPC mechanism to fail.
+Apps that can't avoid concurrent access can wrap their credential builder
+in an [exclusive](crate::exclusive) builder, which serializes the operations
+on its credentials.
  */
 
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
 use log::debug;
-use std::collections::HashMap;
 
+use credential::AttributeMap;
 pub use credential::{Credential, CredentialBuilder};
 pub use error::{Error, Result};
 
+pub mod memory;
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
 pub mod mock;
 
 //
@@ -182,6 +309,36 @@ pub mod mock;
 )]
 pub mod secret_service;
 
+#[cfg(all(
+    any(target_os = "linux", target_os = "freebsd", target_os = "openbsd"),
+    feature = "kwallet"
+))]
+#[cfg_attr(
+    docsrs,
+    doc(cfg(any(target_os = "linux", target_os = "freebsd", target_os = "openbsd")))
+)]
+pub mod kwallet;
+
+#[cfg(all(
+    any(target_os = "linux", target_os = "freebsd", target_os = "openbsd"),
+    feature = "libsecret"
+))]
+#[cfg_attr(
+    docsrs,
+    doc(cfg(any(target_os = "linux", target_os = "freebsd", target_os = "openbsd")))
+)]
+pub mod libsecret;
+
+#[cfg(all(
+    any(target_os = "linux", target_os = "freebsd", target_os = "openbsd"),
+    feature = "portal"
+))]
+#[cfg_attr(
+    docsrs,
+    doc(cfg(any(target_os = "linux", target_os = "freebsd", target_os = "openbsd")))
+)]
+pub mod portal;
+
 //
 // pick the Apple keystore
 //
@@ -200,16 +357,123 @@ pub mod ios;
 #[cfg_attr(docsrs, doc(cfg(target_os = "windows")))]
 pub mod windows;
 
+#[cfg(all(target_os = "windows", feature = "windows-dpapi"))]
+#[cfg_attr(docsrs, doc(cfg(target_os = "windows")))]
+pub mod windows_dpapi;
+
+//
+// pick the Android keystore
+//
+#[cfg(all(target_os = "android", feature = "android-native"))]
+#[cfg_attr(docsrs, doc(cfg(target_os = "android")))]
+pub mod android;
+
+//
+// pick the browser keystore
+//
+#[cfg(all(target_arch = "wasm32", feature = "web-storage"))]
+#[cfg_attr(docsrs, doc(cfg(target_arch = "wasm32")))]
+pub mod web_storage;
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub mod caching;
+pub mod chained;
+pub mod chunked;
 pub mod credential;
 pub mod error;
-
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub mod exclusive;
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub mod expiry;
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub mod import;
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub mod journal;
+pub mod middleware;
+#[cfg(feature = "migration")]
+#[cfg_attr(docsrs, doc(cfg(feature = "migration")))]
+pub mod migration;
+pub mod namespace;
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub mod prompt;
+pub mod read_only;
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub mod retry;
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub mod rotation;
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub mod timeout;
+pub mod versioned;
+
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+pub mod serialized;
+
+#[cfg(feature = "oauth")]
+#[cfg_attr(docsrs, doc(cfg(feature = "oauth")))]
+pub mod oauth;
+
+#[cfg(feature = "crypto-rust")]
+#[cfg_attr(docsrs, doc(cfg(feature = "crypto-rust")))]
+pub mod crypto;
+
+#[cfg(feature = "client-crypto")]
+#[cfg_attr(docsrs, doc(cfg(feature = "client-crypto")))]
+pub mod encrypted;
+
+#[cfg(feature = "client-crypto")]
+#[cfg_attr(docsrs, doc(cfg(feature = "client-crypto")))]
+pub mod integrity;
+
+#[cfg(feature = "async")]
+#[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+pub mod async_entry;
+
+#[cfg(feature = "file-store")]
+#[cfg_attr(docsrs, doc(cfg(feature = "file-store")))]
+pub mod file_store;
+
+#[cfg(feature = "vault")]
+#[cfg_attr(docsrs, doc(cfg(feature = "vault")))]
+pub mod vault;
+
+#[cfg(feature = "aws")]
+#[cfg_attr(docsrs, doc(cfg(feature = "aws")))]
+pub mod aws;
+
+#[cfg(feature = "pkcs11")]
+#[cfg_attr(docsrs, doc(cfg(feature = "pkcs11")))]
+pub mod pkcs11;
+
+#[cfg(all(target_os = "linux", feature = "tpm"))]
+#[cfg_attr(docsrs, doc(cfg(all(target_os = "linux", feature = "tpm"))))]
+pub mod tpm;
+
+#[cfg(feature = "env-store")]
+#[cfg_attr(docsrs, doc(cfg(feature = "env-store")))]
+pub mod env_store;
+
+#[cfg(feature = "std")]
 #[derive(Default, Debug)]
 struct EntryBuilder {
     inner: Option<Box<CredentialBuilder>>,
+    fallback: Option<Box<CredentialBuilder>>,
 }
 
-static DEFAULT_BUILDER: std::sync::RwLock<EntryBuilder> =
-    std::sync::RwLock::new(EntryBuilder { inner: None });
+#[cfg(feature = "std")]
+static DEFAULT_BUILDER: std::sync::RwLock<EntryBuilder> = std::sync::RwLock::new(EntryBuilder {
+    inner: None,
+    fallback: None,
+});
 
 /// Set the credential builder used by default to create entries.
 ///
@@ -221,6 +485,8 @@ static DEFAULT_BUILDER: std::sync::RwLock<EntryBuilder> =
 /// This will block waiting for all other threads currently creating entries
 /// to complete what they are doing. It's really meant to be called
 /// at app startup before you start creating entries.
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
 pub fn set_default_credential_builder(new: Box<CredentialBuilder>) {
     let mut guard = DEFAULT_BUILDER
         .write()
@@ -228,6 +494,546 @@ pub fn set_default_credential_builder(new: Box<CredentialBuilder>) {
     guard.inner = Some(new);
 }
 
+/// Set a credential builder to fall back on when the default one fails.
+///
+/// If the default credential builder (see [set_default_credential_builder])
+/// returns an error when asked to build the credential for a new entry,
+/// then [Entry::new] and [Entry::new_with_target] will try this builder instead.
+/// For example, an application that prefers the platform store, but can't
+/// be sure the store will be reachable at runtime, could set an in-memory
+/// store as its fallback.
+///
+/// If the fallback builder also fails, the error from the default builder
+/// is returned. Which builder was used for each entry is logged at the
+/// `debug` level.
+///
+/// Like [set_default_credential_builder], this is meant to be called
+/// at app startup before you start creating entries.
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub fn set_fallback_credential_builder(new: Box<CredentialBuilder>) {
+    let mut guard = DEFAULT_BUILDER
+        .write()
+        .expect("Poisoned RwLock in keyring-rs: please report a bug!");
+    guard.fallback = Some(new);
+}
+
+/// Set the default credential builder from a spec string.
+///
+/// This lets the store be chosen when an app is run rather than when
+/// it's built, for example from an environment variable:
+/// ```
+/// if let Ok(spec) = std::env::var("KEYRING_BACKEND") {
+///     keyring::set_default_credential_builder_from_str(&spec).unwrap();
+/// }
+/// ```
+/// See [credential_builder_from_str] for the format of the spec.
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub fn set_default_credential_builder_from_str(spec: &str) -> Result<()> {
+    set_default_credential_builder(credential_builder_from_str(spec)?);
+    Ok(())
+}
+
+/// Make a credential builder from a spec string.
+///
+/// A spec is the name of a store, optionally followed by a `?` and
+/// `&`-separated `option=value` pairs, as in
+/// `secret-service?ambiguity=first-match&non-interactive=true`.
+/// The stores and their options are:
+///
+/// - `default`: the platform's default store (no options).
+/// - `memory`: a new, empty [memory] store (no options).
+/// - `mock`: the [mock] store (no options).
+/// - `secret-service`: `bus` (`session` or `system`), `ambiguity`
+///   (`strict` or `first-match`), `auto-dedupe` and `non-interactive`
+///   (`true` or `false`), `collection` (the collection for entries
+///   without a target), `session-encryption` (`encrypted` or `plain`),
+///   and `attribute` (a custom attribute, as `name:value`, which can be
+///   given more than once).
+/// - `kwallet`: the [KWallet](crate::kwallet) store (no options).
+/// - `libsecret`: the [libsecret](crate::libsecret) store (no options).
+/// - `portal`: `path` (the file to keep credentials in).
+/// - `macos`: `non-interactive` (`true` or `false`), `keychain`
+///   (the path of a keychain file for entries with no target),
+///   `access-policy` (see below), `access-group`, and `synchronizable`
+///   (`true` or `false`).
+/// - `ios`: `biometrics-required` (`true` or `false`), `access-policy`
+///   (`user-presence`, `biometry-any`, `biometry-current-set`, or `device-passcode`),
+///   and `accessibility` (`when-unlocked` or `after-first-unlock`, either
+///   optionally followed by `-this-device-only`, or `when-passcode-set-this-device-only`),
+///   `access-group`, and `synchronizable` (`true` or `false`).
+/// - `windows`: `nul-handling` (`preserve` or `reject`),
+///   `relaxed-lookup` (`true` or `false`), `password-encoding`
+///   (`utf-16`, `utf-8`, or `auto`), `persist`
+///   (`session`, `local-machine`, or `enterprise`), and `type`
+///   (`generic`, `domain-password`, or `domain-certificate`).
+/// - `windows-dpapi`: `dir` (the folder to keep credential files in).
+/// - `android`: the [Android](crate::android) store (no options).
+/// - `vault`: `address`, `namespace`, `mount`, `path` (which the secrets are
+///   kept under), and either `token` or both `role-id` and `secret-id`. The
+///   options that aren't given are taken from the environment (see
+///   [from_env](crate::vault::VaultCredentialBuilder::from_env)).
+/// - `aws`: `service` (`secrets-manager`, the default, or `parameter-store`),
+///   `region`, `endpoint`, `prefix`, and `kms-key-id`.
+/// - `pkcs11`: `module` (the path of the PKCS#11 module, which is required),
+///   `token` (the label of the token for entries with no target), and `pin`.
+/// - `tpm`: `dir` (the folder the credential files are kept in), `pcrs` (a
+///   comma-separated list of the PCRs that keys are bound to), and `tcti`.
+/// - `env`: `prefix` (the prefix of the environment variable names).
+/// - `file`: the [encrypted file](crate::file_store) store, with `path`
+///   (the file to keep credentials in) and `key-file` (a file holding
+///   the 32-byte key, and nothing else), which are both required.
+///   The key itself can't be given in the spec.
+///
+/// Only the stores that are built into this crate for the current platform
+/// are available. Returns an [Invalid](Error::Invalid) error if the spec
+/// names an unknown or unavailable store, or an unknown option or value.
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub fn credential_builder_from_str(spec: &str) -> Result<Box<CredentialBuilder>> {
+    let (name, query) = spec.split_once('?').unwrap_or((spec, ""));
+    let mut options = Vec::new();
+    for option in query.split('&').filter(|option| !option.is_empty()) {
+        match option.split_once('=') {
+            Some(pair) => options.push(pair),
+            None => return Err(invalid_spec(format!("option `{option}` has no value"))),
+        }
+    }
+    let options = options.into_iter();
+    match name {
+        "default" => no_options(name, options).map(|_| default_credential_builder()),
+        "memory" => no_options(name, options).map(|_| memory::default_credential_builder()),
+        "mock" => no_options(name, options).map(|_| mock::default_credential_builder()),
+        "secret-service" => {
+            #[cfg(any(
+                all(target_os = "linux", feature = "secret-service"),
+                all(target_os = "freebsd", feature = "secret-service"),
+                all(target_os = "openbsd", feature = "secret-service")
+            ))]
+            {
+                use credential::AmbiguityMode;
+                use secret_service::{BusKind, SessionEncryption, SsCredentialBuilder};
+                // the bus is chosen by constructor, so it has to come first
+                let mut options: Vec<_> = options.collect();
+                options.sort_by_key(|(key, _)| *key != "bus");
+                let mut builder = SsCredentialBuilder::default();
+                for (key, value) in options {
+                    builder = match (key, value) {
+                        ("bus", "session") => SsCredentialBuilder::with_bus(BusKind::Session),
+                        ("bus", "system") => SsCredentialBuilder::with_bus(BusKind::System),
+                        ("ambiguity", "strict") => builder.with_ambiguity(AmbiguityMode::Strict),
+                        ("ambiguity", "first-match") => {
+                            builder.with_ambiguity(AmbiguityMode::FirstMatch)
+                        }
+                        ("auto-dedupe", _) => builder.with_auto_dedupe(bool_option(key, value)?),
+                        ("non-interactive", _) => {
+                            builder.with_non_interactive(bool_option(key, value)?)
+                        }
+                        ("collection", _) => builder.with_collection(Some(value)),
+                        ("session-encryption", "encrypted") => {
+                            builder.with_session_encryption(SessionEncryption::Encrypted)
+                        }
+                        ("session-encryption", "plain") => {
+                            builder.with_session_encryption(SessionEncryption::Plain)
+                        }
+                        ("attribute", _) => match value.split_once(':') {
+                            Some((attr, attr_value)) => builder.with_attributes(
+                                &std::collections::HashMap::from([(attr, attr_value)]),
+                            ),
+                            None => return Err(bad_option(name, key, value)),
+                        },
+                        _ => return Err(bad_option(name, key, value)),
+                    }
+                }
+                Ok(Box::new(builder))
+            }
+            #[cfg(not(any(
+                all(target_os = "linux", feature = "secret-service"),
+                all(target_os = "freebsd", feature = "secret-service"),
+                all(target_os = "openbsd", feature = "secret-service")
+            )))]
+            Err(unavailable_store(name))
+        }
+        "kwallet" => {
+            #[cfg(any(
+                all(target_os = "linux", feature = "kwallet"),
+                all(target_os = "freebsd", feature = "kwallet"),
+                all(target_os = "openbsd", feature = "kwallet")
+            ))]
+            {
+                no_options(name, options).map(|_| kwallet::default_credential_builder())
+            }
+            #[cfg(not(any(
+                all(target_os = "linux", feature = "kwallet"),
+                all(target_os = "freebsd", feature = "kwallet"),
+                all(target_os = "openbsd", feature = "kwallet")
+            )))]
+            Err(unavailable_store(name))
+        }
+        "libsecret" => {
+            #[cfg(any(
+                all(target_os = "linux", feature = "libsecret"),
+                all(target_os = "freebsd", feature = "libsecret"),
+                all(target_os = "openbsd", feature = "libsecret")
+            ))]
+            {
+                no_options(name, options).map(|_| libsecret::default_credential_builder())
+            }
+            #[cfg(not(any(
+                all(target_os = "linux", feature = "libsecret"),
+                all(target_os = "freebsd", feature = "libsecret"),
+                all(target_os = "openbsd", feature = "libsecret")
+            )))]
+            Err(unavailable_store(name))
+        }
+        "portal" => {
+            #[cfg(any(
+                all(target_os = "linux", feature = "portal"),
+                all(target_os = "freebsd", feature = "portal"),
+                all(target_os = "openbsd", feature = "portal")
+            ))]
+            {
+                let mut builder = portal::default_credential_builder();
+                for (key, value) in options {
+                    builder = match key {
+                        "path" => Box::new(portal::PortalCredentialBuilder::new(value)),
+                        _ => return Err(bad_option(name, key, value)),
+                    }
+                }
+                Ok(builder)
+            }
+            #[cfg(not(any(
+                all(target_os = "linux", feature = "portal"),
+                all(target_os = "freebsd", feature = "portal"),
+                all(target_os = "openbsd", feature = "portal")
+            )))]
+            Err(unavailable_store(name))
+        }
+        "macos" => {
+            #[cfg(all(target_os = "macos", feature = "apple-native"))]
+            {
+                let mut builder = macos::MacCredentialBuilder::default();
+                for (key, value) in options {
+                    builder = match key {
+                        "non-interactive" => builder.with_non_interactive(bool_option(key, value)?),
+                        "keychain" => builder.with_keychain(value),
+                        "access-policy" => builder.with_access_policy(Some(value.parse()?)),
+                        "access-group" => builder.with_access_group(Some(value)),
+                        "synchronizable" => builder.with_synchronizable(bool_option(key, value)?),
+                        _ => return Err(bad_option(name, key, value)),
+                    }
+                }
+                Ok(Box::new(builder))
+            }
+            #[cfg(not(all(target_os = "macos", feature = "apple-native")))]
+            Err(unavailable_store(name))
+        }
+        "ios" => {
+            #[cfg(all(target_os = "ios", feature = "apple-native"))]
+            {
+                let mut builder = ios::IosCredentialBuilder::default();
+                for (key, value) in options {
+                    builder = match key {
+                        "biometrics-required" => {
+                            builder.with_biometrics_required(bool_option(key, value)?)
+                        }
+                        "access-policy" => builder.with_access_policy(Some(value.parse()?)),
+                        "accessibility" => builder.with_accessibility(Some(value.parse()?)),
+                        "access-group" => builder.with_access_group(Some(value)),
+                        "synchronizable" => builder.with_synchronizable(bool_option(key, value)?),
+                        _ => return Err(bad_option(name, key, value)),
+                    }
+                }
+                Ok(Box::new(builder))
+            }
+            #[cfg(not(all(target_os = "ios", feature = "apple-native")))]
+            Err(unavailable_store(name))
+        }
+        "windows" => {
+            #[cfg(all(target_os = "windows", feature = "windows-native"))]
+            {
+                use windows::{
+                    NulHandling, PasswordEncoding, PersistScope, WinCredentialBuilder,
+                    WinCredentialType,
+                };
+                // the NUL handling is chosen by constructor, so it has to come first
+                let mut options: Vec<_> = options.collect();
+                options.sort_by_key(|(key, _)| *key != "nul-handling");
+                let mut builder = WinCredentialBuilder::default();
+                for (key, value) in options {
+                    builder = match (key, value) {
+                        ("nul-handling", "preserve") => {
+                            WinCredentialBuilder::with_nul_handling(NulHandling::Preserve)
+                        }
+                        ("nul-handling", "reject") => {
+                            WinCredentialBuilder::with_nul_handling(NulHandling::Reject)
+                        }
+                        ("relaxed-lookup", _) => {
+                            builder.with_relaxed_lookup(bool_option(key, value)?)
+                        }
+                        ("password-encoding", "utf-16") => {
+                            builder.with_password_encoding(PasswordEncoding::Utf16)
+                        }
+                        ("password-encoding", "utf-8") => {
+                            builder.with_password_encoding(PasswordEncoding::Utf8)
+                        }
+                        ("password-encoding", "auto") => {
+                            builder.with_password_encoding(PasswordEncoding::Auto)
+                        }
+                        ("persist", "session") => builder.with_persist(PersistScope::Session),
+                        ("persist", "local-machine") => {
+                            builder.with_persist(PersistScope::LocalMachine)
+                        }
+                        ("persist", "enterprise") => builder.with_persist(PersistScope::Enterprise),
+                        ("type", "generic") => builder.with_cred_type(WinCredentialType::Generic),
+                        ("type", "domain-password") => {
+                            builder.with_cred_type(WinCredentialType::DomainPassword)
+                        }
+                        ("type", "domain-certificate") => {
+                            builder.with_cred_type(WinCredentialType::DomainCertificate)
+                        }
+                        _ => return Err(bad_option(name, key, value)),
+                    }
+                }
+                Ok(Box::new(builder))
+            }
+            #[cfg(not(all(target_os = "windows", feature = "windows-native")))]
+            Err(unavailable_store(name))
+        }
+        "windows-dpapi" => {
+            #[cfg(all(target_os = "windows", feature = "windows-dpapi"))]
+            {
+                let mut builder = windows_dpapi::DpapiCredentialBuilder::default();
+                for (key, value) in options {
+                    builder = match key {
+                        "dir" => builder.with_dir(value),
+                        _ => return Err(bad_option(name, key, value)),
+                    }
+                }
+                Ok(Box::new(builder))
+            }
+            #[cfg(not(all(target_os = "windows", feature = "windows-dpapi")))]
+            Err(unavailable_store(name))
+        }
+        "android" => {
+            #[cfg(all(target_os = "android", feature = "android-native"))]
+            {
+                no_options(name, options).map(|_| android::default_credential_builder())
+            }
+            #[cfg(not(all(target_os = "android", feature = "android-native")))]
+            Err(unavailable_store(name))
+        }
+        "vault" => {
+            #[cfg(feature = "vault")]
+            {
+                use vault::{VaultAuth, VaultCredentialBuilder};
+                let mut builder = VaultCredentialBuilder::from_env();
+                let (mut role_id, mut secret_id) = (None, None);
+                for (key, value) in options {
+                    builder = match key {
+                        "address" => builder.with_address(value),
+                        "namespace" => builder.with_namespace(Some(value)),
+                        "mount" => builder.with_mount(value),
+                        "path" => builder.with_path(value),
+                        "token" => builder.with_auth(VaultAuth::Token(value.to_string())),
+                        "role-id" => {
+                            role_id = Some(value.to_string());
+                            builder
+                        }
+                        "secret-id" => {
+                            secret_id = Some(value.to_string());
+                            builder
+                        }
+                        _ => return Err(bad_option(name, key, value)),
+                    }
+                }
+                match (role_id, secret_id) {
+                    (Some(role_id), Some(secret_id)) => {
+                        builder = builder.with_auth(VaultAuth::AppRole { role_id, secret_id })
+                    }
+                    (None, None) => {}
+                    _ => {
+                        return Err(invalid_spec(
+                            "the `vault` credential store needs both `role-id` and `secret-id`"
+                                .to_string(),
+                        ));
+                    }
+                }
+                Ok(Box::new(builder))
+            }
+            #[cfg(not(feature = "vault"))]
+            Err(unavailable_store(name))
+        }
+        "aws" => {
+            #[cfg(feature = "aws")]
+            {
+                use aws::{AwsCredentialBuilder, AwsService};
+                // the service is chosen by constructor, so it has to come first
+                let mut options: Vec<_> = options.collect();
+                options.sort_by_key(|(key, _)| *key != "service");
+                let mut builder = AwsCredentialBuilder::new(AwsService::SecretsManager);
+                for (key, value) in options {
+                    builder = match (key, value) {
+                        ("service", "secrets-manager") => {
+                            AwsCredentialBuilder::new(AwsService::SecretsManager)
+                        }
+                        ("service", "parameter-store") => {
+                            AwsCredentialBuilder::new(AwsService::ParameterStore)
+                        }
+                        ("region", _) => builder.with_region(value),
+                        ("endpoint", _) => builder.with_endpoint(Some(value)),
+                        ("prefix", _) => builder.with_prefix(value),
+                        ("kms-key-id", _) => builder.with_kms_key_id(Some(value)),
+                        _ => return Err(bad_option(name, key, value)),
+                    }
+                }
+                Ok(Box::new(builder))
+            }
+            #[cfg(not(feature = "aws"))]
+            Err(unavailable_store(name))
+        }
+        "pkcs11" => {
+            #[cfg(feature = "pkcs11")]
+            {
+                // the module is loaded by constructor, so it has to come first
+                let mut options: Vec<_> = options.collect();
+                options.sort_by_key(|(key, _)| *key != "module");
+                let mut options = options.into_iter();
+                let mut builder = match options.next() {
+                    Some(("module", value)) => pkcs11::Pkcs11CredentialBuilder::new(value)?,
+                    _ => {
+                        return Err(invalid_spec(
+                            "the `pkcs11` credential store needs a `module`".to_string(),
+                        ));
+                    }
+                };
+                for (key, value) in options {
+                    builder = match key {
+                        "token" => builder.with_token_label(Some(value)),
+                        "pin" => builder.with_pin(Some(value)),
+                        _ => return Err(bad_option(name, key, value)),
+                    }
+                }
+                Ok(Box::new(builder))
+            }
+            #[cfg(not(feature = "pkcs11"))]
+            Err(unavailable_store(name))
+        }
+        "tpm" => {
+            #[cfg(all(target_os = "linux", feature = "tpm"))]
+            {
+                let mut builder = tpm::TpmCredentialBuilder::default();
+                for (key, value) in options {
+                    builder = match key {
+                        "dir" => builder.with_dir(value),
+                        "pcrs" => {
+                            let pcrs = value
+                                .split(',')
+                                .map(|pcr| pcr.parse())
+                                .collect::<std::result::Result<Vec<u8>, _>>()
+                                .map_err(|_| bad_option(name, key, value))?;
+                            builder.with_pcrs(&pcrs)
+                        }
+                        "tcti" => builder.with_tcti(Some(value)),
+                        _ => return Err(bad_option(name, key, value)),
+                    }
+                }
+                Ok(Box::new(builder))
+            }
+            #[cfg(not(all(target_os = "linux", feature = "tpm")))]
+            Err(unavailable_store(name))
+        }
+        "env" => {
+            #[cfg(feature = "env-store")]
+            {
+                let mut builder = env_store::EnvCredentialBuilder::new();
+                for (key, value) in options {
+                    builder = match key {
+                        "prefix" => builder.with_prefix(value),
+                        _ => return Err(bad_option(name, key, value)),
+                    }
+                }
+                Ok(Box::new(builder))
+            }
+            #[cfg(not(feature = "env-store"))]
+            Err(unavailable_store(name))
+        }
+        "file" => {
+            #[cfg(feature = "file-store")]
+            {
+                let (mut path, mut key_file) = (None, None);
+                for (key, value) in options {
+                    match key {
+                        "path" => path = Some(value),
+                        "key-file" => key_file = Some(value),
+                        _ => return Err(bad_option(name, key, value)),
+                    }
+                }
+                let (Some(path), Some(key_file)) = (path, key_file) else {
+                    return Err(invalid_spec(
+                        "the `file` credential store needs a `path` and a `key-file`".to_string(),
+                    ));
+                };
+                let key = zeroize::Zeroizing::new(
+                    std::fs::read(key_file).map_err(|err| Error::NoStorageAccess(Box::new(err)))?,
+                );
+                let key: &[u8; crypto::KEY_LEN] = key.as_slice().try_into().map_err(|_| {
+                    Error::Invalid(
+                        key_file.to_string(),
+                        format!("doesn't hold a {}-byte key", crypto::KEY_LEN),
+                    )
+                })?;
+                Ok(Box::new(file_store::FileCredentialBuilder::new(path, key)))
+            }
+            #[cfg(not(feature = "file-store"))]
+            Err(unavailable_store(name))
+        }
+        _ => Err(invalid_spec(format!(
+            "there is no credential store named `{name}`"
+        ))),
+    }
+}
+
+#[cfg(feature = "std")]
+fn invalid_spec(reason: String) -> Error {
+    Error::Invalid("credential builder spec".to_string(), reason)
+}
+
+#[cfg(feature = "std")]
+fn unavailable_store(name: &str) -> Error {
+    invalid_spec(format!(
+        "the `{name}` credential store is not available in this build"
+    ))
+}
+
+#[cfg(feature = "std")]
+fn bad_option(name: &str, key: &str, value: &str) -> Error {
+    invalid_spec(format!(
+        "the `{name}` credential store has no option `{key}={value}`"
+    ))
+}
+
+#[cfg(feature = "std")]
+fn no_options<'a>(name: &str, mut options: impl Iterator<Item = (&'a str, &'a str)>) -> Result<()> {
+    match options.next() {
+        Some((key, value)) => Err(bad_option(name, key, value)),
+        None => Ok(()),
+    }
+}
+
+#[cfg(feature = "std")]
+#[allow(dead_code)]
+fn bool_option(key: &str, value: &str) -> Result<bool> {
+    match value {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(invalid_spec(format!(
+            "option `{key}` must be `true` or `false`, not `{value}`"
+        ))),
+    }
+}
+
 pub fn default_credential_builder() -> Box<CredentialBuilder> {
     #[cfg(any(
         all(target_os = "linux", feature = "secret-service"),
@@ -252,20 +1058,398 @@ pub fn default_credential_builder() -> Box<CredentialBuilder> {
     credential::nop_credential_builder()
 }
 
-fn build_default_credential(target: Option<&str>, service: &str, user: &str) -> Result<Entry> {
-    static DEFAULT: std::sync::LazyLock<Box<CredentialBuilder>> =
-        std::sync::LazyLock::new(default_credential_builder);
+#[cfg(feature = "std")]
+std::thread_local! {
+    static INTERACTION_ALLOWED: core::cell::Cell<bool> = const { core::cell::Cell::new(false) };
+}
+
+/// Whether the current thread is running a closure passed to
+/// [Entry::with_interaction_allowed].
+///
+/// Credential stores that can be configured not to prompt the user
+/// should allow prompts anyway while this is true.
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub fn interaction_allowed_override() -> bool {
+    INTERACTION_ALLOWED.with(|allowed| allowed.get())
+}
+
+#[cfg(feature = "std")]
+static DEFAULT: std::sync::LazyLock<Box<CredentialBuilder>> =
+    std::sync::LazyLock::new(default_credential_builder);
+
+/// Information about how this crate was built and where its entries are stored.
+///
+/// See [build_info].
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct BuildInfo {
+    /// The platform credential store built into this crate, if any:
+    /// `"secret-service"`, `"macos"`, `"ios"`, or `"windows"`.
+    pub platform_store: Option<&'static str>,
+    /// Whether entries made with [Entry::new] (or [Entry::new_with_target])
+    /// may be kept in a store that doesn't persist them beyond the process,
+    /// such as the [mock] or [memory] stores, or fail for lack of a store.
+    ///
+    /// This is true if the default credential builder doesn't persist its
+    /// credentials, or if a fallback builder that doesn't persist its credentials
+    /// has been set (see [set_fallback_credential_builder]).
+    pub is_fallback_mock: bool,
+}
+
+/// Report how this crate was built and where its entries are stored.
+///
+/// This is mostly for tests that are meant to exercise a real credential store:
+/// they can check [is_fallback_mock](BuildInfo::is_fallback_mock) to make
+/// sure they aren't silently running against a store that proves nothing about
+/// persistence.
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub fn build_info() -> BuildInfo {
+    let transient = |builder: &CredentialBuilder| !persists(builder);
     let guard = DEFAULT_BUILDER
         .read()
         .expect("Poisoned RwLock in keyring-rs: please report a bug!");
     let builder = guard.inner.as_ref().unwrap_or_else(|| &DEFAULT);
+    BuildInfo {
+        platform_store: platform_store(),
+        is_fallback_mock: transient(builder.as_ref())
+            || guard.fallback.as_deref().is_some_and(transient),
+    }
+}
+
+/// Whether the default credential store persists credentials beyond the process.
+///
+/// This is false if no platform store was built into this crate and
+/// no default credential builder has been set, or if the default builder
+/// is one (such as the [mock] or [memory] store) whose credentials vanish
+/// when the process exits. Apps that must not silently lose secrets can
+/// check this at startup, or create their entries with [Entry::new_strict].
+/// The fallback credential builder (if any) is not considered.
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub fn default_store_is_persistent() -> bool {
+    let guard = DEFAULT_BUILDER
+        .read()
+        .expect("Poisoned RwLock in keyring-rs: please report a bug!");
+    let builder = guard.inner.as_ref().unwrap_or_else(|| &DEFAULT);
+    persists(builder.as_ref())
+}
+
+#[cfg(feature = "std")]
+fn persists(builder: &CredentialBuilder) -> bool {
+    !matches!(
+        builder.persistence(),
+        credential::CredentialPersistence::EntryOnly
+            | credential::CredentialPersistence::ProcessOnly
+    )
+}
+
+/// Describe the default credential store and its capabilities.
+///
+/// Apps can use this to adapt to the store they are running with:
+/// for example, to warn when secrets won't outlive the session.
+/// The fallback credential builder (if any) is not described.
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub fn store_info() -> credential::CredentialStoreInfo {
+    let guard = DEFAULT_BUILDER
+        .read()
+        .expect("Poisoned RwLock in keyring-rs: please report a bug!");
+    let builder = guard.inner.as_ref().unwrap_or_else(|| &DEFAULT);
+    builder.info()
+}
+
+#[cfg(feature = "std")]
+fn platform_store() -> Option<&'static str> {
+    #[cfg(any(
+        all(target_os = "linux", feature = "secret-service"),
+        all(target_os = "freebsd", feature = "secret-service"),
+        all(target_os = "openbsd", feature = "secret-service")
+    ))]
+    return Some("secret-service");
+    #[cfg(all(target_os = "macos", feature = "apple-native"))]
+    return Some("macos");
+    #[cfg(all(target_os = "ios", feature = "apple-native"))]
+    return Some("ios");
+    #[cfg(all(target_os = "windows", feature = "windows-native"))]
+    return Some("windows");
+    #[cfg(not(any(
+        all(target_os = "linux", feature = "secret-service"),
+        all(target_os = "freebsd", feature = "secret-service"),
+        all(target_os = "openbsd", feature = "secret-service"),
+        all(target_os = "macos", feature = "apple-native"),
+        all(target_os = "ios", feature = "apple-native"),
+        all(target_os = "windows", feature = "windows-native"),
+    )))]
+    None
+}
+
+#[cfg(feature = "std")]
+fn build_strict_credential(target: Option<&str>, service: &str, user: &str) -> Result<Entry> {
+    let guard = DEFAULT_BUILDER
+        .read()
+        .expect("Poisoned RwLock in keyring-rs: please report a bug!");
+    let builder = guard.inner.as_ref().unwrap_or_else(|| &DEFAULT);
+    if !persists(builder.as_ref()) {
+        debug!("default builder {builder:?} doesn't persist credentials");
+        return Err(Error::NoPersistentStore);
+    }
     let credential = builder.build(target, service, user)?;
-    Ok(Entry { inner: credential })
+    debug!("created strict entry {credential:?}");
+    Ok(Entry {
+        spec: Some(credential::CredentialSpec {
+            target: target.map(str::to_string),
+            service: service.to_string(),
+            user: user.to_string(),
+        }),
+        inner: credential,
+        store: builder.info().name,
+    })
+}
+
+#[cfg(feature = "std")]
+fn build_default_credential(target: Option<&str>, service: &str, user: &str) -> Result<Entry> {
+    let guard = DEFAULT_BUILDER
+        .read()
+        .expect("Poisoned RwLock in keyring-rs: please report a bug!");
+    let builder = guard.inner.as_ref().unwrap_or_else(|| &DEFAULT);
+    let (credential, store) = match builder.build(target, service, user) {
+        Ok(credential) => (credential, builder.info().name),
+        Err(err) => match guard.fallback.as_ref() {
+            Some(fallback) => {
+                debug!("default builder {builder:?} failed ({err}), trying fallback {fallback:?}");
+                match fallback.build(target, service, user) {
+                    Ok(credential) => (credential, fallback.info().name),
+                    Err(fallback_err) => {
+                        debug!("fallback builder {fallback:?} also failed: {fallback_err}");
+                        return Err(err);
+                    }
+                }
+            }
+            None => return Err(err),
+        },
+    };
+    let spec = credential::CredentialSpec {
+        target: target.map(str::to_string),
+        service: service.to_string(),
+        user: user.to_string(),
+    };
+    Ok(Entry {
+        inner: credential,
+        spec: Some(spec),
+        store,
+    })
+}
+
+/// Find the existing credentials in the default store that match a search spec,
+/// and return an entry for each of them.
+///
+/// The spec can match each of the target, service, and user exactly,
+/// by prefix, or not at all. For example, if an app namespaces its users
+/// by tenant, as in `tenant1:alice` and `tenant1:bob`, then all the
+/// credentials for one tenant can be found with a [Prefix](credential::Match::Prefix)
+/// match on the user.
+///
+/// The entries are returned sorted by service, then user, then target,
+/// so their order is the same on every platform. If you don't need
+/// that order, you can save time on large result sets by passing
+/// an [unsorted](credential::SearchSpec::unsorted) spec.
+///
+/// Searching never reads the secrets of the found credentials, so it won't
+/// prompt the user to unlock them; secrets are only read when you ask
+/// one of the returned entries for its password or secret.
+///
+/// Searching is only supported by stores that can enumerate their credentials;
+/// others will return a [NotSupportedByStore](Error::NotSupportedByStore) error.
+/// The fallback credential builder (if any) is not searched.
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub fn search(spec: &credential::SearchSpec) -> Result<Vec<Entry>> {
+    debug!("searching default store with {spec:?}");
+    let guard = DEFAULT_BUILDER
+        .read()
+        .expect("Poisoned RwLock in keyring-rs: please report a bug!");
+    let builder = guard.inner.as_ref().unwrap_or_else(|| &DEFAULT);
+    let credentials = builder.search(spec)?;
+    debug!("search found {} credentials", credentials.len());
+    let store = builder.info().name;
+    Ok(credentials
+        .into_iter()
+        .map(|inner| Entry {
+            spec: inner.spec(),
+            inner,
+            store,
+        })
+        .collect())
+}
+
+/// Read the secrets of many credentials in the default store.
+///
+/// There is one result for each spec, in the same order as the specs,
+/// so one missing credential doesn't keep the others from being read.
+/// Stores that can (such as the Secret Service) do the whole batch
+/// over a single connection; others read the credentials one by one.
+/// The fallback credential builder (if any) is not used.
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub fn get_many(specs: &[credential::CredentialSpec]) -> Vec<Result<Vec<u8>>> {
+    debug!("getting {} secrets from default store", specs.len());
+    let guard = DEFAULT_BUILDER
+        .read()
+        .expect("Poisoned RwLock in keyring-rs: please report a bug!");
+    let builder = guard.inner.as_ref().unwrap_or_else(|| &DEFAULT);
+    builder.get_many(specs)
+}
+
+/// Set the secrets of many credentials in the default store.
+///
+/// See [get_many] for how the results are returned and the batch is done.
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub fn set_many(items: &[(credential::CredentialSpec, &[u8])]) -> Vec<Result<()>> {
+    debug!("setting {} secrets in default store", items.len());
+    let guard = DEFAULT_BUILDER
+        .read()
+        .expect("Poisoned RwLock in keyring-rs: please report a bug!");
+    let builder = guard.inner.as_ref().unwrap_or_else(|| &DEFAULT);
+    builder.set_many(items)
+}
+
+/// Delete many credentials from the default store.
+///
+/// See [get_many] for how the results are returned and the batch is done.
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub fn delete_many(specs: &[credential::CredentialSpec]) -> Vec<Result<()>> {
+    debug!("deleting {} credentials from default store", specs.len());
+    let guard = DEFAULT_BUILDER
+        .read()
+        .expect("Poisoned RwLock in keyring-rs: please report a bug!");
+    let builder = guard.inner.as_ref().unwrap_or_else(|| &DEFAULT);
+    builder.delete_many(specs)
+}
+
+/// Copy (or move) credentials from one store to another.
+///
+/// Each selected credential's secret and attributes are read from the
+/// `from` store and written to a credential with the same target, service,
+/// and user in the `to` store (see [Entry::copy_to]), overwriting any
+/// that's already there. In [Move](credential::TransferMode::Move) mode,
+/// each source credential is deleted once it has been copied.
+///
+/// Credentials are transferred one at a time, so a failure to transfer one
+/// of them doesn't stop the others; failures are listed in the returned
+/// report. Selected credentials that can't be built in the `from` store
+/// (because their specs are invalid there), or that don't exist,
+/// are listed as failures.
+/// An error is returned only if the `from` store can't be searched, or if
+/// the search finds credentials that don't know their identity.
+///
+/// Credentials found by a search are copied with the arguments they
+/// would have been built with: a found credential whose target is the
+/// one that the `from` store gives credentials built without a target
+/// (such as `default` on the Secret Service) is copied without one.
+/// Moves onto the source credential itself are refused
+/// (see [Entry::move_to]).
+pub fn transfer(
+    from: &dyn credential::CredentialBuilderApi,
+    to: &dyn credential::CredentialBuilderApi,
+    selection: credential::Selection<'_>,
+    mode: credential::TransferMode,
+) -> Result<credential::TransferReport> {
+    debug!(
+        "transferring {selection:?} from {:?} to {:?}",
+        from.as_any(),
+        to.as_any()
+    );
+    let store = from.info().name;
+    let sources = match selection {
+        credential::Selection::Search(spec) => from
+            .search(spec)?
+            .into_iter()
+            .map(|inner| match inner.spec() {
+                Some(spec) => Ok((build_arguments(from, spec), Ok(inner))),
+                None => Err(Error::NotSupportedByStore(
+                    "search results don't have a known service and user".to_string(),
+                )),
+            })
+            .collect::<Result<Vec<_>>>()?,
+        credential::Selection::Specs(specs) => specs
+            .iter()
+            .map(|spec| {
+                let inner = from.build(spec.target.as_deref(), &spec.service, &spec.user);
+                (spec.clone(), inner)
+            })
+            .collect(),
+    };
+    let mut report = credential::TransferReport::default();
+    for (spec, inner) in sources {
+        let inner = match inner {
+            Ok(inner) => inner,
+            Err(err) => {
+                report.failed.push((spec, err));
+                continue;
+            }
+        };
+        let source = Entry {
+            inner,
+            spec: Some(spec.clone()),
+            store,
+        };
+        let result = match mode {
+            credential::TransferMode::Copy => source.copy_to(to).map(|_| ()),
+            credential::TransferMode::Move => source.move_to(to).map(|_| ()),
+        };
+        match result {
+            Ok(()) => report.transferred.push(spec),
+            Err(err) => report.failed.push((spec, err)),
+        }
+    }
+    debug!(
+        "transferred {} credentials ({} failed)",
+        report.transferred.len(),
+        report.failed.len()
+    );
+    Ok(report)
+}
+
+/// The secret and attributes read from an entry being copied.
+type CopiedCredential = (zeroize::Zeroizing<Vec<u8>>, AttributeMap<String, String>);
+
+/// The arguments that a credential found in a store's search would
+/// have been built with, given its spec as the store reports it.
+///
+/// Stores that normalize a missing target (to `default`, say) report it
+/// as a target, so it's mapped back to none.
+fn build_arguments(
+    from: &dyn credential::CredentialBuilderApi,
+    spec: credential::CredentialSpec,
+) -> credential::CredentialSpec {
+    if spec.target.is_some() {
+        let untargeted = from
+            .build(None, &spec.service, &spec.user)
+            .ok()
+            .and_then(|cred| cred.spec());
+        if untargeted.is_some_and(|untargeted| untargeted.target == spec.target) {
+            return credential::CredentialSpec {
+                target: None,
+                ..spec
+            };
+        }
+    }
+    spec
 }
 
 #[derive(Debug)]
 pub struct Entry {
     inner: Box<Credential>,
+    spec: Option<credential::CredentialSpec>,
+    // the name of the store the credential is in, for tracing
+    #[cfg_attr(not(feature = "tracing"), allow(dead_code))]
+    store: &'static str,
 }
 
 impl Entry {
@@ -283,6 +1467,8 @@ impl Entry {
     /// In the very unlikely event that the internal credential builder's `RwLock`` is poisoned, this function
     /// will panic. If you encounter this, and especially if you can reproduce it, please report a bug with the
     /// details (and preferably a backtrace) so the developers can investigate.
+    #[cfg(feature = "std")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
     pub fn new(service: &str, user: &str) -> Result<Entry> {
         debug!("creating entry with service {service}, user {user}, and no target");
         let entry = build_default_credential(None, service, user)?;
@@ -293,6 +1479,8 @@ impl Entry {
     /// Create an entry for the given target, service, and user.
     ///
     /// The default credential builder is used.
+    #[cfg(feature = "std")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
     pub fn new_with_target(target: &str, service: &str, user: &str) -> Result<Entry> {
         debug!("creating entry with service {service}, user {user}, and target {target}");
         let entry = build_default_credential(Some(target), service, user)?;
@@ -300,10 +1488,39 @@ impl Entry {
         Ok(entry)
     }
 
+    /// Create an entry for the given service and user, but only in a
+    /// credential store that persists credentials beyond the process.
+    ///
+    /// The default credential builder is used, as with [Entry::new], but
+    /// the fallback builder never is. If the default store doesn't persist
+    /// credentials (see [default_store_is_persistent]), this returns a
+    /// [NoPersistentStore](Error::NoPersistentStore) error rather than
+    /// creating an entry whose secrets would silently be lost.
+    #[cfg(feature = "std")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    pub fn new_strict(service: &str, user: &str) -> Result<Entry> {
+        debug!("creating strict entry with service {service}, user {user}, and no target");
+        build_strict_credential(None, service, user)
+    }
+
+    /// Create a strict entry for the given target, service, and user.
+    ///
+    /// See [Entry::new_strict].
+    #[cfg(feature = "std")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    pub fn new_strict_with_target(target: &str, service: &str, user: &str) -> Result<Entry> {
+        debug!("creating strict entry with service {service}, user {user}, and target {target}");
+        build_strict_credential(Some(target), service, user)
+    }
+
     /// Create an entry from a credential that may be in any credential store.
     pub fn new_with_credential(credential: Box<Credential>) -> Entry {
         debug!("create entry from {credential:?}");
-        Entry { inner: credential }
+        Entry {
+            spec: credential.spec(),
+            inner: credential,
+            store: "unknown",
+        }
     }
 
     /// Set the password for this entry.
@@ -315,7 +1532,7 @@ impl Entry {
     /// application wrote the ambiguous credential.
     pub fn set_password(&self, password: &str) -> Result<()> {
         debug!("set password for entry {:?}", self.inner);
-        self.inner.set_password(password)
+        self.traced("set_password", || self.inner.set_password(password))
     }
 
     /// Set the secret for this entry.
@@ -327,7 +1544,96 @@ impl Entry {
     /// application wrote the ambiguous credential.
     pub fn set_secret(&self, secret: &[u8]) -> Result<()> {
         debug!("set secret for entry {:?}", self.inner);
-        self.inner.set_secret(secret)
+        self.traced("set_secret", || self.inner.set_secret(secret))
+    }
+
+    /// Set the password for this entry, but only if its current password is
+    /// `expected`; otherwise, return a [Conflict](Error::Conflict) error.
+    ///
+    /// This makes it safe for several processes that share a credential
+    /// (such as an OAuth token that each of them may refresh) to update it:
+    /// a process whose update fails with a conflict should read the new
+    /// password rather than overwrite it. With most stores, though,
+    /// there is a short window between the check and the update in which
+    /// another process can update the password. The exceptions are the
+    /// [memory] store, whose credentials are only ever shared within
+    /// one process, and the file store, but only for updates made through
+    /// one builder (or its clones) in one process, which share a lock:
+    /// updates made from other processes, or through an unrelated builder
+    /// for the same file, are not checked atomically against them.
+    /// (See the [versioned] module for updates that are conditional
+    /// on a version number rather than the secret.)
+    ///
+    /// Returns a [NoEntry](Error::NoEntry) error if there is no credential.
+    /// See [set_password](Entry::set_password) for the other errors this can return.
+    pub fn update_password(&self, expected: &str, password: &str) -> Result<()> {
+        debug!("update password for entry {:?}", self.inner);
+        self.traced("compare_and_set_password", || {
+            self.inner.compare_and_set_password(expected, password)
+        })
+    }
+
+    /// Set the secret for this entry, but only if its current secret is
+    /// `expected`; otherwise, return a [Conflict](Error::Conflict) error.
+    ///
+    /// This is like [update_password](Entry::update_password), but for secrets.
+    pub fn update_secret(&self, expected: &[u8], secret: &[u8]) -> Result<()> {
+        debug!("update secret for entry {:?}", self.inner);
+        self.traced("compare_and_set_secret", || {
+            self.inner.compare_and_set_secret(expected, secret)
+        })
+    }
+
+    /// Set the secret for this entry to the contents of a reader.
+    ///
+    /// The secret is read into a buffer that is zeroized when it's dropped,
+    /// so no other plaintext copies are left in memory. If the underlying
+    /// store has a limit on the length of secrets, reading stops as soon as
+    /// the limit is exceeded and a [TooLong](Error::TooLong) error is returned.
+    /// An [Invalid](Error::Invalid) error is returned if the reader fails.
+    ///
+    /// See [set_secret](Entry::set_secret) for the other errors this can return.
+    #[cfg(feature = "std")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    pub fn set_secret_from_reader(&self, reader: &mut impl std::io::Read) -> Result<()> {
+        debug!("set secret from reader for entry {:?}", self.inner);
+        let limit = self.inner.max_secret_len();
+        let mut secret = zeroize::Zeroizing::new(Vec::new());
+        let mut chunk = zeroize::Zeroizing::new([0u8; 1024]);
+        loop {
+            let count = match reader.read(chunk.as_mut_slice()) {
+                Ok(0) => break,
+                Ok(count) => count,
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(err) => {
+                    return Err(Error::Invalid("secret reader".to_string(), err.to_string()));
+                }
+            };
+            if let Some(limit) = limit {
+                if secret.len() + count > limit {
+                    return Err(Error::TooLong("secret".to_string(), limit as u32));
+                }
+            }
+            // grow by hand, so the old buffer is zeroized rather than just freed
+            if secret.len() + count > secret.capacity() {
+                let capacity = (secret.len() + count).max(2 * secret.capacity());
+                let mut bigger = zeroize::Zeroizing::new(Vec::with_capacity(capacity));
+                bigger.extend_from_slice(&secret);
+                secret = bigger;
+            }
+            secret.extend_from_slice(&chunk[..count]);
+        }
+        self.traced("set_secret", || self.inner.set_secret(&secret))
+    }
+
+    /// The maximum length (in bytes) of a secret that can be stored
+    /// for this entry, if the underlying store has a limit.
+    ///
+    /// Longer secrets produce a [TooLong](Error::TooLong) error when they are set.
+    /// (Entries whose credentials are built by a
+    /// [ChunkedCredentialBuilder](chunked::ChunkedCredentialBuilder) have no limit.)
+    pub fn max_secret_len(&self) -> Option<usize> {
+        self.inner.max_secret_len()
     }
 
     /// Retrieve the password saved for this entry.
@@ -341,7 +1647,79 @@ impl Entry {
     /// application wrote the ambiguous credential.
     pub fn get_password(&self) -> Result<String> {
         debug!("get password from entry {:?}", self.inner);
-        self.inner.get_password()
+        self.traced("get_password", || self.inner.get_password())
+    }
+
+    /// Retrieve the password saved for this entry, if there is one.
+    ///
+    /// This is like [get_password](Entry::get_password), except that it
+    /// returns `Ok(None)` rather than a [NoEntry](Error::NoEntry) error if
+    /// there is no credential for this entry. (A stored empty password is
+    /// returned as `Ok(Some(""))`.) This is handy for reading optional values.
+    pub fn get_password_opt(&self) -> Result<Option<String>> {
+        match self.get_password() {
+            Ok(password) => Ok(Some(password)),
+            Err(Error::NoEntry) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Whether there is a credential saved for this entry.
+    ///
+    /// Most stores check this without reading the secret, so it's faster than
+    /// calling [get_password](Entry::get_password), and it doesn't prompt the
+    /// user to unlock a secret that isn't going to be read. (Stores that can't
+    /// check it any other way read the secret, but don't return it.)
+    ///
+    /// Can return an [Ambiguous](Error::Ambiguous) error in the same cases
+    /// as [get_password](Entry::get_password).
+    pub fn exists(&self) -> Result<bool> {
+        debug!("check existence of entry {:?}", self.inner);
+        self.traced("exists", || self.inner.exists())
+    }
+
+    /// Retrieve the password saved for this entry, or, if there isn't one,
+    /// save the password made by `f` and return that.
+    ///
+    /// This is handy for values (such as device keys) that are generated the
+    /// first time they're needed. `f` is only called if there is no credential.
+    /// Stores that can (such as the [memory] store and the file store) create
+    /// the credential atomically, so that when several threads or processes race
+    /// to create it they all get the same password; with other stores, the
+    /// password is saved and then read back, so racers usually (but not always)
+    /// agree on the password that was saved last.
+    ///
+    /// See [get_password](Entry::get_password) and [set_password](Entry::set_password)
+    /// for the errors this can return.
+    pub fn get_password_or_set_with(&self, f: impl FnOnce() -> String) -> Result<String> {
+        match self.get_password() {
+            Err(Error::NoEntry) => {
+                debug!("get or set password for entry {:?}", self.inner);
+                let password = zeroize::Zeroizing::new(f());
+                self.traced("get_password_or_set", || {
+                    self.inner.get_password_or_set(&password)
+                })
+            }
+            result => result,
+        }
+    }
+
+    /// Retrieve the secret saved for this entry, or, if there isn't one,
+    /// save the secret made by `f` and return that.
+    ///
+    /// This is like [get_password_or_set_with](Entry::get_password_or_set_with),
+    /// but for secrets.
+    pub fn get_secret_or_set_with(&self, f: impl FnOnce() -> Vec<u8>) -> Result<Vec<u8>> {
+        match self.get_secret() {
+            Err(Error::NoEntry) => {
+                debug!("get or set secret for entry {:?}", self.inner);
+                let secret = zeroize::Zeroizing::new(f());
+                self.traced("get_secret_or_set", || {
+                    self.inner.get_secret_or_set(&secret)
+                })
+            }
+            result => result,
+        }
     }
 
     /// Retrieve the secret saved for this entry.
@@ -355,7 +1733,41 @@ impl Entry {
     /// application wrote the ambiguous credential.
     pub fn get_secret(&self) -> Result<Vec<u8>> {
         debug!("get secret from entry {:?}", self.inner);
-        self.inner.get_secret()
+        self.traced("get_secret", || self.inner.get_secret())
+    }
+
+    /// Retrieve the secret saved for this entry, in a buffer
+    /// that is zeroized when it's dropped.
+    ///
+    /// This is like [get_secret](Entry::get_secret), but saves clients
+    /// from having to protect the returned secret themselves.
+    pub fn get_secret_zeroizing(&self) -> Result<zeroize::Zeroizing<Vec<u8>>> {
+        self.get_secret().map(zeroize::Zeroizing::new)
+    }
+
+    /// Retrieve the password saved for this entry, as a
+    /// [SecretString](secrecy::SecretString).
+    ///
+    /// This is like [get_password](Entry::get_password), except that the
+    /// password is never held in an unprotected `String` that is left to the client.
+    #[cfg(feature = "secrecy")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "secrecy")))]
+    pub fn get_password_secret(&self) -> Result<secrecy::SecretString> {
+        let password = zeroize::Zeroizing::new(self.get_password()?);
+        Ok(secrecy::SecretString::from(password.as_str()))
+    }
+
+    /// Retrieve the secret saved for this entry, as a
+    /// [SecretSlice](secrecy::SecretSlice) (which replaces `SecretVec`
+    /// in current versions of `secrecy`).
+    ///
+    /// This is like [get_secret](Entry::get_secret), except that the
+    /// secret is never held in an unprotected `Vec` that is left to the client.
+    #[cfg(feature = "secrecy")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "secrecy")))]
+    pub fn get_secret_protected(&self) -> Result<secrecy::SecretSlice<u8>> {
+        let secret = self.get_secret_zeroizing()?;
+        Ok(secrecy::SecretSlice::from(secret.to_vec()))
     }
 
     /// Get the attributes on the underlying credential for this entry.
@@ -371,9 +1783,9 @@ impl Entry {
     /// that matches this entry.  This can only happen
     /// on some platforms, and then only if a third-party
     /// application wrote the ambiguous credential.
-    pub fn get_attributes(&self) -> Result<HashMap<String, String>> {
+    pub fn get_attributes(&self) -> Result<AttributeMap<String, String>> {
         debug!("get attributes from entry {:?}", self.inner);
-        self.inner.get_attributes()
+        self.traced("get_attributes", || self.inner.get_attributes())
     }
 
     /// Update the attributes on the underlying credential for this entry.
@@ -391,12 +1803,82 @@ impl Entry {
     /// that matches this entry.  This can only happen
     /// on some platforms, and then only if a third-party
     /// application wrote the ambiguous credential.
-    pub fn update_attributes(&self, attributes: &HashMap<&str, &str>) -> Result<()> {
+    pub fn update_attributes(&self, attributes: &AttributeMap<&str, &str>) -> Result<()> {
         debug!(
             "update attributes for entry {:?} from map {attributes:?}",
             self.inner
         );
-        self.inner.update_attributes(attributes)
+        self.traced("update_attributes", || {
+            self.inner.update_attributes(attributes)
+        })
+    }
+
+    /// Get the creation, modification, and access times that the store keeps
+    /// for the underlying credential for this entry.
+    ///
+    /// Stores only fill in the times they keep: the secret service keeps
+    /// creation and modification times, macOS keychains keep creation and
+    /// modification dates, and Windows keeps the time a credential was last
+    /// written. Other stores return no times. Apps can use these to decide
+    /// when a secret is due to be rotated.
+    ///
+    /// Returns a [NoEntry](Error::NoEntry) error if there isn't a credential for this entry.
+    pub fn get_metadata(&self) -> Result<credential::CredentialMetadata> {
+        debug!("get metadata from entry {:?}", self.inner);
+        self.traced("get_metadata", || self.inner.get_metadata())
+    }
+
+    /// Get the human-readable label of the underlying credential for this entry.
+    ///
+    /// The label is kept in the attribute that each store shows to users
+    /// as the credential's description: the _label_ of keychain items
+    /// and secret service items, and the _comment_ of Windows credentials.
+    ///
+    /// Returns a [NoEntry](Error::NoEntry) error if there isn't a credential for this entry,
+    /// and a [NotSupportedByStore](Error::NotSupportedByStore) error
+    /// if the store doesn't keep labels.
+    pub fn get_label(&self) -> Result<String> {
+        debug!("get label from entry {:?}", self.inner);
+        self.traced("get_label", || self.inner.get_label())
+    }
+
+    /// Set the human-readable label of the underlying credential for this entry.
+    ///
+    /// See [get_label](Entry::get_label) for where the label is kept.
+    ///
+    /// Returns a [NoEntry](Error::NoEntry) error if there isn't a credential for this entry,
+    /// and a [NotSupportedByStore](Error::NotSupportedByStore) error
+    /// if the store doesn't keep labels.
+    pub fn set_label(&self, label: &str) -> Result<()> {
+        debug!("set label {label:?} on entry {:?}", self.inner);
+        self.traced("set_label", || self.inner.set_label(label))
+    }
+
+    /// Unlock the part of the credential store that holds this entry's credential.
+    ///
+    /// This prompts the user if necessary (even if the entry's credential
+    /// was built not to), so apps that get a [Locked](Error::Locked) error
+    /// can call it and then retry.
+    ///
+    /// Returns a [NoEntry](Error::NoEntry) error if there's nothing to unlock,
+    /// and a [NotSupportedByStore](Error::NotSupportedByStore) error
+    /// if the store can't be unlocked this way.
+    pub fn unlock(&self) -> Result<()> {
+        debug!("unlock entry {:?}", self.inner);
+        self.traced("unlock", || self.inner.unlock())
+    }
+
+    /// Make the underlying credential for this entry expire after the given time.
+    ///
+    /// Once it has expired, the store removes the credential, so reading it
+    /// returns a [NoEntry](Error::NoEntry) error.
+    ///
+    /// Returns a [NoEntry](Error::NoEntry) error if there isn't a credential for this entry,
+    /// and a [NotSupportedByStore](Error::NotSupportedByStore) error
+    /// if the store can't expire credentials (which none of the built-in stores can).
+    pub fn set_expiration(&self, ttl: core::time::Duration) -> Result<()> {
+        debug!("set expiration {ttl:?} on entry {:?}", self.inner);
+        self.traced("set_expiration", || self.inner.set_expiration(ttl))
     }
 
     /// Delete the underlying credential for this entry.
@@ -414,23 +1896,246 @@ impl Entry {
     /// affects the underlying credential store.
     pub fn delete_credential(&self) -> Result<()> {
         debug!("delete entry {:?}", self.inner);
-        self.inner.delete_credential()
+        self.traced("delete_credential", || self.inner.delete_credential())
+    }
+
+    /// Copy this entry's secret and attributes into a credential built by another store.
+    ///
+    /// The new credential is built with the same target, service, and user
+    /// as this entry, and an entry for it is returned. This entry is left
+    /// untouched, and the copied secret is zeroized once it has been written.
+    /// Attributes that aren't supported by the other store are ignored.
+    ///
+    /// Only entries that know their identity (see [spec](Entry::spec)) can be
+    /// copied; for other entries, an [Invalid](Error::Invalid) error is returned.
+    /// Otherwise, this can return any of the errors returned by
+    /// [get_secret](Entry::get_secret) on this entry
+    /// and [set_secret](Entry::set_secret) on the new entry.
+    pub fn copy_to(&self, builder: &dyn credential::CredentialBuilderApi) -> Result<Entry> {
+        let copy = self.build_copy(builder)?;
+        self.copy_into(&copy)?;
+        Ok(copy)
+    }
+
+    /// Copy the secret and attributes of this entry's credential
+    /// into another entry's credential (which may be in another store).
+    ///
+    /// Unlike [copy_to](Entry::copy_to), this works for entries that don't
+    /// know their identity, and the other entry can have a different target,
+    /// service, or user. Its secret is overwritten, and attributes that
+    /// aren't supported by its store are ignored. This entry is left untouched.
+    ///
+    /// This can return any of the errors returned by
+    /// [get_secret](Entry::get_secret) on this entry
+    /// and [set_secret](Entry::set_secret) on the other entry.
+    pub fn copy_into(&self, other: &Entry) -> Result<()> {
+        debug!("copy entry {:?} into {:?}", self.inner, other.inner);
+        let (secret, attributes) = self.read_for_copy()?;
+        other.write_copy(&secret, &attributes)
+    }
+
+    /// Move this entry's credential to the store of the given builder.
+    ///
+    /// This is [copy_to](Entry::copy_to), followed by deleting this entry's
+    /// credential once the copy has been made. If the delete fails, its error
+    /// is returned, and the credential is left in both stores.
+    ///
+    /// If the new credential turns out to be this entry's own credential
+    /// (because the builder is for the same store, or wraps it), deleting
+    /// this entry's credential deletes the copy too. That's noticed, and the
+    /// credential is then written back and an [Invalid](Error::Invalid)
+    /// error is returned, rather than losing it.
+    pub fn move_to(&self, builder: &dyn credential::CredentialBuilderApi) -> Result<Entry> {
+        let copy = self.build_copy(builder)?;
+        let (secret, attributes) = self.read_for_copy()?;
+        copy.write_copy(&secret, &attributes)?;
+        self.delete_credential()?;
+        match copy.traced("exists", || copy.inner.exists()) {
+            Ok(false) => {
+                debug!("moved entry {:?} onto itself; restoring it", self.inner);
+                copy.write_copy(&secret, &attributes)?;
+                Err(Error::Invalid(
+                    "destination".to_string(),
+                    "is the same credential as the source".to_string(),
+                ))
+            }
+            Ok(true) => Ok(copy),
+            Err(err) => {
+                debug!("couldn't check moved entry {:?}: {err}", copy.inner);
+                Ok(copy)
+            }
+        }
+    }
+
+    /// Build an entry for this entry's identity in the store of the given builder.
+    fn build_copy(&self, builder: &dyn credential::CredentialBuilderApi) -> Result<Entry> {
+        let spec = self.spec.as_ref().ok_or_else(|| {
+            Error::Invalid(
+                "entry".to_string(),
+                "has no known service and user to copy".to_string(),
+            )
+        })?;
+        debug!("copy entry {:?} to {:?}", self.inner, builder.as_any());
+        let inner = builder.build(spec.target.as_deref(), &spec.service, &spec.user)?;
+        Ok(Entry {
+            inner,
+            spec: Some(spec.clone()),
+            store: builder.info().name,
+        })
+    }
+
+    /// Read the secret and attributes to be copied from this entry.
+    fn read_for_copy(&self) -> Result<CopiedCredential> {
+        let secret =
+            zeroize::Zeroizing::new(self.traced("get_secret", || self.inner.get_secret())?);
+        let attributes = self.traced("get_attributes", || self.inner.get_attributes())?;
+        Ok((secret, attributes))
+    }
+
+    /// Write a copied secret and attributes to this entry.
+    fn write_copy(&self, secret: &[u8], attributes: &AttributeMap<String, String>) -> Result<()> {
+        self.traced("set_secret", || self.inner.set_secret(secret))?;
+        if !attributes.is_empty() {
+            let attributes: AttributeMap<&str, &str> = attributes
+                .iter()
+                .map(|(k, v)| (k.as_str(), v.as_str()))
+                .collect();
+            self.traced("update_attributes", || {
+                self.inner.update_attributes(&attributes)
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Run the given function on this entry, allowing the credential store
+    /// to prompt the user even if it was built to be non-interactive.
+    ///
+    /// This is for apps that are mostly non-interactive but that have the
+    /// occasional moment when prompting the user is acceptable. (Only operations
+    /// made on the current thread during the call are allowed to prompt.)
+    /// See the documentation of each credential store for which of them can
+    /// be made non-interactive.
+    #[cfg(feature = "std")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    pub fn with_interaction_allowed<T>(&self, f: impl FnOnce(&Entry) -> T) -> T {
+        // reset the override on the way out, even if the function panics
+        struct Reset(bool);
+        impl Drop for Reset {
+            fn drop(&mut self) {
+                INTERACTION_ALLOWED.with(|allowed| allowed.set(self.0));
+            }
+        }
+        let _reset = Reset(INTERACTION_ALLOWED.with(|allowed| allowed.replace(true)));
+        f(self)
+    }
+
+    /// Whether the store that holds this entry's credential encrypts its secret at rest.
+    ///
+    /// Apps with at-rest encryption requirements can check this
+    /// (or log it) rather than relying on assumptions about the platform.
+    pub fn at_rest_encryption(&self) -> credential::AtRestStatus {
+        self.inner.at_rest_encryption()
+    }
+
+    /// The target, service, and user this entry was created with, if known.
+    ///
+    /// This is always known for entries created with [new](Entry::new) or
+    /// [new_with_target](Entry::new_with_target). For other entries, it's
+    /// the identity reported by the wrapped credential's
+    /// [spec](credential::CredentialApi::spec) method, if any.
+    pub fn spec(&self) -> Option<&credential::CredentialSpec> {
+        self.spec.as_ref()
     }
 
     /// Return a reference to this entry's wrapped credential.
     ///
-    /// The reference is of the [Any](std::any::Any) type, so it can be
+    /// The reference is of the [Any](core::any::Any) type, so it can be
     /// downgraded to a concrete credential object.  The client must know
     /// what type of concrete object to cast to.
-    pub fn get_credential(&self) -> &dyn std::any::Any {
+    pub fn get_credential(&self) -> &dyn core::any::Any {
         self.inner.as_any()
     }
+
+    /// Run an operation on this entry's credential, and add this entry's
+    /// identity (if known) to any platform error it returns.
+    ///
+    /// With the `tracing` feature, the operation runs in a span that
+    /// records the operation, the store, and fingerprints of the entry's
+    /// service and user, and its outcome and duration are reported in
+    /// an event. Secrets (and errors, which may name the entry) are never traced.
+    fn traced<T>(&self, op: &'static str, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        #[cfg(feature = "tracing")]
+        {
+            let (service, user) = match &self.spec {
+                Some(spec) => (fingerprint(&spec.service), fingerprint(&spec.user)),
+                None => (String::new(), String::new()),
+            };
+            let span = tracing::debug_span!("keyring", op, store = self.store, service, user);
+            let _entered = span.enter();
+            let start = std::time::Instant::now();
+            let result = self.annotate(f());
+            let elapsed_us = start.elapsed().as_micros() as u64;
+            match &result {
+                Ok(_) => tracing::debug!(elapsed_us, "succeeded"),
+                Err(err) => tracing::debug!(elapsed_us, error = error_kind(err), "failed"),
+            }
+            result
+        }
+        #[cfg(not(feature = "tracing"))]
+        {
+            let _ = op;
+            self.annotate(f())
+        }
+    }
+
+    /// Add this entry's identity (if known) to any platform error in the result.
+    fn annotate<T>(&self, result: Result<T>) -> Result<T> {
+        match &self.spec {
+            Some(spec) => result.map_err(|err| err.with_context(&spec.to_string())),
+            None => result,
+        }
+    }
+}
+
+/// A fingerprint of a service or user name, so traces can tell entries
+/// apart without revealing who they are for.
+#[cfg(feature = "tracing")]
+fn fingerprint(value: &str) -> String {
+    use std::hash::{DefaultHasher, Hash, Hasher};
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
 }
 
-#[cfg(doctest)]
+/// The name of an error's variant, which (unlike its message)
+/// never includes the identity of the entry it's about.
+#[cfg(feature = "tracing")]
+fn error_kind(err: &Error) -> &'static str {
+    match err {
+        Error::PlatformFailure(_) => "PlatformFailure",
+        Error::NoStorageAccess(_) => "NoStorageAccess",
+        Error::NoEntry => "NoEntry",
+        Error::BadEncoding(_) => "BadEncoding",
+        Error::TooLong(_, _) => "TooLong",
+        Error::Invalid(_, _) => "Invalid",
+        Error::Ambiguous(_) => "Ambiguous",
+        Error::NoDefaultCredentialBuilder => "NoDefaultCredentialBuilder",
+        Error::NotSupportedByStore(_) => "NotSupportedByStore",
+        Error::Cancelled => "Cancelled",
+        Error::Locked => "Locked",
+        Error::ReadOnly => "ReadOnly",
+        Error::Conflict => "Conflict",
+        Error::NoPersistentStore => "NoPersistentStore",
+        Error::Timeout => "Timeout",
+        Error::Expired => "Expired",
+        Error::IntegrityFailure => "IntegrityFailure",
+    }
+}
+
+#[cfg(all(doctest, feature = "std"))]
 doc_comment::doctest!("../README.md", readme);
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 /// There are no actual tests in this module.
 /// Instead, it contains generics that each keystore invokes in their tests,
 /// passing their store-specific parameters for the generic ones.
@@ -538,6 +2243,116 @@ mod tests {
         generate_random_string_of_len(30)
     }
 
+    /// A memory store that normalizes a missing target to `default`,
+    /// as some platform stores do, so its credentials' specs don't
+    /// match the arguments they were built with.
+    #[derive(Debug, Default)]
+    pub struct DefaultTargetCredentialBuilder(crate::memory::MemoryCredentialBuilder);
+
+    impl crate::credential::CredentialBuilderApi for DefaultTargetCredentialBuilder {
+        fn build(
+            &self,
+            target: Option<&str>,
+            service: &str,
+            user: &str,
+        ) -> Result<Box<crate::credential::Credential>> {
+            self.0
+                .build(Some(target.unwrap_or("default")), service, user)
+        }
+
+        fn as_any(&self) -> &dyn std::any::Any {
+            self
+        }
+
+        fn search(
+            &self,
+            spec: &crate::credential::SearchSpec,
+        ) -> Result<Vec<Box<crate::credential::Credential>>> {
+            self.0.search(spec)
+        }
+    }
+
+    /// A memory store whose credentials, once they have a secret,
+    /// can only be updated with a compare-and-set, so wrappers
+    /// can check that they forward it.
+    #[derive(Debug, Default)]
+    pub struct CasOnlyCredentialBuilder(crate::memory::MemoryCredentialBuilder);
+
+    impl crate::credential::CredentialBuilderApi for CasOnlyCredentialBuilder {
+        fn build(
+            &self,
+            target: Option<&str>,
+            service: &str,
+            user: &str,
+        ) -> Result<Box<crate::credential::Credential>> {
+            Ok(Box::new(CasOnlyCredential(
+                self.0.build(target, service, user)?,
+            )))
+        }
+
+        fn as_any(&self) -> &dyn std::any::Any {
+            self
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct CasOnlyCredential(Box<crate::credential::Credential>);
+
+    impl crate::credential::CredentialApi for CasOnlyCredential {
+        fn set_secret(&self, secret: &[u8]) -> Result<()> {
+            if self.0.exists()? {
+                return Err(Error::NotSupportedByStore(
+                    "updates without compare-and-set".to_string(),
+                ));
+            }
+            self.0.set_secret(secret)
+        }
+
+        fn get_secret(&self) -> Result<Vec<u8>> {
+            self.0.get_secret()
+        }
+
+        fn compare_and_set_password(&self, expected: &str, password: &str) -> Result<()> {
+            self.0.compare_and_set_password(expected, password)
+        }
+
+        fn compare_and_set_secret(&self, expected: &[u8], secret: &[u8]) -> Result<()> {
+            self.0.compare_and_set_secret(expected, secret)
+        }
+
+        fn spec(&self) -> Option<crate::credential::CredentialSpec> {
+            self.0.spec()
+        }
+
+        fn delete_credential(&self) -> Result<()> {
+            self.0.delete_credential()
+        }
+
+        fn as_any(&self) -> &dyn std::any::Any {
+            self
+        }
+    }
+
+    /// Check that the credentials of a builder that wraps a
+    /// [CasOnlyCredentialBuilder] forward their compare-and-set to it.
+    pub fn test_forwards_compare_and_set(builder: &dyn crate::credential::CredentialBuilderApi) {
+        let name = generate_random_string();
+        let entry = Entry::new_with_credential(builder.build(None, &name, "user").unwrap());
+        entry.set_password("first").unwrap();
+        assert!(matches!(
+            entry.set_password("second"),
+            Err(Error::NotSupportedByStore(_))
+        ));
+        entry.update_password("first", "second").unwrap();
+        assert_eq!(entry.get_password().unwrap(), "second");
+        assert!(matches!(
+            entry.update_password("first", "third"),
+            Err(Error::Conflict)
+        ));
+        entry.update_secret(b"second", b"third").unwrap();
+        assert_eq!(entry.get_secret().unwrap(), b"third");
+    }
+
     fn generate_random_bytes_of_len(len: usize) -> Vec<u8> {
         use fastrand;
         use std::iter::repeat_with;
@@ -0,0 +1,450 @@
+/*!
+
+# libsecret credential store
+
+This module keeps credentials in the Secret Service by way of the
+native `libsecret` C library (rather than by talking DBus directly,
+as the [secret_service](crate::secret_service) module does).
+That gets this store the features of libsecret that a direct DBus client
+lacks, such as unlocking collections through the XDG desktop portal,
+and lets it work in the (Flatpak) sandboxes where only libsecret
+is allowed to reach the user's secrets. It's available when the
+`libsecret` feature is enabled, which requires `libsecret-1` (0.19 or later)
+to be installed. It's not the default store on any platform, so clients
+have to set it as their default (or build their credentials from it directly):
+```no_run
+keyring::set_default_credential_builder(keyring::libsecret::default_credential_builder());
+```
+
+Items are identified by the same `target`, `service`, and `username`
+attributes (and have the same `application` attribute) as those made by
+the [secret_service](crate::secret_service) module, so the two stores
+can read and write each other's items. The target defaults to `default`.
+
+Unlike the [secret_service](crate::secret_service) module, this store
+always creates items in the default collection, because libsecret's
+password API can only name collections by alias. Existing items are
+found in any collection, and locked collections are unlocked as needed
+(which may prompt the user). If there is more than one matching item,
+libsecret uses (and deletes) just one of them.
+
+This store doesn't support [search](crate::search), attributes, or labels.
+ */
+use std::ffi::{CStr, CString, c_char, c_int, c_void};
+use std::ptr::{null, null_mut};
+
+use super::credential::{
+    AtRestStatus, Credential, CredentialApi, CredentialBuilder, CredentialBuilderApi,
+    CredentialPersistence, CredentialSpec, CredentialStoreInfo,
+};
+use super::error::{Error as ErrorCode, Result};
+
+/// The representation of a libsecret item.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LibSecretCredential {
+    pub target: String,
+    pub service: String,
+    pub user: String,
+}
+
+impl CredentialApi for LibSecretCredential {
+    /// Create or replace the item for this credential, in the default collection.
+    fn set_secret(&self, secret: &[u8]) -> Result<()> {
+        let [target, service, user] = self.attributes()?;
+        let label = c_string(
+            "label",
+            &format!(
+                "{}@{}:{} (keyring v{})",
+                self.user,
+                self.service,
+                self.target,
+                env!("CARGO_PKG_VERSION")
+            ),
+        )?;
+        let mut error = null_mut();
+        let stored = unsafe {
+            let value = secret_value_new(
+                secret.as_ptr() as *const c_char,
+                secret.len() as isize,
+                c"text/plain".as_ptr(),
+            );
+            let stored = secret_password_store_binary_sync(
+                &schema(),
+                null(),
+                label.as_ptr(),
+                value,
+                null_mut(),
+                &mut error,
+                c"target".as_ptr(),
+                target.as_ptr(),
+                c"service".as_ptr(),
+                service.as_ptr(),
+                c"username".as_ptr(),
+                user.as_ptr(),
+                c"application".as_ptr(),
+                c"rust-keyring".as_ptr(),
+                null::<c_char>(),
+            );
+            secret_value_unref(value as *mut c_void);
+            stored
+        };
+        match stored {
+            0 => Err(decode_error(error)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Read the secret of the item for this credential, if it exists.
+    ///
+    /// Returns a [NoEntry](ErrorCode::NoEntry) error if there is no item.
+    fn get_secret(&self) -> Result<Vec<u8>> {
+        let [target, service, user] = self.attributes()?;
+        let mut error = null_mut();
+        let value = unsafe {
+            secret_password_lookup_binary_sync(
+                &schema(),
+                null_mut(),
+                &mut error,
+                c"target".as_ptr(),
+                target.as_ptr(),
+                c"service".as_ptr(),
+                service.as_ptr(),
+                c"username".as_ptr(),
+                user.as_ptr(),
+                null::<c_char>(),
+            )
+        };
+        if !error.is_null() {
+            return Err(decode_error(error));
+        }
+        if value.is_null() {
+            return Err(ErrorCode::NoEntry);
+        }
+        let mut len = 0;
+        let secret = unsafe {
+            let bytes = secret_value_get(value, &mut len) as *const u8;
+            let secret = std::slice::from_raw_parts(bytes, len).to_vec();
+            secret_value_unref(value as *mut c_void);
+            secret
+        };
+        Ok(secret)
+    }
+
+    /// Delete the item for this credential, if it exists.
+    ///
+    /// Returns a [NoEntry](ErrorCode::NoEntry) error if there is no item.
+    fn delete_credential(&self) -> Result<()> {
+        let [target, service, user] = self.attributes()?;
+        let mut error = null_mut();
+        let cleared = unsafe {
+            secret_password_clear_sync(
+                &schema(),
+                null_mut(),
+                &mut error,
+                c"target".as_ptr(),
+                target.as_ptr(),
+                c"service".as_ptr(),
+                service.as_ptr(),
+                c"username".as_ptr(),
+                user.as_ptr(),
+                null::<c_char>(),
+            )
+        };
+        if !error.is_null() {
+            return Err(decode_error(error));
+        }
+        match cleared {
+            0 => Err(ErrorCode::NoEntry),
+            _ => Ok(()),
+        }
+    }
+
+    /// The target, service, and user of this credential.
+    fn spec(&self) -> Option<CredentialSpec> {
+        Some(CredentialSpec {
+            target: Some(self.target.clone()),
+            service: self.service.clone(),
+            user: self.user.clone(),
+        })
+    }
+
+    /// Whether items are encrypted at rest depends on the secret-service
+    /// provider and on the collection, so it's unknown.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        AtRestStatus::Unknown
+    }
+
+    /// Return the underlying concrete object with an `Any` type so that it can
+    /// be downgraded to a [LibSecretCredential] for platform-specific processing.
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    /// Expose the concrete debug formatter for use via the [Credential] trait
+    fn debug_fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self, f)
+    }
+}
+
+impl LibSecretCredential {
+    /// Create a credential for the given target, service, and user.
+    ///
+    /// The target defaults to `default`. Returns an [Invalid](ErrorCode::Invalid)
+    /// error if the target is empty, or if any of them contains a NUL character.
+    pub fn new_with_target(target: Option<&str>, service: &str, user: &str) -> Result<Self> {
+        if let Some("") = target {
+            return Err(ErrorCode::Invalid(
+                "target".to_string(),
+                "cannot be empty".to_string(),
+            ));
+        }
+        let credential = Self {
+            target: target.unwrap_or("default").to_string(),
+            service: service.to_string(),
+            user: user.to_string(),
+        };
+        credential.attributes()?;
+        Ok(credential)
+    }
+
+    /// The target, service, and user as C strings.
+    fn attributes(&self) -> Result<[CString; 3]> {
+        Ok([
+            c_string("target", &self.target)?,
+            c_string("service", &self.service)?,
+            c_string("user", &self.user)?,
+        ])
+    }
+}
+
+/// The builder for libsecret credentials.
+#[derive(Debug, Default)]
+pub struct LibSecretCredentialBuilder {}
+
+/// Returns an instance of the libsecret credential builder.
+///
+/// This is never called by the crate, since libsecret is
+/// not the default store on any platform.
+pub fn default_credential_builder() -> Box<CredentialBuilder> {
+    Box::new(LibSecretCredentialBuilder {})
+}
+
+impl CredentialBuilderApi for LibSecretCredentialBuilder {
+    /// Build a [LibSecretCredential] for the given target, service, and user.
+    fn build(&self, target: Option<&str>, service: &str, user: &str) -> Result<Box<Credential>> {
+        Ok(Box::new(LibSecretCredential::new_with_target(
+            target, service, user,
+        )?))
+    }
+
+    /// Whether items are encrypted at rest depends on the secret-service
+    /// provider and on the collection, so it's unknown.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        AtRestStatus::Unknown
+    }
+
+    /// Return the underlying builder object with an `Any` type so that it can
+    /// be downgraded to a [LibSecretCredentialBuilder] for platform-specific processing.
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    /// Items in the default collection are kept until deleted.
+    fn persistence(&self) -> CredentialPersistence {
+        CredentialPersistence::UntilDelete
+    }
+
+    /// Credentials have no updatable attributes, and the store can't be searched.
+    fn info(&self) -> CredentialStoreInfo {
+        CredentialStoreInfo::new("libsecret", self.persistence(), self.at_rest_encryption())
+    }
+}
+
+fn c_string(attr: &str, value: &str) -> Result<CString> {
+    CString::new(value).map_err(|_| {
+        ErrorCode::Invalid(
+            attr.to_string(),
+            "cannot contain NUL characters".to_string(),
+        )
+    })
+}
+
+/// Map a libsecret error to a crate error, freeing it.
+///
+/// An item that's still locked (because the user declined to unlock it)
+/// produces a [Locked](ErrorCode::Locked) error.
+fn decode_error(error: *mut GError) -> ErrorCode {
+    let (domain, code, message) = unsafe {
+        let message = CStr::from_ptr((*error).message)
+            .to_string_lossy()
+            .into_owned();
+        let result = ((*error).domain, (*error).code, message);
+        g_error_free(error);
+        result
+    };
+    if domain == unsafe { secret_error_get_quark() } {
+        match code {
+            SECRET_ERROR_IS_LOCKED => return ErrorCode::Locked,
+            SECRET_ERROR_NO_SUCH_OBJECT => return ErrorCode::NoEntry,
+            _ => {}
+        }
+    }
+    ErrorCode::PlatformFailure(Box::from(format!("libsecret: {message}")))
+}
+
+//
+// libsecret and GLib declarations
+//
+
+#[repr(C)]
+struct GError {
+    domain: u32,
+    code: c_int,
+    message: *mut c_char,
+}
+
+#[repr(C)]
+struct SecretSchemaAttribute {
+    name: *const c_char,
+    attr_type: c_int,
+}
+
+#[repr(C)]
+struct SecretSchema {
+    name: *const c_char,
+    flags: c_int,
+    attributes: [SecretSchemaAttribute; 32],
+    reserved: c_int,
+    reserved1: *mut c_void,
+    reserved2: *mut c_void,
+    reserved3: *mut c_void,
+    reserved4: *mut c_void,
+    reserved5: *mut c_void,
+    reserved6: *mut c_void,
+    reserved7: *mut c_void,
+}
+
+/// An opaque `SecretValue`.
+#[repr(C)]
+struct SecretValue {
+    _private: [u8; 0],
+}
+
+const SECRET_SCHEMA_DONT_MATCH_NAME: c_int = 1 << 1;
+const SECRET_SCHEMA_ATTRIBUTE_STRING: c_int = 0;
+const SECRET_ERROR_IS_LOCKED: c_int = 2;
+const SECRET_ERROR_NO_SUCH_OBJECT: c_int = 3;
+
+/// The schema of this crate's items.
+///
+/// The schema name isn't matched, so items made by other
+/// clients with the same attributes are found.
+fn schema() -> SecretSchema {
+    let attribute = |name: &'static CStr| SecretSchemaAttribute {
+        name: name.as_ptr(),
+        attr_type: SECRET_SCHEMA_ATTRIBUTE_STRING,
+    };
+    let mut attributes = std::array::from_fn(|_| SecretSchemaAttribute {
+        name: null(),
+        attr_type: 0,
+    });
+    attributes[0] = attribute(c"target");
+    attributes[1] = attribute(c"service");
+    attributes[2] = attribute(c"username");
+    attributes[3] = attribute(c"application");
+    SecretSchema {
+        name: c"rust-keyring".as_ptr(),
+        flags: SECRET_SCHEMA_DONT_MATCH_NAME,
+        attributes,
+        reserved: 0,
+        reserved1: null_mut(),
+        reserved2: null_mut(),
+        reserved3: null_mut(),
+        reserved4: null_mut(),
+        reserved5: null_mut(),
+        reserved6: null_mut(),
+        reserved7: null_mut(),
+    }
+}
+
+#[link(name = "secret-1")]
+unsafe extern "C" {
+    fn secret_error_get_quark() -> u32;
+    fn secret_value_new(
+        secret: *const c_char,
+        length: isize,
+        content_type: *const c_char,
+    ) -> *mut SecretValue;
+    fn secret_value_get(value: *mut SecretValue, length: *mut usize) -> *const c_char;
+    fn secret_value_unref(value: *mut c_void);
+    fn secret_password_store_binary_sync(
+        schema: *const SecretSchema,
+        collection: *const c_char,
+        label: *const c_char,
+        value: *mut SecretValue,
+        cancellable: *mut c_void,
+        error: *mut *mut GError,
+        ...
+    ) -> c_int;
+    fn secret_password_lookup_binary_sync(
+        schema: *const SecretSchema,
+        cancellable: *mut c_void,
+        error: *mut *mut GError,
+        ...
+    ) -> *mut SecretValue;
+    fn secret_password_clear_sync(
+        schema: *const SecretSchema,
+        cancellable: *mut c_void,
+        error: *mut *mut GError,
+        ...
+    ) -> c_int;
+}
+
+#[link(name = "glib-2.0")]
+unsafe extern "C" {
+    fn g_error_free(error: *mut GError);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::credential::CredentialPersistence;
+    use crate::{Entry, Error, tests::generate_random_string};
+
+    use super::{LibSecretCredential, default_credential_builder};
+
+    #[test]
+    fn test_persistence() {
+        assert!(matches!(
+            default_credential_builder().persistence(),
+            CredentialPersistence::UntilDelete
+        ))
+    }
+
+    #[test]
+    fn test_invalid_parameter() {
+        for (target, service, user) in [(Some(""), "s", "u"), (None, "s\0", "u"), (None, "s", "\0")]
+        {
+            assert!(matches!(
+                LibSecretCredential::new_with_target(target, service, user),
+                Err(Error::Invalid(_, _))
+            ));
+        }
+    }
+
+    fn entry_new(service: &str, user: &str) -> Entry {
+        crate::tests::entry_from_constructor(LibSecretCredential::new_with_target, service, user)
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let name = generate_random_string();
+        let entry = entry_new(&name, "user");
+        assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+        entry.set_password("libsecret password").unwrap();
+        assert_eq!(entry.get_password().unwrap(), "libsecret password");
+        entry.set_secret(&[0, 0xff, 1]).unwrap();
+        assert_eq!(entry.get_secret().unwrap(), vec![0, 0xff, 1]);
+        entry.delete_credential().unwrap();
+        assert!(matches!(entry.delete_credential(), Err(Error::NoEntry)));
+    }
+}
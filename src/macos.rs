@@ -16,6 +16,18 @@ which keychain that entry's credential is created in or searched for.
 If the entry has no target, or the specified target doesn't name (case-insensitive)
 one of the keychains listed above, the 'User' keychain is used.
 
+## Keychain files
+
+Apps can also keep their credentials in a keychain file of their own
+(which isolates them from the user's other items). A target that is an
+absolute path (such as `/Users/me/Library/Keychains/my-app.keychain-db`)
+names a keychain file, and a builder made
+[with_keychain](MacCredentialBuilder::with_keychain) uses its keychain file
+for entries that have no target. Use [create_keychain] to make a new
+keychain file and [unlock_keychain] to unlock an existing one; keychain
+files that aren't unlocked will prompt the user for their password when
+they are used.
+
 For a given service/user pair, this module creates/searches for a credential
 in the target keychain whose _account_ attribute holds the user
 and whose _name_ attribute holds the service.
@@ -30,27 +42,146 @@ also generic credentials, so existing _notes_ created by third-party
 applications can be accessed by this module if you know the value
 of their _account_ attribute (which is not displayed by _Keychain Access_).
 
-Credentials on macOS can have a large number of _key/value_ attributes,
-but this module controls the _account_ and _name_ attributes and
-ignores all the others. so clients can't use it to access or update any attributes.
+Credentials on macOS can have a large number of _key/value_ attributes.
+This module controls the _account_ and _name_ attributes, and
+[get_attributes](crate::Entry::get_attributes) and
+[update_attributes](crate::Entry::update_attributes) give access to three more:
+`label` (which Keychain Access shows as the item's name), `comment`,
+and `description` (which Keychain Access shows as its _kind_).
+All the other attributes are ignored.
+
+Migration tools that need to find items written by other apps, whose
+service and account don't follow this module's mapping, can instead use
+[search_items](MacCredentialBuilder::search_items), which matches items
+by any combination of their service, account, label, and kind.
+
+This store supports [search](crate::search). The user's login keychain
+(or the builder's keychain file, if it has one) is searched for credentials
+with no target, and the _System_, _Common_, and _Dynamic_ keychains for
+credentials whose target matches their name. A keychain file is searched
+if the spec's target is exactly its path.
+(The _Protected_ data keychain is never searched.)
+
+## Internet passwords
+
+Safari and other WebKit-based applications save website credentials
+as _Internet passwords_, which are a different class of keychain item
+than the generic passwords used for entries. To read or update one of these,
+use the [server_credential](MacCredentialBuilder::server_credential) method
+of the builder (or [MacInternetCredential::new_with_host]), which normalizes
+the given host (case, scheme, port, trailing dot) the same way WebKit does,
+and wrap the result with [Entry::new_with_credential](crate::Entry::new_with_credential).
+
+Mail clients and other apps save Internet passwords too, often with
+a specific protocol, port, or path. Entries whose target is `internet`
+(or `internet:` followed by a keychain name or path, as in `internet:System`)
+are Internet passwords whose _server_ is given by the entry's service
+(normalized as above, so it can include a scheme and port) and whose
+_account_ is the entry's user. The protocol, port, and path of a concrete
+[MacInternetCredential] can be set with its
+[with_protocol](MacInternetCredential::with_protocol),
+[with_port](MacInternetCredential::with_port), and
+[with_path](MacInternetCredential::with_path) methods.
+
+## Access control lists
+
+Each item in a macOS keychain has an access control list that names the
+applications that can read its secret without prompting the user.
+(By default, this is just the application that created the item.)
+If your app keeps getting prompted for access to one of its credentials,
+you can downcast the entry to a [MacCredential] and use its
+[trusted_applications](MacCredential::trusted_applications) method to
+see which applications are trusted, and its
+[add_trusted_application](MacCredential::add_trusted_application)
+method to add another one. (Changing the access control list of an item
+will prompt the user for permission.)
+
+## Access control
+
+Apps handling high-value secrets can have the OS prompt for Touch ID (or the
+user's password) before a secret is read, by building their credentials
+[with_access_policy](MacCredentialBuilder::with_access_policy). The items
+of such credentials are kept in the _Protected_ data keychain (which is the only
+one that supports access control), so the builder rejects other targets;
+see the [ios](crate::ios) module for details. The same goes for credentials
+built [with_access_group](MacCredentialBuilder::with_access_group) (to share
+them with other apps) or
+[with_synchronizable](MacCredentialBuilder::with_synchronizable)
+(to sync them through iCloud Keychain).
+
+## Prompts
+
+Apps that run without a user at hand (such as daemons) can build their
+credentials with [MacCredentialBuilder::with_non_interactive], so that
+operations that would prompt the user fail with a
+[NoStorageAccess](ErrorCode::NoStorageAccess) error instead. Since the
+keychain's switch for prompting is process-wide, prompts for other
+keychain items are also suppressed while a non-interactive credential is
+in use. An app that is prepared to prompt at a given moment can wrap those
+operations in [Entry::with_interaction_allowed](crate::Entry::with_interaction_allowed).
  */
-use super::credential::{Credential, CredentialApi, CredentialBuilder, CredentialBuilderApi};
+use std::collections::HashMap;
+use std::ffi::{CString, c_char};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+use super::credential::{
+    AtRestStatus, Credential, CredentialApi, CredentialBuilder, CredentialBuilderApi,
+    CredentialMetadata, CredentialSpec, CredentialStoreInfo, Match, SearchSpec,
+};
 use super::error::{Error as ErrorCode, Result, decode_password};
-use crate::ios::IosCredential;
+use crate::ios::{AccessPolicy, IosCredential};
+use core_foundation::array::{CFArray, CFArrayRef};
+use core_foundation::base::{CFType, CFTypeRef, OSStatus, TCFType};
+use core_foundation::data::{CFData, CFDataRef};
+use core_foundation::date::CFDate;
+use core_foundation::dictionary::{CFDictionary, CFDictionaryRef};
+use core_foundation::string::{CFString, CFStringRef};
 use security_framework::base::Error;
-use security_framework::os::macos::keychain::{SecKeychain, SecPreferencesDomain};
-use security_framework::os::macos::passwords::find_generic_password;
+use security_framework::item::{
+    ItemClass, ItemSearchOptions, ItemUpdateOptions, Limit, SearchResult, update_item,
+};
+use security_framework::os::macos::keychain::{
+    CreateOptions, KeychainUserInteractionLock, SecKeychain, SecPreferencesDomain,
+};
+use security_framework::os::macos::keychain_item::SecKeychainItem;
+use security_framework::os::macos::passwords::{
+    SecAuthenticationType, SecKeychainItemPassword, SecProtocolType, find_generic_password,
+    find_internet_password,
+};
+
+/// The attributes that can be read and updated, with the keys
+/// the keychain uses for them (`kSecAttrLabel`, `kSecAttrComment`,
+/// and `kSecAttrDescription`).
+const ITEM_ATTRIBUTES: [(&str, &str); 3] = [
+    ("label", "labl"),
+    ("comment", "icmt"),
+    ("description", "desc"),
+];
+
+/// Read a date attribute (such as `kSecAttrCreationDate`, whose key is `cdat`)
+/// from an item's attributes, as a duration since the Unix epoch.
+fn date_attribute(attributes: &CFDictionary, key: &str) -> Option<std::time::Duration> {
+    // Core Foundation's absolute times are in seconds since 2001
+    const UNIX_EPOCH_ABSOLUTE_TIME: f64 = -978_307_200.0;
+    let key = CFString::new(key);
+    let value = attributes.find(key.as_CFTypeRef())?;
+    let value = unsafe { CFType::wrap_under_get_rule(*value) };
+    let date = value.downcast::<CFDate>()?;
+    std::time::Duration::try_from_secs_f64(date.abs_time() - UNIX_EPOCH_ABSOLUTE_TIME).ok()
+}
 
 /// The representation of a generic Keychain credential.
 ///
 /// The actual credentials can have lots of attributes
-/// not represented here.  There's no way to use this
-/// module to get at those attributes.
+/// not represented here. Only the label, comment, and
+/// description can be read and updated with this module.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct MacCredential {
     pub domain: MacKeychainDomain,
     pub service: String,
     pub account: String,
+    pub non_interactive: bool,
 }
 
 impl CredentialApi for MacCredential {
@@ -60,6 +191,7 @@ impl CredentialApi for MacCredential {
     /// Since there is only one credential with a given _account_ and _user_
     /// in any given keychain, there is no chance of ambiguity.
     fn set_password(&self, password: &str) -> Result<()> {
+        let _lock = self.interaction_lock()?;
         get_keychain(self)?
             .set_generic_password(&self.service, &self.account, password.as_bytes())
             .map_err(decode_error)?;
@@ -72,6 +204,7 @@ impl CredentialApi for MacCredential {
     /// Since there is only one credential with a given _account_ and _user_
     /// in any given keychain, there is no chance of ambiguity.
     fn set_secret(&self, secret: &[u8]) -> Result<()> {
+        let _lock = self.interaction_lock()?;
         get_keychain(self)?
             .set_generic_password(&self.service, &self.account, secret)
             .map_err(decode_error)?;
@@ -83,6 +216,7 @@ impl CredentialApi for MacCredential {
     /// Returns a [NoEntry](ErrorCode::NoEntry) error if there is no
     /// credential in the store.
     fn get_password(&self) -> Result<String> {
+        let _lock = self.interaction_lock()?;
         let (password_bytes, _) =
             find_generic_password(Some(&[get_keychain(self)?]), &self.service, &self.account)
                 .map_err(decode_error)?;
@@ -94,6 +228,7 @@ impl CredentialApi for MacCredential {
     /// Returns a [NoEntry](ErrorCode::NoEntry) error if there is no
     /// credential in the store.
     fn get_secret(&self) -> Result<Vec<u8>> {
+        let _lock = self.interaction_lock()?;
         let (password_bytes, _) =
             find_generic_password(Some(&[get_keychain(self)?]), &self.service, &self.account)
                 .map_err(decode_error)?;
@@ -105,6 +240,7 @@ impl CredentialApi for MacCredential {
     /// Returns a [NoEntry](ErrorCode::NoEntry) error if there is no
     /// credential in the store.
     fn delete_credential(&self) -> Result<()> {
+        let _lock = self.interaction_lock()?;
         let (_, item) =
             find_generic_password(Some(&[get_keychain(self)?]), &self.service, &self.account)
                 .map_err(decode_error)?;
@@ -112,6 +248,131 @@ impl CredentialApi for MacCredential {
         Ok(())
     }
 
+    /// Get the `label`, `comment`, and `description` attributes of the
+    /// underlying generic credential, if it has them.
+    ///
+    /// Returns a [NoEntry](ErrorCode::NoEntry) error if there is no
+    /// credential in the store.
+    fn get_attributes(&self) -> Result<HashMap<String, String>> {
+        let _lock = self.interaction_lock()?;
+        let found = self
+            .item_search()?
+            .load_attributes(true)
+            .limit(1)
+            .search()
+            .map_err(decode_error)?;
+        let raw = found
+            .first()
+            .and_then(|item| item.simplify_dict())
+            .ok_or(ErrorCode::NoEntry)?;
+        let mut attributes = HashMap::new();
+        for (name, key) in ITEM_ATTRIBUTES {
+            if let Some(value) = raw.get(key) {
+                attributes.insert(name.to_string(), value.clone());
+            }
+        }
+        Ok(attributes)
+    }
+
+    /// Whether there is an underlying generic credential.
+    ///
+    /// This only searches for the item, so it doesn't read its secret
+    /// (or prompt the user for access to it).
+    fn exists(&self) -> Result<bool> {
+        let _lock = self.interaction_lock()?;
+        match self.item_search()?.search().map_err(decode_error) {
+            Ok(_) => Ok(true),
+            Err(ErrorCode::NoEntry) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// The creation and modification dates of the underlying generic credential.
+    ///
+    /// Returns a [NoEntry](ErrorCode::NoEntry) error if there is no
+    /// credential in the store.
+    fn get_metadata(&self) -> Result<CredentialMetadata> {
+        let _lock = self.interaction_lock()?;
+        let found = self
+            .item_search()?
+            .load_attributes(true)
+            .limit(1)
+            .search()
+            .map_err(decode_error)?;
+        match found.first() {
+            Some(SearchResult::Dict(dict)) => Ok(CredentialMetadata {
+                created: date_attribute(dict, "cdat"),
+                modified: date_attribute(dict, "mdat"),
+                ..Default::default()
+            }),
+            _ => Err(ErrorCode::NoEntry),
+        }
+    }
+
+    /// Update the `label`, `comment`, and `description` attributes of the
+    /// underlying generic credential. Other attributes are ignored.
+    ///
+    /// Returns a [NoEntry](ErrorCode::NoEntry) error if there is no
+    /// credential in the store.
+    fn update_attributes(&self, attributes: &HashMap<&str, &str>) -> Result<()> {
+        let _lock = self.interaction_lock()?;
+        let mut options = ItemUpdateOptions::new();
+        if let Some(label) = attributes.get("label") {
+            options.set_label(label);
+        }
+        if let Some(comment) = attributes.get("comment") {
+            options.set_comment(comment);
+        }
+        if let Some(description) = attributes.get("description") {
+            options.set_description(description);
+        }
+        let search = self.item_search()?;
+        if options.label.is_none() && options.comment.is_none() && options.description.is_none() {
+            // nothing to update, but the credential must exist
+            search.search().map_err(decode_error)?;
+            return Ok(());
+        }
+        update_item(&search, &options).map_err(decode_error)
+    }
+
+    /// The label of the underlying generic credential.
+    ///
+    /// Items without a label of their own (which is rare) are
+    /// labeled with their service.
+    fn get_label(&self) -> Result<String> {
+        let attributes = self.get_attributes()?;
+        Ok(attributes
+            .get("label")
+            .cloned()
+            .unwrap_or_else(|| self.service.clone()))
+    }
+
+    /// Set the label of the underlying generic credential.
+    fn set_label(&self, label: &str) -> Result<()> {
+        self.update_attributes(&HashMap::from([("label", label)]))
+    }
+
+    /// The target (keychain domain), service, and user of this credential.
+    ///
+    /// The target is `None` for credentials in the user's login keychain
+    /// (the default domain).
+    fn spec(&self) -> Option<CredentialSpec> {
+        let target = match self.domain {
+            MacKeychainDomain::User => None,
+            ref domain => Some(domain.to_string()),
+        };
+        Some(CredentialSpec {
+            target,
+            service: self.service.clone(),
+            user: self.account.clone(),
+        })
+    }
+
+    /// Keychain items are encrypted at rest.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        AtRestStatus::Encrypted
+    }
+
     /// Return the underlying concrete object with an `Any` type so that it can
     /// be downgraded to a [MacCredential] for platform-specific processing.
     fn as_any(&self) -> &dyn std::any::Any {
@@ -137,6 +398,100 @@ impl MacCredential {
         Ok(self.clone())
     }
 
+    /// Search options that match the underlying generic credential.
+    fn item_search(&self) -> Result<ItemSearchOptions> {
+        let mut search = ItemSearchOptions::new();
+        search
+            .class(ItemClass::generic_password())
+            .keychains(&[get_keychain(self)?])
+            .service(&self.service)
+            .account(&self.account);
+        Ok(search)
+    }
+
+    /// Run a raw query against the keychain with `SecItemCopyMatching`.
+    ///
+    /// This is an escape hatch for interoperating with third-party items that
+    /// can't be described by this crate's model. The query dictionary is
+    /// passed to the Keychain Services API as-is, so it must contain the
+    /// item class and whatever `kSecReturn...` and `kSecMatch...` keys the
+    /// caller wants; the result (if any was asked for) is returned untouched.
+    ///
+    /// Returns a [NoEntry](ErrorCode::NoEntry) error if no items match.
+    pub fn raw_query(query: &CFDictionary) -> Result<Option<CFType>> {
+        let mut result: CFTypeRef = std::ptr::null();
+        check(unsafe { SecItemCopyMatching(query.as_concrete_TypeRef(), &mut result) })?;
+        if result.is_null() {
+            Ok(None)
+        } else {
+            Ok(Some(unsafe { CFType::wrap_under_create_rule(result) }))
+        }
+    }
+
+    /// List the paths of the applications that are trusted to access
+    /// the underlying generic credential.
+    ///
+    /// Entries in the item's access control list that allow access by
+    /// any application don't contribute any paths.
+    ///
+    /// Returns a [NoEntry](ErrorCode::NoEntry) error if there is no
+    /// credential in the store.
+    pub fn trusted_applications(&self) -> Result<Vec<String>> {
+        let (_, item) =
+            find_generic_password(Some(&[get_keychain(self)?]), &self.service, &self.account)
+                .map_err(decode_error)?;
+        let access = copy_item_access(&item)?;
+        let mut paths: Vec<String> = Vec::new();
+        for acl in copy_acl_list(&access)?.iter() {
+            let (applications, _, _) = copy_acl_contents(&acl)?;
+            for app in applications.iter().flat_map(|apps| apps.iter()) {
+                paths.push(copy_trusted_application_path(&app)?);
+            }
+        }
+        paths.sort();
+        paths.dedup();
+        Ok(paths)
+    }
+
+    /// Add the application at the given path to those trusted to access
+    /// the underlying generic credential.
+    ///
+    /// The application is added to every entry in the item's access control list
+    /// that is restricted to specific applications. This will prompt the user
+    /// for permission to change the item.
+    ///
+    /// Returns a [NoEntry](ErrorCode::NoEntry) error if there is no
+    /// credential in the store.
+    pub fn add_trusted_application(&self, path: &Path) -> Result<()> {
+        let (_, item) =
+            find_generic_password(Some(&[get_keychain(self)?]), &self.service, &self.account)
+                .map_err(decode_error)?;
+        let path = CString::new(path.as_os_str().as_bytes()).map_err(|_| {
+            ErrorCode::Invalid("path".to_string(), "cannot contain NUL".to_string())
+        })?;
+        let mut app_ref: CFTypeRef = std::ptr::null();
+        check(unsafe { SecTrustedApplicationCreateFromPath(path.as_ptr(), &mut app_ref) })?;
+        let app = unsafe { CFType::wrap_under_create_rule(app_ref) };
+        let access = copy_item_access(&item)?;
+        for acl in copy_acl_list(&access)?.iter() {
+            let (applications, description, selector) = copy_acl_contents(&acl)?;
+            if let Some(applications) = applications {
+                let mut updated: Vec<CFType> = applications.iter().map(|a| (*a).clone()).collect();
+                updated.push(app.clone());
+                let updated = CFArray::from_CFTypes(&updated);
+                check(unsafe {
+                    SecACLSetContents(
+                        acl.as_CFTypeRef(),
+                        updated.as_concrete_TypeRef(),
+                        description.as_concrete_TypeRef(),
+                        selector,
+                    )
+                })?;
+            }
+        }
+        check(unsafe { SecKeychainItemSetAccess(item.as_CFTypeRef(), access.as_CFTypeRef()) })
+    }
+
     /// Create a credential representing a Mac keychain entry.
     ///
     /// Creating a credential does not put anything into the keychain.
@@ -173,59 +528,624 @@ impl MacCredential {
             domain,
             service: service.to_string(),
             account: user.to_string(),
+            non_interactive: false,
+        })
+    }
+
+    /// Return this credential with prompting turned off or on.
+    ///
+    /// While a non-interactive credential is reading or writing its item,
+    /// keychain prompts are disabled, so operations that would need the user's
+    /// permission fail with a [NoStorageAccess](ErrorCode::NoStorageAccess)
+    /// error instead, except when used inside
+    /// [Entry::with_interaction_allowed](crate::Entry::with_interaction_allowed).
+    pub fn with_non_interactive(mut self, non_interactive: bool) -> Self {
+        self.non_interactive = non_interactive;
+        self
+    }
+
+    /// Disable keychain prompts until the returned lock is dropped,
+    /// if this credential is non-interactive.
+    fn interaction_lock(&self) -> Result<Option<KeychainUserInteractionLock>> {
+        interaction_lock(self.non_interactive)
+    }
+}
+
+/// The representation of an Internet password Keychain credential.
+///
+/// Internet passwords are a distinct class of keychain item from the generic
+/// passwords used by [MacCredential]. They are identified by a _server_, an
+/// _account_, a _protocol_, a _port_, and a _path_, and they are what Safari
+/// (and other WebKit-based browsers) use to save website credentials.
+///
+/// When looking up an existing item, a protocol of [Any](SecProtocolType::Any),
+/// a port of `None`, and an empty path all act as wildcards.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MacInternetCredential {
+    pub domain: MacKeychainDomain,
+    pub server: String,
+    pub account: String,
+    pub protocol: SecProtocolType,
+    pub port: Option<u16>,
+    pub path: String,
+}
+
+impl CredentialApi for MacInternetCredential {
+    /// Create and write a credential with secret for this entry.
+    ///
+    /// If there is an existing matching item, its secret is replaced.
+    /// Otherwise a new item is created with an authentication type
+    /// of HTML form, which is what Safari uses for website passwords.
+    /// (If the credential's protocol is a wildcard, the new item
+    /// uses HTTPS, again to match Safari.)
+    fn set_secret(&self, secret: &[u8]) -> Result<()> {
+        let keychain = get_domain_keychain(&self.domain)?;
+        let protocol = match self.protocol {
+            SecProtocolType::Any => SecProtocolType::HTTPS,
+            protocol => protocol,
+        };
+        match self.find() {
+            Ok((_, mut item)) => item.set_password(secret).map_err(decode_error)?,
+            Err(ErrorCode::NoEntry) => keychain
+                .add_internet_password(
+                    &self.server,
+                    None,
+                    &self.account,
+                    &self.path,
+                    self.port,
+                    protocol,
+                    SecAuthenticationType::HTMLForm,
+                    secret,
+                )
+                .map_err(decode_error)?,
+            Err(err) => return Err(err),
+        }
+        Ok(())
+    }
+
+    /// Look up the secret for this entry, if any.
+    ///
+    /// Returns a [NoEntry](ErrorCode::NoEntry) error if there is no
+    /// credential in the store.
+    fn get_secret(&self) -> Result<Vec<u8>> {
+        let (password_bytes, _) = self.find()?;
+        Ok(password_bytes.to_owned())
+    }
+
+    /// Delete the underlying internet credential for this entry, if any.
+    ///
+    /// Returns a [NoEntry](ErrorCode::NoEntry) error if there is no
+    /// credential in the store.
+    fn delete_credential(&self) -> Result<()> {
+        let (_, item) = self.find()?;
+        item.delete();
+        Ok(())
+    }
+
+    /// The target (`internet`, followed by the keychain if it isn't
+    /// the user's login keychain), server, and account of this credential.
+    fn spec(&self) -> Option<CredentialSpec> {
+        let target = match self.domain {
+            MacKeychainDomain::User => "internet".to_string(),
+            ref domain => format!("internet:{domain}"),
+        };
+        Some(CredentialSpec {
+            target: Some(target),
+            service: self.server.clone(),
+            user: self.account.clone(),
         })
     }
+
+    /// Keychain items are encrypted at rest.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        AtRestStatus::Encrypted
+    }
+
+    /// Return the underlying concrete object with an `Any` type so that it can
+    /// be downgraded to a [MacInternetCredential] for platform-specific processing.
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    /// Expose the concrete debug formatter for use via the [Credential] trait
+    fn debug_fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self, f)
+    }
+}
+
+impl MacInternetCredential {
+    /// Return this credential with the given protocol.
+    ///
+    /// A protocol of [Any](SecProtocolType::Any) matches items with any protocol.
+    pub fn with_protocol(mut self, protocol: SecProtocolType) -> Self {
+        self.protocol = protocol;
+        self
+    }
+
+    /// Return this credential with the given port.
+    ///
+    /// A port of `None` matches items with any port.
+    pub fn with_port(mut self, port: Option<u16>) -> Self {
+        self.port = port;
+        self
+    }
+
+    /// Return this credential with the given path (such as `/login`).
+    ///
+    /// An empty path matches items with any path.
+    pub fn with_path(mut self, path: &str) -> Self {
+        self.path = path.to_string();
+        self
+    }
+
+    /// Create a credential representing a keychain Internet password
+    /// for the given host and account.
+    ///
+    /// The host is normalized the way WebKit normalizes it before saving
+    /// website credentials (see [normalize_host]), so that a host given
+    /// as, say, `https://Example.COM/` will find the item Safari saved
+    /// for `example.com`. If the host specifies a scheme or port, only
+    /// items with that protocol or port will match.
+    ///
+    /// This will fail if the host or account are empty,
+    /// because empty attribute values act as wildcards in the
+    /// Keychain Services API.
+    pub fn new_with_host(
+        domain: Option<MacKeychainDomain>,
+        host: &str,
+        account: &str,
+    ) -> Result<Self> {
+        let domain = domain.unwrap_or(MacKeychainDomain::User);
+        if let MacKeychainDomain::Protected = domain {
+            return Err(ErrorCode::Invalid(
+                "target".to_string(),
+                "Internet passwords can't be in the Protected keychain".to_string(),
+            ));
+        }
+        let (server, protocol, port) = normalize_host(host);
+        if server.is_empty() {
+            return Err(ErrorCode::Invalid(
+                "host".to_string(),
+                "cannot be empty".to_string(),
+            ));
+        }
+        if account.is_empty() {
+            return Err(ErrorCode::Invalid(
+                "account".to_string(),
+                "cannot be empty".to_string(),
+            ));
+        }
+        Ok(Self {
+            domain,
+            server,
+            account: account.to_string(),
+            protocol,
+            port,
+            path: String::new(),
+        })
+    }
+
+    /// Construct a credential from the underlying internet credential.
+    ///
+    /// This checks whether the underlying credential exists.
+    pub fn get_credential(&self) -> Result<Self> {
+        self.find()?;
+        Ok(self.clone())
+    }
+
+    fn find(&self) -> Result<(SecKeychainItemPassword, SecKeychainItem)> {
+        find_internet_password(
+            Some(&[get_domain_keychain(&self.domain)?]),
+            &self.server,
+            None,
+            &self.account,
+            &self.path,
+            self.port,
+            self.protocol,
+            SecAuthenticationType::Any,
+        )
+        .map_err(decode_error)
+    }
+}
+
+/// Normalize a host specification the way WebKit does before saving
+/// website credentials in the keychain.
+///
+/// The returned server is the lowercased host name, without any scheme,
+/// port, path, user info, or trailing dot. The returned protocol is
+/// HTTPS or HTTP if the host specification started with that scheme,
+/// and [Any](SecProtocolType::Any) otherwise. The returned port is
+/// present only if the specification included one.
+pub fn normalize_host(host: &str) -> (String, SecProtocolType, Option<u16>) {
+    let host = host.trim();
+    let (protocol, rest) = match host.split_once("://") {
+        Some((scheme, rest)) if scheme.eq_ignore_ascii_case("https") => {
+            (SecProtocolType::HTTPS, rest)
+        }
+        Some((scheme, rest)) if scheme.eq_ignore_ascii_case("http") => {
+            (SecProtocolType::HTTP, rest)
+        }
+        Some((_, rest)) => (SecProtocolType::Any, rest),
+        None => (SecProtocolType::Any, host),
+    };
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let authority = match authority.rsplit_once('@') {
+        Some((_, authority)) => authority,
+        None => authority,
+    };
+    let (server, port) = match authority.rsplit_once(':') {
+        Some((server, port)) if !port.is_empty() && port.bytes().all(|b| b.is_ascii_digit()) => {
+            (server, port.parse().ok())
+        }
+        _ => (authority, None),
+    };
+    let server = server.trim_end_matches('.').to_ascii_lowercase();
+    (server, protocol, port)
 }
 
 /// The builder for Mac keychain credentials
-pub struct MacCredentialBuilder {}
+#[derive(Debug, Default)]
+pub struct MacCredentialBuilder {
+    non_interactive: bool,
+    keychain: Option<PathBuf>,
+    access_policy: Option<AccessPolicy>,
+    access_group: Option<String>,
+    synchronizable: bool,
+}
+
+impl MacCredentialBuilder {
+    /// Return this builder with prompting turned off or on for the credentials it builds.
+    ///
+    /// See [MacCredential::with_non_interactive].
+    pub fn with_non_interactive(mut self, non_interactive: bool) -> Self {
+        self.non_interactive = non_interactive;
+        self
+    }
+
+    /// Whether this builder's credentials are non-interactive.
+    pub fn non_interactive(&self) -> bool {
+        self.non_interactive
+    }
+
+    /// Return this builder with a keychain file to use for entries that have no target.
+    ///
+    /// The path must be absolute. (Entries whose target names a keychain are
+    /// unaffected.) See [create_keychain] and [unlock_keychain] for making
+    /// the keychain file and unlocking it.
+    pub fn with_keychain(mut self, path: impl Into<PathBuf>) -> Self {
+        self.keychain = Some(path.into());
+        self
+    }
+
+    /// The keychain file this builder uses for entries that have no target, if any.
+    pub fn keychain(&self) -> Option<&Path> {
+        self.keychain.as_deref()
+    }
+
+    /// Return this builder with an access policy (or none) for the credentials it builds.
+    ///
+    /// Credentials with an access policy are kept in the _Protected_ data keychain,
+    /// so building one for any other target gives an [Invalid](ErrorCode::Invalid) error.
+    /// See [IosCredential::with_access_policy].
+    pub fn with_access_policy(mut self, access_policy: Option<AccessPolicy>) -> Self {
+        self.access_policy = access_policy;
+        self
+    }
+
+    /// The access policy of this builder's credentials, if any.
+    pub fn access_policy(&self) -> Option<AccessPolicy> {
+        self.access_policy
+    }
+
+    /// Return this builder with an access group (or none) for the credentials it builds.
+    ///
+    /// Like those with an access policy, these credentials are kept in the
+    /// _Protected_ data keychain. See [IosCredential::with_access_group].
+    pub fn with_access_group(mut self, access_group: Option<&str>) -> Self {
+        self.access_group = access_group.map(|group| group.to_string());
+        self
+    }
+
+    /// The access group of this builder's credentials, if any.
+    pub fn access_group(&self) -> Option<&str> {
+        self.access_group.as_deref()
+    }
+
+    /// Return this builder with iCloud Keychain syncing turned on or off
+    /// for the credentials it builds.
+    ///
+    /// Like those with an access policy, synced credentials are kept in the
+    /// _Protected_ data keychain. See [IosCredential::with_synchronizable].
+    pub fn with_synchronizable(mut self, synchronizable: bool) -> Self {
+        self.synchronizable = synchronizable;
+        self
+    }
+
+    /// Whether this builder's credentials are synced through iCloud Keychain.
+    pub fn synchronizable(&self) -> bool {
+        self.synchronizable
+    }
+
+    /// Build a [MacInternetCredential] for the given host and account
+    /// in the User keychain.
+    ///
+    /// Use this to read (and update) website credentials saved by
+    /// Safari and other WebKit-based applications, which are stored as
+    /// keychain Internet passwords rather than generic passwords.
+    /// See [MacInternetCredential::new_with_host] for how the host
+    /// is normalized.
+    pub fn server_credential(&self, host: &str, account: &str) -> Result<Box<Credential>> {
+        Ok(Box::new(MacInternetCredential::new_with_host(
+            None, host, account,
+        )?))
+    }
+}
 
 /// Returns an instance of the Mac credential builder.
 ///
 /// On Mac, with default features enabled,
 /// this is called once when an entry is first created.
 pub fn default_credential_builder() -> Box<CredentialBuilder> {
-    Box::new(MacCredentialBuilder {})
+    Box::new(MacCredentialBuilder::default())
 }
 
 impl CredentialBuilderApi for MacCredentialBuilder {
     /// Build a [MacCredential] for the given target, service, and user.
     ///
-    /// If a target is specified but not recognized as a keychain name,
-    /// the User keychain is selected.
+    /// If a target is specified but not recognized as a keychain name
+    /// (or path), the User keychain is selected. If no target is specified,
+    /// the builder's keychain file (if any) is selected.
+    ///
+    /// If the target selects Internet passwords (see the module header),
+    /// a [MacInternetCredential] is built instead, for the server given by
+    /// the service and the account given by the user. Its keychain must be
+    /// a recognized keychain name or path, and is the builder's keychain
+    /// file (if any) when the target doesn't name one.
     fn build(&self, target: Option<&str>, service: &str, user: &str) -> Result<Box<Credential>> {
+        if self.access_policy.is_some() || self.access_group.is_some() || self.synchronizable {
+            if let Some(target) = target {
+                if !matches!(target.parse(), Ok(MacKeychainDomain::Protected)) {
+                    return Err(ErrorCode::Invalid(
+                        "target".to_string(),
+                        "must be Protected for credentials with an access policy, \
+                        access group, or syncing"
+                            .to_string(),
+                    ));
+                }
+            }
+            let credential = IosCredential::new_with_target(None, service, user)?
+                .with_access_policy(self.access_policy)
+                .with_access_group(self.access_group.as_deref())
+                .with_synchronizable(self.synchronizable);
+            return Ok(Box::new(credential));
+        }
+        if let Some(keychain) = target.and_then(internet_keychain) {
+            let domain = match keychain {
+                Some(keychain) => keychain.parse()?,
+                None => self.default_domain(),
+            };
+            let credential = MacInternetCredential::new_with_host(Some(domain), service, user)?;
+            return Ok(Box::new(credential));
+        }
         let domain: MacKeychainDomain = if let Some(target) = target {
             target.parse().unwrap_or(MacKeychainDomain::User)
         } else {
-            MacKeychainDomain::User
+            self.default_domain()
         };
         match domain {
             MacKeychainDomain::Protected => Ok(Box::new(IosCredential::new_with_target(
                 None, service, user,
             )?)),
-            _ => Ok(Box::new(MacCredential::new_with_target(
-                Some(domain),
-                service,
-                user,
-            )?)),
+            _ => {
+                let credential = MacCredential::new_with_target(Some(domain), service, user)?;
+                Ok(Box::new(
+                    credential.with_non_interactive(self.non_interactive),
+                ))
+            }
         }
     }
 
+    /// Keychain items are encrypted at rest.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        AtRestStatus::Encrypted
+    }
+
+    /// Return a credential for each generic credential that matches the spec.
+    ///
+    /// Keychains that can't be opened are skipped, except for the user's
+    /// login keychain.
+    fn search(&self, spec: &SearchSpec) -> Result<Vec<Box<Credential>>> {
+        let _lock = interaction_lock(self.non_interactive)?;
+        let mut found: Vec<MacCredential> = Vec::new();
+        let mut domains = vec![
+            self.default_domain(),
+            MacKeychainDomain::System,
+            MacKeychainDomain::Common,
+            MacKeychainDomain::Dynamic,
+        ];
+        if let Some(Ok(MacKeychainDomain::File(path))) =
+            spec.target.exact().map(str::parse::<MacKeychainDomain>)
+        {
+            if self.keychain.as_ref() != Some(&path) {
+                domains.push(MacKeychainDomain::File(path));
+            }
+        }
+        for domain in domains {
+            let target = match domain {
+                MacKeychainDomain::User => None,
+                MacKeychainDomain::File(ref path) if self.keychain.as_ref() == Some(path) => None,
+                ref domain => Some(domain.to_string()),
+            };
+            let target_matches = match &target {
+                Some(target) => spec.target.matches(target),
+                None => matches!(spec.target, Match::Any),
+            };
+            if !target_matches {
+                continue;
+            }
+            let keychain = match get_domain_keychain(&domain) {
+                Ok(keychain) => keychain,
+                Err(_) if target.is_some() => continue,
+                Err(err) => return Err(err),
+            };
+            let items = find_items(keychain, spec.service.exact(), spec.user.exact(), None)?;
+            for attributes in items {
+                let (Some(service), Some(account)) =
+                    (attributes.get("svce"), attributes.get("acct"))
+                else {
+                    continue;
+                };
+                if spec.matches(target.as_deref(), service, account) {
+                    found.push(MacCredential {
+                        domain: domain.clone(),
+                        service: service.clone(),
+                        account: account.clone(),
+                        non_interactive: self.non_interactive,
+                    });
+                }
+            }
+        }
+        if !spec.unsorted {
+            found.sort_by(|a, b| {
+                (&a.service, &a.account, a.domain.to_string()).cmp(&(
+                    &b.service,
+                    &b.account,
+                    b.domain.to_string(),
+                ))
+            });
+        }
+        Ok(found
+            .into_iter()
+            .map(|cred| Box::new(cred) as Box<Credential>)
+            .collect())
+    }
+
     /// Return the underlying builder object with an `Any` type so that it can
     /// be downgraded to a [MacCredentialBuilder] for platform-specific processing.
     fn as_any(&self) -> &dyn std::any::Any {
         self
     }
+
+    /// Keychain items have attributes and can be searched.
+    fn info(&self) -> CredentialStoreInfo {
+        CredentialStoreInfo::new("macos", self.persistence(), self.at_rest_encryption())
+            .with_attributes(true)
+            .with_search(true)
+    }
+}
+
+impl MacCredentialBuilder {
+    /// Find the generic password items whose attributes match a query.
+    ///
+    /// Unlike [search](CredentialBuilderApi::search), which only matches
+    /// the service and account, this can also find items (such as those
+    /// written by other apps) by their label or kind. Exact matches are done
+    /// by the keychain, and prefix matches by this crate. Items with an empty
+    /// service or account are skipped, because empty values act as wildcards
+    /// when credentials look up their items. The found credentials are sorted
+    /// by service, account, and keychain.
+    ///
+    /// Returns an [Invalid](ErrorCode::Invalid) error if the query names
+    /// the Protected keychain, which can't be searched this way.
+    pub fn search_items(&self, query: &MacItemQuery) -> Result<Vec<MacCredential>> {
+        let _lock = interaction_lock(self.non_interactive)?;
+        let domains = match &query.keychain {
+            Some(MacKeychainDomain::Protected) => {
+                return Err(ErrorCode::Invalid(
+                    "keychain".to_string(),
+                    "the Protected keychain can't be searched for items".to_string(),
+                ));
+            }
+            Some(domain) => vec![domain.clone()],
+            None => vec![
+                self.default_domain(),
+                MacKeychainDomain::System,
+                MacKeychainDomain::Common,
+                MacKeychainDomain::Dynamic,
+            ],
+        };
+        let mut found: Vec<MacCredential> = Vec::new();
+        for domain in domains {
+            let keychain = match get_domain_keychain(&domain) {
+                Ok(keychain) => keychain,
+                // only the keychain the query names, or the default one, must exist
+                Err(_) if query.keychain.is_none() && domain != self.default_domain() => continue,
+                Err(err) => return Err(err),
+            };
+            let items = find_items(
+                keychain,
+                query.service.exact(),
+                query.account.exact(),
+                query.label.exact(),
+            )?;
+            for attributes in items {
+                let field = |key: &str| attributes.get(key).map(String::as_str).unwrap_or("");
+                let (service, account) = (field("svce"), field("acct"));
+                if service.is_empty() || account.is_empty() {
+                    continue;
+                }
+                if query.service.matches(service)
+                    && query.account.matches(account)
+                    && query.label.matches(field("labl"))
+                    && query.kind.matches(field("desc"))
+                {
+                    found.push(MacCredential {
+                        domain: domain.clone(),
+                        service: service.to_string(),
+                        account: account.to_string(),
+                        non_interactive: self.non_interactive,
+                    });
+                }
+            }
+        }
+        found.sort_by(|a, b| {
+            (&a.service, &a.account, a.domain.to_string()).cmp(&(
+                &b.service,
+                &b.account,
+                b.domain.to_string(),
+            ))
+        });
+        Ok(found)
+    }
+
+    /// The keychain for entries with no target.
+    fn default_domain(&self) -> MacKeychainDomain {
+        match &self.keychain {
+            Some(path) => MacKeychainDomain::File(path.clone()),
+            None => MacKeychainDomain::User,
+        }
+    }
+}
+
+/// A query for [MacCredentialBuilder::search_items].
+///
+/// The default query matches every generic password item
+/// in the builder's default keychain and the System, Common,
+/// and Dynamic keychains.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MacItemQuery {
+    /// Matches the item's service (its _where_ in Keychain Access)
+    pub service: Match,
+    /// Matches the item's account
+    pub account: Match,
+    /// Matches the item's label (its _name_ in Keychain Access)
+    pub label: Match,
+    /// Matches the item's kind (its description)
+    pub kind: Match,
+    /// The only keychain to search, if given
+    pub keychain: Option<MacKeychainDomain>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
-/// The four pre-defined Mac keychains.
+/// The pre-defined Mac keychains, or a keychain file.
 pub enum MacKeychainDomain {
     User,
     System,
     Common,
     Dynamic,
     Protected,
+    /// The keychain in the file with the given (absolute) path.
+    File(PathBuf),
 }
 
 impl std::fmt::Display for MacKeychainDomain {
@@ -236,6 +1156,7 @@ impl std::fmt::Display for MacKeychainDomain {
             MacKeychainDomain::Common => "Common".fmt(f),
             MacKeychainDomain::Dynamic => "Dynamic".fmt(f),
             MacKeychainDomain::Protected => "Protected".fmt(f),
+            MacKeychainDomain::File(path) => path.display().fmt(f),
         }
     }
 }
@@ -247,8 +1168,12 @@ impl std::str::FromStr for MacKeychainDomain {
     ///
     /// We accept any case in the string,
     /// but the value has to match a known keychain domain name
+    /// (or be an absolute path to a keychain file)
     /// or else we assume the login keychain is meant.
     fn from_str(s: &str) -> Result<Self> {
+        if s.starts_with('/') {
+            return Ok(MacKeychainDomain::File(PathBuf::from(s)));
+        }
         match s.to_ascii_lowercase().as_str() {
             "user" => Ok(MacKeychainDomain::User),
             "system" => Ok(MacKeychainDomain::System),
@@ -258,19 +1183,85 @@ impl std::str::FromStr for MacKeychainDomain {
             "data protection" => Ok(MacKeychainDomain::Protected),
             _ => Err(ErrorCode::Invalid(
                 "target".to_string(),
-                format!("'{s}' is not User, System, Common, Dynamic, or Protected"),
+                format!("'{s}' is not User, System, Common, Dynamic, Protected, or a path"),
             )),
         }
     }
 }
 
+/// The attributes of the generic password items in a keychain
+/// that have the given service, account, and label (where given).
+///
+/// Finding no items is not an error.
+fn find_items(
+    keychain: SecKeychain,
+    service: Option<&str>,
+    account: Option<&str>,
+    label: Option<&str>,
+) -> Result<Vec<HashMap<String, String>>> {
+    let mut options = ItemSearchOptions::new();
+    options
+        .class(ItemClass::generic_password())
+        .keychains(&[keychain])
+        .load_attributes(true)
+        .limit(Limit::All);
+    if let Some(service) = service {
+        options.service(service);
+    }
+    if let Some(account) = account {
+        options.account(account);
+    }
+    if let Some(label) = label {
+        options.label(label);
+    }
+    match options.search().map_err(decode_error) {
+        Ok(items) => Ok(items
+            .iter()
+            .filter_map(|item| item.simplify_dict())
+            .collect()),
+        Err(ErrorCode::NoEntry) => Ok(Vec::new()),
+        Err(err) => Err(err),
+    }
+}
+
+/// If a target selects Internet passwords (it's `internet`, or `internet:`
+/// followed by a keychain), the keychain it names (if any).
+fn internet_keychain(target: &str) -> Option<Option<&str>> {
+    let prefix = target.get(..8)?;
+    if !prefix.eq_ignore_ascii_case("internet") {
+        return None;
+    }
+    match &target[8..] {
+        "" => Some(None),
+        rest => Some(Some(rest.strip_prefix(':')?)),
+    }
+}
+
+/// Disable keychain prompts until the returned lock is dropped,
+/// if the caller is non-interactive.
+fn interaction_lock(non_interactive: bool) -> Result<Option<KeychainUserInteractionLock>> {
+    if non_interactive && !crate::interaction_allowed_override() {
+        let lock = SecKeychain::disable_user_interaction().map_err(decode_error)?;
+        Ok(Some(lock))
+    } else {
+        Ok(None)
+    }
+}
+
 fn get_keychain(cred: &MacCredential) -> Result<SecKeychain> {
-    let domain = match cred.domain {
+    get_domain_keychain(&cred.domain)
+}
+
+fn get_domain_keychain(domain: &MacKeychainDomain) -> Result<SecKeychain> {
+    let domain = match domain {
         MacKeychainDomain::User => SecPreferencesDomain::User,
         MacKeychainDomain::System => SecPreferencesDomain::System,
         MacKeychainDomain::Common => SecPreferencesDomain::Common,
         MacKeychainDomain::Dynamic => SecPreferencesDomain::Dynamic,
         MacKeychainDomain::Protected => panic!("Protected is not a keychain domain on macOS"),
+        MacKeychainDomain::File(path) => {
+            return SecKeychain::open(path).map_err(decode_error);
+        }
     };
     match SecKeychain::default_for_domain(domain) {
         Ok(keychain) => Ok(keychain),
@@ -278,6 +1269,110 @@ fn get_keychain(cred: &MacCredential) -> Result<SecKeychain> {
     }
 }
 
+/// Create a keychain file at the given (absolute) path, protected by the given password.
+///
+/// The new keychain is unlocked. Fails if there's already a keychain at the path.
+pub fn create_keychain(path: &Path, password: &str) -> Result<()> {
+    CreateOptions::new()
+        .password(password)
+        .create(path)
+        .map_err(decode_error)?;
+    Ok(())
+}
+
+/// Unlock the keychain file at the given path.
+///
+/// If no password is given, the user is prompted for it.
+pub fn unlock_keychain(path: &Path, password: Option<&str>) -> Result<()> {
+    let mut keychain = SecKeychain::open(path).map_err(decode_error)?;
+    keychain.unlock(password).map_err(decode_error)
+}
+
+//
+// Raw query and access control list utilities. The access control list APIs
+// are deprecated (along with file-based keychains), but they are the only way
+// to read or change which applications can access an item, so we declare them
+// ourselves (along with the raw query API, which isn't wrapped either).
+//
+
+#[link(name = "Security", kind = "framework")]
+unsafe extern "C" {
+    fn SecItemCopyMatching(query: CFDictionaryRef, result: *mut CFTypeRef) -> OSStatus;
+    fn SecKeychainItemCopyAccess(item: CFTypeRef, access: *mut CFTypeRef) -> OSStatus;
+    fn SecKeychainItemSetAccess(item: CFTypeRef, access: CFTypeRef) -> OSStatus;
+    fn SecAccessCopyACLList(access: CFTypeRef, acl_list: *mut CFArrayRef) -> OSStatus;
+    fn SecACLCopyContents(
+        acl: CFTypeRef,
+        application_list: *mut CFArrayRef,
+        description: *mut CFStringRef,
+        prompt_selector: *mut u16,
+    ) -> OSStatus;
+    fn SecACLSetContents(
+        acl: CFTypeRef,
+        application_list: CFArrayRef,
+        description: CFStringRef,
+        prompt_selector: u16,
+    ) -> OSStatus;
+    fn SecTrustedApplicationCopyData(app: CFTypeRef, data: *mut CFDataRef) -> OSStatus;
+    fn SecTrustedApplicationCreateFromPath(path: *const c_char, app: *mut CFTypeRef) -> OSStatus;
+}
+
+fn check(status: OSStatus) -> Result<()> {
+    match status {
+        0 => Ok(()),
+        code => Err(decode_error(Error::from_code(code))),
+    }
+}
+
+fn copy_item_access(item: &SecKeychainItem) -> Result<CFType> {
+    let mut access: CFTypeRef = std::ptr::null();
+    check(unsafe { SecKeychainItemCopyAccess(item.as_CFTypeRef(), &mut access) })?;
+    Ok(unsafe { CFType::wrap_under_create_rule(access) })
+}
+
+fn copy_acl_list(access: &CFType) -> Result<CFArray<CFType>> {
+    let mut list: CFArrayRef = std::ptr::null();
+    check(unsafe { SecAccessCopyACLList(access.as_CFTypeRef(), &mut list) })?;
+    Ok(unsafe { CFArray::wrap_under_create_rule(list) })
+}
+
+/// Returns the applications (if the ACL is restricted to specific ones),
+/// the description, and the prompt selector of an ACL.
+fn copy_acl_contents(acl: &CFType) -> Result<(Option<CFArray<CFType>>, CFString, u16)> {
+    let mut applications: CFArrayRef = std::ptr::null();
+    let mut description: CFStringRef = std::ptr::null();
+    let mut selector: u16 = 0;
+    check(unsafe {
+        SecACLCopyContents(
+            acl.as_CFTypeRef(),
+            &mut applications,
+            &mut description,
+            &mut selector,
+        )
+    })?;
+    let applications = if applications.is_null() {
+        None
+    } else {
+        Some(unsafe { CFArray::wrap_under_create_rule(applications) })
+    };
+    let description = if description.is_null() {
+        CFString::new("")
+    } else {
+        unsafe { CFString::wrap_under_create_rule(description) }
+    };
+    Ok((applications, description, selector))
+}
+
+fn copy_trusted_application_path(app: &CFType) -> Result<String> {
+    let mut data: CFDataRef = std::ptr::null();
+    check(unsafe { SecTrustedApplicationCopyData(app.as_CFTypeRef(), &mut data) })?;
+    let data = unsafe { CFData::wrap_under_create_rule(data) };
+    // the data is the application's path as a NUL-terminated C string
+    let bytes = data.bytes();
+    let bytes = bytes.strip_suffix(&[0]).unwrap_or(bytes);
+    Ok(String::from_utf8_lossy(bytes).to_string())
+}
+
 /// Map a Mac API error to a crate error with appropriate annotation
 ///
 /// The macOS error code values used here are from
@@ -289,16 +1384,26 @@ pub fn decode_error(err: Error) -> ErrorCode {
         -25294 => ErrorCode::NoStorageAccess(Box::new(err)), // errSecNoSuchKeychain
         -25295 => ErrorCode::NoStorageAccess(Box::new(err)), // errSecInvalidKeychain
         -25300 => ErrorCode::NoEntry,                        // errSecItemNotFound
+        -25308 => ErrorCode::NoStorageAccess(Box::new(err)), // errSecInteractionNotAllowed
+        -128 => ErrorCode::Cancelled,                        // errSecUserCanceled
         _ => ErrorCode::PlatformFailure(Box::new(err)),
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::credential::CredentialPersistence;
+    use std::collections::HashMap;
+
+    use crate::credential::{
+        CredentialApi, CredentialBuilderApi, CredentialPersistence, Match, SearchSpec,
+    };
     use crate::{Entry, Error, tests::generate_random_string};
 
-    use super::{MacCredential, default_credential_builder};
+    use super::{
+        AccessPolicy, IosCredential, MacCredential, MacCredentialBuilder, MacInternetCredential,
+        MacItemQuery, MacKeychainDomain, SecProtocolType, default_credential_builder,
+        internet_keychain, normalize_host,
+    };
 
     #[test]
     fn test_persistence() {
@@ -384,7 +1489,158 @@ mod tests {
 
     #[test]
     fn test_get_update_attributes() {
-        crate::tests::test_noop_get_update_attributes(entry_new);
+        let name = generate_random_string();
+        let entry = entry_new(&name, &name);
+        assert!(matches!(entry.get_attributes(), Err(Error::NoEntry)));
+        let map = HashMap::from([("comment", "a comment"), ("ignored", "value")]);
+        assert!(matches!(entry.update_attributes(&map), Err(Error::NoEntry)));
+        entry
+            .set_password("test password for attributes")
+            .expect("Can't set password for attribute test");
+        entry
+            .update_attributes(&map)
+            .expect("Can't update attributes");
+        let attributes = entry.get_attributes().expect("Can't get attributes");
+        assert_eq!(
+            attributes.get("comment").map(String::as_str),
+            Some("a comment")
+        );
+        assert!(!attributes.contains_key("ignored"));
+        entry
+            .update_attributes(&HashMap::from([("label", "a label")]))
+            .expect("Can't update label");
+        let attributes = entry.get_attributes().expect("Can't get attributes");
+        assert_eq!(attributes.get("label").map(String::as_str), Some("a label"));
+        assert_eq!(
+            attributes.get("comment").map(String::as_str),
+            Some("a comment")
+        );
+        entry
+            .delete_credential()
+            .expect("Couldn't delete after attribute test");
+    }
+
+    #[test]
+    fn test_search() {
+        let service = generate_random_string();
+        let users = ["tenant1:alice", "tenant1:bob", "tenant2:carol"];
+        for user in users {
+            entry_new(&service, user)
+                .set_password("test search")
+                .expect("Can't set password for search");
+        }
+        let spec = SearchSpec {
+            service: Match::Exact(service.clone()),
+            user: Match::Prefix("tenant1:".to_string()),
+            ..Default::default()
+        };
+        let found = default_credential_builder()
+            .search(&spec)
+            .expect("Can't search");
+        let found_users: Vec<String> = found
+            .iter()
+            .map(|cred| cred.spec().expect("Found credential has no spec").user)
+            .collect();
+        assert_eq!(found_users, vec!["tenant1:alice", "tenant1:bob"]);
+        for user in users {
+            entry_new(&service, user)
+                .delete_credential()
+                .expect("Can't delete search credential");
+        }
+    }
+
+    #[test]
+    fn test_search_items() {
+        let service = generate_random_string();
+        let label = generate_random_string();
+        for (user, labeled) in [("alice", true), ("bob", false), ("carol", true)] {
+            let entry = entry_new(&service, user);
+            entry
+                .set_password("test search items")
+                .expect("Can't set password for search items");
+            if labeled {
+                entry.set_label(&label).expect("Can't set label");
+                entry
+                    .update_attributes(&HashMap::from([("description", "migrated")]))
+                    .expect("Can't set kind");
+            }
+        }
+        let builder = MacCredentialBuilder::default();
+        let query = MacItemQuery {
+            label: Match::Exact(label.clone()),
+            ..Default::default()
+        };
+        let found = builder.search_items(&query).expect("Can't search items");
+        let accounts: Vec<&str> = found.iter().map(|cred| cred.account.as_str()).collect();
+        assert_eq!(accounts, vec!["alice", "carol"]);
+        let query = MacItemQuery {
+            service: Match::Exact(service.clone()),
+            kind: Match::Prefix("migr".to_string()),
+            account: Match::Prefix("c".to_string()),
+            keychain: Some(MacKeychainDomain::User),
+            ..Default::default()
+        };
+        let found = builder.search_items(&query).expect("Can't search items");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].account, "carol");
+        let query = MacItemQuery {
+            keychain: Some(MacKeychainDomain::Protected),
+            ..Default::default()
+        };
+        assert!(matches!(
+            builder.search_items(&query),
+            Err(Error::Invalid(_, _))
+        ));
+        for user in ["alice", "bob", "carol"] {
+            entry_new(&service, user)
+                .delete_credential()
+                .expect("Can't delete search items credential");
+        }
+    }
+
+    #[test]
+    fn test_keychain_file() {
+        let path = std::env::temp_dir().join(format!("{}.keychain-db", generate_random_string()));
+        super::create_keychain(&path, "test password").expect("Can't create keychain");
+        super::unlock_keychain(&path, Some("test password")).expect("Can't unlock keychain");
+        let builder = MacCredentialBuilder::default().with_keychain(&path);
+        assert_eq!(builder.keychain(), Some(path.as_path()));
+        let name = generate_random_string();
+        let entry = Entry::new_with_credential(builder.build(None, &name, &name).unwrap());
+        entry.set_password("in a file").expect("Can't set password");
+        let target = path.to_str().unwrap();
+        let by_path = builder.build(Some(target), &name, &name).unwrap();
+        assert_eq!(by_path.get_password().unwrap(), "in a file");
+        assert!(matches!(
+            entry_new(&name, &name).get_password(),
+            Err(Error::NoEntry)
+        ));
+        let found = builder
+            .search(&SearchSpec {
+                service: Match::Exact(name.clone()),
+                ..Default::default()
+            })
+            .expect("Can't search");
+        assert_eq!(found.len(), 1);
+        entry.delete_credential().expect("Can't delete password");
+        std::fs::remove_file(&path).expect("Can't remove keychain file");
+    }
+
+    #[test]
+    fn test_access_policy_target() {
+        let builder =
+            MacCredentialBuilder::default().with_access_policy(Some(AccessPolicy::BiometryAny));
+        assert_eq!(builder.access_policy(), Some(AccessPolicy::BiometryAny));
+        assert!(matches!(
+            builder.build(Some("System"), "service", "user"),
+            Err(Error::Invalid(_, _))
+        ));
+        let cred = builder.build(Some("protected"), "service", "user").unwrap();
+        let ios_cred: &IosCredential = cred
+            .as_any()
+            .downcast_ref()
+            .expect("credential not an IosCredential");
+        assert_eq!(ios_cred.access_policy, Some(AccessPolicy::BiometryAny));
     }
 
     #[test]
@@ -414,4 +1670,201 @@ mod tests {
                 .expect("credential not an iOS credential");
         }
     }
+
+    #[test]
+    fn test_trusted_applications() {
+        let name = generate_random_string();
+        let cred = MacCredential::new_with_target(None, &name, &name)
+            .expect("Can't create credential for ACL test");
+        assert!(
+            matches!(cred.trusted_applications(), Err(Error::NoEntry)),
+            "Read ACL of missing credential"
+        );
+        cred.set_password("test password for ACL")
+            .expect("Can't set password for ACL test");
+        let apps = cred
+            .trusted_applications()
+            .expect("Can't read trusted applications");
+        assert!(!apps.is_empty(), "Creating app is not trusted");
+        cred.delete_credential()
+            .expect("Can't delete credential for ACL test");
+    }
+
+    #[test]
+    fn test_raw_query() {
+        use core_foundation::base::{CFType, TCFType};
+        use core_foundation::boolean::CFBoolean;
+        use core_foundation::dictionary::CFDictionary;
+        use core_foundation::string::CFString;
+
+        let name = generate_random_string();
+        let cred = MacCredential::new_with_target(None, &name, &name)
+            .expect("Can't create credential for raw query");
+        // these are the values of kSecClass, kSecClassGenericPassword,
+        // kSecAttrService, and kSecReturnAttributes
+        let query = CFDictionary::from_CFType_pairs(&[
+            (CFString::new("class"), CFString::new("genp").as_CFType()),
+            (CFString::new("svce"), CFString::new(&name).as_CFType()),
+            (
+                CFString::new("r_Attributes"),
+                CFBoolean::true_value().as_CFType(),
+            ),
+        ]);
+        assert!(
+            matches!(
+                MacCredential::raw_query(&query.to_untyped()),
+                Err(Error::NoEntry)
+            ),
+            "Raw query found missing credential"
+        );
+        cred.set_password("test raw query")
+            .expect("Can't set password for raw query");
+        let result: Option<CFType> =
+            MacCredential::raw_query(&query.to_untyped()).expect("Can't run raw query");
+        assert!(result.is_some(), "Raw query returned no attributes");
+        cred.delete_credential()
+            .expect("Can't delete credential for raw query");
+    }
+
+    #[test]
+    fn test_normalize_host() {
+        for (host, server, protocol, port) in [
+            ("example.com", "example.com", SecProtocolType::Any, None),
+            ("Example.COM.", "example.com", SecProtocolType::Any, None),
+            (
+                "https://Example.com/",
+                "example.com",
+                SecProtocolType::HTTPS,
+                None,
+            ),
+            (
+                "HTTP://example.com:8080/login?x=1",
+                "example.com",
+                SecProtocolType::HTTP,
+                Some(8080),
+            ),
+            (
+                "ftp://user@files.example.com",
+                "files.example.com",
+                SecProtocolType::Any,
+                None,
+            ),
+            (
+                "  example.com:443  ",
+                "example.com",
+                SecProtocolType::Any,
+                Some(443),
+            ),
+        ] {
+            assert_eq!(
+                normalize_host(host),
+                (server.to_string(), protocol, port),
+                "Wrong normalization of {host}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_internet_target() {
+        for (target, keychain) in [
+            ("internet", Some(None)),
+            ("Internet:System", Some(Some("System"))),
+            (
+                "internet:/tmp/test.keychain-db",
+                Some(Some("/tmp/test.keychain-db")),
+            ),
+            ("internets", None),
+            ("user", None),
+            ("", None),
+        ] {
+            assert_eq!(
+                internet_keychain(target),
+                keychain,
+                "Wrong keychain for {target}"
+            );
+        }
+        let builder = MacCredentialBuilder::default();
+        let cred = builder
+            .build(
+                Some("internet:system"),
+                "imap://Mail.Example.com:993",
+                "user",
+            )
+            .expect("Can't build internet credential");
+        let internet: &MacInternetCredential = cred
+            .as_any()
+            .downcast_ref()
+            .expect("Not a mac internet credential");
+        assert_eq!(internet.domain, MacKeychainDomain::System);
+        assert_eq!(internet.server, "mail.example.com");
+        assert_eq!(internet.port, Some(993));
+        let spec = cred.spec().expect("No spec for internet credential");
+        assert_eq!(spec.target.as_deref(), Some("internet:System"));
+        assert!(matches!(
+            builder.build(Some("internet:nowhere"), "example.com", "user"),
+            Err(Error::Invalid(_, _))
+        ));
+    }
+
+    #[test]
+    fn test_round_trip_internet_path() {
+        let name = generate_random_string();
+        let host = format!("{name}.example.com");
+        let base = MacInternetCredential::new_with_host(None, &host, &name)
+            .expect("Can't create internet credential")
+            .with_protocol(SecProtocolType::HTTPS)
+            .with_port(Some(8443));
+        let login = Entry::new_with_credential(Box::new(base.clone().with_path("/login")));
+        crate::tests::test_round_trip("internet credential with path", &login, "test path");
+        login.set_password("test path").expect("Can't set password");
+        // other paths don't match, but an empty path matches any path
+        let other = base.clone().with_path("/other");
+        assert!(matches!(other.get_password(), Err(Error::NoEntry)));
+        assert_eq!(
+            base.get_password().expect("Can't match any path"),
+            "test path"
+        );
+        login
+            .delete_credential()
+            .expect("Can't delete internet credential");
+    }
+
+    #[test]
+    fn test_invalid_server_credential() {
+        let builder = MacCredentialBuilder::default();
+        assert!(
+            matches!(
+                builder.server_credential("https://", "user"),
+                Err(Error::Invalid(_, _))
+            ),
+            "Created server credential with empty host"
+        );
+        assert!(
+            matches!(
+                builder.server_credential("example.com", ""),
+                Err(Error::Invalid(_, _))
+            ),
+            "Created server credential with empty account"
+        );
+    }
+
+    #[test]
+    fn test_round_trip_server_credential() {
+        let name = generate_random_string();
+        let host = format!("https://{name}.Example.com/");
+        let entry = Entry::new_with_credential(
+            MacCredentialBuilder::default()
+                .server_credential(&host, &name)
+                .expect("Can't create server credential"),
+        );
+        crate::tests::test_round_trip("server credential", &entry, "test server password");
+        let credential: &MacInternetCredential = entry
+            .get_credential()
+            .downcast_ref()
+            .expect("Not a mac internet credential");
+        assert_eq!(
+            credential.server,
+            format!("{}.example.com", name.to_ascii_lowercase())
+        );
+    }
 }
@@ -0,0 +1,587 @@
+/*!
+
+# In-memory credential store
+
+This is a platform-independent credential store that keeps credentials
+in process memory, so they vanish when the process terminates.
+Unlike the [mock](crate::mock) store, credentials are shared by all the
+entries built by the same builder, so an entry for a given target, service,
+and user will see the secret set by any other entry for them.
+Each credential can also carry arbitrary attributes (its
+[label](crate::Entry::get_label) is kept in the `label` attribute), and the store
+supports [search](crate::credential::CredentialBuilderApi::search).
+
+This store is always available, and it does not depend on `std`,
+so it is the store to use when this crate is built without the `std`
+feature (for example, in firmware-adjacent tools). Since there is no default
+credential builder in such builds, entries are made from the builder directly:
+```rust
+use keyring::credential::CredentialBuilderApi;
+use keyring::{Entry, memory::MemoryCredentialBuilder};
+
+let builder = MemoryCredentialBuilder::default();
+let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+entry.set_password("test").unwrap();
+let other = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+assert_eq!(other.get_password().unwrap(), "test");
+```
+
+Builders can be cloned, and clones share their credentials.
+ */
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use super::credential::{
+    AtRestStatus, AttributeMap, Credential, CredentialApi, CredentialBuilder, CredentialBuilderApi,
+    CredentialPersistence, CredentialSpec, CredentialStoreInfo, SearchSpec,
+};
+use super::error::{Error, Result, decode_password};
+use zeroize::Zeroizing;
+
+type Key = (Option<String>, String, String);
+
+/// The (in-memory) persisted data for a credential.
+#[derive(Debug, Default)]
+struct StoredData {
+    secret: Zeroizing<Vec<u8>>,
+    attributes: AttributeMap<String, String>,
+}
+
+type Store = Arc<SpinLock<BTreeMap<Key, StoredData>>>;
+
+/// A credential in the in-memory store.
+#[derive(Debug, Clone)]
+pub struct MemoryCredential {
+    key: Key,
+    store: Store,
+}
+
+impl CredentialApi for MemoryCredential {
+    /// Set the secret on this credential, creating it if necessary.
+    fn set_secret(&self, secret: &[u8]) -> Result<()> {
+        let mut store = self.store.lock();
+        let data = store.entry(self.key.clone()).or_default();
+        data.secret = Zeroizing::new(secret.to_vec());
+        Ok(())
+    }
+
+    /// Get the secret from this credential, if it exists.
+    fn get_secret(&self) -> Result<Vec<u8>> {
+        let store = self.store.lock();
+        match store.get(&self.key) {
+            Some(data) => Ok(data.secret.to_vec()),
+            None => Err(Error::NoEntry),
+        }
+    }
+
+    /// Whether this credential is in the store.
+    fn exists(&self) -> Result<bool> {
+        Ok(self.store.lock().contains_key(&self.key))
+    }
+
+    /// Get the password from this credential, or set it if the credential
+    /// doesn't exist (atomically).
+    fn get_password_or_set(&self, password: &str) -> Result<String> {
+        decode_password(self.get_secret_or_set(password.as_bytes())?)
+    }
+
+    /// Get the secret from this credential, or set it if the credential
+    /// doesn't exist (atomically).
+    fn get_secret_or_set(&self, secret: &[u8]) -> Result<Vec<u8>> {
+        let mut store = self.store.lock();
+        let data = store.entry(self.key.clone()).or_insert_with(|| StoredData {
+            secret: Zeroizing::new(secret.to_vec()),
+            ..Default::default()
+        });
+        Ok(data.secret.to_vec())
+    }
+
+    /// Set the password on this credential, if it exists and has the
+    /// expected password (atomically).
+    fn compare_and_set_password(&self, expected: &str, password: &str) -> Result<()> {
+        self.compare_and_set_secret(expected.as_bytes(), password.as_bytes())
+    }
+
+    /// Set the secret on this credential, if it exists and has the
+    /// expected secret (atomically).
+    fn compare_and_set_secret(&self, expected: &[u8], secret: &[u8]) -> Result<()> {
+        let mut store = self.store.lock();
+        match store.get_mut(&self.key) {
+            Some(data) if data.secret.as_slice() == expected => {
+                data.secret = Zeroizing::new(secret.to_vec());
+                Ok(())
+            }
+            Some(_) => Err(Error::Conflict),
+            None => Err(Error::NoEntry),
+        }
+    }
+
+    /// Get the attributes on this credential, if it exists.
+    fn get_attributes(&self) -> Result<AttributeMap<String, String>> {
+        let store = self.store.lock();
+        match store.get(&self.key) {
+            Some(data) => Ok(data.attributes.clone()),
+            None => Err(Error::NoEntry),
+        }
+    }
+
+    /// Update the attributes on this credential, if it exists.
+    ///
+    /// Any attribute name can be given a value.
+    fn update_attributes(&self, attributes: &AttributeMap<&str, &str>) -> Result<()> {
+        let mut store = self.store.lock();
+        match store.get_mut(&self.key) {
+            Some(data) => {
+                for (key, value) in attributes {
+                    data.attributes.insert(key.to_string(), value.to_string());
+                }
+                Ok(())
+            }
+            None => Err(Error::NoEntry),
+        }
+    }
+
+    /// The label is kept in the `label` attribute (and is empty if that isn't set).
+    fn get_label(&self) -> Result<String> {
+        let attributes = self.get_attributes()?;
+        Ok(attributes.get("label").cloned().unwrap_or_default())
+    }
+
+    /// The label is kept in the `label` attribute.
+    fn set_label(&self, label: &str) -> Result<()> {
+        self.update_attributes(&AttributeMap::from([("label", label)]))
+    }
+
+    /// Delete this credential, if it exists.
+    fn delete_credential(&self) -> Result<()> {
+        let mut store = self.store.lock();
+        match store.remove(&self.key) {
+            Some(_) => Ok(()),
+            None => Err(Error::NoEntry),
+        }
+    }
+
+    /// The target, service, and user this credential was built with.
+    fn spec(&self) -> Option<CredentialSpec> {
+        let (target, service, user) = self.key.clone();
+        Some(CredentialSpec {
+            target,
+            service,
+            user,
+        })
+    }
+
+    /// This store keeps secrets unencrypted in process memory.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        AtRestStatus::Unencrypted
+    }
+
+    /// Return this credential with an `Any` type so it can be downcast.
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+
+    /// Expose the concrete debug formatter for use via the [Credential] trait
+    fn debug_fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("MemoryCredential")
+            .field("key", &self.key)
+            .finish()
+    }
+}
+
+/// The builder for in-memory credentials.
+///
+/// Each builder (and its clones) has its own store of credentials.
+#[derive(Debug, Default, Clone)]
+pub struct MemoryCredentialBuilder {
+    store: Store,
+}
+
+impl CredentialBuilderApi for MemoryCredentialBuilder {
+    /// Build a credential for the given target, service, and user.
+    ///
+    /// The credential isn't stored until its secret is set.
+    fn build(&self, target: Option<&str>, service: &str, user: &str) -> Result<Box<Credential>> {
+        Ok(Box::new(MemoryCredential {
+            key: (
+                target.map(|t| t.to_string()),
+                service.to_string(),
+                user.to_string(),
+            ),
+            store: self.store.clone(),
+        }))
+    }
+
+    /// This store keeps secrets unencrypted in process memory.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        AtRestStatus::Unencrypted
+    }
+
+    /// Return this builder with an `Any` type so it can be downcast.
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+
+    /// This keystore keeps credentials in process memory.
+    fn persistence(&self) -> CredentialPersistence {
+        CredentialPersistence::ProcessOnly
+    }
+
+    /// Return a credential for each stored credential that matches the spec.
+    fn search(&self, spec: &SearchSpec) -> Result<Vec<Box<Credential>>> {
+        let store = self.store.lock();
+        let mut keys: Vec<&Key> = store
+            .keys()
+            .filter(|(target, service, user)| spec.matches(target.as_deref(), service, user))
+            .collect();
+        if !spec.unsorted {
+            keys.sort_by_key(|(target, service, user)| (service, user, target));
+        }
+        let results = keys
+            .into_iter()
+            .map(|key| {
+                Box::new(MemoryCredential {
+                    key: key.clone(),
+                    store: self.store.clone(),
+                }) as Box<Credential>
+            })
+            .collect();
+        Ok(results)
+    }
+
+    /// The store can be searched, and credentials have attributes.
+    fn info(&self) -> CredentialStoreInfo {
+        CredentialStoreInfo::new("memory", self.persistence(), self.at_rest_encryption())
+            .with_attributes(true)
+            .with_search(true)
+    }
+}
+
+/// Return an in-memory credential builder with an empty store.
+pub fn default_credential_builder() -> Box<CredentialBuilder> {
+    Box::new(MemoryCredentialBuilder::default())
+}
+
+/// A minimal spin lock, so that this store doesn't need `std`.
+///
+/// Every critical section in this module is a short map operation,
+/// so spinning is cheaper than parking would be.
+#[derive(Default)]
+struct SpinLock<T> {
+    locked: AtomicBool,
+    value: UnsafeCell<T>,
+}
+
+// SAFETY: the lock ensures that only one thread at a time can access the value.
+unsafe impl<T: Send> Sync for SpinLock<T> {}
+
+impl<T> SpinLock<T> {
+    fn lock(&self) -> SpinLockGuard<'_, T> {
+        while self
+            .locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            core::hint::spin_loop();
+        }
+        SpinLockGuard { lock: self }
+    }
+}
+
+impl<T> core::fmt::Debug for SpinLock<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("SpinLock")
+    }
+}
+
+struct SpinLockGuard<'a, T> {
+    lock: &'a SpinLock<T>,
+}
+
+impl<T> Deref for SpinLockGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: the guard holds the lock.
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<T> DerefMut for SpinLockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: the guard holds the lock.
+        unsafe { &mut *self.lock.value.get() }
+    }
+}
+
+impl<T> Drop for SpinLockGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.locked.store(false, Ordering::Release);
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::{MemoryCredential, MemoryCredentialBuilder, default_credential_builder};
+    use crate::credential::{
+        AtRestStatus, CredentialBuilderApi, CredentialMetadata, CredentialPersistence,
+        CredentialSpec, Match, SearchSpec,
+    };
+    use crate::{Entry, Error, tests::generate_random_string};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_persistence() {
+        assert!(matches!(
+            default_credential_builder().persistence(),
+            CredentialPersistence::ProcessOnly
+        ))
+    }
+
+    fn entry_new(service: &str, user: &str) -> Entry {
+        static BUILDER: std::sync::LazyLock<MemoryCredentialBuilder> =
+            std::sync::LazyLock::new(MemoryCredentialBuilder::default);
+        Entry::new_with_credential(BUILDER.build(None, service, user).unwrap())
+    }
+
+    #[test]
+    fn test_missing_entry() {
+        crate::tests::test_missing_entry(entry_new);
+    }
+
+    #[test]
+    fn test_empty_password() {
+        crate::tests::test_empty_password(entry_new);
+    }
+
+    #[test]
+    fn test_round_trip_ascii_password() {
+        crate::tests::test_round_trip_ascii_password(entry_new);
+    }
+
+    #[test]
+    fn test_round_trip_non_ascii_password() {
+        crate::tests::test_round_trip_non_ascii_password(entry_new);
+    }
+
+    #[test]
+    fn test_round_trip_random_secret() {
+        crate::tests::test_round_trip_random_secret(entry_new);
+    }
+
+    #[test]
+    fn test_update() {
+        crate::tests::test_update(entry_new);
+    }
+
+    #[test]
+    fn test_shared_store() {
+        let name = generate_random_string();
+        let entry1 = entry_new(&name, &name);
+        let entry2 = entry_new(&name, &name);
+        entry1
+            .set_password("shared password")
+            .expect("Can't set password");
+        assert_eq!(entry2.get_password().unwrap(), "shared password");
+        let map = HashMap::from([("comment", "shared")]);
+        entry2
+            .update_attributes(&map)
+            .expect("Can't update attributes");
+        assert_eq!(entry1.get_attributes().unwrap()["comment"], "shared");
+        entry2.delete_credential().expect("Can't delete credential");
+        assert!(matches!(entry1.get_password(), Err(Error::NoEntry)));
+    }
+
+    #[test]
+    fn test_get_or_set_race() {
+        let name = generate_random_string();
+        let results: Vec<String> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..8)
+                .map(|i| {
+                    let name = &name;
+                    scope.spawn(move || {
+                        entry_new(name, name)
+                            .get_password_or_set_with(|| format!("racer {i}"))
+                            .unwrap()
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert!(results.iter().all(|password| *password == results[0]));
+        assert_eq!(entry_new(&name, &name).get_password().unwrap(), results[0]);
+    }
+
+    #[test]
+    fn test_update_password() {
+        let name = generate_random_string();
+        let entry = entry_new(&name, &name);
+        assert!(matches!(
+            entry.update_password("old", "new"),
+            Err(Error::NoEntry)
+        ));
+        entry.set_password("old").unwrap();
+        entry.update_password("old", "new").unwrap();
+        assert!(matches!(
+            entry.update_password("old", "newer"),
+            Err(Error::Conflict)
+        ));
+        assert_eq!(entry.get_password().unwrap(), "new");
+        entry.delete_credential().unwrap();
+    }
+
+    #[test]
+    fn test_exists() {
+        let name = generate_random_string();
+        let entry = entry_new(&name, &name);
+        assert!(!entry.exists().unwrap());
+        entry.set_password("test exists").unwrap();
+        assert!(entry.exists().unwrap());
+        entry.delete_credential().unwrap();
+        assert!(!entry.exists().unwrap());
+    }
+
+    #[test]
+    fn test_batch() {
+        let builder = MemoryCredentialBuilder::default();
+        let spec = |user: &str| CredentialSpec {
+            target: None,
+            service: "service".to_string(),
+            user: user.to_string(),
+        };
+        let specs = [spec("alice"), spec("bob"), spec("carol")];
+        let items: Vec<_> = specs[..2]
+            .iter()
+            .map(|spec| (spec.clone(), spec.user.as_bytes()))
+            .collect();
+        assert!(builder.set_many(&items).iter().all(|r| r.is_ok()));
+        let secrets = builder.get_many(&specs);
+        assert_eq!(secrets[0].as_ref().unwrap(), b"alice");
+        assert_eq!(secrets[1].as_ref().unwrap(), b"bob");
+        assert!(matches!(secrets[2], Err(Error::NoEntry)));
+        let deleted = builder.delete_many(&specs);
+        assert!(deleted[0].is_ok() && deleted[1].is_ok());
+        assert!(matches!(deleted[2], Err(Error::NoEntry)));
+        assert!(builder.get_many(&specs).iter().all(|r| r.is_err()));
+    }
+
+    #[test]
+    fn test_info() {
+        let info = MemoryCredentialBuilder::default().info();
+        assert_eq!(info.name, "memory");
+        assert_eq!(info.persistence, CredentialPersistence::ProcessOnly);
+        assert_eq!(info.at_rest_encryption, AtRestStatus::Unencrypted);
+        assert!(info.supports_attributes && info.supports_binary_secrets && info.supports_search);
+        assert_eq!(info.max_secret_len, None);
+    }
+
+    #[test]
+    fn test_no_metadata() {
+        let name = generate_random_string();
+        let entry = entry_new(&name, &name);
+        assert!(matches!(entry.get_metadata(), Err(Error::NoEntry)));
+        entry.set_password("test metadata").unwrap();
+        assert_eq!(entry.get_metadata().unwrap(), CredentialMetadata::default());
+        entry.delete_credential().unwrap();
+    }
+
+    #[test]
+    fn test_search() {
+        let builder = MemoryCredentialBuilder::default();
+        for user in ["tenant1:alice", "tenant1:bob", "tenant2:carol"] {
+            builder
+                .build(None, "service", user)
+                .unwrap()
+                .set_password("test search")
+                .unwrap();
+        }
+        let spec = SearchSpec {
+            user: Match::Prefix("tenant1:".to_string()),
+            ..Default::default()
+        };
+        let found = builder.search(&spec).expect("Can't search");
+        assert_eq!(found.len(), 2);
+        for cred in found {
+            assert_eq!(cred.get_password().unwrap(), "test search");
+        }
+        let found = builder.search(&spec.unsorted()).expect("Can't search");
+        assert_eq!(found.len(), 2);
+    }
+
+    #[test]
+    fn test_spec() {
+        let builder = MemoryCredentialBuilder::default();
+        let entry = Entry::new_with_credential(builder.build(Some("t"), "s", "u").unwrap());
+        let spec = entry.spec().expect("Memory entry has no spec");
+        assert_eq!(
+            (
+                spec.target.as_deref(),
+                spec.service.as_str(),
+                spec.user.as_str()
+            ),
+            (Some("t"), "s", "u")
+        );
+        entry.set_password("test spec").unwrap();
+        let found = builder.search(&SearchSpec::default()).unwrap();
+        assert_eq!(found[0].spec().as_ref(), Some(spec));
+    }
+
+    #[test]
+    fn test_at_rest_encryption() {
+        let builder = MemoryCredentialBuilder::default();
+        assert_eq!(builder.at_rest_encryption(), AtRestStatus::Unencrypted);
+        let entry = Entry::new_with_credential(builder.build(None, "s", "u").unwrap());
+        assert_eq!(entry.at_rest_encryption(), AtRestStatus::Unencrypted);
+    }
+
+    #[test]
+    fn test_label() {
+        let builder = MemoryCredentialBuilder::default();
+        let entry = Entry::new_with_credential(builder.build(None, "s", "u").unwrap());
+        assert!(matches!(entry.get_label(), Err(Error::NoEntry)));
+        assert!(matches!(entry.set_label("label"), Err(Error::NoEntry)));
+        entry.set_password("labeled").unwrap();
+        assert_eq!(entry.get_label().unwrap(), "");
+        entry.set_label("My account").unwrap();
+        assert_eq!(entry.get_label().unwrap(), "My account");
+        assert_eq!(entry.get_attributes().unwrap()["label"], "My account");
+    }
+
+    #[test]
+    fn test_search_order() {
+        let builder = MemoryCredentialBuilder::default();
+        let keys = [
+            (Some("t1"), "s2", "u1"),
+            (None, "s1", "u2"),
+            (Some("t2"), "s1", "u1"),
+            (None, "s1", "u1"),
+        ];
+        for (target, service, user) in keys {
+            builder
+                .build(target, service, user)
+                .unwrap()
+                .set_password("test order")
+                .unwrap();
+        }
+        let found: Vec<_> = builder
+            .search(&SearchSpec::default())
+            .expect("Can't search")
+            .iter()
+            .map(|cred| {
+                let cred: &MemoryCredential = cred.as_any().downcast_ref().unwrap();
+                cred.key.clone()
+            })
+            .collect();
+        let expected: Vec<_> = [keys[3], keys[2], keys[1], keys[0]]
+            .iter()
+            .map(|(t, s, u)| (t.map(|t| t.to_string()), s.to_string(), u.to_string()))
+            .collect();
+        assert_eq!(found, expected);
+    }
+}
@@ -0,0 +1,297 @@
+/*!
+
+# Secret middleware
+
+Some apps want to transform every secret they store, whatever the store:
+for example, to wrap it in an application-level envelope, add a MAC,
+or encrypt it with an app-held key. The [MiddlewareCredentialBuilder]
+in this module does that by wrapping another builder and passing every
+secret written through its credentials to a [SecretMiddleware]'s
+[on_write](SecretMiddleware::on_write) method, and every secret read
+through them to its [on_read](SecretMiddleware::on_read) method.
+
+```
+# use keyring::{Entry, Error, Result, credential::CredentialBuilderApi};
+# use keyring::{memory::MemoryCredentialBuilder, middleware::{MiddlewareCredentialBuilder, SecretMiddleware}};
+/// Tag each secret with a format version, and check the tag on read.
+struct Versioned;
+
+impl SecretMiddleware for Versioned {
+    fn on_write(&self, plaintext: &[u8]) -> Vec<u8> {
+        [b"v1:", plaintext].concat()
+    }
+
+    fn on_read(&self, stored: &[u8]) -> Result<Vec<u8>> {
+        match stored.strip_prefix(b"v1:") {
+            Some(plaintext) => Ok(plaintext.to_vec()),
+            None => Err(Error::Invalid("secret".to_string(), "has no version tag".to_string())),
+        }
+    }
+}
+
+let store = MemoryCredentialBuilder::default();
+let builder = MiddlewareCredentialBuilder::new(Box::new(Versioned), Box::new(store.clone()));
+let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+entry.set_password("tagged").unwrap();
+assert_eq!(entry.get_password().unwrap(), "tagged");
+let raw = store.build(None, "service", "user").unwrap();
+assert_eq!(raw.get_secret().unwrap(), b"v1:tagged");
+```
+
+Passwords are handled as their UTF-8 bytes, so the wrapped store only ever
+sees the middleware's output, even if it would normally store passwords
+in some other encoding. Credentials returned by a search through a
+middleware builder are wrapped with the middleware, too.
+ */
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::time::Duration;
+
+use super::credential::{
+    AtRestStatus, AttributeMap, Credential, CredentialApi, CredentialBuilder, CredentialBuilderApi,
+    CredentialPersistence, CredentialSpec, CredentialStoreInfo, SearchSpec,
+};
+use super::error::{Error, Result};
+
+/// A transformation applied to secrets as they are written to and read from a store.
+pub trait SecretMiddleware: Send + Sync {
+    /// Transform a secret that's about to be written into the form to store.
+    fn on_write(&self, plaintext: &[u8]) -> Vec<u8>;
+
+    /// Transform a stored secret back into the secret that was written.
+    ///
+    /// Errors returned here (for example, when the stored secret fails an
+    /// integrity check) are returned to the caller that read the secret.
+    fn on_read(&self, stored: &[u8]) -> Result<Vec<u8>>;
+}
+
+/// A credential builder that applies a [SecretMiddleware] to the secrets
+/// of the credentials built by another builder.
+pub struct MiddlewareCredentialBuilder {
+    middleware: Arc<dyn SecretMiddleware>,
+    inner: Box<CredentialBuilder>,
+}
+
+impl MiddlewareCredentialBuilder {
+    /// Create a builder that applies `middleware` to the credentials built by `inner`.
+    pub fn new(middleware: Box<dyn SecretMiddleware>, inner: Box<CredentialBuilder>) -> Self {
+        Self {
+            middleware: Arc::from(middleware),
+            inner,
+        }
+    }
+
+    /// The wrapped builder.
+    pub fn inner(&self) -> &CredentialBuilder {
+        self.inner.as_ref()
+    }
+
+    fn wrap(&self, inner: Box<Credential>) -> Box<Credential> {
+        Box::new(MiddlewareCredential {
+            middleware: self.middleware.clone(),
+            inner,
+        })
+    }
+}
+
+impl core::fmt::Debug for MiddlewareCredentialBuilder {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("MiddlewareCredentialBuilder")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CredentialBuilderApi for MiddlewareCredentialBuilder {
+    /// Build a credential in the wrapped store, and apply the middleware to it.
+    fn build(&self, target: Option<&str>, service: &str, user: &str) -> Result<Box<Credential>> {
+        Ok(self.wrap(self.inner.build(target, service, user)?))
+    }
+
+    /// The at-rest encryption of the wrapped store.
+    ///
+    /// (Any encryption done by the middleware isn't known to this crate.)
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        self.inner.at_rest_encryption()
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+
+    /// The persistence of the wrapped store.
+    fn persistence(&self) -> CredentialPersistence {
+        self.inner.persistence()
+    }
+
+    /// Search the wrapped store, and apply the middleware to the found credentials.
+    fn search(&self, spec: &SearchSpec) -> Result<Vec<Box<Credential>>> {
+        let found = self.inner.search(spec)?;
+        Ok(found.into_iter().map(|cred| self.wrap(cred)).collect())
+    }
+
+    /// The info of the wrapped store.
+    fn info(&self) -> CredentialStoreInfo {
+        self.inner.info()
+    }
+}
+
+/// A credential whose secrets pass through a [SecretMiddleware].
+pub struct MiddlewareCredential {
+    middleware: Arc<dyn SecretMiddleware>,
+    inner: Box<Credential>,
+}
+
+impl MiddlewareCredential {
+    /// The wrapped credential.
+    pub fn inner(&self) -> &Credential {
+        self.inner.as_ref()
+    }
+}
+
+impl CredentialApi for MiddlewareCredential {
+    fn set_password(&self, password: &str) -> Result<()> {
+        self.set_secret(password.as_bytes())
+    }
+
+    fn set_secret(&self, secret: &[u8]) -> Result<()> {
+        let stored = zeroize::Zeroizing::new(self.middleware.on_write(secret));
+        self.inner.set_secret(&stored)
+    }
+
+    fn get_secret(&self) -> Result<Vec<u8>> {
+        let stored = zeroize::Zeroizing::new(self.inner.get_secret()?);
+        self.middleware.on_read(&stored)
+    }
+
+    fn compare_and_set_password(&self, expected: &str, password: &str) -> Result<()> {
+        self.compare_and_set_secret(expected.as_bytes(), password.as_bytes())
+    }
+
+    /// Store the secret, but only if the stored one reads as `expected`.
+    ///
+    /// The stored secret that was read is what's compared by the wrapped
+    /// credential when it stores the new one, so this is as atomic as the
+    /// wrapped store's [compare_and_set_secret](CredentialApi::compare_and_set_secret).
+    fn compare_and_set_secret(&self, expected: &[u8], secret: &[u8]) -> Result<()> {
+        let stored = zeroize::Zeroizing::new(self.inner.get_secret()?);
+        if *zeroize::Zeroizing::new(self.middleware.on_read(&stored)?) != expected {
+            return Err(Error::Conflict);
+        }
+        let new = zeroize::Zeroizing::new(self.middleware.on_write(secret));
+        self.inner.compare_and_set_secret(&stored, &new)
+    }
+
+    fn exists(&self) -> Result<bool> {
+        self.inner.exists()
+    }
+
+    fn get_attributes(&self) -> Result<AttributeMap<String, String>> {
+        self.inner.get_attributes()
+    }
+
+    fn update_attributes(&self, attributes: &AttributeMap<&str, &str>) -> Result<()> {
+        self.inner.update_attributes(attributes)
+    }
+
+    fn get_label(&self) -> Result<String> {
+        self.inner.get_label()
+    }
+
+    fn set_label(&self, label: &str) -> Result<()> {
+        self.inner.set_label(label)
+    }
+
+    fn set_expiration(&self, ttl: Duration) -> Result<()> {
+        self.inner.set_expiration(ttl)
+    }
+
+    fn unlock(&self) -> Result<()> {
+        self.inner.unlock()
+    }
+
+    /// The middleware can change the length of secrets, so there's no known limit.
+    fn max_secret_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn spec(&self) -> Option<CredentialSpec> {
+        self.inner.spec()
+    }
+
+    fn delete_credential(&self) -> Result<()> {
+        self.inner.delete_credential()
+    }
+
+    /// The at-rest encryption of the wrapped store.
+    ///
+    /// (Any encryption done by the middleware isn't known to this crate.)
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        self.inner.at_rest_encryption()
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+
+    fn debug_fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("MiddlewareCredential")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::{MiddlewareCredentialBuilder, SecretMiddleware};
+    use crate::credential::{CredentialBuilderApi, SearchSpec};
+    use crate::memory::MemoryCredentialBuilder;
+    use crate::tests::CasOnlyCredentialBuilder;
+    use crate::{Entry, Error, Result};
+
+    /// Reverse secrets on write, and reject empty ones on read.
+    struct Reverse;
+
+    impl SecretMiddleware for Reverse {
+        fn on_write(&self, plaintext: &[u8]) -> Vec<u8> {
+            plaintext.iter().rev().copied().collect()
+        }
+
+        fn on_read(&self, stored: &[u8]) -> Result<Vec<u8>> {
+            if stored.is_empty() {
+                return Err(Error::Invalid("secret".to_string(), "is empty".to_string()));
+            }
+            Ok(stored.iter().rev().copied().collect())
+        }
+    }
+
+    #[test]
+    fn test_middleware() {
+        let store = MemoryCredentialBuilder::default();
+        let builder = MiddlewareCredentialBuilder::new(Box::new(Reverse), Box::new(store.clone()));
+        let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+        entry.set_password("forward").unwrap();
+        assert_eq!(entry.get_password().unwrap(), "forward");
+        let raw = Entry::new_with_credential(store.build(None, "service", "user").unwrap());
+        assert_eq!(raw.get_password().unwrap(), "drawrof");
+        raw.set_secret(b"").unwrap();
+        assert!(matches!(entry.get_secret(), Err(Error::Invalid(_, _))));
+        raw.set_secret(b"dnuof").unwrap();
+        let found = builder.search(&SearchSpec::default()).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].get_secret().unwrap(), b"found");
+        entry.delete_credential().unwrap();
+        assert!(matches!(raw.get_secret(), Err(Error::NoEntry)));
+    }
+
+    #[test]
+    fn test_compare_and_set() {
+        let builder = MiddlewareCredentialBuilder::new(
+            Box::new(Reverse),
+            Box::new(CasOnlyCredentialBuilder::default()),
+        );
+        crate::tests::test_forwards_compare_and_set(&builder);
+    }
+}
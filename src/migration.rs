@@ -0,0 +1,416 @@
+/*!
+
+# Moving credentials between machines and stores
+
+This module exports credentials from one store into an encrypted archive,
+and imports them from the archive into another store, which may be on
+another machine or platform (say, from the Secret Service on Linux to the
+Windows Credential Manager). It's available when the `migration` feature
+is enabled.
+
+The credentials to export are chosen by a [search](SearchSpec) of their
+store ([export_search]) or by an explicit list of their specs ([export_specs]).
+Each one is exported with its target, service, user, secret, and attributes,
+and imported with the same target, service, and user, so entries made
+for it on the other side find it.
+```
+# use keyring::credential::{CredentialBuilderApi, Match, SearchSpec};
+# use keyring::memory::MemoryCredentialBuilder;
+# use keyring::migration::{export_search, import_archive};
+let old = MemoryCredentialBuilder::default();
+old.build(None, "my-app", "alice").unwrap().set_password("alice's password").unwrap();
+let spec = SearchSpec {
+    service: Match::Exact("my-app".to_string()),
+    ..Default::default()
+};
+let (archive, exported) = export_search(&old, &spec, "a long passphrase").unwrap();
+assert_eq!(exported.migrated.len(), 1);
+
+let new = MemoryCredentialBuilder::default();
+let imported = import_archive(&archive, "a long passphrase", &new).unwrap();
+assert_eq!(imported.migrated.len(), 1);
+let alice = new.build(None, "my-app", "alice").unwrap();
+assert_eq!(alice.get_password().unwrap(), "alice's password");
+```
+
+Credentials are exported and imported one at a time, so a failure to
+read or write one of them doesn't stop the others; failures are listed
+in the returned [MigrationReport]. Imported credentials overwrite any
+existing ones with the same identity. Attributes that the other store
+doesn't support are ignored (as they are by
+[update_attributes](crate::Entry::update_attributes)).
+
+## The archive
+
+The archive is sealed (see [seal](crate::crypto::seal)) with a key derived
+from the passphrase by [derive_key_from_passphrase](crate::crypto::derive_key_from_passphrase),
+with a salt that's unique to the archive, so it's only as safe as the
+passphrase is hard to guess. The format is: the bytes `keyring-archive`,
+a format version byte, the number of key derivation rounds (a big-endian
+`u32`), the 16-byte salt, and then the sealed credentials.
+An archive that's been tampered with, or that's opened with the
+wrong passphrase, is rejected with an [Invalid](Error::Invalid) error.
+ */
+use zeroize::Zeroizing;
+
+use super::credential::{
+    AttributeMap, Credential, CredentialBuilderApi, CredentialSpec, SearchSpec,
+};
+use super::crypto::{derive_key_from_passphrase, fill_random, open, seal};
+use super::error::{Error, Result};
+
+/// The bytes that start every archive.
+const MAGIC: &[u8] = b"keyring-archive";
+
+/// The archive format version that follows the magic bytes.
+const VERSION: u8 = 1;
+
+/// The length of the key derivation salt.
+const SALT_LEN: usize = 16;
+
+/// The number of key derivation rounds used for new archives.
+pub const PASSPHRASE_ROUNDS: u32 = 600_000;
+
+/// The most key derivation rounds an archive may ask for
+/// (so a doctored archive can't tie up its importer).
+const MAX_ROUNDS: u32 = 10_000_000;
+
+/// The outcome of an export or import.
+#[derive(Debug, Default)]
+pub struct MigrationReport {
+    /// The credentials that were exported or imported, in order
+    pub migrated: Vec<CredentialSpec>,
+    /// The credentials that couldn't be exported or imported, with the reason
+    pub failed: Vec<(CredentialSpec, Error)>,
+}
+
+/// Export the credentials found by a search of the given builder's store
+/// to an archive sealed with the given passphrase.
+///
+/// Returns the archive and a report of which credentials are in it.
+/// Returns an error if the search fails, or if it finds credentials
+/// that don't know their identity (which couldn't be imported).
+pub fn export_search(
+    builder: &dyn CredentialBuilderApi,
+    spec: &SearchSpec,
+    passphrase: &str,
+) -> Result<(Vec<u8>, MigrationReport)> {
+    let credentials = builder
+        .search(spec)?
+        .into_iter()
+        .map(|credential| match credential.spec() {
+            Some(spec) => Ok((spec, credential)),
+            None => Err(Error::NotSupportedByStore(
+                "search results don't have a known service and user".to_string(),
+            )),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(export(credentials, passphrase, PASSPHRASE_ROUNDS))
+}
+
+/// Export the credentials with the given specs from the given builder's
+/// store to an archive sealed with the given passphrase.
+///
+/// Returns the archive and a report of which credentials are in it
+/// (credentials that don't exist are listed as failures).
+pub fn export_specs(
+    builder: &dyn CredentialBuilderApi,
+    specs: &[CredentialSpec],
+    passphrase: &str,
+) -> Result<(Vec<u8>, MigrationReport)> {
+    let credentials = specs
+        .iter()
+        .map(|spec| {
+            let credential = builder.build(spec.target.as_deref(), &spec.service, &spec.user)?;
+            Ok((spec.clone(), credential))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(export(credentials, passphrase, PASSPHRASE_ROUNDS))
+}
+
+/// Import the credentials in an archive into the given builder's store.
+///
+/// Returns an error if the archive is malformed, has been tampered with,
+/// or wasn't sealed with the given passphrase; credentials that
+/// can't be stored are listed in the report.
+pub fn import_archive(
+    archive: &[u8],
+    passphrase: &str,
+    builder: &dyn CredentialBuilderApi,
+) -> Result<MigrationReport> {
+    let payload = unseal(archive, passphrase)?;
+    let mut reader = Reader {
+        data: &payload,
+        pos: 0,
+    };
+    let mut report = MigrationReport::default();
+    for _ in 0..reader.u32()? {
+        let item = reader.item()?;
+        match import_one(&item, builder) {
+            Ok(()) => report.migrated.push(item.spec),
+            Err(err) => report.failed.push((item.spec, err)),
+        }
+    }
+    if reader.pos != payload.len() {
+        return Err(invalid("has trailing data"));
+    }
+    Ok(report)
+}
+
+/// One credential in an archive.
+struct Item {
+    spec: CredentialSpec,
+    secret: Zeroizing<Vec<u8>>,
+    attributes: AttributeMap<String, String>,
+}
+
+fn export(
+    credentials: Vec<(CredentialSpec, Box<Credential>)>,
+    passphrase: &str,
+    rounds: u32,
+) -> (Vec<u8>, MigrationReport) {
+    let mut report = MigrationReport::default();
+    let mut payload = Zeroizing::new(Vec::new());
+    let mut count = 0u32;
+    for (spec, credential) in credentials {
+        match export_one(spec.clone(), credential.as_ref()) {
+            Ok(item) => {
+                write_item(&mut payload, &item);
+                count += 1;
+                report.migrated.push(spec);
+            }
+            Err(err) => report.failed.push((spec, err)),
+        }
+    }
+    let mut counted = Zeroizing::new(Vec::with_capacity(4 + payload.len()));
+    counted.extend_from_slice(&count.to_be_bytes());
+    counted.extend_from_slice(&payload);
+    (seal_archive(&counted, passphrase, rounds), report)
+}
+
+fn export_one(spec: CredentialSpec, credential: &Credential) -> Result<Item> {
+    let secret = Zeroizing::new(credential.get_secret()?);
+    let attributes = credential.get_attributes()?;
+    Ok(Item {
+        spec,
+        secret,
+        attributes,
+    })
+}
+
+fn import_one(item: &Item, builder: &dyn CredentialBuilderApi) -> Result<()> {
+    let spec = &item.spec;
+    let credential = builder.build(spec.target.as_deref(), &spec.service, &spec.user)?;
+    credential.set_secret(&item.secret)?;
+    if !item.attributes.is_empty() {
+        let attributes: AttributeMap<&str, &str> = item
+            .attributes
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        credential.update_attributes(&attributes)?;
+    }
+    Ok(())
+}
+
+fn seal_archive(payload: &[u8], passphrase: &str, rounds: u32) -> Vec<u8> {
+    let salt = unique_salt();
+    let key = derive_key_from_passphrase(passphrase.as_bytes(), &salt, rounds);
+    let mut archive = Vec::new();
+    archive.extend_from_slice(MAGIC);
+    archive.push(VERSION);
+    archive.extend_from_slice(&rounds.to_be_bytes());
+    archive.extend_from_slice(&salt);
+    archive.extend_from_slice(&seal(payload, &key));
+    archive
+}
+
+fn unseal(archive: &[u8], passphrase: &str) -> Result<Zeroizing<Vec<u8>>> {
+    let header_len = MAGIC.len() + 1 + 4 + SALT_LEN;
+    if archive.len() < header_len || !archive.starts_with(MAGIC) {
+        return Err(invalid("is not a keyring archive"));
+    }
+    if archive[MAGIC.len()] != VERSION {
+        return Err(invalid("has an unknown format version"));
+    }
+    let rounds_at = MAGIC.len() + 1;
+    let rounds = u32::from_be_bytes(archive[rounds_at..rounds_at + 4].try_into().unwrap());
+    if rounds == 0 || rounds > MAX_ROUNDS {
+        return Err(invalid("has a bad number of key derivation rounds"));
+    }
+    let salt = &archive[rounds_at + 4..header_len];
+    let key = derive_key_from_passphrase(passphrase.as_bytes(), salt, rounds);
+    let payload = open(&archive[header_len..], &key).map_err(|_| {
+        invalid("has been tampered with, or was sealed with a different passphrase")
+    })?;
+    Ok(Zeroizing::new(payload))
+}
+
+/// A random salt, so it's (all but certainly) different for every archive.
+fn unique_salt() -> [u8; SALT_LEN] {
+    let mut salt = [0u8; SALT_LEN];
+    fill_random(&mut salt);
+    salt
+}
+
+fn write_item(out: &mut Vec<u8>, item: &Item) {
+    match &item.spec.target {
+        Some(target) => {
+            out.push(1);
+            write_bytes(out, target.as_bytes());
+        }
+        None => out.push(0),
+    }
+    write_bytes(out, item.spec.service.as_bytes());
+    write_bytes(out, item.spec.user.as_bytes());
+    write_bytes(out, &item.secret);
+    out.extend_from_slice(&(item.attributes.len() as u32).to_be_bytes());
+    for (key, value) in &item.attributes {
+        write_bytes(out, key.as_bytes());
+        write_bytes(out, value.as_bytes());
+    }
+}
+
+fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    out.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+    out.extend_from_slice(bytes);
+}
+
+/// Reads the items in an unsealed archive.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn bytes(&mut self, len: usize) -> Result<&[u8]> {
+        if self.data.len() - self.pos < len {
+            return Err(invalid("is truncated"));
+        }
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn field(&mut self) -> Result<&[u8]> {
+        let len = self.u32()? as usize;
+        self.bytes(len)
+    }
+
+    fn string(&mut self) -> Result<String> {
+        String::from_utf8(self.field()?.to_vec()).map_err(|_| invalid("has a non-UTF-8 name"))
+    }
+
+    fn item(&mut self) -> Result<Item> {
+        let target = match self.bytes(1)?[0] {
+            0 => None,
+            1 => Some(self.string()?),
+            _ => return Err(invalid("has a malformed target")),
+        };
+        let service = self.string()?;
+        let user = self.string()?;
+        let secret = Zeroizing::new(self.field()?.to_vec());
+        let mut attributes = AttributeMap::new();
+        for _ in 0..self.u32()? {
+            let key = self.string()?;
+            let value = self.string()?;
+            attributes.insert(key, value);
+        }
+        Ok(Item {
+            spec: CredentialSpec {
+                target,
+                service,
+                user,
+            },
+            secret,
+            attributes,
+        })
+    }
+}
+
+fn invalid(reason: &str) -> Error {
+    Error::Invalid("archive".to_string(), reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{export, import_archive, invalid};
+    use crate::Error;
+    use crate::credential::{AttributeMap, CredentialBuilderApi, CredentialSpec};
+    use crate::memory::MemoryCredentialBuilder;
+
+    fn spec(target: Option<&str>, service: &str, user: &str) -> CredentialSpec {
+        CredentialSpec {
+            target: target.map(str::to_string),
+            service: service.to_string(),
+            user: user.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let old = MemoryCredentialBuilder::default();
+        let alice = old.build(None, "service", "alice").unwrap();
+        alice.set_secret(b"alice's \x00 secret").unwrap();
+        alice
+            .update_attributes(&AttributeMap::from([("label", "Alice")]))
+            .unwrap();
+        let bob = old.build(Some("work"), "service", "bob").unwrap();
+        bob.set_password("bob's password").unwrap();
+        let specs = [
+            spec(None, "service", "alice"),
+            spec(Some("work"), "service", "bob"),
+            spec(None, "service", "missing"),
+        ];
+        let credentials = specs
+            .iter()
+            .map(|s| {
+                let credential = old.build(s.target.as_deref(), &s.service, &s.user);
+                (s.clone(), credential.unwrap())
+            })
+            .collect();
+        // few rounds, so the test is quick
+        let (archive, report) = export(credentials, "passphrase", 10);
+        assert_eq!(report.migrated, specs[..2]);
+        assert_eq!(report.failed.len(), 1);
+        assert!(matches!(report.failed[0], (_, Error::NoEntry)));
+
+        let new = MemoryCredentialBuilder::default();
+        let report = import_archive(&archive, "passphrase", &new).unwrap();
+        assert_eq!(report.migrated, specs[..2]);
+        assert!(report.failed.is_empty());
+        let alice = new.build(None, "service", "alice").unwrap();
+        assert_eq!(alice.get_secret().unwrap(), b"alice's \x00 secret");
+        assert_eq!(alice.get_attributes().unwrap()["label"], "Alice");
+        let bob = new.build(Some("work"), "service", "bob").unwrap();
+        assert_eq!(bob.get_password().unwrap(), "bob's password");
+    }
+
+    #[test]
+    fn test_bad_archives() {
+        let (archive, _) = export(Vec::new(), "passphrase", 10);
+        let (again, _) = export(Vec::new(), "passphrase", 10);
+        assert_ne!(archive, again, "Archives should have unique salts");
+        let builder = MemoryCredentialBuilder::default();
+        let report = import_archive(&archive, "passphrase", &builder).unwrap();
+        assert!(report.migrated.is_empty());
+        let expected = invalid("has been tampered with, or was sealed with a different passphrase");
+        for bad in [
+            import_archive(&archive, "wrong passphrase", &builder),
+            import_archive(&archive[..archive.len() - 1], "passphrase", &builder),
+        ] {
+            match bad {
+                Err(err) => assert_eq!(err.to_string(), expected.to_string()),
+                Ok(_) => panic!("Bad archive was imported"),
+            }
+        }
+        assert!(matches!(
+            import_archive(b"not an archive", "passphrase", &builder),
+            Err(Error::Invalid(_, _))
+        ));
+    }
+}
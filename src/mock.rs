@@ -18,6 +18,20 @@ to set, get, and delete passwords.  There is no persistence other than
 in the entry itself, so getting a password before setting it will always result
 in a [NoEntry](Error::NoEntry) error.
 
+If the code you are testing writes a credential through one entry and reads
+it back through another, use a shared mock builder instead. The credentials
+built by a [shared](MockCredentialBuilder::shared) builder (or by its clones)
+keep their secrets in a map shared by the builder, keyed by target, service,
+and user, so they see each other's changes (until the process exits).
+The [shared_credential_builder] function returns a builder whose map is
+shared by the whole process:
+```rust
+# use keyring::{Entry, mock};
+keyring::set_default_credential_builder(mock::shared_credential_builder());
+Entry::new("service", "user").unwrap().set_password("shared").unwrap();
+assert_eq!(Entry::new("service", "user").unwrap().get_password().unwrap(), "shared");
+```
+
 If you want a method call on an entry to fail in a specific way, you can
 downcast the entry to a [MockCredential] and then call [set_error](MockCredential::set_error)
 with the appropriate error.  The next entry method called on the credential
@@ -32,28 +46,100 @@ mock.set_error(Error::Invalid("mock error".to_string(), "takes precedence".to_st
 entry.set_password("test").expect_err("error will override");
 entry.set_password("test").expect("error has been cleared");
 ```
+
+To test retry and fallback logic, you can instead queue up the outcomes
+of a sequence of calls with [set_outcomes](MockCredential::set_outcomes):
+each call takes the next outcome from the queue, failing if it's an error
+and operating as usual if it's `None` (or the queue is empty).
+Each mock credential also records the calls made on it, which you can
+inspect with [calls](MockCredential::calls):
+```rust
+# use keyring::{Entry, Error, mock, mock::MockCredential};
+# keyring::set_default_credential_builder(mock::default_credential_builder());
+let entry = Entry::new("service", "user").unwrap();
+let mock: &MockCredential = entry.get_credential().downcast_ref().unwrap();
+mock.set_outcomes([Some(Error::NoStorageAccess("locked".into())), None]);
+entry.set_password("test").expect_err("first call fails");
+entry.set_password("test").expect("second call succeeds");
+let calls = mock.calls();
+assert_eq!(calls.len(), 2);
+assert_eq!(calls[1].op, "set_password");
+assert_eq!(calls[1].secret.as_deref().unwrap().as_slice(), b"test");
+```
+
+A scripted outcome can be any error, including an [Ambiguous](Error::Ambiguous)
+one made with [ambiguous] (as some platform stores return when they hold
+duplicate credentials). To test how an app copes with a slow or fragile
+store, a mock builder can also be configured to delay every call on its
+credentials (see [with_delay](MockCredentialBuilder::with_delay)), and to fail
+any call that starts while another call on one of its credentials is still
+running (see [with_concurrency_faults](MockCredentialBuilder::with_concurrency_faults)),
+as some platform stores do when accessed from several threads at once.
  */
 use std::cell::RefCell;
-use std::sync::Mutex;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant};
 
 use super::credential::{
-    Credential, CredentialApi, CredentialBuilder, CredentialBuilderApi, CredentialPersistence,
+    AtRestStatus, Credential, CredentialApi, CredentialBuilder, CredentialBuilderApi,
+    CredentialPersistence, CredentialStoreInfo,
 };
 use super::error::{Error, Result, decode_password};
+use zeroize::Zeroizing;
+
+/// The target, service, and user of a shared mock credential.
+type Key = (Option<String>, String, String);
+
+/// The secrets of the credentials built by a shared mock builder.
+type SharedSecrets = Arc<Mutex<HashMap<Key, Zeroizing<Vec<u8>>>>>;
+
+/// The simulated faults of the credentials built by a mock builder.
+#[derive(Debug, Default, Clone)]
+struct Faults {
+    delay: Duration,
+    concurrency_faults: bool,
+    in_flight: Arc<AtomicUsize>,
+}
+
+/// A call in flight on one of a mock builder's credentials.
+struct InFlight<'a>(&'a AtomicUsize);
+
+impl<'a> InFlight<'a> {
+    /// Start a call, and return whether another call was already in flight.
+    fn start(count: &'a AtomicUsize) -> (Self, bool) {
+        let prior = count.fetch_add(1, Ordering::SeqCst);
+        (Self(count), prior > 0)
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
 
 /// The concrete mock credential
 ///
 /// Mocks use an internal mutability pattern since entries are read-only.
 /// The mutex is used to make sure these are Sync.
+///
+/// The secret of a credential built by a [shared](MockCredentialBuilder::shared)
+/// builder is kept in the builder's map rather than in its [MockData].
 #[derive(Debug)]
 pub struct MockCredential {
     pub inner: Mutex<RefCell<MockData>>,
+    shared: Option<(SharedSecrets, Key)>,
+    faults: Faults,
 }
 
 impl Default for MockCredential {
     fn default() -> Self {
         Self {
             inner: Mutex::new(RefCell::new(Default::default())),
+            shared: None,
+            faults: Faults::default(),
         }
     }
 }
@@ -61,14 +147,30 @@ impl Default for MockCredential {
 /// The (in-memory) persisted data for a mock credential.
 ///
 /// We keep a password, but unlike most keystores
-/// we also keep an intended error to return on the next call.
+/// we also keep an intended error to return on the next call,
+/// the outcomes of the calls after that, and a record of the calls made.
 ///
 /// (Everything about this structure is public for transparency.
 /// Most keystore implementation hide their internals.)
+/// The secret is zeroized when it's replaced or dropped.
 #[derive(Debug, Default)]
 pub struct MockData {
-    pub secret: Option<Vec<u8>>,
+    pub secret: Option<Zeroizing<Vec<u8>>>,
     pub error: Option<Error>,
+    pub outcomes: VecDeque<Option<Error>>,
+    pub calls: Vec<MockCall>,
+}
+
+/// A call made on a mock credential, as recorded in its [MockData].
+#[derive(Debug, Clone)]
+pub struct MockCall {
+    /// The name of the credential method that was called,
+    /// such as `"set_password"` or `"delete_credential"`
+    pub op: &'static str,
+    /// The password or secret that was passed to a set call
+    pub secret: Option<Zeroizing<Vec<u8>>>,
+    /// When the call was made
+    pub at: Instant,
 }
 
 impl CredentialApi for MockCredential {
@@ -78,16 +180,10 @@ impl CredentialApi for MockCredential {
     /// and the password will _not_ be set.  The error will
     /// be cleared, so calling again will set the password.
     fn set_password(&self, password: &str) -> Result<()> {
-        let mut inner = self.inner.lock().expect("Can't access mock data for set");
-        let data = inner.get_mut();
-        let err = data.error.take();
-        match err {
-            None => {
-                data.secret = Some(password.as_bytes().to_vec());
-                Ok(())
-            }
-            Some(err) => Err(err),
-        }
+        self.with_secret("set_password", Some(password.as_bytes()), |secret| {
+            *secret = Some(Zeroizing::new(password.as_bytes().to_vec()));
+            Ok(())
+        })
     }
 
     /// Set a password on a mock credential.
@@ -96,16 +192,10 @@ impl CredentialApi for MockCredential {
     /// and the password will _not_ be set.  The error will
     /// be cleared, so calling again will set the password.
     fn set_secret(&self, secret: &[u8]) -> Result<()> {
-        let mut inner = self.inner.lock().expect("Can't access mock data for set");
-        let data = inner.get_mut();
-        let err = data.error.take();
-        match err {
-            None => {
-                data.secret = Some(secret.to_vec());
-                Ok(())
-            }
-            Some(err) => Err(err),
-        }
+        self.with_secret("set_secret", Some(secret), |stored| {
+            *stored = Some(Zeroizing::new(secret.to_vec()));
+            Ok(())
+        })
     }
 
     /// Get the password from a mock credential, if any.
@@ -113,16 +203,10 @@ impl CredentialApi for MockCredential {
     /// If there is an error set in the mock, it will
     /// be returned instead of a password.
     fn get_password(&self) -> Result<String> {
-        let mut inner = self.inner.lock().expect("Can't access mock data for get");
-        let data = inner.get_mut();
-        let err = data.error.take();
-        match err {
-            None => match &data.secret {
-                None => Err(Error::NoEntry),
-                Some(val) => decode_password(val.clone()),
-            },
-            Some(err) => Err(err),
-        }
+        self.with_secret("get_password", None, |secret| match secret {
+            None => Err(Error::NoEntry),
+            Some(val) => decode_password(val.to_vec()),
+        })
     }
 
     /// Get the password from a mock credential, if any.
@@ -130,16 +214,10 @@ impl CredentialApi for MockCredential {
     /// If there is an error set in the mock, it will
     /// be returned instead of a password.
     fn get_secret(&self) -> Result<Vec<u8>> {
-        let mut inner = self.inner.lock().expect("Can't access mock data for get");
-        let data = inner.get_mut();
-        let err = data.error.take();
-        match err {
-            None => match &data.secret {
-                None => Err(Error::NoEntry),
-                Some(val) => Ok(val.clone()),
-            },
-            Some(err) => Err(err),
-        }
+        self.with_secret("get_secret", None, |secret| match secret {
+            None => Err(Error::NoEntry),
+            Some(val) => Ok(val.to_vec()),
+        })
     }
 
     /// Delete the password in a mock credential
@@ -150,22 +228,15 @@ impl CredentialApi for MockCredential {
     /// If there is no password, a [NoEntry](Error::NoEntry) error
     /// will be returned.
     fn delete_credential(&self) -> Result<()> {
-        let mut inner = self
-            .inner
-            .lock()
-            .expect("Can't access mock data for delete");
-        let data = inner.get_mut();
-        let err = data.error.take();
-        match err {
-            None => match data.secret {
-                Some(_) => {
-                    data.secret = None;
-                    Ok(())
-                }
-                None => Err(Error::NoEntry),
-            },
-            Some(err) => Err(err),
-        }
+        self.with_secret("delete_credential", None, |secret| match secret.take() {
+            Some(_) => Ok(()),
+            None => Err(Error::NoEntry),
+        })
+    }
+
+    /// This store keeps secrets unencrypted in the entry.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        AtRestStatus::Unencrypted
     }
 
     /// Return this mock credential concrete object
@@ -190,6 +261,59 @@ impl MockCredential {
         Ok(Default::default())
     }
 
+    /// Record a call, and run its operation on this credential's secret,
+    /// unless there's an error set in the mock (or it's the next outcome),
+    /// in which case it's cleared and returned.
+    ///
+    /// The call is delayed, and fails if it overlaps another call,
+    /// as this credential's builder was configured.
+    ///
+    /// The secret is the one in the shared map for shared credentials.
+    fn with_secret<T>(
+        &self,
+        op: &'static str,
+        arg: Option<&[u8]>,
+        f: impl FnOnce(&mut Option<Zeroizing<Vec<u8>>>) -> Result<T>,
+    ) -> Result<T> {
+        let (_in_flight, overlapped) = InFlight::start(&self.faults.in_flight);
+        if !self.faults.delay.is_zero() {
+            std::thread::sleep(self.faults.delay);
+        }
+        let mut inner = self
+            .inner
+            .lock()
+            .unwrap_or_else(|_| panic!("Can't access mock data for {op}"));
+        let data = inner.get_mut();
+        data.calls.push(MockCall {
+            op,
+            secret: arg.map(|arg| Zeroizing::new(arg.to_vec())),
+            at: Instant::now(),
+        });
+        let error = data.error.take();
+        if let Some(err) = error.or_else(|| data.outcomes.pop_front().flatten()) {
+            return Err(err);
+        }
+        if overlapped && self.faults.concurrency_faults {
+            return Err(Error::PlatformFailure(
+                "simulated failure under concurrent access".into(),
+            ));
+        }
+        match &self.shared {
+            None => f(&mut data.secret),
+            Some((secrets, key)) => {
+                let mut secrets = secrets
+                    .lock()
+                    .unwrap_or_else(|_| panic!("Can't access shared mock data for {op}"));
+                let mut secret = secrets.remove(key);
+                let result = f(&mut secret);
+                if let Some(secret) = secret {
+                    secrets.insert(key.clone(), secret);
+                }
+                result
+            }
+        }
+    }
+
     /// Set an error to be returned from this mock credential.
     ///
     /// Error returns always take precedence over the normal
@@ -203,41 +327,166 @@ impl MockCredential {
         let data = inner.get_mut();
         data.error = Some(err);
     }
+
+    /// Set the outcomes of the next calls on this mock credential.
+    ///
+    /// Each call takes the next outcome: if it's an error, the call
+    /// returns it; if it's `None`, the call operates as usual.
+    /// Once the outcomes are used up, calls operate as usual.
+    /// (An error set with [set_error](MockCredential::set_error)
+    /// is returned before any of these outcomes are used.)
+    pub fn set_outcomes(&self, outcomes: impl IntoIterator<Item = Option<Error>>) {
+        let mut inner = self
+            .inner
+            .lock()
+            .expect("Can't access mock data for set_outcomes");
+        let data = inner.get_mut();
+        data.outcomes = outcomes.into_iter().collect();
+    }
+
+    /// The calls made on this mock credential, in the order they were made.
+    pub fn calls(&self) -> Vec<MockCall> {
+        let mut inner = self.inner.lock().expect("Can't access mock data for calls");
+        inner.get_mut().calls.clone()
+    }
 }
 
 /// The builder for mock credentials.
-pub struct MockCredentialBuilder {}
+///
+/// A default builder's credentials each keep their own secret;
+/// a [shared](MockCredentialBuilder::shared) builder's credentials
+/// share their secrets with the other credentials built by it (or its clones).
+#[derive(Debug, Default, Clone)]
+pub struct MockCredentialBuilder {
+    shared: Option<SharedSecrets>,
+    faults: Faults,
+}
+
+impl MockCredentialBuilder {
+    /// Create a builder whose credentials share their secrets.
+    pub fn shared() -> Self {
+        Self {
+            shared: Some(SharedSecrets::default()),
+            faults: Faults::default(),
+        }
+    }
+
+    /// Whether this builder's credentials share their secrets.
+    pub fn is_shared(&self) -> bool {
+        self.shared.is_some()
+    }
+
+    /// Delay every call on this builder's credentials by `delay`.
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.faults.delay = delay;
+        self
+    }
+
+    /// How long every call on this builder's credentials is delayed.
+    pub fn delay(&self) -> Duration {
+        self.faults.delay
+    }
+
+    /// Whether a call on one of this builder's credentials that starts while
+    /// another call on one of them (or on those of its clones) is running
+    /// fails with a [PlatformFailure](Error::PlatformFailure) error.
+    pub fn with_concurrency_faults(mut self, faults: bool) -> Self {
+        self.faults.concurrency_faults = faults;
+        self
+    }
+
+    /// Whether overlapping calls on this builder's credentials fail.
+    pub fn concurrency_faults(&self) -> bool {
+        self.faults.concurrency_faults
+    }
+}
 
 impl CredentialBuilderApi for MockCredentialBuilder {
     /// Build a mock credential for the given target, service, and user.
     ///
-    /// Since mocks don't persist between sessions,  all mocks
-    /// start off without passwords.
+    /// Since mocks don't persist between sessions, all mocks
+    /// start off without passwords, unless they are shared
+    /// and another credential for the same target, service,
+    /// and user has set one.
     fn build(&self, target: Option<&str>, service: &str, user: &str) -> Result<Box<Credential>> {
-        let credential = MockCredential::new_with_target(target, service, user)?;
+        let mut credential = MockCredential::new_with_target(target, service, user)?;
+        credential.faults = self.faults.clone();
+        if let Some(secrets) = &self.shared {
+            let key = (
+                target.map(str::to_string),
+                service.to_string(),
+                user.to_string(),
+            );
+            credential.shared = Some((secrets.clone(), key));
+        }
         Ok(Box::new(credential))
     }
 
+    /// This store keeps secrets unencrypted in the entry.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        AtRestStatus::Unencrypted
+    }
+
     /// Get an [Any][std::any::Any] reference to the mock credential builder.
     fn as_any(&self) -> &dyn std::any::Any {
         self
     }
 
     /// This keystore keeps the password in the entry!
+    /// (Or, if it's shared, in process memory.)
     fn persistence(&self) -> CredentialPersistence {
-        CredentialPersistence::EntryOnly
+        match self.shared {
+            None => CredentialPersistence::EntryOnly,
+            Some(_) => CredentialPersistence::ProcessOnly,
+        }
+    }
+
+    /// Mock credentials have no attributes and can't be searched.
+    fn info(&self) -> CredentialStoreInfo {
+        CredentialStoreInfo::new("mock", self.persistence(), self.at_rest_encryption())
     }
 }
 
 /// Return a mock credential builder for use by clients.
 pub fn default_credential_builder() -> Box<CredentialBuilder> {
-    Box::new(MockCredentialBuilder {})
+    Box::new(MockCredentialBuilder::default())
+}
+
+static PROCESS_SECRETS: LazyLock<SharedSecrets> = LazyLock::new(SharedSecrets::default);
+
+/// Return a mock credential builder whose credentials share their secrets
+/// with those of every other builder returned by this function.
+pub fn shared_credential_builder() -> Box<CredentialBuilder> {
+    Box::new(MockCredentialBuilder {
+        shared: Some(PROCESS_SECRETS.clone()),
+        faults: Faults::default(),
+    })
+}
+
+/// Make an [Ambiguous](Error::Ambiguous) error whose credentials
+/// are mock credentials with the given secrets.
+///
+/// This is meant to be used as a scripted outcome
+/// (see [set_outcomes](MockCredential::set_outcomes)).
+pub fn ambiguous(secrets: &[&[u8]]) -> Error {
+    let credentials = secrets
+        .iter()
+        .map(|secret| {
+            let credential = MockCredential::default();
+            credential.inner.lock().unwrap().get_mut().secret =
+                Some(Zeroizing::new(secret.to_vec()));
+            Box::new(credential) as Box<Credential>
+        })
+        .collect();
+    Error::Ambiguous(credentials)
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{MockCredential, default_credential_builder};
-    use crate::credential::CredentialPersistence;
+    use std::time::Duration;
+
+    use super::{MockCredential, MockCredentialBuilder, ambiguous, default_credential_builder};
+    use crate::credential::{CredentialBuilderApi, CredentialPersistence, Match, SearchSpec};
     use crate::{Entry, Error, tests::generate_random_string};
 
     #[test]
@@ -248,6 +497,131 @@ mod tests {
         ))
     }
 
+    #[test]
+    fn test_shared() {
+        let name = generate_random_string();
+        let builder = MockCredentialBuilder::shared();
+        let first = Entry::new_with_credential(builder.build(None, &name, "user").unwrap());
+        let second =
+            Entry::new_with_credential(builder.clone().build(None, &name, "user").unwrap());
+        let other =
+            Entry::new_with_credential(builder.build(Some("target"), &name, "user").unwrap());
+        first.set_password("shared").unwrap();
+        assert_eq!(second.get_password().unwrap(), "shared");
+        assert!(matches!(other.get_password(), Err(Error::NoEntry)));
+        let mock: &MockCredential = second.get_credential().downcast_ref().unwrap();
+        mock.set_error(Error::NoEntry);
+        assert!(matches!(second.get_password(), Err(Error::NoEntry)));
+        assert_eq!(first.get_password().unwrap(), "shared");
+        second.delete_credential().unwrap();
+        assert!(matches!(first.get_password(), Err(Error::NoEntry)));
+        let isolated = MockCredentialBuilder::default();
+        isolated
+            .build(None, &name, "user")
+            .unwrap()
+            .set_password("isolated")
+            .unwrap();
+        assert!(matches!(
+            isolated.build(None, &name, "user").unwrap().get_password(),
+            Err(Error::NoEntry)
+        ));
+    }
+
+    #[test]
+    fn test_outcomes() {
+        let name = generate_random_string();
+        let entry = entry_new(&name, &name);
+        let mock: &MockCredential = entry.get_credential().downcast_ref().unwrap();
+        entry.set_password("retried").unwrap();
+        mock.set_outcomes([
+            Some(Error::NoEntry),
+            None,
+            Some(Error::PlatformFailure("failed".into())),
+        ]);
+        assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+        assert_eq!(entry.get_password().unwrap(), "retried");
+        assert!(matches!(
+            entry.get_password(),
+            Err(Error::PlatformFailure(_))
+        ));
+        assert_eq!(entry.get_password().unwrap(), "retried");
+        let calls = mock.calls();
+        let ops: Vec<_> = calls.iter().map(|call| call.op).collect();
+        assert_eq!(
+            ops,
+            [
+                "set_password",
+                "get_password",
+                "get_password",
+                "get_password",
+                "get_password"
+            ]
+        );
+        assert_eq!(calls[0].secret.as_deref().unwrap().as_slice(), b"retried");
+        assert!(calls[1].secret.is_none());
+        assert!(calls.windows(2).all(|pair| pair[0].at <= pair[1].at));
+    }
+
+    #[test]
+    fn test_ambiguous() {
+        let name = generate_random_string();
+        let entry = entry_new(&name, &name);
+        let mock: &MockCredential = entry.get_credential().downcast_ref().unwrap();
+        mock.set_outcomes([Some(ambiguous(&[b"first", b"second"]))]);
+        match entry.get_secret() {
+            Err(Error::Ambiguous(creds)) => {
+                assert_eq!(creds.len(), 2);
+                assert_eq!(creds[1].get_secret().unwrap(), b"second");
+            }
+            other => panic!("Expected an ambiguous error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_concurrency_faults() {
+        let name = generate_random_string();
+        let builder = MockCredentialBuilder::shared()
+            .with_delay(Duration::from_millis(200))
+            .with_concurrency_faults(true);
+        let first = builder.build(None, &name, "user").unwrap();
+        let second = builder.build(None, &name, "user").unwrap();
+        let slow = std::thread::spawn(move || first.set_password("first"));
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(matches!(
+            second.set_password("second"),
+            Err(Error::PlatformFailure(_))
+        ));
+        slow.join().unwrap().unwrap();
+        assert_eq!(second.get_password().unwrap(), "first");
+    }
+
+    #[test]
+    fn test_search() {
+        let spec = SearchSpec {
+            user: Match::Prefix("tenant1:".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            default_credential_builder().search(&spec),
+            Err(Error::NotSupportedByStore(_))
+        ))
+    }
+
+    #[test]
+    fn test_expiration() {
+        let entry = entry_new("test-expiration", &generate_random_string());
+        assert!(matches!(
+            entry.set_expiration(core::time::Duration::from_secs(60)),
+            Err(Error::NotSupportedByStore(_))
+        ))
+    }
+
+    #[test]
+    fn test_unlock() {
+        let entry = entry_new("test-unlock", &generate_random_string());
+        assert!(matches!(entry.unlock(), Err(Error::NotSupportedByStore(_))))
+    }
+
     fn entry_new(service: &str, user: &str) -> Entry {
         let credential = MockCredential::new_with_target(None, service, user).unwrap();
         Entry::new_with_credential(Box::new(credential))
@@ -288,6 +662,23 @@ mod tests {
         crate::tests::test_noop_get_update_attributes(entry_new);
     }
 
+    #[test]
+    fn test_get_password_opt() {
+        let name = generate_random_string();
+        let entry = entry_new(&name, &name);
+        assert!(matches!(entry.get_password_opt(), Ok(None)));
+        entry.set_password("").expect("Can't set empty password");
+        assert!(matches!(entry.get_password_opt(), Ok(Some(p)) if p.is_empty()));
+        entry.set_password("test").expect("Can't set password");
+        assert!(matches!(entry.get_password_opt(), Ok(Some(p)) if p == "test"));
+        let mock: &MockCredential = entry.get_credential().downcast_ref().unwrap();
+        mock.set_error(Error::NoStorageAccess("locked".into()));
+        assert!(matches!(
+            entry.get_password_opt(),
+            Err(Error::NoStorageAccess(_))
+        ));
+    }
+
     #[test]
     fn test_set_error() {
         let name = generate_random_string();
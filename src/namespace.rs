@@ -0,0 +1,247 @@
+/*!
+
+# Namespaced credential builder
+
+A library that is embedded in a host application, and that keeps its
+credentials in the same store as the host, has to make sure that its
+credentials can't collide with those of the host (or of other libraries).
+The [NamespacedCredentialBuilder] in this module does that by wrapping another
+builder and transparently prefixing the service of every credential
+it builds with a namespace string. Builders can also be asked to prefix
+the target of every credential that has one (see
+[with_target_prefixing](NamespacedCredentialBuilder::with_target_prefixing)),
+for stores in which the target picks a shared collection or keychain.
+
+Searches done through a namespaced builder are restricted to the namespace,
+and the search spec's service matcher is applied to the unprefixed service,
+so it's easy to find (and then clean up) just the credentials in a namespace:
+```
+# use keyring::{Entry, credential::{CredentialBuilderApi, SearchSpec}};
+# use keyring::{memory::MemoryCredentialBuilder, namespace::NamespacedCredentialBuilder};
+let store = MemoryCredentialBuilder::default();
+let builder = NamespacedCredentialBuilder::new("my-library:", Box::new(store.clone()));
+builder.build(None, "service", "user").unwrap().set_password("mine").unwrap();
+store.build(None, "service", "user").unwrap().set_password("host's").unwrap();
+let mine = builder.search(&SearchSpec::default()).unwrap();
+assert_eq!(mine.len(), 1);
+assert_eq!(mine[0].get_password().unwrap(), "mine");
+```
+
+Since entries record the service they were created with, entries created
+through a namespaced default builder (see
+[set_default_credential_builder](crate::set_default_credential_builder))
+report their service without the namespace.
+ */
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use super::credential::{
+    AtRestStatus, Credential, CredentialBuilder, CredentialBuilderApi, CredentialPersistence,
+    CredentialStoreInfo, Match, SearchSpec,
+};
+use super::error::Result;
+
+/// A credential builder that prefixes the service of every credential
+/// built by another builder with a namespace.
+#[derive(Debug)]
+pub struct NamespacedCredentialBuilder {
+    namespace: String,
+    inner: Box<CredentialBuilder>,
+    prefix_targets: bool,
+}
+
+impl NamespacedCredentialBuilder {
+    /// Create a builder that puts the credentials built by `inner` into `namespace`.
+    ///
+    /// The namespace is used as-is as a prefix, so you will typically want
+    /// it to end with a separator character (such as `:` or `.`).
+    pub fn new(namespace: &str, inner: Box<CredentialBuilder>) -> Self {
+        Self {
+            namespace: namespace.to_string(),
+            inner,
+            prefix_targets: false,
+        }
+    }
+
+    /// Return this builder with the targets of its credentials
+    /// prefixed with its namespace (or not).
+    ///
+    /// Credentials without a target are left without one.
+    pub fn with_target_prefixing(mut self, prefix_targets: bool) -> Self {
+        self.prefix_targets = prefix_targets;
+        self
+    }
+
+    /// Whether this builder prefixes the targets of its credentials.
+    pub fn prefixes_targets(&self) -> bool {
+        self.prefix_targets
+    }
+
+    /// The namespace used by this builder.
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    /// The wrapped builder.
+    pub fn inner(&self) -> &CredentialBuilder {
+        self.inner.as_ref()
+    }
+
+    /// The service used in the wrapped store for the given service.
+    pub fn namespaced(&self, service: &str) -> String {
+        format!("{}{service}", self.namespace)
+    }
+
+    /// The target used in the wrapped store for the given target.
+    pub fn namespaced_target(&self, target: Option<&str>) -> Option<String> {
+        match target {
+            Some(target) if self.prefix_targets => Some(self.namespaced(target)),
+            _ => target.map(|t| t.to_string()),
+        }
+    }
+
+    /// The service in this builder's namespace that corresponds to the given
+    /// service in the wrapped store, if there is one.
+    pub fn strip_namespace<'a>(&self, service: &'a str) -> Option<&'a str> {
+        service.strip_prefix(self.namespace.as_str())
+    }
+}
+
+impl CredentialBuilderApi for NamespacedCredentialBuilder {
+    /// Build a credential in the wrapped store with the namespaced service
+    /// (and target, if targets are prefixed).
+    fn build(&self, target: Option<&str>, service: &str, user: &str) -> Result<Box<Credential>> {
+        let target = self.namespaced_target(target);
+        self.inner
+            .build(target.as_deref(), &self.namespaced(service), user)
+    }
+
+    /// The at-rest encryption of the wrapped store.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        self.inner.at_rest_encryption()
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+
+    /// The persistence of the wrapped store.
+    fn persistence(&self) -> CredentialPersistence {
+        self.inner.persistence()
+    }
+
+    /// Search the wrapped store for credentials in this builder's namespace.
+    ///
+    /// The service matcher in the spec is applied to the unprefixed service
+    /// (and, if targets are prefixed, the target matcher to the unprefixed target).
+    fn search(&self, spec: &SearchSpec) -> Result<Vec<Box<Credential>>> {
+        let service = match &spec.service {
+            Match::Exact(service) => Match::Exact(self.namespaced(service)),
+            Match::Prefix(prefix) => Match::Prefix(self.namespaced(prefix)),
+            Match::Any => Match::Prefix(self.namespace.clone()),
+        };
+        let target = match &spec.target {
+            Match::Exact(target) if self.prefix_targets => Match::Exact(self.namespaced(target)),
+            Match::Prefix(prefix) if self.prefix_targets => Match::Prefix(self.namespaced(prefix)),
+            target => target.clone(),
+        };
+        let spec = SearchSpec {
+            target,
+            service,
+            ..spec.clone()
+        };
+        self.inner.search(&spec)
+    }
+
+    /// The info of the wrapped store.
+    fn info(&self) -> CredentialStoreInfo {
+        self.inner.info()
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::NamespacedCredentialBuilder;
+    use crate::credential::{CredentialBuilderApi, Match, SearchSpec};
+    use crate::memory::MemoryCredentialBuilder;
+
+    #[test]
+    fn test_namespace() {
+        let store = MemoryCredentialBuilder::default();
+        let builder = NamespacedCredentialBuilder::new("ns:", Box::new(store.clone()));
+        builder
+            .build(None, "service", "user")
+            .unwrap()
+            .set_password("namespaced")
+            .unwrap();
+        let raw = store.build(None, "ns:service", "user").unwrap();
+        assert_eq!(raw.get_password().unwrap(), "namespaced");
+        assert!(
+            store
+                .build(None, "service", "user")
+                .unwrap()
+                .get_password()
+                .is_err()
+        );
+        assert_eq!(builder.strip_namespace("ns:service"), Some("service"));
+        assert_eq!(builder.strip_namespace("service"), None);
+    }
+
+    #[test]
+    fn test_target_prefixing() {
+        let store = MemoryCredentialBuilder::default();
+        let builder = NamespacedCredentialBuilder::new("ns:", Box::new(store.clone()))
+            .with_target_prefixing(true);
+        assert!(builder.prefixes_targets());
+        builder
+            .build(Some("target"), "service", "user")
+            .unwrap()
+            .set_password("namespaced")
+            .unwrap();
+        let raw = store
+            .build(Some("ns:target"), "ns:service", "user")
+            .unwrap();
+        assert_eq!(raw.get_password().unwrap(), "namespaced");
+        assert_eq!(builder.namespaced_target(None), None);
+        let spec = SearchSpec {
+            target: Match::Exact("target".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(builder.search(&spec).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_namespaced_search() {
+        let store = MemoryCredentialBuilder::default();
+        let builder = NamespacedCredentialBuilder::new("ns:", Box::new(store.clone()));
+        for service in ["service1", "service2", "other"] {
+            builder
+                .build(None, service, "user")
+                .unwrap()
+                .set_password("namespaced")
+                .unwrap();
+            store
+                .build(None, service, "user")
+                .unwrap()
+                .set_password("raw")
+                .unwrap();
+        }
+        let all = builder.search(&SearchSpec::default()).unwrap();
+        assert_eq!(all.len(), 3);
+        let spec = SearchSpec {
+            service: Match::Prefix("service".to_string()),
+            ..Default::default()
+        };
+        let found = builder.search(&spec).unwrap();
+        assert_eq!(found.len(), 2);
+        let spec = SearchSpec {
+            service: Match::Exact("other".to_string()),
+            ..Default::default()
+        };
+        let found = builder.search(&spec).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].get_password().unwrap(), "namespaced");
+    }
+}
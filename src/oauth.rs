@@ -0,0 +1,204 @@
+/*!
+
+# OAuth-style token sets
+
+A very common use of a credential store is to keep the tokens handed out
+by an OAuth (or OAuth-like) authorization server: an access token,
+an optional refresh token, and the time at which the access token expires.
+This module provides a [TokenSet] type for that data, and [Entry] methods
+that store and retrieve a token set as the entry's secret.
+
+Token sets are serialized as JSON before being stored, so they can be
+read by any client of the underlying credential store that can parse JSON.
+Expiration times are stored as seconds and nanoseconds since the Unix epoch.
+
+The tokens in a token set are secrets, too, so they are left out of its
+`Debug` output, and they are zeroized when the token set is dropped.
+
+This module is only available if the `oauth` feature is specified.
+
+```
+# use keyring::{Entry, mock, oauth::TokenSet};
+# keyring::set_default_credential_builder(mock::default_credential_builder());
+use std::time::{Duration, SystemTime};
+
+let entry = Entry::new("my-service", "my-name").unwrap();
+let tokens = TokenSet {
+    access_token: "access".to_string(),
+    refresh_token: Some("refresh".to_string()),
+    expires_at: Some(SystemTime::now() + Duration::from_secs(3600)),
+};
+entry.set_tokens(&tokens).unwrap();
+let stored = entry.get_tokens().unwrap();
+assert_eq!(stored, tokens);
+assert!(!stored.is_expired());
+```
+ */
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use super::Entry;
+use super::error::Result;
+
+/// An access token, with an optional refresh token and expiration time.
+///
+/// The tokens are zeroized when a token set is dropped (which means
+/// they can't be moved out of it; clone or [take](core::mem::take) them).
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenSet {
+    pub access_token: String,
+    pub refresh_token: Option<String>,
+    pub expires_at: Option<SystemTime>,
+}
+
+impl core::fmt::Debug for TokenSet {
+    /// Shows the expiration time, and whether there's a refresh token,
+    /// but never the tokens.
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("TokenSet")
+            .field("access_token", &"<redacted>")
+            .field(
+                "refresh_token",
+                &self.refresh_token.as_ref().map(|_| "<redacted>"),
+            )
+            .field("expires_at", &self.expires_at)
+            .finish()
+    }
+}
+
+impl Drop for TokenSet {
+    fn drop(&mut self) {
+        self.access_token.zeroize();
+        self.refresh_token.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for TokenSet {}
+
+impl TokenSet {
+    /// Whether the access token has expired as of now.
+    ///
+    /// Token sets with no expiration time never expire.
+    pub fn is_expired(&self) -> bool {
+        self.is_expired_at(SystemTime::now())
+    }
+
+    /// Whether the access token has expired as of the given time.
+    ///
+    /// This is useful when the caller has its own notion of the current time
+    /// (for example, a clock that is adjusted for skew with the server, or a
+    /// fixed time in tests). A token whose expiration time is exactly `now`
+    /// is considered expired.
+    pub fn is_expired_at(&self, now: SystemTime) -> bool {
+        match self.expires_at {
+            Some(expires_at) => expires_at <= now,
+            None => false,
+        }
+    }
+}
+
+impl Entry {
+    /// Set the secret for this entry to the serialized form of a token set.
+    ///
+    /// This is [set_serialized](Entry::set_serialized) for token sets.
+    /// See [set_secret](Entry::set_secret) for the errors this can return.
+    pub fn set_tokens(&self, tokens: &TokenSet) -> Result<()> {
+        self.set_serialized(tokens)
+    }
+
+    /// Retrieve the token set saved for this entry.
+    ///
+    /// Returns a [NoEntry](crate::Error::NoEntry) error if there isn't one,
+    /// and an [Invalid](crate::Error::Invalid) error if the saved secret
+    /// is not a serialized token set.
+    ///
+    /// This is [get_deserialized](Entry::get_deserialized) for token sets.
+    /// See [get_secret](Entry::get_secret) for the other errors this can return.
+    pub fn get_tokens(&self) -> Result<TokenSet> {
+        self.get_deserialized()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use super::TokenSet;
+    use crate::mock::MockCredential;
+    use crate::{Entry, Error};
+
+    fn entry_new() -> Entry {
+        Entry::new_with_credential(Box::new(MockCredential::default()))
+    }
+
+    #[test]
+    fn test_round_trip_tokens() {
+        let entry = entry_new();
+        let tokens = TokenSet {
+            access_token: "test access token".to_string(),
+            refresh_token: Some("test refresh token".to_string()),
+            expires_at: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000)),
+        };
+        entry.set_tokens(&tokens).expect("Can't set tokens");
+        let stored = entry.get_tokens().expect("Can't get tokens");
+        assert_eq!(stored, tokens, "Retrieved and set tokens don't match");
+        entry.delete_credential().expect("Can't delete tokens");
+        assert!(
+            matches!(entry.get_tokens(), Err(Error::NoEntry)),
+            "Able to read deleted tokens"
+        );
+    }
+
+    #[test]
+    fn test_bad_tokens() {
+        let entry = entry_new();
+        entry
+            .set_password("not a token set")
+            .expect("Can't set password");
+        assert!(
+            matches!(entry.get_tokens(), Err(Error::Invalid(_, _))),
+            "Read a token set from a plain password"
+        );
+    }
+
+    #[test]
+    fn test_debug_redacts_tokens() {
+        let tokens = TokenSet {
+            access_token: "test access token".to_string(),
+            refresh_token: Some("test refresh token".to_string()),
+            expires_at: None,
+        };
+        let debug = format!("{tokens:?}");
+        assert!(
+            !debug.contains("test access token"),
+            "Debug shows access token"
+        );
+        assert!(
+            !debug.contains("test refresh token"),
+            "Debug shows refresh token"
+        );
+        assert!(
+            debug.contains("refresh_token: Some"),
+            "Debug hides refresh token presence"
+        );
+    }
+
+    #[test]
+    fn test_expiration() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let mut tokens = TokenSet {
+            access_token: "test access token".to_string(),
+            refresh_token: None,
+            expires_at: None,
+        };
+        assert!(!tokens.is_expired_at(now), "Token with no expiry expired");
+        tokens.expires_at = Some(now + Duration::from_secs(1));
+        assert!(!tokens.is_expired_at(now), "Token expired early");
+        tokens.expires_at = Some(now);
+        assert!(tokens.is_expired_at(now), "Token didn't expire on time");
+        tokens.expires_at = Some(now - Duration::from_secs(1));
+        assert!(tokens.is_expired_at(now), "Token didn't expire late");
+    }
+}
@@ -0,0 +1,489 @@
+/*!
+
+# PKCS#11 credential store
+
+Organizations that issue smartcards (or other hardware tokens) often want
+secrets bound to the token rather than kept in a user's OS store. This
+module keeps each credential as a PKCS#11 data object (`CKO_DATA`) on a
+token, talking to the token through its vendor's PKCS#11 module (a shared
+library, such as OpenSC's `opensc-pkcs11.so` or SoftHSM's `libsofthsm2.so`),
+which is loaded when the builder is created. It's available when the
+`pkcs11` feature is enabled. It's not the default store on any platform,
+so clients have to set it as their default (or build their credentials
+from it directly):
+```no_run
+use keyring::pkcs11::Pkcs11CredentialBuilder;
+
+let builder = Pkcs11CredentialBuilder::new("/usr/lib/softhsm/libsofthsm2.so")
+    .unwrap()
+    .with_pin(Some("1234"));
+keyring::set_default_credential_builder(Box::new(builder));
+```
+
+For a given <_target_, _service_, _user_> triple, this module uses the
+token whose label is the target (or the builder's token, if there is no
+target, which by default is the first token that has been initialized),
+and the data object whose application is `keyring-rs` and whose label is
+the service and user (separated by a colon, with colons and percent signs in
+them percent-encoded). The object's value is the secret. Objects are created
+as private token objects, so they persist on the token and can only be read
+after logging in.
+
+Every operation opens a session with the token and, if the token requires
+it, logs in as its user: with the builder's PIN, if it has one, or else
+through the token's protected authentication path (such as a PIN pad).
+An incorrect (or locked) PIN, or a missing token, produces a
+[NoStorageAccess](crate::Error::NoStorageAccess) error, and a cancelled
+PIN pad produces a [Cancelled](crate::Error::Cancelled) error.
+
+If (because of some other app) more than one object matches a credential,
+its secret is read from (and written to) the first of them, and they are
+all deleted together. This store supports [search](crate::search) of a
+single token (the builder's), but not attributes or labels.
+ */
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use cryptoki::context::{CInitializeArgs, CInitializeFlags, Pkcs11};
+use cryptoki::error::{Error as Pkcs11Error, RvError};
+use cryptoki::object::{Attribute, AttributeType, ObjectClass, ObjectHandle};
+use cryptoki::session::{Session, UserType};
+use cryptoki::types::AuthPin;
+use zeroize::Zeroizing;
+
+use super::credential::{
+    AtRestStatus, Credential, CredentialApi, CredentialBuilderApi, CredentialPersistence,
+    CredentialSpec, CredentialStoreInfo, SearchSpec,
+};
+use super::error::{Error as ErrorCode, Result};
+
+/// The application of the data objects kept by this module.
+const APPLICATION: &str = "keyring-rs";
+
+/// A token (or the default token) in a loaded module, and how to log in to it.
+#[derive(Clone)]
+struct Token {
+    pkcs11: Pkcs11,
+    label: Option<String>,
+    pin: Option<Arc<Zeroizing<String>>>,
+}
+
+impl Token {
+    /// Open a read-write session with the token, logging in if necessary.
+    fn session(&self) -> Result<Session> {
+        let mut found = None;
+        for slot in self
+            .pkcs11
+            .get_slots_with_initialized_token()
+            .map_err(decode_error)?
+        {
+            let info = self.pkcs11.get_token_info(slot).map_err(decode_error)?;
+            match &self.label {
+                Some(label) if info.label() != label => continue,
+                _ => {
+                    found = Some((slot, info.login_required()));
+                    break;
+                }
+            }
+        }
+        let (slot, login_required) = found.ok_or_else(|| {
+            let reason = match &self.label {
+                Some(label) => format!("there is no PKCS#11 token labeled `{label}`"),
+                None => "there is no initialized PKCS#11 token".to_string(),
+            };
+            ErrorCode::NoStorageAccess(Box::from(reason))
+        })?;
+        let session = self.pkcs11.open_rw_session(slot).map_err(decode_error)?;
+        if login_required {
+            let pin = self.pin.as_ref().map(|pin| AuthPin::from(pin.as_str()));
+            match session.login(UserType::User, pin.as_ref()) {
+                Ok(()) | Err(Pkcs11Error::Pkcs11(RvError::UserAlreadyLoggedIn, _)) => {}
+                Err(err) => return Err(decode_error(err)),
+            }
+        }
+        Ok(session)
+    }
+}
+
+/// The template that matches all of this module's data objects.
+fn data_template() -> Vec<Attribute> {
+    vec![
+        Attribute::Class(ObjectClass::DATA),
+        Attribute::Token(true),
+        Attribute::Application(APPLICATION.as_bytes().to_vec()),
+    ]
+}
+
+/// The representation of a PKCS#11 data object.
+#[derive(Clone)]
+pub struct Pkcs11Credential {
+    target: Option<String>,
+    service: String,
+    user: String,
+    token: Token,
+}
+
+impl CredentialApi for Pkcs11Credential {
+    /// Set the value of this credential's object, creating it if necessary.
+    fn set_secret(&self, secret: &[u8]) -> Result<()> {
+        let session = self.token.session()?;
+        let value = Attribute::Value(secret.to_vec());
+        match self.find(&session)?.first() {
+            Some(object) => session
+                .update_attributes(*object, &[value])
+                .map_err(decode_error),
+            None => {
+                let mut template = self.template();
+                template.push(Attribute::Private(true));
+                template.push(value);
+                session.create_object(&template).map_err(decode_error)?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Read the value of this credential's object, if it exists.
+    ///
+    /// Returns a [NoEntry](ErrorCode::NoEntry) error if there is no object.
+    fn get_secret(&self) -> Result<Vec<u8>> {
+        let session = self.token.session()?;
+        let object = *self.find(&session)?.first().ok_or(ErrorCode::NoEntry)?;
+        let attributes = session
+            .get_attributes(object, &[AttributeType::Value])
+            .map_err(decode_error)?;
+        match attributes.into_iter().next() {
+            Some(Attribute::Value(secret)) => Ok(secret),
+            _ => Err(ErrorCode::PlatformFailure(Box::from(
+                "PKCS#11 data object has no value",
+            ))),
+        }
+    }
+
+    /// Destroy this credential's object (or objects), if it exists.
+    ///
+    /// Returns a [NoEntry](ErrorCode::NoEntry) error if there is no object.
+    fn delete_credential(&self) -> Result<()> {
+        let session = self.token.session()?;
+        let objects = self.find(&session)?;
+        if objects.is_empty() {
+            return Err(ErrorCode::NoEntry);
+        }
+        for object in objects {
+            session.destroy_object(object).map_err(decode_error)?;
+        }
+        Ok(())
+    }
+
+    /// The target (token), service, and user of this credential.
+    fn spec(&self) -> Option<CredentialSpec> {
+        Some(CredentialSpec {
+            target: self.target.clone(),
+            service: self.service.clone(),
+            user: self.user.clone(),
+        })
+    }
+
+    /// Private token objects are protected by the token.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        AtRestStatus::Encrypted
+    }
+
+    /// Return the underlying concrete object with an `Any` type so that it can
+    /// be downgraded to a [Pkcs11Credential] for platform-specific processing.
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    /// Expose the concrete debug formatter for use via the [Credential] trait
+    fn debug_fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self, f)
+    }
+}
+
+impl std::fmt::Debug for Pkcs11Credential {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Pkcs11Credential")
+            .field("target", &self.target)
+            .field("service", &self.service)
+            .field("user", &self.user)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Pkcs11Credential {
+    /// The label of this credential's object.
+    pub fn object_label(&self) -> String {
+        object_label(&self.service, &self.user)
+    }
+
+    /// The template that matches this credential's object.
+    fn template(&self) -> Vec<Attribute> {
+        let mut template = data_template();
+        template.push(Attribute::Label(self.object_label().into_bytes()));
+        template
+    }
+
+    fn find(&self, session: &Session) -> Result<Vec<ObjectHandle>> {
+        session.find_objects(&self.template()).map_err(decode_error)
+    }
+}
+
+/// The builder for PKCS#11 credentials.
+#[derive(Clone)]
+pub struct Pkcs11CredentialBuilder {
+    module: PathBuf,
+    token: Token,
+}
+
+impl Pkcs11CredentialBuilder {
+    /// Create a builder for the tokens of the PKCS#11 module at `module`.
+    ///
+    /// The module is loaded and initialized (unless this process has already
+    /// initialized it). Returns a [NoStorageAccess](ErrorCode::NoStorageAccess)
+    /// error if it can't be loaded.
+    pub fn new(module: impl AsRef<Path>) -> Result<Self> {
+        let pkcs11 = Pkcs11::new(module.as_ref()).map_err(decode_error)?;
+        match pkcs11.initialize(CInitializeArgs::new(CInitializeFlags::OS_LOCKING_OK)) {
+            Ok(()) | Err(Pkcs11Error::Pkcs11(RvError::CryptokiAlreadyInitialized, _)) => {}
+            Err(err) => return Err(decode_error(err)),
+        }
+        Ok(Self {
+            module: module.as_ref().to_path_buf(),
+            token: Token {
+                pkcs11,
+                label: None,
+                pin: None,
+            },
+        })
+    }
+
+    /// The path of this builder's module.
+    pub fn module(&self) -> &Path {
+        &self.module
+    }
+
+    /// Use the token with the given label (or, if `None`, the first initialized
+    /// token) for credentials that have no target.
+    pub fn with_token_label(mut self, label: Option<&str>) -> Self {
+        self.token.label = label.map(|l| l.to_string());
+        self
+    }
+
+    /// The label of the token used for credentials with no target, if there is one.
+    pub fn token_label(&self) -> Option<&str> {
+        self.token.label.as_deref()
+    }
+
+    /// Log in to tokens with the given user PIN (or, if `None`, through
+    /// their protected authentication path).
+    pub fn with_pin(mut self, pin: Option<&str>) -> Self {
+        self.token.pin = pin.map(|p| Arc::new(Zeroizing::new(p.to_string())));
+        self
+    }
+}
+
+impl std::fmt::Debug for Pkcs11CredentialBuilder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Pkcs11CredentialBuilder")
+            .field("module", &self.module)
+            .field("token_label", &self.token.label)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CredentialBuilderApi for Pkcs11CredentialBuilder {
+    /// Build a [Pkcs11Credential] for the given target, service, and user.
+    ///
+    /// The token isn't opened until the credential is used.
+    fn build(&self, target: Option<&str>, service: &str, user: &str) -> Result<Box<Credential>> {
+        let mut token = self.token.clone();
+        if let Some(target) = target {
+            token.label = Some(target.to_string());
+        }
+        Ok(Box::new(Pkcs11Credential {
+            target: target.map(|t| t.to_string()),
+            service: service.to_string(),
+            user: user.to_string(),
+            token,
+        }))
+    }
+
+    /// Private token objects are protected by the token.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        AtRestStatus::Encrypted
+    }
+
+    /// Return the underlying builder object with an `Any` type so that it can
+    /// be downgraded to a [Pkcs11CredentialBuilder] for platform-specific processing.
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    /// Token objects are kept until they are destroyed.
+    fn persistence(&self) -> CredentialPersistence {
+        CredentialPersistence::UntilDelete
+    }
+
+    /// Return a credential for each of this module's objects on the builder's
+    /// token that matches the spec.
+    ///
+    /// The credentials have no target, so a spec that requires one matches nothing.
+    fn search(&self, spec: &SearchSpec) -> Result<Vec<Box<Credential>>> {
+        let session = self.token.session()?;
+        let objects = session
+            .find_objects(&data_template())
+            .map_err(decode_error)?;
+        let mut found = Vec::new();
+        for object in objects {
+            let attributes = session
+                .get_attributes(object, &[AttributeType::Label])
+                .map_err(decode_error)?;
+            let Some(Attribute::Label(label)) = attributes.into_iter().next() else {
+                continue;
+            };
+            let Some((service, user)) = parse_object_label(&String::from_utf8_lossy(&label)) else {
+                continue;
+            };
+            if spec.matches(None, &service, &user)
+                && !found.contains(&(service.clone(), user.clone()))
+            {
+                found.push((service, user));
+            }
+        }
+        if !spec.unsorted {
+            found.sort();
+        }
+        Ok(found
+            .into_iter()
+            .map(|(service, user)| {
+                Box::new(Pkcs11Credential {
+                    target: None,
+                    service,
+                    user,
+                    token: self.token.clone(),
+                }) as Box<Credential>
+            })
+            .collect())
+    }
+
+    /// Data objects can be searched, but have no updatable attributes.
+    fn info(&self) -> CredentialStoreInfo {
+        CredentialStoreInfo::new("pkcs11", self.persistence(), self.at_rest_encryption())
+            .with_search(true)
+    }
+}
+
+/// The label of the object for a service and user.
+fn object_label(service: &str, user: &str) -> String {
+    format!("{}:{}", escape(service), escape(user))
+}
+
+/// The service and user of an object label, if it's one of this module's.
+fn parse_object_label(label: &str) -> Option<(String, String)> {
+    let (service, user) = label.split_once(':')?;
+    Some((unescape(service)?, unescape(user)?))
+}
+
+fn escape(part: &str) -> String {
+    part.replace('%', "%25").replace(':', "%3A")
+}
+
+fn unescape(part: &str) -> Option<String> {
+    let mut result = String::new();
+    let mut rest = part;
+    while let Some(i) = rest.find('%') {
+        result.push_str(&rest[..i]);
+        match rest.get(i..i + 3)? {
+            "%25" => result.push('%'),
+            "%3A" => result.push(':'),
+            _ => return None,
+        }
+        rest = &rest[i + 3..];
+    }
+    result.push_str(rest);
+    Some(result)
+}
+
+/// Map PKCS#11 errors to crate errors.
+///
+/// Failures to load the module, reach the token, or log in to it
+/// mean there's no access to the store.
+fn decode_error(err: Pkcs11Error) -> ErrorCode {
+    match err {
+        Pkcs11Error::Pkcs11(RvError::FunctionCanceled, _) => ErrorCode::Cancelled,
+        Pkcs11Error::LibraryLoading(_)
+        | Pkcs11Error::Pkcs11(
+            RvError::PinIncorrect
+            | RvError::PinLocked
+            | RvError::PinExpired
+            | RvError::UserPinNotInitialized
+            | RvError::UserNotLoggedIn
+            | RvError::TokenNotPresent
+            | RvError::TokenNotRecognized
+            | RvError::DeviceRemoved,
+            _,
+        ) => ErrorCode::NoStorageAccess(Box::new(err)),
+        _ => ErrorCode::PlatformFailure(Box::new(err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cryptoki::context::Function;
+    use cryptoki::error::{Error as Pkcs11Error, RvError};
+
+    use crate::credential::CredentialBuilderApi;
+    use crate::{Entry, Error, tests::generate_random_string};
+
+    use super::{Pkcs11CredentialBuilder, decode_error, object_label, parse_object_label};
+
+    #[test]
+    fn test_object_labels() {
+        for (service, user) in [("service", "user"), ("a:b", "100%"), ("", "c:%3A")] {
+            let label = object_label(service, user);
+            assert_eq!(
+                parse_object_label(&label),
+                Some((service.to_string(), user.to_string()))
+            );
+        }
+        assert_eq!(parse_object_label("no separator"), None);
+        assert_eq!(parse_object_label("bad%escape:user"), None);
+    }
+
+    #[test]
+    fn test_decode_error() {
+        let err = Pkcs11Error::Pkcs11(RvError::PinIncorrect, Function::Login);
+        assert!(matches!(decode_error(err), Error::NoStorageAccess(_)));
+        let err = Pkcs11Error::Pkcs11(RvError::FunctionCanceled, Function::Login);
+        assert!(matches!(decode_error(err), Error::Cancelled));
+        let err = Pkcs11Error::Pkcs11(RvError::DeviceMemory, Function::CreateObject);
+        assert!(matches!(decode_error(err), Error::PlatformFailure(_)));
+    }
+
+    #[test]
+    fn test_missing_module() {
+        assert!(matches!(
+            Pkcs11CredentialBuilder::new("/no/such/pkcs11/module.so"),
+            Err(Error::NoStorageAccess(_))
+        ));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        // needs a token, such as SoftHSM's, whose module and user PIN are in the environment
+        let module = std::env::var("PKCS11_MODULE").unwrap();
+        let pin = std::env::var("PKCS11_PIN").ok();
+        let builder = Pkcs11CredentialBuilder::new(module)
+            .unwrap()
+            .with_pin(pin.as_deref());
+        let service = generate_random_string();
+        let entry = Entry::new_with_credential(builder.build(None, &service, "user").unwrap());
+        assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+        entry.set_password("pkcs11 password").unwrap();
+        assert_eq!(entry.get_password().unwrap(), "pkcs11 password");
+        entry.set_secret(&[0xff, 0]).unwrap();
+        assert_eq!(entry.get_secret().unwrap(), [0xff, 0]);
+        entry.delete_credential().unwrap();
+        assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+    }
+}
@@ -0,0 +1,239 @@
+/*!
+
+# Secret portal credential store
+
+Inside Flatpak (and some snap) sandboxes, the Secret Service usually
+can't be reached; instead, the sandbox exposes the
+[Secret portal](https://flatpak.github.io/xdg-desktop-portal/docs/doc-org.freedesktop.portal.Secret.html),
+which gives each app a _master secret_ of its own (kept by the desktop's
+keyring on the app's behalf). This module retrieves that secret and uses it
+(by way of [derive_key](crate::crypto::derive_key)) as the key of an
+[encrypted file store](crate::file_store) inside the sandbox, which is what
+libsecret does for sandboxed apps. It's available when the `portal` feature
+is enabled. It's not the default store on any platform, so clients have to
+set it as their default (or build their credentials from it directly):
+```no_run
+keyring::set_default_credential_builder(keyring::portal::default_credential_builder());
+```
+
+By default, the file is `keyrings/keyring-rs.keyring` in the app's data
+directory (`$XDG_DATA_HOME`, which the sandbox makes private to the app);
+[PortalCredentialBuilder::new] uses another file. The master secret is
+retrieved (which may prompt the user) when the builder first builds or
+searches for credentials, and is then kept for the life of the builder.
+If there is no portal, or it doesn't provide a secret, a
+[NoStorageAccess](crate::Error::NoStorageAccess) error is returned.
+
+Credentials behave just like those of the [file_store](crate::file_store),
+so this store supports [search](crate::search), attributes, and labels.
+ */
+use std::collections::HashMap;
+use std::io::Read;
+use std::os::unix::io::IntoRawFd;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use dbus::arg::{OwnedFd, PropMap};
+use dbus::blocking::Connection;
+use zeroize::Zeroizing;
+
+use super::credential::{
+    AtRestStatus, Credential, CredentialBuilder, CredentialBuilderApi, CredentialPersistence,
+    CredentialStoreInfo, SearchSpec,
+};
+use super::crypto::derive_key;
+use super::error::{Error as ErrorCode, Result};
+use super::file_store::FileCredentialBuilder;
+
+/// The bus name and object path of the desktop portal.
+const PORTAL: (&str, &str) = (
+    "org.freedesktop.portal.Desktop",
+    "/org/freedesktop/portal/desktop",
+);
+
+/// The DBus interface of the Secret portal.
+const INTERFACE: &str = "org.freedesktop.portal.Secret";
+
+/// How long to wait for the portal, which may be prompting the user.
+const TIMEOUT: Duration = Duration::from_secs(120);
+
+/// The builder for credentials kept in a file encrypted with the portal's secret.
+pub struct PortalCredentialBuilder {
+    path: PathBuf,
+    store: Mutex<Option<FileCredentialBuilder>>,
+}
+
+/// Returns an instance of the Secret portal credential builder,
+/// which uses the default file.
+///
+/// This is never called by the crate, since the portal is
+/// not the default store on any platform.
+pub fn default_credential_builder() -> Box<CredentialBuilder> {
+    Box::new(PortalCredentialBuilder::new(default_path()))
+}
+
+impl PortalCredentialBuilder {
+    /// Create a builder for the store in the file at `path`.
+    ///
+    /// The master secret isn't retrieved until a credential is built, so this can't fail.
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            store: Mutex::new(None),
+        }
+    }
+
+    /// The path of this builder's file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The file store for this builder's file, opened with the portal's secret.
+    fn store(&self) -> Result<FileCredentialBuilder> {
+        let mut store = self.store.lock().expect("Portal store lock poisoned");
+        if store.is_none() {
+            let secret = retrieve_secret()?;
+            let key = derive_key(&secret, b"", b"keyring-rs portal file store");
+            *store = Some(FileCredentialBuilder::new(&self.path, &key));
+        }
+        Ok(store.as_ref().unwrap().clone())
+    }
+}
+
+impl std::fmt::Debug for PortalCredentialBuilder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PortalCredentialBuilder")
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CredentialBuilderApi for PortalCredentialBuilder {
+    /// Build a file store credential for the given target, service, and user.
+    ///
+    /// The first call retrieves the portal's secret.
+    fn build(&self, target: Option<&str>, service: &str, user: &str) -> Result<Box<Credential>> {
+        self.store()?.build(target, service, user)
+    }
+
+    /// The file is encrypted.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        AtRestStatus::Encrypted
+    }
+
+    /// Return the underlying builder object with an `Any` type so that it can
+    /// be downgraded to a [PortalCredentialBuilder] for platform-specific processing.
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    /// The file keeps credentials until they are deleted.
+    fn persistence(&self) -> CredentialPersistence {
+        CredentialPersistence::UntilDelete
+    }
+
+    /// Return a credential for each stored credential that matches the spec.
+    fn search(&self, spec: &SearchSpec) -> Result<Vec<Box<Credential>>> {
+        self.store()?.search(spec)
+    }
+
+    /// The portal's store is a file store.
+    fn info(&self) -> CredentialStoreInfo {
+        CredentialStoreInfo::new("portal", self.persistence(), self.at_rest_encryption())
+            .with_attributes(true)
+            .with_search(true)
+    }
+}
+
+/// Retrieve this app's master secret from the Secret portal.
+///
+/// This may prompt the user. Returns a [NoStorageAccess](ErrorCode::NoStorageAccess)
+/// error if there is no portal or it doesn't provide a secret.
+pub fn retrieve_secret() -> Result<Zeroizing<Vec<u8>>> {
+    let connection = Connection::new_session().map_err(no_access)?;
+    let (mut reader, writer) = UnixStream::pair().map_err(io_failure)?;
+    // the portal writes the secret to its end of the socket and then closes it
+    let fd = unsafe { OwnedFd::new(writer.into_raw_fd()) };
+    let options: PropMap = HashMap::new();
+    let _: (dbus::Path,) = connection
+        .with_proxy(PORTAL.0, PORTAL.1, TIMEOUT)
+        .method_call(INTERFACE, "RetrieveSecret", (fd, options))
+        .map_err(decode_error)?;
+    reader.set_read_timeout(Some(TIMEOUT)).map_err(io_failure)?;
+    let mut secret = Zeroizing::new(Vec::new());
+    reader.read_to_end(&mut secret).map_err(io_failure)?;
+    if secret.is_empty() {
+        return Err(ErrorCode::NoStorageAccess(Box::from(
+            "the Secret portal didn't provide a secret",
+        )));
+    }
+    Ok(secret)
+}
+
+/// The default file: `keyrings/keyring-rs.keyring` in the user's data directory.
+fn default_path() -> PathBuf {
+    let data_home = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+        .unwrap_or_default();
+    data_home.join("keyrings").join("keyring-rs.keyring")
+}
+
+/// Map DBus errors to crate errors.
+///
+/// A missing portal (or one without the Secret interface) means
+/// there's no access to the store.
+fn decode_error(err: dbus::Error) -> ErrorCode {
+    match err.name() {
+        Some("org.freedesktop.DBus.Error.ServiceUnknown")
+        | Some("org.freedesktop.DBus.Error.UnknownMethod")
+        | Some("org.freedesktop.DBus.Error.UnknownInterface")
+        | Some("org.freedesktop.DBus.Error.AccessDenied") => no_access(err),
+        _ => ErrorCode::PlatformFailure(Box::new(err)),
+    }
+}
+
+fn no_access(err: dbus::Error) -> ErrorCode {
+    ErrorCode::NoStorageAccess(Box::new(err))
+}
+
+fn io_failure(err: std::io::Error) -> ErrorCode {
+    ErrorCode::PlatformFailure(Box::new(err))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::credential::{CredentialBuilderApi, CredentialPersistence};
+    use crate::{Entry, Error, tests::generate_random_string};
+
+    use super::{PortalCredentialBuilder, default_credential_builder};
+
+    #[test]
+    fn test_persistence() {
+        assert!(matches!(
+            default_credential_builder().persistence(),
+            CredentialPersistence::UntilDelete
+        ))
+    }
+
+    #[test]
+    fn test_default_path() {
+        let builder = default_credential_builder();
+        let builder: &PortalCredentialBuilder = builder.as_any().downcast_ref().unwrap();
+        assert!(builder.path().ends_with("keyrings/keyring-rs.keyring"));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let path = std::env::temp_dir().join(generate_random_string());
+        let builder = PortalCredentialBuilder::new(&path);
+        let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+        assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+        entry.set_password("portal password").unwrap();
+        assert_eq!(entry.get_password().unwrap(), "portal password");
+        entry.delete_credential().unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+}
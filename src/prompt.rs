@@ -0,0 +1,189 @@
+/*!
+
+# Prompt handlers
+
+Some credential stores interact with the user: the Secret Service, for
+example, shows a dialog asking for the login password when it unlocks a
+locked collection. Apps that want to know when that happens (to show a
+status message, say) can register a [PromptHandler] with
+[set_prompt_handler]. Stores tell the handler when an interaction is
+required, and then when it has started, completed, or been denied.
+
+The handler can also refuse an interaction before it starts, by returning
+`false` from [interaction_required](PromptHandler::interaction_required).
+The operation then fails with a [Locked](crate::Error::Locked) error
+instead of waiting on a prompt that nobody will see, which is what headless
+services want.
+
+```
+# use keyring::prompt::{Prompt, PromptHandler, set_prompt_handler, clear_prompt_handler};
+#[derive(Debug)]
+struct Headless;
+
+impl PromptHandler for Headless {
+    fn interaction_required(&self, prompt: &Prompt) -> bool {
+        eprintln!("refusing to prompt: {prompt}");
+        false
+    }
+}
+
+set_prompt_handler(Box::new(Headless));
+// ... operations that would prompt now fail with a `Locked` error
+# clear_prompt_handler();
+```
+
+Where a store can take a password in place of a prompt, it asks the handler
+for one with [password](PromptHandler::password) before prompting.
+
+The built-in Secret Service store reports its unlock prompts and the
+creation of new collections (none of the built-in stores can take a
+password yet). Other stores can report their interactions by running
+them with [interact], and ask for passwords with [password].
+ */
+use std::sync::{Arc, RwLock};
+
+use zeroize::Zeroizing;
+
+use super::error::{Error, Result};
+
+/// The kind of interaction a store needs from the user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PromptKind {
+    /// Unlocking a locked collection or item.
+    Unlock,
+    /// Creating a new (password-protected) collection.
+    CreateCollection,
+    /// Authenticating the user, e.g. with a biometric check.
+    Authenticate,
+}
+
+/// An interaction that a credential store needs from the user.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Prompt {
+    /// What the interaction is for.
+    pub kind: PromptKind,
+    /// The name of the store that needs it, e.g. `secret-service`.
+    pub store: &'static str,
+    /// What the interaction is about, e.g. the label of a collection.
+    pub subject: String,
+}
+
+impl Prompt {
+    pub fn new(kind: PromptKind, store: &'static str, subject: &str) -> Self {
+        Self {
+            kind,
+            store,
+            subject: subject.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for Prompt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self.kind {
+            PromptKind::Unlock => "unlock",
+            PromptKind::CreateCollection => "create collection",
+            PromptKind::Authenticate => "authenticate",
+        };
+        write!(f, "{} {kind} '{}'", self.store, self.subject)
+    }
+}
+
+/// A client callback that's told about (and can refuse) user interactions.
+///
+/// All methods have defaults, which allow every interaction and supply
+/// no passwords, so handlers need only implement the ones they care about.
+/// Handlers are called on the thread running the operation.
+pub trait PromptHandler: Send + Sync + std::fmt::Debug {
+    /// Called when a store needs to interact with the user.
+    ///
+    /// Return `false` to refuse the interaction, in which case the
+    /// operation fails with a [Locked](Error::Locked) error.
+    fn interaction_required(&self, prompt: &Prompt) -> bool {
+        let _ = prompt;
+        true
+    }
+
+    /// Called when the interaction starts.
+    fn interaction_started(&self, prompt: &Prompt) {
+        let _ = prompt;
+    }
+
+    /// Called when the interaction completed successfully.
+    fn interaction_completed(&self, prompt: &Prompt) {
+        let _ = prompt;
+    }
+
+    /// Called when the interaction was refused, by this handler or by the user,
+    /// or failed.
+    fn interaction_denied(&self, prompt: &Prompt) {
+        let _ = prompt;
+    }
+
+    /// Supply the password for an interaction, in place of prompting the user.
+    ///
+    /// This is only asked of stores that can take a password programmatically.
+    fn password(&self, prompt: &Prompt) -> Option<Zeroizing<String>> {
+        let _ = prompt;
+        None
+    }
+}
+
+static PROMPT_HANDLER: RwLock<Option<Arc<dyn PromptHandler>>> = RwLock::new(None);
+
+/// Register the prompt handler for this process, replacing any previous one.
+pub fn set_prompt_handler(handler: Box<dyn PromptHandler>) {
+    let mut guard = PROMPT_HANDLER
+        .write()
+        .expect("Poisoned RwLock in keyring-rs: please report a bug!");
+    *guard = Some(Arc::from(handler));
+}
+
+/// Remove the registered prompt handler, if any.
+pub fn clear_prompt_handler() {
+    let mut guard = PROMPT_HANDLER
+        .write()
+        .expect("Poisoned RwLock in keyring-rs: please report a bug!");
+    *guard = None;
+}
+
+/// Run an operation that interacts with the user, reporting it to the
+/// registered prompt handler.
+///
+/// If the handler refuses the interaction, the operation isn't run and
+/// a [Locked](Error::Locked) error is returned. Otherwise the handler is
+/// told the interaction has started, and then (depending on the operation's
+/// result) that it has completed or been denied. With no handler registered,
+/// the operation is just run.
+///
+/// The handler that's registered when the interaction is required gets
+/// all of its reports, even if it's replaced while the operation runs.
+pub fn interact<T>(prompt: &Prompt, op: impl FnOnce() -> Result<T>) -> Result<T> {
+    let Some(handler) = current_handler() else {
+        return op();
+    };
+    if !handler.interaction_required(prompt) {
+        handler.interaction_denied(prompt);
+        return Err(Error::Locked);
+    }
+    handler.interaction_started(prompt);
+    let result = op();
+    match &result {
+        Ok(_) => handler.interaction_completed(prompt),
+        Err(_) => handler.interaction_denied(prompt),
+    }
+    result
+}
+
+/// Ask the registered prompt handler for the password for an interaction.
+pub fn password(prompt: &Prompt) -> Option<Zeroizing<String>> {
+    current_handler().and_then(|handler| handler.password(prompt))
+}
+
+fn current_handler() -> Option<Arc<dyn PromptHandler>> {
+    PROMPT_HANDLER
+        .read()
+        .expect("Poisoned RwLock in keyring-rs: please report a bug!")
+        .clone()
+}
@@ -0,0 +1,221 @@
+/*!
+
+# Read-only credential builder
+
+Some apps (such as audit tools, or services deployed to production with
+their credentials provisioned by someone else) should read credentials but
+never change them. The [ReadOnlyCredentialBuilder] in this module wraps
+another builder, and the credentials it builds refuse every change:
+setting a password or secret, changing attributes, labels, or expiration,
+and deleting the credential all return a [ReadOnly](Error::ReadOnly) error
+without touching the wrapped store.
+
+```
+# use keyring::{Entry, Error, credential::CredentialBuilderApi};
+# use keyring::{memory::MemoryCredentialBuilder, read_only::ReadOnlyCredentialBuilder};
+let store = MemoryCredentialBuilder::default();
+store.build(None, "service", "user").unwrap().set_password("provisioned").unwrap();
+let builder = ReadOnlyCredentialBuilder::new(Box::new(store));
+let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+assert_eq!(entry.get_password().unwrap(), "provisioned");
+assert!(matches!(entry.set_password("changed"), Err(Error::ReadOnly)));
+assert!(matches!(entry.delete_credential(), Err(Error::ReadOnly)));
+```
+
+Unlocking is allowed, since stores may need to be unlocked to be read.
+Credentials returned by a search through a read-only builder are read-only, too.
+ */
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::time::Duration;
+
+use super::credential::{
+    AtRestStatus, AttributeMap, Credential, CredentialApi, CredentialBuilder, CredentialBuilderApi,
+    CredentialPersistence, CredentialSpec, CredentialStoreInfo, SearchSpec,
+};
+use super::error::{Error, Result};
+
+/// A credential builder whose credentials can't be changed.
+#[derive(Debug)]
+pub struct ReadOnlyCredentialBuilder {
+    inner: Box<CredentialBuilder>,
+}
+
+impl ReadOnlyCredentialBuilder {
+    /// Create a builder that makes the credentials built by `inner` read-only.
+    pub fn new(inner: Box<CredentialBuilder>) -> Self {
+        Self { inner }
+    }
+
+    /// The wrapped builder.
+    pub fn inner(&self) -> &CredentialBuilder {
+        self.inner.as_ref()
+    }
+}
+
+impl CredentialBuilderApi for ReadOnlyCredentialBuilder {
+    /// Build a credential in the wrapped store, and make it read-only.
+    fn build(&self, target: Option<&str>, service: &str, user: &str) -> Result<Box<Credential>> {
+        let inner = self.inner.build(target, service, user)?;
+        Ok(Box::new(ReadOnlyCredential::new(inner)))
+    }
+
+    /// The at-rest encryption of the wrapped store.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        self.inner.at_rest_encryption()
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+
+    /// The persistence of the wrapped store.
+    fn persistence(&self) -> CredentialPersistence {
+        self.inner.persistence()
+    }
+
+    /// Search the wrapped store, and make the found credentials read-only.
+    fn search(&self, spec: &SearchSpec) -> Result<Vec<Box<Credential>>> {
+        let found = self.inner.search(spec)?;
+        Ok(found
+            .into_iter()
+            .map(|cred| Box::new(ReadOnlyCredential::new(cred)) as Box<Credential>)
+            .collect())
+    }
+
+    /// The info of the wrapped store.
+    fn info(&self) -> CredentialStoreInfo {
+        self.inner.info()
+    }
+}
+
+/// A credential that can be read, but not changed.
+#[derive(Debug)]
+pub struct ReadOnlyCredential {
+    inner: Box<Credential>,
+}
+
+impl ReadOnlyCredential {
+    /// Make a credential read-only.
+    pub fn new(inner: Box<Credential>) -> Self {
+        Self { inner }
+    }
+
+    /// The wrapped credential.
+    pub fn inner(&self) -> &Credential {
+        self.inner.as_ref()
+    }
+}
+
+impl CredentialApi for ReadOnlyCredential {
+    fn set_password(&self, _: &str) -> Result<()> {
+        Err(Error::ReadOnly)
+    }
+
+    fn set_secret(&self, _: &[u8]) -> Result<()> {
+        Err(Error::ReadOnly)
+    }
+
+    fn get_password(&self) -> Result<String> {
+        self.inner.get_password()
+    }
+
+    fn get_secret(&self) -> Result<Vec<u8>> {
+        self.inner.get_secret()
+    }
+
+    fn compare_and_set_password(&self, _: &str, _: &str) -> Result<()> {
+        Err(Error::ReadOnly)
+    }
+
+    fn compare_and_set_secret(&self, _: &[u8], _: &[u8]) -> Result<()> {
+        Err(Error::ReadOnly)
+    }
+
+    fn exists(&self) -> Result<bool> {
+        self.inner.exists()
+    }
+
+    fn get_attributes(&self) -> Result<AttributeMap<String, String>> {
+        self.inner.get_attributes()
+    }
+
+    fn update_attributes(&self, _: &AttributeMap<&str, &str>) -> Result<()> {
+        Err(Error::ReadOnly)
+    }
+
+    fn get_label(&self) -> Result<String> {
+        self.inner.get_label()
+    }
+
+    fn set_label(&self, _: &str) -> Result<()> {
+        Err(Error::ReadOnly)
+    }
+
+    fn set_expiration(&self, _: Duration) -> Result<()> {
+        Err(Error::ReadOnly)
+    }
+
+    fn unlock(&self) -> Result<()> {
+        self.inner.unlock()
+    }
+
+    fn max_secret_len(&self) -> Option<usize> {
+        self.inner.max_secret_len()
+    }
+
+    fn spec(&self) -> Option<CredentialSpec> {
+        self.inner.spec()
+    }
+
+    fn delete_credential(&self) -> Result<()> {
+        Err(Error::ReadOnly)
+    }
+
+    /// The at-rest encryption of the wrapped credential.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        self.inner.at_rest_encryption()
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+
+    fn debug_fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Debug::fmt(self, f)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::ReadOnlyCredentialBuilder;
+    use crate::credential::{AttributeMap, CredentialBuilderApi, SearchSpec};
+    use crate::memory::MemoryCredentialBuilder;
+    use crate::{Entry, Error};
+
+    #[test]
+    fn test_read_only() {
+        let store = MemoryCredentialBuilder::default();
+        let raw = Entry::new_with_credential(store.build(None, "service", "user").unwrap());
+        let builder = ReadOnlyCredentialBuilder::new(Box::new(store));
+        let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+        assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+        assert!(matches!(entry.set_secret(b"secret"), Err(Error::ReadOnly)));
+        raw.set_secret(b"secret").unwrap();
+        assert_eq!(entry.get_secret().unwrap(), b"secret");
+        assert!(matches!(
+            entry.update_secret(b"secret", b"changed"),
+            Err(Error::ReadOnly)
+        ));
+        assert!(matches!(
+            entry.update_attributes(&AttributeMap::new()),
+            Err(Error::ReadOnly)
+        ));
+        assert!(matches!(entry.delete_credential(), Err(Error::ReadOnly)));
+        let found = builder.search(&SearchSpec::default()).unwrap();
+        assert_eq!(found.len(), 1);
+        assert!(matches!(found[0].delete_credential(), Err(Error::ReadOnly)));
+        assert_eq!(raw.get_secret().unwrap(), b"secret");
+    }
+}
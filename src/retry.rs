@@ -0,0 +1,403 @@
+/*!
+
+# Retrying transient failures
+
+Most credential stores occasionally fail in ways that go away if the
+operation is tried again: a DBus connection drops, two processes race
+to change the same Windows credential, or the macOS keychain refuses
+access while it's being unlocked. The [RetryingCredentialBuilder] in this
+module wraps another builder, and retries the operations on the credentials
+it builds that fail with a transient error, waiting longer (exponentially)
+between each try, as configured by a [RetryPolicy].
+
+```
+# use std::time::Duration;
+# use keyring::{Entry, credential::CredentialBuilderApi, memory::MemoryCredentialBuilder};
+# use keyring::retry::{RetryPolicy, RetryingCredentialBuilder};
+let policy = RetryPolicy::default()
+    .with_max_attempts(5)
+    .with_initial_backoff(Duration::from_millis(20));
+let builder = RetryingCredentialBuilder::new(Box::new(MemoryCredentialBuilder::default()), policy);
+let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+entry.set_password("retried if need be").unwrap();
+```
+
+By default, the errors that are retried are [PlatformFailure](crate::Error::PlatformFailure)
+errors (which is how dropped connections are reported) and
+[Timeout](crate::Error::Timeout) errors. Errors that can mean the user
+has refused a prompt, such as [NoStorageAccess](crate::Error::NoStorageAccess),
+aren't retried by default, because retrying them would prompt again.
+Apps that know better (say, that run on macOS without ever prompting)
+can choose which errors are retried with [RetryPolicy::with_retry_if].
+
+Operations that change the store are retried too, so an app that
+uses [update_password](crate::Entry::update_password) may get
+a [Conflict](crate::Error::Conflict) error from a retry when the first
+try did take effect but reported an error. Each retry is logged at the
+`debug` level. Searches through a retrying builder are retried too, but
+the credentials they return are those of the wrapped store.
+ */
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::debug;
+
+use super::credential::{
+    AtRestStatus, AttributeMap, Credential, CredentialApi, CredentialBuilder, CredentialBuilderApi,
+    CredentialMetadata, CredentialPersistence, CredentialSpec, CredentialStoreInfo, SearchSpec,
+};
+use super::error::{Error, Result};
+
+/// How many times, how often, and on which errors operations are retried.
+#[derive(Clone)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    multiplier: u32,
+    retry_if: Arc<dyn Fn(&Error) -> bool + Send + Sync>,
+}
+
+impl Default for RetryPolicy {
+    /// Try each operation at most 3 times, waiting 50ms after the first try
+    /// and twice as long after each later try (but never more than 1s),
+    /// retrying the errors for which [is_transient] is true.
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(1),
+            multiplier: 2,
+            retry_if: Arc::new(is_transient),
+        }
+    }
+}
+
+impl core::fmt::Debug for RetryPolicy {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("RetryPolicy")
+            .field("max_attempts", &self.max_attempts)
+            .field("initial_backoff", &self.initial_backoff)
+            .field("max_backoff", &self.max_backoff)
+            .field("multiplier", &self.multiplier)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RetryPolicy {
+    /// Return this policy with the most times an operation is tried
+    /// (including the first). A policy that tries just once never retries.
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Return this policy with the time to wait after the first try.
+    pub fn with_initial_backoff(mut self, backoff: Duration) -> Self {
+        self.initial_backoff = backoff;
+        self
+    }
+
+    /// Return this policy with the longest time to wait between tries.
+    pub fn with_max_backoff(mut self, backoff: Duration) -> Self {
+        self.max_backoff = backoff;
+        self
+    }
+
+    /// Return this policy with the factor by which the wait grows after each try.
+    pub fn with_multiplier(mut self, multiplier: u32) -> Self {
+        self.multiplier = multiplier;
+        self
+    }
+
+    /// Return this policy retrying the errors for which `retry_if` is true.
+    pub fn with_retry_if(
+        mut self,
+        retry_if: impl Fn(&Error) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.retry_if = Arc::new(retry_if);
+        self
+    }
+
+    /// The most times an operation is tried.
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// How long to wait after the given (1-based) try before the next one.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = self.multiplier.saturating_pow(attempt.saturating_sub(1));
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+
+    /// Run an operation, retrying it as this policy says.
+    pub fn run<T>(&self, op: &str, mut f: impl FnMut() -> Result<T>) -> Result<T> {
+        let mut attempt = 1;
+        loop {
+            match f() {
+                Err(err) if attempt < self.max_attempts && (self.retry_if)(&err) => {
+                    let backoff = self.backoff(attempt);
+                    debug!("retrying {op} in {backoff:?} after try {attempt} failed: {err}");
+                    std::thread::sleep(backoff);
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Whether an error is one that's retried by default: a
+/// [PlatformFailure](Error::PlatformFailure) or a [Timeout](Error::Timeout).
+pub fn is_transient(err: &Error) -> bool {
+    matches!(err, Error::PlatformFailure(_) | Error::Timeout)
+}
+
+/// A credential builder whose credentials retry operations
+/// that fail with transient errors.
+#[derive(Debug)]
+pub struct RetryingCredentialBuilder {
+    inner: Box<CredentialBuilder>,
+    policy: RetryPolicy,
+}
+
+impl RetryingCredentialBuilder {
+    /// Create a builder that retries the operations on the credentials
+    /// built by `inner` (and its searches) according to `policy`.
+    pub fn new(inner: Box<CredentialBuilder>, policy: RetryPolicy) -> Self {
+        Self { inner, policy }
+    }
+
+    /// The wrapped builder.
+    pub fn inner(&self) -> &CredentialBuilder {
+        self.inner.as_ref()
+    }
+
+    /// The policy of this builder's credentials.
+    pub fn policy(&self) -> &RetryPolicy {
+        &self.policy
+    }
+}
+
+impl CredentialBuilderApi for RetryingCredentialBuilder {
+    /// Build a credential in the wrapped store, and retry its operations.
+    fn build(&self, target: Option<&str>, service: &str, user: &str) -> Result<Box<Credential>> {
+        let inner = self.inner.build(target, service, user)?;
+        Ok(Box::new(RetryingCredential::new(
+            inner,
+            self.policy.clone(),
+        )))
+    }
+
+    /// The at-rest encryption of the wrapped store.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        self.inner.at_rest_encryption()
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    /// The persistence of the wrapped store.
+    fn persistence(&self) -> CredentialPersistence {
+        self.inner.persistence()
+    }
+
+    /// Search the wrapped store, retrying transient failures.
+    fn search(&self, spec: &SearchSpec) -> Result<Vec<Box<Credential>>> {
+        self.policy.run("search", || self.inner.search(spec))
+    }
+
+    /// The info of the wrapped store.
+    fn info(&self) -> CredentialStoreInfo {
+        self.inner.info()
+    }
+}
+
+/// A credential that retries operations that fail with transient errors.
+#[derive(Debug)]
+pub struct RetryingCredential {
+    inner: Box<Credential>,
+    policy: RetryPolicy,
+}
+
+impl RetryingCredential {
+    /// Wrap a credential, retrying its operations according to `policy`.
+    pub fn new(inner: Box<Credential>, policy: RetryPolicy) -> Self {
+        Self { inner, policy }
+    }
+
+    /// The wrapped credential.
+    pub fn inner(&self) -> &Credential {
+        self.inner.as_ref()
+    }
+}
+
+impl CredentialApi for RetryingCredential {
+    fn set_password(&self, password: &str) -> Result<()> {
+        self.policy
+            .run("set_password", || self.inner.set_password(password))
+    }
+
+    fn set_secret(&self, secret: &[u8]) -> Result<()> {
+        self.policy
+            .run("set_secret", || self.inner.set_secret(secret))
+    }
+
+    fn get_password(&self) -> Result<String> {
+        self.policy
+            .run("get_password", || self.inner.get_password())
+    }
+
+    fn get_secret(&self) -> Result<Vec<u8>> {
+        self.policy.run("get_secret", || self.inner.get_secret())
+    }
+
+    fn exists(&self) -> Result<bool> {
+        self.policy.run("exists", || self.inner.exists())
+    }
+
+    fn get_password_or_set(&self, password: &str) -> Result<String> {
+        self.policy.run("get_password_or_set", || {
+            self.inner.get_password_or_set(password)
+        })
+    }
+
+    fn get_secret_or_set(&self, secret: &[u8]) -> Result<Vec<u8>> {
+        self.policy
+            .run("get_secret_or_set", || self.inner.get_secret_or_set(secret))
+    }
+
+    fn compare_and_set_password(&self, expected: &str, password: &str) -> Result<()> {
+        self.policy.run("compare_and_set_password", || {
+            self.inner.compare_and_set_password(expected, password)
+        })
+    }
+
+    fn compare_and_set_secret(&self, expected: &[u8], secret: &[u8]) -> Result<()> {
+        self.policy.run("compare_and_set_secret", || {
+            self.inner.compare_and_set_secret(expected, secret)
+        })
+    }
+
+    fn get_attributes(&self) -> Result<AttributeMap<String, String>> {
+        self.policy
+            .run("get_attributes", || self.inner.get_attributes())
+    }
+
+    fn update_attributes(&self, attributes: &AttributeMap<&str, &str>) -> Result<()> {
+        self.policy.run("update_attributes", || {
+            self.inner.update_attributes(attributes)
+        })
+    }
+
+    fn get_metadata(&self) -> Result<CredentialMetadata> {
+        self.policy
+            .run("get_metadata", || self.inner.get_metadata())
+    }
+
+    fn get_label(&self) -> Result<String> {
+        self.policy.run("get_label", || self.inner.get_label())
+    }
+
+    fn set_label(&self, label: &str) -> Result<()> {
+        self.policy.run("set_label", || self.inner.set_label(label))
+    }
+
+    fn set_expiration(&self, ttl: Duration) -> Result<()> {
+        self.policy
+            .run("set_expiration", || self.inner.set_expiration(ttl))
+    }
+
+    fn unlock(&self) -> Result<()> {
+        self.policy.run("unlock", || self.inner.unlock())
+    }
+
+    fn max_secret_len(&self) -> Option<usize> {
+        self.inner.max_secret_len()
+    }
+
+    fn spec(&self) -> Option<CredentialSpec> {
+        self.inner.spec()
+    }
+
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        self.inner.at_rest_encryption()
+    }
+
+    fn delete_credential(&self) -> Result<()> {
+        self.policy
+            .run("delete_credential", || self.inner.delete_credential())
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{RetryPolicy, RetryingCredentialBuilder};
+    use crate::credential::CredentialBuilderApi;
+    use crate::mock::{MockCredential, MockCredentialBuilder};
+    use crate::{Entry, Error};
+
+    fn failure() -> Option<Error> {
+        Some(Error::PlatformFailure("connection dropped".into()))
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy::default()
+            .with_initial_backoff(Duration::from_millis(10))
+            .with_max_backoff(Duration::from_millis(50));
+        let backoffs: Vec<_> = (1..=4).map(|attempt| policy.backoff(attempt)).collect();
+        assert_eq!(
+            backoffs,
+            [10, 20, 40, 50].map(Duration::from_millis).to_vec()
+        );
+    }
+
+    #[test]
+    fn test_retry() {
+        let policy = RetryPolicy::default().with_initial_backoff(Duration::from_millis(1));
+        let builder =
+            RetryingCredentialBuilder::new(Box::new(MockCredentialBuilder::default()), policy);
+        let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+        let retrying: &super::RetryingCredential = entry.get_credential().downcast_ref().unwrap();
+        let mock: &MockCredential = retrying.inner().as_any().downcast_ref().unwrap();
+        mock.set_outcomes([failure(), failure()]);
+        entry.set_password("third time").unwrap();
+        assert_eq!(entry.get_password().unwrap(), "third time");
+        mock.set_outcomes([failure(), failure(), failure()]);
+        assert!(matches!(
+            entry.get_password(),
+            Err(Error::PlatformFailure(_))
+        ));
+        // errors that aren't transient aren't retried
+        mock.set_outcomes([Some(Error::NoStorageAccess("dismissed".into())), None]);
+        assert!(matches!(
+            entry.get_password(),
+            Err(Error::NoStorageAccess(_))
+        ));
+        let policy = RetryPolicy::default()
+            .with_initial_backoff(Duration::from_millis(1))
+            .with_retry_if(|err| matches!(err, Error::NoStorageAccess(_)));
+        let builder =
+            RetryingCredentialBuilder::new(Box::new(MockCredentialBuilder::default()), policy);
+        let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+        let retrying: &super::RetryingCredential = entry.get_credential().downcast_ref().unwrap();
+        let mock: &MockCredential = retrying.inner().as_any().downcast_ref().unwrap();
+        mock.set_outcomes([Some(Error::NoStorageAccess("locked".into()))]);
+        entry.set_password("unlocked").unwrap();
+        mock.set_outcomes([failure()]);
+        assert!(matches!(
+            entry.get_password(),
+            Err(Error::PlatformFailure(_))
+        ));
+    }
+}
@@ -0,0 +1,402 @@
+/*!
+
+# Secret rotation
+
+Services that roll an API key (or any other shared secret) usually can't
+switch every client over at the same moment, so for a while they have to
+accept both the new key and the one it replaced. The
+[RotatingCredentialBuilder] in this module wraps another builder, and the
+credentials it builds can [rotate](RotatingCredential::rotate_secret) their
+secret: the new secret becomes the credential's secret, and the old one stays
+readable (with [get_previous_secret](RotatingCredential::get_previous_secret))
+for a grace period. Entries for rotating credentials have the same methods,
+[Entry::rotate_secret] and [Entry::get_previous_secret].
+
+```
+# use std::time::Duration;
+# use keyring::{Entry, credential::CredentialBuilderApi, memory::MemoryCredentialBuilder};
+# use keyring::rotation::RotatingCredentialBuilder;
+let store = MemoryCredentialBuilder::default();
+let builder = RotatingCredentialBuilder::new(Box::new(store), Duration::from_secs(3600));
+let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+entry.set_password("first key").unwrap();
+entry.rotate_secret(b"second key").unwrap();
+assert_eq!(entry.get_password().unwrap(), "second key");
+assert_eq!(entry.get_previous_secret().unwrap(), b"first key");
+```
+
+The previous secret is kept in a sibling credential in the wrapped store,
+which has the same target and user, and whose service is the credential's
+service followed by [PREVIOUS_SUFFIX]. (The sibling is told apart by its
+service, rather than its target, because in several stores the target
+picks the collection or keychain that the credential is kept in.) The sibling's
+secret is the time its grace period ends (as a big-endian count of seconds
+since the Unix epoch), followed by the previous secret, so this works with
+any store. Once the grace period has ended, reading the previous secret
+deletes the sibling and returns a [NoEntry](Error::NoEntry) error.
+
+Setting a secret without rotating it leaves any previous secret alone,
+and deleting a rotating credential deletes its previous secret, too.
+Searches through a rotating builder don't return the siblings, and the
+credentials they return can rotate their secrets.
+ */
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use zeroize::Zeroizing;
+
+use super::Entry;
+use super::credential::{
+    AtRestStatus, AttributeMap, Credential, CredentialApi, CredentialBuilder, CredentialBuilderApi,
+    CredentialMetadata, CredentialPersistence, CredentialSpec, CredentialStoreInfo, SearchSpec,
+};
+use super::error::{Error, Result};
+
+/// The suffix added to a credential's service to make the service of
+/// the sibling credential that holds its previous secret.
+pub const PREVIOUS_SUFFIX: &str = ".keyring-previous";
+
+/// A credential builder whose credentials can rotate their secrets.
+#[derive(Debug)]
+pub struct RotatingCredentialBuilder {
+    inner: Arc<CredentialBuilder>,
+    grace_period: Duration,
+}
+
+impl RotatingCredentialBuilder {
+    /// Create a builder whose credentials are built by `inner`, and keep
+    /// their previous secrets for `grace_period` after a rotation.
+    pub fn new(inner: Box<CredentialBuilder>, grace_period: Duration) -> Self {
+        Self {
+            inner: Arc::from(inner),
+            grace_period,
+        }
+    }
+
+    /// The wrapped builder.
+    pub fn inner(&self) -> &CredentialBuilder {
+        self.inner.as_ref()
+    }
+
+    /// How long previous secrets are kept after a rotation.
+    pub fn grace_period(&self) -> Duration {
+        self.grace_period
+    }
+
+    fn wrap(&self, inner: Box<Credential>, spec: CredentialSpec) -> RotatingCredential {
+        RotatingCredential {
+            inner,
+            spec,
+            builder: self.inner.clone(),
+            grace_period: self.grace_period,
+        }
+    }
+}
+
+impl CredentialBuilderApi for RotatingCredentialBuilder {
+    /// Build a credential in the wrapped store that can rotate its secret.
+    fn build(&self, target: Option<&str>, service: &str, user: &str) -> Result<Box<Credential>> {
+        let inner = self.inner.build(target, service, user)?;
+        let spec = CredentialSpec {
+            target: target.map(str::to_string),
+            service: service.to_string(),
+            user: user.to_string(),
+        };
+        Ok(Box::new(self.wrap(inner, spec)))
+    }
+
+    /// The at-rest encryption of the wrapped store.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        self.inner.at_rest_encryption()
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    /// The persistence of the wrapped store.
+    fn persistence(&self) -> CredentialPersistence {
+        self.inner.persistence()
+    }
+
+    /// Search the wrapped store, skipping the credentials that hold previous secrets.
+    ///
+    /// Found credentials that know their identity can rotate their secrets.
+    fn search(&self, spec: &SearchSpec) -> Result<Vec<Box<Credential>>> {
+        let found = self.inner.search(spec)?;
+        Ok(found
+            .into_iter()
+            .filter_map(|cred| match cred.spec() {
+                Some(spec) if spec.service.ends_with(PREVIOUS_SUFFIX) => None,
+                Some(spec) => Some(Box::new(self.wrap(cred, spec)) as Box<Credential>),
+                None => Some(cred),
+            })
+            .collect())
+    }
+
+    /// The info of the wrapped store.
+    fn info(&self) -> CredentialStoreInfo {
+        self.inner.info()
+    }
+}
+
+/// A credential that keeps its previous secret for a while after a rotation.
+#[derive(Debug)]
+pub struct RotatingCredential {
+    inner: Box<Credential>,
+    spec: CredentialSpec,
+    builder: Arc<CredentialBuilder>,
+    grace_period: Duration,
+}
+
+impl RotatingCredential {
+    /// The wrapped credential.
+    pub fn inner(&self) -> &Credential {
+        self.inner.as_ref()
+    }
+
+    /// Replace this credential's secret, keeping the current one
+    /// as the previous secret until the grace period ends.
+    ///
+    /// If there is no current secret, the new one is just set
+    /// (and any previous secret is left alone). The previous secret
+    /// is saved before the new one is set, so a failure leaves the
+    /// current secret in place.
+    pub fn rotate_secret(&self, secret: &[u8]) -> Result<()> {
+        let current = match self.inner.get_secret() {
+            Ok(current) => Zeroizing::new(current),
+            Err(Error::NoEntry) => return self.inner.set_secret(secret),
+            Err(err) => return Err(err),
+        };
+        let deadline = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .saturating_add(self.grace_period);
+        let mut envelope = Zeroizing::new(Vec::with_capacity(8 + current.len()));
+        envelope.extend_from_slice(&deadline.as_secs().to_be_bytes());
+        envelope.extend_from_slice(&current);
+        self.previous()?.set_secret(&envelope)?;
+        self.inner.set_secret(secret)
+    }
+
+    /// Retrieve the secret that this credential had before its last
+    /// rotation, if the rotation's grace period hasn't ended.
+    ///
+    /// Returns a [NoEntry](Error::NoEntry) error if there's no previous
+    /// secret (or its grace period has ended), and an [Invalid](Error::Invalid)
+    /// error if the sibling credential that holds it is malformed.
+    pub fn get_previous_secret(&self) -> Result<Vec<u8>> {
+        let previous = self.previous()?;
+        let envelope = Zeroizing::new(previous.get_secret()?);
+        if envelope.len() < 8 {
+            return Err(Error::Invalid(
+                "previous secret".to_string(),
+                "is too short to have a deadline".to_string(),
+            ));
+        }
+        let deadline = u64::from_be_bytes(envelope[..8].try_into().unwrap());
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        if now.as_secs() >= deadline {
+            match previous.delete_credential() {
+                Ok(()) | Err(Error::NoEntry) => {}
+                Err(err) => return Err(err),
+            }
+            return Err(Error::NoEntry);
+        }
+        Ok(envelope[8..].to_vec())
+    }
+
+    /// The sibling credential that holds the previous secret.
+    fn previous(&self) -> Result<Box<Credential>> {
+        let service = format!("{}{PREVIOUS_SUFFIX}", self.spec.service);
+        self.builder
+            .build(self.spec.target.as_deref(), &service, &self.spec.user)
+    }
+}
+
+impl CredentialApi for RotatingCredential {
+    fn set_password(&self, password: &str) -> Result<()> {
+        self.inner.set_password(password)
+    }
+
+    fn set_secret(&self, secret: &[u8]) -> Result<()> {
+        self.inner.set_secret(secret)
+    }
+
+    fn get_password(&self) -> Result<String> {
+        self.inner.get_password()
+    }
+
+    fn get_secret(&self) -> Result<Vec<u8>> {
+        self.inner.get_secret()
+    }
+
+    fn compare_and_set_password(&self, expected: &str, password: &str) -> Result<()> {
+        self.inner.compare_and_set_password(expected, password)
+    }
+
+    fn compare_and_set_secret(&self, expected: &[u8], secret: &[u8]) -> Result<()> {
+        self.inner.compare_and_set_secret(expected, secret)
+    }
+
+    fn exists(&self) -> Result<bool> {
+        self.inner.exists()
+    }
+
+    fn get_attributes(&self) -> Result<AttributeMap<String, String>> {
+        self.inner.get_attributes()
+    }
+
+    fn update_attributes(&self, attributes: &AttributeMap<&str, &str>) -> Result<()> {
+        self.inner.update_attributes(attributes)
+    }
+
+    fn get_metadata(&self) -> Result<CredentialMetadata> {
+        self.inner.get_metadata()
+    }
+
+    fn get_label(&self) -> Result<String> {
+        self.inner.get_label()
+    }
+
+    fn set_label(&self, label: &str) -> Result<()> {
+        self.inner.set_label(label)
+    }
+
+    fn set_expiration(&self, ttl: Duration) -> Result<()> {
+        self.inner.set_expiration(ttl)
+    }
+
+    fn unlock(&self) -> Result<()> {
+        self.inner.unlock()
+    }
+
+    /// Secrets become previous secrets, which are stored with an 8-byte
+    /// deadline, so they must leave room for it in the wrapped store.
+    fn max_secret_len(&self) -> Option<usize> {
+        self.inner.max_secret_len().map(|len| len.saturating_sub(8))
+    }
+
+    fn spec(&self) -> Option<CredentialSpec> {
+        Some(self.spec.clone())
+    }
+
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        self.inner.at_rest_encryption()
+    }
+
+    /// Delete this credential and its previous secret (if any).
+    fn delete_credential(&self) -> Result<()> {
+        self.inner.delete_credential()?;
+        match self.previous()?.delete_credential() {
+            Ok(()) | Err(Error::NoEntry) => Ok(()),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+impl Entry {
+    /// Replace the secret of this entry's credential, keeping the current
+    /// one readable with [get_previous_secret](Entry::get_previous_secret)
+    /// until the grace period ends.
+    ///
+    /// Returns a [NotSupportedByStore](Error::NotSupportedByStore) error
+    /// if the credential wasn't built by a [RotatingCredentialBuilder].
+    /// See [RotatingCredential::rotate_secret] for the details.
+    pub fn rotate_secret(&self, secret: &[u8]) -> Result<()> {
+        self.rotating()?.rotate_secret(secret)
+    }
+
+    /// Retrieve the secret that this entry's credential had before its
+    /// last rotation, if the rotation's grace period hasn't ended.
+    ///
+    /// Returns a [NotSupportedByStore](Error::NotSupportedByStore) error
+    /// if the credential wasn't built by a [RotatingCredentialBuilder].
+    /// See [RotatingCredential::get_previous_secret] for the details.
+    pub fn get_previous_secret(&self) -> Result<Vec<u8>> {
+        self.rotating()?.get_previous_secret()
+    }
+
+    fn rotating(&self) -> Result<&RotatingCredential> {
+        self.get_credential()
+            .downcast_ref()
+            .ok_or_else(|| Error::NotSupportedByStore("secret rotation".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{PREVIOUS_SUFFIX, RotatingCredentialBuilder};
+    use crate::credential::{CredentialBuilderApi, SearchSpec};
+    use crate::memory::MemoryCredentialBuilder;
+    use crate::tests::CasOnlyCredentialBuilder;
+    use crate::{Entry, Error};
+
+    #[test]
+    fn test_rotation() {
+        let store = MemoryCredentialBuilder::default();
+        let builder =
+            RotatingCredentialBuilder::new(Box::new(store.clone()), Duration::from_secs(3600));
+        let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+        assert!(matches!(entry.get_previous_secret(), Err(Error::NoEntry)));
+        entry.rotate_secret(b"first").unwrap();
+        assert!(matches!(entry.get_previous_secret(), Err(Error::NoEntry)));
+        entry.rotate_secret(b"second").unwrap();
+        assert_eq!(entry.get_secret().unwrap(), b"second");
+        assert_eq!(entry.get_previous_secret().unwrap(), b"first");
+        entry.rotate_secret(b"third").unwrap();
+        assert_eq!(entry.get_previous_secret().unwrap(), b"second");
+        let sibling = format!("service{PREVIOUS_SUFFIX}");
+        assert!(
+            store
+                .build(None, &sibling, "user")
+                .unwrap()
+                .exists()
+                .unwrap()
+        );
+        assert_eq!(store.search(&SearchSpec::default()).unwrap().len(), 2);
+        assert_eq!(builder.search(&SearchSpec::default()).unwrap().len(), 1);
+        entry.delete_credential().unwrap();
+        assert!(matches!(entry.get_previous_secret(), Err(Error::NoEntry)));
+        assert!(store.search(&SearchSpec::default()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_grace_period() {
+        let store = MemoryCredentialBuilder::default();
+        let builder = RotatingCredentialBuilder::new(Box::new(store.clone()), Duration::ZERO);
+        let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+        entry.set_secret(b"first").unwrap();
+        entry.rotate_secret(b"second").unwrap();
+        assert!(matches!(entry.get_previous_secret(), Err(Error::NoEntry)));
+        // the expired previous secret has been cleaned up
+        assert_eq!(store.search(&SearchSpec::default()).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_not_rotating() {
+        let store = MemoryCredentialBuilder::default();
+        let entry = Entry::new_with_credential(store.build(None, "service", "user").unwrap());
+        assert!(matches!(
+            entry.rotate_secret(b"secret"),
+            Err(Error::NotSupportedByStore(_))
+        ));
+    }
+
+    #[test]
+    fn test_compare_and_set() {
+        let builder = RotatingCredentialBuilder::new(
+            Box::new(CasOnlyCredentialBuilder::default()),
+            Duration::from_secs(3600),
+        );
+        crate::tests::test_forwards_compare_and_set(&builder);
+    }
+}
@@ -22,6 +22,18 @@ by GUI tools as the name for the item. But this implementation treats the
 label as if it were any other non-controlled attribute, with the caveat that
 it will reject any attempt to set the label to an empty string.)
 
+## Custom attributes
+
+Apps that share items with libsecret-based applications often need
+the items to carry the attributes of those applications' schemas
+(such as `{"application": "myapp", "profile": "work"}`).
+Credentials built with [SsCredentialBuilder::with_attributes] (or
+given them with [SsCredential::with_attributes]) set these custom
+attributes on the items they create, and also require them to match
+when searching for existing items. (Custom values for the three
+controlled attributes are ignored, but the `application` attribute
+can be given a custom value.)
+
 Existing items are always searched for at the service level, which
 means all collections are searched. The search attributes used are
 `target` (set from the entry target), `service` (set from the entry
@@ -36,6 +48,10 @@ unless a target other than `default` is
 specified for the entry, in which case the item
 will be created in a collection (created if necessary)
 that is labeled with the specified target.
+Apps that keep all their items in a collection of their own
+can build their entries with [SsCredentialBuilder::with_collection],
+so that entries created without a target use that collection
+(as if it had been given as their target).
 
 Setting the password on an entry will always update the password on an
 existing item in preference to creating a new item.
@@ -44,6 +60,72 @@ versions of this crate, that may already
 have created items that match the entry, and thus reduces the chance
 of ambiguity in later searches.
 
+If ambiguity does arise, operations on the entry return an
+[Ambiguous](crate::Error::Ambiguous) error. Apps that would rather
+use any one of the matching items can build their entries with
+[SsCredentialBuilder::with_ambiguity] set to
+[FirstMatch](crate::credential::AmbiguityMode::FirstMatch).
+Apps that would rather clean up the duplicates can call
+[SsCredential::dedupe], or turn on [SsCredentialBuilder::with_auto_dedupe]
+so that changes to ambiguous entries dedupe and retry automatically.
+
+## Unlocking and prompts
+
+Reading or writing a locked item (or creating an item in a locked collection)
+unlocks it first, which typically prompts the user for their login password.
+Background apps that must never prompt can build their entries with
+[SsCredentialBuilder::with_non_interactive], and then locked items and
+collections produce a [Locked](crate::Error::Locked)
+error instead, except within [Entry::with_interaction_allowed](crate::Entry::with_interaction_allowed).
+Apps that want to offer a retry can call [Entry::unlock](crate::Entry::unlock),
+which unlocks the entry's collection and items (prompting the user
+whether or not the entry is non-interactive).
+
+Each unlock, and each creation of a new collection, is reported to the
+[prompt handler](crate::prompt) if the app has registered one, and
+fails with a [Locked](crate::Error::Locked) error if the handler refuses it.
+
+This store supports [search](crate::search). Exact matches in the search spec
+are done by the secret service; prefix matches are done by this crate on the
+items it returns.
+
+## Connections
+
+Each operation needs a connection to the secret service, and making one
+(which includes negotiating an encrypted session) takes several round trips.
+So the credentials made by a builder share a connection: it's made when
+one of them first needs it, and then it's used by each of them in turn.
+(Credentials that find the shared connection in use make their own,
+so operations from several threads aren't serialized.) If an operation
+over the shared connection fails because the connection was dropped
+(with a DBus `Disconnected` or `NoReply` error), the operation is retried
+once over a new connection; other failures are returned as they are,
+so operations are never run twice. Credentials made without a builder
+(for example, with [SsCredential::new_with_target]) make a new connection
+for each operation.
+
+## Session encryption
+
+By default, secrets are encrypted on their way to and from the secret service,
+with a key negotiated for each connection's session. Clients whose secrets
+never leave the machine can save that negotiation by asking for plain
+sessions instead, with [SsCredentialBuilder::with_session_encryption].
+A connection is never downgraded: if the secret service can't make an
+encrypted session, operations on credentials that ask for one fail,
+so a credential's [session_encryption](SsCredential::session_encryption)
+is the encryption its operations actually used.
+
+## Choice of bus
+
+By default, this store connects to the secret service on the DBus session bus,
+which is where desktop secret services run. The builder API allows clients to
+specify a different bus (see [SsCredentialBuilder::with_bus]), but the
+underlying DBus client library can only connect to the session bus, so
+builders configured for any other bus will fail to build credentials
+with a [NotSupportedByStore](crate::Error::NotSupportedByStore) error.
+(Clients who need to reach a secret service at a non-standard address can
+set `DBUS_SESSION_BUS_ADDRESS` in the environment before starting their process.)
+
 ## Headless usage
 
 If you must use the secret-service on a headless linux box,
@@ -78,12 +160,21 @@ there is no "default" collection defined under WSL.  So
 this keystore doesn't work "out of the box" on WSL.  See the
 issue for more details and possible workarounds.
  */
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
 
-use dbus_secret_service::{Collection, EncryptionType, Error, Item, SecretService};
+use dbus_secret_service::{Collection, EncryptionType, Error, Item, Path, SecretService};
+use log::debug;
 
-use super::credential::{Credential, CredentialApi, CredentialBuilder, CredentialBuilderApi};
+use super::credential::{
+    AmbiguityMode, AtRestStatus, Credential, CredentialApi, CredentialBuilder,
+    CredentialBuilderApi, CredentialMetadata, CredentialSpec, CredentialStoreInfo, SearchSpec,
+};
 use super::error::{Error as ErrorCode, Result, decode_password};
+use super::prompt::{self, Prompt, PromptKind};
 
 /// The representation of an item in the secret-service.
 ///
@@ -98,6 +189,13 @@ pub struct SsCredential {
     pub attributes: HashMap<String, String>,
     pub label: String,
     target: Option<String>,
+    ambiguity: AmbiguityMode,
+    auto_dedupe: bool,
+    non_interactive: bool,
+    custom_attributes: Vec<String>,
+    session_encryption: SessionEncryption,
+    item_path: Option<Path<'static>>,
+    connection: Option<Arc<SharedConnection>>,
 }
 
 impl CredentialApi for SsCredential {
@@ -123,28 +221,39 @@ impl CredentialApi for SsCredential {
     /// attribute.  
     fn set_secret(&self, secret: &[u8]) -> Result<()> {
         // first try to find a unique, existing, matching item and set its password
-        match self.map_matching_items(|i| set_item_secret(i, secret), true) {
+        match self.retry_deduped(|| self.map_matching_items(|i| set_item_secret(i, secret), true)) {
             Ok(_) => return Ok(()),
-            Err(ErrorCode::NoEntry) => {}
+            Err(ErrorCode::NoEntry) if self.item_path.is_none() => {}
             Err(err) => return Err(err),
         }
         // if there is no existing item, create one for this credential.  In order to create
         // an item, the credential must have an explicit target.  All entries created with
         // the [new] or [new_with_target] commands will have explicit targets.  But entries
         // created to wrap 3rd-party items that don't have `target` attributes may not.
-        let ss = SecretService::connect(EncryptionType::Dh).map_err(platform_failure)?;
         let name = self.target.as_ref().ok_or_else(empty_target)?;
-        let collection = get_collection(&ss, name).or_else(|_| create_collection(&ss, name))?;
-        collection
-            .create_item(
-                self.label.as_str(),
-                self.all_attributes(),
-                secret,
-                true, // replace
-                "text/plain",
-            )
+        self.connected(|ss| {
+            let collection = match find_collection(ss, name) {
+                Ok(collection) => {
+                    if collection.is_locked().map_err(decode_error)? {
+                        self.check_interaction()?;
+                        unlock_prompted(name, || collection.unlock())?;
+                    }
+                    collection
+                }
+                Err(_) => create_collection(ss, name)?,
+            };
+            round_trip("create_item", || {
+                collection.create_item(
+                    self.label.as_str(),
+                    self.all_attributes(),
+                    secret,
+                    true, // replace
+                    "text/plain",
+                )
+            })
             .map_err(platform_failure)?;
-        Ok(())
+            Ok(())
+        })
     }
 
     /// Gets the password on a unique matching item, if it exists.
@@ -169,6 +278,40 @@ impl CredentialApi for SsCredential {
         Ok(self.map_matching_items(get_item_secret, true)?.remove(0))
     }
 
+    /// Whether there are any matching items.
+    ///
+    /// This only searches for items, so it doesn't unlock them (or
+    /// prompt the user), and it doesn't fail if they are ambiguous.
+    fn exists(&self) -> Result<bool> {
+        if let Some(path) = &self.item_path {
+            return self.connected(|ss| match Item::new(ss, path.clone()).is_locked() {
+                Ok(_) => Ok(true),
+                Err(err) => match item_error(err) {
+                    ErrorCode::NoEntry => Ok(false),
+                    err => Err(err),
+                },
+            });
+        }
+        self.connected(|ss| {
+            let attributes: HashMap<&str, &str> =
+                self.search_attributes(false).into_iter().collect();
+            let search =
+                round_trip("search_items", || ss.search_items(attributes)).map_err(decode_error)?;
+            if !search.locked.is_empty() || !search.unlocked.is_empty() {
+                return Ok(true);
+            }
+            if let Some("default") = self.target.as_deref() {
+                let collection = ss.get_default_collection().map_err(decode_error)?;
+                let legacy = round_trip("search_items", || {
+                    collection.search_items(self.search_attributes(true))
+                })
+                .map_err(decode_error)?;
+                return Ok(!legacy.is_empty());
+            }
+            Ok(false)
+        })
+    }
+
     /// Get attributes on a unique matching item, if it exists
     fn get_attributes(&self) -> Result<HashMap<String, String>> {
         let attributes: Vec<HashMap<String, String>> =
@@ -178,10 +321,71 @@ impl CredentialApi for SsCredential {
 
     /// Update attributes on a unique matching item, if it exists
     fn update_attributes(&self, attributes: &HashMap<&str, &str>) -> Result<()> {
-        self.map_matching_items(|i| update_item_attributes(i, attributes), true)?;
+        self.retry_deduped(|| {
+            self.map_matching_items(|i| update_item_attributes(i, attributes), true)
+        })?;
         Ok(())
     }
 
+    /// The creation and modification times of a unique matching item, if it exists.
+    fn get_metadata(&self) -> Result<CredentialMetadata> {
+        let metadata: Vec<CredentialMetadata> = self.map_matching_items(get_item_metadata, true)?;
+        Ok(metadata.into_iter().next().unwrap())
+    }
+
+    /// The label of a unique matching item, if it exists.
+    fn get_label(&self) -> Result<String> {
+        let labels: Vec<String> =
+            self.map_matching_items(|item| item.get_label().map_err(decode_error), true)?;
+        Ok(labels.into_iter().next().unwrap())
+    }
+
+    /// Set the label of a unique matching item, if it exists.
+    ///
+    /// The label can't be empty.
+    fn set_label(&self, label: &str) -> Result<()> {
+        self.update_attributes(&HashMap::from([("label", label)]))
+    }
+
+    /// Unlock the collection named by this credential's target (if it exists)
+    /// and the items matching this credential, prompting the user if necessary.
+    ///
+    /// Returns a [NoEntry](ErrorCode::NoEntry) error if there is neither
+    /// such a collection nor any matching item.
+    fn unlock(&self) -> Result<()> {
+        if let Some(path) = &self.item_path {
+            return self.connected(|ss| {
+                let item = Item::new(ss, path.clone());
+                if item.is_locked().map_err(item_error)? {
+                    unlock_prompted(&self.label, || item.unlock())?;
+                }
+                Ok(())
+            });
+        }
+        self.connected(|ss| {
+            let mut found = false;
+            if let Some(name) = &self.target {
+                match get_collection(ss, name) {
+                    Ok(_) => found = true,
+                    Err(ErrorCode::NoEntry) => {}
+                    Err(err) => return Err(err),
+                }
+            }
+            let search = round_trip("search_items", || {
+                ss.search_items(self.search_attributes(false))
+            })
+            .map_err(decode_error)?;
+            for item in search.locked.iter() {
+                unlock_prompted(&self.label, || item.unlock())?;
+            }
+            if found || !search.locked.is_empty() || !search.unlocked.is_empty() {
+                Ok(())
+            } else {
+                Err(ErrorCode::NoEntry)
+            }
+        })
+    }
+
     /// Deletes the unique matching item, if it exists.
     ///
     /// If there are no
@@ -190,10 +394,29 @@ impl CredentialApi for SsCredential {
     /// returns an [Ambiguous](ErrorCode::Ambiguous)
     /// error with a credential for each matching item.
     fn delete_credential(&self) -> Result<()> {
-        self.map_matching_items(delete_item, true)?;
+        self.retry_deduped(|| self.map_matching_items(delete_item, true))?;
         Ok(())
     }
 
+    /// The target, service, and user in this credential's attributes.
+    ///
+    /// Returns `None` if the credential (which may wrap a third-party item)
+    /// has no `service` or `username` attribute.
+    fn spec(&self) -> Option<CredentialSpec> {
+        Some(CredentialSpec {
+            target: self.target.clone(),
+            service: self.attributes.get("service")?.clone(),
+            user: self.attributes.get("username")?.clone(),
+        })
+    }
+
+    /// Whether items are encrypted at rest depends on the secret-service
+    /// provider and on the collection (which may be unencrypted),
+    /// so it's unknown.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        AtRestStatus::Unknown
+    }
+
     /// Return the underlying credential object with an `Any` type so that it can
     /// be downgraded to an [SsCredential] for platform-specific processing.
     fn as_any(&self) -> &dyn std::any::Any {
@@ -234,6 +457,13 @@ impl SsCredential {
                 env!("CARGO_PKG_VERSION"),
             ),
             target: Some(target.to_string()),
+            ambiguity: AmbiguityMode::default(),
+            auto_dedupe: false,
+            non_interactive: false,
+            custom_attributes: Vec::new(),
+            session_encryption: SessionEncryption::default(),
+            item_path: None,
+            connection: None,
         })
     }
 
@@ -254,6 +484,13 @@ impl SsCredential {
                 env!("CARGO_PKG_VERSION"),
             ),
             target: None,
+            ambiguity: AmbiguityMode::default(),
+            auto_dedupe: false,
+            non_interactive: false,
+            custom_attributes: Vec::new(),
+            session_encryption: SessionEncryption::default(),
+            item_path: None,
+            connection: None,
         })
     }
 
@@ -262,12 +499,216 @@ impl SsCredential {
     /// The created credential will have all the attributes and label
     /// of the underlying item, so you can examine them.
     pub fn new_from_item(item: &Item) -> Result<Self> {
-        let attributes = item.get_attributes().map_err(decode_error)?;
+        let attributes = item.get_attributes().map_err(item_error)?;
         let target = attributes.get("target").cloned();
         Ok(Self {
             attributes,
             label: item.get_label().map_err(decode_error)?,
             target,
+            ambiguity: AmbiguityMode::default(),
+            auto_dedupe: false,
+            non_interactive: false,
+            custom_attributes: Vec::new(),
+            session_encryption: SessionEncryption::default(),
+            item_path: None,
+            connection: None,
+        })
+    }
+
+    /// Create a credential for the item at the given DBus object path.
+    ///
+    /// This is for interoperating with items found by a prior search (see
+    /// [get_item_paths](SsCredential::get_item_paths)) or by another application:
+    /// the credential operates on exactly that item, rather than on the items
+    /// matching its attributes, so it's never ambiguous. It has the attributes
+    /// and label the item has when it's created, and it never creates an item,
+    /// so setting its secret after the item has been deleted fails.
+    ///
+    /// Returns an [Invalid](ErrorCode::Invalid) error if the path isn't an
+    /// object path, and a [NoEntry](ErrorCode::NoEntry) error if there's
+    /// no item at the path.
+    pub fn new_with_item_path(path: &str) -> Result<Self> {
+        let path = Path::new(path.to_string())
+            .map_err(|reason| ErrorCode::Invalid("path".to_string(), reason))?;
+        let mut cred = with_connection(SessionEncryption::default(), None, |ss| {
+            Self::new_from_item(&Item::new(ss, path.clone()))
+        })?;
+        cred.item_path = Some(path);
+        Ok(cred)
+    }
+
+    /// The DBus object path of the item this credential operates on,
+    /// if it was made with [new_with_item_path](SsCredential::new_with_item_path).
+    pub fn item_path(&self) -> Option<&str> {
+        self.item_path.as_deref()
+    }
+
+    /// Return this credential with the given behavior when it matches multiple items.
+    ///
+    /// With [FirstMatch](AmbiguityMode::FirstMatch), operations
+    /// that would return an [Ambiguous](ErrorCode::Ambiguous) error
+    /// instead use the matching item whose DBus object path sorts first.
+    pub fn with_ambiguity(mut self, ambiguity: AmbiguityMode) -> Self {
+        self.ambiguity = ambiguity;
+        self
+    }
+
+    /// Return this credential with the given custom attributes,
+    /// which are set on created items and matched when searching.
+    ///
+    /// Values for the `target`, `service`, and `username` attributes are ignored.
+    /// See the module header for details.
+    pub fn with_attributes(mut self, attributes: &HashMap<&str, &str>) -> Self {
+        for (name, value) in attributes {
+            if ["target", "service", "username"].contains(name) {
+                continue;
+            }
+            self.attributes.insert(name.to_string(), value.to_string());
+            if !self.custom_attributes.iter().any(|n| n == name) {
+                self.custom_attributes.push(name.to_string());
+            }
+        }
+        self
+    }
+
+    /// Return this credential with automatic deduplication turned on or off.
+    ///
+    /// When it's on, an operation that changes the store (setting the secret,
+    /// updating attributes, or deleting) and that finds multiple matching items
+    /// will [dedupe](SsCredential::dedupe) them and retry exactly once.
+    /// If the retry fails, the original [Ambiguous](ErrorCode::Ambiguous)
+    /// error is returned.
+    pub fn with_auto_dedupe(mut self, auto_dedupe: bool) -> Self {
+        self.auto_dedupe = auto_dedupe;
+        self
+    }
+
+    /// Return this credential with prompting turned off or on.
+    ///
+    /// A non-interactive credential won't unlock locked items or collections
+    /// (which prompts the user), and returns a
+    /// [Locked](ErrorCode::Locked) error instead, except
+    /// when used inside [Entry::with_interaction_allowed](crate::Entry::with_interaction_allowed).
+    pub fn with_non_interactive(mut self, non_interactive: bool) -> Self {
+        self.non_interactive = non_interactive;
+        self
+    }
+
+    /// Return this credential with the given encryption of its sessions
+    /// with the secret service.
+    ///
+    /// See the module header for details.
+    pub fn with_session_encryption(mut self, encryption: SessionEncryption) -> Self {
+        self.session_encryption = encryption;
+        self
+    }
+
+    /// The encryption of this credential's sessions with the secret service.
+    ///
+    /// Every operation on this credential is done in a session with
+    /// this encryption (or fails), so this is the encryption that was used.
+    pub fn session_encryption(&self) -> SessionEncryption {
+        self.session_encryption
+    }
+
+    /// A credential for one of the items matching this credential, which
+    /// uses the same session encryption and shares the same connection.
+    fn for_item(&self, item: &Item) -> Result<Self> {
+        let mut cred = Self::new_from_item(item)?.with_session_encryption(self.session_encryption);
+        cred.connection = self.connection.clone();
+        Ok(cred)
+    }
+
+    /// Return this credential sharing its builder's connection.
+    fn sharing(mut self, connection: &Arc<SharedConnection>) -> Self {
+        self.connection = Some(connection.clone());
+        self
+    }
+
+    /// Run an operation over a connection to the secret service,
+    /// sharing the connection of this credential's builder (if any).
+    fn connected<T>(&self, op: impl Fn(&SecretService) -> Result<T>) -> Result<T> {
+        with_connection(self.session_encryption, self.connection.as_deref(), op)
+    }
+
+    /// Fail if this credential would have to prompt the user, but may not.
+    fn check_interaction(&self) -> Result<()> {
+        if self.non_interactive && !crate::interaction_allowed_override() {
+            return Err(ErrorCode::Locked);
+        }
+        Ok(())
+    }
+
+    /// Delete all but the most recently modified of the items matching this credential.
+    ///
+    /// Returns the number of items deleted. (Items set by keyring v1,
+    /// which are only found when there are no other matches,
+    /// are not deduplicated.)
+    pub fn dedupe(&self) -> Result<usize> {
+        if self.item_path.is_some() {
+            return Ok(0);
+        }
+        self.connected(|ss| {
+            let search = round_trip("search_items", || {
+                ss.search_items(self.search_attributes(false))
+            })
+            .map_err(decode_error)?;
+            let mut items: Vec<(u64, &Item)> = vec![];
+            for item in search.unlocked.iter().chain(search.locked.iter()) {
+                items.push((item.get_modified().map_err(decode_error)?, item));
+            }
+            // newest first, with ties broken by path so the choice is stable
+            items.sort_by(|(m1, i1), (m2, i2)| (m2, &i2.path).cmp(&(m1, &i1.path)));
+            for (_, item) in items.iter().skip(1) {
+                if item.is_locked().map_err(decode_error)? {
+                    self.check_interaction()?;
+                    unlock_prompted(&self.label, || item.unlock())?;
+                }
+                delete_item(item)?;
+            }
+            Ok(items.len().saturating_sub(1))
+        })
+    }
+
+    /// Run a mutating operation, deduping and retrying once if it's ambiguous
+    /// and this credential has automatic deduplication turned on.
+    fn retry_deduped<T>(&self, op: impl Fn() -> Result<T>) -> Result<T> {
+        match op() {
+            Err(ErrorCode::Ambiguous(creds)) if self.auto_dedupe => {
+                if self.dedupe().is_ok() {
+                    if let Ok(result) = op() {
+                        return Ok(result);
+                    }
+                }
+                Err(ErrorCode::Ambiguous(creds))
+            }
+            other => other,
+        }
+    }
+
+    /// Find all items in the secret service that have the given attribute values,
+    /// and return a credential for each of them.
+    ///
+    /// This is an escape hatch for interoperating with third-party items that
+    /// can't be described by this crate's target/service/user model: the
+    /// attributes are passed to the secret service as-is, items are searched
+    /// for in all collections, and items are not unlocked. Since the returned
+    /// credentials find their items using the `service` and `username`
+    /// attributes, reading or writing them will fail if those are missing.
+    pub fn raw_search(attributes: &HashMap<String, String>) -> Result<Vec<Self>> {
+        with_connection(SessionEncryption::default(), None, |ss| {
+            let attributes: HashMap<&str, &str> = attributes
+                .iter()
+                .map(|(k, v)| (k.as_str(), v.as_str()))
+                .collect();
+            let search =
+                round_trip("search_items", || ss.search_items(attributes)).map_err(decode_error)?;
+            search
+                .unlocked
+                .iter()
+                .chain(search.locked.iter())
+                .map(Self::new_from_item)
+                .collect()
         })
     }
 
@@ -279,6 +720,15 @@ impl SsCredential {
             .remove(0))
     }
 
+    /// The DBus object paths of the items matching this credential.
+    ///
+    /// Items are unlocked (as they are by other operations) before their
+    /// paths are returned. Use [new_with_item_path](SsCredential::new_with_item_path)
+    /// to make a credential for exactly one of them.
+    pub fn get_item_paths(&self) -> Result<Vec<String>> {
+        self.map_matching_items(|item| Ok(item.path.to_string()), false)
+    }
+
     /// If there are multiple matching items for this credential, get all of their passwords.
     ///
     /// (This is useful if [get_password](SsCredential::get_password)
@@ -305,42 +755,69 @@ impl SsCredential {
     /// If `require_unique` is true, and there are multiple matches,
     /// then an [Ambiguous](ErrorCode::Ambiguous) error is returned
     /// with a vector containing one
-    /// credential for each of the matching items.
+    /// credential for each of the matching items (unless this credential's
+    /// ambiguity mode is [FirstMatch](AmbiguityMode::FirstMatch), in which case
+    /// the function is applied only to the first of them).
+    ///
+    /// For a credential made with [new_with_item_path](SsCredential::new_with_item_path),
+    /// the only matching item is the one at its path (if it exists).
     pub fn map_matching_items<F, T>(&self, f: F, require_unique: bool) -> Result<Vec<T>>
     where
         F: Fn(&Item) -> Result<T>,
         T: Sized,
     {
-        let ss = SecretService::connect(EncryptionType::Dh).map_err(platform_failure)?;
-        let attributes: HashMap<&str, &str> = self.search_attributes(false).into_iter().collect();
-        let search = ss.search_items(attributes).map_err(decode_error)?;
-        let count = search.locked.len() + search.unlocked.len();
-        if count == 0 {
-            if let Some("default") = self.target.as_deref() {
-                return self.map_matching_legacy_items(&ss, f, require_unique);
-            }
+        if let Some(path) = &self.item_path {
+            return self.connected(|ss| {
+                let item = Item::new(ss, path.clone());
+                if item.is_locked().map_err(item_error)? {
+                    self.check_interaction()?;
+                    unlock_prompted(&self.label, || item.unlock())?;
+                }
+                Ok(vec![f(&item)?])
+            });
         }
-        if require_unique {
+        self.connected(|ss| {
+            let attributes: HashMap<&str, &str> =
+                self.search_attributes(false).into_iter().collect();
+            let search =
+                round_trip("search_items", || ss.search_items(attributes)).map_err(decode_error)?;
+            let count = search.locked.len() + search.unlocked.len();
             if count == 0 {
-                return Err(ErrorCode::NoEntry);
-            } else if count > 1 {
-                let mut creds: Vec<Box<Credential>> = vec![];
-                for item in search.locked.iter().chain(search.unlocked.iter()) {
-                    let cred = Self::new_from_item(item)?;
-                    creds.push(Box::new(cred))
+                if let Some("default") = self.target.as_deref() {
+                    return self.map_matching_legacy_items(ss, &f, require_unique);
                 }
-                return Err(ErrorCode::Ambiguous(creds));
             }
-        }
-        let mut results: Vec<T> = vec![];
-        for item in search.unlocked.iter() {
-            results.push(f(item)?);
-        }
-        for item in search.locked.iter() {
-            item.unlock().map_err(decode_error)?;
-            results.push(f(item)?);
-        }
-        Ok(results)
+            if require_unique {
+                if count == 0 {
+                    return Err(ErrorCode::NoEntry);
+                } else if count > 1 {
+                    if self.ambiguity == AmbiguityMode::FirstMatch {
+                        let all = search.unlocked.iter().chain(search.locked.iter());
+                        let item = all.min_by(|a, b| a.path.cmp(&b.path)).unwrap();
+                        if item.is_locked().map_err(decode_error)? {
+                            self.check_interaction()?;
+                            unlock_prompted(&self.label, || item.unlock())?;
+                        }
+                        return Ok(vec![f(item)?]);
+                    }
+                    let mut creds: Vec<Box<Credential>> = vec![];
+                    for item in search.locked.iter().chain(search.unlocked.iter()) {
+                        creds.push(Box::new(self.for_item(item)?))
+                    }
+                    return Err(ErrorCode::Ambiguous(creds));
+                }
+            }
+            let mut results: Vec<T> = vec![];
+            for item in search.unlocked.iter() {
+                results.push(f(item)?);
+            }
+            for item in search.locked.iter() {
+                self.check_interaction()?;
+                unlock_prompted(&self.label, || item.unlock())?;
+                results.push(f(item)?);
+            }
+            Ok(results)
+        })
     }
 
     /// Map a function over items that older versions of keyring
@@ -378,15 +855,19 @@ impl SsCredential {
     {
         let collection = ss.get_default_collection().map_err(decode_error)?;
         let attributes = self.search_attributes(true);
-        let search = collection.search_items(attributes).map_err(decode_error)?;
+        let search = round_trip("search_items", || collection.search_items(attributes))
+            .map_err(decode_error)?;
         if require_unique {
             if search.is_empty() && require_unique {
                 return Err(ErrorCode::NoEntry);
             } else if search.len() > 1 {
+                if self.ambiguity == AmbiguityMode::FirstMatch {
+                    let item = search.iter().min_by(|a, b| a.path.cmp(&b.path)).unwrap();
+                    return Ok(vec![f(item)?]);
+                }
                 let mut creds: Vec<Box<Credential>> = vec![];
                 for item in search.iter() {
-                    let cred = Self::new_from_item(item)?;
-                    creds.push(Box::new(cred))
+                    creds.push(Box::new(self.for_item(item)?))
                 }
                 return Err(ErrorCode::Ambiguous(creds));
             }
@@ -418,28 +899,200 @@ impl SsCredential {
         }
         result.insert("service", self.attributes["service"].as_str());
         result.insert("username", self.attributes["username"].as_str());
+        for name in &self.custom_attributes {
+            result.insert(name.as_str(), self.attributes[name].as_str());
+        }
         result
     }
 }
 
+/// The DBus bus on which to reach the secret service.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum BusKind {
+    /// The session bus (the default)
+    #[default]
+    Session,
+    /// The system bus
+    System,
+    /// The bus at the given address
+    Address(String),
+}
+
+/// How secrets are protected on their way to and from the secret service.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SessionEncryption {
+    /// Secrets are encrypted with a key negotiated for the session.
+    #[default]
+    Encrypted,
+    /// Secrets are sent in the clear (over the local bus).
+    Plain,
+}
+
 /// The builder for secret-service credentials
 #[derive(Debug, Default)]
-pub struct SsCredentialBuilder {}
+pub struct SsCredentialBuilder {
+    bus: BusKind,
+    ambiguity: AmbiguityMode,
+    auto_dedupe: bool,
+    non_interactive: bool,
+    collection: Option<String>,
+    attributes: HashMap<String, String>,
+    session_encryption: SessionEncryption,
+    connection: Arc<SharedConnection>,
+}
+
+impl SsCredentialBuilder {
+    /// Return a builder whose credentials use the given bus.
+    ///
+    /// See the module header for which buses are supported.
+    pub fn with_bus(bus: BusKind) -> Self {
+        Self {
+            bus,
+            ..Default::default()
+        }
+    }
+
+    /// The bus used by this builder's credentials.
+    pub fn bus(&self) -> &BusKind {
+        &self.bus
+    }
+
+    /// Return this builder with the given behavior for the credentials it builds
+    /// when they match multiple items.
+    ///
+    /// See [SsCredential::with_ambiguity].
+    pub fn with_ambiguity(mut self, ambiguity: AmbiguityMode) -> Self {
+        self.ambiguity = ambiguity;
+        self
+    }
+
+    /// The behavior of this builder's credentials when they match multiple items.
+    pub fn ambiguity(&self) -> AmbiguityMode {
+        self.ambiguity
+    }
+
+    /// Return this builder with automatic deduplication turned on or off
+    /// for the credentials it builds.
+    ///
+    /// See [SsCredential::with_auto_dedupe].
+    pub fn with_auto_dedupe(mut self, auto_dedupe: bool) -> Self {
+        self.auto_dedupe = auto_dedupe;
+        self
+    }
+
+    /// Whether this builder's credentials automatically dedupe their items.
+    pub fn auto_dedupe(&self) -> bool {
+        self.auto_dedupe
+    }
+
+    /// Return this builder with prompting turned off or on for the credentials it builds.
+    ///
+    /// See [SsCredential::with_non_interactive].
+    pub fn with_non_interactive(mut self, non_interactive: bool) -> Self {
+        self.non_interactive = non_interactive;
+        self
+    }
+
+    /// Whether this builder's credentials are non-interactive.
+    pub fn non_interactive(&self) -> bool {
+        self.non_interactive
+    }
+
+    /// Return this builder with the given collection as the target
+    /// of the credentials it builds without one.
+    ///
+    /// See the module header for details.
+    pub fn with_collection(mut self, collection: Option<&str>) -> Self {
+        self.collection = collection.map(str::to_string);
+        self
+    }
+
+    /// The collection used by this builder's credentials that have no target, if any.
+    pub fn collection(&self) -> Option<&str> {
+        self.collection.as_deref()
+    }
+
+    /// Return this builder with the given custom attributes added
+    /// to those of the credentials it builds.
+    ///
+    /// Custom attributes are also required to match by [search](crate::search).
+    /// See [SsCredential::with_attributes].
+    pub fn with_attributes(mut self, attributes: &HashMap<&str, &str>) -> Self {
+        for (name, value) in attributes {
+            self.attributes.insert(name.to_string(), value.to_string());
+        }
+        self
+    }
+
+    /// The custom attributes of this builder's credentials.
+    pub fn attributes(&self) -> &HashMap<String, String> {
+        &self.attributes
+    }
+
+    /// Return this builder with the given encryption of the sessions
+    /// used by the credentials it builds.
+    ///
+    /// See [SsCredential::with_session_encryption].
+    pub fn with_session_encryption(mut self, encryption: SessionEncryption) -> Self {
+        self.session_encryption = encryption;
+        self
+    }
+
+    /// The encryption of the sessions used by this builder's credentials.
+    pub fn session_encryption(&self) -> SessionEncryption {
+        self.session_encryption
+    }
+
+    fn custom_attributes(&self) -> HashMap<&str, &str> {
+        self.attributes
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect()
+    }
+
+    fn check_bus(&self) -> Result<()> {
+        match &self.bus {
+            BusKind::Session => Ok(()),
+            other => Err(ErrorCode::NotSupportedByStore(format!(
+                "connecting to the {other:?} bus"
+            ))),
+        }
+    }
+}
 
 /// Returns an instance of the secret-service credential builder.
 ///
 /// If secret-service is the default credential store,
 /// this is called once when an entry is first created.
 pub fn default_credential_builder() -> Box<CredentialBuilder> {
-    Box::new(SsCredentialBuilder {})
+    Box::new(SsCredentialBuilder::default())
 }
 
 impl CredentialBuilderApi for SsCredentialBuilder {
     /// Build an [SsCredential] for the given target, service, and user.
+    ///
+    /// If there is no target, the builder's collection (if any) is used.
+    /// Fails if the builder is configured for a bus other than the session bus.
     fn build(&self, target: Option<&str>, service: &str, user: &str) -> Result<Box<Credential>> {
-        Ok(Box::new(SsCredential::new_with_target(
-            target, service, user,
-        )?))
+        self.check_bus()?;
+        let target = target.or(self.collection.as_deref());
+        let credential = SsCredential::new_with_target(target, service, user)?;
+        Ok(Box::new(
+            credential
+                .with_ambiguity(self.ambiguity)
+                .with_auto_dedupe(self.auto_dedupe)
+                .with_non_interactive(self.non_interactive)
+                .with_attributes(&self.custom_attributes())
+                .with_session_encryption(self.session_encryption)
+                .sharing(&self.connection),
+        ))
+    }
+
+    /// Whether items are encrypted at rest depends on the secret-service
+    /// provider and on the collection (which may be unencrypted),
+    /// so it's unknown.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        AtRestStatus::Unknown
     }
 
     /// Return the underlying builder object with an `Any` type so that it can
@@ -447,6 +1100,120 @@ impl CredentialBuilderApi for SsCredentialBuilder {
     fn as_any(&self) -> &dyn std::any::Any {
         self
     }
+
+    /// Search all collections for items that match the given spec.
+    ///
+    /// Exact matches in the spec are passed to the secret service as search
+    /// attributes; prefix matches are applied to the items that it returns.
+    /// Items without both a `service` and a `username` attribute are skipped,
+    /// and items without a `target` attribute are only matched if the spec
+    /// matches any target. Found items are not unlocked.
+    ///
+    /// Unless the spec is unsorted, the results are sorted by service,
+    /// then user, then target.
+    fn search(&self, spec: &SearchSpec) -> Result<Vec<Box<Credential>>> {
+        self.check_bus()?;
+        let results = with_connection(self.session_encryption, Some(&self.connection), |ss| {
+            let mut attributes: HashMap<&str, &str> = HashMap::new();
+            for (name, matcher) in [
+                ("target", &spec.target),
+                ("service", &spec.service),
+                ("username", &spec.user),
+            ] {
+                if let Some(value) = matcher.exact() {
+                    attributes.insert(name, value);
+                }
+            }
+            attributes.extend(self.custom_attributes());
+            let search =
+                round_trip("search_items", || ss.search_items(attributes)).map_err(decode_error)?;
+            let mut results: Vec<SsCredential> = vec![];
+            for item in search.unlocked.iter().chain(search.locked.iter()) {
+                let cred = SsCredential::new_from_item(item)?
+                    .with_session_encryption(self.session_encryption)
+                    .sharing(&self.connection);
+                let (Some(service), Some(user)) = (
+                    cred.attributes.get("service"),
+                    cred.attributes.get("username"),
+                ) else {
+                    continue;
+                };
+                if spec.matches(cred.target.as_deref(), service, user) {
+                    results.push(cred);
+                }
+            }
+            if !spec.unsorted {
+                results.sort_by(|a, b| {
+                    let key = |c: &SsCredential| {
+                        (
+                            c.attributes["service"].clone(),
+                            c.attributes["username"].clone(),
+                            c.target.clone(),
+                        )
+                    };
+                    key(a).cmp(&key(b))
+                });
+            }
+            Ok(results)
+        })?;
+        Ok(results
+            .into_iter()
+            .map(|cred| Box::new(cred) as Box<Credential>)
+            .collect())
+    }
+
+    /// Read the secrets of the credentials with the given specs
+    /// over a single connection to the secret service.
+    fn get_many(&self, specs: &[CredentialSpec]) -> Vec<Result<Vec<u8>>> {
+        in_batch(self.session_encryption, || {
+            specs
+                .iter()
+                .map(|spec| {
+                    self.build(spec.target.as_deref(), &spec.service, &spec.user)
+                        .and_then(|cred| cred.get_secret())
+                })
+                .collect()
+        })
+    }
+
+    /// Set the secrets of the credentials with the given specs
+    /// over a single connection to the secret service.
+    fn set_many(&self, items: &[(CredentialSpec, &[u8])]) -> Vec<Result<()>> {
+        in_batch(self.session_encryption, || {
+            items
+                .iter()
+                .map(|(spec, secret)| {
+                    self.build(spec.target.as_deref(), &spec.service, &spec.user)
+                        .and_then(|cred| cred.set_secret(secret))
+                })
+                .collect()
+        })
+    }
+
+    /// Delete the credentials with the given specs
+    /// over a single connection to the secret service.
+    fn delete_many(&self, specs: &[CredentialSpec]) -> Vec<Result<()>> {
+        in_batch(self.session_encryption, || {
+            specs
+                .iter()
+                .map(|spec| {
+                    self.build(spec.target.as_deref(), &spec.service, &spec.user)
+                        .and_then(|cred| cred.delete_credential())
+                })
+                .collect()
+        })
+    }
+
+    /// Items have attributes and can be searched.
+    fn info(&self) -> CredentialStoreInfo {
+        CredentialStoreInfo::new(
+            "secret-service",
+            self.persistence(),
+            self.at_rest_encryption(),
+        )
+        .with_attributes(true)
+        .with_search(true)
+    }
 }
 
 //
@@ -458,19 +1225,24 @@ impl CredentialBuilderApi for SsCredentialBuilder {
 /// The name `default` is treated specially and is interpreted as naming
 /// the default collection regardless of its label (which might be different).
 pub fn get_collection<'a>(ss: &'a SecretService, name: &str) -> Result<Collection<'a>> {
-    let collection = if name.eq("default") {
-        ss.get_default_collection().map_err(decode_error)?
+    let collection = find_collection(ss, name)?;
+    if collection.is_locked().map_err(decode_error)? {
+        unlock_prompted(name, || collection.unlock())?;
+    }
+    Ok(collection)
+}
+
+/// Find the (possibly locked) secret service collection labeled with the given name.
+fn find_collection<'a>(ss: &'a SecretService, name: &str) -> Result<Collection<'a>> {
+    if name.eq("default") {
+        ss.get_default_collection().map_err(decode_error)
     } else {
         let all = ss.get_all_collections().map_err(decode_error)?;
         let found = all
             .into_iter()
             .find(|c| c.get_label().map(|l| l.eq(name)).unwrap_or(false));
-        found.ok_or(ErrorCode::NoEntry)?
-    };
-    if collection.is_locked().map_err(decode_error)? {
-        collection.unlock().map_err(decode_error)?;
+        found.ok_or(ErrorCode::NoEntry)
     }
-    Ok(collection)
 }
 
 /// Create a secret service collection labeled with the given name.
@@ -482,28 +1254,217 @@ pub fn create_collection<'a>(ss: &'a SecretService, name: &str) -> Result<Collec
     let collection = if name.eq("default") {
         ss.get_default_collection().map_err(decode_error)?
     } else {
-        ss.create_collection(name, "").map_err(decode_error)?
+        let prompt = Prompt::new(PromptKind::CreateCollection, "secret-service", name);
+        prompt::interact(&prompt, || {
+            round_trip("create_collection", || ss.create_collection(name, "")).map_err(decode_error)
+        })?
     };
     Ok(collection)
 }
 
+thread_local! {
+    /// The connection shared by the operations of the batch
+    /// that's running on this thread, if there is one.
+    static BATCH_CONNECTION: RefCell<Option<(Rc<SecretService>, SessionEncryption)>> =
+        const { RefCell::new(None) };
+}
+
+/// Make a new connection (and session) to the secret service.
+///
+/// If the session can't be given the requested encryption, this fails
+/// rather than settling for less.
+fn connect(encryption: SessionEncryption) -> Result<SecretService> {
+    let encryption_type = match encryption {
+        SessionEncryption::Encrypted => EncryptionType::Dh,
+        SessionEncryption::Plain => EncryptionType::Plain,
+    };
+    let ss = round_trip("connect", || SecretService::connect(encryption_type))
+        .map_err(platform_failure)?;
+    debug!("connected to the secret service with a {encryption:?} session");
+    Ok(ss)
+}
+
+/// The connection to the secret service that's shared by the
+/// credentials of a builder while none of them is using it.
+#[derive(Default)]
+struct SharedConnection {
+    idle: Mutex<Option<(SecretService, SessionEncryption)>>,
+}
+
+impl SharedConnection {
+    fn idle(&self) -> MutexGuard<'_, Option<(SecretService, SessionEncryption)>> {
+        self.idle
+            .lock()
+            .expect("Poisoned Mutex in keyring-rs: please report a bug!")
+    }
+
+    /// Take the shared connection, if it's been made with the given
+    /// encryption and isn't in use.
+    fn take(&self, encryption: SessionEncryption) -> Option<SecretService> {
+        let mut idle = self.idle();
+        match idle.take() {
+            Some((ss, shared)) if shared == encryption => Some(ss),
+            other => {
+                *idle = other;
+                None
+            }
+        }
+    }
+
+    /// Offer a working connection for sharing, unless one is shared already.
+    fn put_back(&self, ss: SecretService, encryption: SessionEncryption) {
+        let mut idle = self.idle();
+        if idle.is_none() {
+            *idle = Some((ss, encryption));
+        }
+    }
+}
+
+impl std::fmt::Debug for SharedConnection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SharedConnection")
+            .field("idle", &self.idle().is_some())
+            .finish()
+    }
+}
+
+/// Run an operation over a connection to the secret service
+/// whose session has the given encryption.
+///
+/// The connection of the batch that's running on this thread is used if
+/// there is one, and then the shared connection (if any) if it's not in use.
+/// (Connections whose sessions have some other encryption are not used.)
+/// Otherwise a new connection is made, and is then shared if it works.
+/// If an operation over a shared connection that's been used before fails
+/// because the connection was dropped (see [is_disconnect]), it's discarded
+/// and the operation is retried (once) over a new connection. Other failures
+/// aren't retried, because the operation may have changed the store
+/// (or prompted the user) before it failed.
+fn with_connection<T>(
+    encryption: SessionEncryption,
+    shared: Option<&SharedConnection>,
+    op: impl Fn(&SecretService) -> Result<T>,
+) -> Result<T> {
+    let batch = BATCH_CONNECTION.with_borrow(|batch| batch.clone());
+    if let Some((ss, _)) = batch.filter(|(_, batch)| *batch == encryption) {
+        return op(&ss);
+    }
+    let Some(shared) = shared else {
+        return op(&connect(encryption)?);
+    };
+    if let Some(ss) = shared.take(encryption) {
+        match op(&ss) {
+            Err(err) if is_disconnect(&err) => {
+                debug!("reconnecting to the secret service after failure: {err}");
+            }
+            result => {
+                shared.put_back(ss, encryption);
+                return result;
+            }
+        }
+    }
+    let ss = connect(encryption)?;
+    let result = op(&ss);
+    if !result.as_ref().is_err_and(is_disconnect) {
+        shared.put_back(ss, encryption);
+    }
+    result
+}
+
+/// Whether an error shows that the connection to the secret service was dropped.
+fn is_disconnect(err: &ErrorCode) -> bool {
+    let Some(code) = err.platform_code() else {
+        return false;
+    };
+    matches!(
+        code.name.as_deref(),
+        Some("org.freedesktop.DBus.Error.Disconnected" | "org.freedesktop.DBus.Error.NoReply")
+    )
+}
+
+/// Make a round trip to the secret service.
+///
+/// With the `tracing` feature, the round trip's duration is reported in an event.
+fn round_trip<T>(call: &'static str, f: impl FnOnce() -> T) -> T {
+    #[cfg(feature = "tracing")]
+    {
+        let start = std::time::Instant::now();
+        let result = f();
+        let elapsed_us = start.elapsed().as_micros() as u64;
+        tracing::trace!(call, elapsed_us, "secret service round trip");
+        result
+    }
+    #[cfg(not(feature = "tracing"))]
+    {
+        let _ = call;
+        f()
+    }
+}
+
+/// Unlock a collection or item (which typically prompts the user),
+/// reporting the prompt to the registered [prompt handler](crate::prompt).
+fn unlock_prompted(
+    subject: &str,
+    unlock: impl FnOnce() -> std::result::Result<(), Error>,
+) -> Result<()> {
+    let prompt = Prompt::new(PromptKind::Unlock, "secret-service", subject);
+    prompt::interact(&prompt, || {
+        round_trip("unlock", unlock).map_err(decode_error)
+    })
+}
+
+/// Run a batch of operations on this thread over a single connection
+/// whose session has the given encryption.
+///
+/// If the connection can't be made, the operations are run anyway
+/// (so that each of them reports the failure).
+fn in_batch<T>(encryption: SessionEncryption, f: impl FnOnce() -> T) -> T {
+    /// Restores the previous batch connection, even if the batch panics.
+    struct Restore(Option<(Rc<SecretService>, SessionEncryption)>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            BATCH_CONNECTION.set(self.0.take());
+        }
+    }
+
+    let Ok(ss) = connect(encryption) else {
+        return f();
+    };
+    let _restore = Restore(BATCH_CONNECTION.replace(Some((Rc::new(ss), encryption))));
+    f()
+}
+
 /// Given an existing item, set its secret.
 pub fn set_item_secret(item: &Item, secret: &[u8]) -> Result<()> {
-    item.set_secret(secret, "text/plain").map_err(decode_error)
+    round_trip("set_secret", || item.set_secret(secret, "text/plain")).map_err(decode_error)
 }
 
 /// Given an existing item, retrieve and decode its password.
 pub fn get_item_password(item: &Item) -> Result<String> {
-    let bytes = item.get_secret().map_err(decode_error)?;
+    let bytes = round_trip("get_secret", || item.get_secret()).map_err(decode_error)?;
     decode_password(bytes)
 }
 
 /// Given an existing item, retrieve its secret.
 pub fn get_item_secret(item: &Item) -> Result<Vec<u8>> {
-    let secret = item.get_secret().map_err(decode_error)?;
+    let secret = round_trip("get_secret", || item.get_secret()).map_err(decode_error)?;
     Ok(secret)
 }
 
+/// Given an existing item, retrieve its creation and modification times.
+pub fn get_item_metadata(item: &Item) -> Result<CredentialMetadata> {
+    Ok(CredentialMetadata {
+        created: Some(Duration::from_secs(
+            item.get_created().map_err(decode_error)?,
+        )),
+        modified: Some(Duration::from_secs(
+            item.get_modified().map_err(decode_error)?,
+        )),
+        ..Default::default()
+    })
+}
+
 /// Given an existing item, retrieve its non-controlled attributes.
 pub fn get_item_attributes(item: &Item) -> Result<HashMap<String, String>> {
     let mut attributes = item.get_attributes().map_err(decode_error)?;
@@ -546,7 +1507,7 @@ pub fn update_item_attributes(item: &Item, attributes: &HashMap<&str, &str>) ->
 
 // Given an existing item, delete it.
 pub fn delete_item(item: &Item) -> Result<()> {
-    item.delete().map_err(decode_error)
+    round_trip("delete", || item.delete()).map_err(decode_error)
 }
 
 //
@@ -557,13 +1518,33 @@ pub fn delete_item(item: &Item) -> Result<()> {
 /// appropriate annotation.
 pub fn decode_error(err: Error) -> ErrorCode {
     match err {
-        Error::Locked => no_access(err),
+        Error::Locked => ErrorCode::Locked,
         Error::NoResult => no_access(err),
         Error::Prompt => no_access(err),
         _ => platform_failure(err),
     }
 }
 
+/// Map errors from operations on a particular item, treating
+/// the absence of the item as a [NoEntry](ErrorCode::NoEntry) error.
+fn item_error(err: Error) -> ErrorCode {
+    match &err {
+        Error::Dbus(dbus_err)
+            if matches!(
+                dbus_err.name(),
+                Some(
+                    "org.freedesktop.DBus.Error.UnknownObject"
+                        | "org.freedesktop.DBus.Error.UnknownMethod"
+                        | "org.freedesktop.Secret.Error.NoSuchObject"
+                )
+            ) =>
+        {
+            ErrorCode::NoEntry
+        }
+        _ => decode_error(err),
+    }
+}
+
 fn empty_target() -> ErrorCode {
     ErrorCode::Invalid("target".to_string(), "cannot be empty".to_string())
 }
@@ -582,11 +1563,17 @@ fn wrap(err: Error) -> Box<dyn std::error::Error + Send + Sync> {
 
 #[cfg(test)]
 mod tests {
-    use crate::credential::CredentialPersistence;
+    use crate::credential::{
+        AmbiguityMode, CredentialApi, CredentialBuilderApi, CredentialPersistence, Match,
+        SearchSpec,
+    };
     use crate::{Entry, Error, tests::generate_random_string};
     use std::collections::HashMap;
 
-    use super::{EncryptionType, SecretService, SsCredential, default_credential_builder};
+    use super::{
+        BusKind, EncryptionType, SecretService, SessionEncryption, SsCredential,
+        SsCredentialBuilder, decode_error, default_credential_builder, is_disconnect,
+    };
 
     #[test]
     fn test_persistence() {
@@ -600,6 +1587,272 @@ mod tests {
         crate::tests::entry_from_constructor(SsCredential::new_with_target, service, user)
     }
 
+    #[test]
+    fn test_bus() {
+        let builder = SsCredentialBuilder::with_bus(BusKind::Session);
+        assert!(builder.build(None, "service", "user").is_ok());
+        for bus in [
+            BusKind::System,
+            BusKind::Address("unix:path=/tmp/test-bus".to_string()),
+        ] {
+            let builder = SsCredentialBuilder::with_bus(bus);
+            assert!(
+                matches!(
+                    builder.build(None, "service", "user"),
+                    Err(Error::NotSupportedByStore(_))
+                ),
+                "Built credential for {:?} bus",
+                builder.bus()
+            );
+        }
+    }
+
+    #[test]
+    fn test_collection() {
+        let builder = SsCredentialBuilder::default().with_collection(Some("keyring-rs-test"));
+        assert_eq!(builder.collection(), Some("keyring-rs-test"));
+        for (target, expected) in [(None, "keyring-rs-test"), (Some("other"), "other")] {
+            let cred = builder.build(target, "service", "user").unwrap();
+            let spec = cred.spec().unwrap();
+            assert_eq!(spec.target.as_deref(), Some(expected));
+        }
+    }
+
+    #[test]
+    fn test_is_disconnect() {
+        // only dropped connections are retried, not other failures
+        assert!(!is_disconnect(&Error::NoEntry));
+        assert!(!is_disconnect(&decode_error(
+            dbus_secret_service::Error::Unavailable
+        )));
+        assert!(!is_disconnect(&decode_error(
+            dbus_secret_service::Error::Locked
+        )));
+        let io = std::io::Error::from(std::io::ErrorKind::BrokenPipe);
+        assert!(!is_disconnect(&Error::PlatformFailure(Box::new(io))));
+    }
+
+    #[test]
+    fn test_shared_connection() {
+        let builder = SsCredentialBuilder::default();
+        assert!(
+            builder
+                .connection
+                .take(SessionEncryption::Encrypted)
+                .is_none()
+        );
+        let name = generate_random_string();
+        let first = Entry::new_with_credential(builder.build(None, &name, "first").unwrap());
+        let second = Entry::new_with_credential(builder.build(None, &name, "second").unwrap());
+        first.set_password("first").unwrap();
+        // the connection made by the first entry is shared with the second
+        let ss = builder
+            .connection
+            .take(SessionEncryption::Encrypted)
+            .expect("No shared connection");
+        // it isn't used for plain sessions
+        assert!(builder.connection.take(SessionEncryption::Plain).is_none());
+        builder
+            .connection
+            .put_back(ss, SessionEncryption::Encrypted);
+        second.set_password("second").unwrap();
+        assert_eq!(first.get_password().unwrap(), "first");
+        assert_eq!(second.get_password().unwrap(), "second");
+        first.delete_credential().unwrap();
+        second.delete_credential().unwrap();
+        assert!(
+            builder
+                .connection
+                .take(SessionEncryption::Encrypted)
+                .is_some()
+        );
+        // credentials made without a builder don't share connections
+        let cred = SsCredential::new_with_target(None, &name, "first").unwrap();
+        assert!(cred.connection.is_none());
+    }
+
+    #[test]
+    fn test_session_encryption() {
+        let builder = SsCredentialBuilder::default();
+        assert_eq!(builder.session_encryption(), SessionEncryption::Encrypted);
+        let builder = builder.with_session_encryption(SessionEncryption::Plain);
+        assert_eq!(builder.session_encryption(), SessionEncryption::Plain);
+        let name = generate_random_string();
+        let cred = builder.build(None, &name, "user").unwrap();
+        let ss_cred: &SsCredential = cred.as_any().downcast_ref().unwrap();
+        assert_eq!(ss_cred.session_encryption(), SessionEncryption::Plain);
+        let entry = Entry::new_with_credential(cred);
+        entry.set_password("in the clear").unwrap();
+        assert!(builder.connection.take(SessionEncryption::Plain).is_some());
+        // entries with encrypted sessions see the same items
+        let encrypted = SsCredential::new_with_target(None, &name, "user").unwrap();
+        assert_eq!(encrypted.session_encryption(), SessionEncryption::Encrypted);
+        assert_eq!(encrypted.get_password().unwrap(), "in the clear");
+        entry.delete_credential().unwrap();
+    }
+
+    #[test]
+    fn test_item_path() {
+        let name = generate_random_string();
+        let entry = entry_new(&name, &name);
+        entry.set_password("by path").unwrap();
+        let cred: &SsCredential = entry.get_credential().downcast_ref().unwrap();
+        let paths = cred.get_item_paths().unwrap();
+        assert_eq!(paths.len(), 1);
+        let by_path = SsCredential::new_with_item_path(&paths[0]).unwrap();
+        assert_eq!(by_path.item_path(), Some(paths[0].as_str()));
+        assert_eq!(by_path.attributes, cred.attributes);
+        assert_eq!(by_path.get_password().unwrap(), "by path");
+        by_path.set_password("changed by path").unwrap();
+        assert_eq!(entry.get_password().unwrap(), "changed by path");
+        by_path.delete_credential().unwrap();
+        assert!(!by_path.exists().unwrap());
+        assert!(matches!(by_path.get_password(), Err(Error::NoEntry)));
+        // items are never created at a path
+        assert!(matches!(by_path.set_password("gone"), Err(Error::NoEntry)));
+        assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+        assert!(matches!(
+            SsCredential::new_with_item_path(&paths[0]),
+            Err(Error::NoEntry)
+        ));
+        assert!(matches!(
+            SsCredential::new_with_item_path("not a path"),
+            Err(Error::Invalid(_, _))
+        ));
+    }
+
+    #[test]
+    fn test_custom_attributes() {
+        let builder = SsCredentialBuilder::default().with_attributes(&HashMap::from([
+            ("profile", "work"),
+            ("service", "ignored"),
+        ]));
+        assert_eq!(builder.attributes()["profile"], "work");
+        let name = generate_random_string();
+        let entry = Entry::new_with_credential(builder.build(None, &name, &name).unwrap());
+        entry
+            .set_password("custom attributes")
+            .expect("Can't set password with custom attributes");
+        let attributes = entry.get_attributes().unwrap();
+        assert_eq!(attributes["profile"], "work");
+        // an entry without the custom attribute doesn't match it
+        let plain = Entry::new_with_credential(
+            SsCredentialBuilder::default()
+                .with_attributes(&HashMap::from([("profile", "home")]))
+                .build(None, &name, &name)
+                .unwrap(),
+        );
+        assert!(matches!(plain.get_password(), Err(Error::NoEntry)));
+        entry
+            .delete_credential()
+            .expect("Can't delete credential with custom attributes");
+    }
+
+    #[test]
+    fn test_ambiguity() {
+        let name = generate_random_string();
+        let cred = SsCredential::new_with_target(None, &name, &name)
+            .expect("Can't create credential for ambiguity");
+        let ss = SecretService::connect(EncryptionType::Dh).expect("Can't connect");
+        let collection = ss
+            .get_default_collection()
+            .expect("Can't get default collection");
+        for copy in ["1", "2"] {
+            let mut attributes = cred.all_attributes();
+            attributes.insert("copy", copy);
+            collection
+                .create_item(
+                    &cred.label,
+                    attributes,
+                    copy.as_bytes(),
+                    false,
+                    "text/plain",
+                )
+                .expect("Can't create ambiguous item");
+        }
+        assert!(
+            matches!(cred.get_password(), Err(Error::Ambiguous(_))),
+            "Strict credential wasn't ambiguous"
+        );
+        let first = cred.clone().with_ambiguity(AmbiguityMode::FirstMatch);
+        let password = first.get_password().expect("Can't get first password");
+        for _ in 0..3 {
+            assert_eq!(first.get_password().unwrap(), password);
+        }
+        cred.delete_all_passwords()
+            .expect("Can't delete ambiguous items");
+    }
+
+    #[test]
+    fn test_auto_dedupe() {
+        let name = generate_random_string();
+        let cred = SsCredential::new_with_target(None, &name, &name)
+            .expect("Can't create credential for dedupe");
+        let ss = SecretService::connect(EncryptionType::Dh).expect("Can't connect");
+        let collection = ss
+            .get_default_collection()
+            .expect("Can't get default collection");
+        for copy in ["1", "2", "3"] {
+            let mut attributes = cred.all_attributes();
+            attributes.insert("copy", copy);
+            collection
+                .create_item(
+                    &cred.label,
+                    attributes,
+                    copy.as_bytes(),
+                    false,
+                    "text/plain",
+                )
+                .expect("Can't create duplicate item");
+        }
+        assert!(
+            matches!(cred.set_password("deduped"), Err(Error::Ambiguous(_))),
+            "Set password without dedupe wasn't ambiguous"
+        );
+        let deduping = cred.clone().with_auto_dedupe(true);
+        deduping
+            .set_password("deduped")
+            .expect("Can't set password with dedupe");
+        assert_eq!(cred.get_password().unwrap(), "deduped");
+        assert_eq!(cred.dedupe().unwrap(), 0);
+        cred.delete_credential().expect("Can't delete deduped item");
+    }
+
+    #[test]
+    fn test_non_interactive() {
+        let builder = SsCredentialBuilder::default().with_non_interactive(true);
+        assert!(builder.non_interactive());
+        let name = generate_random_string();
+        let entry = Entry::new_with_credential(builder.build(None, &name, &name).unwrap());
+        // the default collection is unlocked during testing, so there are no prompts
+        entry
+            .set_password("non-interactive")
+            .expect("Can't set non-interactive password");
+        let password = entry.with_interaction_allowed(|entry| entry.get_password());
+        assert_eq!(password.unwrap(), "non-interactive");
+        entry
+            .delete_credential()
+            .expect("Can't delete non-interactive credential");
+    }
+
+    #[test]
+    fn test_unlock() {
+        let name = generate_random_string();
+        let entry = Entry::new_with_credential(
+            SsCredentialBuilder::default()
+                .with_non_interactive(true)
+                .build(None, &name, &name)
+                .unwrap(),
+        );
+        // the default collection exists, even if the item doesn't
+        entry.unlock().expect("Can't unlock default collection");
+        let missing = SsCredential::new_with_target(Some(&name), &name, &name).unwrap();
+        assert!(matches!(
+            Entry::new_with_credential(Box::new(missing)).unlock(),
+            Err(Error::NoEntry)
+        ));
+    }
+
     #[test]
     fn test_invalid_parameter() {
         let credential = SsCredential::new_with_target(Some(""), "service", "user");
@@ -672,6 +1925,57 @@ mod tests {
         assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
     }
 
+    #[test]
+    fn test_search() {
+        let service = generate_random_string();
+        let users = ["tenant1:alice", "tenant1:bob", "tenant2:carol"];
+        for user in users {
+            entry_new(&service, user)
+                .set_password("test search")
+                .expect("Can't set password for search");
+        }
+        let spec = SearchSpec {
+            service: Match::Exact(service.clone()),
+            user: Match::Prefix("tenant1:".to_string()),
+            ..Default::default()
+        };
+        let found = default_credential_builder()
+            .search(&spec)
+            .expect("Can't search");
+        let found_users: Vec<String> = found
+            .iter()
+            .map(|cred| {
+                let cred: &SsCredential = cred.as_any().downcast_ref().unwrap();
+                cred.attributes["username"].clone()
+            })
+            .collect();
+        assert_eq!(found_users, vec!["tenant1:alice", "tenant1:bob"]);
+        for user in users {
+            entry_new(&service, user)
+                .delete_credential()
+                .expect("Can't delete search credential");
+        }
+    }
+
+    #[test]
+    fn test_raw_search() {
+        let name = generate_random_string();
+        let entry = entry_new(&name, &name);
+        entry
+            .set_password("test raw search")
+            .expect("Can't set password for raw search");
+        let attributes = HashMap::from([
+            ("service".to_string(), name.clone()),
+            ("application".to_string(), "rust-keyring".to_string()),
+        ]);
+        let found = SsCredential::raw_search(&attributes).expect("Can't do raw search");
+        assert_eq!(found.len(), 1, "Wrong number of raw search results");
+        assert_eq!(found[0].attributes["username"], name);
+        entry
+            .delete_credential()
+            .expect("Can't delete raw search credential");
+    }
+
     #[test]
     fn test_get_update_attributes() {
         let name = generate_random_string();
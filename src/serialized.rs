@@ -0,0 +1,142 @@
+/*!
+
+# Structured secrets
+
+Many clients need to store more than a single string in a credential:
+a token and its expiration time, a username and password pair, or
+some other small structure. This module provides [Entry] methods that
+serialize any [Serialize] value as the entry's secret, and deserialize
+the stored secret back into a value of the client's choosing.
+
+Values are serialized as JSON, so they can be read by any client of the
+underlying credential store that can parse JSON. (The `oauth` feature's
+token sets are stored the same way.) The buffers that hold serialized
+secrets are zeroized when they are freed, including the ones that are
+outgrown while a value is being serialized.
+
+This module is only available if the `serde` feature is specified.
+
+```
+# use keyring::{Entry, mock};
+# keyring::set_default_credential_builder(mock::default_credential_builder());
+use std::collections::HashMap;
+
+let entry = Entry::new("my-service", "my-name").unwrap();
+let value = HashMap::from([("token", "abc"), ("scope", "read")]);
+entry.set_serialized(&value).unwrap();
+let stored: HashMap<String, String> = entry.get_deserialized().unwrap();
+assert_eq!(stored["token"], "abc");
+```
+ */
+use serde::{Serialize, de::DeserializeOwned};
+use zeroize::Zeroizing;
+
+use super::Entry;
+use super::error::{Error, Result};
+
+impl Entry {
+    /// Set the secret for this entry to the serialized form of a value.
+    ///
+    /// Returns an [Invalid](Error::Invalid) error if the value can't be serialized.
+    /// See [set_secret](Entry::set_secret) for the other errors this can return.
+    pub fn set_serialized<T: Serialize + ?Sized>(&self, value: &T) -> Result<()> {
+        let mut secret = ZeroizingWriter::default();
+        serde_json::to_writer(&mut secret, value).map_err(invalid)?;
+        self.set_secret(&secret.0)
+    }
+
+    /// Retrieve the value saved for this entry by
+    /// [set_serialized](Entry::set_serialized).
+    ///
+    /// Returns a [NoEntry](Error::NoEntry) error if there isn't one,
+    /// and an [Invalid](Error::Invalid) error if the saved secret
+    /// can't be deserialized as a value of the requested type.
+    ///
+    /// See [get_secret](Entry::get_secret) for the other errors this can return.
+    pub fn get_deserialized<T: DeserializeOwned>(&self) -> Result<T> {
+        let secret = self.get_secret_zeroizing()?;
+        serde_json::from_slice(&secret).map_err(invalid)
+    }
+}
+
+/// A buffer that zeroizes its contents when it's dropped and when it grows
+/// (which moves its contents to a new allocation).
+#[derive(Default)]
+struct ZeroizingWriter(Zeroizing<Vec<u8>>);
+
+impl std::io::Write for ZeroizingWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let needed = self.0.len() + buf.len();
+        if needed > self.0.capacity() {
+            let mut grown = Zeroizing::new(Vec::with_capacity(needed.max(64).next_power_of_two()));
+            grown.extend_from_slice(&self.0);
+            self.0 = grown;
+        }
+        self.0.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+fn invalid(err: serde_json::Error) -> Error {
+    Error::Invalid("serialized value".to_string(), err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::ZeroizingWriter;
+    use crate::mock::MockCredential;
+    use crate::{Entry, Error};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Login {
+        user: String,
+        password: String,
+        expires: Option<u64>,
+    }
+
+    fn entry_new() -> Entry {
+        Entry::new_with_credential(Box::new(MockCredential::default()))
+    }
+
+    #[test]
+    fn test_round_trip_serialized() {
+        let entry = entry_new();
+        let login = Login {
+            user: "test user".to_string(),
+            password: "test password".to_string(),
+            expires: Some(1_700_000_000),
+        };
+        entry.set_serialized(&login).expect("Can't set value");
+        let stored: Login = entry.get_deserialized().expect("Can't get value");
+        assert_eq!(stored, login, "Retrieved and set values don't match");
+        assert!(
+            matches!(entry.get_deserialized::<u64>(), Err(Error::Invalid(_, _))),
+            "Read a value of the wrong type"
+        );
+        entry.delete_credential().expect("Can't delete value");
+        assert!(
+            matches!(entry.get_deserialized::<Login>(), Err(Error::NoEntry)),
+            "Able to read a deleted value"
+        );
+    }
+
+    #[test]
+    fn test_zeroizing_writer() {
+        use std::io::Write;
+
+        let mut writer = ZeroizingWriter::default();
+        for chunk in [&b"a"[..], &[b'b'; 100], b"c"] {
+            writer.write_all(chunk).unwrap();
+        }
+        assert_eq!(writer.0.len(), 102);
+        assert_eq!(writer.0[0], b'a');
+        assert_eq!(writer.0[101], b'c');
+        assert!(writer.0.capacity() >= 128);
+    }
+}
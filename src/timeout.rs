@@ -0,0 +1,331 @@
+/*!
+
+# Timeouts on store operations
+
+A credential store that stops responding (a hung DBus daemon, say, or
+a keychain waiting on a prompt that never appears) can block the calls
+on its credentials forever. The [TimeoutCredentialBuilder] in this module
+wraps another builder, and gives every operation on the credentials it
+builds a time limit: if the wrapped credential hasn't finished the
+operation in time, the call returns a [Timeout](crate::Error::Timeout) error.
+
+```
+# use std::time::Duration;
+# use keyring::{Entry, Error, credential::CredentialBuilderApi, mock::MockCredentialBuilder};
+# use keyring::timeout::TimeoutCredentialBuilder;
+let slow = MockCredentialBuilder::default().with_delay(Duration::from_millis(500));
+let builder = TimeoutCredentialBuilder::new(Box::new(slow), Duration::from_millis(50));
+let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+assert!(matches!(entry.set_password("too slow"), Err(Error::Timeout)));
+```
+
+Blocking platform calls can't be interrupted, so each operation runs on
+a watchdog thread of its own, and an operation that times out is left to
+finish (or not) on that thread; its result is discarded (and secrets
+that it read are zeroized). This means an operation that returns a
+[Timeout](crate::Error::Timeout) error may still finish, and take effect,
+afterwards: a `set_password` that times out can still change the stored
+password, and a `delete_credential` can still delete the credential.
+Apps that retry after a timeout should be prepared to find their change
+already made.
+Operations run inside [Entry::with_interaction_allowed](crate::Entry::with_interaction_allowed)
+keep that permission on their threads.
+
+Searches through a timeout builder are limited too. Credentials returned
+by a search, or attached to an [Ambiguous](crate::Error::Ambiguous) error,
+are those of the wrapped store, so operations on them have no time limit.
+ */
+use std::panic::{AssertUnwindSafe, catch_unwind, resume_unwind};
+use std::sync::{Arc, mpsc};
+use std::time::Duration;
+
+use zeroize::{Zeroize, Zeroizing};
+
+use super::credential::{
+    AtRestStatus, AttributeMap, Credential, CredentialApi, CredentialBuilder, CredentialBuilderApi,
+    CredentialMetadata, CredentialPersistence, CredentialSpec, CredentialStoreInfo, SearchSpec,
+};
+use super::error::{Error, Result};
+use super::{INTERACTION_ALLOWED, interaction_allowed_override};
+
+/// A credential builder whose credentials give up on operations
+/// that take longer than a timeout.
+#[derive(Debug)]
+pub struct TimeoutCredentialBuilder {
+    inner: Arc<CredentialBuilder>,
+    timeout: Duration,
+}
+
+impl TimeoutCredentialBuilder {
+    /// Create a builder that limits the operations on the credentials
+    /// built by `inner` (and its searches) to `timeout`.
+    ///
+    /// Operations that time out aren't cancelled, so they may still
+    /// take effect after the timeout error is returned.
+    pub fn new(inner: Box<CredentialBuilder>, timeout: Duration) -> Self {
+        Self {
+            inner: Arc::from(inner),
+            timeout,
+        }
+    }
+
+    /// The wrapped builder.
+    pub fn inner(&self) -> &CredentialBuilder {
+        self.inner.as_ref()
+    }
+
+    /// How long this builder's operations may take.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+}
+
+impl CredentialBuilderApi for TimeoutCredentialBuilder {
+    /// Build a credential in the wrapped store, and limit its operations.
+    ///
+    /// Building a credential doesn't touch the store, so it has no time limit.
+    fn build(&self, target: Option<&str>, service: &str, user: &str) -> Result<Box<Credential>> {
+        let inner = self.inner.build(target, service, user)?;
+        Ok(Box::new(TimeoutCredential::new(inner, self.timeout)))
+    }
+
+    /// The at-rest encryption of the wrapped store.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        self.inner.at_rest_encryption()
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    /// The persistence of the wrapped store.
+    fn persistence(&self) -> CredentialPersistence {
+        self.inner.persistence()
+    }
+
+    /// Search the wrapped store, giving up after the timeout.
+    fn search(&self, spec: &SearchSpec) -> Result<Vec<Box<Credential>>> {
+        let inner = self.inner.clone();
+        let spec = spec.clone();
+        with_timeout(self.timeout, move || inner.search(&spec))
+    }
+
+    /// The info of the wrapped store.
+    fn info(&self) -> CredentialStoreInfo {
+        self.inner.info()
+    }
+}
+
+/// A credential whose operations give up after a timeout.
+#[derive(Debug)]
+pub struct TimeoutCredential {
+    inner: Arc<Credential>,
+    timeout: Duration,
+}
+
+impl TimeoutCredential {
+    /// Wrap a credential, limiting its operations to `timeout`.
+    pub fn new(inner: Box<Credential>, timeout: Duration) -> Self {
+        Self {
+            inner: Arc::from(inner),
+            timeout,
+        }
+    }
+
+    /// The wrapped credential.
+    pub fn inner(&self) -> &Credential {
+        self.inner.as_ref()
+    }
+
+    /// Run an operation on the wrapped credential, giving up after the timeout.
+    fn run<T: Send + 'static>(
+        &self,
+        op: impl FnOnce(&Credential) -> Result<T> + Send + 'static,
+    ) -> Result<T> {
+        let inner = self.inner.clone();
+        with_timeout(self.timeout, move || op(inner.as_ref()))
+    }
+
+    /// Run an operation that returns a secret, giving up after the timeout.
+    ///
+    /// The secret is zeroized if the operation finishes after the timeout,
+    /// when there's no one left to take it.
+    fn run_secret<T: Zeroize + Default + Send + 'static>(
+        &self,
+        op: impl FnOnce(&Credential) -> Result<T> + Send + 'static,
+    ) -> Result<T> {
+        let mut secret = self.run(move |inner| op(inner).map(Zeroizing::new))?;
+        Ok(std::mem::take(&mut *secret))
+    }
+}
+
+impl CredentialApi for TimeoutCredential {
+    fn set_password(&self, password: &str) -> Result<()> {
+        let password = Zeroizing::new(password.to_string());
+        self.run(move |inner| inner.set_password(&password))
+    }
+
+    fn set_secret(&self, secret: &[u8]) -> Result<()> {
+        let secret = Zeroizing::new(secret.to_vec());
+        self.run(move |inner| inner.set_secret(&secret))
+    }
+
+    fn get_password(&self) -> Result<String> {
+        self.run_secret(|inner| inner.get_password())
+    }
+
+    fn get_secret(&self) -> Result<Vec<u8>> {
+        self.run_secret(|inner| inner.get_secret())
+    }
+
+    fn exists(&self) -> Result<bool> {
+        self.run(|inner| inner.exists())
+    }
+
+    fn get_password_or_set(&self, password: &str) -> Result<String> {
+        let password = Zeroizing::new(password.to_string());
+        self.run_secret(move |inner| inner.get_password_or_set(&password))
+    }
+
+    fn get_secret_or_set(&self, secret: &[u8]) -> Result<Vec<u8>> {
+        let secret = Zeroizing::new(secret.to_vec());
+        self.run_secret(move |inner| inner.get_secret_or_set(&secret))
+    }
+
+    fn compare_and_set_password(&self, expected: &str, password: &str) -> Result<()> {
+        let expected = Zeroizing::new(expected.to_string());
+        let password = Zeroizing::new(password.to_string());
+        self.run(move |inner| inner.compare_and_set_password(&expected, &password))
+    }
+
+    fn compare_and_set_secret(&self, expected: &[u8], secret: &[u8]) -> Result<()> {
+        let expected = Zeroizing::new(expected.to_vec());
+        let secret = Zeroizing::new(secret.to_vec());
+        self.run(move |inner| inner.compare_and_set_secret(&expected, &secret))
+    }
+
+    fn get_attributes(&self) -> Result<AttributeMap<String, String>> {
+        self.run(|inner| inner.get_attributes())
+    }
+
+    fn update_attributes(&self, attributes: &AttributeMap<&str, &str>) -> Result<()> {
+        let attributes: AttributeMap<String, String> = attributes
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        self.run(move |inner| {
+            let attributes = attributes
+                .iter()
+                .map(|(key, value)| (key.as_str(), value.as_str()))
+                .collect();
+            inner.update_attributes(&attributes)
+        })
+    }
+
+    fn get_metadata(&self) -> Result<CredentialMetadata> {
+        self.run(|inner| inner.get_metadata())
+    }
+
+    fn get_label(&self) -> Result<String> {
+        self.run(|inner| inner.get_label())
+    }
+
+    fn set_label(&self, label: &str) -> Result<()> {
+        let label = label.to_string();
+        self.run(move |inner| inner.set_label(&label))
+    }
+
+    fn set_expiration(&self, ttl: Duration) -> Result<()> {
+        self.run(move |inner| inner.set_expiration(ttl))
+    }
+
+    fn unlock(&self) -> Result<()> {
+        self.run(|inner| inner.unlock())
+    }
+
+    fn max_secret_len(&self) -> Option<usize> {
+        self.inner.max_secret_len()
+    }
+
+    fn spec(&self) -> Option<CredentialSpec> {
+        self.inner.spec()
+    }
+
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        self.inner.at_rest_encryption()
+    }
+
+    fn delete_credential(&self) -> Result<()> {
+        self.run(|inner| inner.delete_credential())
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+/// Run an operation on a watchdog thread, giving up on it after `timeout`.
+///
+/// A panic in the operation is passed on to the caller. An operation that's
+/// given up on keeps running, and may still take effect; its result is
+/// dropped (on the watchdog thread) when it finishes.
+fn with_timeout<T: Send + 'static>(
+    timeout: Duration,
+    op: impl FnOnce() -> Result<T> + Send + 'static,
+) -> Result<T> {
+    let (sender, receiver) = mpsc::sync_channel(1);
+    let allowed = interaction_allowed_override();
+    std::thread::Builder::new()
+        .name("keyring-watchdog".to_string())
+        .spawn(move || {
+            INTERACTION_ALLOWED.with(|cell| cell.set(allowed));
+            // the receiver is gone if the operation timed out,
+            // in which case the result is dropped right here
+            let _ = sender.send(catch_unwind(AssertUnwindSafe(op)));
+        })
+        .map_err(|err| Error::PlatformFailure(Box::new(err)))?;
+    match receiver.recv_timeout(timeout) {
+        Ok(Ok(result)) => result,
+        Ok(Err(panic)) => resume_unwind(panic),
+        Err(mpsc::RecvTimeoutError::Timeout) => Err(Error::Timeout),
+        Err(mpsc::RecvTimeoutError::Disconnected) => {
+            unreachable!("The watchdog thread always sends a result")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::TimeoutCredentialBuilder;
+    use crate::credential::{AttributeMap, CredentialBuilderApi};
+    use crate::mock::MockCredentialBuilder;
+    use crate::{Entry, Error};
+
+    #[test]
+    fn test_timeout() {
+        let fast = MockCredentialBuilder::shared();
+        let builder = TimeoutCredentialBuilder::new(Box::new(fast), Duration::from_secs(5));
+        let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+        entry.set_password("in time").unwrap();
+        assert_eq!(entry.get_password().unwrap(), "in time");
+        let attributes = AttributeMap::from([("key", "value")]);
+        entry.update_attributes(&attributes).unwrap();
+        entry.delete_credential().unwrap();
+        assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+
+        let slow = MockCredentialBuilder::shared().with_delay(Duration::from_millis(500));
+        let builder = TimeoutCredentialBuilder::new(Box::new(slow), Duration::from_millis(50));
+        let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+        assert!(matches!(entry.set_password("late"), Err(Error::Timeout)));
+        // the operation that timed out still finishes on its own thread
+        std::thread::sleep(Duration::from_millis(1000));
+        let inner = builder.inner().build(None, "service", "user").unwrap();
+        assert_eq!(inner.get_password().unwrap(), "late");
+        // and so do reads, whose secrets are then dropped on that thread
+        assert!(matches!(entry.get_password(), Err(Error::Timeout)));
+        assert!(matches!(entry.get_secret(), Err(Error::Timeout)));
+        std::thread::sleep(Duration::from_millis(1000));
+    }
+}
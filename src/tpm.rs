@@ -0,0 +1,697 @@
+/*!
+
+# TPM 2.0 sealed file credential store
+
+Headless Linux machines (such as servers and appliances) usually have no
+user keyring daemon, but many have a TPM. This module keeps each credential
+in a file of its own, encrypted (with the [crypto](crate::crypto) module)
+under a random key that is _sealed_ to the machine's TPM, so the file can
+only be decrypted on the machine that wrote it. Optionally, the key can
+also be bound to the values of some of the TPM's platform configuration
+registers (PCRs), so that it can only be unsealed while the machine is in
+the same (measured) state, for example, booted with the same firmware and
+boot loader. It's available when the `tpm` feature is enabled. It's not
+the default store, so clients have to set it as their default (or build
+their credentials from it directly):
+```no_run
+keyring::set_default_credential_builder(keyring::tpm::default_credential_builder());
+```
+
+This module talks to the TPM through the
+[tpm2-tss](https://github.com/tpm2-software/tpm2-tss) libraries
+(by way of the [tss-esapi](https://docs.rs/tss-esapi) crate), which must be
+installed. By default, it uses the TPM named by the `TPM2TOOLS_TCTI` (or
+`TCTI`) environment variable, as the `tpm2-tools` do, or else the kernel's
+resource manager (`/dev/tpmrm0`); a builder can be given another TCTI (such
+as `swtpm:port=2321` for a software TPM) with
+[with_tcti](TpmCredentialBuilder::with_tcti). Keys are sealed under a
+primary storage key in the owner hierarchy, whose authorization must be
+empty (as it is unless the machine's owner has set one). A TPM that can't
+be reached produces a [NoStorageAccess](crate::Error::NoStorageAccess)
+error, as does unsealing a key whose PCRs have changed.
+
+By default, the files are kept in the `keyring-rs/tpm` folder of the user's
+data directory (`$XDG_DATA_HOME`, or `~/.local/share`), which is created
+when the first credential is stored; a builder can be given another folder
+with [with_dir](TpmCredentialBuilder::with_dir). Each file's name is made from
+the hex encoding of its credential's target, service, and user, and the name
+is sealed along with the secret, so a file that's renamed (or copied over
+another) won't decrypt. Each file records the PCRs its key was bound to, so
+changing a builder's PCRs only affects the secrets it sets afterwards.
+
+The tss-esapi crate doesn't support Windows' TPM Base Services, so this store
+is only available on Linux. Credentials have no attributes. The store supports
+[search](crate::search).
+ */
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use sha2::{Digest as _, Sha256};
+use tss_esapi::Context;
+use tss_esapi::abstraction::pcr::read_all;
+use tss_esapi::attributes::{ObjectAttributesBuilder, SessionAttributesBuilder};
+use tss_esapi::constants::SessionType;
+use tss_esapi::handles::{KeyHandle, SessionHandle};
+use tss_esapi::interface_types::algorithm::{HashingAlgorithm, PublicAlgorithm};
+use tss_esapi::interface_types::key_bits::RsaKeyBits;
+use tss_esapi::interface_types::resource_handles::Hierarchy;
+use tss_esapi::interface_types::session_handles::{AuthSession, PolicySession};
+use tss_esapi::structures::{
+    Digest, KeyedHashScheme, PcrSelectionList, PcrSelectionListBuilder, PcrSlot, Private, Public,
+    PublicBuilder, PublicKeyedHashParameters, RsaExponent, SensitiveData, SymmetricDefinition,
+    SymmetricDefinitionObject,
+};
+use tss_esapi::tcti_ldr::{DeviceConfig, TctiNameConf};
+use tss_esapi::traits::{Marshall, UnMarshall};
+use tss_esapi::utils::create_restricted_decryption_rsa_public;
+use zeroize::Zeroizing;
+
+use super::credential::{
+    AtRestStatus, Credential, CredentialApi, CredentialBuilder, CredentialBuilderApi,
+    CredentialPersistence, CredentialSpec, CredentialStoreInfo, SearchSpec,
+};
+use super::crypto::{KEY_LEN, open, seal};
+use super::error::{Error as ErrorCode, Result};
+
+/// The extension of credential files.
+const EXTENSION: &str = "tpm";
+
+/// The first bytes of every credential file (which identify its format).
+const MAGIC: &[u8] = b"keyring-rs tpm 1\n";
+
+/// The number of PCRs in a bank.
+const PCR_COUNT: u8 = 24;
+
+/// A credential kept in a file encrypted under a TPM-sealed key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TpmCredential {
+    pub target: Option<String>,
+    pub service: String,
+    pub user: String,
+    /// The folder that holds the credential's file
+    pub dir: PathBuf,
+    /// The SHA-256 PCRs that new keys are bound to
+    pub pcrs: Vec<u8>,
+    /// The TCTI used to reach the TPM, if it isn't the default
+    pub tcti: Option<String>,
+}
+
+impl CredentialApi for TpmCredential {
+    /// Seal a new key to the TPM, encrypt the secret with it,
+    /// and write both to this credential's file, replacing any existing one.
+    fn set_secret(&self, secret: &[u8]) -> Result<()> {
+        let name = self.file_name();
+        let mask = pcr_mask(&self.pcrs)?;
+        let mut context = self.context()?;
+        // the TPM's buffers zero themselves when they're dropped
+        let random = context.get_random(KEY_LEN).map_err(tpm_failure)?;
+        let key: Zeroizing<[u8; KEY_LEN]> =
+            Zeroizing::new(random.value().try_into().map_err(|_| {
+                ErrorCode::PlatformFailure(Box::from("TPM returned too few bytes"))
+            })?);
+        let (public, private) = seal_key(&mut context, key.as_slice(), mask)?;
+        let public = public.marshall().map_err(tpm_failure)?;
+        let mut contents = MAGIC.to_vec();
+        contents.extend_from_slice(&mask.to_le_bytes());
+        for part in [&public, private.value()] {
+            contents.extend_from_slice(&(part.len() as u16).to_le_bytes());
+            contents.extend_from_slice(part);
+        }
+        contents.extend_from_slice(&seal(&sealed_plaintext(&name, secret), &key));
+        std::fs::create_dir_all(&self.dir).map_err(platform_failure)?;
+        let temp = self.dir.join(format!("{name}.tmp"));
+        std::fs::write(&temp, &contents)
+            .and_then(|_| std::fs::rename(&temp, self.dir.join(&name)))
+            .map_err(platform_failure)
+    }
+
+    /// Unseal the key in this credential's file (if it exists),
+    /// and decrypt the secret with it.
+    ///
+    /// Returns a [NoEntry](ErrorCode::NoEntry) error if there is no file.
+    fn get_secret(&self) -> Result<Vec<u8>> {
+        let name = self.file_name();
+        let contents = match std::fs::read(self.dir.join(&name)) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return Err(ErrorCode::NoEntry);
+            }
+            Err(err) => return Err(platform_failure(err)),
+        };
+        let file =
+            SealedFile::parse(&contents).ok_or_else(|| invalid("isn't a TPM sealed file"))?;
+        let public =
+            Public::unmarshall(file.public).map_err(|_| invalid("has a bad public area"))?;
+        let private = Private::try_from(file.private.to_vec())
+            .map_err(|_| invalid("has a bad private area"))?;
+        let mut context = self.context()?;
+        let key = unseal_key(&mut context, public, private, file.mask)?;
+        let plaintext = Zeroizing::new(open(file.sealed, &key)?);
+        match plaintext.strip_prefix(name.as_bytes()) {
+            Some([b'\n', secret @ ..]) => Ok(secret.to_vec()),
+            _ => Err(invalid("was sealed for another credential")),
+        }
+    }
+
+    /// Whether this credential's file exists (which is checked without decrypting it).
+    fn exists(&self) -> Result<bool> {
+        self.dir
+            .join(self.file_name())
+            .try_exists()
+            .map_err(platform_failure)
+    }
+
+    /// Delete this credential's file, if it exists.
+    ///
+    /// Returns a [NoEntry](ErrorCode::NoEntry) error if there is no file.
+    fn delete_credential(&self) -> Result<()> {
+        match std::fs::remove_file(self.dir.join(self.file_name())) {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Err(ErrorCode::NoEntry),
+            Err(err) => Err(platform_failure(err)),
+        }
+    }
+
+    /// The target, service, and user of this credential.
+    fn spec(&self) -> Option<CredentialSpec> {
+        Some(CredentialSpec {
+            target: self.target.clone(),
+            service: self.service.clone(),
+            user: self.user.clone(),
+        })
+    }
+
+    /// The files are encrypted under TPM-sealed keys.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        AtRestStatus::Encrypted
+    }
+
+    /// Return the underlying concrete object with an `Any` type so that it can
+    /// be downgraded to a [TpmCredential] for platform-specific processing.
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    /// Expose the concrete debug formatter for use via the [Credential] trait
+    fn debug_fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self, f)
+    }
+}
+
+impl TpmCredential {
+    /// The name of this credential's file: the hex-encoded target
+    /// (or `-` if there is none), service, and user, separated by dots.
+    pub fn file_name(&self) -> String {
+        let target = match &self.target {
+            Some(target) => hex_encode(target.as_bytes()),
+            None => "-".to_string(),
+        };
+        format!(
+            "{target}.{}.{}.{EXTENSION}",
+            hex_encode(self.service.as_bytes()),
+            hex_encode(self.user.as_bytes())
+        )
+    }
+
+    /// Parse a file name made by [file_name](TpmCredential::file_name).
+    fn from_file_name(builder: &TpmCredentialBuilder, dir: &Path, name: &str) -> Option<Self> {
+        let mut parts = name.strip_suffix(&format!(".{EXTENSION}"))?.split('.');
+        let target = match parts.next()? {
+            "-" => None,
+            target => Some(hex_decode(target)?),
+        };
+        let service = hex_decode(parts.next()?)?;
+        let user = hex_decode(parts.next()?)?;
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(Self {
+            target,
+            service,
+            user,
+            dir: dir.to_path_buf(),
+            pcrs: builder.pcrs.clone(),
+            tcti: builder.tcti.clone(),
+        })
+    }
+
+    /// Open a context with this credential's TPM.
+    fn context(&self) -> Result<Context> {
+        let tcti = match &self.tcti {
+            Some(tcti) => TctiNameConf::from_str(tcti).map_err(|err| {
+                ErrorCode::Invalid("tcti".to_string(), format!("can't be parsed: {err}"))
+            })?,
+            None => TctiNameConf::from_environment_variable()
+                .unwrap_or_else(|_| TctiNameConf::Device(DeviceConfig::default())),
+        };
+        Context::new(tcti).map_err(|err| ErrorCode::NoStorageAccess(Box::new(err)))
+    }
+}
+
+/// The parts of a credential file.
+struct SealedFile<'a> {
+    mask: u32,
+    public: &'a [u8],
+    private: &'a [u8],
+    sealed: &'a [u8],
+}
+
+impl<'a> SealedFile<'a> {
+    fn parse(contents: &'a [u8]) -> Option<Self> {
+        let rest = contents.strip_prefix(MAGIC)?;
+        let (mask, rest) = rest.split_at_checked(4)?;
+        let (public, rest) = length_prefixed(rest)?;
+        let (private, sealed) = length_prefixed(rest)?;
+        Some(Self {
+            mask: u32::from_le_bytes(mask.try_into().ok()?),
+            public,
+            private,
+            sealed,
+        })
+    }
+}
+
+fn length_prefixed(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    let (len, rest) = data.split_at_checked(2)?;
+    let len = u16::from_le_bytes(len.try_into().ok()?) as usize;
+    rest.split_at_checked(len)
+}
+
+/// The bit mask of a list of PCRs.
+///
+/// Returns an [Invalid](ErrorCode::Invalid) error if one of them doesn't exist.
+fn pcr_mask(pcrs: &[u8]) -> Result<u32> {
+    let mut mask = 0;
+    for pcr in pcrs {
+        if *pcr >= PCR_COUNT {
+            return Err(ErrorCode::Invalid(
+                "pcrs".to_string(),
+                format!("PCR {pcr} doesn't exist"),
+            ));
+        }
+        mask |= 1 << pcr;
+    }
+    Ok(mask)
+}
+
+/// The selection of the SHA-256 PCRs in a bit mask.
+fn pcr_selection(mask: u32) -> Result<PcrSelectionList> {
+    let slots = (0..PCR_COUNT)
+        .filter(|pcr| mask & (1 << pcr) != 0)
+        .map(|pcr| PcrSlot::try_from(1u32 << pcr))
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(tpm_failure)?;
+    PcrSelectionListBuilder::new()
+        .with_selection(HashingAlgorithm::Sha256, &slots)
+        .build()
+        .map_err(tpm_failure)
+}
+
+/// Create the primary storage key that keys are sealed under.
+///
+/// The key is derived from the owner hierarchy's seed, so it's
+/// the same every time it's created.
+fn primary_key(context: &mut Context) -> Result<KeyHandle> {
+    let public = create_restricted_decryption_rsa_public(
+        SymmetricDefinitionObject::AES_128_CFB,
+        RsaKeyBits::Rsa2048,
+        RsaExponent::default(),
+    )
+    .map_err(tpm_failure)?;
+    context
+        .execute_with_nullauth_session(|context| {
+            context.create_primary(Hierarchy::Owner, public, None, None, None, None)
+        })
+        .map(|result| result.key_handle)
+        .map_err(tpm_failure)
+}
+
+/// Start a policy (or trial policy) session requiring the current values of the PCRs.
+fn pcr_policy(context: &mut Context, trial: bool, mask: u32) -> Result<AuthSession> {
+    let selection = pcr_selection(mask)?;
+    let session_type = if trial {
+        SessionType::Trial
+    } else {
+        SessionType::Policy
+    };
+    let session = context
+        .start_auth_session(
+            None,
+            None,
+            None,
+            session_type,
+            SymmetricDefinition::AES_128_CFB,
+            HashingAlgorithm::Sha256,
+        )
+        .map_err(tpm_failure)?
+        .ok_or_else(|| ErrorCode::PlatformFailure(Box::from("TPM started no session")))?;
+    let (attributes, mask_attributes) = SessionAttributesBuilder::new()
+        .with_decrypt(true)
+        .with_encrypt(true)
+        .build();
+    context
+        .tr_sess_set_attributes(session, attributes, mask_attributes)
+        .map_err(tpm_failure)?;
+    // a trial session is given the digest of the PCRs' values, but a real
+    // session is given none, so that the TPM uses the values it has now
+    let digest = if trial {
+        let values = read_all(context, selection.clone()).map_err(tpm_failure)?;
+        let bank = values.pcr_bank(HashingAlgorithm::Sha256);
+        let mut hasher = Sha256::new();
+        for (_, value) in bank.into_iter().flatten() {
+            hasher.update(value.value());
+        }
+        Digest::try_from(hasher.finalize().to_vec()).map_err(tpm_failure)?
+    } else {
+        Digest::default()
+    };
+    let policy = PolicySession::try_from(session).map_err(tpm_failure)?;
+    context
+        .policy_pcr(policy, digest, selection)
+        .map_err(tpm_failure)?;
+    Ok(session)
+}
+
+/// Seal a key to the TPM (and the current values of the given PCRs, if any).
+fn seal_key(context: &mut Context, key: &[u8], mask: u32) -> Result<(Public, Private)> {
+    let policy = if mask == 0 {
+        Digest::default()
+    } else {
+        let session = pcr_policy(context, true, mask)?;
+        let policy = context
+            .policy_get_digest(PolicySession::try_from(session).map_err(tpm_failure)?)
+            .map_err(tpm_failure)?;
+        context
+            .flush_context(SessionHandle::from(session).into())
+            .map_err(tpm_failure)?;
+        policy
+    };
+    let attributes = ObjectAttributesBuilder::new()
+        .with_fixed_tpm(true)
+        .with_fixed_parent(true)
+        .with_no_da(true)
+        .with_admin_with_policy(true)
+        .with_user_with_auth(mask == 0)
+        .build()
+        .map_err(tpm_failure)?;
+    let public = PublicBuilder::new()
+        .with_public_algorithm(PublicAlgorithm::KeyedHash)
+        .with_name_hashing_algorithm(HashingAlgorithm::Sha256)
+        .with_object_attributes(attributes)
+        .with_auth_policy(policy)
+        .with_keyed_hash_parameters(PublicKeyedHashParameters::new(KeyedHashScheme::Null))
+        .with_keyed_hash_unique_identifier(Digest::default())
+        .build()
+        .map_err(tpm_failure)?;
+    let sensitive = SensitiveData::try_from(key.to_vec()).map_err(tpm_failure)?;
+    let primary = primary_key(context)?;
+    let result = context
+        .execute_with_nullauth_session(|context| {
+            context.create(primary, public, None, Some(sensitive), None, None)
+        })
+        .map_err(tpm_failure)?;
+    context.flush_context(primary.into()).map_err(tpm_failure)?;
+    Ok((result.out_public, result.out_private))
+}
+
+/// Unseal a key sealed by [seal_key].
+fn unseal_key(
+    context: &mut Context,
+    public: Public,
+    private: Private,
+    mask: u32,
+) -> Result<Zeroizing<[u8; KEY_LEN]>> {
+    let primary = primary_key(context)?;
+    let sealed = context
+        .execute_with_nullauth_session(|context| context.load(primary, private, public))
+        .map_err(|err| ErrorCode::Invalid("credential file".to_string(), err.to_string()))?;
+    context.flush_context(primary.into()).map_err(tpm_failure)?;
+    let data = if mask == 0 {
+        context.execute_with_nullauth_session(|context| context.unseal(sealed.into()))
+    } else {
+        let session = pcr_policy(context, false, mask)?;
+        let data =
+            context.execute_with_session(Some(session), |context| context.unseal(sealed.into()));
+        context
+            .flush_context(SessionHandle::from(session).into())
+            .map_err(tpm_failure)?;
+        data
+    }
+    .map_err(|err| ErrorCode::NoStorageAccess(Box::new(err)))?;
+    context.flush_context(sealed.into()).map_err(tpm_failure)?;
+    let key: [u8; KEY_LEN] = data
+        .value()
+        .try_into()
+        .map_err(|_| invalid("has a sealed key of the wrong size"))?;
+    Ok(Zeroizing::new(key))
+}
+
+/// The plaintext that's encrypted: the file name, a newline, and the secret.
+fn sealed_plaintext(name: &str, secret: &[u8]) -> Zeroizing<Vec<u8>> {
+    let mut plaintext = Zeroizing::new(Vec::with_capacity(name.len() + 1 + secret.len()));
+    plaintext.extend_from_slice(name.as_bytes());
+    plaintext.push(b'\n');
+    plaintext.extend_from_slice(secret);
+    plaintext
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn hex_decode(hex: &str) -> Option<String> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    String::from_utf8(bytes).ok()
+}
+
+fn invalid(reason: &str) -> ErrorCode {
+    ErrorCode::Invalid("credential file".to_string(), reason.to_string())
+}
+
+fn tpm_failure(err: tss_esapi::Error) -> ErrorCode {
+    ErrorCode::PlatformFailure(Box::new(err))
+}
+
+fn platform_failure(err: std::io::Error) -> ErrorCode {
+    ErrorCode::PlatformFailure(Box::new(err))
+}
+
+/// The builder for TPM sealed file credentials.
+#[derive(Debug, Default, Clone)]
+pub struct TpmCredentialBuilder {
+    dir: Option<PathBuf>,
+    pcrs: Vec<u8>,
+    tcti: Option<String>,
+}
+
+/// Returns an instance of the TPM sealed file credential builder.
+///
+/// This is never called by the crate, since this is
+/// not the default store on Linux.
+pub fn default_credential_builder() -> Box<CredentialBuilder> {
+    Box::new(TpmCredentialBuilder::default())
+}
+
+impl TpmCredentialBuilder {
+    /// Return this builder with its credentials kept in the given folder.
+    pub fn with_dir(mut self, dir: impl AsRef<Path>) -> Self {
+        self.dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// The folder this builder's credentials are kept in, if one was given.
+    pub fn dir(&self) -> Option<&Path> {
+        self.dir.as_deref()
+    }
+
+    /// Return this builder with the keys of the secrets it sets bound to
+    /// the current values of the given SHA-256 PCRs (such as `[0, 7]`).
+    ///
+    /// PCRs that don't exist produce an [Invalid](ErrorCode::Invalid)
+    /// error when a secret is set.
+    pub fn with_pcrs(mut self, pcrs: &[u8]) -> Self {
+        self.pcrs = pcrs.to_vec();
+        self
+    }
+
+    /// The PCRs that the keys of this builder's secrets are bound to.
+    pub fn pcrs(&self) -> &[u8] {
+        &self.pcrs
+    }
+
+    /// Return this builder with its TPM reached by the given TCTI
+    /// (such as `device:/dev/tpm0`) rather than the default.
+    pub fn with_tcti(mut self, tcti: Option<&str>) -> Self {
+        self.tcti = tcti.map(|t| t.to_string());
+        self
+    }
+
+    /// The TCTI that this builder's TPM is reached by, if one was given.
+    pub fn tcti(&self) -> Option<&str> {
+        self.tcti.as_deref()
+    }
+
+    /// The folder to keep credentials in: the given one, or the default.
+    fn resolved_dir(&self) -> Result<PathBuf> {
+        if let Some(dir) = &self.dir {
+            return Ok(dir.clone());
+        }
+        let data_home = std::env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| {
+                std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share"))
+            });
+        match data_home {
+            Some(data_home) => Ok(data_home.join("keyring-rs").join("tpm")),
+            None => Err(ErrorCode::NoStorageAccess(Box::from(
+                "neither XDG_DATA_HOME nor HOME is set, so there's no folder for credential files",
+            ))),
+        }
+    }
+}
+
+impl CredentialBuilderApi for TpmCredentialBuilder {
+    /// Build a [TpmCredential] for the given target, service, and user.
+    fn build(&self, target: Option<&str>, service: &str, user: &str) -> Result<Box<Credential>> {
+        Ok(Box::new(TpmCredential {
+            target: target.map(|t| t.to_string()),
+            service: service.to_string(),
+            user: user.to_string(),
+            dir: self.resolved_dir()?,
+            pcrs: self.pcrs.clone(),
+            tcti: self.tcti.clone(),
+        }))
+    }
+
+    /// The files are encrypted under TPM-sealed keys.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        AtRestStatus::Encrypted
+    }
+
+    /// Return the underlying builder object with an `Any` type so that it can
+    /// be downgraded to a [TpmCredentialBuilder] for platform-specific processing.
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    /// Files are kept until they are deleted.
+    fn persistence(&self) -> CredentialPersistence {
+        CredentialPersistence::UntilDelete
+    }
+
+    /// Return a credential for each credential file that matches the spec.
+    fn search(&self, spec: &SearchSpec) -> Result<Vec<Box<Credential>>> {
+        let dir = self.resolved_dir()?;
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(platform_failure(err)),
+        };
+        let mut found = Vec::new();
+        for entry in entries {
+            let name = entry.map_err(platform_failure)?.file_name();
+            let Some(cred) = name
+                .to_str()
+                .and_then(|name| TpmCredential::from_file_name(self, &dir, name))
+            else {
+                continue;
+            };
+            if spec.matches(cred.target.as_deref(), &cred.service, &cred.user) {
+                found.push(cred);
+            }
+        }
+        if !spec.unsorted {
+            found.sort_by(|a, b| {
+                (&a.service, &a.user, &a.target).cmp(&(&b.service, &b.user, &b.target))
+            });
+        }
+        Ok(found
+            .into_iter()
+            .map(|cred| Box::new(cred) as Box<Credential>)
+            .collect())
+    }
+
+    /// The store can be searched, and its format version is that of its files.
+    fn info(&self) -> CredentialStoreInfo {
+        CredentialStoreInfo::new("tpm", self.persistence(), self.at_rest_encryption())
+            .with_version("1")
+            .with_search(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MAGIC, SealedFile, TpmCredential, TpmCredentialBuilder, pcr_mask};
+    use crate::credential::CredentialBuilderApi;
+    use crate::{Entry, Error, tests::generate_random_string};
+
+    #[test]
+    fn test_file_name() {
+        let dir = std::env::temp_dir();
+        let builder = TpmCredentialBuilder::default();
+        let cred = TpmCredential {
+            target: None,
+            service: "service".to_string(),
+            user: "user.name".to_string(),
+            dir: dir.clone(),
+            pcrs: Vec::new(),
+            tcti: None,
+        };
+        let name = cred.file_name();
+        assert_eq!(name, "-.73657276696365.757365722e6e616d65.tpm");
+        assert_eq!(
+            TpmCredential::from_file_name(&builder, &dir, &name),
+            Some(cred)
+        );
+        assert_eq!(TpmCredential::from_file_name(&builder, &dir, "x.tpm"), None);
+    }
+
+    #[test]
+    fn test_pcr_mask() {
+        assert_eq!(pcr_mask(&[]).unwrap(), 0);
+        assert_eq!(pcr_mask(&[0, 7]).unwrap(), 0x81);
+        assert!(matches!(pcr_mask(&[24]), Err(Error::Invalid(_, _))));
+    }
+
+    #[test]
+    fn test_sealed_file() {
+        let mut contents = MAGIC.to_vec();
+        contents.extend_from_slice(&0x81u32.to_le_bytes());
+        contents.extend_from_slice(&[2, 0, 1, 2, 1, 0, 3, 4, 5]);
+        let file = SealedFile::parse(&contents).unwrap();
+        assert_eq!(file.mask, 0x81);
+        assert_eq!(file.public, [1, 2]);
+        assert_eq!(file.private, [3]);
+        assert_eq!(file.sealed, [4, 5]);
+        assert!(SealedFile::parse(&contents[..MAGIC.len() + 6]).is_none());
+        assert!(SealedFile::parse(b"something else").is_none());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let dir = std::env::temp_dir().join(format!("keyring-tpm-{}", generate_random_string()));
+        let builder = TpmCredentialBuilder::default().with_dir(&dir);
+        let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+        assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+        let secret = vec![42u8; 10_000];
+        entry.set_secret(&secret).unwrap();
+        assert_eq!(entry.get_secret().unwrap(), secret);
+        let bound = TpmCredentialBuilder::default()
+            .with_dir(&dir)
+            .with_pcrs(&[0, 7])
+            .build(None, "service", "bound")
+            .unwrap();
+        bound.set_password("bound").unwrap();
+        assert_eq!(bound.get_password().unwrap(), "bound");
+        entry.delete_credential().unwrap();
+        assert!(matches!(entry.delete_credential(), Err(Error::NoEntry)));
+        bound.delete_credential().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
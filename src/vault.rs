@@ -0,0 +1,680 @@
+/*!
+
+# HashiCorp Vault credential store
+
+Server-side apps often keep their secrets in a central
+[HashiCorp Vault](https://developer.hashicorp.com/vault) rather than on
+the machine they run on. This module keeps each credential as a secret in
+a Vault [KV version 2](https://developer.hashicorp.com/vault/docs/secrets/kv/kv-v2)
+secrets engine, talking to Vault over its HTTP API. It's available
+(on every platform) when the `vault` feature is enabled. It's not the
+default store on any platform, so clients have to set it as their default
+(or build their credentials from it directly):
+```no_run
+use keyring::vault::{VaultAuth, VaultCredentialBuilder};
+
+let builder = VaultCredentialBuilder::new(
+    "https://vault.example.com:8200",
+    VaultAuth::AppRole {
+        role_id: "my-role-id".to_string(),
+        secret_id: "my-secret-id".to_string(),
+    },
+)
+.with_mount("kv");
+keyring::set_default_credential_builder(Box::new(builder));
+```
+The [default_credential_builder] is configured the way the `vault` CLI is:
+by the `VAULT_ADDR`, `VAULT_TOKEN` (or the `~/.vault-token` file), and
+`VAULT_NAMESPACE` environment variables.
+
+Clients authenticate either with a token or with an
+[AppRole](https://developer.hashicorp.com/vault/docs/auth/approle) role
+and secret ID, which are exchanged for a token when it's first needed
+(and again if the token is refused, say because it has expired).
+Tokens are shared by all the credentials built by a builder.
+
+For a given <_target_, _service_, _user_> triple, the secret's path in the
+engine (which is mounted at `secret` by default) is the builder's path
+(`keyring-rs` by default) followed by the target (if there is one), the
+service, and the user. Slashes and percent signs in those parts are
+percent-encoded, so each is a single path segment, and none of them may be
+empty. For example, the credential for service `my-app` and user `alice`
+is kept at `secret/keyring-rs/my-app/alice`. The secret's data has a single
+field: `secret`, if the secret is UTF-8, or otherwise `secret_hex`, which
+holds its hex encoding. Setting the secret writes a new version of the
+secret, and deleting the credential removes all of its versions.
+
+Credential attributes are kept in the secret's custom metadata, and the
+label is kept in its `label` attribute. This store doesn't support
+[search](crate::search).
+
+If Vault can't be reached, is sealed, or refuses the client's
+authentication, a [NoStorageAccess](crate::Error::NoStorageAccess) error
+is returned.
+ */
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde_json::{Map, Value, json};
+use zeroize::Zeroizing;
+
+use super::credential::{
+    AtRestStatus, AttributeMap, Credential, CredentialApi, CredentialBuilder, CredentialBuilderApi,
+    CredentialPersistence, CredentialSpec, CredentialStoreInfo,
+};
+use super::error::{Error as ErrorCode, Result};
+
+/// The address of the Vault server used when `VAULT_ADDR` isn't set.
+const DEFAULT_ADDRESS: &str = "http://127.0.0.1:8200";
+
+/// How long to wait for Vault to respond.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How a client authenticates to Vault.
+#[derive(Clone)]
+pub enum VaultAuth {
+    /// Use the given token.
+    Token(String),
+    /// Log in with the given AppRole role and secret ID
+    /// (using the auth method mounted at `approle`).
+    AppRole { role_id: String, secret_id: String },
+}
+
+impl std::fmt::Debug for VaultAuth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VaultAuth::Token(_) => f.write_str("Token(..)"),
+            VaultAuth::AppRole { role_id, .. } => f
+                .debug_struct("AppRole")
+                .field("role_id", role_id)
+                .finish_non_exhaustive(),
+        }
+    }
+}
+
+/// The server, engine, and authentication that credentials are kept with.
+#[derive(Clone)]
+struct Connection {
+    agent: ureq::Agent,
+    address: String,
+    namespace: Option<String>,
+    mount: String,
+    auth: Arc<VaultAuth>,
+    token: Arc<Mutex<Option<Zeroizing<String>>>>,
+}
+
+impl std::fmt::Debug for Connection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Connection")
+            .field("address", &self.address)
+            .field("namespace", &self.namespace)
+            .field("mount", &self.mount)
+            .field("auth", &self.auth)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Connection {
+    fn new(address: &str, auth: VaultAuth) -> Self {
+        Self {
+            agent: ureq::AgentBuilder::new().timeout(TIMEOUT).build(),
+            address: address.trim_end_matches('/').to_string(),
+            namespace: None,
+            mount: "secret".to_string(),
+            auth: Arc::new(auth),
+            token: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Make an authenticated request of the engine.
+    ///
+    /// Returns `None` if Vault responds that there's nothing at the path,
+    /// and `Null` if it responds with no content. If Vault refuses an AppRole
+    /// login's token, the client logs in again and retries the request once.
+    fn request(&self, method: &str, path: &str, body: Option<&Value>) -> Result<Option<Value>> {
+        let url = format!("{}/v1/{}/{path}", self.address, encode_path(&self.mount));
+        let token = self.token()?;
+        let response = match self.call(method, &url, Some(&token), body) {
+            Err(err)
+                if matches!(*err, ureq::Error::Status(403, _))
+                    && matches!(*self.auth, VaultAuth::AppRole { .. }) =>
+            {
+                self.token.lock().expect("Vault token lock poisoned").take();
+                let token = self.token()?;
+                self.call(method, &url, Some(&token), body)
+            }
+            response => response,
+        };
+        match response {
+            Ok(response) if response.status() == 204 => Ok(Some(Value::Null)),
+            Ok(response) => Ok(Some(response.into_json().map_err(io_failure)?)),
+            Err(err) if matches!(*err, ureq::Error::Status(404, _)) => Ok(None),
+            Err(err) => Err(decode_error(*err)),
+        }
+    }
+
+    fn call(
+        &self,
+        method: &str,
+        url: &str,
+        token: Option<&str>,
+        body: Option<&Value>,
+    ) -> std::result::Result<ureq::Response, Box<ureq::Error>> {
+        let mut request = self.agent.request(method, url);
+        if let Some(token) = token {
+            request = request.set("X-Vault-Token", token);
+        }
+        if let Some(namespace) = &self.namespace {
+            request = request.set("X-Vault-Namespace", namespace);
+        }
+        match body {
+            Some(body) => request.send_json(body),
+            None => request.call(),
+        }
+        .map_err(Box::new)
+    }
+
+    /// The client's token, logging in to get one if necessary.
+    fn token(&self) -> Result<Zeroizing<String>> {
+        let mut token = self.token.lock().expect("Vault token lock poisoned");
+        if let Some(token) = token.as_ref() {
+            return Ok(token.clone());
+        }
+        let new_token = match self.auth.as_ref() {
+            VaultAuth::Token(token) => Zeroizing::new(token.clone()),
+            VaultAuth::AppRole { role_id, secret_id } => {
+                let url = format!("{}/v1/auth/approle/login", self.address);
+                let body = json!({ "role_id": role_id, "secret_id": secret_id });
+                let response: Value = self
+                    .call("POST", &url, None, Some(&body))
+                    .map_err(|err| decode_error(*err))?
+                    .into_json()
+                    .map_err(io_failure)?;
+                match response["auth"]["client_token"].as_str() {
+                    Some(token) => Zeroizing::new(token.to_string()),
+                    None => return Err(failure("login response has no client token")),
+                }
+            }
+        };
+        *token = Some(new_token.clone());
+        Ok(new_token)
+    }
+}
+
+/// The representation of a Vault KV secret.
+#[derive(Debug, Clone)]
+pub struct VaultCredential {
+    target: Option<String>,
+    service: String,
+    user: String,
+    path: String,
+    connection: Connection,
+}
+
+impl CredentialApi for VaultCredential {
+    /// Write the secret as a new version of this credential's secret.
+    fn set_secret(&self, secret: &[u8]) -> Result<()> {
+        let data = match std::str::from_utf8(secret) {
+            Ok(secret) => json!({ "secret": secret }),
+            Err(_) => json!({ "secret_hex": to_hex(secret) }),
+        };
+        self.connection
+            .request("POST", &self.data_path(), Some(&json!({ "data": data })))?;
+        Ok(())
+    }
+
+    /// Read the latest version of this credential's secret, if it exists.
+    ///
+    /// Returns a [NoEntry](ErrorCode::NoEntry) error if there is no secret
+    /// (or its latest version has been deleted).
+    fn get_secret(&self) -> Result<Vec<u8>> {
+        let response = self
+            .connection
+            .request("GET", &self.data_path(), None)?
+            .ok_or(ErrorCode::NoEntry)?;
+        let data = &response["data"]["data"];
+        if let Some(secret) = data["secret"].as_str() {
+            return Ok(secret.as_bytes().to_vec());
+        }
+        data["secret_hex"]
+            .as_str()
+            .and_then(from_hex)
+            .ok_or_else(|| {
+                ErrorCode::Invalid(
+                    "stored secret".to_string(),
+                    "has no `secret` or hex-encoded `secret_hex` field".to_string(),
+                )
+            })
+    }
+
+    /// Get the custom metadata of this credential's secret, if it exists.
+    fn get_attributes(&self) -> Result<AttributeMap<String, String>> {
+        Ok(self
+            .custom_metadata()?
+            .into_iter()
+            .filter_map(|(key, value)| Some((key, value.as_str()?.to_string())))
+            .collect())
+    }
+
+    /// Update the custom metadata of this credential's secret, if it exists.
+    ///
+    /// Any attribute name can be given a value.
+    fn update_attributes(&self, attributes: &AttributeMap<&str, &str>) -> Result<()> {
+        let mut metadata = self.custom_metadata()?;
+        for (key, value) in attributes {
+            metadata.insert(key.to_string(), Value::from(*value));
+        }
+        let body = json!({ "custom_metadata": metadata });
+        self.connection
+            .request("POST", &self.metadata_path(), Some(&body))?;
+        Ok(())
+    }
+
+    /// The label is kept in the `label` attribute (and is empty if that isn't set).
+    fn get_label(&self) -> Result<String> {
+        let attributes = self.get_attributes()?;
+        Ok(attributes.get("label").cloned().unwrap_or_default())
+    }
+
+    /// The label is kept in the `label` attribute.
+    fn set_label(&self, label: &str) -> Result<()> {
+        self.update_attributes(&AttributeMap::from([("label", label)]))
+    }
+
+    /// Delete all the versions (and the metadata) of this credential's secret.
+    ///
+    /// Returns a [NoEntry](ErrorCode::NoEntry) error if there is no secret.
+    fn delete_credential(&self) -> Result<()> {
+        self.connection
+            .request("GET", &self.data_path(), None)?
+            .ok_or(ErrorCode::NoEntry)?;
+        self.connection
+            .request("DELETE", &self.metadata_path(), None)?;
+        Ok(())
+    }
+
+    /// The target, service, and user this credential was built with.
+    fn spec(&self) -> Option<CredentialSpec> {
+        Some(CredentialSpec {
+            target: self.target.clone(),
+            service: self.service.clone(),
+            user: self.user.clone(),
+        })
+    }
+
+    /// Vault encrypts all the data it stores.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        AtRestStatus::Encrypted
+    }
+
+    /// Return the underlying concrete object with an `Any` type so that it can
+    /// be downgraded to a [VaultCredential] for platform-specific processing.
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    /// Expose the concrete debug formatter for use via the [Credential] trait
+    fn debug_fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self, f)
+    }
+}
+
+impl VaultCredential {
+    /// The path of this credential's secret in the engine, without the mount.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    fn data_path(&self) -> String {
+        format!("data/{}", self.path)
+    }
+
+    fn metadata_path(&self) -> String {
+        format!("metadata/{}", self.path)
+    }
+
+    fn custom_metadata(&self) -> Result<Map<String, Value>> {
+        let mut response = self
+            .connection
+            .request("GET", &self.metadata_path(), None)?
+            .ok_or(ErrorCode::NoEntry)?;
+        match response["data"]["custom_metadata"].take() {
+            Value::Object(metadata) => Ok(metadata),
+            _ => Ok(Map::new()),
+        }
+    }
+}
+
+/// The builder for Vault credentials.
+#[derive(Debug, Clone)]
+pub struct VaultCredentialBuilder {
+    connection: Connection,
+    path: String,
+}
+
+/// Returns a Vault credential builder configured by the environment.
+///
+/// This is never called by the crate, since Vault is
+/// not the default store on any platform.
+pub fn default_credential_builder() -> Box<CredentialBuilder> {
+    Box::new(VaultCredentialBuilder::from_env())
+}
+
+impl VaultCredentialBuilder {
+    /// Create a builder for the Vault server at `address`
+    /// (such as `https://vault.example.com:8200`).
+    pub fn new(address: &str, auth: VaultAuth) -> Self {
+        Self {
+            connection: Connection::new(address, auth),
+            path: "keyring-rs".to_string(),
+        }
+    }
+
+    /// Create a builder configured the way the `vault` CLI is.
+    ///
+    /// The server is given by `VAULT_ADDR` (or is the local dev server),
+    /// the token by `VAULT_TOKEN` (or the contents of `~/.vault-token`),
+    /// and the namespace (if any) by `VAULT_NAMESPACE`.
+    pub fn from_env() -> Self {
+        let address = std::env::var("VAULT_ADDR").unwrap_or_else(|_| DEFAULT_ADDRESS.to_string());
+        let token = std::env::var("VAULT_TOKEN").ok().or_else(|| {
+            let home = std::env::var_os("HOME")?;
+            let token = std::fs::read_to_string(std::path::Path::new(&home).join(".vault-token"));
+            Some(token.ok()?.trim().to_string())
+        });
+        let namespace = std::env::var("VAULT_NAMESPACE").ok();
+        Self::new(&address, VaultAuth::Token(token.unwrap_or_default()))
+            .with_namespace(namespace.as_deref())
+    }
+
+    /// Use the Vault server at `address`.
+    pub fn with_address(mut self, address: &str) -> Self {
+        self.connection.address = address.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// The address of this builder's Vault server.
+    pub fn address(&self) -> &str {
+        &self.connection.address
+    }
+
+    /// Authenticate with `auth` (rather than as this builder was created).
+    ///
+    /// Credentials built from this builder will share a new token.
+    pub fn with_auth(mut self, auth: VaultAuth) -> Self {
+        self.connection.auth = Arc::new(auth);
+        self.connection.token = Arc::new(Mutex::new(None));
+        self
+    }
+
+    /// Use the KV engine mounted at `mount` (rather than at `secret`).
+    pub fn with_mount(mut self, mount: &str) -> Self {
+        self.connection.mount = mount.trim_matches('/').to_string();
+        self
+    }
+
+    /// The mount point of this builder's KV engine.
+    pub fn mount(&self) -> &str {
+        &self.connection.mount
+    }
+
+    /// Keep secrets under `path` in the engine (rather than under `keyring-rs`).
+    ///
+    /// The path can have several segments (such as `apps/my-app`),
+    /// or be empty (to keep secrets at the top of the engine).
+    pub fn with_path(mut self, path: &str) -> Self {
+        self.path = path.trim_matches('/').to_string();
+        self
+    }
+
+    /// The path that this builder's secrets are kept under.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Use the given Vault Enterprise namespace (or none).
+    pub fn with_namespace(mut self, namespace: Option<&str>) -> Self {
+        self.connection.namespace = namespace.map(|n| n.to_string());
+        self
+    }
+
+    /// The namespace, if any, of this builder's requests.
+    pub fn namespace(&self) -> Option<&str> {
+        self.connection.namespace.as_deref()
+    }
+}
+
+impl CredentialBuilderApi for VaultCredentialBuilder {
+    /// Build a [VaultCredential] for the given target, service, and user.
+    ///
+    /// Returns an [Invalid](ErrorCode::Invalid) error if any of them is empty,
+    /// since they are segments of the secret's path. The secret isn't written
+    /// until its value is set.
+    fn build(&self, target: Option<&str>, service: &str, user: &str) -> Result<Box<Credential>> {
+        let mut segments = Vec::new();
+        if !self.path.is_empty() {
+            segments.push(encode_path(&self.path));
+        }
+        for (name, part) in [
+            ("target", target),
+            ("service", Some(service)),
+            ("user", Some(user)),
+        ] {
+            match part {
+                Some("") => {
+                    return Err(ErrorCode::Invalid(
+                        name.to_string(),
+                        "cannot be empty".to_string(),
+                    ));
+                }
+                Some(part) => segments.push(encode_segment(part)),
+                None => {}
+            }
+        }
+        Ok(Box::new(VaultCredential {
+            target: target.map(|t| t.to_string()),
+            service: service.to_string(),
+            user: user.to_string(),
+            path: segments.join("/"),
+            connection: self.connection.clone(),
+        }))
+    }
+
+    /// Vault encrypts all the data it stores.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        AtRestStatus::Encrypted
+    }
+
+    /// Return the underlying builder object with an `Any` type so that it can
+    /// be downgraded to a [VaultCredentialBuilder] for platform-specific processing.
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    /// Vault keeps secrets until they are deleted.
+    fn persistence(&self) -> CredentialPersistence {
+        CredentialPersistence::UntilDelete
+    }
+
+    /// Secrets have custom metadata (as attributes), but the store isn't searched.
+    fn info(&self) -> CredentialStoreInfo {
+        CredentialStoreInfo::new("vault", self.persistence(), self.at_rest_encryption())
+            .with_attributes(true)
+    }
+}
+
+/// Encode a part of a credential's identity as a single segment of a URL path.
+///
+/// Slashes and percent signs are first escaped (because Vault decodes
+/// the URL path, and they would otherwise split or alter the segment),
+/// and segments of just dots are escaped so they aren't taken as
+/// relative paths.
+fn encode_segment(part: &str) -> String {
+    let mut name = part.replace('%', "%25").replace('/', "%2F");
+    if name.chars().all(|c| c == '.') {
+        name = name.replace('.', "%2E");
+    }
+    encode_path(&name)
+}
+
+/// Percent-encode all the characters of a URL path that aren't unreserved or slashes.
+fn encode_path(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for b in path.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                encoded.push(b as char)
+            }
+            _ => encoded.push_str(&format!("%{b:02X}")),
+        }
+    }
+    encoded
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Map HTTP errors to crate errors.
+///
+/// Refused authentication, a sealed (or otherwise unavailable) server,
+/// and an unreachable one all mean there's no access to the store.
+fn decode_error(err: ureq::Error) -> ErrorCode {
+    match err {
+        ureq::Error::Status(status, response) => {
+            let errors = response
+                .into_json::<Value>()
+                .ok()
+                .and_then(|body| body["errors"].as_array().cloned())
+                .unwrap_or_default();
+            let errors: Vec<&str> = errors.iter().filter_map(Value::as_str).collect();
+            let message = format!("Vault responded {status}: {}", errors.join("; "));
+            match status {
+                401 | 403 | 503 => ErrorCode::NoStorageAccess(Box::from(message)),
+                _ => ErrorCode::PlatformFailure(Box::from(message)),
+            }
+        }
+        ureq::Error::Transport(err) => ErrorCode::NoStorageAccess(Box::new(err)),
+    }
+}
+
+fn io_failure(err: std::io::Error) -> ErrorCode {
+    ErrorCode::PlatformFailure(Box::new(err))
+}
+
+fn failure(reason: &str) -> ErrorCode {
+    ErrorCode::PlatformFailure(Box::from(format!("Vault {reason}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::credential::{CredentialBuilderApi, CredentialPersistence};
+    use crate::{Entry, Error, tests::generate_random_string};
+
+    use super::{
+        VaultAuth, VaultCredential, VaultCredentialBuilder, default_credential_builder, from_hex,
+        to_hex,
+    };
+
+    fn path_of(builder: &VaultCredentialBuilder, target: Option<&str>, service: &str) -> String {
+        let credential = builder.build(target, service, "user").unwrap();
+        let credential: &VaultCredential = credential.as_any().downcast_ref().unwrap();
+        credential.path().to_string()
+    }
+
+    #[test]
+    fn test_persistence() {
+        assert!(matches!(
+            default_credential_builder().persistence(),
+            CredentialPersistence::UntilDelete
+        ))
+    }
+
+    #[test]
+    fn test_paths() {
+        let builder = VaultCredentialBuilder::new("http://vault", VaultAuth::Token(String::new()));
+        assert_eq!(
+            path_of(&builder, None, "service"),
+            "keyring-rs/service/user"
+        );
+        assert_eq!(
+            path_of(&builder, Some("target"), "service"),
+            "keyring-rs/target/service/user"
+        );
+        assert_eq!(
+            path_of(&builder, None, "a/b c%"),
+            "keyring-rs/a%252Fb%20c%2525/user"
+        );
+        assert_eq!(path_of(&builder, None, ".."), "keyring-rs/%252E%252E/user");
+        let builder = builder.with_path("/apps/my app/");
+        assert_eq!(builder.path(), "apps/my app");
+        assert_eq!(
+            path_of(&builder, None, "service"),
+            "apps/my%20app/service/user"
+        );
+        let builder = builder.with_path("");
+        assert_eq!(path_of(&builder, None, "service"), "service/user");
+    }
+
+    #[test]
+    fn test_invalid_parameter() {
+        let builder = VaultCredentialBuilder::new("http://vault", VaultAuth::Token(String::new()));
+        for (target, service, user) in [(Some(""), "s", "u"), (None, "", "u"), (None, "s", "")] {
+            assert!(matches!(
+                builder.build(target, service, user),
+                Err(Error::Invalid(_, _))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_builder_options() {
+        let builder = VaultCredentialBuilder::new(
+            "http://vault/",
+            VaultAuth::AppRole {
+                role_id: "role".to_string(),
+                secret_id: "hidden".to_string(),
+            },
+        )
+        .with_mount("/kv/")
+        .with_namespace(Some("team"));
+        assert_eq!(builder.address(), "http://vault");
+        assert_eq!(builder.mount(), "kv");
+        assert_eq!(builder.namespace(), Some("team"));
+        assert!(!format!("{builder:?}").contains("hidden"));
+    }
+
+    #[test]
+    fn test_hex() {
+        let bytes = [0u8, 1, 0x7f, 0xff];
+        assert_eq!(to_hex(&bytes), "00017fff");
+        assert_eq!(from_hex("00017fff").unwrap(), bytes);
+        assert_eq!(from_hex("0"), None);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let builder = default_credential_builder();
+        let service = generate_random_string();
+        let entry = Entry::new_with_credential(builder.build(None, &service, "user").unwrap());
+        assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+        entry.set_password("vault password").unwrap();
+        assert_eq!(entry.get_password().unwrap(), "vault password");
+        entry.set_secret(&[0xff, 0]).unwrap();
+        assert_eq!(entry.get_secret().unwrap(), [0xff, 0]);
+        entry.set_label("label").unwrap();
+        assert_eq!(entry.get_label().unwrap(), "label");
+        entry.delete_credential().unwrap();
+        assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+    }
+}
@@ -0,0 +1,159 @@
+/*!
+
+# Versioned secrets
+
+Processes that share a credential (for example, to coordinate the rotation
+of an API key) sometimes need to know whether the secret has changed since
+they last read it.  Comparing the secrets themselves works, but can be
+expensive if they are large.  This module provides a lighter-weight
+alternative: each versioned write of an entry's secret bumps a counter that is
+kept in the credential's `keyring-version` attribute, and versioned reads return
+the secret along with its version.  A write can then be made conditional
+on the version not having changed since the secret was read.
+
+Because versions are kept in an attribute, versioned writes only work with
+credential stores that allow arbitrary attributes to be set on credentials,
+such as the secret-service and [memory](crate::memory) stores. On other stores,
+versioned writes return a [NotSupportedByStore](Error::NotSupportedByStore)
+error (after the secret has been written).  A credential that has never
+had a versioned write has version 0.
+
+Note that versions are checked by this crate, not by the underlying store,
+so there is a short window between the version check and the write
+in which another process can write the secret.  Versions make conflicting
+rotations unlikely, but they are not a lock.
+
+```
+# use keyring::{Entry, credential::CredentialBuilderApi, memory::MemoryCredentialBuilder};
+# let builder = MemoryCredentialBuilder::default();
+# let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+let version = entry.set_secret_versioned(b"first key").unwrap();
+let (secret, read_version) = entry.get_secret_versioned().unwrap();
+assert_eq!((secret.as_slice(), read_version), (b"first key".as_slice(), version));
+assert!(entry.set_if_version(b"second key", version).unwrap());
+assert!(!entry.set_if_version(b"third key", version).unwrap());
+```
+ */
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+use super::Entry;
+use super::credential::AttributeMap;
+use super::error::{Error, Result};
+
+/// The name of the attribute that holds a credential's version.
+pub const VERSION_ATTRIBUTE: &str = "keyring-version";
+
+impl Entry {
+    /// Retrieve the secret saved for this entry, along with its version.
+    ///
+    /// Returns a [NoEntry](Error::NoEntry) error if there isn't one,
+    /// and an [Invalid](Error::Invalid) error if the credential's
+    /// version attribute isn't a number.
+    pub fn get_secret_versioned(&self) -> Result<(Vec<u8>, u64)> {
+        let secret = self.get_secret()?;
+        let version = self.get_version()?;
+        Ok((secret, version))
+    }
+
+    /// Set the secret for this entry, and bump its version.
+    ///
+    /// Returns the new version. See the module header for which stores
+    /// support versions.
+    pub fn set_secret_versioned(&self, secret: &[u8]) -> Result<u64> {
+        let version = match self.get_version() {
+            Ok(version) => version + 1,
+            Err(Error::NoEntry) => 1,
+            Err(err) => return Err(err),
+        };
+        self.set_secret(secret)?;
+        let value = version.to_string();
+        let attributes = AttributeMap::from([(VERSION_ATTRIBUTE, value.as_str())]);
+        self.update_attributes(&attributes)?;
+        if self.get_version()? != version {
+            return Err(Error::NotSupportedByStore("versioned secrets".to_string()));
+        }
+        Ok(version)
+    }
+
+    /// Set the secret for this entry, but only if its version is the expected one.
+    ///
+    /// Returns whether the secret was set. If there is no credential for this entry,
+    /// its version is taken to be 0, so an expected version of 0 will create it.
+    pub fn set_if_version(&self, secret: &[u8], expected_version: u64) -> Result<bool> {
+        let version = match self.get_version() {
+            Ok(version) => version,
+            Err(Error::NoEntry) => 0,
+            Err(err) => return Err(err),
+        };
+        if version != expected_version {
+            return Ok(false);
+        }
+        self.set_secret_versioned(secret)?;
+        Ok(true)
+    }
+
+    fn get_version(&self) -> Result<u64> {
+        match self.get_attributes()?.get(VERSION_ATTRIBUTE) {
+            Some(value) => value
+                .parse()
+                .map_err(|_| Error::Invalid(VERSION_ATTRIBUTE.to_string(), value.to_string())),
+            None => Ok(0),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::credential::CredentialBuilderApi;
+    use crate::memory::MemoryCredentialBuilder;
+    use crate::mock::MockCredential;
+    use crate::{Entry, Error};
+
+    fn entry_new(builder: &MemoryCredentialBuilder) -> Entry {
+        Entry::new_with_credential(builder.build(None, "service", "user").unwrap())
+    }
+
+    #[test]
+    fn test_versions() {
+        let builder = MemoryCredentialBuilder::default();
+        let entry = entry_new(&builder);
+        assert!(matches!(entry.get_secret_versioned(), Err(Error::NoEntry)));
+        assert!(!entry.set_if_version(b"first", 1).unwrap());
+        assert!(entry.set_if_version(b"first", 0).unwrap());
+        let (secret, version) = entry.get_secret_versioned().unwrap();
+        assert_eq!((secret.as_slice(), version), (b"first".as_slice(), 1));
+        let other = entry_new(&builder);
+        assert_eq!(other.set_secret_versioned(b"second").unwrap(), 2);
+        assert!(!entry.set_if_version(b"third", 1).unwrap());
+        assert_eq!(entry.get_secret().unwrap(), b"second");
+        assert!(entry.set_if_version(b"third", 2).unwrap());
+        assert_eq!(other.get_secret_versioned().unwrap().1, 3);
+    }
+
+    #[test]
+    fn test_unversioned_secret() {
+        let builder = MemoryCredentialBuilder::default();
+        let entry = entry_new(&builder);
+        entry.set_secret(b"unversioned").unwrap();
+        assert_eq!(entry.get_secret_versioned().unwrap().1, 0);
+        entry
+            .update_attributes(&HashMap::from([(super::VERSION_ATTRIBUTE, "bad")]))
+            .unwrap();
+        assert!(matches!(
+            entry.get_secret_versioned(),
+            Err(Error::Invalid(_, _))
+        ));
+    }
+
+    #[test]
+    fn test_store_without_attributes() {
+        let entry = Entry::new_with_credential(Box::new(MockCredential::default()));
+        assert!(matches!(
+            entry.set_secret_versioned(b"secret"),
+            Err(Error::NotSupportedByStore(_))
+        ));
+    }
+}
@@ -0,0 +1,349 @@
+/*!
+
+# Browser local storage credential store
+
+Apps compiled to `wasm32-unknown-unknown` and run in a browser have no
+platform credential store, so without this module their entries are
+built by the default (no-op) builder, and their secrets are lost on reload.
+This module keeps each credential as an item in the browser's
+[local storage](https://developer.mozilla.org/en-US/docs/Web/API/Window/localStorage),
+encrypted and authenticated with the [crypto](crate::crypto) module under
+a 32-byte key that the client supplies when creating the builder. It's
+available when the `web-storage` feature is enabled. It's not the default
+store on any platform, so clients have to set it as their default (or
+build their credentials from it directly):
+```no_run
+let key = [7u8; 32]; // use a real key!
+let builder = keyring::web_storage::WebStorageCredentialBuilder::new(&key);
+keyring::set_default_credential_builder(Box::new(builder));
+```
+
+Local storage is readable by any script on the page's origin, so the
+encryption only protects secrets if the key isn't kept there too.
+Where the key comes from is up to the client: for example, it can be
+derived (with [derive_key](crate::crypto::derive_key)) from a secret
+that the app's server hands out after the user logs in.
+
+Each credential's item is named by its target, service, and user
+(so they aren't secret), and its value is the hex encoding of its sealed
+secret (which is sealed together with its name, so values can't be
+swapped between items). A value that was sealed with a different key (or
+that has been tampered with) produces an [Invalid](crate::Error::Invalid)
+error. This store supports [search](crate::search), but not attributes
+or labels.
+ */
+use std::sync::Arc;
+
+use web_sys::Storage;
+use zeroize::Zeroizing;
+
+use super::credential::{
+    AtRestStatus, Credential, CredentialApi, CredentialBuilderApi, CredentialPersistence,
+    CredentialSpec, CredentialStoreInfo, SearchSpec,
+};
+use super::crypto::{KEY_LEN, open, seal};
+use super::error::{Error as ErrorCode, Result};
+
+/// The prefix of the names of this store's items.
+const PREFIX: &str = "keyring-rs:";
+
+/// The representation of a local storage item.
+#[derive(Clone)]
+pub struct WebStorageCredential {
+    target: Option<String>,
+    service: String,
+    user: String,
+    key: Arc<Zeroizing<[u8; KEY_LEN]>>,
+}
+
+impl CredentialApi for WebStorageCredential {
+    /// Seal the secret and store it in this credential's item.
+    fn set_secret(&self, secret: &[u8]) -> Result<()> {
+        let name = self.item_name();
+        let sealed = seal(&sealed_plaintext(&name, secret), &self.key);
+        local_storage()?
+            .set_item(&name, &to_hex(&sealed))
+            .map_err(js_failure)
+    }
+
+    /// Read and unseal the secret in this credential's item, if it exists.
+    ///
+    /// Returns a [NoEntry](ErrorCode::NoEntry) error if there is no item.
+    fn get_secret(&self) -> Result<Vec<u8>> {
+        let name = self.item_name();
+        let value = local_storage()?
+            .get_item(&name)
+            .map_err(js_failure)?
+            .ok_or(ErrorCode::NoEntry)?;
+        let sealed = from_hex(&value).ok_or_else(|| invalid("isn't hex encoded"))?;
+        let plaintext = Zeroizing::new(open(&sealed, &self.key)?);
+        match plaintext.strip_prefix(name.as_bytes()) {
+            Some([b'\n', secret @ ..]) => Ok(secret.to_vec()),
+            _ => Err(invalid("was sealed for another item")),
+        }
+    }
+
+    /// Remove this credential's item, if it exists.
+    ///
+    /// Returns a [NoEntry](ErrorCode::NoEntry) error if there is no item.
+    fn delete_credential(&self) -> Result<()> {
+        let name = self.item_name();
+        let storage = local_storage()?;
+        if storage.get_item(&name).map_err(js_failure)?.is_none() {
+            return Err(ErrorCode::NoEntry);
+        }
+        storage.remove_item(&name).map_err(js_failure)
+    }
+
+    /// The target, service, and user this credential was built with.
+    fn spec(&self) -> Option<CredentialSpec> {
+        Some(CredentialSpec {
+            target: self.target.clone(),
+            service: self.service.clone(),
+            user: self.user.clone(),
+        })
+    }
+
+    /// This store encrypts its items.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        AtRestStatus::Encrypted
+    }
+
+    /// Return this credential with an `Any` type so it can be downcast.
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    /// Expose the concrete debug formatter for use via the [Credential] trait
+    fn debug_fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self, f)
+    }
+}
+
+impl std::fmt::Debug for WebStorageCredential {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WebStorageCredential")
+            .field("target", &self.target)
+            .field("service", &self.service)
+            .field("user", &self.user)
+            .finish_non_exhaustive()
+    }
+}
+
+impl WebStorageCredential {
+    /// The name of this credential's item.
+    fn item_name(&self) -> String {
+        item_name(self.target.as_deref(), &self.service, &self.user)
+    }
+}
+
+/// The builder for local storage credentials.
+#[derive(Clone)]
+pub struct WebStorageCredentialBuilder {
+    key: Arc<Zeroizing<[u8; KEY_LEN]>>,
+}
+
+impl WebStorageCredentialBuilder {
+    /// Create a builder whose credentials are encrypted with `key`.
+    pub fn new(key: &[u8; KEY_LEN]) -> Self {
+        Self {
+            key: Arc::new(Zeroizing::new(*key)),
+        }
+    }
+}
+
+impl std::fmt::Debug for WebStorageCredentialBuilder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WebStorageCredentialBuilder")
+            .finish_non_exhaustive()
+    }
+}
+
+impl CredentialBuilderApi for WebStorageCredentialBuilder {
+    /// Build a credential for the given target, service, and user.
+    ///
+    /// The credential isn't stored until its secret is set.
+    fn build(&self, target: Option<&str>, service: &str, user: &str) -> Result<Box<Credential>> {
+        Ok(Box::new(WebStorageCredential {
+            target: target.map(|t| t.to_string()),
+            service: service.to_string(),
+            user: user.to_string(),
+            key: self.key.clone(),
+        }))
+    }
+
+    /// This store encrypts its items.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        AtRestStatus::Encrypted
+    }
+
+    /// Return the underlying builder object with an `Any` type so that it can
+    /// be downgraded to a [WebStorageCredentialBuilder] for platform-specific processing.
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    /// Local storage is kept (by the browser) until it's cleared.
+    fn persistence(&self) -> CredentialPersistence {
+        CredentialPersistence::UntilDelete
+    }
+
+    /// Return a credential for each stored item that matches the spec.
+    fn search(&self, spec: &SearchSpec) -> Result<Vec<Box<Credential>>> {
+        let storage = local_storage()?;
+        let mut found = Vec::new();
+        for i in 0..storage.length().map_err(js_failure)? {
+            let Some(name) = storage.key(i).map_err(js_failure)? else {
+                continue;
+            };
+            let Some((target, service, user)) = parse_item_name(&name) else {
+                continue;
+            };
+            if spec.matches(target.as_deref(), &service, &user) {
+                found.push(WebStorageCredential {
+                    target,
+                    service,
+                    user,
+                    key: self.key.clone(),
+                });
+            }
+        }
+        if !spec.unsorted {
+            found.sort_by(|a, b| {
+                (&a.service, &a.user, &a.target).cmp(&(&b.service, &b.user, &b.target))
+            });
+        }
+        Ok(found
+            .into_iter()
+            .map(|cred| Box::new(cred) as Box<Credential>)
+            .collect())
+    }
+
+    /// The store can be searched.
+    fn info(&self) -> CredentialStoreInfo {
+        CredentialStoreInfo::new("web-storage", self.persistence(), self.at_rest_encryption())
+            .with_search(true)
+    }
+}
+
+fn local_storage() -> Result<Storage> {
+    web_sys::window()
+        .ok_or_else(|| ErrorCode::NoStorageAccess(Box::from("there is no browser window")))?
+        .local_storage()
+        .map_err(|err| ErrorCode::NoStorageAccess(Box::from(format!("{err:?}"))))?
+        .ok_or_else(|| ErrorCode::NoStorageAccess(Box::from("local storage is unavailable")))
+}
+
+/// The name of the item for a target, service, and user.
+///
+/// The parts are separated by colons (which are escaped within them),
+/// and a missing target is distinguished from an empty one.
+fn item_name(target: Option<&str>, service: &str, user: &str) -> String {
+    let target = match target {
+        None => "-".to_string(),
+        Some(target) => format!("+{}", escape(target)),
+    };
+    format!("{PREFIX}{target}:{}:{}", escape(service), escape(user))
+}
+
+/// The target, service, and user named by an item name, if it's one of this store's.
+#[allow(clippy::type_complexity)]
+fn parse_item_name(name: &str) -> Option<(Option<String>, String, String)> {
+    let mut parts = name.strip_prefix(PREFIX)?.split(':');
+    let target = match parts.next()? {
+        "-" => None,
+        target => Some(unescape(target.strip_prefix('+')?)?),
+    };
+    let service = unescape(parts.next()?)?;
+    let user = unescape(parts.next()?)?;
+    match parts.next() {
+        None => Some((target, service, user)),
+        Some(_) => None,
+    }
+}
+
+fn escape(part: &str) -> String {
+    part.replace('%', "%25").replace(':', "%3A")
+}
+
+fn unescape(part: &str) -> Option<String> {
+    let mut result = String::new();
+    let mut rest = part;
+    while let Some(i) = rest.find('%') {
+        result.push_str(&rest[..i]);
+        match rest.get(i..i + 3)? {
+            "%25" => result.push('%'),
+            "%3A" => result.push(':'),
+            _ => return None,
+        }
+        rest = &rest[i + 3..];
+    }
+    result.push_str(rest);
+    Some(result)
+}
+
+/// The plaintext that's sealed: the item name, a newline, and the secret.
+fn sealed_plaintext(name: &str, secret: &[u8]) -> Zeroizing<Vec<u8>> {
+    let mut plaintext = Zeroizing::new(Vec::with_capacity(name.len() + 1 + secret.len()));
+    plaintext.extend_from_slice(name.as_bytes());
+    plaintext.push(b'\n');
+    plaintext.extend_from_slice(secret);
+    plaintext
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn invalid(reason: &str) -> ErrorCode {
+    ErrorCode::Invalid("stored item".to_string(), reason.to_string())
+}
+
+fn js_failure(err: web_sys::wasm_bindgen::JsValue) -> ErrorCode {
+    ErrorCode::PlatformFailure(Box::from(format!("{err:?}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_hex, item_name, parse_item_name, to_hex};
+
+    #[test]
+    fn test_item_names() {
+        for (target, service, user) in [
+            (None, "service", "user"),
+            (Some(""), "service", "user"),
+            (Some("a:b"), "100%", "c:%3A"),
+        ] {
+            let name = item_name(target, service, user);
+            assert_eq!(
+                parse_item_name(&name),
+                Some((
+                    target.map(|t| t.to_string()),
+                    service.to_string(),
+                    user.to_string()
+                ))
+            );
+        }
+        assert_eq!(parse_item_name("other:-:service:user"), None);
+        assert_eq!(parse_item_name("keyring-rs:-:service"), None);
+    }
+
+    #[test]
+    fn test_hex() {
+        let bytes = [0u8, 1, 0x7f, 0xff];
+        assert_eq!(to_hex(&bytes), "00017fff");
+        assert_eq!(from_hex("00017fff").unwrap(), bytes);
+        assert_eq!(from_hex("0"), None);
+        assert_eq!(from_hex("zz"), None);
+    }
+}
@@ -27,6 +27,107 @@ call will return the values in the `username`, `comment`, and `target_alias` fie
 and the [update_attributes](crate::Entry::update_attributes)
 call allows setting those fields.
 
+Credentials can also carry up to 64 _custom attributes_ (Windows'
+`CREDENTIAL_ATTRIBUTE`s), each a keyword with a value of up to 256 bytes,
+which apps can use to tag credentials with metadata (such as when they
+were rotated) without encoding it into the secret. They are read and written
+with [get_custom_attributes](WinCredential::get_custom_attributes),
+[set_custom_attribute](WinCredential::set_custom_attribute), and
+[remove_custom_attribute](WinCredential::remove_custom_attribute), and
+those with UTF-8 values are also returned by `get_attributes` (and any
+attribute other than the three fields above is set by `update_attributes`).
+Setting a credential's secret keeps its custom attributes.
+
+Some applications (such as enterprise SSO tools) locate their credentials
+by _target alias_ rather than by _target name_.  To interoperate with them,
+the alias of an existing credential can be read and written with
+[get_target_alias](WinCredential::get_target_alias) and
+[set_target_alias](WinCredential::set_target_alias), and the credentials
+with a given alias can be found with
+[search_by_alias](WinCredentialBuilder::search_by_alias).
+
+This store supports [search](crate::search). All the Generic credentials
+are enumerated (or, if the spec matches an exact target, just that one),
+and the service and user of each are recovered from its _target name_ and
+_comment_, so credentials that weren't written by this crate aren't found.
+The comment is also where the credential's [label](crate::Entry::get_label)
+is kept, so a credential made with an explicit target won't be found by
+search after its label has been changed.
+
+Administrative and migration tools that need to see everything in the
+Credential Manager can use [WinCredentialBuilder::list] instead, which
+lists the target name, username, comment, and last-written time of every
+credential (Generic or domain) whose target name matches a filter.
+
+## NUL characters
+
+Generic credential secrets are stored with an explicit length, so secrets
+that contain NUL bytes (or passwords that contain NUL characters) are stored
+and retrieved exactly. But the _target name_, _username_, _comment_, and
+_target alias_ are NUL-terminated strings, so values that contain NUL characters
+would be silently truncated by Windows; this module rejects them with an
+[Invalid](crate::Error::Invalid) error instead. Apps that don't want NUL
+characters in their secrets either (for example, because they pass secrets
+to NUL-terminated APIs of their own) can build their credentials with
+[NulHandling::Reject] (see [WinCredentialBuilder::with_nul_handling]).
+
+## Password encoding
+
+A credential's secret is just bytes, so passwords have to be encoded.
+This module has always encoded them as UTF-16LE (the native charset for Windows
+strings), as do .NET and PowerShell tools, but some applications use UTF-8.
+Credentials built with [WinCredentialBuilder::with_password_encoding] can
+read and write passwords as UTF-8 instead ([PasswordEncoding::Utf8]), or
+write them as UTF-16LE but read them in either encoding ([PasswordEncoding::Auto]).
+Auto-detection takes a secret that is valid UTF-8 and contains no control
+characters (other than tabs and line breaks) to be UTF-8, and any other
+to be UTF-16LE, so it can misread a UTF-8 password that contains control
+characters. Secrets (as opposed to passwords) are never encoded.
+
+## Relaxed lookup
+
+Some native Windows tools write credentials whose _target name_ doesn't
+quite follow this crate's conventions (for example, it differs in case,
+has the `LegacyGeneric:target=` prefix shown by `cmdkey`, or is just
+the service name), so a direct read won't find them. Credentials built
+with [relaxed lookup](WinCredentialBuilder::with_relaxed_lookup) fall back,
+when a direct read finds nothing, to enumerating all the Generic credentials
+and picking the one with the same _username_ whose _target name_ matches
+when those differences are ignored. (If more than one matches, an
+[Ambiguous](crate::Error::Ambiguous) error is returned.) Relaxed lookup
+applies to reading secrets and attributes, and attribute updates go to the
+credential that was found, but secrets are always written to (and deleted
+from) the exact _target name_.
+
+## Persistence
+
+By default, Generic credentials are written with _enterprise_ persistence,
+so they persist across logons and roam with the user's profile.
+Credentials built with [WinCredentialBuilder::with_persist] can instead be
+kept on the local machine only ([PersistScope::LocalMachine]) or only for
+the current logon session ([PersistScope::Session]), in which case they
+vanish when the user logs off. (An existing credential keeps its persistence
+when its attributes are updated.)
+
+## Domain credentials
+
+Windows itself uses _domain_ credentials (rather than Generic ones) to
+log on to network shares, remote desktops, and scheduled tasks on behalf
+of the user. Credentials built with [WinCredentialBuilder::with_cred_type]
+can be written (and read and deleted) as domain password credentials
+([WinCredentialType::DomainPassword]) or domain certificate credentials
+([WinCredentialType::DomainCertificate]), so tools that provision such
+credentials can write entries that Windows will use. For these, the
+_target name_ is the name of the server (or domain) the credential is for,
+and, for a certificate credential, the _username_ must be a marshaled
+certificate reference (as produced by `CredMarshalCredential`).
+
+Windows never discloses the secrets of domain credentials to applications,
+so reading the password or secret of one returns a
+[NotSupportedByStore](crate::Error::NotSupportedByStore) error; their
+attributes can still be read and updated (which preserves their secrets).
+Search and relaxed lookup only find Generic credentials.
+
 ## Caveat
 
 Reads and writes of the same entry from multiple threads
@@ -36,10 +137,14 @@ shown that modifying the same entry in the same (almost simultaneous) order from
 different threads produces different results on different runs.
 */
 
-use super::credential::{Credential, CredentialApi, CredentialBuilder, CredentialBuilderApi};
+use super::credential::{
+    AtRestStatus, Credential, CredentialApi, CredentialBuilder, CredentialBuilderApi,
+    CredentialMetadata, CredentialPersistence, CredentialSpec, CredentialStoreInfo, Match,
+    SearchSpec,
+};
 use super::error::{Error as ErrorCode, Result};
 use byteorder::{ByteOrder, LittleEndian};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::iter::once;
 use std::mem::MaybeUninit;
 use std::str;
@@ -48,12 +153,25 @@ use windows_sys::Win32::Foundation::{
     ERROR_NOT_FOUND, FILETIME, GetLastError,
 };
 use windows_sys::Win32::Security::Credentials::{
-    CRED_FLAGS, CRED_MAX_CREDENTIAL_BLOB_SIZE, CRED_MAX_GENERIC_TARGET_NAME_LENGTH,
-    CRED_MAX_STRING_LENGTH, CRED_MAX_USERNAME_LENGTH, CRED_PERSIST_ENTERPRISE, CRED_TYPE_GENERIC,
-    CREDENTIAL_ATTRIBUTEW, CREDENTIALW, CredDeleteW, CredFree, CredReadW, CredWriteW,
+    CRED_FLAGS, CRED_MAX_ATTRIBUTES, CRED_MAX_CREDENTIAL_BLOB_SIZE,
+    CRED_MAX_DOMAIN_TARGET_NAME_LENGTH, CRED_MAX_GENERIC_TARGET_NAME_LENGTH,
+    CRED_MAX_STRING_LENGTH, CRED_MAX_USERNAME_LENGTH, CRED_MAX_VALUE_SIZE, CRED_PERSIST,
+    CRED_PERSIST_ENTERPRISE, CRED_PERSIST_LOCAL_MACHINE, CRED_PERSIST_SESSION,
+    CRED_PRESERVE_CREDENTIAL_BLOB, CRED_TYPE, CRED_TYPE_DOMAIN_CERTIFICATE,
+    CRED_TYPE_DOMAIN_PASSWORD, CRED_TYPE_GENERIC, CREDENTIAL_ATTRIBUTEW, CREDENTIALW, CredDeleteW,
+    CredEnumerateW, CredFree, CredReadW, CredWriteW,
 };
 use zeroize::Zeroize;
 
+/// Convert a Windows file time (in 100ns intervals since 1601)
+/// to a duration since the Unix epoch, if it's after the epoch.
+fn filetime_to_unix(time: &FILETIME) -> Option<std::time::Duration> {
+    const UNIX_EPOCH_INTERVALS: u64 = 11_644_473_600 * 10_000_000;
+    let intervals = ((time.dwHighDateTime as u64) << 32) | time.dwLowDateTime as u64;
+    let since_epoch = intervals.checked_sub(UNIX_EPOCH_INTERVALS)?;
+    Some(std::time::Duration::from_nanos(since_epoch * 100))
+}
+
 /// The representation of a Windows Generic credential.
 ///
 /// See the module header for the meanings of these fields.
@@ -63,6 +181,155 @@ pub struct WinCredential {
     pub target_name: String,
     pub target_alias: String,
     pub comment: String,
+    pub nul_handling: NulHandling,
+    pub relaxed_lookup: bool,
+    pub password_encoding: PasswordEncoding,
+    pub persist: PersistScope,
+    pub cred_type: WinCredentialType,
+    /// The custom attributes (`CREDENTIAL_ATTRIBUTE`s), by keyword
+    pub attributes: BTreeMap<String, Vec<u8>>,
+}
+
+/// A credential in the Windows Credential Manager, as listed by
+/// [WinCredentialBuilder::list].
+///
+/// Listing a credential doesn't read its secret.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WinCredentialListing {
+    pub target_name: String,
+    pub username: String,
+    pub comment: String,
+    pub target_alias: String,
+    pub cred_type: WinCredentialType,
+    pub persist: PersistScope,
+    /// When the credential was last written, as a duration since the Unix epoch
+    pub last_written: Option<std::time::Duration>,
+}
+
+impl WinCredentialListing {
+    fn extract(w_credential: &CREDENTIALW) -> Option<Self> {
+        let cred_type = match w_credential.Type {
+            CRED_TYPE_GENERIC | CRED_TYPE_DOMAIN_PASSWORD | CRED_TYPE_DOMAIN_CERTIFICATE => {
+                WinCredentialType::from_type(w_credential.Type)
+            }
+            _ => return None,
+        };
+        Some(Self {
+            target_name: unsafe { from_wstr(w_credential.TargetName) },
+            username: unsafe { from_wstr(w_credential.UserName) },
+            comment: unsafe { from_wstr(w_credential.Comment) },
+            target_alias: unsafe { from_wstr(w_credential.TargetAlias) },
+            cred_type,
+            persist: PersistScope::from_persist(w_credential.Persist),
+            last_written: filetime_to_unix(&w_credential.LastWritten),
+        })
+    }
+
+    /// A credential for the listed one, which can read (if it's
+    /// a Generic credential), update, or delete it.
+    pub fn credential(&self) -> WinCredential {
+        WinCredential {
+            username: self.username.clone(),
+            target_name: self.target_name.clone(),
+            target_alias: self.target_alias.clone(),
+            comment: self.comment.clone(),
+            nul_handling: NulHandling::default(),
+            relaxed_lookup: false,
+            password_encoding: PasswordEncoding::default(),
+            persist: self.persist,
+            cred_type: self.cred_type,
+            attributes: BTreeMap::new(),
+        }
+    }
+}
+
+/// How a [WinCredential] treats NUL characters in secrets.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NulHandling {
+    /// Store secrets that contain NULs exactly as given
+    #[default]
+    Preserve,
+    /// Reject secrets that contain NULs with an [Invalid](ErrorCode::Invalid) error
+    Reject,
+}
+
+/// How a [WinCredential] encodes passwords in its credential's secret.
+///
+/// See the module header for details.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PasswordEncoding {
+    /// As UTF-16LE, like this crate always has (and many Windows tools do)
+    #[default]
+    Utf16,
+    /// As UTF-8
+    Utf8,
+    /// As UTF-16LE when written, and as whichever encoding the secret
+    /// seems to be in when read
+    Auto,
+}
+
+/// How long a [WinCredential]'s Generic credential persists.
+///
+/// See the module header for details.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PersistScope {
+    /// Until the end of the current logon session (`CRED_PERSIST_SESSION`)
+    Session,
+    /// Across logon sessions, on this machine only (`CRED_PERSIST_LOCAL_MACHINE`)
+    LocalMachine,
+    /// Across logon sessions, roaming with the user's profile (`CRED_PERSIST_ENTERPRISE`)
+    #[default]
+    Enterprise,
+}
+
+impl PersistScope {
+    fn to_persist(self) -> CRED_PERSIST {
+        match self {
+            PersistScope::Session => CRED_PERSIST_SESSION,
+            PersistScope::LocalMachine => CRED_PERSIST_LOCAL_MACHINE,
+            PersistScope::Enterprise => CRED_PERSIST_ENTERPRISE,
+        }
+    }
+
+    fn from_persist(persist: CRED_PERSIST) -> Self {
+        match persist {
+            CRED_PERSIST_SESSION => PersistScope::Session,
+            CRED_PERSIST_LOCAL_MACHINE => PersistScope::LocalMachine,
+            _ => PersistScope::Enterprise,
+        }
+    }
+}
+
+/// The type of Windows credential a [WinCredential] reads and writes.
+///
+/// See the module header for details.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WinCredentialType {
+    /// A Generic credential, used only by applications (`CRED_TYPE_GENERIC`)
+    #[default]
+    Generic,
+    /// A domain password credential, used by Windows (`CRED_TYPE_DOMAIN_PASSWORD`)
+    DomainPassword,
+    /// A domain certificate credential, used by Windows (`CRED_TYPE_DOMAIN_CERTIFICATE`)
+    DomainCertificate,
+}
+
+impl WinCredentialType {
+    fn to_type(self) -> CRED_TYPE {
+        match self {
+            WinCredentialType::Generic => CRED_TYPE_GENERIC,
+            WinCredentialType::DomainPassword => CRED_TYPE_DOMAIN_PASSWORD,
+            WinCredentialType::DomainCertificate => CRED_TYPE_DOMAIN_CERTIFICATE,
+        }
+    }
+
+    fn from_type(cred_type: CRED_TYPE) -> Self {
+        match cred_type {
+            CRED_TYPE_DOMAIN_PASSWORD => WinCredentialType::DomainPassword,
+            CRED_TYPE_DOMAIN_CERTIFICATE => WinCredentialType::DomainCertificate,
+            _ => WinCredentialType::Generic,
+        }
+    }
 }
 
 // Windows API type mappings:
@@ -79,6 +346,12 @@ impl CredentialApi for WinCredential {
     /// there is no chance of ambiguity.
     fn set_password(&self, password: &str) -> Result<()> {
         self.validate_attributes(None, Some(password))?;
+        if self.nul_handling == NulHandling::Reject && password.contains('\0') {
+            return Err(nul_in("password"));
+        }
+        if self.password_encoding == PasswordEncoding::Utf8 {
+            return self.replace_credential(password.as_bytes());
+        }
         // Password strings are converted to UTF-16, because that's the native
         // charset for Windows strings.  This allows interoperability with native
         // Windows credential APIs.  But the storage for the credential is actually
@@ -86,7 +359,7 @@ impl CredentialApi for WinCredential {
         let mut blob_u16 = to_wstr_no_null(password);
         let mut blob = vec![0; blob_u16.len() * 2];
         LittleEndian::write_u16_into(&blob_u16, &mut blob);
-        let result = self.set_secret(&blob);
+        let result = self.replace_credential(&blob);
         // make sure that the copies of the secret are erased
         blob_u16.zeroize();
         blob.zeroize();
@@ -100,7 +373,10 @@ impl CredentialApi for WinCredential {
     /// there is no chance of ambiguity.
     fn set_secret(&self, secret: &[u8]) -> Result<()> {
         self.validate_attributes(Some(secret), None)?;
-        self.save_credential(secret)
+        if self.nul_handling == NulHandling::Reject && secret.contains(&0) {
+            return Err(nul_in("secret"));
+        }
+        self.replace_credential(secret)
     }
 
     /// Look up the password for this entry, if any.
@@ -108,7 +384,10 @@ impl CredentialApi for WinCredential {
     /// Returns a [NoEntry](ErrorCode::NoEntry) error if there is no
     /// credential in the store.
     fn get_password(&self) -> Result<String> {
-        self.extract_from_platform(extract_password)
+        self.check_secret_readable()?;
+        self.extract_from_platform(|credential| {
+            extract_password(credential, self.password_encoding)
+        })
     }
 
     /// Look up the secret for this entry, if any.
@@ -116,16 +395,36 @@ impl CredentialApi for WinCredential {
     /// Returns a [NoEntry](ErrorCode::NoEntry) error if there is no
     /// credential in the store.
     fn get_secret(&self) -> Result<Vec<u8>> {
+        self.check_secret_readable()?;
         self.extract_from_platform(extract_secret)
     }
 
+    /// Whether there is a credential for this entry.
+    ///
+    /// Windows reads the whole credential, but its secret isn't decoded.
+    fn exists(&self) -> Result<bool> {
+        match self.extract_from_platform(|_| Ok(())) {
+            Ok(()) => Ok(true),
+            Err(ErrorCode::NoEntry) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
     /// Get the attributes from the credential for this entry, if it exists.
     ///
+    /// These are the `comment`, `target_alias`, and `username`, and the
+    /// custom attributes whose values are UTF-8.
+    ///
     /// Returns a [NoEntry](ErrorCode::NoEntry) error if there is no
     /// credential in the store.
     fn get_attributes(&self) -> Result<HashMap<String, String>> {
         let cred = self.extract_from_platform(Self::extract_credential)?;
         let mut attributes: HashMap<String, String> = HashMap::new();
+        for (keyword, value) in &cred.attributes {
+            if let Ok(value) = str::from_utf8(value) {
+                attributes.insert(keyword.clone(), value.to_string());
+            }
+        }
         attributes.insert("comment".to_string(), cred.comment.clone());
         attributes.insert("target_alias".to_string(), cred.target_alias.clone());
         attributes.insert("username".to_string(), cred.username.clone());
@@ -134,38 +433,102 @@ impl CredentialApi for WinCredential {
 
     /// Update the attributes on the credential for this entry, if it exists.
     ///
+    /// Attributes other than `comment`, `target_alias`, and `username`
+    /// are set as custom attributes (with UTF-8 values).
+    ///
     /// Returns a [NoEntry](ErrorCode::NoEntry) error if there is no
     /// credential in the store.
     fn update_attributes(&self, attributes: &HashMap<&str, &str>) -> Result<()> {
-        let secret = self.extract_from_platform(extract_secret)?;
-        let mut cred = self.extract_from_platform(Self::extract_credential)?;
-        if let Some(comment) = attributes.get(&"comment") {
-            cred.comment = comment.to_string();
-        }
-        if let Some(target_alias) = attributes.get(&"target_alias") {
-            cred.target_alias = target_alias.to_string();
-        }
-        if let Some(username) = attributes.get(&"username") {
-            cred.username = username.to_string();
-        }
-        cred.validate_attributes(Some(&secret), None)?;
-        cred.save_credential(&secret)
+        self.update_credential(|cred| {
+            for (key, value) in attributes {
+                match *key {
+                    "comment" => cred.comment = value.to_string(),
+                    "target_alias" => cred.target_alias = value.to_string(),
+                    "username" => cred.username = value.to_string(),
+                    keyword => {
+                        cred.attributes
+                            .insert(keyword.to_string(), value.as_bytes().to_vec());
+                    }
+                }
+            }
+        })
     }
 
-    /// Delete the underlying generic credential for this entry, if any.
+    /// The time the credential for this entry was last written, if it exists.
+    ///
+    /// Returns a [NoEntry](ErrorCode::NoEntry) error if there is no
+    /// credential in the store.
+    fn get_metadata(&self) -> Result<CredentialMetadata> {
+        let last_written = self.extract_from_platform(|cred| Ok(cred.LastWritten))?;
+        Ok(CredentialMetadata {
+            modified: filetime_to_unix(&last_written),
+            ..Default::default()
+        })
+    }
+
+    /// The label of a Generic credential is its comment.
+    fn get_label(&self) -> Result<String> {
+        let cred = self.extract_from_platform(Self::extract_credential)?;
+        Ok(cred.comment)
+    }
+
+    /// The label of a Generic credential is its comment.
+    ///
+    /// See the module header for why this can affect search.
+    fn set_label(&self, label: &str) -> Result<()> {
+        self.update_attributes(&HashMap::from([("comment", label)]))
+    }
+
+    /// Generic credentials can hold at most `CRED_MAX_CREDENTIAL_BLOB_SIZE` bytes.
+    fn max_secret_len(&self) -> Option<usize> {
+        Some(CRED_MAX_CREDENTIAL_BLOB_SIZE as usize)
+    }
+
+    /// Delete the underlying credential for this entry, if any.
     ///
     /// Returns a [NoEntry](ErrorCode::NoEntry) error if there is no
     /// credential in the store.
     fn delete_credential(&self) -> Result<()> {
         self.validate_attributes(None, None)?;
         let target_name = to_wstr(&self.target_name);
-        let cred_type = CRED_TYPE_GENERIC;
+        let cred_type = self.cred_type.to_type();
         match unsafe { CredDeleteW(target_name.as_ptr(), cred_type, 0) } {
             0 => Err(decode_error()),
             _ => Ok(()),
         }
     }
 
+    /// The target, service, and user of this credential.
+    ///
+    /// Windows only stores the target name and username, so the service is
+    /// recovered from the target name (for credentials with the default
+    /// target) or from the comment written by this crate (for others).
+    /// Returns `None` if neither of these identifies the service.
+    fn spec(&self) -> Option<CredentialSpec> {
+        let user = &self.username;
+        if let Some(service) = self.target_name.strip_prefix(&format!("{user}.")) {
+            return Some(CredentialSpec {
+                target: None,
+                service: service.to_string(),
+                user: user.to_string(),
+            });
+        }
+        let identity = self.comment.split(" (keyring v").next()?;
+        let service = identity
+            .strip_prefix(&format!("{user}@"))?
+            .strip_suffix(&format!(":{}", self.target_name))?;
+        Some(CredentialSpec {
+            target: Some(self.target_name.clone()),
+            service: service.to_string(),
+            user: user.to_string(),
+        })
+    }
+
+    /// The Credential Manager encrypts credentials at rest (with DPAPI).
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        AtRestStatus::Encrypted
+    }
+
     /// Return the underlying concrete object with an `Any` type so that it can
     /// be downgraded to a [WinCredential] for platform-specific processing.
     fn as_any(&self) -> &dyn std::any::Any {
@@ -180,6 +543,16 @@ impl CredentialApi for WinCredential {
 
 impl WinCredential {
     fn validate_attributes(&self, secret: Option<&[u8]>, password: Option<&str>) -> Result<()> {
+        for (name, value) in [
+            ("user", &self.username),
+            ("target", &self.target_name),
+            ("target alias", &self.target_alias),
+            ("comment", &self.comment),
+        ] {
+            if value.contains('\0') {
+                return Err(nul_in(name));
+            }
+        }
         if self.username.len() > CRED_MAX_USERNAME_LENGTH as usize {
             return Err(ErrorCode::TooLong(
                 String::from("user"),
@@ -192,11 +565,12 @@ impl WinCredential {
                 "cannot be empty".to_string(),
             ));
         }
-        if self.target_name.len() > CRED_MAX_GENERIC_TARGET_NAME_LENGTH as usize {
-            return Err(ErrorCode::TooLong(
-                String::from("target"),
-                CRED_MAX_GENERIC_TARGET_NAME_LENGTH,
-            ));
+        let max_target_len = match self.cred_type {
+            WinCredentialType::Generic => CRED_MAX_GENERIC_TARGET_NAME_LENGTH,
+            _ => CRED_MAX_DOMAIN_TARGET_NAME_LENGTH,
+        };
+        if self.target_name.len() > max_target_len as usize {
+            return Err(ErrorCode::TooLong(String::from("target"), max_target_len));
         }
         if self.target_alias.len() > CRED_MAX_STRING_LENGTH as usize {
             return Err(ErrorCode::TooLong(
@@ -210,6 +584,35 @@ impl WinCredential {
                 CRED_MAX_STRING_LENGTH,
             ));
         }
+        if self.attributes.len() > CRED_MAX_ATTRIBUTES as usize {
+            return Err(ErrorCode::TooLong(
+                String::from("attributes"),
+                CRED_MAX_ATTRIBUTES,
+            ));
+        }
+        for (keyword, value) in &self.attributes {
+            if keyword.is_empty() {
+                return Err(ErrorCode::Invalid(
+                    "attribute keyword".to_string(),
+                    "cannot be empty".to_string(),
+                ));
+            }
+            if keyword.contains('\0') {
+                return Err(nul_in("attribute keyword"));
+            }
+            if keyword.len() > CRED_MAX_STRING_LENGTH as usize {
+                return Err(ErrorCode::TooLong(
+                    String::from("attribute keyword"),
+                    CRED_MAX_STRING_LENGTH,
+                ));
+            }
+            if value.len() > CRED_MAX_VALUE_SIZE as usize {
+                return Err(ErrorCode::TooLong(
+                    format!("attribute {keyword}"),
+                    CRED_MAX_VALUE_SIZE,
+                ));
+            }
+        }
         if let Some(secret) = secret {
             if secret.len() > CRED_MAX_CREDENTIAL_BLOB_SIZE as usize {
                 return Err(ErrorCode::TooLong(
@@ -219,11 +622,15 @@ impl WinCredential {
             }
         }
         if let Some(password) = password {
-            // We're going to store the password as UTF-16, so first transform it to UTF-16,
-            // count its runes, and then multiply by 2 to get the number of bytes needed.
-            if password.encode_utf16().count() * 2 > CRED_MAX_CREDENTIAL_BLOB_SIZE as usize {
+            // We're going to store the password as UTF-8 or UTF-16, so we count the
+            // bytes it takes in that encoding (2 for each UTF-16 code unit).
+            let (encoding, encoded_len) = match self.password_encoding {
+                PasswordEncoding::Utf8 => ("UTF-8", password.len()),
+                _ => ("UTF-16", password.encode_utf16().count() * 2),
+            };
+            if encoded_len > CRED_MAX_CREDENTIAL_BLOB_SIZE as usize {
                 return Err(ErrorCode::TooLong(
-                    String::from("password encoded as UTF-16"),
+                    format!("password encoded as {encoding}"),
                     CRED_MAX_CREDENTIAL_BLOB_SIZE,
                 ));
             }
@@ -231,10 +638,10 @@ impl WinCredential {
         Ok(())
     }
 
-    /// Write this credential into the underlying store as a Generic credential
+    /// Write this credential into the underlying store, with the given `CredWriteW` flags.
     ///
     /// You must always have validated attributes before you call this!
-    fn save_credential(&self, secret: &[u8]) -> Result<()> {
+    fn save_credential(&self, secret: &[u8], write_flags: u32) -> Result<()> {
         let mut username = to_wstr(&self.username);
         let mut target_name = to_wstr(&self.target_name);
         let mut target_alias = to_wstr(&self.target_alias);
@@ -242,15 +649,32 @@ impl WinCredential {
         let mut blob = secret.to_vec();
         let blob_len = blob.len() as u32;
         let flags = CRED_FLAGS::default();
-        let cred_type = CRED_TYPE_GENERIC;
-        let persist = CRED_PERSIST_ENTERPRISE;
+        let cred_type = self.cred_type.to_type();
+        let persist = self.persist.to_persist();
         // Ignored by CredWriteW
         let last_written = FILETIME {
             dwLowDateTime: 0,
             dwHighDateTime: 0,
         };
-        let attribute_count = 0;
-        let attributes: *mut CREDENTIAL_ATTRIBUTEW = std::ptr::null_mut();
+        // the keywords and values must outlive the attributes that point at them
+        let mut keywords: Vec<Vec<u16>> = self.attributes.keys().map(|k| to_wstr(k)).collect();
+        let mut values: Vec<Vec<u8>> = self.attributes.values().cloned().collect();
+        let mut w_attributes: Vec<CREDENTIAL_ATTRIBUTEW> = keywords
+            .iter_mut()
+            .zip(values.iter_mut())
+            .map(|(keyword, value)| CREDENTIAL_ATTRIBUTEW {
+                Keyword: keyword.as_mut_ptr(),
+                Flags: 0,
+                ValueSize: value.len() as u32,
+                Value: value.as_mut_ptr(),
+            })
+            .collect();
+        let attribute_count = w_attributes.len() as u32;
+        let attributes: *mut CREDENTIAL_ATTRIBUTEW = if w_attributes.is_empty() {
+            std::ptr::null_mut()
+        } else {
+            w_attributes.as_mut_ptr()
+        };
         let mut credential = CREDENTIALW {
             Flags: flags,
             Type: cred_type,
@@ -268,7 +692,7 @@ impl WinCredential {
         // raw pointer to credential, is coerced from &mut
         let p_credential: *const CREDENTIALW = &mut credential;
         // Call windows API
-        let result = match unsafe { CredWriteW(p_credential, 0) } {
+        let result = match unsafe { CredWriteW(p_credential, write_flags) } {
             0 => Err(decode_error()),
             _ => Ok(()),
         };
@@ -277,52 +701,207 @@ impl WinCredential {
         result
     }
 
-    /// Construct a credential from this credential's underlying Generic credential.
+    /// Write this credential with the given secret, replacing the existing one
+    /// (if any) but keeping those of its custom attributes that this
+    /// credential doesn't have.
+    fn replace_credential(&self, secret: &[u8]) -> Result<()> {
+        let existing = match read_credential(&self.target_name, self.cred_type.to_type()) {
+            Ok(p_credential) => {
+                let w_credential = unsafe { &*p_credential };
+                let attributes = extract_attributes(w_credential);
+                erase_secret(w_credential);
+                unsafe { CredFree(p_credential as *mut _) };
+                attributes
+            }
+            Err(ErrorCode::NoEntry) => BTreeMap::new(),
+            Err(err) => return Err(err),
+        };
+        if existing.is_empty() {
+            return self.save_credential(secret, 0);
+        }
+        let mut cred = self.clone();
+        for (keyword, value) in existing {
+            cred.attributes.entry(keyword).or_insert(value);
+        }
+        cred.validate_attributes(None, None)?;
+        cred.save_credential(secret, 0)
+    }
+
+    /// Rewrite the existing credential for this entry with the changes made by `update`,
+    /// keeping its secret.
+    fn update_credential(&self, update: impl FnOnce(&mut WinCredential)) -> Result<()> {
+        // Windows won't disclose the secret of a domain credential, but will preserve it
+        let (secret, flags) = match self.cred_type {
+            WinCredentialType::Generic => (self.extract_from_platform(extract_secret)?, 0),
+            _ => (Vec::new(), CRED_PRESERVE_CREDENTIAL_BLOB),
+        };
+        let mut cred = self.extract_from_platform(Self::extract_credential)?;
+        update(&mut cred);
+        cred.validate_attributes(Some(&secret), None)?;
+        cred.save_credential(&secret, flags)
+    }
+
+    /// Get the custom attributes of this credential's underlying Windows credential.
+    ///
+    /// Returns a [NoEntry](ErrorCode::NoEntry) error if there is no
+    /// credential in the store.
+    pub fn get_custom_attributes(&self) -> Result<BTreeMap<String, Vec<u8>>> {
+        Ok(self.get_credential()?.attributes)
+    }
+
+    /// Set a custom attribute on this credential's underlying Windows credential.
+    ///
+    /// Windows allows at most `CRED_MAX_ATTRIBUTES` attributes on a credential,
+    /// each with a value of at most `CRED_MAX_VALUE_SIZE` bytes.
+    /// Returns a [NoEntry](ErrorCode::NoEntry) error if there is no
+    /// credential in the store.
+    pub fn set_custom_attribute(&self, keyword: &str, value: &[u8]) -> Result<()> {
+        self.update_credential(|cred| {
+            cred.attributes.insert(keyword.to_string(), value.to_vec());
+        })
+    }
+
+    /// Remove a custom attribute from this credential's underlying Windows credential.
+    ///
+    /// Removing an attribute that isn't there does nothing.
+    /// Returns a [NoEntry](ErrorCode::NoEntry) error if there is no
+    /// credential in the store.
+    pub fn remove_custom_attribute(&self, keyword: &str) -> Result<()> {
+        self.update_credential(|cred| {
+            cred.attributes.remove(keyword);
+        })
+    }
+
+    /// Construct a credential from this credential's underlying Windows credential.
     ///
     /// This can be useful for seeing modifications made by a third party.
     pub fn get_credential(&self) -> Result<Self> {
         self.extract_from_platform(Self::extract_credential)
     }
 
+    /// Return this credential with the given treatment of NULs in secrets.
+    pub fn with_nul_handling(mut self, nul_handling: NulHandling) -> Self {
+        self.nul_handling = nul_handling;
+        self
+    }
+
+    /// Return this credential with the given encoding of passwords.
+    ///
+    /// See the module header for details.
+    pub fn with_password_encoding(mut self, password_encoding: PasswordEncoding) -> Self {
+        self.password_encoding = password_encoding;
+        self
+    }
+
+    /// Return this credential with relaxed lookup turned on or off.
+    ///
+    /// See the module header for details.
+    pub fn with_relaxed_lookup(mut self, relaxed_lookup: bool) -> Self {
+        self.relaxed_lookup = relaxed_lookup;
+        self
+    }
+
+    /// Return this credential with the given persistence.
+    ///
+    /// See the module header for details.
+    pub fn with_persist(mut self, persist: PersistScope) -> Self {
+        self.persist = persist;
+        self
+    }
+
+    /// Return this credential with the given type of Windows credential.
+    ///
+    /// See the module header for details.
+    pub fn with_cred_type(mut self, cred_type: WinCredentialType) -> Self {
+        self.cred_type = cred_type;
+        self
+    }
+
+    /// Windows doesn't disclose the secrets of domain credentials to applications.
+    fn check_secret_readable(&self) -> Result<()> {
+        match self.cred_type {
+            WinCredentialType::Generic => Ok(()),
+            _ => Err(ErrorCode::NotSupportedByStore(
+                "reading the secret of a domain credential".to_string(),
+            )),
+        }
+    }
+
+    /// Enumerate the Generic credentials whose target names match the given filter.
+    ///
+    /// This is an escape hatch for interoperating with third-party credentials:
+    /// the filter is passed as-is to `CredEnumerateW`, so it can end with
+    /// a `*` wildcard (as in `MicrosoftOffice*`). The secrets of the found
+    /// credentials are not read.
+    pub fn raw_enumerate(filter: &str) -> Result<Vec<Self>> {
+        enumerate_credentials(Some(filter))
+    }
+
+    /// Get the target alias of this credential's underlying Generic credential.
+    ///
+    /// Returns a [NoEntry](ErrorCode::NoEntry) error if there is no
+    /// credential in the store.
+    pub fn get_target_alias(&self) -> Result<String> {
+        Ok(self.get_credential()?.target_alias)
+    }
+
+    /// Set the target alias of this credential's underlying Generic credential.
+    ///
+    /// Returns a [NoEntry](ErrorCode::NoEntry) error if there is no
+    /// credential in the store.
+    pub fn set_target_alias(&self, alias: &str) -> Result<()> {
+        self.update_attributes(&HashMap::from([("target_alias", alias)]))
+    }
+
     fn extract_from_platform<F, T>(&self, f: F) -> Result<T>
     where
         F: FnOnce(&CREDENTIALW) -> Result<T>,
     {
         self.validate_attributes(None, None)?;
-        let mut p_credential = MaybeUninit::uninit();
-        // at this point, p_credential is just a pointer to nowhere.
-        // The allocation happens in the `CredReadW` call below.
-        let result = {
-            let cred_type = CRED_TYPE_GENERIC;
-            let target_name = to_wstr(&self.target_name);
-            unsafe {
-                CredReadW(
-                    target_name.as_ptr(),
-                    cred_type,
-                    0,
-                    p_credential.as_mut_ptr(),
-                )
+        let cred_type = self.cred_type.to_type();
+        let p_credential = match read_credential(&self.target_name, cred_type) {
+            Err(ErrorCode::NoEntry)
+                if self.relaxed_lookup && self.cred_type == WinCredentialType::Generic =>
+            {
+                read_credential(&self.find_relaxed_target()?, cred_type)?
             }
+            result => result?,
         };
-        match result {
-            0 => {
-                // `CredReadW` failed, so no allocation has been done, so no free needs to be done
-                Err(decode_error())
-            }
-            _ => {
-                // `CredReadW` succeeded, so p_credential points at an allocated credential.
-                // To do anything with it, we need to cast it to the right type.  That takes two steps:
-                // first we remove the "uninitialized" guard around it, then we reinterpret it as a
-                // pointer to the right structure type.
-                let p_credential = unsafe { p_credential.assume_init() };
-                let w_credential: CREDENTIALW = unsafe { *p_credential };
-                // Now we can apply the passed extractor function to the credential.
-                let result = f(&w_credential);
-                // Finally, we erase the secret and free the allocated credential.
-                erase_secret(&w_credential);
-                unsafe { CredFree(p_credential as *mut _) };
-                result
-            }
+        let w_credential: CREDENTIALW = unsafe { *p_credential };
+        // Now we can apply the passed extractor function to the credential.
+        let result = f(&w_credential);
+        // Finally, we erase the secret and free the allocated credential.
+        erase_secret(&w_credential);
+        unsafe { CredFree(p_credential as *mut _) };
+        result
+    }
+
+    /// Find the target name of the one Generic credential that matches this one
+    /// when target names are compared in the relaxed way described in the module header.
+    fn find_relaxed_target(&self) -> Result<String> {
+        let mut targets = vec![relaxed_target(&self.target_name)];
+        if let Some(CredentialSpec {
+            target: None,
+            service,
+            ..
+        }) = self.spec()
+        {
+            targets.push(relaxed_target(&service));
+        }
+        let mut found = enumerate_credentials(None)?;
+        found.retain(|cred| {
+            cred.username.to_lowercase() == self.username.to_lowercase()
+                && targets.contains(&relaxed_target(&cred.target_name))
+        });
+        match found.len() {
+            0 => Err(ErrorCode::NoEntry),
+            1 => Ok(found.remove(0).target_name),
+            _ => Err(ErrorCode::Ambiguous(
+                found
+                    .into_iter()
+                    .map(|cred| Box::new(cred) as Box<Credential>)
+                    .collect(),
+            )),
         }
     }
 
@@ -332,6 +911,12 @@ impl WinCredential {
             target_name: unsafe { from_wstr(w_credential.TargetName) },
             target_alias: unsafe { from_wstr(w_credential.TargetAlias) },
             comment: unsafe { from_wstr(w_credential.Comment) },
+            nul_handling: NulHandling::default(),
+            relaxed_lookup: false,
+            password_encoding: PasswordEncoding::default(),
+            persist: PersistScope::from_persist(w_credential.Persist),
+            cred_type: WinCredentialType::from_type(w_credential.Type),
+            attributes: extract_attributes(w_credential),
         })
     }
 
@@ -363,6 +948,13 @@ impl WinCredential {
                 target_name: target.to_string(),
                 target_alias: String::new(),
                 comment: format!("{user}@{service}:{target} (keyring v{VERSION})"),
+                nul_handling: NulHandling::default(),
+                relaxed_lookup: false,
+                password_encoding: PasswordEncoding::default(),
+                password_encoding: PasswordEncoding::default(),
+                persist: PersistScope::default(),
+                cred_type: WinCredentialType::default(),
+                attributes: BTreeMap::new(),
             }
         } else {
             Self {
@@ -378,6 +970,13 @@ impl WinCredential {
                 target_name: format!("{user}.{service}"),
                 target_alias: String::new(),
                 comment: format!("{user}@{service}:{user}.{service} (keyring v{VERSION})"),
+                nul_handling: NulHandling::default(),
+                relaxed_lookup: false,
+                password_encoding: PasswordEncoding::default(),
+                password_encoding: PasswordEncoding::default(),
+                persist: PersistScope::default(),
+                cred_type: WinCredentialType::default(),
+                attributes: BTreeMap::new(),
             }
         };
         credential.validate_attributes(None, None)?;
@@ -386,22 +985,77 @@ impl WinCredential {
 }
 
 /// The builder for Windows Generic credentials.
-pub struct WinCredentialBuilder {}
+#[derive(Debug, Default)]
+pub struct WinCredentialBuilder {
+    nul_handling: NulHandling,
+    relaxed_lookup: bool,
+    password_encoding: PasswordEncoding,
+    persist: PersistScope,
+    cred_type: WinCredentialType,
+}
 
 /// Returns an instance of the Windows credential builder.
 ///
 /// On Windows, with the default feature set,
 /// this is called once when an entry is first created.
 pub fn default_credential_builder() -> Box<CredentialBuilder> {
-    Box::new(WinCredentialBuilder {})
+    Box::new(WinCredentialBuilder::default())
 }
 
 impl CredentialBuilderApi for WinCredentialBuilder {
     /// Build a [WinCredential] for the given target, service, and user.
     fn build(&self, target: Option<&str>, service: &str, user: &str) -> Result<Box<Credential>> {
-        Ok(Box::new(WinCredential::new_with_target(
-            target, service, user,
-        )?))
+        let credential = WinCredential::new_with_target(target, service, user)?
+            .with_nul_handling(self.nul_handling)
+            .with_relaxed_lookup(self.relaxed_lookup)
+            .with_password_encoding(self.password_encoding)
+            .with_persist(self.persist)
+            .with_cred_type(self.cred_type);
+        Ok(Box::new(credential))
+    }
+
+    /// Session credentials vanish when the user logs off; others persist until deleted.
+    fn persistence(&self) -> CredentialPersistence {
+        match self.persist {
+            PersistScope::Session => CredentialPersistence::UntilLogout,
+            _ => CredentialPersistence::UntilDelete,
+        }
+    }
+
+    /// The Credential Manager encrypts credentials at rest (with DPAPI).
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        AtRestStatus::Encrypted
+    }
+
+    /// Return a credential for each Generic credential that matches the spec.
+    ///
+    /// Credentials whose service and user can't be recovered from their
+    /// target name and comment (because they weren't written by this crate)
+    /// are not matched.
+    fn search(&self, spec: &SearchSpec) -> Result<Vec<Box<Credential>>> {
+        let mut found: Vec<(CredentialSpec, WinCredential)> =
+            enumerate_credentials(spec.target.exact())?
+                .into_iter()
+                .filter_map(|cred| Some((cred.spec()?, cred)))
+                .filter(|(found, _)| {
+                    spec.matches(found.target.as_deref(), &found.service, &found.user)
+                })
+                .collect();
+        if !spec.unsorted {
+            found.sort_by(|(a, _), (b, _)| {
+                (&a.service, &a.user, &a.target).cmp(&(&b.service, &b.user, &b.target))
+            });
+        }
+        Ok(found
+            .into_iter()
+            .map(|(_, cred)| {
+                Box::new(
+                    cred.with_nul_handling(self.nul_handling)
+                        .with_relaxed_lookup(self.relaxed_lookup)
+                        .with_password_encoding(self.password_encoding),
+                ) as Box<Credential>
+            })
+            .collect())
     }
 
     /// Return the underlying builder object with an `Any` type so that it can
@@ -409,10 +1063,227 @@ impl CredentialBuilderApi for WinCredentialBuilder {
     fn as_any(&self) -> &dyn std::any::Any {
         self
     }
+
+    /// Generic credentials can hold at most `CRED_MAX_CREDENTIAL_BLOB_SIZE` bytes.
+    fn info(&self) -> CredentialStoreInfo {
+        CredentialStoreInfo::new("windows", self.persistence(), self.at_rest_encryption())
+            .with_attributes(true)
+            .with_search(true)
+            .with_max_secret_len(Some(CRED_MAX_CREDENTIAL_BLOB_SIZE as usize))
+    }
 }
 
-fn extract_password(credential: &CREDENTIALW) -> Result<String> {
-    let mut blob = extract_secret(credential)?;
+impl WinCredentialBuilder {
+    /// Return a builder whose credentials treat NULs in secrets as given.
+    ///
+    /// See the module header for details.
+    pub fn with_nul_handling(nul_handling: NulHandling) -> Self {
+        Self {
+            nul_handling,
+            ..Default::default()
+        }
+    }
+
+    /// How this builder's credentials treat NULs in secrets.
+    pub fn nul_handling(&self) -> NulHandling {
+        self.nul_handling
+    }
+
+    /// Return this builder with relaxed lookup turned on or off
+    /// for the credentials it builds.
+    ///
+    /// See the module header for details.
+    pub fn with_relaxed_lookup(mut self, relaxed_lookup: bool) -> Self {
+        self.relaxed_lookup = relaxed_lookup;
+        self
+    }
+
+    /// Whether this builder's credentials use relaxed lookup.
+    pub fn relaxed_lookup(&self) -> bool {
+        self.relaxed_lookup
+    }
+
+    /// Return this builder with the given encoding of passwords
+    /// for the credentials it builds.
+    ///
+    /// See the module header for details.
+    pub fn with_password_encoding(mut self, password_encoding: PasswordEncoding) -> Self {
+        self.password_encoding = password_encoding;
+        self
+    }
+
+    /// How this builder's credentials encode passwords.
+    pub fn password_encoding(&self) -> PasswordEncoding {
+        self.password_encoding
+    }
+
+    /// Return this builder with the given persistence for the credentials it writes.
+    ///
+    /// See the module header for details.
+    pub fn with_persist(mut self, persist: PersistScope) -> Self {
+        self.persist = persist;
+        self
+    }
+
+    /// The persistence of the credentials this builder writes.
+    pub fn persist(&self) -> PersistScope {
+        self.persist
+    }
+
+    /// Return this builder with the given type of Windows credential
+    /// for the credentials it builds.
+    ///
+    /// See the module header for details.
+    pub fn with_cred_type(mut self, cred_type: WinCredentialType) -> Self {
+        self.cred_type = cred_type;
+        self
+    }
+
+    /// The type of Windows credential this builder's credentials use.
+    pub fn cred_type(&self) -> WinCredentialType {
+        self.cred_type
+    }
+
+    /// Find the Generic credentials whose target alias is matched by the given matcher.
+    ///
+    /// All the Generic credentials in the store are enumerated, and their
+    /// aliases are matched by this crate, so this may be slow if there are
+    /// many credentials. The secrets of the found credentials are not read.
+    /// The found credentials are sorted by target name.
+    pub fn search_by_alias(&self, alias: &Match) -> Result<Vec<WinCredential>> {
+        let mut found = enumerate_credentials(None)?;
+        found.retain(|cred| alias.matches(&cred.target_alias));
+        found.sort_by(|a, b| a.target_name.cmp(&b.target_name));
+        Ok(found)
+    }
+
+    /// List the credentials in the Credential Manager whose target names
+    /// match the given filter (or all of them, if there's no filter).
+    ///
+    /// This is for administrative and migration tools, so, unlike search,
+    /// it lists domain credentials as well as Generic ones, including
+    /// those not written by this crate. (Credentials of other types are
+    /// not listed.) The filter is passed as-is to `CredEnumerateW`, so
+    /// it can end with a `*` wildcard. Secrets are not read. The listed
+    /// credentials are sorted by target name.
+    pub fn list(&self, filter: Option<&str>) -> Result<Vec<WinCredentialListing>> {
+        let mut found = enumerate(filter, WinCredentialListing::extract)?;
+        found.sort_by(|a, b| a.target_name.cmp(&b.target_name));
+        Ok(found)
+    }
+}
+
+/// Enumerate the Generic credentials whose target names match the given filter.
+///
+/// The filter is passed to `CredEnumerateW`, so it can end with a `*` wildcard.
+/// If there is no filter, all Generic credentials are returned.
+fn enumerate_credentials(filter: Option<&str>) -> Result<Vec<WinCredential>> {
+    enumerate(filter, |w_credential| {
+        if w_credential.Type != CRED_TYPE_GENERIC {
+            return None;
+        }
+        WinCredential::extract_credential(w_credential).ok()
+    })
+}
+
+/// Enumerate the credentials (of any type) whose target names match the given filter,
+/// keeping those that `extract` makes something of.
+///
+/// The filter is passed to `CredEnumerateW`, as in [enumerate_credentials].
+/// The secrets of the enumerated credentials are erased after extraction.
+fn enumerate<T>(
+    filter: Option<&str>,
+    extract: impl Fn(&CREDENTIALW) -> Option<T>,
+) -> Result<Vec<T>> {
+    let filter = filter.map(to_wstr);
+    let p_filter = filter.as_ref().map_or(std::ptr::null(), |f| f.as_ptr());
+    let mut count: u32 = 0;
+    let mut p_credentials: *mut *mut CREDENTIALW = std::ptr::null_mut();
+    if unsafe { CredEnumerateW(p_filter, 0, &mut count, &mut p_credentials) } == 0 {
+        // an empty enumeration is reported as ERROR_NOT_FOUND
+        return match decode_error() {
+            ErrorCode::NoEntry => Ok(Vec::new()),
+            err => Err(err),
+        };
+    }
+    // `CredEnumerateW` succeeded, so p_credentials points at an allocated
+    // array of pointers to credentials, all of which must be freed at once.
+    let w_credentials = unsafe { std::slice::from_raw_parts(p_credentials, count as usize) };
+    let mut results: Vec<T> = Vec::new();
+    for &p_credential in w_credentials {
+        let w_credential = unsafe { &*p_credential };
+        if let Some(result) = extract(w_credential) {
+            results.push(result);
+        }
+        erase_secret(w_credential);
+    }
+    unsafe { CredFree(p_credentials as *mut _) };
+    Ok(results)
+}
+
+/// Read the credential with the given target name and type.
+///
+/// The returned credential has been allocated by `CredReadW`,
+/// so the caller must free it with `CredFree`.
+fn read_credential(target_name: &str, cred_type: CRED_TYPE) -> Result<*mut CREDENTIALW> {
+    let mut p_credential = MaybeUninit::uninit();
+    // at this point, p_credential is just a pointer to nowhere.
+    // The allocation happens in the `CredReadW` call below.
+    let target_name = to_wstr(target_name);
+    let result = unsafe {
+        CredReadW(
+            target_name.as_ptr(),
+            cred_type,
+            0,
+            p_credential.as_mut_ptr(),
+        )
+    };
+    match result {
+        // `CredReadW` failed, so no allocation has been done, so no free needs to be done
+        0 => Err(decode_error()),
+        // `CredReadW` succeeded, so p_credential points at an allocated credential.
+        _ => Ok(unsafe { p_credential.assume_init() }),
+    }
+}
+
+/// Normalize a target name for relaxed lookup, ignoring case, surrounding
+/// whitespace, and the `LegacyGeneric:target=` prefix.
+fn relaxed_target(target_name: &str) -> String {
+    let target_name = target_name.trim().to_lowercase();
+    match target_name.strip_prefix("legacygeneric:target=") {
+        Some(rest) => rest.trim().to_string(),
+        None => target_name,
+    }
+}
+
+fn extract_password(credential: &CREDENTIALW, encoding: PasswordEncoding) -> Result<String> {
+    let blob = extract_secret(credential)?;
+    match encoding {
+        PasswordEncoding::Utf16 => decode_utf16(blob),
+        PasswordEncoding::Utf8 => decode_utf8(blob),
+        PasswordEncoding::Auto if looks_like_utf8(&blob) => decode_utf8(blob),
+        PasswordEncoding::Auto => decode_utf16(blob),
+    }
+}
+
+/// Whether a secret of unknown encoding is (most likely) UTF-8.
+///
+/// UTF-16 text usually contains NULs (as the high bytes of ASCII characters)
+/// or other control characters when its bytes are read as UTF-8, so secrets
+/// that are valid UTF-8 without control characters (other than whitespace)
+/// are taken to be UTF-8.
+fn looks_like_utf8(blob: &[u8]) -> bool {
+    str::from_utf8(blob).is_ok_and(|s| {
+        !s.chars()
+            .any(|c| c.is_control() && !matches!(c, '\t' | '\n' | '\r'))
+    })
+}
+
+fn decode_utf8(blob: Vec<u8>) -> Result<String> {
+    String::from_utf8(blob).map_err(|err| ErrorCode::BadEncoding(err.into_bytes()))
+}
+
+fn decode_utf16(mut blob: Vec<u8>) -> Result<String> {
     // 3rd parties may write credential data with an odd number of bytes,
     // so we make sure that we don't try to decode those as utf16
     if blob.len() % 2 != 0 {
@@ -435,6 +1306,29 @@ fn extract_password(credential: &CREDENTIALW) -> Result<String> {
     result
 }
 
+/// The custom attributes of a credential, by keyword.
+fn extract_attributes(credential: &CREDENTIALW) -> BTreeMap<String, Vec<u8>> {
+    if credential.Attributes.is_null() || credential.AttributeCount == 0 {
+        return BTreeMap::new();
+    }
+    let attributes = unsafe {
+        std::slice::from_raw_parts(credential.Attributes, credential.AttributeCount as usize)
+    };
+    attributes
+        .iter()
+        .map(|attribute| {
+            let keyword = unsafe { from_wstr(attribute.Keyword) };
+            let value = match attribute.ValueSize {
+                0 => Vec::new(),
+                size => {
+                    unsafe { std::slice::from_raw_parts(attribute.Value, size as usize) }.to_vec()
+                }
+            };
+            (keyword, value)
+        })
+        .collect()
+}
+
 fn extract_secret(credential: &CREDENTIALW) -> Result<Vec<u8>> {
     let blob_pointer: *const u8 = credential.CredentialBlob;
     let blob_len: usize = credential.CredentialBlobSize as usize;
@@ -455,6 +1349,13 @@ fn erase_secret(credential: &CREDENTIALW) {
     blob.zeroize();
 }
 
+fn nul_in(name: &str) -> ErrorCode {
+    ErrorCode::Invalid(
+        name.to_string(),
+        "cannot contain a NUL character".to_string(),
+    )
+}
+
 fn to_wstr(s: &str) -> Vec<u16> {
     s.encode_utf16().chain(once(0)).collect()
 }
@@ -535,6 +1436,54 @@ mod tests {
         crate::tests::entry_from_constructor(WinCredential::new_with_target, service, user)
     }
 
+    #[test]
+    fn test_filetime_to_unix() {
+        // 2001-09-09T01:46:40Z is 1,000,000,000 seconds after the Unix epoch
+        let intervals: u64 = (11_644_473_600 + 1_000_000_000) * 10_000_000;
+        let time = FILETIME {
+            dwLowDateTime: intervals as u32,
+            dwHighDateTime: (intervals >> 32) as u32,
+        };
+        assert_eq!(
+            filetime_to_unix(&time),
+            Some(std::time::Duration::from_secs(1_000_000_000))
+        );
+        let before = FILETIME {
+            dwLowDateTime: 0,
+            dwHighDateTime: 0,
+        };
+        assert_eq!(filetime_to_unix(&before), None);
+    }
+
+    #[test]
+    fn test_search() {
+        let service = generate_random_string();
+        let users = ["tenant1:alice", "tenant1:bob", "tenant2:carol"];
+        for user in users {
+            entry_new(&service, user)
+                .set_password("test search")
+                .expect("Can't set password for search");
+        }
+        let spec = SearchSpec {
+            service: Match::Exact(service.clone()),
+            user: Match::Prefix("tenant1:".to_string()),
+            ..Default::default()
+        };
+        let found = default_credential_builder()
+            .search(&spec)
+            .expect("Can't search");
+        let found_users: Vec<String> = found
+            .iter()
+            .map(|cred| cred.spec().expect("Found credential has no spec").user)
+            .collect();
+        assert_eq!(found_users, vec!["tenant1:alice", "tenant1:bob"]);
+        for user in users {
+            entry_new(&service, user)
+                .delete_credential()
+                .expect("Can't delete search credential");
+        }
+    }
+
     #[test]
     fn test_bad_password() {
         fn make_platform_credential(password: &mut Vec<u8>) -> CREDENTIALW {
@@ -600,6 +1549,12 @@ mod tests {
             target_name: "target_name".to_string(),
             target_alias: "target_alias".to_string(),
             comment: "comment".to_string(),
+            nul_handling: NulHandling::default(),
+            relaxed_lookup: false,
+            password_encoding: PasswordEncoding::default(),
+            persist: PersistScope::default(),
+            cred_type: WinCredentialType::default(),
+            attributes: BTreeMap::new(),
         };
         for (attr, len) in [
             ("user", CRED_MAX_USERNAME_LENGTH),
@@ -640,6 +1595,12 @@ mod tests {
             target_name: "target_name".to_string(),
             target_alias: "target_alias".to_string(),
             comment: "comment".to_string(),
+            nul_handling: NulHandling::default(),
+            relaxed_lookup: false,
+            password_encoding: PasswordEncoding::default(),
+            persist: PersistScope::default(),
+            cred_type: WinCredentialType::default(),
+            attributes: BTreeMap::new(),
         };
 
         let len = CRED_MAX_CREDENTIAL_BLOB_SIZE / 2;
@@ -694,6 +1655,72 @@ mod tests {
         crate::tests::test_update(entry_new);
     }
 
+    #[test]
+    fn test_label() {
+        let name = generate_random_string();
+        let entry = entry_new(&name, &name);
+        assert!(matches!(entry.get_label(), Err(ErrorCode::NoEntry)));
+        entry
+            .set_password("test label")
+            .expect("Can't set password for label test");
+        entry.set_label("My account").expect("Can't set label");
+        assert_eq!(entry.get_label().expect("Can't get label"), "My account");
+        assert_eq!(entry.get_attributes().unwrap()["comment"], "My account");
+        entry
+            .delete_credential()
+            .expect("Couldn't delete after label test");
+    }
+
+    #[test]
+    fn test_persist() {
+        let builder = WinCredentialBuilder::default().with_persist(PersistScope::Session);
+        assert_eq!(builder.persist(), PersistScope::Session);
+        assert!(matches!(
+            builder.persistence(),
+            CredentialPersistence::UntilLogout
+        ));
+        let name = generate_random_string();
+        let entry = Entry::new_with_credential(builder.build(None, &name, &name).unwrap());
+        entry
+            .set_password("test persist")
+            .expect("Can't set session password");
+        let cred: &WinCredential = entry.get_credential().downcast_ref().unwrap();
+        let stored = cred
+            .get_credential()
+            .expect("Can't read session credential");
+        assert_eq!(stored.persist, PersistScope::Session);
+        entry
+            .delete_credential()
+            .expect("Couldn't delete after persist test");
+    }
+
+    #[test]
+    fn test_domain_password() {
+        let builder =
+            WinCredentialBuilder::default().with_cred_type(WinCredentialType::DomainPassword);
+        assert_eq!(builder.cred_type(), WinCredentialType::DomainPassword);
+        let name = generate_random_string();
+        let target = format!("{name}.example.com");
+        let entry = Entry::new_with_credential(builder.build(Some(&target), &name, &name).unwrap());
+        entry
+            .set_password("test domain password")
+            .expect("Can't set domain password");
+        assert!(matches!(
+            entry.get_password(),
+            Err(ErrorCode::NotSupportedByStore(_))
+        ));
+        entry
+            .update_attributes(&HashMap::from([("comment", "domain comment")]))
+            .expect("Can't update domain credential attributes");
+        let cred: &WinCredential = entry.get_credential().downcast_ref().unwrap();
+        let stored = cred.get_credential().expect("Can't read domain credential");
+        assert_eq!(stored.cred_type, WinCredentialType::DomainPassword);
+        assert_eq!(stored.comment, "domain comment");
+        entry
+            .delete_credential()
+            .expect("Couldn't delete after domain password test");
+    }
+
     #[test]
     fn test_get_update_attributes() {
         let name = generate_random_string();
@@ -745,6 +1772,259 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_target_alias() {
+        let name = generate_random_string();
+        let alias = generate_random_string();
+        let cred = WinCredential::new_with_target(None, &name, &name)
+            .expect("Can't create credential for alias test");
+        assert!(
+            matches!(cred.set_target_alias(&alias), Err(ErrorCode::NoEntry)),
+            "Set alias on missing credential"
+        );
+        cred.set_password("test password for alias")
+            .expect("Can't set password for alias test");
+        cred.set_target_alias(&alias).expect("Can't set alias");
+        assert_eq!(cred.get_target_alias().expect("Can't get alias"), alias);
+        let found = WinCredentialBuilder::default()
+            .search_by_alias(&Match::Exact(alias.clone()))
+            .expect("Can't search by alias");
+        assert_eq!(found.len(), 1, "Wrong number of credentials for alias");
+        assert_eq!(found[0].target_name, cred.target_name);
+        let found = WinCredentialBuilder::default()
+            .search_by_alias(&Match::Prefix(alias[..10].to_string()))
+            .expect("Can't search by alias prefix");
+        assert_eq!(
+            found.len(),
+            1,
+            "Wrong number of credentials for alias prefix"
+        );
+        cred.delete_credential()
+            .expect("Can't delete credential for alias test");
+    }
+
+    #[test]
+    fn test_nul_handling() {
+        let name = generate_random_string();
+        let entry = entry_new(&name, &name);
+        let secret = b"nul\0in the middle and at the end\0";
+        entry
+            .set_secret(secret)
+            .expect("Can't set secret with NULs");
+        assert_eq!(entry.get_secret().unwrap(), secret);
+        entry
+            .set_password("nul\0password\0")
+            .expect("Can't set password with NULs");
+        assert_eq!(entry.get_password().unwrap(), "nul\0password\0");
+        entry
+            .delete_credential()
+            .expect("Can't delete credential with NULs");
+        let builder = WinCredentialBuilder::with_nul_handling(NulHandling::Reject);
+        let entry = Entry::new_with_credential(builder.build(None, &name, &name).unwrap());
+        assert!(matches!(
+            entry.set_secret(secret),
+            Err(ErrorCode::Invalid(_, _))
+        ));
+        assert!(matches!(
+            entry.set_password("nul\0password"),
+            Err(ErrorCode::Invalid(_, _))
+        ));
+        assert!(matches!(entry.get_secret(), Err(ErrorCode::NoEntry)));
+        assert!(matches!(
+            WinCredential::new_with_target(None, &name, "nul\0user"),
+            Err(ErrorCode::Invalid(_, _))
+        ));
+    }
+
+    #[test]
+    fn test_relaxed_lookup() {
+        let name = generate_random_string();
+        let native = WinCredential::new_with_target(Some(&name.to_uppercase()), &name, &name)
+            .expect("Can't create native-style credential");
+        native
+            .set_password("test relaxed lookup")
+            .expect("Can't set native-style password");
+        let strict = entry_new(&name, &name);
+        assert!(matches!(strict.get_password(), Err(ErrorCode::NoEntry)));
+        let builder = WinCredentialBuilder::default().with_relaxed_lookup(true);
+        let relaxed = Entry::new_with_credential(builder.build(None, &name, &name).unwrap());
+        assert_eq!(relaxed.get_password().unwrap(), "test relaxed lookup");
+        let other_user = Entry::new_with_credential(builder.build(None, &name, "other").unwrap());
+        assert!(matches!(other_user.get_password(), Err(ErrorCode::NoEntry)));
+        assert!(matches!(
+            relaxed.delete_credential(),
+            Err(ErrorCode::NoEntry)
+        ));
+        native
+            .delete_credential()
+            .expect("Can't delete native-style credential");
+        assert!(matches!(relaxed.get_password(), Err(ErrorCode::NoEntry)));
+        assert_eq!(
+            relaxed_target(" LegacyGeneric:target=My.Target "),
+            "my.target"
+        );
+    }
+
+    #[test]
+    fn test_raw_enumerate() {
+        let name = generate_random_string();
+        let entry = entry_new(&name, &name);
+        entry
+            .set_password("test raw enumerate")
+            .expect("Can't set password for raw enumerate");
+        let found =
+            WinCredential::raw_enumerate(&format!("{name}*")).expect("Can't do raw enumerate");
+        assert_eq!(found.len(), 1, "Wrong number of enumerated credentials");
+        assert_eq!(found[0].target_name, format!("{name}.{name}"));
+        entry
+            .delete_credential()
+            .expect("Can't delete raw enumerate credential");
+    }
+
+    #[test]
+    fn test_looks_like_utf8() {
+        let utf16 = |s: &str| {
+            let wide = to_wstr_no_null(s);
+            let mut blob = vec![0; wide.len() * 2];
+            LittleEndian::write_u16_into(&wide, &mut blob);
+            blob
+        };
+        for password in ["password", "pass word\n", "笑 and ü", ""] {
+            assert!(looks_like_utf8(password.as_bytes()), "{password} not UTF-8");
+            if !password.is_empty() {
+                assert!(!looks_like_utf8(&utf16(password)), "{password} UTF-8");
+            }
+        }
+        assert!(!looks_like_utf8(&[0xff, 0xfe]));
+    }
+
+    #[test]
+    fn test_password_encoding() {
+        let name = generate_random_string();
+        let password = "test password encoding ü";
+        let utf8 = WinCredential::new_with_target(None, &name, &name)
+            .expect("Can't create UTF-8 credential")
+            .with_password_encoding(PasswordEncoding::Utf8);
+        utf8.set_password(password)
+            .expect("Can't set UTF-8 password");
+        assert_eq!(
+            utf8.get_secret().expect("Can't get UTF-8 secret"),
+            password.as_bytes()
+        );
+        assert_eq!(
+            utf8.get_password().expect("Can't get UTF-8 password"),
+            password
+        );
+        let auto = utf8.clone().with_password_encoding(PasswordEncoding::Auto);
+        assert_eq!(
+            auto.get_password().expect("Can't auto-detect UTF-8"),
+            password
+        );
+        let utf16 = utf8.clone().with_password_encoding(PasswordEncoding::Utf16);
+        // the UTF-8 encoding of the password has an odd number of bytes
+        assert!(matches!(
+            utf16.get_password(),
+            Err(ErrorCode::BadEncoding(_))
+        ));
+        utf16
+            .set_password(password)
+            .expect("Can't set UTF-16 password");
+        assert_eq!(
+            auto.get_password().expect("Can't auto-detect UTF-16"),
+            password
+        );
+        assert!(utf8.get_password().is_err(), "Read UTF-16 as UTF-8");
+        utf8.delete_credential()
+            .expect("Can't delete password encoding credential");
+    }
+
+    #[test]
+    fn test_custom_attributes() {
+        let name = generate_random_string();
+        let cred = WinCredential::new_with_target(None, &name, &name)
+            .expect("Can't create credential for attributes test");
+        assert!(
+            matches!(
+                cred.set_custom_attribute("rotated", b"2024-01-01"),
+                Err(ErrorCode::NoEntry)
+            ),
+            "Set attribute on missing credential"
+        );
+        cred.set_password("test password for attributes")
+            .expect("Can't set password for attributes test");
+        cred.set_custom_attribute("rotated", b"2024-01-01")
+            .expect("Can't set custom attribute");
+        cred.set_custom_attribute("raw", &[0, 255])
+            .expect("Can't set binary custom attribute");
+        cred.update_attributes(&HashMap::from([("schema", "v2")]))
+            .expect("Can't update attributes");
+        let attributes = cred.get_attributes().expect("Can't get attributes");
+        assert_eq!(attributes["rotated"], "2024-01-01");
+        assert_eq!(attributes["schema"], "v2");
+        assert!(
+            !attributes.contains_key("raw"),
+            "Non-UTF-8 attribute listed"
+        );
+        // setting the password keeps the attributes, and so does the secret
+        cred.set_password("new password for attributes")
+            .expect("Can't reset password for attributes test");
+        let custom = cred
+            .get_custom_attributes()
+            .expect("Can't get custom attributes");
+        assert_eq!(custom.len(), 3);
+        assert_eq!(custom["raw"], vec![0, 255]);
+        assert_eq!(
+            cred.get_password().expect("Can't get password"),
+            "new password for attributes"
+        );
+        cred.remove_custom_attribute("raw")
+            .expect("Can't remove custom attribute");
+        assert!(
+            !cred
+                .get_custom_attributes()
+                .expect("Can't get custom attributes")
+                .contains_key("raw")
+        );
+        let too_long = vec![b'x'; CRED_MAX_VALUE_SIZE as usize + 1];
+        assert!(matches!(
+            cred.set_custom_attribute("long", &too_long),
+            Err(ErrorCode::TooLong(_, _))
+        ));
+        cred.delete_credential()
+            .expect("Can't delete credential for attributes test");
+    }
+
+    #[test]
+    fn test_list() {
+        let name = generate_random_string();
+        let entry = entry_new(&name, &name);
+        entry
+            .set_password("test list")
+            .expect("Can't set password for list");
+        let builder = WinCredentialBuilder::default();
+        let found = builder
+            .list(Some(&format!("{name}*")))
+            .expect("Can't list credentials");
+        assert_eq!(found.len(), 1, "Wrong number of listed credentials");
+        let listing = &found[0];
+        assert_eq!(listing.target_name, format!("{name}.{name}"));
+        assert_eq!(listing.username, name);
+        assert_eq!(listing.cred_type, WinCredentialType::Generic);
+        assert!(listing.comment.contains("keyring"), "Unexpected comment");
+        let metadata = entry.get_metadata().expect("Can't get metadata");
+        assert_eq!(listing.last_written, metadata.modified);
+        let cred = listing.credential();
+        assert_eq!(cred.get_password().expect("Can't read listed"), "test list");
+        cred.delete_credential()
+            .expect("Can't delete listed credential");
+        assert!(
+            builder
+                .list(Some(&format!("{name}*")))
+                .expect("Can't list credentials")
+                .is_empty()
+        );
+    }
+
     #[test]
     fn test_get_credential() {
         let name = generate_random_string();
@@ -0,0 +1,418 @@
+/*!
+
+# Windows DPAPI file credential store
+
+The Windows Credential Manager limits secrets to 2560 bytes, which is too
+small for some tokens and certificates. This module provides an alternative
+Windows store, with no limit on the size of secrets, that keeps each
+credential in a file of its own, encrypted with the Data Protection API
+(`CryptProtectData`) so that only the same Windows user can decrypt it.
+It's available when the `windows-dpapi` feature is enabled. It's not
+the default store, so clients have to set it as their default (or build
+their credentials from it directly):
+```no_run
+keyring::set_default_credential_builder(keyring::windows_dpapi::default_credential_builder());
+```
+
+By default, the files are kept in the `keyring-rs\dpapi` folder of the
+user's local (non-roaming) application data folder, which is created when
+the first credential is stored; a builder can be given another folder with
+[with_dir](DpapiCredentialBuilder::with_dir). (DPAPI keys don't roam, so
+neither should the files.) Each file's name is made from the hex encoding
+of its credential's target, service, and user, so the three together
+can't be more than about 120 bytes long. The name is also passed to DPAPI
+as extra entropy, so a file that's renamed (or copied over another)
+won't decrypt.
+
+Credentials have no attributes. The store supports [search](crate::search).
+ */
+use std::path::{Path, PathBuf};
+
+use windows_sys::Win32::Foundation::LocalFree;
+use windows_sys::Win32::Security::Cryptography::{
+    CRYPT_INTEGER_BLOB, CRYPTPROTECT_UI_FORBIDDEN, CryptProtectData, CryptUnprotectData,
+};
+use zeroize::Zeroizing;
+
+use super::credential::{
+    AtRestStatus, Credential, CredentialApi, CredentialBuilder, CredentialBuilderApi,
+    CredentialPersistence, CredentialSpec, CredentialStoreInfo, SearchSpec,
+};
+use super::error::{Error as ErrorCode, Result};
+
+/// The extension of credential files.
+const EXTENSION: &str = "dpapi";
+
+/// A credential kept in a DPAPI-encrypted file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DpapiCredential {
+    pub target: Option<String>,
+    pub service: String,
+    pub user: String,
+    /// The folder that holds the credential's file
+    pub dir: PathBuf,
+}
+
+impl CredentialApi for DpapiCredential {
+    /// Encrypt the secret and write it to this credential's file,
+    /// replacing any existing one.
+    fn set_secret(&self, secret: &[u8]) -> Result<()> {
+        let name = self.file_name();
+        let sealed = protect(secret, name.as_bytes())?;
+        std::fs::create_dir_all(&self.dir).map_err(platform_failure)?;
+        let temp = self.dir.join(format!("{name}.tmp"));
+        std::fs::write(&temp, &sealed)
+            .and_then(|_| std::fs::rename(&temp, self.dir.join(&name)))
+            .map_err(platform_failure)
+    }
+
+    /// Read and decrypt the secret in this credential's file, if it exists.
+    ///
+    /// Returns a [NoEntry](ErrorCode::NoEntry) error if there is no file.
+    fn get_secret(&self) -> Result<Vec<u8>> {
+        let name = self.file_name();
+        let sealed = match std::fs::read(self.dir.join(&name)) {
+            Ok(sealed) => sealed,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return Err(ErrorCode::NoEntry);
+            }
+            Err(err) => return Err(platform_failure(err)),
+        };
+        let mut secret = unprotect(&sealed, name.as_bytes())?;
+        Ok(std::mem::take(&mut *secret))
+    }
+
+    /// Whether this credential's file exists (which is checked without decrypting it).
+    fn exists(&self) -> Result<bool> {
+        self.dir
+            .join(self.file_name())
+            .try_exists()
+            .map_err(platform_failure)
+    }
+
+    /// Delete this credential's file, if it exists.
+    ///
+    /// Returns a [NoEntry](ErrorCode::NoEntry) error if there is no file.
+    fn delete_credential(&self) -> Result<()> {
+        match std::fs::remove_file(self.dir.join(self.file_name())) {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Err(ErrorCode::NoEntry),
+            Err(err) => Err(platform_failure(err)),
+        }
+    }
+
+    /// The target, service, and user of this credential.
+    fn spec(&self) -> Option<CredentialSpec> {
+        Some(CredentialSpec {
+            target: self.target.clone(),
+            service: self.service.clone(),
+            user: self.user.clone(),
+        })
+    }
+
+    /// DPAPI encrypts the files.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        AtRestStatus::Encrypted
+    }
+
+    /// Return the underlying concrete object with an `Any` type so that it can
+    /// be downgraded to a [DpapiCredential] for platform-specific processing.
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    /// Expose the concrete debug formatter for use via the [Credential] trait
+    fn debug_fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self, f)
+    }
+}
+
+impl DpapiCredential {
+    /// The name of this credential's file: the hex-encoded target
+    /// (or `-` if there is none), service, and user, separated by dots.
+    pub fn file_name(&self) -> String {
+        let target = match &self.target {
+            Some(target) => hex_encode(target.as_bytes()),
+            None => "-".to_string(),
+        };
+        format!(
+            "{target}.{}.{}.{EXTENSION}",
+            hex_encode(self.service.as_bytes()),
+            hex_encode(self.user.as_bytes())
+        )
+    }
+
+    /// Parse a file name made by [file_name](DpapiCredential::file_name).
+    fn from_file_name(dir: &Path, name: &str) -> Option<Self> {
+        let mut parts = name.strip_suffix(&format!(".{EXTENSION}"))?.split('.');
+        let target = match parts.next()? {
+            "-" => None,
+            target => Some(hex_decode(target)?),
+        };
+        let service = hex_decode(parts.next()?)?;
+        let user = hex_decode(parts.next()?)?;
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(Self {
+            target,
+            service,
+            user,
+            dir: dir.to_path_buf(),
+        })
+    }
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn hex_decode(hex: &str) -> Option<String> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    String::from_utf8(bytes).ok()
+}
+
+/// Encrypt data for the current user with `CryptProtectData`.
+fn protect(data: &[u8], entropy: &[u8]) -> Result<Vec<u8>> {
+    let input = blob(data);
+    let entropy = blob(entropy);
+    let mut output = CRYPT_INTEGER_BLOB {
+        cbData: 0,
+        pbData: std::ptr::null_mut(),
+    };
+    let ok = unsafe {
+        CryptProtectData(
+            &input,
+            std::ptr::null(),
+            &entropy,
+            std::ptr::null(),
+            std::ptr::null(),
+            CRYPTPROTECT_UI_FORBIDDEN,
+            &mut output,
+        )
+    };
+    if ok == 0 {
+        return Err(platform_failure(std::io::Error::last_os_error()));
+    }
+    Ok(take_blob(output).to_vec())
+}
+
+/// Decrypt data encrypted by [protect] with `CryptUnprotectData`.
+///
+/// Data that was encrypted for another user (or with other entropy)
+/// produces an [Invalid](ErrorCode::Invalid) error.
+fn unprotect(data: &[u8], entropy: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
+    let input = blob(data);
+    let entropy = blob(entropy);
+    let mut output = CRYPT_INTEGER_BLOB {
+        cbData: 0,
+        pbData: std::ptr::null_mut(),
+    };
+    let ok = unsafe {
+        CryptUnprotectData(
+            &input,
+            std::ptr::null_mut(),
+            &entropy,
+            std::ptr::null(),
+            std::ptr::null(),
+            CRYPTPROTECT_UI_FORBIDDEN,
+            &mut output,
+        )
+    };
+    if ok == 0 {
+        return Err(ErrorCode::Invalid(
+            "credential file".to_string(),
+            format!("can't be decrypted: {}", std::io::Error::last_os_error()),
+        ));
+    }
+    Ok(take_blob(output))
+}
+
+fn blob(data: &[u8]) -> CRYPT_INTEGER_BLOB {
+    CRYPT_INTEGER_BLOB {
+        cbData: data.len() as u32,
+        pbData: data.as_ptr() as *mut u8,
+    }
+}
+
+/// Copy the data out of a blob allocated by DPAPI, and free it.
+fn take_blob(blob: CRYPT_INTEGER_BLOB) -> Zeroizing<Vec<u8>> {
+    // DPAPI allocated the blob's data with `LocalAlloc`, so it must be
+    // freed with `LocalFree` (after being wiped, since it may be a secret).
+    let data = unsafe { std::slice::from_raw_parts_mut(blob.pbData, blob.cbData as usize) };
+    let copy = Zeroizing::new(data.to_vec());
+    zeroize::Zeroize::zeroize(data);
+    unsafe { LocalFree(blob.pbData as _) };
+    copy
+}
+
+fn platform_failure(err: std::io::Error) -> ErrorCode {
+    ErrorCode::PlatformFailure(Box::new(err))
+}
+
+/// The builder for DPAPI file credentials.
+#[derive(Debug, Default, Clone)]
+pub struct DpapiCredentialBuilder {
+    dir: Option<PathBuf>,
+}
+
+/// Returns an instance of the DPAPI file credential builder.
+///
+/// This is never called by the crate, since this is
+/// not the default store on Windows.
+pub fn default_credential_builder() -> Box<CredentialBuilder> {
+    Box::new(DpapiCredentialBuilder::default())
+}
+
+impl DpapiCredentialBuilder {
+    /// Return this builder with its credentials kept in the given folder.
+    pub fn with_dir(mut self, dir: impl AsRef<Path>) -> Self {
+        self.dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// The folder this builder's credentials are kept in, if one was given.
+    pub fn dir(&self) -> Option<&Path> {
+        self.dir.as_deref()
+    }
+
+    /// The folder to keep credentials in: the given one, or the default.
+    fn resolved_dir(&self) -> Result<PathBuf> {
+        if let Some(dir) = &self.dir {
+            return Ok(dir.clone());
+        }
+        match std::env::var_os("LOCALAPPDATA") {
+            Some(app_data) => Ok(PathBuf::from(app_data).join("keyring-rs").join("dpapi")),
+            None => Err(ErrorCode::NoStorageAccess(Box::from(
+                "LOCALAPPDATA is not set, so there's no folder for credential files",
+            ))),
+        }
+    }
+}
+
+impl CredentialBuilderApi for DpapiCredentialBuilder {
+    /// Build a [DpapiCredential] for the given target, service, and user.
+    fn build(&self, target: Option<&str>, service: &str, user: &str) -> Result<Box<Credential>> {
+        Ok(Box::new(DpapiCredential {
+            target: target.map(|t| t.to_string()),
+            service: service.to_string(),
+            user: user.to_string(),
+            dir: self.resolved_dir()?,
+        }))
+    }
+
+    /// DPAPI encrypts the files.
+    fn at_rest_encryption(&self) -> AtRestStatus {
+        AtRestStatus::Encrypted
+    }
+
+    /// Return the underlying builder object with an `Any` type so that it can
+    /// be downgraded to a [DpapiCredentialBuilder] for platform-specific processing.
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    /// Files are kept until they are deleted.
+    fn persistence(&self) -> CredentialPersistence {
+        CredentialPersistence::UntilDelete
+    }
+
+    /// Return a credential for each credential file that matches the spec.
+    fn search(&self, spec: &SearchSpec) -> Result<Vec<Box<Credential>>> {
+        let dir = self.resolved_dir()?;
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(platform_failure(err)),
+        };
+        let mut found = Vec::new();
+        for entry in entries {
+            let name = entry.map_err(platform_failure)?.file_name();
+            let Some(cred) = name
+                .to_str()
+                .and_then(|name| DpapiCredential::from_file_name(&dir, name))
+            else {
+                continue;
+            };
+            if spec.matches(cred.target.as_deref(), &cred.service, &cred.user) {
+                found.push(cred);
+            }
+        }
+        if !spec.unsorted {
+            found.sort_by(|a, b| {
+                (&a.service, &a.user, &a.target).cmp(&(&b.service, &b.user, &b.target))
+            });
+        }
+        Ok(found
+            .into_iter()
+            .map(|cred| Box::new(cred) as Box<Credential>)
+            .collect())
+    }
+
+    /// The store can be searched, and secrets have no size limit.
+    fn info(&self) -> CredentialStoreInfo {
+        CredentialStoreInfo::new(
+            "windows-dpapi",
+            self.persistence(),
+            self.at_rest_encryption(),
+        )
+        .with_search(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DpapiCredential, DpapiCredentialBuilder};
+    use crate::credential::{CredentialBuilderApi, Match, SearchSpec};
+    use crate::{Entry, Error, tests::generate_random_string};
+
+    #[test]
+    fn test_file_name() {
+        let dir = std::env::temp_dir();
+        let cred = DpapiCredential {
+            target: None,
+            service: "service".to_string(),
+            user: "user.name".to_string(),
+            dir: dir.clone(),
+        };
+        let name = cred.file_name();
+        assert_eq!(name, "-.73657276696365.757365722e6e616d65.dpapi");
+        assert_eq!(DpapiCredential::from_file_name(&dir, &name), Some(cred));
+        assert_eq!(DpapiCredential::from_file_name(&dir, "x.dpapi"), None);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let dir = std::env::temp_dir().join(format!("keyring-dpapi-{}", generate_random_string()));
+        let builder = DpapiCredentialBuilder::default().with_dir(&dir);
+        let entry = Entry::new_with_credential(builder.build(None, "service", "user").unwrap());
+        assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+        // bigger than the Credential Manager allows
+        let secret = vec![42u8; 10_000];
+        entry.set_secret(&secret).unwrap();
+        assert_eq!(entry.get_secret().unwrap(), secret);
+        let other = builder.build(Some("target"), "service", "other").unwrap();
+        other.set_password("other").unwrap();
+        let spec = SearchSpec {
+            service: Match::Exact("service".to_string()),
+            ..Default::default()
+        };
+        let found: Vec<String> = builder
+            .search(&spec)
+            .unwrap()
+            .iter()
+            .map(|cred| cred.spec().unwrap().user)
+            .collect();
+        assert_eq!(found, ["other", "user"]);
+        entry.delete_credential().unwrap();
+        assert!(matches!(entry.delete_credential(), Err(Error::NoEntry)));
+        other.delete_credential().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
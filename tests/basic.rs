@@ -1,8 +1,19 @@
-use common::{generate_random_bytes_of_len, generate_random_string, init_logger};
+#![cfg(feature = "std")]
+
+use common::{
+    assert_persistent_store, generate_random_bytes_of_len, generate_random_string, init_logger,
+};
 use keyring::{Entry, Error};
 
 mod common;
 
+#[test]
+fn test_persistent_store() {
+    init_logger();
+
+    assert_persistent_store();
+}
+
 #[test]
 fn test_missing_entry() {
     init_logger();
@@ -136,6 +147,33 @@ fn test_round_trip_random_secret() {
     )
 }
 
+#[test]
+fn test_get_secret_zeroizing() {
+    init_logger();
+
+    let name = generate_random_string();
+    let entry = Entry::new(&name, &name).expect("Can't create entry");
+    assert!(
+        matches!(entry.get_secret_zeroizing(), Err(Error::NoEntry)),
+        "Missing entry has secret"
+    );
+    let secret = generate_random_bytes_of_len(24);
+    entry
+        .set_secret(secret.as_slice())
+        .expect("Can't set random secret");
+    let stored_secret = entry
+        .get_secret_zeroizing()
+        .expect("Can't get zeroizing secret");
+    assert_eq!(
+        stored_secret.as_slice(),
+        secret.as_slice(),
+        "Retrieved and set secrets don't match"
+    );
+    entry
+        .delete_credential()
+        .expect("Can't delete random secret");
+}
+
 #[test]
 fn test_update() {
     init_logger();
@@ -168,3 +206,22 @@ fn test_update() {
         "Able to read a deleted updated password"
     )
 }
+
+#[test]
+fn test_get_password_or_set_with() {
+    init_logger();
+
+    let name = generate_random_string();
+    let entry = Entry::new(&name, &name).expect("Can't create entry");
+    let created = entry
+        .get_password_or_set_with(|| "generated".to_string())
+        .expect("Can't create password");
+    assert_eq!(created, "generated", "Created password wasn't returned");
+    let existing = entry
+        .get_password_or_set_with(|| panic!("Generated a password for an existing entry"))
+        .expect("Can't get existing password");
+    assert_eq!(existing, "generated", "Existing password wasn't returned");
+    entry
+        .delete_credential()
+        .expect("Can't delete created password");
+}
@@ -0,0 +1,176 @@
+#![cfg(feature = "std")]
+
+use common::{generate_random_string, init_logger};
+use keyring::memory::MemoryCredential;
+use keyring::{Entry, Error};
+
+mod common;
+
+#[test]
+fn test_builder_from_str() {
+    init_logger();
+
+    keyring::set_default_credential_builder_from_str("memory").expect("Can't set memory store");
+    let name = generate_random_string();
+    let entry = Entry::new(&name, "user").expect("Can't create entry");
+    let _: &MemoryCredential = entry
+        .get_credential()
+        .downcast_ref()
+        .expect("Entry is not a memory credential");
+    for spec in [
+        "no-such-store",
+        "memory?option=value",
+        "mock?option",
+        "secret-service?bus=nowhere",
+        "secret-service?auto-dedupe=maybe",
+        "windows?nul-handling=drop",
+    ] {
+        assert!(
+            matches!(
+                keyring::credential_builder_from_str(spec),
+                Err(Error::Invalid(_, _))
+            ),
+            "Spec {spec} didn't fail"
+        );
+    }
+    keyring::credential_builder_from_str("default").expect("Can't make default store");
+}
+
+#[test]
+#[cfg(all(
+    any(target_os = "linux", target_os = "freebsd", target_os = "openbsd"),
+    feature = "secret-service"
+))]
+fn test_secret_service_from_str() {
+    use keyring::credential::AmbiguityMode;
+    use keyring::secret_service::{BusKind, SsCredentialBuilder};
+
+    let builder =
+        keyring::credential_builder_from_str("secret-service?ambiguity=first-match&bus=system")
+            .expect("Can't make secret-service store");
+    let builder: &SsCredentialBuilder = builder
+        .as_any()
+        .downcast_ref()
+        .expect("Builder is not a secret-service builder");
+    assert!(matches!(builder.bus(), BusKind::System));
+    assert_eq!(builder.ambiguity(), AmbiguityMode::FirstMatch);
+    assert!(!builder.non_interactive());
+}
+
+#[test]
+#[cfg(feature = "vault")]
+fn test_vault_from_str() {
+    use keyring::vault::VaultCredentialBuilder;
+
+    let builder = keyring::credential_builder_from_str(
+        "vault?address=https://vault.example.com&mount=kv&path=apps/test&role-id=r&secret-id=s",
+    )
+    .expect("Can't make vault store");
+    let builder: &VaultCredentialBuilder = builder
+        .as_any()
+        .downcast_ref()
+        .expect("Builder is not a vault builder");
+    assert_eq!(builder.address(), "https://vault.example.com");
+    assert_eq!(builder.mount(), "kv");
+    assert_eq!(builder.path(), "apps/test");
+    assert!(matches!(
+        keyring::credential_builder_from_str("vault?role-id=r"),
+        Err(Error::Invalid(_, _))
+    ));
+}
+
+#[test]
+#[cfg(feature = "aws")]
+fn test_aws_from_str() {
+    use keyring::aws::{AwsCredentialBuilder, AwsService};
+
+    let builder = keyring::credential_builder_from_str(
+        "aws?region=eu-west-1&service=parameter-store&prefix=apps/test",
+    )
+    .expect("Can't make aws store");
+    let builder: &AwsCredentialBuilder = builder
+        .as_any()
+        .downcast_ref()
+        .expect("Builder is not an aws builder");
+    assert_eq!(builder.service(), AwsService::ParameterStore);
+    assert_eq!(builder.region().as_deref(), Some("eu-west-1"));
+    assert_eq!(builder.prefix(), "apps/test");
+    assert!(matches!(
+        keyring::credential_builder_from_str("aws?service=s3"),
+        Err(Error::Invalid(_, _))
+    ));
+}
+
+#[test]
+#[cfg(feature = "pkcs11")]
+fn test_pkcs11_from_str() {
+    assert!(matches!(
+        keyring::credential_builder_from_str("pkcs11?token=card"),
+        Err(Error::Invalid(_, _))
+    ));
+    assert!(matches!(
+        keyring::credential_builder_from_str("pkcs11?module=/no/such/module.so"),
+        Err(Error::NoStorageAccess(_))
+    ));
+}
+
+#[test]
+#[cfg(feature = "file-store")]
+fn test_file_from_str() {
+    use keyring::credential::CredentialBuilderApi;
+    use keyring::file_store::FileCredentialBuilder;
+
+    let dir = std::env::temp_dir();
+    let name = generate_random_string();
+    let path = dir.join(format!("{name}.keyring"));
+    let key_file = dir.join(format!("{name}.key"));
+    std::fs::write(&key_file, [7u8; 32]).unwrap();
+    let spec = format!(
+        "file?path={}&key-file={}",
+        path.display(),
+        key_file.display()
+    );
+    let builder = keyring::credential_builder_from_str(&spec).expect("Can't make file store");
+    let file: &FileCredentialBuilder = builder
+        .as_any()
+        .downcast_ref()
+        .expect("Builder is not a file builder");
+    assert_eq!(file.path(), path);
+    let entry = Entry::new_with_credential(builder.build(None, &name, "user").unwrap());
+    entry.set_password("from a spec").unwrap();
+    let again = FileCredentialBuilder::new(&path, &[7u8; 32]);
+    assert_eq!(
+        again
+            .build(None, &name, "user")
+            .unwrap()
+            .get_password()
+            .unwrap(),
+        "from a spec"
+    );
+    std::fs::remove_file(&path).unwrap();
+
+    // both options are needed, and the key file must hold just a key
+    for spec in [
+        format!("file?path={}", path.display()),
+        format!("file?key-file={}", key_file.display()),
+        format!("file?path={}&key={}", path.display(), "0".repeat(64)),
+    ] {
+        assert!(
+            matches!(
+                keyring::credential_builder_from_str(&spec),
+                Err(Error::Invalid(_, _))
+            ),
+            "Spec {spec} didn't fail"
+        );
+    }
+    std::fs::write(&key_file, b"too short").unwrap();
+    assert!(matches!(
+        keyring::credential_builder_from_str(&spec),
+        Err(Error::Invalid(_, _))
+    ));
+    std::fs::remove_file(&key_file).unwrap();
+    assert!(matches!(
+        keyring::credential_builder_from_str(&spec),
+        Err(Error::NoStorageAccess(_))
+    ));
+}
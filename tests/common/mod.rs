@@ -28,6 +28,17 @@ pub fn generate_random_bytes_of_len(len: usize) -> Vec<u8> {
     repeat_with(|| fastrand::u8(..)).take(len).collect()
 }
 
+/// Panic unless entries made with `Entry::new` are kept in a persistent store,
+/// so that tests meant for a platform store can't silently pass against
+/// the mock or memory stores.
+pub fn assert_persistent_store() {
+    let info = keyring::build_info();
+    assert!(
+        !info.is_fallback_mock,
+        "Tests are not running against a persistent store: {info:?}"
+    );
+}
+
 pub fn init_logger() {
     let _ = env_logger::builder().is_test(true).try_init();
 }
@@ -0,0 +1,59 @@
+#![cfg(feature = "std")]
+
+use common::{generate_random_string, init_logger};
+use keyring::error::CredentialContext;
+use keyring::{Entry, Error, mock::MockCredential};
+
+mod common;
+
+#[test]
+fn test_error_context() {
+    init_logger();
+
+    keyring::set_default_credential_builder(keyring::mock::default_credential_builder());
+    let name = generate_random_string();
+    let entry = Entry::new(&name, "user").expect("Can't create entry");
+    let mock: &MockCredential = entry
+        .get_credential()
+        .downcast_ref()
+        .expect("Entry is not a mock credential");
+    mock.set_error(Error::PlatformFailure("test failure".into()));
+    let err = entry
+        .set_password("test context")
+        .expect_err("Set password didn't fail");
+    assert!(
+        err.to_string()
+            .ends_with(&format!("test failure (for credential {name}/user)")),
+        "Error has no context: {err}"
+    );
+    let source = std::error::Error::source(&err).expect("Error has no source");
+    let context: &CredentialContext = source
+        .downcast_ref()
+        .expect("Error source is not a context");
+    assert_eq!(context.context, format!("{name}/user"));
+    assert_eq!(
+        err.platform_error()
+            .expect("Error has no platform error")
+            .to_string(),
+        "test failure"
+    );
+    assert!(
+        matches!(entry.get_password(), Err(Error::NoEntry)),
+        "Missing entry error was changed by context"
+    );
+}
+
+#[test]
+fn test_entry_spec() {
+    init_logger();
+
+    keyring::set_default_credential_builder(keyring::mock::default_credential_builder());
+    let name = generate_random_string();
+    let entry = Entry::new_with_target("target", &name, "user").expect("Can't create entry");
+    let spec = entry.spec().expect("Entry has no spec");
+    assert_eq!(spec.target.as_deref(), Some("target"));
+    assert_eq!(spec.service, name);
+    assert_eq!(spec.user, "user");
+    let entry = Entry::new_with_credential(Box::new(MockCredential::default()));
+    assert!(entry.spec().is_none(), "Wrapped credential has a spec");
+}
@@ -0,0 +1,303 @@
+#![cfg(feature = "std")]
+
+use common::{generate_random_string, init_logger};
+use keyring::credential::{
+    AttributeMap, Credential, CredentialBuilderApi, CredentialSpec, Match, SearchSpec, Selection,
+    TransferMode,
+};
+use keyring::{Entry, Error, Result, memory::MemoryCredentialBuilder, mock::MockCredential};
+
+mod common;
+
+/// A memory store that won't build credentials with an empty user.
+struct PickyCredentialBuilder(MemoryCredentialBuilder);
+
+impl CredentialBuilderApi for PickyCredentialBuilder {
+    fn build(&self, target: Option<&str>, service: &str, user: &str) -> Result<Box<Credential>> {
+        if user.is_empty() {
+            return Err(Error::Invalid(
+                "user".to_string(),
+                "cannot be empty".to_string(),
+            ));
+        }
+        self.0.build(target, service, user)
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+/// A memory store that gives credentials built without a target the `default` one.
+struct DefaultTargetCredentialBuilder(MemoryCredentialBuilder);
+
+impl CredentialBuilderApi for DefaultTargetCredentialBuilder {
+    fn build(&self, target: Option<&str>, service: &str, user: &str) -> Result<Box<Credential>> {
+        self.0
+            .build(Some(target.unwrap_or("default")), service, user)
+    }
+
+    fn search(&self, spec: &SearchSpec) -> Result<Vec<Box<Credential>>> {
+        self.0.search(spec)
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+#[test]
+fn test_copy_to() {
+    init_logger();
+
+    keyring::set_default_credential_builder(keyring::mock::default_credential_builder());
+    let name = generate_random_string();
+    let entry = Entry::new(&name, &name).expect("Can't create entry");
+    entry
+        .set_password("test copy password")
+        .expect("Can't set password on source entry");
+    let builder = keyring::mock::default_credential_builder();
+    let copy = entry.copy_to(&*builder).expect("Can't copy entry");
+    let _: &MockCredential = copy
+        .get_credential()
+        .downcast_ref()
+        .expect("Copied entry is not a mock credential");
+    assert_eq!(
+        copy.get_password().expect("Can't get copied password"),
+        "test copy password"
+    );
+    assert_eq!(
+        entry.get_password().expect("Can't get source password"),
+        "test copy password"
+    );
+    copy.delete_credential().expect("Can't delete copy");
+    assert!(entry.get_password().is_ok(), "Deleting copy deleted source");
+}
+
+#[test]
+fn test_copy_to_without_identity() {
+    init_logger();
+
+    let entry = Entry::new_with_credential(Box::new(MockCredential::default()));
+    entry
+        .set_password("test copy password")
+        .expect("Can't set password on source entry");
+    let builder = keyring::mock::default_credential_builder();
+    assert!(
+        matches!(entry.copy_to(&*builder), Err(Error::Invalid(_, _))),
+        "Copied entry with no identity"
+    );
+}
+
+#[test]
+fn test_move_to() {
+    init_logger();
+
+    let from = MemoryCredentialBuilder::default();
+    let to = MemoryCredentialBuilder::default();
+    let entry = Entry::new_with_credential(from.build(None, "service", "user").unwrap());
+    entry
+        .set_password("test move password")
+        .expect("Can't set password on source entry");
+    let moved = entry.move_to(&to).expect("Can't move entry");
+    assert_eq!(
+        moved.get_password().expect("Can't get moved password"),
+        "test move password"
+    );
+    assert!(
+        matches!(entry.get_password(), Err(Error::NoEntry)),
+        "Moving didn't delete source"
+    );
+}
+
+#[test]
+fn test_move_to_same_credential() {
+    init_logger();
+
+    let store = MemoryCredentialBuilder::default();
+    let entry = Entry::new_with_credential(store.build(None, "service", "user").unwrap());
+    entry
+        .set_password("test move password")
+        .expect("Can't set password on source entry");
+    entry
+        .update_attributes(&AttributeMap::from([("label", "kept")]))
+        .expect("Can't set attributes on source entry");
+    assert!(
+        matches!(entry.move_to(&store.clone()), Err(Error::Invalid(_, _))),
+        "Moved entry onto itself"
+    );
+    assert_eq!(
+        entry
+            .get_password()
+            .expect("Move onto itself lost the password"),
+        "test move password"
+    );
+    assert_eq!(entry.get_attributes().unwrap()["label"], "kept");
+}
+
+#[test]
+fn test_copy_into() {
+    init_logger();
+
+    let entry = Entry::new_with_credential(Box::new(MockCredential::default()));
+    entry
+        .set_password("test copy password")
+        .expect("Can't set password on source entry");
+    let builder = MemoryCredentialBuilder::default();
+    let other = Entry::new_with_credential(builder.build(None, "other", "user").unwrap());
+    entry
+        .copy_into(&other)
+        .expect("Can't copy into other entry");
+    assert_eq!(
+        other.get_password().expect("Can't get copied password"),
+        "test copy password"
+    );
+}
+
+#[test]
+fn test_transfer() {
+    init_logger();
+
+    let from = MemoryCredentialBuilder::default();
+    for (target, user) in [(None, "alice"), (Some("work"), "bob"), (None, "carol")] {
+        let credential = from.build(target, "service", user).unwrap();
+        credential.set_password(user).unwrap();
+        credential
+            .update_attributes(&AttributeMap::from([("label", user)]))
+            .unwrap();
+    }
+    from.build(None, "other", "dave")
+        .unwrap()
+        .set_password("dave")
+        .unwrap();
+    let to = MemoryCredentialBuilder::default();
+    let spec = SearchSpec {
+        service: Match::Exact("service".to_string()),
+        ..Default::default()
+    };
+    let report = keyring::transfer(&from, &to, Selection::Search(&spec), TransferMode::Copy)
+        .expect("Can't copy credentials");
+    assert_eq!(report.transferred.len(), 3);
+    assert!(report.failed.is_empty());
+    let bob = to.build(Some("work"), "service", "bob").unwrap();
+    assert_eq!(bob.get_password().unwrap(), "bob");
+    assert_eq!(bob.get_attributes().unwrap()["label"], "bob");
+    assert!(
+        to.build(None, "other", "dave")
+            .unwrap()
+            .get_password()
+            .is_err()
+    );
+    assert_eq!(from.search(&spec).unwrap().len(), 3);
+
+    let specs = [
+        CredentialSpec {
+            target: None,
+            service: "other".to_string(),
+            user: "dave".to_string(),
+        },
+        CredentialSpec {
+            target: None,
+            service: "other".to_string(),
+            user: "missing".to_string(),
+        },
+    ];
+    let report = keyring::transfer(&from, &to, Selection::Specs(&specs), TransferMode::Move)
+        .expect("Can't move credentials");
+    assert_eq!(report.transferred, specs[..1]);
+    assert_eq!(report.failed.len(), 1);
+    assert!(matches!(report.failed[0], (_, Error::NoEntry)));
+    assert_eq!(
+        to.build(None, "other", "dave")
+            .unwrap()
+            .get_password()
+            .unwrap(),
+        "dave"
+    );
+    assert!(matches!(
+        from.build(None, "other", "dave").unwrap().get_password(),
+        Err(Error::NoEntry)
+    ));
+}
+
+#[test]
+fn test_transfer_with_unbuildable_spec() {
+    init_logger();
+
+    let from = PickyCredentialBuilder(MemoryCredentialBuilder::default());
+    let specs = ["alice", "", "bob"].map(|user| CredentialSpec {
+        target: None,
+        service: "service".to_string(),
+        user: user.to_string(),
+    });
+    for spec in [&specs[0], &specs[2]] {
+        from.build(None, &spec.service, &spec.user)
+            .unwrap()
+            .set_password(&spec.user)
+            .unwrap();
+    }
+    let to = MemoryCredentialBuilder::default();
+    let report = keyring::transfer(&from, &to, Selection::Specs(&specs), TransferMode::Copy)
+        .expect("Transfer stopped at a spec that can't be built");
+    assert_eq!(report.transferred, [specs[0].clone(), specs[2].clone()]);
+    assert_eq!(report.failed.len(), 1);
+    assert_eq!(report.failed[0].0, specs[1]);
+    assert!(matches!(report.failed[0].1, Error::Invalid(_, _)));
+    assert_eq!(
+        to.build(None, "service", "bob")
+            .unwrap()
+            .get_password()
+            .unwrap(),
+        "bob"
+    );
+}
+
+#[test]
+fn test_transfer_normalized_targets() {
+    init_logger();
+
+    let from = DefaultTargetCredentialBuilder(MemoryCredentialBuilder::default());
+    from.build(None, "service", "alice")
+        .unwrap()
+        .set_password("alice")
+        .unwrap();
+    from.build(Some("work"), "service", "bob")
+        .unwrap()
+        .set_password("bob")
+        .unwrap();
+    let to = MemoryCredentialBuilder::default();
+    let spec = SearchSpec {
+        service: Match::Exact("service".to_string()),
+        ..Default::default()
+    };
+    let report = keyring::transfer(&from, &to, Selection::Search(&spec), TransferMode::Move)
+        .expect("Can't move credentials");
+    assert_eq!(report.transferred.len(), 2);
+    assert!(report.failed.is_empty());
+    let alice = report
+        .transferred
+        .iter()
+        .find(|spec| spec.user == "alice")
+        .unwrap();
+    assert_eq!(alice.target, None);
+    assert_eq!(
+        to.build(None, "service", "alice")
+            .unwrap()
+            .get_password()
+            .unwrap(),
+        "alice"
+    );
+    assert!(matches!(
+        to.build(Some("default"), "service", "alice")
+            .unwrap()
+            .get_password(),
+        Err(Error::NoEntry)
+    ));
+    assert_eq!(
+        to.build(Some("work"), "service", "bob")
+            .unwrap()
+            .get_password()
+            .unwrap(),
+        "bob"
+    );
+}
@@ -0,0 +1,55 @@
+#![cfg(feature = "std")]
+
+use common::{generate_random_string, init_logger};
+use keyring::credential::{Credential, CredentialBuilderApi};
+use keyring::{Entry, Error, Result, mock::MockCredential};
+
+mod common;
+
+struct FailingCredentialBuilder;
+
+impl CredentialBuilderApi for FailingCredentialBuilder {
+    fn build(&self, _: Option<&str>, _: &str, _: &str) -> Result<Box<Credential>> {
+        Err(Error::Invalid(
+            "builder".to_string(),
+            "always fails".to_string(),
+        ))
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+#[test]
+fn test_fallback_builder() {
+    init_logger();
+
+    keyring::set_default_credential_builder(Box::new(FailingCredentialBuilder));
+    assert!(
+        !keyring::build_info().is_fallback_mock,
+        "Failing builder is reported as transient"
+    );
+    let name = generate_random_string();
+    assert!(
+        matches!(Entry::new(&name, &name), Err(Error::Invalid(_, _))),
+        "Created entry with failing builder and no fallback"
+    );
+    keyring::set_fallback_credential_builder(keyring::mock::default_credential_builder());
+    assert!(
+        keyring::build_info().is_fallback_mock,
+        "Mock fallback builder isn't reported"
+    );
+    let entry = Entry::new(&name, &name).expect("Can't create entry with fallback builder");
+    let _: &MockCredential = entry
+        .get_credential()
+        .downcast_ref()
+        .expect("Fallback entry is not a mock credential");
+    entry
+        .set_password("test fallback password")
+        .expect("Can't set password on fallback entry");
+    assert_eq!(
+        entry.get_password().expect("Can't get fallback password"),
+        "test fallback password"
+    );
+}
@@ -0,0 +1,135 @@
+#![cfg(feature = "std")]
+
+use std::sync::{Arc, Mutex};
+
+use common::init_logger;
+use keyring::credential::CredentialApi;
+use keyring::prompt::{self, Prompt, PromptHandler, PromptKind};
+use keyring::{Entry, Error};
+
+mod common;
+
+/// A credential that records whether interaction was allowed when it was read.
+#[derive(Debug, Default)]
+struct RecordingCredential {
+    allowed: Mutex<Vec<bool>>,
+}
+
+impl CredentialApi for RecordingCredential {
+    fn set_secret(&self, _secret: &[u8]) -> keyring::Result<()> {
+        Ok(())
+    }
+
+    fn get_secret(&self) -> keyring::Result<Vec<u8>> {
+        let allowed = keyring::interaction_allowed_override();
+        self.allowed.lock().unwrap().push(allowed);
+        Ok(Vec::new())
+    }
+
+    fn delete_credential(&self) -> keyring::Result<()> {
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+#[test]
+fn test_with_interaction_allowed() {
+    init_logger();
+
+    let entry = Entry::new_with_credential(Box::new(RecordingCredential::default()));
+    entry.get_secret().unwrap();
+    let result = entry.with_interaction_allowed(|entry| {
+        entry.get_secret().unwrap();
+        entry.with_interaction_allowed(|entry| entry.get_secret().unwrap());
+        entry.get_secret().unwrap();
+        std::thread::scope(|scope| {
+            scope.spawn(|| entry.get_secret().unwrap());
+        });
+        "result"
+    });
+    assert_eq!(result, "result");
+    entry.get_secret().unwrap();
+    let credential: &RecordingCredential = entry.get_credential().downcast_ref().unwrap();
+    assert_eq!(
+        *credential.allowed.lock().unwrap(),
+        [false, true, true, true, false, false]
+    );
+}
+
+/// A prompt handler that records what it's told, and refuses
+/// interactions about `refused`.
+#[derive(Debug, Default, Clone)]
+struct RecordingHandler {
+    events: Arc<Mutex<Vec<String>>>,
+}
+
+impl PromptHandler for RecordingHandler {
+    fn interaction_required(&self, prompt: &Prompt) -> bool {
+        self.events
+            .lock()
+            .unwrap()
+            .push(format!("required {prompt}"));
+        prompt.subject != "refused"
+    }
+
+    fn interaction_started(&self, prompt: &Prompt) {
+        self.events
+            .lock()
+            .unwrap()
+            .push(format!("started {prompt}"));
+    }
+
+    fn interaction_completed(&self, prompt: &Prompt) {
+        self.events
+            .lock()
+            .unwrap()
+            .push(format!("completed {prompt}"));
+    }
+
+    fn interaction_denied(&self, prompt: &Prompt) {
+        self.events.lock().unwrap().push(format!("denied {prompt}"));
+    }
+}
+
+#[test]
+fn test_prompt_handler() {
+    init_logger();
+
+    let unlock = |subject| Prompt::new(PromptKind::Unlock, "test", subject);
+    // with no handler, the interaction just happens
+    assert_eq!(prompt::interact(&unlock("unhandled"), || Ok(1)).unwrap(), 1);
+    let handler = RecordingHandler::default();
+    prompt::set_prompt_handler(Box::new(handler.clone()));
+    assert_eq!(prompt::interact(&unlock("allowed"), || Ok(2)).unwrap(), 2);
+    assert!(matches!(
+        prompt::interact(&unlock("dismissed"), || Err::<(), _>(Error::Cancelled)),
+        Err(Error::Cancelled)
+    ));
+    let mut ran = false;
+    assert!(matches!(
+        prompt::interact(&unlock("refused"), || {
+            ran = true;
+            Ok(())
+        }),
+        Err(Error::Locked)
+    ));
+    assert!(!ran, "a refused interaction must not run");
+    assert!(prompt::password(&unlock("allowed")).is_none());
+    prompt::clear_prompt_handler();
+    assert_eq!(
+        *handler.events.lock().unwrap(),
+        [
+            "required test unlock 'allowed'",
+            "started test unlock 'allowed'",
+            "completed test unlock 'allowed'",
+            "required test unlock 'dismissed'",
+            "started test unlock 'dismissed'",
+            "denied test unlock 'dismissed'",
+            "required test unlock 'refused'",
+            "denied test unlock 'refused'",
+        ]
+    );
+}
@@ -0,0 +1,69 @@
+#![cfg(feature = "std")]
+
+use std::io::Cursor;
+
+use common::{generate_random_bytes_of_len, init_logger};
+use keyring::credential::CredentialApi;
+use keyring::{Entry, Error, Result, mock::MockCredential};
+
+mod common;
+
+/// A mock credential whose store has a limit on secret length
+#[derive(Debug, Default)]
+struct LimitedCredential {
+    inner: MockCredential,
+}
+
+impl CredentialApi for LimitedCredential {
+    fn set_secret(&self, secret: &[u8]) -> Result<()> {
+        self.inner.set_secret(secret)
+    }
+
+    fn get_secret(&self) -> Result<Vec<u8>> {
+        self.inner.get_secret()
+    }
+
+    fn max_secret_len(&self) -> Option<usize> {
+        Some(2048)
+    }
+
+    fn delete_credential(&self) -> Result<()> {
+        self.inner.delete_credential()
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+#[test]
+fn test_set_secret_from_reader() {
+    init_logger();
+
+    let entry = Entry::new_with_credential(Box::new(MockCredential::default()));
+    let secret = generate_random_bytes_of_len(5000);
+    entry
+        .set_secret_from_reader(&mut Cursor::new(&secret))
+        .expect("Can't set secret from reader");
+    assert_eq!(entry.get_secret().expect("Can't get secret"), secret);
+}
+
+#[test]
+fn test_set_secret_from_reader_limit() {
+    init_logger();
+
+    let entry = Entry::new_with_credential(Box::new(LimitedCredential::default()));
+    let secret = generate_random_bytes_of_len(2048);
+    entry
+        .set_secret_from_reader(&mut Cursor::new(&secret))
+        .expect("Can't set secret at limit from reader");
+    assert_eq!(entry.get_secret().expect("Can't get secret"), secret);
+    let secret = generate_random_bytes_of_len(2049);
+    assert!(
+        matches!(
+            entry.set_secret_from_reader(&mut Cursor::new(&secret)),
+            Err(Error::TooLong(_, 2048))
+        ),
+        "Set secret over limit from reader"
+    );
+}
@@ -0,0 +1,26 @@
+#![cfg(all(feature = "std", feature = "secrecy"))]
+
+use common::{generate_random_bytes_of_len, init_logger};
+use keyring::{Entry, Error, mock::MockCredential};
+use secrecy::ExposeSecret;
+
+mod common;
+
+#[test]
+fn test_get_protected() {
+    init_logger();
+
+    let entry = Entry::new_with_credential(Box::new(MockCredential::default()));
+    assert!(
+        matches!(entry.get_password_secret(), Err(Error::NoEntry)),
+        "Missing entry has password"
+    );
+    entry.set_password("protected").expect("Can't set password");
+    let password = entry.get_password_secret().expect("Can't get password");
+    assert_eq!(password.expose_secret(), "protected");
+    let secret = generate_random_bytes_of_len(24);
+    entry.set_secret(&secret).expect("Can't set secret");
+    let stored = entry.get_secret_protected().expect("Can't get secret");
+    assert_eq!(stored.expose_secret(), secret.as_slice());
+    entry.delete_credential().expect("Can't delete secret");
+}
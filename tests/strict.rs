@@ -0,0 +1,60 @@
+#![cfg(feature = "std")]
+
+use common::{generate_random_string, init_logger};
+use keyring::credential::{Credential, CredentialBuilderApi, CredentialPersistence};
+use keyring::{Entry, Error, Result, memory::MemoryCredentialBuilder};
+
+mod common;
+
+/// A memory store that claims to persist its credentials.
+#[derive(Debug, Default)]
+struct PersistentBuilder(MemoryCredentialBuilder);
+
+impl CredentialBuilderApi for PersistentBuilder {
+    fn build(&self, target: Option<&str>, service: &str, user: &str) -> Result<Box<Credential>> {
+        self.0.build(target, service, user)
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn persistence(&self) -> CredentialPersistence {
+        CredentialPersistence::UntilDelete
+    }
+}
+
+#[test]
+fn test_strict_entry() {
+    init_logger();
+
+    let name = generate_random_string();
+    keyring::set_default_credential_builder(keyring::mock::default_credential_builder());
+    assert!(
+        !keyring::default_store_is_persistent(),
+        "Mock store is reported as persistent"
+    );
+    assert!(
+        matches!(
+            Entry::new_strict(&name, &name),
+            Err(Error::NoPersistentStore)
+        ),
+        "Created strict entry in mock store"
+    );
+    Entry::new(&name, &name).expect("Can't create non-strict entry in mock store");
+
+    keyring::set_default_credential_builder(Box::new(PersistentBuilder::default()));
+    keyring::set_fallback_credential_builder(keyring::mock::default_credential_builder());
+    assert!(
+        keyring::default_store_is_persistent(),
+        "Persistent store isn't reported as persistent"
+    );
+    let entry = Entry::new_strict(&name, &name).expect("Can't create strict entry");
+    entry
+        .set_password("test strict password")
+        .expect("Can't set password on strict entry");
+    assert_eq!(
+        entry.get_password().expect("Can't get strict password"),
+        "test strict password"
+    );
+}
@@ -1,3 +1,5 @@
+#![cfg(feature = "std")]
+
 use common::{generate_random_string, init_logger};
 use keyring::{Entry, Error};
 
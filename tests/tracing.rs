@@ -0,0 +1,84 @@
+#![cfg(feature = "tracing")]
+
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+
+use common::{generate_random_string, init_logger};
+use keyring::{Entry, Error, credential::CredentialBuilderApi, memory::MemoryCredentialBuilder};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+
+mod common;
+
+/// A subscriber that records the fields of every span and event as text.
+#[derive(Clone, Default)]
+struct Recorder(Arc<Mutex<Vec<String>>>);
+
+struct Fields(String);
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0.push_str(&format!("{}={value:?} ", field.name()));
+    }
+}
+
+impl Subscriber for Recorder {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let mut fields = Fields(String::new());
+        span.record(&mut fields);
+        self.0.lock().unwrap().push(fields.0);
+        Id::from_u64(1)
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = Fields(String::new());
+        event.record(&mut fields);
+        self.0.lock().unwrap().push(fields.0);
+    }
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+#[test]
+fn test_tracing() {
+    init_logger();
+
+    let recorder = Recorder::default();
+    let service = generate_random_string();
+    let user = generate_random_string();
+    let secret = generate_random_string();
+    tracing::subscriber::with_default(recorder.clone(), || {
+        let builder = MemoryCredentialBuilder::default();
+        let entry = Entry::new_with_credential(builder.build(None, &service, &user).unwrap());
+        entry.set_password(&secret).unwrap();
+        assert_eq!(entry.get_password().unwrap(), secret);
+        entry.delete_credential().unwrap();
+        assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+    });
+    let records = recorder.0.lock().unwrap().join("\n");
+    for op in ["set_password", "get_password", "delete_credential"] {
+        assert!(
+            records.contains(&format!("op=\"{op}\"")),
+            "{op} isn't traced"
+        );
+    }
+    assert!(
+        records.contains("error=\"NoEntry\""),
+        "failure isn't traced"
+    );
+    assert!(records.contains("elapsed_us="), "duration isn't traced");
+    for value in [&service, &user, &secret] {
+        assert!(!records.contains(value.as_str()), "{value} is traced");
+    }
+}